@@ -0,0 +1,122 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use lumos_desktop::core::window_manager::scene_graph::{BoundingBox, NodeId, NodeType, SceneGraph, SpatialIndex};
+
+const SIZES: [usize; 3] = [100, 1_000, 10_000];
+
+/// 画面いっぱいに重なり合うノードの境界（再現できるように疑似乱数で生成）
+fn scattered_bounds(count: usize) -> Vec<BoundingBox> {
+    let mut state = 0x2545_f491_4f6c_dd1du64;
+    let mut next = move |max: f32| {
+        state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (state >> 40) as f32 / (1u64 << 24) as f32 * max
+    };
+    (0..count)
+        .map(|_| {
+            let (x, y) = (next(3840.0), next(2160.0));
+            let (width, height) = (20.0 + next(400.0), 20.0 + next(300.0));
+            BoundingBox::new((x, y, 0.0), (x + width, y + height, 0.0))
+        })
+        .collect()
+}
+
+fn create_scene(bounds: &[BoundingBox]) -> (SceneGraph, Vec<NodeId>) {
+    let mut graph = SceneGraph::new();
+    let root_id = graph.root();
+    let ids = bounds
+        .iter()
+        .enumerate()
+        .map(|(i, b)| {
+            let id = graph.create_node(root_id, NodeType::Widget, format!("widget_{}", i)).unwrap();
+            graph.set_bounds(id, BoundingBox::from_size(b.max.0 - b.min.0, b.max.1 - b.min.1, 0.0));
+            graph.get_node_mut(id).unwrap().transform.position = b.min;
+            id
+        })
+        .collect();
+    graph.update();
+    (graph, ids)
+}
+
+/// ポインターの軌跡（画面を斜めに横切る）
+fn pointer_path() -> Vec<(f32, f32, f32)> {
+    (0..256).map(|i| (i as f32 * 15.0, i as f32 * 8.4, 0.0)).collect()
+}
+
+fn benchmark_hit_test(c: &mut Criterion) {
+    let mut group = c.benchmark_group("scene_graph_hit_test");
+    let path = pointer_path();
+
+    for size in SIZES {
+        let bounds = scattered_bounds(size);
+        let (graph, _) = create_scene(&bounds);
+        group.bench_with_input(BenchmarkId::new("scene_graph", size), &path, |b, path| {
+            let mut points = path.iter().cycle();
+            b.iter(|| black_box(graph.hit_test(*points.next().unwrap())))
+        });
+
+        let mut index = SpatialIndex::new();
+        for (i, b) in bounds.iter().enumerate() {
+            index.update(NodeId(i as u64), *b);
+        }
+        group.bench_with_input(BenchmarkId::new("rtree", size), &path, |b, path| {
+            let mut points = path.iter().cycle();
+            b.iter(|| black_box(index.query_point(*points.next().unwrap())))
+        });
+
+        // 比較用の全件走査
+        group.bench_with_input(BenchmarkId::new("linear", size), &path, |b, path| {
+            let mut points = path.iter().cycle();
+            b.iter(|| {
+                let point = *points.next().unwrap();
+                black_box(bounds.iter().filter(|b| b.contains_point(point)).count())
+            })
+        });
+    }
+
+    group.finish();
+}
+
+fn benchmark_query_region(c: &mut Criterion) {
+    let mut group = c.benchmark_group("scene_graph_query_region");
+    // 1280x720の出力1枚分
+    let region = BoundingBox::new((1280.0, 720.0, 0.0), (2560.0, 1440.0, 0.0));
+
+    for size in SIZES {
+        let (graph, _) = create_scene(&scattered_bounds(size));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &region, |b, region| {
+            b.iter(|| black_box(graph.query_region(*region)))
+        });
+    }
+
+    group.finish();
+}
+
+fn benchmark_incremental_update(c: &mut Criterion) {
+    let mut group = c.benchmark_group("scene_graph_incremental_update");
+
+    for size in SIZES {
+        let (mut graph, ids) = create_scene(&scattered_bounds(size));
+        let dragged = ids[size / 2];
+        let mut step = 0u32;
+
+        // ドラッグ中の1ノードの移動だけをインデックスに反映する
+        group.bench_function(BenchmarkId::from_parameter(size), |b| {
+            b.iter(|| {
+                step = step.wrapping_add(1);
+                let offset = (step % 64) as f32 * 3.0;
+                graph.get_node_mut(dragged).unwrap().transform.position = (100.0 + offset, 200.0 + offset, 0.0);
+                graph.mark_dirty(dragged);
+                graph.update();
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    benchmark_hit_test,
+    benchmark_query_region,
+    benchmark_incremental_update
+);
+criterion_main!(benches);
@@ -0,0 +1,366 @@
+// LumosDesktop 非同期リソースローダー
+// 優先度付きの読み込みキューと、tokioのブロッキングプールでのデコード
+
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::atomic::{self, AtomicBool};
+use std::sync::{mpsc, Arc};
+use std::task::{Context, Poll};
+
+use futures::channel::oneshot;
+use futures::FutureExt;
+use tokio::runtime::{Builder, Handle, Runtime};
+
+use super::GraphicsError;
+use super::resource_manager::{self, DecodeSettings, ResourceHandle};
+use super::texture_loader::{DecodedTexture, TextureLoadOptions};
+
+/// 読み込み優先度
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum LoadPriority {
+    /// 先読みなど、急がない読み込み
+    Low,
+    #[default]
+    Normal,
+    /// 表示中の要素が待っている読み込み
+    High,
+    /// 次のフレームまでに必要な読み込み
+    Critical,
+}
+
+/// 非同期読み込みの完了を待つFuture
+///
+/// レンダースレッドで `ResourceManager::process_pending_loads` が呼ばれ、GPUへのアップロードが終わると完了します。
+/// 読み込み中もハンドルは有効で、リソースの状態は `ResourceState::Loading` になります。
+#[derive(Debug)]
+pub struct PendingLoad {
+    handle: ResourceHandle,
+    receiver: oneshot::Receiver<Result<ResourceHandle, GraphicsError>>,
+}
+
+impl PendingLoad {
+    pub fn handle(&self) -> ResourceHandle {
+        self.handle
+    }
+}
+
+impl Future for PendingLoad {
+    type Output = Result<ResourceHandle, GraphicsError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.receiver.poll_unpin(cx).map(|result| {
+            result.unwrap_or_else(|_| Err(GraphicsError::Resource("読み込みが中断されました".to_string())))
+        })
+    }
+}
+
+/// キューに入っている読み込み要求
+struct QueuedLoad {
+    id: u64,
+    priority: LoadPriority,
+    name: String,
+    path: PathBuf,
+    options: TextureLoadOptions,
+    settings: DecodeSettings,
+}
+
+impl PartialEq for QueuedLoad {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for QueuedLoad {}
+
+impl PartialOrd for QueuedLoad {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for QueuedLoad {
+    // 優先度が高いものから、同じ優先度なら先に要求されたものから取り出す
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.priority.cmp(&other.priority).then_with(|| other.id.cmp(&self.id))
+    }
+}
+
+/// 読み込みの完了通知
+pub(crate) struct LoadCompletion {
+    sender: oneshot::Sender<Result<ResourceHandle, GraphicsError>>,
+}
+
+impl LoadCompletion {
+    /// 待機中のFutureに結果を通知
+    pub fn complete(self, result: Result<ResourceHandle, GraphicsError>) {
+        let _ = self.sender.send(result);
+    }
+}
+
+/// デコードが終わった読み込み
+pub(crate) struct DecodedLoad {
+    pub name: String,
+    pub path: PathBuf,
+    pub options: TextureLoadOptions,
+    pub handle: ResourceHandle,
+    pub result: Result<DecodedTexture, GraphicsError>,
+    pub completion: LoadCompletion,
+}
+
+struct DecodeResult {
+    id: u64,
+    name: String,
+    result: Result<DecodedTexture, GraphicsError>,
+}
+
+/// 進行中の読み込み
+struct LoadState {
+    id: u64,
+    handle: ResourceHandle,
+    path: PathBuf,
+    options: TextureLoadOptions,
+    cancelled: Arc<AtomicBool>,
+    sender: oneshot::Sender<Result<ResourceHandle, GraphicsError>>,
+}
+
+fn cancelled_error(name: &str) -> GraphicsError {
+    GraphicsError::Resource(format!("読み込みがキャンセルされました: {}", name))
+}
+
+/// 非同期ローダー
+///
+/// デコードは同時に `max_in_flight` 件までブロッキングプールで実行し、結果はレンダースレッドが取り出します。
+pub(crate) struct AsyncLoader {
+    runtime: Handle,
+    owned_runtime: Option<Runtime>,
+    queue: BinaryHeap<QueuedLoad>,
+    loads: HashMap<String, LoadState>,
+    ready: VecDeque<DecodeResult>,
+    in_flight: usize,
+    max_in_flight: usize,
+    next_id: u64,
+    completed_tx: mpsc::Sender<DecodeResult>,
+    completed_rx: mpsc::Receiver<DecodeResult>,
+}
+
+impl AsyncLoader {
+    /// 実行中のtokioランタイムがあればそれを使い、なければ専用のランタイムを作成
+    pub fn new(max_in_flight: usize) -> Result<Self, GraphicsError> {
+        let (runtime, owned_runtime) = match Handle::try_current() {
+            Ok(handle) => (handle, None),
+            Err(_) => {
+                let runtime = Builder::new_multi_thread()
+                    .worker_threads(1)
+                    .max_blocking_threads(max_in_flight.max(1))
+                    .thread_name("lumos-resource-loader")
+                    .build()
+                    .map_err(|e| GraphicsError::Initialization(format!("読み込み用ランタイムの作成に失敗: {}", e)))?;
+                (runtime.handle().clone(), Some(runtime))
+            }
+        };
+
+        let (completed_tx, completed_rx) = mpsc::channel();
+        Ok(Self {
+            runtime,
+            owned_runtime,
+            queue: BinaryHeap::new(),
+            loads: HashMap::new(),
+            ready: VecDeque::new(),
+            in_flight: 0,
+            max_in_flight: max_in_flight.max(1),
+            next_id: 1,
+            completed_tx,
+            completed_rx,
+        })
+    }
+
+    /// 読み込みをキューに追加（同名の読み込みが進行中の場合はキャンセル）
+    pub fn enqueue(
+        &mut self,
+        name: &str,
+        handle: ResourceHandle,
+        path: PathBuf,
+        options: TextureLoadOptions,
+        settings: DecodeSettings,
+        priority: LoadPriority,
+    ) -> PendingLoad {
+        self.cancel(name);
+
+        let id = self.next_id;
+        self.next_id += 1;
+        let (sender, receiver) = oneshot::channel();
+        self.loads.insert(name.to_string(), LoadState {
+            id,
+            handle,
+            path: path.clone(),
+            options: options.clone(),
+            cancelled: Arc::new(AtomicBool::new(false)),
+            sender,
+        });
+        self.queue.push(QueuedLoad { id, priority, name: name.to_string(), path, options, settings });
+        self.dispatch();
+
+        PendingLoad { handle, receiver }
+    }
+
+    /// 空きがあればキューから優先度順にデコードを開始
+    fn dispatch(&mut self) {
+        while self.in_flight < self.max_in_flight {
+            let Some(load) = self.queue.pop() else { break };
+            let Some(state) = self.loads.get(&load.name) else { continue };
+
+            let cancelled = Arc::clone(&state.cancelled);
+            let completed_tx = self.completed_tx.clone();
+            self.in_flight += 1;
+            self.runtime.spawn_blocking(move || {
+                let result = if cancelled.load(atomic::Ordering::Acquire) {
+                    Err(cancelled_error(&load.name))
+                } else {
+                    resource_manager::decode_texture_file(&load.path, &load.options, load.settings)
+                };
+                let _ = completed_tx.send(DecodeResult { id: load.id, name: load.name, result });
+            });
+        }
+    }
+
+    /// キャンセルし、キャンセルした読み込みのハンドルを返す
+    pub fn cancel(&mut self, name: &str) -> Option<ResourceHandle> {
+        let state = self.loads.remove(name)?;
+        state.cancelled.store(true, atomic::Ordering::Release);
+        self.queue.retain(|load| load.id != state.id);
+        let _ = state.sender.send(Err(cancelled_error(name)));
+        Some(state.handle)
+    }
+
+    /// すべての読み込みをキャンセル
+    pub fn cancel_all(&mut self) {
+        let names: Vec<String> = self.loads.keys().cloned().collect();
+        for name in names {
+            self.cancel(&name);
+        }
+    }
+
+    /// キュー内の読み込みの優先度を変更（デコード開始後は変更できない）
+    pub fn set_priority(&mut self, name: &str, priority: LoadPriority) -> bool {
+        let Some(id) = self.loads.get(name).map(|state| state.id) else { return false };
+        let mut queue = std::mem::take(&mut self.queue).into_vec();
+        let Some(load) = queue.iter_mut().find(|load| load.id == id) else {
+            self.queue = queue.into();
+            return false;
+        };
+        load.priority = priority;
+        self.queue = queue.into();
+        true
+    }
+
+    pub fn is_loading(&self, name: &str) -> bool {
+        self.loads.contains_key(name)
+    }
+
+    /// 完了していない読み込みの数
+    pub fn pending_count(&self) -> usize {
+        self.loads.len()
+    }
+
+    /// デコードが終わった読み込みを最大 `max` 件取り出す
+    pub fn take_decoded(&mut self, max: usize) -> Vec<DecodedLoad> {
+        while let Ok(result) = self.completed_rx.try_recv() {
+            self.in_flight -= 1;
+            self.ready.push_back(result);
+        }
+        self.dispatch();
+
+        let mut decoded = Vec::new();
+        while decoded.len() < max {
+            let Some(result) = self.ready.pop_front() else { break };
+            // キャンセルされたか、新しい要求に置き換えられた読み込みは捨てる
+            if self.loads.get(&result.name).is_none_or(|state| state.id != result.id) {
+                continue;
+            }
+            if let Some(state) = self.loads.remove(&result.name) {
+                decoded.push(DecodedLoad {
+                    name: result.name,
+                    path: state.path,
+                    options: state.options,
+                    handle: state.handle,
+                    result: result.result,
+                    completion: LoadCompletion { sender: state.sender },
+                });
+            }
+        }
+        decoded
+    }
+}
+
+impl Drop for AsyncLoader {
+    fn drop(&mut self) {
+        self.cancel_all();
+        // 非同期コンテキスト内で破棄されてもブロックしないようにする
+        if let Some(runtime) = self.owned_runtime.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queued(id: u64, priority: LoadPriority) -> QueuedLoad {
+        QueuedLoad {
+            id,
+            priority,
+            name: id.to_string(),
+            path: PathBuf::new(),
+            options: TextureLoadOptions::default(),
+            settings: DecodeSettings { max_texture_size: 4096, native_compression: true },
+        }
+    }
+
+    #[test]
+    fn test_queue_order() {
+        let mut queue = BinaryHeap::new();
+        queue.push(queued(1, LoadPriority::Low));
+        queue.push(queued(2, LoadPriority::Normal));
+        queue.push(queued(3, LoadPriority::Critical));
+        queue.push(queued(4, LoadPriority::Normal));
+        let order: Vec<u64> = std::iter::from_fn(|| queue.pop()).map(|load| load.id).collect();
+        assert_eq!(order, vec![3, 2, 4, 1]);
+    }
+
+    #[test]
+    fn test_cancel_resolves_future() {
+        let mut loader = AsyncLoader::new(1).unwrap();
+        let settings = DecodeSettings { max_texture_size: 4096, native_compression: true };
+        let first = loader.enqueue("a", ResourceHandle::new(1), PathBuf::from("a.png"), TextureLoadOptions::default(), settings, LoadPriority::Normal);
+        let second = loader.enqueue("b", ResourceHandle::new(2), PathBuf::from("b.png"), TextureLoadOptions::default(), settings, LoadPriority::Low);
+        assert_eq!(loader.pending_count(), 2);
+
+        assert!(loader.set_priority("b", LoadPriority::High));
+        assert_eq!(loader.cancel("b"), Some(ResourceHandle::new(2)));
+        assert!(!loader.set_priority("b", LoadPriority::High));
+        assert!(futures::executor::block_on(second).is_err());
+
+        // 同名の要求は古い要求をキャンセルする
+        let third = loader.enqueue("a", ResourceHandle::new(3), PathBuf::from("a.png"), TextureLoadOptions::default(), settings, LoadPriority::Normal);
+        assert!(futures::executor::block_on(first).is_err());
+        assert_eq!(third.handle(), ResourceHandle::new(3));
+        assert!(loader.is_loading("a"));
+    }
+
+    #[test]
+    fn test_drop_owned_runtime_in_async_context() {
+        // 専用ランタイムを持つローダーを別のランタイム上で破棄してもパニックしない
+        let mut loader = AsyncLoader::new(1).unwrap();
+        assert!(loader.owned_runtime.is_some());
+        let settings = DecodeSettings { max_texture_size: 4096, native_compression: true };
+        let pending = loader.enqueue("a", ResourceHandle::new(1), PathBuf::from("a.png"), TextureLoadOptions::default(), settings, LoadPriority::Normal);
+
+        let outer = Builder::new_current_thread().build().unwrap();
+        outer.block_on(async move { drop(loader) });
+        assert!(futures::executor::block_on(pending).is_err());
+    }
+}
@@ -0,0 +1,932 @@
+// LumosDesktop BCテクスチャデコーダー
+// BC1〜BC7で圧縮されたテクスチャのCPU展開（圧縮テクスチャ非対応のバックエンド向け）
+
+use super::renderer::TextureFormat;
+use super::resource_manager::ResourceLoadError;
+
+/// ブロック圧縮フォーマット
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BlockFormat {
+    /// DXT1
+    Bc1,
+    /// DXT3
+    Bc2,
+    /// DXT5
+    Bc3,
+    /// 1チャンネル（RGTC1）
+    Bc4,
+    /// 2チャンネル（RGTC2）
+    Bc5,
+    /// HDR（BPTC float）
+    Bc6h { signed: bool },
+    /// BPTC
+    Bc7,
+}
+
+impl BlockFormat {
+    /// 4x4ブロックあたりのバイト数
+    pub fn block_size(self) -> usize {
+        match self {
+            BlockFormat::Bc1 | BlockFormat::Bc4 => 8,
+            _ => 16,
+        }
+    }
+
+    /// 指定サイズの画像1枚のバイト数
+    pub fn image_size(self, width: u32, height: u32) -> usize {
+        width.div_ceil(4) as usize * height.div_ceil(4) as usize * self.block_size()
+    }
+
+    /// 対応する `TextureFormat`（対応するものがない場合は常に展開が必要）
+    pub fn texture_format(self) -> Option<TextureFormat> {
+        match self {
+            BlockFormat::Bc1 => Some(TextureFormat::BC1),
+            BlockFormat::Bc2 => Some(TextureFormat::BC2),
+            BlockFormat::Bc3 => Some(TextureFormat::BC3),
+            BlockFormat::Bc7 => Some(TextureFormat::BC7),
+            BlockFormat::Bc4 | BlockFormat::Bc5 | BlockFormat::Bc6h { .. } => None,
+        }
+    }
+
+    /// 展開後のフォーマット
+    pub fn decompressed_format(self) -> TextureFormat {
+        match self {
+            BlockFormat::Bc4 => TextureFormat::R8,
+            BlockFormat::Bc5 => TextureFormat::R8G8,
+            BlockFormat::Bc6h { .. } => TextureFormat::R16G16B16A16F,
+            _ => TextureFormat::R8G8B8A8,
+        }
+    }
+
+    /// 展開後の1画素あたりのバイト数
+    fn decompressed_pixel_size(self) -> usize {
+        match self {
+            BlockFormat::Bc4 => 1,
+            BlockFormat::Bc5 => 2,
+            BlockFormat::Bc6h { .. } => 8,
+            _ => 4,
+        }
+    }
+}
+
+/// 画像1枚を展開（出力は `decompressed_format` の形式）
+pub fn decompress_image(format: BlockFormat, width: u32, height: u32, data: &[u8]) -> Result<Vec<u8>, ResourceLoadError> {
+    let required = format.image_size(width, height);
+    if data.len() < required {
+        return Err(ResourceLoadError::InvalidResource(format!(
+            "圧縮テクスチャのデータが不足しています: {} バイト (必要: {} バイト)", data.len(), required
+        )));
+    }
+
+    let pixel_size = format.decompressed_pixel_size();
+    let row_size = width as usize * pixel_size;
+    let mut output = vec![0u8; row_size * height as usize];
+    let blocks_x = width.div_ceil(4) as usize;
+
+    for (i, block) in data[..required].chunks_exact(format.block_size()).enumerate() {
+        let texels = decode_block(format, block);
+        let (bx, by) = ((i % blocks_x) * 4, (i / blocks_x) * 4);
+
+        // 画像の端からはみ出す画素は捨てる
+        for y in 0..4.min(height as usize - by) {
+            let columns = 4.min(width as usize - bx);
+            let dst = (by + y) * row_size + bx * pixel_size;
+            let src = y * 4 * pixel_size;
+            output[dst..dst + columns * pixel_size].copy_from_slice(&texels[src..src + columns * pixel_size]);
+        }
+    }
+
+    Ok(output)
+}
+
+/// 1ブロックを展開し、4x4画素を行優先で返す
+fn decode_block(format: BlockFormat, block: &[u8]) -> Vec<u8> {
+    match format {
+        BlockFormat::Bc1 => decode_bc1(block).concat(),
+        BlockFormat::Bc2 => decode_bc2(block).concat(),
+        BlockFormat::Bc3 => decode_bc3(block).concat(),
+        BlockFormat::Bc4 => decode_bc4(block).to_vec(),
+        BlockFormat::Bc5 => decode_bc5(block).concat(),
+        BlockFormat::Bc6h { signed } => decode_bc6h(block, signed)
+            .iter()
+            .flat_map(|[r, g, b]| [*r, *g, *b, HALF_ONE])
+            .flat_map(u16::to_le_bytes)
+            .collect(),
+        BlockFormat::Bc7 => decode_bc7(block).concat(),
+    }
+}
+
+/// 半精度浮動小数点数の1.0
+const HALF_ONE: u16 = 0x3c00;
+
+// ---- BC1〜BC5 ----
+
+fn rgb565(color: u16) -> [u8; 4] {
+    let r = ((color >> 11) & 0x1f) as u8;
+    let g = ((color >> 5) & 0x3f) as u8;
+    let b = (color & 0x1f) as u8;
+    [(r << 3) | (r >> 2), (g << 2) | (g >> 4), (b << 3) | (b >> 2), 255]
+}
+
+/// BC1〜BC3の色ブロックを展開
+///
+/// BC1では端点の大小関係で3色+透明モードになりますが、BC2/BC3では常に4色です。
+fn decode_color_block(block: &[u8], allow_transparent: bool) -> [[u8; 4]; 16] {
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let (e0, e1) = (rgb565(c0), rgb565(c1));
+
+    let mix = |w0: u16, w1: u16, d: u16| -> [u8; 4] {
+        let mut color = [255u8; 4];
+        for c in 0..3 {
+            color[c] = ((e0[c] as u16 * w0 + e1[c] as u16 * w1) / d) as u8;
+        }
+        color
+    };
+    let palette = if c0 > c1 || !allow_transparent {
+        [e0, e1, mix(2, 1, 3), mix(1, 2, 3)]
+    } else {
+        [e0, e1, mix(1, 1, 2), [0, 0, 0, 0]]
+    };
+
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+    std::array::from_fn(|i| palette[((indices >> (2 * i)) & 3) as usize])
+}
+
+/// BC3のアルファ、BC4/BC5の各チャンネルに使われる8段階補間ブロックを展開
+fn decode_channel_block(block: &[u8]) -> [u8; 16] {
+    let (a0, a1) = (block[0] as u32, block[1] as u32);
+    let mut palette = [0u8; 8];
+    palette[0] = a0 as u8;
+    palette[1] = a1 as u8;
+    if a0 > a1 {
+        for i in 1..7 {
+            palette[i + 1] = (((7 - i as u32) * a0 + i as u32 * a1) / 7) as u8;
+        }
+    } else {
+        for i in 1..5 {
+            palette[i + 1] = (((5 - i as u32) * a0 + i as u32 * a1) / 5) as u8;
+        }
+        palette[6] = 0;
+        palette[7] = 255;
+    }
+
+    let mut bits = [0u8; 8];
+    bits[..6].copy_from_slice(&block[2..8]);
+    let indices = u64::from_le_bytes(bits);
+    std::array::from_fn(|i| palette[((indices >> (3 * i)) & 7) as usize])
+}
+
+pub fn decode_bc1(block: &[u8]) -> [[u8; 4]; 16] {
+    decode_color_block(block, true)
+}
+
+pub fn decode_bc2(block: &[u8]) -> [[u8; 4]; 16] {
+    let mut texels = decode_color_block(&block[8..16], false);
+    let alpha = u64::from_le_bytes([block[0], block[1], block[2], block[3], block[4], block[5], block[6], block[7]]);
+    for (i, texel) in texels.iter_mut().enumerate() {
+        texel[3] = ((alpha >> (4 * i)) & 0xf) as u8 * 17;
+    }
+    texels
+}
+
+pub fn decode_bc3(block: &[u8]) -> [[u8; 4]; 16] {
+    let mut texels = decode_color_block(&block[8..16], false);
+    let alpha = decode_channel_block(&block[..8]);
+    for (texel, a) in texels.iter_mut().zip(alpha) {
+        texel[3] = a;
+    }
+    texels
+}
+
+pub fn decode_bc4(block: &[u8]) -> [u8; 16] {
+    decode_channel_block(&block[..8])
+}
+
+pub fn decode_bc5(block: &[u8]) -> [[u8; 2]; 16] {
+    let red = decode_channel_block(&block[..8]);
+    let green = decode_channel_block(&block[8..16]);
+    std::array::from_fn(|i| [red[i], green[i]])
+}
+
+// ---- BC6H / BC7 共通 ----
+
+/// 128ビットのブロックを下位ビットから順に読み出す
+struct BitReader {
+    bits: u128,
+    position: u32,
+}
+
+impl BitReader {
+    fn new(block: &[u8]) -> Self {
+        let mut bytes = [0u8; 16];
+        bytes.copy_from_slice(&block[..16]);
+        Self { bits: u128::from_le_bytes(bytes), position: 0 }
+    }
+
+    fn read(&mut self, count: u32) -> u32 {
+        if count == 0 {
+            return 0;
+        }
+        let value = (self.bits >> self.position) as u32 & (u32::MAX >> (32 - count));
+        self.position += count;
+        value
+    }
+}
+
+const WEIGHTS_2: [u32; 4] = [0, 21, 43, 64];
+const WEIGHTS_3: [u32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+const WEIGHTS_4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+fn weight(index_bits: u32, index: u32) -> u32 {
+    match index_bits {
+        2 => WEIGHTS_2[index as usize],
+        3 => WEIGHTS_3[index as usize],
+        _ => WEIGHTS_4[index as usize],
+    }
+}
+
+/// 2分割パーティション（ビットiが画素iのサブセット）
+const PARTITIONS_2: [u16; 64] = [
+    0xcccc, 0x8888, 0xeeee, 0xecc8, 0xc880, 0xfeec, 0xfec8, 0xec80,
+    0xc800, 0xffec, 0xfe80, 0xe800, 0xffe8, 0xff00, 0xfff0, 0xf000,
+    0xf710, 0x008e, 0x7100, 0x08ce, 0x008c, 0x7310, 0x3100, 0x8cce,
+    0x088c, 0x3110, 0x6666, 0x366c, 0x17e8, 0x0ff0, 0x718e, 0x399c,
+    0xaaaa, 0xf0f0, 0x5a5a, 0x33cc, 0x3c3c, 0x55aa, 0x9696, 0xa55a,
+    0x73ce, 0x13c8, 0x324c, 0x3bdc, 0x6996, 0xc33c, 0x9966, 0x0660,
+    0x0272, 0x04e4, 0x4e40, 0x2720, 0xc936, 0x936c, 0x39c6, 0x639c,
+    0x9336, 0x9cc6, 0x817e, 0xe718, 0xccf0, 0x0fcc, 0x7744, 0xee22,
+];
+
+/// 3分割パーティション
+const PARTITIONS_3: [[u8; 16]; 64] = [
+    [0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 1, 2, 2, 2, 2],
+    [0, 0, 0, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 2, 0, 0, 1, 2, 2, 1, 1, 2, 2, 1, 1],
+    [0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 1, 0, 1, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2],
+    [0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 2, 2],
+    [0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1],
+    [0, 0, 1, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2],
+    [0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2],
+    [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2],
+    [0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2],
+    [0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2],
+    [0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2, 1, 2, 2, 2],
+    [0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0, 2, 2, 2, 0],
+    [0, 0, 0, 1, 0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2],
+    [0, 1, 1, 1, 0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0],
+    [0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2],
+    [0, 0, 2, 2, 0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1],
+    [0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2, 0, 2, 2, 2],
+    [0, 0, 0, 1, 0, 0, 0, 1, 2, 2, 2, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2],
+    [0, 0, 0, 0, 1, 1, 0, 0, 2, 2, 1, 0, 2, 2, 1, 0],
+    [0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1, 0, 0, 0, 0],
+    [0, 0, 1, 2, 0, 0, 1, 2, 1, 1, 2, 2, 2, 2, 2, 2],
+    [0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1, 0, 1, 1, 0],
+    [0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1],
+    [0, 0, 2, 2, 1, 1, 0, 2, 1, 1, 0, 2, 0, 0, 2, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 2, 0, 0, 2, 2, 2, 2, 2],
+    [0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1],
+    [0, 0, 0, 0, 2, 0, 0, 0, 2, 2, 1, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 2, 2, 2],
+    [0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 2, 0, 0, 1, 1],
+    [0, 0, 1, 1, 0, 0, 1, 2, 0, 0, 2, 2, 0, 2, 2, 2],
+    [0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0],
+    [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0],
+    [0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0],
+    [0, 1, 2, 0, 2, 0, 1, 2, 1, 2, 0, 1, 0, 1, 2, 0],
+    [0, 0, 1, 1, 2, 2, 0, 0, 1, 1, 2, 2, 0, 0, 1, 1],
+    [0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0, 1, 1],
+    [0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1],
+    [0, 0, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2, 1, 1, 2, 2],
+    [0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 1, 1],
+    [0, 2, 2, 0, 1, 2, 2, 1, 0, 2, 2, 0, 1, 2, 2, 1],
+    [0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 0, 1, 0, 1],
+    [0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1],
+    [0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2],
+    [0, 2, 2, 2, 0, 1, 1, 1, 0, 2, 2, 2, 0, 1, 1, 1],
+    [0, 0, 0, 2, 1, 1, 1, 2, 0, 0, 0, 2, 1, 1, 1, 2],
+    [0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2],
+    [0, 2, 2, 2, 0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2],
+    [0, 0, 0, 2, 1, 1, 1, 2, 1, 1, 1, 2, 0, 0, 0, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2],
+    [0, 0, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2],
+    [0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 1],
+    [0, 2, 2, 2, 1, 2, 2, 2, 0, 2, 2, 2, 1, 2, 2, 2],
+    [0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 1, 1, 1, 2, 0, 1, 1, 2, 2, 0, 1, 2, 2, 2, 0],
+];
+
+/// 2分割時の2番目のサブセットのアンカー画素
+const ANCHORS_2: [u8; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15,
+    15, 2, 8, 2, 2, 8, 8, 15, 2, 8, 2, 2, 8, 8, 2, 2,
+    15, 15, 6, 8, 2, 8, 15, 15, 2, 8, 2, 2, 2, 15, 15, 6,
+    6, 2, 6, 8, 15, 15, 2, 2, 15, 15, 15, 15, 15, 2, 2, 15,
+];
+
+/// 3分割時の2番目のサブセットのアンカー画素
+const ANCHORS_3_SECOND: [u8; 64] = [
+    3, 3, 15, 15, 8, 3, 15, 15, 8, 8, 6, 6, 6, 5, 3, 3,
+    3, 3, 8, 15, 3, 3, 6, 10, 5, 8, 8, 6, 8, 5, 15, 15,
+    8, 15, 3, 5, 6, 10, 8, 15, 15, 3, 15, 5, 15, 15, 15, 15,
+    3, 15, 5, 5, 5, 8, 5, 10, 5, 10, 8, 13, 15, 12, 3, 3,
+];
+
+/// 3分割時の3番目のサブセットのアンカー画素
+const ANCHORS_3_THIRD: [u8; 64] = [
+    15, 8, 8, 3, 15, 15, 3, 8, 15, 15, 15, 15, 15, 15, 15, 8,
+    15, 8, 15, 3, 15, 8, 15, 8, 3, 15, 6, 10, 15, 15, 10, 8,
+    15, 3, 15, 10, 10, 8, 9, 10, 6, 15, 8, 15, 3, 6, 6, 8,
+    15, 3, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 3, 15, 15, 8,
+];
+
+/// 画素が属するサブセット
+fn subset_of(subsets: u32, partition: usize, pixel: usize) -> usize {
+    match subsets {
+        2 => ((PARTITIONS_2[partition] >> pixel) & 1) as usize,
+        3 => PARTITIONS_3[partition][pixel] as usize,
+        _ => 0,
+    }
+}
+
+/// インデックスのビット数が1少ないアンカー画素かどうか
+fn is_anchor(subsets: u32, partition: usize, pixel: usize) -> bool {
+    pixel == 0
+        || match subsets {
+            2 => pixel == ANCHORS_2[partition] as usize,
+            3 => pixel == ANCHORS_3_SECOND[partition] as usize || pixel == ANCHORS_3_THIRD[partition] as usize,
+            _ => false,
+        }
+}
+
+// ---- BC7 ----
+
+/// BC7の各モードのパラメーター
+struct Bc7Mode {
+    subsets: u32,
+    partition_bits: u32,
+    rotation_bits: u32,
+    index_selection_bits: u32,
+    color_bits: u32,
+    alpha_bits: u32,
+    endpoint_pbits: bool,
+    shared_pbits: bool,
+    index_bits: u32,
+    secondary_index_bits: u32,
+}
+
+#[allow(clippy::too_many_arguments)]
+const fn bc7_mode(
+    subsets: u32,
+    partition_bits: u32,
+    rotation_bits: u32,
+    index_selection_bits: u32,
+    color_bits: u32,
+    alpha_bits: u32,
+    endpoint_pbits: bool,
+    shared_pbits: bool,
+    index_bits: u32,
+    secondary_index_bits: u32,
+) -> Bc7Mode {
+    Bc7Mode {
+        subsets,
+        partition_bits,
+        rotation_bits,
+        index_selection_bits,
+        color_bits,
+        alpha_bits,
+        endpoint_pbits,
+        shared_pbits,
+        index_bits,
+        secondary_index_bits,
+    }
+}
+
+const BC7_MODES: [Bc7Mode; 8] = [
+    bc7_mode(3, 4, 0, 0, 4, 0, true, false, 3, 0),
+    bc7_mode(2, 6, 0, 0, 6, 0, false, true, 3, 0),
+    bc7_mode(3, 6, 0, 0, 5, 0, false, false, 2, 0),
+    bc7_mode(2, 6, 0, 0, 7, 0, true, false, 2, 0),
+    bc7_mode(1, 0, 2, 1, 5, 6, false, false, 2, 3),
+    bc7_mode(1, 0, 2, 0, 7, 8, false, false, 2, 2),
+    bc7_mode(1, 0, 0, 0, 7, 7, true, false, 4, 0),
+    bc7_mode(2, 6, 0, 0, 5, 5, true, false, 2, 0),
+];
+
+/// 指定ビット数の値を8ビットに拡張
+fn expand_bits(value: u32, bits: u32) -> u8 {
+    let value = value << (8 - bits);
+    (value | (value >> bits)) as u8
+}
+
+pub fn decode_bc7(block: &[u8]) -> [[u8; 4]; 16] {
+    let mut reader = BitReader::new(block);
+    let Some(mode_index) = (0..8).find(|&m| reader.bits & (1 << m) != 0) else {
+        // 不正なブロックは透明な黒になる
+        return [[0; 4]; 16];
+    };
+    reader.position = mode_index + 1;
+    let mode = &BC7_MODES[mode_index as usize];
+
+    let partition = reader.read(mode.partition_bits) as usize;
+    let rotation = reader.read(mode.rotation_bits);
+    let index_selection = reader.read(mode.index_selection_bits);
+
+    let endpoint_count = (mode.subsets * 2) as usize;
+    let mut endpoints = [[0u32; 4]; 6];
+    for channel in 0..3 {
+        for endpoint in endpoints.iter_mut().take(endpoint_count) {
+            endpoint[channel] = reader.read(mode.color_bits);
+        }
+    }
+    if mode.alpha_bits > 0 {
+        for endpoint in endpoints.iter_mut().take(endpoint_count) {
+            endpoint[3] = reader.read(mode.alpha_bits);
+        }
+    }
+
+    // Pビットは各成分の最下位ビットになる
+    let mut color_bits = mode.color_bits;
+    let mut alpha_bits = mode.alpha_bits;
+    if mode.endpoint_pbits || mode.shared_pbits {
+        let pbits: Vec<u32> = if mode.endpoint_pbits {
+            (0..endpoint_count).map(|_| reader.read(1)).collect()
+        } else {
+            let shared: Vec<u32> = (0..mode.subsets).map(|_| reader.read(1)).collect();
+            (0..endpoint_count).map(|i| shared[i / 2]).collect()
+        };
+        for (endpoint, pbit) in endpoints.iter_mut().zip(pbits) {
+            for value in endpoint.iter_mut() {
+                *value = (*value << 1) | pbit;
+            }
+        }
+        color_bits += 1;
+        if alpha_bits > 0 {
+            alpha_bits += 1;
+        }
+    }
+
+    let endpoints: Vec<[u8; 4]> = endpoints[..endpoint_count]
+        .iter()
+        .map(|e| [
+            expand_bits(e[0], color_bits),
+            expand_bits(e[1], color_bits),
+            expand_bits(e[2], color_bits),
+            if alpha_bits > 0 { expand_bits(e[3], alpha_bits) } else { 255 },
+        ])
+        .collect();
+
+    let mut indices = [0u32; 16];
+    for (pixel, index) in indices.iter_mut().enumerate() {
+        let bits = mode.index_bits - is_anchor(mode.subsets, partition, pixel) as u32;
+        *index = reader.read(bits);
+    }
+    let mut secondary = [0u32; 16];
+    if mode.secondary_index_bits > 0 {
+        for (pixel, index) in secondary.iter_mut().enumerate() {
+            *index = reader.read(mode.secondary_index_bits - (pixel == 0) as u32);
+        }
+    }
+
+    std::array::from_fn(|pixel| {
+        let subset = subset_of(mode.subsets, partition, pixel);
+        let (e0, e1) = (endpoints[subset * 2], endpoints[subset * 2 + 1]);
+
+        let (color_weight, alpha_weight) = if mode.secondary_index_bits == 0 {
+            let w = weight(mode.index_bits, indices[pixel]);
+            (w, w)
+        } else if index_selection == 0 {
+            (weight(mode.index_bits, indices[pixel]), weight(mode.secondary_index_bits, secondary[pixel]))
+        } else {
+            (weight(mode.secondary_index_bits, secondary[pixel]), weight(mode.index_bits, indices[pixel]))
+        };
+
+        let interpolate = |a: u8, b: u8, w: u32| (((64 - w) * a as u32 + w * b as u32 + 32) >> 6) as u8;
+        let mut texel = [
+            interpolate(e0[0], e1[0], color_weight),
+            interpolate(e0[1], e1[1], color_weight),
+            interpolate(e0[2], e1[2], color_weight),
+            interpolate(e0[3], e1[3], alpha_weight),
+        ];
+        match rotation {
+            1 => texel.swap(0, 3),
+            2 => texel.swap(1, 3),
+            3 => texel.swap(2, 3),
+            _ => {}
+        }
+        texel
+    })
+}
+
+// ---- BC6H ----
+
+// 端点フィールド: w/xが1番目の領域、y/zが2番目の領域の端点
+const RW: u8 = 0;
+const GW: u8 = 1;
+const BW: u8 = 2;
+const RX: u8 = 3;
+const GX: u8 = 4;
+const BX: u8 = 5;
+const RY: u8 = 6;
+const GY: u8 = 7;
+const BY: u8 = 8;
+const RZ: u8 = 9;
+const GZ: u8 = 10;
+const BZ: u8 = 11;
+const PARTITION: u8 = 12;
+
+/// ビット配置（フィールド、最下位ビット、ビット数）。ビット数が負の場合は上位ビットから逆順に並ぶ
+type Bc6hLayout = &'static [(u8, u8, i8)];
+
+/// BC6Hの各モードのパラメーター
+struct Bc6hMode {
+    id: u32,
+    transformed: bool,
+    endpoint_bits: u32,
+    delta_bits: [u32; 3],
+    layout: Bc6hLayout,
+}
+
+const BC6H_MODES: [Bc6hMode; 14] = [
+    Bc6hMode { id: 0b00, transformed: true, endpoint_bits: 10, delta_bits: [5, 5, 5], layout: &[
+        (GY, 4, 1), (BY, 4, 1), (BZ, 4, 1), (RW, 0, 10), (GW, 0, 10), (BW, 0, 10),
+        (RX, 0, 5), (GZ, 4, 1), (GY, 0, 4), (GX, 0, 5), (BZ, 0, 1), (GZ, 0, 4),
+        (BX, 0, 5), (BZ, 1, 1), (BY, 0, 4), (RY, 0, 5), (BZ, 2, 1), (RZ, 0, 5),
+        (BZ, 3, 1), (PARTITION, 0, 5),
+    ] },
+    Bc6hMode { id: 0b01, transformed: true, endpoint_bits: 7, delta_bits: [6, 6, 6], layout: &[
+        (GY, 5, 1), (GZ, 4, 1), (GZ, 5, 1), (RW, 0, 7), (BZ, 0, 1), (BZ, 1, 1),
+        (BY, 4, 1), (GW, 0, 7), (BY, 5, 1), (BZ, 2, 1), (GY, 4, 1), (BW, 0, 7),
+        (BZ, 3, 1), (BZ, 5, 1), (BZ, 4, 1), (RX, 0, 6), (GY, 0, 4), (GX, 0, 6),
+        (GZ, 0, 4), (BX, 0, 6), (BY, 0, 4), (RY, 0, 6), (RZ, 0, 6), (PARTITION, 0, 5),
+    ] },
+    Bc6hMode { id: 0b00010, transformed: true, endpoint_bits: 11, delta_bits: [5, 4, 4], layout: &[
+        (RW, 0, 10), (GW, 0, 10), (BW, 0, 10), (RX, 0, 5), (RW, 10, 1), (GY, 0, 4),
+        (GX, 0, 4), (GW, 10, 1), (BZ, 0, 1), (GZ, 0, 4), (BX, 0, 4), (BW, 10, 1),
+        (BZ, 1, 1), (BY, 0, 4), (RY, 0, 5), (BZ, 2, 1), (RZ, 0, 5), (BZ, 3, 1),
+        (PARTITION, 0, 5),
+    ] },
+    Bc6hMode { id: 0b00110, transformed: true, endpoint_bits: 11, delta_bits: [4, 5, 4], layout: &[
+        (RW, 0, 10), (GW, 0, 10), (BW, 0, 10), (RX, 0, 4), (RW, 10, 1), (GZ, 4, 1),
+        (GY, 0, 4), (GX, 0, 5), (GW, 10, 1), (GZ, 0, 4), (BX, 0, 4), (BW, 10, 1),
+        (BZ, 1, 1), (BY, 0, 4), (RY, 0, 4), (BZ, 0, 1), (BZ, 2, 1), (RZ, 0, 4),
+        (GY, 4, 1), (BZ, 3, 1), (PARTITION, 0, 5),
+    ] },
+    Bc6hMode { id: 0b01010, transformed: true, endpoint_bits: 11, delta_bits: [4, 4, 5], layout: &[
+        (RW, 0, 10), (GW, 0, 10), (BW, 0, 10), (RX, 0, 4), (RW, 10, 1), (BY, 4, 1),
+        (GY, 0, 4), (GX, 0, 4), (GW, 10, 1), (BZ, 0, 1), (GZ, 0, 4), (BX, 0, 5),
+        (BW, 10, 1), (BY, 0, 4), (RY, 0, 4), (BZ, 1, 1), (BZ, 2, 1), (RZ, 0, 4),
+        (BZ, 4, 1), (BZ, 3, 1), (PARTITION, 0, 5),
+    ] },
+    Bc6hMode { id: 0b01110, transformed: true, endpoint_bits: 9, delta_bits: [5, 5, 5], layout: &[
+        (RW, 0, 9), (BY, 4, 1), (GW, 0, 9), (GY, 4, 1), (BW, 0, 9), (BZ, 4, 1),
+        (RX, 0, 5), (GZ, 4, 1), (GY, 0, 4), (GX, 0, 5), (BZ, 0, 1), (GZ, 0, 4),
+        (BX, 0, 5), (BZ, 1, 1), (BY, 0, 4), (RY, 0, 5), (BZ, 2, 1), (RZ, 0, 5),
+        (BZ, 3, 1), (PARTITION, 0, 5),
+    ] },
+    Bc6hMode { id: 0b10010, transformed: true, endpoint_bits: 8, delta_bits: [6, 5, 5], layout: &[
+        (RW, 0, 8), (GZ, 4, 1), (BY, 4, 1), (GW, 0, 8), (BZ, 2, 1), (GY, 4, 1),
+        (BW, 0, 8), (BZ, 3, 1), (BZ, 4, 1), (RX, 0, 6), (GY, 0, 4), (GX, 0, 5),
+        (BZ, 0, 1), (GZ, 0, 4), (BX, 0, 5), (BZ, 1, 1), (BY, 0, 4), (RY, 0, 6),
+        (RZ, 0, 6), (PARTITION, 0, 5),
+    ] },
+    Bc6hMode { id: 0b10110, transformed: true, endpoint_bits: 8, delta_bits: [5, 6, 5], layout: &[
+        (RW, 0, 8), (BZ, 0, 1), (BY, 4, 1), (GW, 0, 8), (GY, 5, 1), (GY, 4, 1),
+        (BW, 0, 8), (GZ, 5, 1), (BZ, 4, 1), (RX, 0, 5), (GZ, 4, 1), (GY, 0, 4),
+        (GX, 0, 6), (GZ, 0, 4), (BX, 0, 5), (BZ, 1, 1), (BY, 0, 4), (RY, 0, 5),
+        (BZ, 2, 1), (RZ, 0, 5), (BZ, 3, 1), (PARTITION, 0, 5),
+    ] },
+    Bc6hMode { id: 0b11010, transformed: true, endpoint_bits: 8, delta_bits: [5, 5, 6], layout: &[
+        (RW, 0, 8), (BZ, 1, 1), (BY, 4, 1), (GW, 0, 8), (BY, 5, 1), (GY, 4, 1),
+        (BW, 0, 8), (BZ, 5, 1), (BZ, 4, 1), (RX, 0, 5), (GZ, 4, 1), (GY, 0, 4),
+        (GX, 0, 5), (BZ, 0, 1), (GZ, 0, 4), (BX, 0, 6), (BY, 0, 4), (RY, 0, 5),
+        (BZ, 2, 1), (RZ, 0, 5), (BZ, 3, 1), (PARTITION, 0, 5),
+    ] },
+    Bc6hMode { id: 0b11110, transformed: false, endpoint_bits: 6, delta_bits: [6, 6, 6], layout: &[
+        (RW, 0, 6), (GZ, 4, 1), (BZ, 0, 1), (BZ, 1, 1), (BY, 4, 1), (GW, 0, 6),
+        (GY, 5, 1), (BY, 5, 1), (BZ, 2, 1), (GY, 4, 1), (BW, 0, 6), (GZ, 5, 1),
+        (BZ, 3, 1), (BZ, 5, 1), (BZ, 4, 1), (RX, 0, 6), (GY, 0, 4), (GX, 0, 6),
+        (GZ, 0, 4), (BX, 0, 6), (BY, 0, 4), (RY, 0, 6), (RZ, 0, 6), (PARTITION, 0, 5),
+    ] },
+    Bc6hMode { id: 0b00011, transformed: false, endpoint_bits: 10, delta_bits: [10, 10, 10], layout: &[
+        (RW, 0, 10), (GW, 0, 10), (BW, 0, 10), (RX, 0, 10), (GX, 0, 10), (BX, 0, 10),
+    ] },
+    Bc6hMode { id: 0b00111, transformed: true, endpoint_bits: 11, delta_bits: [9, 9, 9], layout: &[
+        (RW, 0, 10), (GW, 0, 10), (BW, 0, 10), (RX, 0, 9), (RW, 10, 1), (GX, 0, 9),
+        (GW, 10, 1), (BX, 0, 9), (BW, 10, 1),
+    ] },
+    Bc6hMode { id: 0b01011, transformed: true, endpoint_bits: 12, delta_bits: [8, 8, 8], layout: &[
+        (RW, 0, 10), (GW, 0, 10), (BW, 0, 10), (RX, 0, 8), (RW, 10, -2), (GX, 0, 8),
+        (GW, 10, -2), (BX, 0, 8), (BW, 10, -2),
+    ] },
+    Bc6hMode { id: 0b01111, transformed: true, endpoint_bits: 16, delta_bits: [4, 4, 4], layout: &[
+        (RW, 0, 10), (GW, 0, 10), (BW, 0, 10), (RX, 0, 4), (RW, 10, -6), (GX, 0, 4),
+        (GW, 10, -6), (BX, 0, 4), (BW, 10, -6),
+    ] },
+];
+
+fn sign_extend(value: i32, bits: u32) -> i32 {
+    let shift = 32 - bits;
+    (value << shift) >> shift
+}
+
+/// 量子化された端点を16ビットに戻す
+fn unquantize(value: i32, bits: u32, signed: bool) -> i32 {
+    if !signed {
+        if bits >= 15 || value == 0 {
+            value
+        } else if value == (1 << bits) - 1 {
+            0xffff
+        } else {
+            ((value << 16) + 0x8000) >> bits
+        }
+    } else {
+        if bits >= 16 {
+            return value;
+        }
+        let magnitude = value.abs();
+        let unquantized = if magnitude == 0 {
+            0
+        } else if magnitude >= (1 << (bits - 1)) - 1 {
+            0x7fff
+        } else {
+            ((magnitude << 15) + 0x4000) >> (bits - 1)
+        };
+        if value < 0 { -unquantized } else { unquantized }
+    }
+}
+
+/// 補間した値を半精度浮動小数点数のビット列にする
+fn finish_unquantize(value: i32, signed: bool) -> u16 {
+    if !signed {
+        ((value * 31) >> 6) as u16
+    } else {
+        let scaled = if value < 0 { -(((-value) * 31) >> 5) } else { (value * 31) >> 5 };
+        if scaled < 0 {
+            0x8000 | (-scaled) as u16
+        } else {
+            scaled as u16
+        }
+    }
+}
+
+/// RGBを半精度浮動小数点数で返す
+pub fn decode_bc6h(block: &[u8], signed: bool) -> [[u16; 3]; 16] {
+    let mut reader = BitReader::new(block);
+    let mut id = reader.read(2);
+    if id > 1 {
+        id |= reader.read(3) << 2;
+    }
+    let Some(mode) = BC6H_MODES.iter().find(|mode| mode.id == id) else {
+        // 予約されたモードは黒になる
+        return [[0; 3]; 16];
+    };
+
+    let mut fields = [0i32; 13];
+    for &(field, lsb, count) in mode.layout {
+        if count > 0 {
+            fields[field as usize] |= (reader.read(count as u32) as i32) << lsb;
+        } else {
+            for bit in (lsb..lsb + (-count) as u8).rev() {
+                fields[field as usize] |= (reader.read(1) as i32) << bit;
+            }
+        }
+    }
+
+    let regions = if mode.layout.iter().any(|&(field, _, _)| field == PARTITION) { 2 } else { 1 };
+    let partition = fields[PARTITION as usize] as usize;
+    let mut endpoints: Vec<[i32; 3]> = (0..regions * 2)
+        .map(|e| [fields[e * 3], fields[e * 3 + 1], fields[e * 3 + 2]])
+        .collect();
+
+    // 符号拡張と差分の復元
+    let endpoint_mask = (1i32 << mode.endpoint_bits) - 1;
+    let base = endpoints[0];
+    for (e, endpoint) in endpoints.iter_mut().enumerate() {
+        for c in 0..3 {
+            if e == 0 {
+                if signed {
+                    endpoint[c] = sign_extend(endpoint[c], mode.endpoint_bits);
+                }
+                continue;
+            }
+            if signed || mode.transformed {
+                endpoint[c] = sign_extend(endpoint[c], mode.delta_bits[c]);
+            }
+            if mode.transformed {
+                endpoint[c] = (endpoint[c] + base[c]) & endpoint_mask;
+                if signed {
+                    endpoint[c] = sign_extend(endpoint[c], mode.endpoint_bits);
+                }
+            }
+        }
+    }
+    for endpoint in endpoints.iter_mut() {
+        *endpoint = endpoint.map(|v| unquantize(v, mode.endpoint_bits, signed));
+    }
+
+    let index_bits = if regions == 2 { 3 } else { 4 };
+    std::array::from_fn(|pixel| {
+        let bits = index_bits - is_anchor(regions as u32, partition, pixel) as u32;
+        let index = reader.read(bits);
+        let region = subset_of(regions as u32, partition, pixel);
+        let (e0, e1) = (endpoints[region * 2], endpoints[region * 2 + 1]);
+        let w = weight(index_bits, index) as i32;
+        std::array::from_fn(|c| finish_unquantize(((64 - w) * e0[c] + w * e1[c] + 32) >> 6, signed))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// テスト用に下位ビットから順にブロックを組み立てる
+    struct BitWriter {
+        bits: u128,
+        position: u32,
+    }
+
+    impl BitWriter {
+        fn new() -> Self {
+            Self { bits: 0, position: 0 }
+        }
+
+        fn write(&mut self, value: u32, count: u32) -> &mut Self {
+            self.bits |= ((value as u128) & ((1u128 << count) - 1)) << self.position;
+            self.position += count;
+            self
+        }
+
+        fn finish(&self) -> [u8; 16] {
+            self.bits.to_le_bytes()
+        }
+    }
+
+    #[test]
+    fn test_bc1() {
+        // 赤と青の4色モード、インデックスは画素ごとに0,1,2,3の繰り返し
+        let block = [0x00, 0xf8, 0x1f, 0x00, 0xe4, 0xe4, 0xe4, 0xe4];
+        let texels = decode_bc1(&block);
+        assert_eq!(texels[0], [255, 0, 0, 255]);
+        assert_eq!(texels[1], [0, 0, 255, 255]);
+        assert_eq!(texels[2], [170, 0, 85, 255]);
+        assert_eq!(texels[3], [85, 0, 170, 255]);
+
+        // c0 <= c1 のときは3色+透明
+        let block = [0x1f, 0x00, 0x00, 0xf8, 0xe4, 0xe4, 0xe4, 0xe4];
+        let texels = decode_bc1(&block);
+        assert_eq!(texels[2], [127, 0, 127, 255]);
+        assert_eq!(texels[3], [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_bc2_bc3_alpha() {
+        let color = [0xff, 0xff, 0x00, 0x00, 0, 0, 0, 0];
+
+        let mut bc2 = [0u8; 16];
+        bc2[0] = 0xf0; // 画素0は0、画素1は15
+        bc2[8..].copy_from_slice(&color);
+        let texels = decode_bc2(&bc2);
+        assert_eq!(texels[0], [255, 255, 255, 0]);
+        assert_eq!(texels[1], [255, 255, 255, 255]);
+
+        // a0 > a1 の8段階補間、画素0はインデックス2、画素1はインデックス1
+        let mut bc3 = [0u8; 16];
+        bc3[..3].copy_from_slice(&[255, 3, 0b0000_1010]);
+        bc3[8..].copy_from_slice(&color);
+        let texels = decode_bc3(&bc3);
+        assert_eq!(texels[0][3], ((6 * 255 + 3) / 7) as u8);
+        assert_eq!(texels[1][3], 3);
+
+        // a0 <= a1 のときはインデックス6と7が0と255
+        let alpha = decode_channel_block(&[10, 20, 0b0000_0110, 0, 0, 0, 0, 0]);
+        assert_eq!(alpha[0], 0);
+        assert_eq!(alpha[1], 10);
+    }
+
+    #[test]
+    fn test_bc4_bc5() {
+        let red = [200, 100, 0, 0, 0, 0, 0, 0];
+        let green = [50, 60, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff];
+        assert_eq!(decode_bc4(&red), [200; 16]);
+
+        let mut bc5 = [0u8; 16];
+        bc5[..8].copy_from_slice(&red);
+        bc5[8..].copy_from_slice(&green);
+        assert_eq!(decode_bc5(&bc5)[5], [200, 255]);
+    }
+
+    #[test]
+    fn test_bc7_mode6() {
+        // 端点0が黒、端点1が白で、画素iのインデックスはi
+        let mut writer = BitWriter::new();
+        writer.write(1 << 6, 7);
+        for _ in 0..3 {
+            writer.write(0, 7).write(127, 7);
+        }
+        writer.write(127, 7).write(127, 7);
+        writer.write(0, 1).write(1, 1);
+        for i in 0..16 {
+            writer.write(i, if i == 0 { 3 } else { 4 });
+        }
+
+        // Pビットが0の端点0はアルファが254になる
+        let texels = decode_bc7(&writer.finish());
+        assert_eq!(texels[0], [0, 0, 0, 254]);
+        assert_eq!(texels[15], [255, 255, 255, 255]);
+        assert_eq!(texels[8][0], ((34 * 255 + 32) >> 6) as u8);
+        assert_eq!(texels[8][3], 255);
+
+        // モードビットのないブロックは透明な黒
+        assert_eq!(decode_bc7(&[0; 16]), [[0; 4]; 16]);
+    }
+
+    #[test]
+    fn test_bc7_rotation_and_partition() {
+        // モード5: 回転1でアルファと赤を入れ替える
+        let mut writer = BitWriter::new();
+        writer.write(1 << 5, 6).write(1, 2);
+        for value in [100, 100, 20, 20, 30, 30] {
+            writer.write(value, 7);
+        }
+        writer.write(255, 8).write(255, 8);
+        let texels = decode_bc7(&writer.finish());
+        assert_eq!(texels[0], [255, expand_bits(20, 7), expand_bits(30, 7), expand_bits(100, 7)]);
+
+        // モード1: パーティション13は上半分がサブセット0、下半分がサブセット1
+        let mut writer = BitWriter::new();
+        writer.write(0b10, 2).write(13, 6);
+        for _ in 0..3 {
+            writer.write(0, 6).write(0, 6).write(63, 6).write(63, 6);
+        }
+        writer.write(1, 1).write(1, 1);
+        let texels = decode_bc7(&writer.finish());
+        assert_eq!(texels[0], [expand_bits(1, 7), expand_bits(1, 7), expand_bits(1, 7), 255]);
+        assert_eq!(texels[15], [255, 255, 255, 255]);
+        assert_eq!(subset_of(2, 13, 7), 0);
+        assert_eq!(subset_of(2, 13, 8), 1);
+    }
+
+    #[test]
+    fn test_bc6h_mode11() {
+        // 非変換の1領域モード: 端点をそのまま10ビットで格納
+        let mut writer = BitWriter::new();
+        writer.write(0b00011, 5);
+        for value in [0, 0, 0] {
+            writer.write(value, 10);
+        }
+        for value in [1023, 512, 0] {
+            writer.write(value, 10);
+        }
+        for i in 0..16 {
+            writer.write(if i == 0 { 0 } else { 15 }, if i == 0 { 3 } else { 4 });
+        }
+
+        let texels = decode_bc6h(&writer.finish(), false);
+        assert_eq!(texels[0], [0, 0, 0]);
+        // 最大値は65504に近い半精度の最大有限値になる
+        assert_eq!(texels[1][0], ((0xffff * 31) >> 6) as u16);
+        assert_eq!(texels[1][1], ((unquantize(512, 10, false) * 31) >> 6) as u16);
+        assert_eq!(texels[1][2], 0);
+
+        // 予約されたモードは黒
+        let reserved = BitWriter::new().write(0b10011, 5).finish();
+        assert_eq!(decode_bc6h(&reserved, false), [[0; 3]; 16]);
+    }
+
+    #[test]
+    fn test_bc6h_signed_unquantize() {
+        assert_eq!(unquantize(-1, 10, true), -unquantize(1, 10, true));
+        assert_eq!(unquantize(511, 10, true), 0x7fff);
+        assert_eq!(finish_unquantize(-0x7fff, true), 0x8000 | ((0x7fff * 31) >> 5) as u16);
+        assert_eq!(sign_extend(0b11111, 5), -1);
+    }
+
+    #[test]
+    fn test_decompress_image_crops_edges() {
+        // 6x2の画像は2ブロック分のデータから切り出される
+        let mut data = Vec::new();
+        data.extend_from_slice(&[0x00, 0xf8, 0x00, 0xf8, 0, 0, 0, 0]);
+        data.extend_from_slice(&[0x1f, 0x00, 0x1f, 0x00, 0, 0, 0, 0]);
+
+        let pixels = decompress_image(BlockFormat::Bc1, 6, 2, &data).unwrap();
+        assert_eq!(pixels.len(), 6 * 2 * 4);
+        assert_eq!(&pixels[..4], &[255, 0, 0, 255]);
+        assert_eq!(&pixels[5 * 4..6 * 4], &[0, 0, 255, 255]);
+        assert_eq!(&pixels[6 * 4..7 * 4], &[255, 0, 0, 255]);
+
+        assert!(decompress_image(BlockFormat::Bc1, 8, 8, &data).is_err());
+        assert_eq!(BlockFormat::Bc6h { signed: false }.decompressed_format(), TextureFormat::R16G16B16A16F);
+        assert_eq!(BlockFormat::Bc7.image_size(5, 5), 4 * 16);
+    }
+}
@@ -8,18 +8,37 @@
 //!
 //! グラフィックスモジュールは、様々なバックエンド（Vulkan、Metal、DirectX）への
 //! 抽象化レイヤーとして機能し、プラットフォーム間での一貫した描画APIを提供します。
+//! wgpuバックエンドは実行環境に応じてネイティブAPIを自動選択し、
+//! GPUが利用できない環境ではCPUで描画するソフトウェアバックエンドを使用できます。
+//! オフスクリーンのレンダーターゲットは非同期に読み出してPNGとして保存できます。
+//! シェーダーはnagaで読み込み時に検証され、各バックエンドの形式に変換されます。
+//! 読み込んだシェーダーファイルとインクルードは監視され、変更されると再コンパイルされます。
+//! コンパイル結果はディスクにキャッシュされ、次回起動時のコンパイルを省略します。
 
 pub mod renderer;
+pub mod readback;
 pub mod vulkan_backend;
 pub mod metal_backend;
 pub mod dx_backend;
+pub mod software_backend;
+pub mod wgpu_backend;
 pub mod shader_manager;
+pub mod shader_compiler;
+pub mod shader_cache;
+pub mod shader_include;
+pub mod shader_watcher;
 pub mod resource_manager;
+pub mod async_loader;
+pub mod residency;
+pub mod texture_loader;
+pub mod texture_container;
+pub mod bc_decoder;
 
 use std::sync::{Arc, Mutex, RwLock};
 use std::collections::HashMap;
 use std::error::Error;
 use std::fmt;
+use std::path::PathBuf;
 
 // 主要なモジュールの公開型をre-export
 pub use renderer::{
@@ -28,16 +47,29 @@ pub use renderer::{
     RenderPass,
     RenderTarget,
     RenderCommandBuffer,
-    PipelineState
+    PipelineState,
+    OffscreenTarget
 };
 
+pub use readback::{PixelData, ReadbackFuture};
+
+pub use software_backend::SoftwareRenderer;
+
+pub use wgpu_backend::WgpuRenderer;
+
 pub use shader_manager::{
     ShaderManager,
     Shader,
     ShaderStage,
-    ShaderCompilationError
+    ShaderCompilationError,
+    ShaderDiagnostic,
+    ShaderReloadEvent
 };
 
+pub use shader_compiler::{ShaderReflection, ShaderBinding, BindingType, VertexInput};
+
+pub use shader_cache::ShaderCache;
+
 pub use resource_manager::{
     ResourceManager,
     TextureResource,
@@ -47,6 +79,13 @@ pub use resource_manager::{
     ResourceLoadError
 };
 
+pub use residency::{MemoryBudget, ResourceCategory, ResourceMemoryStats, ResourceRef};
+pub use async_loader::{LoadPriority, PendingLoad};
+
+pub use texture_loader::{ColorSpace, TextureLoadOptions};
+pub use texture_container::{ContainerTexture, SurfaceFormat};
+pub use bc_decoder::BlockFormat;
+
 /// グラフィックスAPIタイプ
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum GraphicsApi {
@@ -58,6 +97,8 @@ pub enum GraphicsApi {
     DirectX,
     /// OpenGL
     OpenGL,
+    /// wgpu (Vulkan/Metal/DX12/GLを自動選択)
+    Wgpu,
     /// ソフトウェアレンダリング (フォールバック)
     Software,
 }
@@ -69,6 +110,7 @@ impl fmt::Display for GraphicsApi {
             GraphicsApi::Metal => write!(f, "Metal"),
             GraphicsApi::DirectX => write!(f, "DirectX"),
             GraphicsApi::OpenGL => write!(f, "OpenGL"),
+            GraphicsApi::Wgpu => write!(f, "wgpu"),
             GraphicsApi::Software => write!(f, "Software"),
         }
     }
@@ -126,10 +168,18 @@ pub struct GraphicsConfig {
     pub max_texture_size: u32,
     /// テクスチャ圧縮有効フラグ
     pub texture_compression: bool,
+    /// リソースのカテゴリ別GPUメモリ予算
+    pub memory_budget: MemoryBudget,
     /// 最大アニソトロピックフィルタリングレベル
     pub max_anisotropy: f32,
     /// GPU省電力モード有効フラグ
     pub power_saving_mode: bool,
+    /// シェーダーファイルの変更を監視して再読み込みする
+    pub shader_hot_reload: bool,
+    /// シェーダーキャッシュのディレクトリ（`None` の場合は既定のディレクトリ）
+    pub shader_cache_dir: Option<PathBuf>,
+    /// シェーダーキャッシュの最大サイズ（バイト、0=無効）
+    pub shader_cache_size: u64,
     /// カスタム設定
     pub custom_settings: HashMap<String, String>,
 }
@@ -138,14 +188,18 @@ impl Default for GraphicsConfig {
     fn default() -> Self {
         Self {
             preferred_api: GraphicsApi::Vulkan,
-            fallback_apis: vec![GraphicsApi::OpenGL, GraphicsApi::Software],
+            fallback_apis: vec![GraphicsApi::Wgpu, GraphicsApi::OpenGL, GraphicsApi::Software],
             hardware_acceleration: true,
             vsync: true,
             msaa_samples: 4,
             max_texture_size: 4096,
             texture_compression: true,
+            memory_budget: MemoryBudget::default(),
             max_anisotropy: 16.0,
             power_saving_mode: false,
+            shader_hot_reload: true,
+            shader_cache_dir: None,
+            shader_cache_size: 64 * 1024 * 1024,
             custom_settings: HashMap::new(),
         }
     }
@@ -189,8 +243,8 @@ impl GraphicsManager {
     /// 設定を指定してグラフィックスマネージャーを作成
     pub fn with_config(config: GraphicsConfig) -> Self {
         Self {
-            config,
             current_api: config.preferred_api,
+            config,
             renderer: None,
             shader_manager: None,
             resource_manager: None,
@@ -208,11 +262,23 @@ impl GraphicsManager {
         // 利用可能なAPIを確認
         let available_apis = self.detect_available_apis()?;
         
-        // 優先順位に基づいてAPIを選択
-        self.current_api = self.select_api(&available_apis)?;
-        
-        // 選択されたAPIに基づいてレンダラーを初期化
-        self.initialize_renderer()?;
+        // 優先順位の高いAPIから順にレンダラーの初期化を試みる
+        let mut last_error = None;
+        for api in self.select_apis(&available_apis)? {
+            self.current_api = api;
+            match self.initialize_renderer() {
+                Ok(()) => break,
+                Err(e) => {
+                    log::warn!("{} レンダラーを初期化できません: {}", api, e);
+                    last_error = Some(e);
+                }
+            }
+        }
+        if self.renderer.is_none() {
+            return Err(last_error.unwrap_or_else(|| {
+                GraphicsError::Initialization("APIを選択できません".to_string())
+            }));
+        }
         
         // シェーダーマネージャーを初期化
         self.initialize_shader_manager()?;
@@ -245,6 +311,9 @@ impl GraphicsManager {
         #[cfg(feature = "opengl")]
         available_apis.push(GraphicsApi::OpenGL);
         
+        // wgpuはアダプターが見つからない場合に初期化時に失敗する
+        available_apis.push(GraphicsApi::Wgpu);
+        
         // ソフトウェアレンダリングは常に利用可能
         available_apis.push(GraphicsApi::Software);
         
@@ -257,24 +326,23 @@ impl GraphicsManager {
         Ok(available_apis)
     }
     
-    /// 優先順位に基づいてAPIを選択
-    fn select_api(&self, available_apis: &[GraphicsApi]) -> Result<GraphicsApi, GraphicsError> {
-        // 優先APIが利用可能か確認
-        if available_apis.contains(&self.config.preferred_api) {
-            return Ok(self.config.preferred_api);
-        }
-        
-        // フォールバックAPIを優先順位に基づいて試行
-        for api in &self.config.fallback_apis {
-            if available_apis.contains(api) {
-                return Ok(*api);
+    /// 優先順位に基づいて試行するAPIを並べる
+    fn select_apis(&self, available_apis: &[GraphicsApi]) -> Result<Vec<GraphicsApi>, GraphicsError> {
+        // 優先API、フォールバックAPI、その他の利用可能なAPIの順
+        let mut apis: Vec<GraphicsApi> = Vec::new();
+        let candidates = std::iter::once(&self.config.preferred_api)
+            .chain(self.config.fallback_apis.iter())
+            .chain(available_apis.iter());
+        for api in candidates {
+            if available_apis.contains(api) && !apis.contains(api) {
+                apis.push(*api);
             }
         }
         
-        // 利用可能なAPIから最初のものを選択
-        available_apis.first().copied().ok_or_else(|| {
-            GraphicsError::Initialization("APIを選択できません".to_string())
-        })
+        if apis.is_empty() {
+            return Err(GraphicsError::Initialization("APIを選択できません".to_string()));
+        }
+        Ok(apis)
     }
     
     /// レンダラーを初期化
@@ -334,17 +402,38 @@ impl GraphicsManager {
                     ));
                 }
             }
+            GraphicsApi::Wgpu => {
+                let mut renderer = wgpu_backend::WgpuRenderer::new(&self.config)?;
+                renderer.initialize(&self.config)?;
+                Some(Arc::new(Mutex::new(renderer)))
+            }
             GraphicsApi::Software => {
-                // ソフトウェアレンダラーの実装（将来的に追加予定）
-                return Err(GraphicsError::UnsupportedApi(
-                    "ソフトウェアレンダリングは現在実装されていません".to_string(),
-                ));
+                let mut renderer = software_backend::SoftwareRenderer::new(&self.config)?;
+                renderer.initialize(&self.config)?;
+                Some(Arc::new(Mutex::new(renderer)))
             }
         };
         
         Ok(())
     }
     
+    /// 設定に従ってシェーダーキャッシュを開く
+    ///
+    /// キャッシュは起動を速くするためのものなので、開けない場合は警告だけ出して無効にします。
+    fn open_shader_cache(config: &GraphicsConfig) -> Option<ShaderCache> {
+        if config.shader_cache_size == 0 {
+            return None;
+        }
+        let dir = config.shader_cache_dir.clone().or_else(ShaderCache::default_dir)?;
+        match ShaderCache::open(&dir, config.shader_cache_size) {
+            Ok(cache) => Some(cache),
+            Err(e) => {
+                log::warn!("シェーダーキャッシュを開けませんでした（{}）: {}", dir.display(), e);
+                None
+            }
+        }
+    }
+    
     /// シェーダーマネージャーを初期化
     fn initialize_shader_manager(&mut self) -> Result<(), GraphicsError> {
         let renderer_ref = self.renderer.as_ref().ok_or_else(|| {
@@ -355,7 +444,9 @@ impl GraphicsManager {
             GraphicsError::Initialization("レンダラーのロックに失敗しました".to_string())
         })?;
         
-        let shader_manager = shader_manager::ShaderManager::new(self.current_api, renderer.get_device_info())?;
+        let mut shader_manager = shader_manager::ShaderManager::new(self.current_api, renderer.get_device_info())?;
+        shader_manager.set_hot_reload(self.config.shader_hot_reload);
+        shader_manager.set_disk_cache(Self::open_shader_cache(&self.config));
         self.shader_manager = Some(Arc::new(RwLock::new(shader_manager)));
         
         Ok(())
@@ -367,12 +458,13 @@ impl GraphicsManager {
             GraphicsError::Initialization("レンダラーが初期化されていません".to_string())
         })?;
         
-        let resource_manager = resource_manager::ResourceManager::new(
+        let mut resource_manager = resource_manager::ResourceManager::new(
             self.current_api,
             Arc::clone(renderer_ref),
             self.config.max_texture_size,
             self.config.texture_compression,
         )?;
+        resource_manager.set_memory_budget(self.config.memory_budget)?;
         
         self.resource_manager = Some(Arc::new(RwLock::new(resource_manager)));
         
@@ -430,6 +522,8 @@ impl GraphicsManager {
         let needs_reinit = self.config.preferred_api != config.preferred_api
             || self.config.fallback_apis != config.fallback_apis
             || self.config.hardware_acceleration != config.hardware_acceleration;
+        let shader_cache_changed = self.config.shader_cache_dir != config.shader_cache_dir
+            || self.config.shader_cache_size != config.shader_cache_size;
             
         self.config = config;
         
@@ -446,11 +540,61 @@ impl GraphicsManager {
                 
                 renderer.update_config(&self.config)?;
             }
+            
+            if let Some(resource_manager_ref) = &self.resource_manager {
+                let mut resource_manager = resource_manager_ref.write().map_err(|_| {
+                    GraphicsError::Initialization("リソースマネージャーのロックに失敗しました".to_string())
+                })?;
+                
+                resource_manager.set_memory_budget(self.config.memory_budget)?;
+            }
+            
+            if let Some(shader_manager_ref) = &self.shader_manager {
+                let mut shader_manager = shader_manager_ref.write().map_err(|_| {
+                    GraphicsError::Initialization("シェーダーマネージャーのロックに失敗しました".to_string())
+                })?;
+                
+                shader_manager.set_hot_reload(self.config.shader_hot_reload);
+                if shader_cache_changed {
+                    shader_manager.set_disk_cache(Self::open_shader_cache(&self.config));
+                }
+            }
         }
         
         Ok(())
     }
     
+    /// 変更されたシェーダーを再コンパイルし、レンダラーにパイプラインを作り直させる
+    ///
+    /// 毎フレームの描画前に呼び出します。再コンパイルに失敗したシェーダーは以前のバージョンが使われます。
+    pub fn reload_changed_shaders(&self) -> Result<Vec<shader_manager::ShaderReloadEvent>, GraphicsError> {
+        let Some(shader_manager_ref) = &self.shader_manager else {
+            return Ok(Vec::new());
+        };
+        let mut shader_manager = shader_manager_ref.write().map_err(|_| {
+            GraphicsError::Other("シェーダーマネージャーのロックに失敗しました".to_string())
+        })?;
+        
+        let events = shader_manager.poll_changes();
+        if let Some(renderer_ref) = &self.renderer {
+            let mut renderer = renderer_ref.lock().map_err(|_| {
+                GraphicsError::Other("レンダラーのロックに失敗しました".to_string())
+            })?;
+            
+            for event in &events {
+                let shader_manager::ShaderReloadEvent::Reloaded { name, .. } = event else { continue };
+                let Some(shader) = shader_manager.get_shader(name) else { continue };
+                let compiled: Vec<&shader_manager::CompiledShader> = shader.compiled.iter()
+                    .filter(|((api, _), _)| *api == self.current_api)
+                    .map(|(_, compiled)| compiled)
+                    .collect();
+                renderer.reload_shader(name, &compiled)?;
+            }
+        }
+        
+        Ok(events)
+    }
+    
     /// グラフィックスシステムをシャットダウン
     pub fn shutdown(&mut self) -> Result<(), GraphicsError> {
         if !self.initialized {
@@ -521,6 +665,7 @@ mod tests {
         assert_eq!(format!("{}", GraphicsApi::Metal), "Metal");
         assert_eq!(format!("{}", GraphicsApi::DirectX), "DirectX");
         assert_eq!(format!("{}", GraphicsApi::OpenGL), "OpenGL");
+        assert_eq!(format!("{}", GraphicsApi::Wgpu), "wgpu");
         assert_eq!(format!("{}", GraphicsApi::Software), "Software");
     }
     
@@ -530,7 +675,19 @@ mod tests {
         assert_eq!(manager.initialized, false);
         assert_eq!(manager.current_api, GraphicsApi::Vulkan);
     }
-    
+
+    #[test]
+    fn test_select_apis_order() {
+        let manager = GraphicsManager::new();
+        let available = vec![GraphicsApi::Software, GraphicsApi::Wgpu];
+
+        // 利用できない優先API（Vulkan）は飛ばし、フォールバックの順に並ぶ
+        let apis = manager.select_apis(&available).unwrap();
+        assert_eq!(apis, vec![GraphicsApi::Wgpu, GraphicsApi::Software]);
+
+        assert!(manager.select_apis(&[]).is_err());
+    }
+
     #[test]
     fn test_graphics_error_display() {
         let err = GraphicsError::Initialization("初期化失敗".to_string());
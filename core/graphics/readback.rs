@@ -0,0 +1,159 @@
+// LumosDesktop フレームバッファ読み出し
+// レンダーターゲットから読み出した画素データとPNG入出力
+
+use std::future::Future;
+use std::io::Cursor;
+use std::path::Path;
+use std::pin::Pin;
+
+use image::{ImageFormat, RgbaImage};
+use image::imageops::FilterType;
+
+use super::GraphicsError;
+
+/// レンダーターゲットの読み出しを待つフューチャー
+///
+/// レンダラーのロックを保持しないため、ロックを解放してから `await` できます。
+pub type ReadbackFuture = Pin<Box<dyn Future<Output = Result<PixelData, GraphicsError>> + Send>>;
+
+/// 読み出した画素データ（RGBA8、行優先・上から下）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PixelData {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl PixelData {
+    pub fn new(width: u32, height: u32, pixels: Vec<u8>) -> Result<Self, GraphicsError> {
+        if pixels.len() != (width as usize) * (height as usize) * 4 {
+            return Err(GraphicsError::Resource(format!(
+                "画素データのサイズが一致しません: {} バイト ({}x{})", pixels.len(), width, height
+            )));
+        }
+        Ok(Self { width, height, pixels })
+    }
+
+    /// 指定位置の画素を取得
+    pub fn pixel(&self, x: u32, y: u32) -> Option<[u8; 4]> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let i = ((y * self.width + x) * 4) as usize;
+        Some([self.pixels[i], self.pixels[i + 1], self.pixels[i + 2], self.pixels[i + 3]])
+    }
+
+    fn to_image(&self) -> Result<RgbaImage, GraphicsError> {
+        RgbaImage::from_raw(self.width, self.height, self.pixels.clone()).ok_or_else(|| {
+            GraphicsError::Resource("画素データから画像を作成できません".to_string())
+        })
+    }
+
+    /// PNGとしてエンコード
+    pub fn encode_png(&self) -> Result<Vec<u8>, GraphicsError> {
+        let mut bytes = Vec::new();
+        self.to_image()?
+            .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+            .map_err(|e| GraphicsError::Resource(format!("PNGのエンコードに失敗しました: {}", e)))?;
+        Ok(bytes)
+    }
+
+    /// PNGファイルとして保存
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> Result<(), GraphicsError> {
+        self.to_image()?
+            .save_with_format(path.as_ref(), ImageFormat::Png)
+            .map_err(|e| GraphicsError::Resource(format!(
+                "PNGの保存に失敗しました: {} ({})", path.as_ref().display(), e
+            )))
+    }
+
+    /// PNGファイルを読み込む
+    pub fn load_png<P: AsRef<Path>>(path: P) -> Result<Self, GraphicsError> {
+        let image = image::open(path.as_ref())
+            .map_err(|e| GraphicsError::Resource(format!(
+                "画像の読み込みに失敗しました: {} ({})", path.as_ref().display(), e
+            )))?
+            .to_rgba8();
+        let (width, height) = image.dimensions();
+        Ok(Self { width, height, pixels: image.into_raw() })
+    }
+
+    /// 縦横比を保って指定サイズに収まるよう縮小（ワークスペース切り替えのサムネイルなど）
+    pub fn thumbnail(&self, max_width: u32, max_height: u32) -> Result<Self, GraphicsError> {
+        if self.width <= max_width && self.height <= max_height {
+            return Ok(self.clone());
+        }
+
+        let scale = (max_width as f64 / self.width as f64).min(max_height as f64 / self.height as f64);
+        let width = ((self.width as f64 * scale).round() as u32).max(1);
+        let height = ((self.height as f64 * scale).round() as u32).max(1);
+        let resized = image::imageops::resize(&self.to_image()?, width, height, FilterType::Triangle);
+        Ok(Self { width, height, pixels: resized.into_raw() })
+    }
+
+    /// 1チャンネルでも許容誤差を超えて異なる画素の数を数える（参照画像テスト用）
+    ///
+    /// サイズが異なる場合は全画素が異なるものとして扱います。
+    pub fn count_mismatches(&self, other: &PixelData, tolerance: u8) -> usize {
+        if self.width != other.width || self.height != other.height {
+            return (self.width * self.height).max(other.width * other.height) as usize;
+        }
+
+        self.pixels
+            .chunks_exact(4)
+            .zip(other.pixels.chunks_exact(4))
+            .filter(|(a, b)| a.iter().zip(b.iter()).any(|(a, b)| a.abs_diff(*b) > tolerance))
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checker(width: u32, height: u32) -> PixelData {
+        let pixels = (0..width * height)
+            .flat_map(|i| if (i % width + i / width).is_multiple_of(2) { [255, 255, 255, 255] } else { [0, 0, 0, 255] })
+            .collect();
+        PixelData::new(width, height, pixels).unwrap()
+    }
+
+    #[test]
+    fn test_png_round_trip() {
+        let data = checker(5, 3);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("checker.png");
+
+        data.save_png(&path).unwrap();
+        let loaded = PixelData::load_png(&path).unwrap();
+        assert_eq!(loaded, data);
+        assert_eq!(loaded.count_mismatches(&data, 0), 0);
+
+        let encoded = data.encode_png().unwrap();
+        assert_eq!(&encoded[1..4], b"PNG");
+    }
+
+    #[test]
+    fn test_thumbnail_keeps_aspect_ratio() {
+        let data = checker(64, 32);
+        let thumbnail = data.thumbnail(16, 16).unwrap();
+        assert_eq!((thumbnail.width, thumbnail.height), (16, 8));
+        assert_eq!(thumbnail.pixels.len(), 16 * 8 * 4);
+
+        // 小さい画像はそのまま
+        assert_eq!(data.thumbnail(128, 128).unwrap(), data);
+    }
+
+    #[test]
+    fn test_count_mismatches() {
+        let a = checker(4, 4);
+        let mut b = a.clone();
+        b.pixels[0] = 250;
+        b.pixels[5] = 100;
+
+        assert_eq!(a.count_mismatches(&b, 5), 1);
+        assert_eq!(a.count_mismatches(&b, 0), 2);
+        assert_eq!(a.count_mismatches(&checker(2, 2), 0), 16);
+        assert!(PixelData::new(2, 2, vec![0; 3]).is_err());
+    }
+}
@@ -5,8 +5,12 @@ use std::collections::HashMap;
 use std::fmt;
 use std::sync::Arc;
 
+use serde::{Deserialize, Serialize};
+
 use super::GraphicsConfig;
 use super::GraphicsError;
+use super::readback::ReadbackFuture;
+use super::shader_manager::CompiledShader;
 
 /// 頂点属性フォーマット
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -303,7 +307,7 @@ pub enum BufferTarget {
 }
 
 /// シェーダーステージ
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum ShaderStage {
     Vertex,
     Fragment,
@@ -319,6 +323,62 @@ pub trait RenderTarget: fmt::Debug {
     fn height(&self) -> u32;
     fn format(&self) -> TextureFormat;
     fn samples(&self) -> u8;
+
+    /// 描画先テクスチャの名前（スワップチェーンなど名前を持たない場合は `None`）
+    fn texture_name(&self) -> Option<&str> {
+        None
+    }
+}
+
+/// 名前付きテクスチャに描画するオフスクリーンレンダーターゲット
+#[derive(Debug, Clone)]
+pub struct OffscreenTarget {
+    name: String,
+    width: u32,
+    height: u32,
+    format: TextureFormat,
+}
+
+impl OffscreenTarget {
+    pub fn new(name: &str, width: u32, height: u32) -> Self {
+        Self {
+            name: name.to_string(),
+            width,
+            height,
+            format: TextureFormat::R8G8B8A8,
+        }
+    }
+
+    pub fn with_format(mut self, format: TextureFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl RenderTarget for OffscreenTarget {
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn format(&self) -> TextureFormat {
+        self.format
+    }
+
+    fn samples(&self) -> u8 {
+        1
+    }
+
+    fn texture_name(&self) -> Option<&str> {
+        Some(&self.name)
+    }
 }
 
 /// レンダーパス
@@ -376,13 +436,60 @@ pub trait Renderer: fmt::Debug + Send + Sync {
     
     /// テクスチャを作成
     fn create_texture(&mut self, name: &str, width: u32, height: u32, format: TextureFormat, data: Option<&[u8]>) -> Result<(), GraphicsError>;
-    
+
+    /// ミップマップ付きのテクスチャを作成（`levels` は基本レベルから順に並ぶ）
+    ///
+    /// ミップマップに対応していないバックエンドでは基本レベルのみを使用します。
+    fn create_texture_with_mips(&mut self, name: &str, width: u32, height: u32, format: TextureFormat, levels: &[&[u8]]) -> Result<(), GraphicsError> {
+        self.create_texture(name, width, height, format, levels.first().copied())
+    }
+
     /// バッファを作成
     fn create_buffer(&mut self, name: &str, target: BufferTarget, usage: BufferUsage, data: Option<&[u8]>, size: usize) -> Result<(), GraphicsError>;
+
+    /// テクスチャのGPUリソースを解放（存在しない場合は何もしない）
+    fn destroy_texture(&mut self, name: &str) -> Result<(), GraphicsError> {
+        let _ = name;
+        Ok(())
+    }
+    
+    /// バッファのGPUリソースを解放（存在しない場合は何もしない）
+    fn destroy_buffer(&mut self, name: &str) -> Result<(), GraphicsError> {
+        let _ = name;
+        Ok(())
+    }
+    
+    /// シェーダーが再読み込みされたときに呼ばれる（`compiled` は現在のAPI向けの全ステージ）
+    ///
+    /// 古いシェーダーで作成したパイプラインを破棄し、次の描画で作り直します。
+    fn reload_shader(&mut self, name: &str, compiled: &[&CompiledShader]) -> Result<(), GraphicsError> {
+        let _ = (name, compiled);
+        Ok(())
+    }
     
     /// デバイス情報を取得
     fn get_device_info(&self) -> HashMap<String, String>;
     
     /// 機能とリミットを取得
     fn get_capabilities(&self) -> HashMap<String, String>;
+    
+    /// オフスクリーンレンダーターゲットを作成
+    ///
+    /// 作成したターゲットは同じ名前のテクスチャとしてサンプリングや読み出しができます。
+    fn create_render_target(&mut self, name: &str, width: u32, height: u32, format: TextureFormat) -> Result<Box<dyn RenderTarget>, GraphicsError> {
+        let _ = (name, width, height, format);
+        Err(GraphicsError::UnsupportedApi(format!(
+            "{} はオフスクリーンレンダーターゲットをサポートしていません", self.name()
+        )))
+    }
+    
+    /// レンダーターゲットの画素をRGBA8で非同期に読み出す
+    ///
+    /// 読み出しは呼び出し時点までに送信されたコマンドの結果を対象とします。
+    fn read_render_target(&self, name: &str) -> ReadbackFuture {
+        let error = GraphicsError::UnsupportedApi(format!(
+            "{} はレンダーターゲットの読み出しをサポートしていません ({})", self.name(), name
+        ));
+        Box::pin(std::future::ready(Err(error)))
+    }
 } 
\ No newline at end of file
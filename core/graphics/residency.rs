@@ -0,0 +1,415 @@
+// LumosDesktop リソース常駐管理
+// カテゴリ別のメモリ予算、参照カウント、LRUによる退避候補の選択
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use super::resource_manager::ResourceHandle;
+
+const MIB: usize = 1024 * 1024;
+
+/// リソースの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ResourceCategory {
+    Texture,
+    Buffer,
+    /// メッシュとその頂点・インデックスバッファ
+    Mesh,
+}
+
+/// カテゴリ別のGPUメモリ予算（バイト、0は無制限）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryBudget {
+    pub textures: usize,
+    pub buffers: usize,
+    pub meshes: usize,
+}
+
+impl MemoryBudget {
+    /// 予算なし
+    pub fn unlimited() -> Self {
+        Self { textures: 0, buffers: 0, meshes: 0 }
+    }
+
+    pub fn get(&self, category: ResourceCategory) -> usize {
+        match category {
+            ResourceCategory::Texture => self.textures,
+            ResourceCategory::Buffer => self.buffers,
+            ResourceCategory::Mesh => self.meshes,
+        }
+    }
+}
+
+impl Default for MemoryBudget {
+    fn default() -> Self {
+        Self {
+            textures: 512 * MIB,
+            buffers: 128 * MIB,
+            meshes: 128 * MIB,
+        }
+    }
+}
+
+/// 参照カウント付きリソースハンドル
+///
+/// 一つでも保持されている間は、対応するリソースが予算超過時に退避されません。
+#[derive(Debug, Clone)]
+pub struct ResourceRef {
+    handle: ResourceHandle,
+    _counter: Arc<()>,
+}
+
+impl ResourceRef {
+    pub fn handle(&self) -> ResourceHandle {
+        self.handle
+    }
+}
+
+/// カテゴリごとのメモリ統計
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CategoryStats {
+    /// GPUに常駐しているバイト数
+    pub resident_bytes: usize,
+    /// 予算（0は無制限）
+    pub budget_bytes: usize,
+    /// 常駐しているリソース数
+    pub resident_count: usize,
+    /// 退避中のリソース数
+    pub evicted_count: usize,
+    /// 参照されているリソース数
+    pub referenced_count: usize,
+    /// 累計の退避回数
+    pub evictions: u64,
+    /// 累計の再読み込み回数
+    pub reloads: u64,
+}
+
+/// リソースマネージャーのメモリ統計
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResourceMemoryStats {
+    pub textures: CategoryStats,
+    pub buffers: CategoryStats,
+    pub meshes: CategoryStats,
+}
+
+impl ResourceMemoryStats {
+    pub fn get(&self, category: ResourceCategory) -> &CategoryStats {
+        match category {
+            ResourceCategory::Texture => &self.textures,
+            ResourceCategory::Buffer => &self.buffers,
+            ResourceCategory::Mesh => &self.meshes,
+        }
+    }
+
+    fn get_mut(&mut self, category: ResourceCategory) -> &mut CategoryStats {
+        match category {
+            ResourceCategory::Texture => &mut self.textures,
+            ResourceCategory::Buffer => &mut self.buffers,
+            ResourceCategory::Mesh => &mut self.meshes,
+        }
+    }
+
+    /// 全カテゴリの常駐バイト数
+    pub fn total_resident_bytes(&self) -> usize {
+        self.textures.resident_bytes + self.buffers.resident_bytes + self.meshes.resident_bytes
+    }
+
+    /// ハードウェアモニターのグラフ用に `カテゴリ.項目` をキーとした値へ変換
+    pub fn to_metrics(&self) -> HashMap<String, f64> {
+        let mut metrics = HashMap::new();
+        for (prefix, stats) in [("textures", &self.textures), ("buffers", &self.buffers), ("meshes", &self.meshes)] {
+            metrics.insert(format!("{}.resident_bytes", prefix), stats.resident_bytes as f64);
+            metrics.insert(format!("{}.budget_bytes", prefix), stats.budget_bytes as f64);
+            metrics.insert(format!("{}.resident_count", prefix), stats.resident_count as f64);
+            metrics.insert(format!("{}.evicted_count", prefix), stats.evicted_count as f64);
+            metrics.insert(format!("{}.evictions", prefix), stats.evictions as f64);
+            metrics.insert(format!("{}.reloads", prefix), stats.reloads as f64);
+        }
+        metrics.insert("total.resident_bytes".to_string(), self.total_resident_bytes() as f64);
+        metrics
+    }
+}
+
+#[derive(Debug)]
+struct ResidencyEntry {
+    category: ResourceCategory,
+    size: usize,
+    resident: bool,
+    last_used: u64,
+    refs: Arc<()>,
+    /// このリソースを所有するリソース（メッシュのバッファなど）
+    owner: Option<String>,
+}
+
+impl ResidencyEntry {
+    fn ref_count(&self) -> usize {
+        Arc::strong_count(&self.refs) - 1
+    }
+}
+
+/// リソースの常駐状態を追跡し、予算超過時の退避候補を選ぶ
+///
+/// 実際のGPUリソースの解放と再作成は呼び出し側が行います。
+#[derive(Debug)]
+pub struct ResidencyTracker {
+    budget: MemoryBudget,
+    entries: HashMap<String, ResidencyEntry>,
+    clock: u64,
+    evictions: HashMap<ResourceCategory, u64>,
+    reloads: HashMap<ResourceCategory, u64>,
+}
+
+impl ResidencyTracker {
+    pub fn new(budget: MemoryBudget) -> Self {
+        Self {
+            budget,
+            entries: HashMap::new(),
+            clock: 0,
+            evictions: HashMap::new(),
+            reloads: HashMap::new(),
+        }
+    }
+
+    pub fn budget(&self) -> MemoryBudget {
+        self.budget
+    }
+
+    pub fn set_budget(&mut self, budget: MemoryBudget) {
+        self.budget = budget;
+    }
+
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    /// 常駐しているリソースとして登録（同名のリソースは置き換え、参照は引き継ぐ）
+    pub fn track(&mut self, name: &str, category: ResourceCategory, size: usize, owner: Option<&str>) {
+        let last_used = self.tick();
+        let refs = self.entries.remove(name).map(|entry| entry.refs).unwrap_or_default();
+        self.entries.insert(name.to_string(), ResidencyEntry {
+            category,
+            size,
+            resident: true,
+            last_used,
+            refs,
+            owner: owner.map(str::to_string),
+        });
+    }
+
+    pub fn untrack(&mut self, name: &str) {
+        self.entries.remove(name);
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// 使用されたことを記録
+    pub fn touch(&mut self, name: &str) {
+        let now = self.tick();
+        if let Some(entry) = self.entries.get_mut(name) {
+            entry.last_used = now;
+        }
+    }
+
+    /// 参照カウント付きハンドルを発行
+    pub fn acquire(&mut self, name: &str, handle: ResourceHandle) -> Option<ResourceRef> {
+        let now = self.tick();
+        let entry = self.entries.get_mut(name)?;
+        entry.last_used = now;
+        Some(ResourceRef { handle, _counter: Arc::clone(&entry.refs) })
+    }
+
+    pub fn ref_count(&self, name: &str) -> usize {
+        self.entries.get(name).map_or(0, ResidencyEntry::ref_count)
+    }
+
+    /// 自身または所有者が参照されているか
+    pub fn is_referenced(&self, name: &str) -> bool {
+        self.entries.get(name).is_some_and(|entry| {
+            entry.ref_count() > 0 || entry.owner.as_deref().is_some_and(|owner| self.is_referenced(owner))
+        })
+    }
+
+    pub fn category(&self, name: &str) -> Option<ResourceCategory> {
+        self.entries.get(name).map(|entry| entry.category)
+    }
+
+    pub fn is_resident(&self, name: &str) -> bool {
+        self.entries.get(name).is_some_and(|entry| entry.resident)
+    }
+
+    /// 指定リソースが所有するリソースの名前
+    pub fn owned_by(&self, owner: &str) -> Vec<String> {
+        self.entries.iter()
+            .filter(|(_, entry)| entry.owner.as_deref() == Some(owner))
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    /// 退避したことを記録
+    pub fn mark_evicted(&mut self, name: &str) {
+        if let Some(entry) = self.entries.get_mut(name) {
+            if entry.resident {
+                entry.resident = false;
+                *self.evictions.entry(entry.category).or_default() += 1;
+            }
+        }
+    }
+
+    /// 再読み込みして常駐に戻ったことを記録
+    pub fn mark_reloaded(&mut self, name: &str, size: usize) {
+        let now = self.tick();
+        if let Some(entry) = self.entries.get_mut(name) {
+            entry.resident = true;
+            entry.size = size;
+            entry.last_used = now;
+            *self.reloads.entry(entry.category).or_default() += 1;
+        }
+    }
+
+    /// カテゴリの常駐バイト数
+    pub fn resident_bytes(&self, category: ResourceCategory) -> usize {
+        self.entries.values()
+            .filter(|entry| entry.category == category && entry.resident)
+            .map(|entry| entry.size)
+            .sum()
+    }
+
+    /// 予算内に収めるために退避すべきリソースを、最も長く使われていない順に返す
+    ///
+    /// `reserve` バイトを追加で確保できるように選びます。参照されているリソースは対象外のため、
+    /// 予算を超えたままになる場合があります。
+    pub fn eviction_candidates(&self, category: ResourceCategory, reserve: usize) -> Vec<String> {
+        let budget = self.budget.get(category);
+        if budget == 0 {
+            return Vec::new();
+        }
+
+        let mut used = self.resident_bytes(category) + reserve;
+        if used <= budget {
+            return Vec::new();
+        }
+
+        let mut candidates: Vec<(&String, &ResidencyEntry)> = self.entries.iter()
+            .filter(|(name, entry)| {
+                entry.category == category && entry.resident && entry.size > 0 && !self.is_referenced(name)
+            })
+            .collect();
+        candidates.sort_by_key(|(name, entry)| (entry.last_used, name.as_str()));
+
+        let mut evict = Vec::new();
+        for (name, entry) in candidates {
+            if used <= budget {
+                break;
+            }
+            used -= entry.size;
+            evict.push(name.clone());
+        }
+        evict
+    }
+
+    /// 現在のメモリ統計
+    pub fn stats(&self) -> ResourceMemoryStats {
+        let mut stats = ResourceMemoryStats::default();
+        for category in [ResourceCategory::Texture, ResourceCategory::Buffer, ResourceCategory::Mesh] {
+            let category_stats = stats.get_mut(category);
+            category_stats.budget_bytes = self.budget.get(category);
+            category_stats.evictions = self.evictions.get(&category).copied().unwrap_or(0);
+            category_stats.reloads = self.reloads.get(&category).copied().unwrap_or(0);
+        }
+        for entry in self.entries.values() {
+            let category_stats = stats.get_mut(entry.category);
+            if entry.resident {
+                category_stats.resident_bytes += entry.size;
+                category_stats.resident_count += 1;
+            } else {
+                category_stats.evicted_count += 1;
+            }
+            if entry.ref_count() > 0 {
+                category_stats.referenced_count += 1;
+            }
+        }
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn budget(textures: usize) -> MemoryBudget {
+        MemoryBudget { textures, ..MemoryBudget::unlimited() }
+    }
+
+    #[test]
+    fn test_lru_eviction_order() {
+        let mut tracker = ResidencyTracker::new(budget(300));
+        tracker.track("a", ResourceCategory::Texture, 100, None);
+        tracker.track("b", ResourceCategory::Texture, 100, None);
+        tracker.track("c", ResourceCategory::Texture, 100, None);
+        assert!(tracker.eviction_candidates(ResourceCategory::Texture, 0).is_empty());
+
+        // aを使うとbが最も古くなる
+        tracker.touch("a");
+        assert_eq!(tracker.eviction_candidates(ResourceCategory::Texture, 100), vec!["b"]);
+        assert_eq!(tracker.eviction_candidates(ResourceCategory::Texture, 150), vec!["b", "c"]);
+
+        // 他のカテゴリの予算には影響しない
+        tracker.track("vb", ResourceCategory::Buffer, 1000, None);
+        assert!(tracker.eviction_candidates(ResourceCategory::Buffer, 0).is_empty());
+        assert_eq!(tracker.resident_bytes(ResourceCategory::Texture), 300);
+    }
+
+    #[test]
+    fn test_referenced_resources_are_kept() {
+        let mut tracker = ResidencyTracker::new(budget(100));
+        tracker.track("wallpaper", ResourceCategory::Texture, 100, None);
+        tracker.track("icon", ResourceCategory::Texture, 50, None);
+
+        let reference = tracker.acquire("wallpaper", ResourceHandle::new(1)).unwrap();
+        let second = reference.clone();
+        assert_eq!(tracker.ref_count("wallpaper"), 2);
+        assert_eq!(tracker.eviction_candidates(ResourceCategory::Texture, 0), vec!["icon"]);
+
+        drop(reference);
+        drop(second);
+        assert_eq!(tracker.ref_count("wallpaper"), 0);
+        assert_eq!(tracker.eviction_candidates(ResourceCategory::Texture, 60), vec!["icon", "wallpaper"]);
+
+        // 置き換えても参照は引き継がれる
+        let reference = tracker.acquire("icon", ResourceHandle::new(2)).unwrap();
+        tracker.track("icon", ResourceCategory::Texture, 60, None);
+        assert_eq!(tracker.ref_count("icon"), 1);
+        assert_eq!(reference.handle(), ResourceHandle::new(2));
+    }
+
+    #[test]
+    fn test_owner_reference_and_stats() {
+        let mut tracker = ResidencyTracker::new(MemoryBudget { meshes: 10, ..MemoryBudget::unlimited() });
+        tracker.track("quad", ResourceCategory::Mesh, 0, None);
+        tracker.track("quad_vb", ResourceCategory::Mesh, 64, Some("quad"));
+        assert_eq!(tracker.owned_by("quad"), vec!["quad_vb"]);
+
+        let reference = tracker.acquire("quad", ResourceHandle::new(1)).unwrap();
+        assert!(tracker.is_referenced("quad_vb"));
+        assert!(tracker.eviction_candidates(ResourceCategory::Mesh, 0).is_empty());
+        drop(reference);
+        assert_eq!(tracker.eviction_candidates(ResourceCategory::Mesh, 0), vec!["quad_vb"]);
+
+        tracker.mark_evicted("quad_vb");
+        tracker.mark_evicted("quad_vb");
+        let stats = tracker.stats();
+        assert_eq!(stats.meshes.resident_bytes, 0);
+        assert_eq!((stats.meshes.resident_count, stats.meshes.evicted_count), (1, 1));
+        assert_eq!(stats.meshes.evictions, 1);
+
+        tracker.mark_reloaded("quad_vb", 64);
+        let stats = tracker.stats();
+        assert_eq!(stats.meshes.resident_bytes, 64);
+        assert_eq!(stats.meshes.reloads, 1);
+        assert_eq!(stats.to_metrics()["meshes.resident_bytes"], 64.0);
+        assert_eq!(stats.to_metrics()["total.resident_bytes"], 64.0);
+    }
+}
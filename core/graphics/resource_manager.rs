@@ -10,7 +10,11 @@ use std::sync::{Arc, Mutex};
 use std::time::SystemTime;
 
 use super::{GraphicsApi, GraphicsError};
+use super::async_loader::{AsyncLoader, DecodedLoad, LoadPriority, PendingLoad};
 use super::renderer::{Renderer, TextureFormat, BufferTarget, BufferUsage};
+use super::residency::{MemoryBudget, ResidencyTracker, ResourceCategory, ResourceMemoryStats, ResourceRef};
+use super::texture_container;
+use super::texture_loader::{self, ColorSpace, DecodedTexture, TextureLoadOptions};
 
 /// リソースハンドル
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -96,6 +100,8 @@ pub struct TextureResource {
     pub format: TextureFormat,
     pub mip_levels: u32,
     pub array_layers: u32,
+    pub color_space: ColorSpace,
+    /// テクスチャデータ（ミップマップがある場合は基本レベルから順に連結）
     pub data: Option<Vec<u8>>,
 }
 
@@ -116,6 +122,7 @@ impl TextureResource {
             format,
             mip_levels: 1,
             array_layers: 1,
+            color_space: ColorSpace::default(),
             data: None,
         }
     }
@@ -137,6 +144,7 @@ impl TextureResource {
             format,
             mip_levels: 1,
             array_layers: 1,
+            color_space: ColorSpace::default(),
             data: Some(data),
         }
     }
@@ -247,6 +255,10 @@ struct ResourceManagerInner {
     buffers: HashMap<String, BufferResource>,
     meshes: HashMap<String, MeshResource>,
     handles: HashMap<ResourceHandle, String>,
+    /// ファイルから読み込んだテクスチャの読み込みオプション（再読み込み用）
+    texture_options: HashMap<String, TextureLoadOptions>,
+    /// 退避すると復元できないため、マネージャー自身が参照を保持するリソース
+    pinned: HashMap<String, ResourceRef>,
 }
 
 impl ResourceManagerInner {
@@ -257,6 +269,8 @@ impl ResourceManagerInner {
             buffers: HashMap::new(),
             meshes: HashMap::new(),
             handles: HashMap::new(),
+            texture_options: HashMap::new(),
+            pinned: HashMap::new(),
         }
     }
     
@@ -266,6 +280,95 @@ impl ResourceManagerInner {
         self.handles.insert(handle, name.to_string());
         handle
     }
+    
+    fn remove_handles(&mut self, name: &str) {
+        self.handles.retain(|_, n| n != name);
+    }
+}
+
+/// テクスチャがGPU上で使用するおおよそのバイト数
+fn texture_memory_size(width: u32, height: u32, format: TextureFormat) -> usize {
+    let pixels = width as usize * height as usize;
+    let blocks = width.div_ceil(4) as usize * height.div_ceil(4) as usize;
+    match format {
+        TextureFormat::R8 => pixels,
+        TextureFormat::R8G8 | TextureFormat::R16F | TextureFormat::Depth16 => pixels * 2,
+        TextureFormat::R8G8B8 => pixels * 3,
+        TextureFormat::R16G16B16F => pixels * 6,
+        TextureFormat::R16G16B16A16F | TextureFormat::R32G32F | TextureFormat::Depth32FStencil8 => pixels * 8,
+        TextureFormat::R32G32B32F => pixels * 12,
+        TextureFormat::R32G32B32A32F => pixels * 16,
+        TextureFormat::BC1 => blocks * 8,
+        TextureFormat::BC2 | TextureFormat::BC3 | TextureFormat::BC7 => blocks * 16,
+        _ => pixels * 4,
+    }
+}
+
+/// テクスチャのデコード設定（デコードをレンダースレッド以外で行うため、マネージャーから切り離して渡す）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct DecodeSettings {
+    pub max_texture_size: u32,
+    /// BC圧縮テクスチャをそのまま使用するか（falseの場合はCPUで展開）
+    pub native_compression: bool,
+}
+
+/// テクスチャファイルを読み込んでデコードする
+pub(crate) fn decode_texture_file(
+    path: &Path,
+    options: &TextureLoadOptions,
+    settings: DecodeSettings,
+) -> Result<DecodedTexture, GraphicsError> {
+    let max_texture_size = settings.max_texture_size;
+    
+    // ファイルの拡張子を取得
+    let extension = path.extension()
+        .and_then(|s| s.to_str())
+        .ok_or_else(|| GraphicsError::Resource("テクスチャファイルに拡張子がありません".to_string()))?
+        .to_lowercase();
+    
+    // ファイルを読み込む
+    let file_data = fs::read(path)
+        .map_err(|e| GraphicsError::Resource(format!("テクスチャファイルの読み込みに失敗: {}", e)))?;
+    
+    // 画像をデコードし、メモリ内にロード
+    let decoded = match extension.as_str() {
+        "png" | "jpg" | "jpeg" | "bmp" | "tga" => {
+            texture_loader::decode_image(
+                &file_data,
+                Some(&extension),
+                max_texture_size,
+                options,
+            ).map_err(|e| GraphicsError::Resource(format!("{}: {}", path.display(), e)))?
+        }
+        "ktx" | "ktx2" | "dds" => {
+            // 圧縮テクスチャフォーマット
+            let mut container = texture_container::parse(&file_data, options.color_space)
+                .map_err(|e| GraphicsError::Resource(format!("{}: {}", path.display(), e)))?;
+            if options.downscale_to_fit {
+                container = container.fit_within(max_texture_size)
+                    .map_err(|e| GraphicsError::Resource(format!("{}: {}", path.display(), e)))?;
+            }
+            
+            // バックエンドがBC圧縮に対応していない場合はCPUで展開する
+            container.into_texture(settings.native_compression)
+                .map_err(|e| GraphicsError::Resource(format!("{}: {}", path.display(), e)))?
+        }
+        _ => {
+            return Err(GraphicsError::Resource(format!(
+                "サポートされていないテクスチャフォーマット: {}", extension
+            )));
+        }
+    };
+    
+    // サイズが最大テクスチャサイズを超えていないか確認
+    if decoded.width > max_texture_size || decoded.height > max_texture_size {
+        return Err(GraphicsError::Resource(format!(
+            "テクスチャサイズが最大許容サイズを超えています: {}x{} (最大: {}x{})",
+            decoded.width, decoded.height, max_texture_size, max_texture_size
+        )));
+    }
+    
+    Ok(decoded)
 }
 
 /// リソースマネージャー
@@ -276,6 +379,8 @@ pub struct ResourceManager {
     search_paths: Vec<PathBuf>,
     max_texture_size: u32,
     texture_compression: bool,
+    residency: ResidencyTracker,
+    loader: Option<AsyncLoader>,
 }
 
 impl fmt::Debug for ResourceManager {
@@ -287,6 +392,8 @@ impl fmt::Debug for ResourceManager {
             .field("current_api", &self.current_api)
             .field("max_texture_size", &self.max_texture_size)
             .field("texture_compression", &self.texture_compression)
+            .field("memory_budget", &self.residency.budget())
+            .field("pending_loads", &self.pending_load_count())
             .finish()
     }
 }
@@ -306,6 +413,8 @@ impl ResourceManager {
             search_paths: vec![PathBuf::from("assets")],
             max_texture_size,
             texture_compression,
+            residency: ResidencyTracker::new(MemoryBudget::default()),
+            loader: None,
         })
     }
     
@@ -314,8 +423,224 @@ impl ResourceManager {
         self.search_paths.push(PathBuf::from(path.as_ref()));
     }
     
+    /// メモリ予算を取得
+    pub fn memory_budget(&self) -> MemoryBudget {
+        self.residency.budget()
+    }
+    
+    /// メモリ予算を設定し、超過しているカテゴリは直ちに退避する
+    pub fn set_memory_budget(&mut self, budget: MemoryBudget) -> Result<(), GraphicsError> {
+        self.residency.set_budget(budget);
+        self.trim()
+    }
+    
+    /// メモリ使用量の統計を取得
+    pub fn memory_stats(&self) -> ResourceMemoryStats {
+        self.residency.stats()
+    }
+    
+    /// すべてのカテゴリを予算内に収める
+    pub fn trim(&mut self) -> Result<(), GraphicsError> {
+        for category in [ResourceCategory::Texture, ResourceCategory::Buffer, ResourceCategory::Mesh] {
+            self.make_room(category, 0)?;
+        }
+        Ok(())
+    }
+    
+    /// 参照カウント付きハンドルを取得
+    ///
+    /// 退避されているリソースはこの時点で再読み込みされます。
+    pub fn acquire(&mut self, handle: ResourceHandle) -> Result<ResourceRef, GraphicsError> {
+        let name = self.get_resource_name(handle)
+            .map(str::to_string)
+            .ok_or_else(|| GraphicsError::Resource(format!("無効なリソースハンドル: {:?}", handle)))?;
+        self.ensure_resident(&name)?;
+        self.residency.acquire(&name, handle)
+            .ok_or_else(|| GraphicsError::Resource(format!("リソースが見つかりません: {}", name)))
+    }
+    
+    /// リソースが使用されたことを記録（退避されている場合は再読み込み）
+    pub fn touch(&mut self, handle: ResourceHandle) -> Result<(), GraphicsError> {
+        let name = self.get_resource_name(handle)
+            .map(str::to_string)
+            .ok_or_else(|| GraphicsError::Resource(format!("無効なリソースハンドル: {:?}", handle)))?;
+        self.ensure_resident(&name)
+    }
+    
+    /// 参照カウントを取得
+    pub fn ref_count(&self, handle: ResourceHandle) -> usize {
+        self.get_resource_name(handle).map_or(0, |name| self.residency.ref_count(name))
+    }
+    
+    /// リソースがGPUに常駐しているか
+    pub fn is_resident(&self, handle: ResourceHandle) -> bool {
+        self.get_resource_name(handle).is_some_and(|name| self.residency.is_resident(name))
+    }
+    
+    /// 退避できないリソースに参照を保持する
+    fn pin(&mut self, name: &str) {
+        if let Some(reference) = self.residency.acquire(name, ResourceHandle::invalid()) {
+            self.inner.pinned.insert(name.to_string(), reference);
+        }
+    }
+    
+    /// `reserve` バイトを確保できるように、参照されていないリソースを古い順に退避する
+    fn make_room(&mut self, category: ResourceCategory, reserve: usize) -> Result<(), GraphicsError> {
+        for name in self.residency.eviction_candidates(category, reserve) {
+            self.evict(&name)?;
+        }
+        Ok(())
+    }
+    
+    /// GPUリソースを解放する
+    ///
+    /// ファイルから読み込んだテクスチャはメモリ上のデータも破棄し、次に使用する時にファイルから再読み込みします。
+    fn evict(&mut self, name: &str) -> Result<(), GraphicsError> {
+        {
+            let mut renderer = self.renderer.lock()
+                .map_err(|_| GraphicsError::Resource("レンダラーのロックに失敗".to_string()))?;
+            
+            if let Some(texture) = self.inner.textures.get_mut(name) {
+                renderer.destroy_texture(name)?;
+                if texture.info.path.is_some() {
+                    texture.data = None;
+                }
+                texture.info.state = ResourceState::Unloaded;
+            } else if let Some(buffer) = self.inner.buffers.get_mut(name) {
+                renderer.destroy_buffer(name)?;
+                buffer.info.state = ResourceState::Unloaded;
+            }
+        }
+        
+        self.residency.mark_evicted(name);
+        Ok(())
+    }
+    
+    /// 退避されているリソースを再読み込みし、使用されたことを記録する
+    fn ensure_resident(&mut self, name: &str) -> Result<(), GraphicsError> {
+        // メッシュは所有するバッファを常駐させる
+        if self.inner.meshes.contains_key(name) {
+            for buffer in self.residency.owned_by(name) {
+                self.ensure_resident(&buffer)?;
+            }
+        } else if !self.residency.is_resident(name) {
+            if let Some(texture) = self.inner.textures.get(name) {
+                // 非同期読み込み中のテクスチャは完了を待つ
+                if texture.info.state != ResourceState::Loading {
+                    self.reload_texture(name)?;
+                }
+            } else if self.inner.buffers.contains_key(name) {
+                self.reload_buffer(name)?;
+            }
+        }
+        
+        self.residency.touch(name);
+        Ok(())
+    }
+    
+    fn reload_texture(&mut self, name: &str) -> Result<(), GraphicsError> {
+        let texture = self.inner.textures.get(name)
+            .ok_or_else(|| GraphicsError::Resource(format!("テクスチャが見つかりません: {}", name)))?;
+        let path = texture.info.path.clone();
+        let (width, height, format, data) = (texture.width, texture.height, texture.format, texture.data.clone());
+        
+        let result = match path {
+            Some(path) => {
+                let options = self.inner.texture_options.get(name).cloned().unwrap_or_default();
+                self.decode_settings()
+                    .and_then(|settings| decode_texture_file(&path, &options, settings))
+                    .and_then(|decoded| {
+                        self.upload_texture(name, &decoded)?;
+                        Ok((decoded.size(), Some(decoded.into_data())))
+                    })
+            }
+            None => {
+                let size = data.as_ref().map_or_else(|| texture_memory_size(width, height, format), Vec::len);
+                self.make_room(ResourceCategory::Texture, size).and_then(|_| {
+                    let mut renderer = self.renderer.lock()
+                        .map_err(|_| GraphicsError::Resource("レンダラーのロックに失敗".to_string()))?;
+                    renderer.create_texture(name, width, height, format, data.as_deref())?;
+                    Ok((size, data))
+                })
+            }
+        };
+        
+        let texture = self.inner.textures.get_mut(name)
+            .ok_or_else(|| GraphicsError::Resource(format!("テクスチャが見つかりません: {}", name)))?;
+        match result {
+            Ok((size, data)) => {
+                texture.data = data;
+                texture.info.state = ResourceState::Loaded;
+                self.residency.mark_reloaded(name, size);
+                Ok(())
+            }
+            Err(e) => {
+                texture.info.state = ResourceState::Failed;
+                Err(e)
+            }
+        }
+    }
+    
+    fn reload_buffer(&mut self, name: &str) -> Result<(), GraphicsError> {
+        let buffer = self.inner.buffers.get(name)
+            .ok_or_else(|| GraphicsError::Resource(format!("バッファが見つかりません: {}", name)))?;
+        let (target, usage, size, data) = (buffer.target, buffer.usage, buffer.size, buffer.data.clone());
+        let category = self.residency.category(name).unwrap_or(ResourceCategory::Buffer);
+        
+        self.make_room(category, size)?;
+        {
+            let mut renderer = self.renderer.lock()
+                .map_err(|_| GraphicsError::Resource("レンダラーのロックに失敗".to_string()))?;
+            renderer.create_buffer(name, target, usage, data.as_deref(), size)?;
+        }
+        
+        if let Some(buffer) = self.inner.buffers.get_mut(name) {
+            buffer.info.state = ResourceState::Loaded;
+        }
+        self.residency.mark_reloaded(name, size);
+        Ok(())
+    }
+    
     /// テクスチャをファイルから読み込む
     pub fn load_texture<P: AsRef<Path>>(&mut self, path: P, name: Option<&str>) -> Result<ResourceHandle, GraphicsError> {
+        self.load_texture_with_options(path, name, &TextureLoadOptions::default())
+    }
+    
+    /// レンダラーがBC圧縮テクスチャに対応しているか（能力情報に記載がない場合は対応とみなす）
+    fn supports_bc_compression(&self) -> Result<bool, GraphicsError> {
+        let renderer = self.renderer.lock()
+            .map_err(|_| GraphicsError::Resource("レンダラーのロックに失敗".to_string()))?;
+        Ok(renderer.get_capabilities()
+            .get("texture_compression_bc")
+            .is_none_or(|supported| supported == "true"))
+    }
+    
+    /// 現在の設定でのデコード設定
+    fn decode_settings(&self) -> Result<DecodeSettings, GraphicsError> {
+        Ok(DecodeSettings {
+            max_texture_size: self.max_texture_size,
+            native_compression: self.texture_compression && self.supports_bc_compression()?,
+        })
+    }
+    
+    /// 予算を確保してからレンダラーにテクスチャを登録（配列テクスチャは先頭レイヤーのみ）
+    fn upload_texture(&mut self, name: &str, decoded: &DecodedTexture) -> Result<(), GraphicsError> {
+        self.make_room(ResourceCategory::Texture, decoded.size())?;
+        
+        let mut renderer = self.renderer.lock()
+            .map_err(|_| GraphicsError::Resource("レンダラーのロックに失敗".to_string()))?;
+        
+        let level_data: Vec<&[u8]> = (0..decoded.levels.len()).map(|level| decoded.first_layer(level)).collect();
+        renderer.create_texture_with_mips(name, decoded.width, decoded.height, decoded.format, &level_data)
+    }
+    
+    /// フォーマットや色空間、ミップマップ生成を指定してテクスチャをファイルから読み込む
+    pub fn load_texture_with_options<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        name: Option<&str>,
+        options: &TextureLoadOptions,
+    ) -> Result<ResourceHandle, GraphicsError> {
         let path = path.as_ref();
         let texture_name = name.map(|s| s.to_string()).unwrap_or_else(|| {
             path.file_stem()
@@ -324,81 +649,151 @@ impl ResourceManager {
                 .unwrap_or_else(|| "unnamed".to_string())
         });
         
-        // ファイルの拡張子を取得
-        let extension = path.extension()
-            .and_then(|s| s.to_str())
-            .ok_or_else(|| GraphicsError::Resource("テクスチャファイルに拡張子がありません".to_string()))?
-            .to_lowercase();
-        
-        // ファイルを読み込む
-        let file_data = fs::read(path)
-            .map_err(|e| GraphicsError::Resource(format!("テクスチャファイルの読み込みに失敗: {}", e)))?;
-        
-        // 画像をデコードし、メモリ内にロード
-        let (format, width, height, data) = match extension.as_str() {
-            "png" | "jpg" | "jpeg" | "bmp" | "tga" => {
-                // 実際のアプリケーションでは画像デコードライブラリを使用
-                // ここではダミーデータを返す
-                (TextureFormat::R8G8B8A8, 256, 256, file_data)
-            }
-            "ktx" | "dds" => {
-                // 圧縮テクスチャフォーマット
-                // 実際にはKTXやDDSパーサーを使用
-                (TextureFormat::BC3, 256, 256, file_data)
-            }
-            _ => {
-                return Err(GraphicsError::Resource(format!(
-                    "サポートされていないテクスチャフォーマット: {}", extension
-                )));
-            }
-        };
-        
-        // サイズが最大テクスチャサイズを超えていないか確認
-        if width > self.max_texture_size || height > self.max_texture_size {
-            return Err(GraphicsError::Resource(format!(
-                "テクスチャサイズが最大許容サイズを超えています: {}x{} (最大: {}x{})",
-                width, height, self.max_texture_size, self.max_texture_size
-            )));
-        }
+        let decoded = decode_texture_file(path, options, self.decode_settings()?)?;
+        self.upload_texture(&texture_name, &decoded)?;
+        self.register_texture(&texture_name, path, options, decoded);
         
+        Ok(self.inner.generate_handle(&texture_name))
+    }
+    
+    /// デコードしてアップロード済みのテクスチャをリソースとして登録
+    fn register_texture(&mut self, name: &str, path: &Path, options: &TextureLoadOptions, decoded: DecodedTexture) {
         // テクスチャリソースを作成
+        let mut metadata = HashMap::new();
+        if decoded.cubemap {
+            metadata.insert("cubemap".to_string(), "true".to_string());
+        }
+        let (width, height, format) = (decoded.width, decoded.height, decoded.format);
+        let mip_levels = decoded.mip_levels();
+        let array_layers = decoded.array_layers;
+        let color_space = decoded.color_space;
+        let data = decoded.into_data();
         let texture = TextureResource {
             info: ResourceInfo {
-                name: texture_name.clone(),
+                name: name.to_string(),
                 path: Some(path.to_path_buf()),
                 state: ResourceState::Loaded,
                 size: data.len(),
                 last_modified: SystemTime::now(),
-                metadata: HashMap::new(),
+                metadata,
             },
             width,
             height,
             depth: 1,
             format,
-            mip_levels: 1,
-            array_layers: 1,
+            mip_levels,
+            array_layers,
+            color_space,
             data: Some(data),
         };
         
-        // レンダラーにテクスチャを登録
-        {
-            let mut renderer = self.renderer.lock()
-                .map_err(|_| GraphicsError::Resource("レンダラーのロックに失敗".to_string()))?;
-            
-            renderer.create_texture(
-                &texture_name,
-                width,
-                height,
-                format,
-                texture.data.as_deref(),
-            )?;
-        }
-        
         // リソースマネージャーに登録
+        self.residency.track(name, ResourceCategory::Texture, texture.info.size, None);
+        self.inner.pinned.remove(name);
+        self.inner.texture_options.insert(name.to_string(), options.clone());
+        self.inner.textures.insert(name.to_string(), texture);
+    }
+    
+    /// テクスチャをバックグラウンドで読み込む
+    ///
+    /// ハンドルはすぐに有効になり、読み込みが終わるまでリソースの状態は `ResourceState::Loading` になります。
+    /// デコードはワーカースレッドで行い、GPUへのアップロードは `process_pending_loads` を呼び出したスレッドで行います。
+    pub fn load_texture_async<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        name: Option<&str>,
+        options: &TextureLoadOptions,
+        priority: LoadPriority,
+    ) -> Result<PendingLoad, GraphicsError> {
+        let path = path.as_ref();
+        let texture_name = name.map(|s| s.to_string()).unwrap_or_else(|| {
+            path.file_stem()
+                .and_then(|s| s.to_str())
+                .map(|s| s.to_string())
+                .unwrap_or_else(|| "unnamed".to_string())
+        });
+        let settings = self.decode_settings()?;
+        
+        // 読み込み中のプレースホルダーを登録（既存のテクスチャは読み込みが終わるまで使用できる）
+        match self.inner.textures.get_mut(&texture_name) {
+            Some(texture) => texture.info.state = ResourceState::Loading,
+            None => {
+                let mut texture = TextureResource::new(&texture_name, 0, 0, options.format);
+                texture.info.path = Some(path.to_path_buf());
+                texture.info.state = ResourceState::Loading;
+                self.residency.track(&texture_name, ResourceCategory::Texture, 0, None);
+                self.inner.textures.insert(texture_name.clone(), texture);
+            }
+        }
         let handle = self.inner.generate_handle(&texture_name);
-        self.inner.textures.insert(texture_name, texture);
         
-        Ok(handle)
+        if self.loader.is_none() {
+            let workers = std::thread::available_parallelism().map_or(2, |n| n.get());
+            self.loader = Some(AsyncLoader::new(workers)?);
+        }
+        let loader = self.loader.as_mut().expect("ローダーは作成済み");
+        Ok(loader.enqueue(&texture_name, handle, path.to_path_buf(), options.clone(), settings, priority))
+    }
+    
+    /// デコードが終わったテクスチャを最大 `max_uploads` 件GPUにアップロードする
+    ///
+    /// レンダースレッドから毎フレーム呼び出します。アップロードした件数を返します。
+    pub fn process_pending_loads(&mut self, max_uploads: usize) -> usize {
+        let Some(loader) = self.loader.as_mut() else { return 0 };
+        let decoded_loads = loader.take_decoded(max_uploads);
+        let count = decoded_loads.len();
+        
+        for DecodedLoad { name, path, options, handle, result, completion } in decoded_loads {
+            let result = result.and_then(|decoded| {
+                self.upload_texture(&name, &decoded)?;
+                self.register_texture(&name, &path, &options, decoded);
+                Ok(handle)
+            });
+            if result.is_err() {
+                if let Some(texture) = self.inner.textures.get_mut(&name) {
+                    texture.info.state = ResourceState::Failed;
+                }
+            }
+            completion.complete(result);
+        }
+        count
+    }
+    
+    /// 非同期読み込みをキャンセル
+    ///
+    /// 読み込み前から存在したテクスチャはそのまま残り、新しく作成されたプレースホルダーは削除されます。
+    pub fn cancel_load(&mut self, handle: ResourceHandle) -> bool {
+        let Some(name) = self.get_resource_name(handle).map(str::to_string) else { return false };
+        if self.loader.as_mut().and_then(|loader| loader.cancel(&name)).is_none() {
+            return false;
+        }
+        
+        let placeholder = self.inner.textures.get(&name).is_some_and(|texture| texture.data.is_none() && texture.width == 0);
+        if placeholder {
+            self.inner.textures.remove(&name);
+            self.inner.remove_handles(&name);
+            self.forget(&name);
+        } else if let Some(texture) = self.inner.textures.get_mut(&name) {
+            texture.info.state = if self.residency.is_resident(&name) { ResourceState::Loaded } else { ResourceState::Unloaded };
+        }
+        true
+    }
+    
+    /// デコード待ちの非同期読み込みの優先度を変更
+    pub fn set_load_priority(&mut self, handle: ResourceHandle, priority: LoadPriority) -> bool {
+        let Some(name) = self.inner.handles.get(&handle) else { return false };
+        self.loader.as_mut().is_some_and(|loader| loader.set_priority(name, priority))
+    }
+    
+    /// ハンドルのテクスチャが非同期読み込み中か
+    pub fn is_loading(&self, handle: ResourceHandle) -> bool {
+        let Some(name) = self.get_resource_name(handle) else { return false };
+        self.loader.as_ref().is_some_and(|loader| loader.is_loading(name))
+    }
+    
+    /// 完了していない非同期読み込みの数
+    pub fn pending_load_count(&self) -> usize {
+        self.loader.as_ref().map_or(0, AsyncLoader::pending_count)
     }
     
     /// メモリからテクスチャを作成
@@ -426,10 +821,13 @@ impl ResourceManager {
             format,
             mip_levels: 1,
             array_layers: 1,
+            color_space: ColorSpace::default(),
             data: data.map(|d| d.to_vec()),
         };
         
         // レンダラーにテクスチャを登録
+        let memory_size = data.map_or_else(|| texture_memory_size(width, height, format), |d| d.len());
+        self.make_room(ResourceCategory::Texture, memory_size)?;
         {
             let mut renderer = self.renderer.lock()
                 .map_err(|_| GraphicsError::Resource("レンダラーのロックに失敗".to_string()))?;
@@ -437,7 +835,13 @@ impl ResourceManager {
             renderer.create_texture(name, width, height, format, data)?;
         }
         
-        // リソースマネージャーに登録
+        // リソースマネージャーに登録（データがない場合は退避すると内容を復元できない）
+        self.residency.track(name, ResourceCategory::Texture, memory_size, None);
+        self.inner.pinned.remove(name);
+        if data.is_none() {
+            self.pin(name);
+        }
+        self.inner.texture_options.remove(name);
         let handle = self.inner.generate_handle(name);
         self.inner.textures.insert(name.to_string(), texture);
         
@@ -452,6 +856,21 @@ impl ResourceManager {
         usage: BufferUsage,
         data: Option<&[u8]>,
         stride: usize,
+    ) -> Result<ResourceHandle, GraphicsError> {
+        self.create_buffer_in(name, target, usage, data, stride, ResourceCategory::Buffer, None)
+    }
+    
+    /// カテゴリと所有者を指定してバッファを作成
+    #[allow(clippy::too_many_arguments)]
+    fn create_buffer_in(
+        &mut self,
+        name: &str,
+        target: BufferTarget,
+        usage: BufferUsage,
+        data: Option<&[u8]>,
+        stride: usize,
+        category: ResourceCategory,
+        owner: Option<&str>,
     ) -> Result<ResourceHandle, GraphicsError> {
         let size = data.map_or(0, |d| d.len());
         
@@ -473,6 +892,7 @@ impl ResourceManager {
         };
         
         // レンダラーにバッファを登録
+        self.make_room(category, size)?;
         {
             let mut renderer = self.renderer.lock()
                 .map_err(|_| GraphicsError::Resource("レンダラーのロックに失敗".to_string()))?;
@@ -480,7 +900,12 @@ impl ResourceManager {
             renderer.create_buffer(name, target, usage, data, size)?;
         }
         
-        // リソースマネージャーに登録
+        // リソースマネージャーに登録（データがない場合は退避すると内容を復元できない）
+        self.residency.track(name, category, size, owner);
+        self.inner.pinned.remove(name);
+        if data.is_none() {
+            self.pin(name);
+        }
         let handle = self.inner.generate_handle(name);
         self.inner.buffers.insert(name.to_string(), buffer);
         
@@ -500,25 +925,33 @@ impl ResourceManager {
         let vertex_count = vertices.len() / vertex_stride;
         let index_count = indices.map_or(0, |i| i.len() / index_stride);
         
+        // メッシュのバッファより先に登録し、作成中のバッファが退避されないようにする
+        self.residency.track(name, ResourceCategory::Mesh, 0, None);
+        let _creating = self.residency.acquire(name, ResourceHandle::invalid());
+        
         // 頂点バッファを作成
         let vertex_buffer_name = format!("{}_vb", name);
-        let vertex_buffer = self.create_buffer(
+        let vertex_buffer = self.create_buffer_in(
             &vertex_buffer_name,
             BufferTarget::Vertex,
             BufferUsage::Static,
             Some(vertices),
             vertex_stride,
+            ResourceCategory::Mesh,
+            Some(name),
         )?;
         
         // インデックスバッファを作成（存在する場合）
         let index_buffer = if let Some(indices) = indices {
             let index_buffer_name = format!("{}_ib", name);
-            Some(self.create_buffer(
+            Some(self.create_buffer_in(
                 &index_buffer_name,
                 BufferTarget::Index,
                 BufferUsage::Static,
                 Some(indices),
                 index_stride,
+                ResourceCategory::Mesh,
+                Some(name),
             )?)
         } else {
             None
@@ -590,15 +1023,18 @@ impl ResourceManager {
     /// テクスチャを削除
     pub fn remove_texture(&mut self, name: &str) -> Result<(), GraphicsError> {
         if self.inner.textures.remove(name).is_some() {
+            if let Some(loader) = self.loader.as_mut() {
+                loader.cancel(name);
+            }
+            
             // ハンドルも削除
-            let handles_to_remove: Vec<_> = self.inner.handles.iter()
-                .filter(|(_, n)| n == &name)
-                .map(|(h, _)| *h)
-                .collect();
+            self.inner.remove_handles(name);
+            self.forget(name);
+            self.inner.texture_options.remove(name);
             
-            for handle in handles_to_remove {
-                self.inner.handles.remove(&handle);
-            }
+            let mut renderer = self.renderer.lock()
+                .map_err(|_| GraphicsError::Resource("レンダラーのロックに失敗".to_string()))?;
+            renderer.destroy_texture(name)?;
             
             Ok(())
         } else {
@@ -610,14 +1046,12 @@ impl ResourceManager {
     pub fn remove_buffer(&mut self, name: &str) -> Result<(), GraphicsError> {
         if self.inner.buffers.remove(name).is_some() {
             // ハンドルも削除
-            let handles_to_remove: Vec<_> = self.inner.handles.iter()
-                .filter(|(_, n)| n == &name)
-                .map(|(h, _)| *h)
-                .collect();
+            self.inner.remove_handles(name);
+            self.forget(name);
             
-            for handle in handles_to_remove {
-                self.inner.handles.remove(&handle);
-            }
+            let mut renderer = self.renderer.lock()
+                .map_err(|_| GraphicsError::Resource("レンダラーのロックに失敗".to_string()))?;
+            renderer.destroy_buffer(name)?;
             
             Ok(())
         } else {
@@ -629,27 +1063,21 @@ impl ResourceManager {
     pub fn remove_mesh(&mut self, name: &str) -> Result<(), GraphicsError> {
         if let Some(mesh) = self.inner.meshes.remove(name) {
             // ハンドルも削除
-            let handles_to_remove: Vec<_> = self.inner.handles.iter()
-                .filter(|(_, n)| n == &name)
-                .map(|(h, _)| *h)
-                .collect();
-            
-            for handle in handles_to_remove {
-                self.inner.handles.remove(&handle);
-            }
+            self.inner.remove_handles(name);
             
             // 関連するバッファも削除（オプション）
             if let Some(vb) = mesh.vertex_buffer {
-                if let Some(vb_name) = self.get_resource_name(vb) {
-                    let _ = self.remove_buffer(vb_name);
+                if let Some(vb_name) = self.get_resource_name(vb).map(str::to_string) {
+                    let _ = self.remove_buffer(&vb_name);
                 }
             }
             
             if let Some(ib) = mesh.index_buffer {
-                if let Some(ib_name) = self.get_resource_name(ib) {
-                    let _ = self.remove_buffer(ib_name);
+                if let Some(ib_name) = self.get_resource_name(ib).map(str::to_string) {
+                    let _ = self.remove_buffer(&ib_name);
                 }
             }
+            self.forget(name);
             
             Ok(())
         } else {
@@ -663,6 +1091,18 @@ impl ResourceManager {
         self.inner.buffers.clear();
         self.inner.meshes.clear();
         self.inner.handles.clear();
+        if let Some(loader) = self.loader.as_mut() {
+            loader.cancel_all();
+        }
+        self.inner.texture_options.clear();
+        self.inner.pinned.clear();
+        self.residency.clear();
+    }
+    
+    /// 常駐管理の対象から外す
+    fn forget(&mut self, name: &str) {
+        self.inner.pinned.remove(name);
+        self.residency.untrack(name);
     }
     
     /// クリーンアップ処理
@@ -750,6 +1190,208 @@ mod tests {
         assert!(!invalid.is_valid());
     }
     
+    #[test]
+    fn test_load_texture_decodes_image() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("wallpaper.png");
+        image::RgbaImage::from_pixel(8, 4, image::Rgba([10, 20, 30, 255])).save(&path).unwrap();
+        
+        let renderer = Arc::new(Mutex::new(MockRenderer));
+        let mut manager = ResourceManager::new(GraphicsApi::Vulkan, renderer, 4096, true).unwrap();
+        let handle = manager.load_texture(&path, None).unwrap();
+        
+        let texture = manager.get_texture_by_handle(handle).unwrap();
+        assert_eq!(texture.info.name, "wallpaper");
+        assert_eq!((texture.width, texture.height, texture.format), (8, 4, TextureFormat::R8G8B8A8));
+        assert_eq!(texture.mip_levels, 4);
+        assert_eq!(texture.color_space, ColorSpace::Srgb);
+        
+        let data = texture.data.as_ref().unwrap();
+        assert_eq!(data.len(), (8 * 4 + 4 * 2 + 2 + 1) * 4);
+        assert_eq!(&data[..4], &[10, 20, 30, 255]);
+        
+        // 最大サイズを超える場合は縮小を指定しない限りエラー
+        let renderer = Arc::new(Mutex::new(MockRenderer));
+        let mut manager = ResourceManager::new(GraphicsApi::Vulkan, renderer, 4, true).unwrap();
+        assert!(manager.load_texture(&path, None).is_err());
+        
+        let options = TextureLoadOptions {
+            format: TextureFormat::B8G8R8A8,
+            generate_mipmaps: false,
+            downscale_to_fit: true,
+            ..TextureLoadOptions::default()
+        };
+        let handle = manager.load_texture_with_options(&path, Some("small"), &options).unwrap();
+        let texture = manager.get_texture_by_handle(handle).unwrap();
+        assert_eq!((texture.width, texture.height, texture.mip_levels), (4, 2, 1));
+        assert_eq!(&texture.data.as_ref().unwrap()[..4], &[30, 20, 10, 255]);
+    }
+
+    #[test]
+    fn test_texture_eviction_and_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths: Vec<_> = ["a", "b", "c"].iter().map(|name| {
+            let path = dir.path().join(format!("{}.png", name));
+            image::RgbaImage::from_pixel(8, 4, image::Rgba([1, 2, 3, 255])).save(&path).unwrap();
+            path
+        }).collect();
+
+        // ミップマップ込みで1枚172バイトなので、予算内には1枚しか常駐できない
+        let renderer = Arc::new(Mutex::new(MockRenderer));
+        let mut manager = ResourceManager::new(GraphicsApi::Vulkan, renderer, 4096, true).unwrap();
+        manager.set_memory_budget(MemoryBudget { textures: 200, ..MemoryBudget::unlimited() }).unwrap();
+
+        let a = manager.load_texture(&paths[0], None).unwrap();
+        let b = manager.load_texture(&paths[1], None).unwrap();
+        assert!(!manager.is_resident(a));
+        assert!(manager.is_resident(b));
+        let texture = manager.get_texture_by_handle(a).unwrap();
+        assert_eq!(texture.info.state, ResourceState::Unloaded);
+        assert!(texture.data.is_none());
+
+        // 参照を取得すると再読み込みされ、参照中は退避されない
+        let reference = manager.acquire(a).unwrap();
+        assert_eq!(manager.ref_count(a), 1);
+        assert!(manager.is_resident(a));
+        assert!(!manager.is_resident(b));
+        assert_eq!(manager.get_texture_by_handle(a).unwrap().data.as_ref().unwrap().len(), 172);
+
+        let c = manager.load_texture(&paths[2], None).unwrap();
+        assert!(manager.is_resident(a) && manager.is_resident(c));
+
+        let stats = manager.memory_stats();
+        assert_eq!(stats.textures.resident_bytes, 344);
+        assert_eq!((stats.textures.resident_count, stats.textures.evicted_count), (2, 1));
+        assert_eq!((stats.textures.evictions, stats.textures.reloads), (2, 1));
+
+        // 参照を解放すると次の予算調整で退避される
+        drop(reference);
+        assert_eq!(manager.ref_count(a), 0);
+        manager.trim().unwrap();
+        assert!(!manager.is_resident(a));
+        assert!(manager.is_resident(c));
+
+        // データのないテクスチャは退避されない
+        manager.create_texture("target", 16, 16, TextureFormat::R8G8B8A8, None).unwrap();
+        manager.trim().unwrap();
+        assert_eq!(manager.get_texture("target").unwrap().info.state, ResourceState::Loaded);
+
+        manager.remove_texture("c").unwrap();
+        assert_eq!(manager.memory_stats().textures.resident_bytes, 16 * 16 * 4);
+    }
+
+    #[test]
+    fn test_mesh_buffers_share_mesh_references() {
+        let renderer = Arc::new(Mutex::new(MockRenderer));
+        let mut manager = ResourceManager::new(GraphicsApi::Vulkan, renderer, 4096, true).unwrap();
+        manager.set_memory_budget(MemoryBudget { meshes: 100, ..MemoryBudget::unlimited() }).unwrap();
+
+        let quad = manager.create_mesh("quad", &[0; 64], Some(&[0; 12]), 16, 2, Vec::new()).unwrap();
+        let reference = manager.acquire(quad).unwrap();
+        let triangle = manager.create_mesh("triangle", &[0; 48], None, 16, 2, Vec::new()).unwrap();
+        assert_eq!(manager.memory_stats().meshes.resident_bytes, 124);
+
+        // 参照されているメッシュのバッファは残り、もう一方が退避される
+        manager.trim().unwrap();
+        let stats = manager.memory_stats();
+        assert_eq!(stats.meshes.resident_bytes, 76);
+        assert_eq!(stats.buffers.resident_bytes, 0);
+        assert_eq!(manager.get_buffer("triangle_vb").unwrap().info.state, ResourceState::Unloaded);
+
+        // メッシュを使用すると所有するバッファが再作成される
+        drop(reference);
+        manager.touch(triangle).unwrap();
+        let vertex_buffer = manager.get_mesh("triangle").unwrap().vertex_buffer.unwrap();
+        assert!(manager.is_resident(vertex_buffer));
+        assert_eq!(manager.get_buffer("quad_vb").unwrap().info.state, ResourceState::Unloaded);
+    }
+
+    #[test]
+    fn test_async_texture_loading() {
+        use futures::FutureExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let icons: Vec<_> = (0..4).map(|i| {
+            let path = dir.path().join(format!("icon{}.png", i));
+            image::RgbaImage::from_pixel(4, 4, image::Rgba([i, 0, 0, 255])).save(&path).unwrap();
+            path
+        }).collect();
+
+        let renderer = Arc::new(Mutex::new(MockRenderer));
+        let mut manager = ResourceManager::new(GraphicsApi::Vulkan, renderer, 4096, true).unwrap();
+        let options = TextureLoadOptions::default();
+
+        let mut loads: Vec<_> = icons.iter().map(|path| {
+            manager.load_texture_async(path, None, &options, LoadPriority::Normal).unwrap()
+        }).collect();
+        let missing = manager.load_texture_async(dir.path().join("missing.png"), None, &options, LoadPriority::Low).unwrap();
+
+        // 読み込み中もハンドルは有効
+        let first = loads[0].handle();
+        assert_eq!(manager.get_texture_by_handle(first).unwrap().info.state, ResourceState::Loading);
+        assert!(manager.is_loading(first));
+        assert!(manager.set_load_priority(loads[3].handle(), LoadPriority::Critical) || manager.pending_load_count() == 5);
+
+        // キャンセルしたプレースホルダーは削除される
+        let cancelled = loads.pop().unwrap();
+        assert!(manager.cancel_load(cancelled.handle()));
+        assert!(!manager.is_loading(cancelled.handle()));
+        assert!(manager.get_texture("icon3").is_none());
+        assert!(cancelled.now_or_never().unwrap().is_err());
+
+        // レンダースレッド側で毎フレームアップロードする
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+        while manager.pending_load_count() > 0 {
+            assert!(std::time::Instant::now() < deadline, "非同期読み込みが完了しません");
+            manager.process_pending_loads(1);
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+
+        for (i, load) in loads.into_iter().enumerate() {
+            let handle = load.now_or_never().unwrap().unwrap();
+            let texture = manager.get_texture_by_handle(handle).unwrap();
+            assert_eq!(texture.info.state, ResourceState::Loaded);
+            assert_eq!((texture.width, texture.height), (4, 4));
+            assert_eq!(texture.data.as_ref().unwrap()[0], i as u8);
+            assert!(manager.is_resident(handle));
+            assert!(!manager.is_loading(handle));
+        }
+        assert!(missing.now_or_never().unwrap().is_err());
+        assert_eq!(manager.get_texture("missing").unwrap().info.state, ResourceState::Failed);
+    }
+
+    #[test]
+    fn test_load_compressed_texture() {
+        // 4x4 赤一色のDXT1
+        let mut dds = vec![0u8; 128];
+        dds[..4].copy_from_slice(b"DDS ");
+        dds[4] = 124;
+        dds[12] = 4;
+        dds[16] = 4;
+        dds[80] = 0x4;
+        dds[84..88].copy_from_slice(b"DXT1");
+        dds.extend_from_slice(&[0x00, 0xf8, 0x00, 0xf8, 0, 0, 0, 0]);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("icon.dds");
+        fs::write(&path, &dds).unwrap();
+
+        let renderer = Arc::new(Mutex::new(MockRenderer));
+        let mut manager = ResourceManager::new(GraphicsApi::Vulkan, renderer, 4096, true).unwrap();
+        let handle = manager.load_texture(&path, None).unwrap();
+        let texture = manager.get_texture_by_handle(handle).unwrap();
+        assert_eq!((texture.width, texture.height, texture.format), (4, 4, TextureFormat::BC1));
+        assert_eq!(texture.data.as_ref().unwrap().len(), 8);
+
+        // 圧縮テクスチャを使わない場合はCPUで展開する
+        let renderer = Arc::new(Mutex::new(MockRenderer));
+        let mut manager = ResourceManager::new(GraphicsApi::Vulkan, renderer, 4096, false).unwrap();
+        let handle = manager.load_texture(&path, None).unwrap();
+        let texture = manager.get_texture_by_handle(handle).unwrap();
+        assert_eq!(texture.format, TextureFormat::R8G8B8A8);
+        assert_eq!(&texture.data.as_ref().unwrap()[..4], &[255, 0, 0, 255]);
+    }
+
     #[test]
     fn test_resource_manager_creation() {
         let renderer = Arc::new(Mutex::new(MockRenderer));
@@ -0,0 +1,522 @@
+// LumosDesktop シェーダーキャッシュ
+// コンパイル済みシェーダーをディスクに保存し、次回起動時のコンパイルを省略
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+use xxhash_rust::xxh3::{xxh3_64, Xxh3};
+
+use super::GraphicsApi;
+use super::renderer::ShaderStage;
+use super::shader_compiler::{self, ShaderReflection};
+use super::shader_manager::{CompiledShader, ShaderSource, ShaderType};
+
+/// キャッシュ形式のバージョン
+///
+/// エントリーの形式やコンパイラの出力が変わる場合に上げると、古いキャッシュはすべて破棄されます。
+pub const SHADER_CACHE_VERSION: u32 = 1;
+
+const MAGIC: &[u8; 4] = b"LSHC";
+const ENTRY_EXTENSION: &str = "bin";
+const TEMP_EXTENSION: &str = "tmp";
+/// 書き込んだプロセスが動作中でも、この時間を過ぎた一時ファイルは途中で残ったものとみなす
+const STALE_TEMP_AGE: Duration = Duration::from_secs(60 * 60);
+/// マジック、バージョン、キー、メタデータ長、データのハッシュ
+const HEADER_SIZE: usize = 4 + 4 + 8 + 4 + 8;
+
+/// エントリーのデータ以外の情報
+#[derive(Debug, Serialize, Deserialize)]
+struct EntryMetadata {
+    format: ShaderType,
+    entry_point: String,
+    stage: ShaderStage,
+    reflection: ShaderReflection,
+}
+
+/// キャッシュ上のエントリー
+#[derive(Debug, Clone, Copy)]
+struct EntryInfo {
+    size: u64,
+    last_used: SystemTime,
+}
+
+/// 長さを前置して文字列をハッシュに加える（連結による衝突を防ぐ）
+fn hash_str(hasher: &mut Xxh3, value: &str) {
+    hash_bytes(hasher, value.as_bytes());
+}
+
+fn hash_bytes(hasher: &mut Xxh3, value: &[u8]) {
+    hasher.update(&(value.len() as u64).to_le_bytes());
+    hasher.update(value);
+}
+
+/// 一時ファイル名 `<key>.<pid>.tmp` から書き込んだプロセスのIDを取り出す
+fn temp_file_pid(path: &Path) -> Option<u32> {
+    if path.extension()? != TEMP_EXTENSION {
+        return None;
+    }
+    let (key, pid) = path.file_stem()?.to_str()?.split_once('.')?;
+    u64::from_str_radix(key, 16).ok()?;
+    pid.parse().ok()
+}
+
+/// プロセスが動作中か（確認できない環境では動作中とみなす）
+fn process_alive(pid: u32) -> bool {
+    let proc_dir = Path::new("/proc");
+    if pid == std::process::id() || !proc_dir.is_dir() {
+        return true;
+    }
+    proc_dir.join(pid.to_string()).exists()
+}
+
+fn hash_sorted_map(hasher: &mut Xxh3, map: &HashMap<String, String>) {
+    let mut entries: Vec<(&String, &String)> = map.iter().collect();
+    entries.sort();
+    hasher.update(&(entries.len() as u64).to_le_bytes());
+    for (key, value) in entries {
+        hash_str(hasher, key);
+        hash_str(hasher, value);
+    }
+}
+
+/// ディスク上のコンパイル済みシェーダーキャッシュ
+///
+/// エントリーはソース（インクルード展開後）、定義、ステージ、出力先のAPI、デバイス情報の
+/// ハッシュをファイル名として `<dir>/v<バージョン>/` に保存されます。合計サイズが上限を
+/// 超えた場合は最後に使われた時刻が古いものから削除します。
+#[derive(Debug)]
+pub struct ShaderCache {
+    dir: PathBuf,
+    max_size: u64,
+    entries: HashMap<u64, EntryInfo>,
+    total_size: u64,
+    hits: u64,
+    misses: u64,
+}
+
+impl ShaderCache {
+    /// キャッシュディレクトリを開く
+    ///
+    /// 他のバージョンのキャッシュは削除されます。
+    pub fn open<P: AsRef<Path>>(dir: P, max_size: u64) -> io::Result<Self> {
+        let root = dir.as_ref();
+        let current = format!("v{}", SHADER_CACHE_VERSION);
+        let versioned = root.join(&current);
+        fs::create_dir_all(&versioned)?;
+
+        for entry in fs::read_dir(root)?.flatten() {
+            let name = entry.file_name();
+            let Some(name) = name.to_str() else { continue };
+            let is_version_dir = name.strip_prefix('v')
+                .is_some_and(|version| !version.is_empty() && version.bytes().all(|b| b.is_ascii_digit()));
+            if is_version_dir && name != current {
+                log::info!("古いシェーダーキャッシュを削除します: {}", entry.path().display());
+                let _ = fs::remove_dir_all(entry.path());
+            }
+        }
+
+        let mut cache = Self {
+            dir: versioned,
+            max_size,
+            entries: HashMap::new(),
+            total_size: 0,
+            hits: 0,
+            misses: 0,
+        };
+        cache.scan()?;
+        cache.evict();
+        Ok(cache)
+    }
+
+    /// 既定のキャッシュディレクトリ（`~/.cache/lumos/shaders` など）
+    pub fn default_dir() -> Option<PathBuf> {
+        dirs::cache_dir().map(|dir| dir.join("lumos").join("shaders"))
+    }
+
+    /// ソースと出力先からキャッシュのキーを計算
+    ///
+    /// インクルードは展開済みのソースに含まれるため、インクルードしたファイルが変更された
+    /// 場合もキーが変わります。ファイルのパスは含めないため、移動しても再利用されます。
+    pub fn key(
+        source: &ShaderSource,
+        api: GraphicsApi,
+        device_info: &HashMap<String, String>,
+        compiler_version: &str,
+    ) -> u64 {
+        let mut hasher = Xxh3::new();
+        hasher.update(&SHADER_CACHE_VERSION.to_le_bytes());
+        hash_str(&mut hasher, compiler_version);
+        hash_str(&mut hasher, &format!("{:?}", source.type_));
+        hash_str(&mut hasher, &format!("{:?}", source.stage));
+        hash_str(&mut hasher, &source.entry_point);
+        hash_str(&mut hasher, &source.source);
+        hash_bytes(&mut hasher, source.bytecode.as_deref().unwrap_or_default());
+        hash_sorted_map(&mut hasher, &source.defines);
+        hash_str(&mut hasher, &format!("{:?}", api));
+        hash_str(&mut hasher, &format!("{:?}", shader_compiler::target_format(api)));
+        hash_sorted_map(&mut hasher, device_info);
+        hasher.digest()
+    }
+
+    fn entry_path(&self, key: u64) -> PathBuf {
+        self.dir.join(format!("{:016x}.{}", key, ENTRY_EXTENSION))
+    }
+
+    /// ディレクトリ内のエントリーを読み込む
+    fn scan(&mut self) -> io::Result<()> {
+        for entry in fs::read_dir(&self.dir)?.flatten() {
+            let path = entry.path();
+            let key = path.extension()
+                .filter(|ext| *ext == ENTRY_EXTENSION)
+                .and_then(|_| path.file_stem())
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| u64::from_str_radix(stem, 16).ok());
+            let Ok(metadata) = entry.metadata() else { continue };
+            let Some(key) = key else {
+                // 書き込み途中で終了したプロセスの一時ファイルだけを削除し、他のファイルには触れない
+                if let Some(pid) = temp_file_pid(&path) {
+                    let age = metadata.modified().ok()
+                        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+                        .unwrap_or_default();
+                    if !process_alive(pid) || age > STALE_TEMP_AGE {
+                        let _ = fs::remove_file(&path);
+                    }
+                }
+                continue;
+            };
+            let info = EntryInfo {
+                size: metadata.len(),
+                last_used: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+            };
+            self.total_size += info.size;
+            self.entries.insert(key, info);
+        }
+        Ok(())
+    }
+
+    /// キャッシュからコンパイル済みシェーダーを読み込む
+    ///
+    /// 壊れているエントリーや別のバージョンのエントリーは削除して `None` を返します。
+    pub fn load(&mut self, key: u64) -> Option<CompiledShader> {
+        if !self.entries.contains_key(&key) {
+            self.misses += 1;
+            return None;
+        }
+
+        let path = self.entry_path(key);
+        match fs::read(&path).ok().and_then(|bytes| decode_entry(key, &bytes)) {
+            Some(compiled) => {
+                self.hits += 1;
+                let now = SystemTime::now();
+                if let Some(info) = self.entries.get_mut(&key) {
+                    info.last_used = now;
+                }
+                // 次回起動時の削除順のために更新時刻を最終使用時刻として使う
+                if let Ok(file) = fs::File::options().write(true).open(&path) {
+                    let _ = file.set_modified(now);
+                }
+                Some(compiled)
+            }
+            None => {
+                log::warn!("壊れたシェーダーキャッシュを削除します: {}", path.display());
+                self.remove(key);
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// コンパイル済みシェーダーをキャッシュに保存
+    ///
+    /// 上限を超えるエントリーは保存しません。
+    pub fn store(&mut self, key: u64, compiled: &CompiledShader) -> io::Result<()> {
+        let bytes = encode_entry(key, compiled)?;
+        let size = bytes.len() as u64;
+        if size > self.max_size {
+            return Ok(());
+        }
+
+        // 他のプロセスが途中のファイルを読まないように、一時ファイルに書いてから置き換える
+        let path = self.entry_path(key);
+        let temp = self.dir.join(format!("{:016x}.{}.{}", key, std::process::id(), TEMP_EXTENSION));
+        fs::write(&temp, &bytes)?;
+        if let Err(error) = fs::rename(&temp, &path) {
+            let _ = fs::remove_file(&temp);
+            return Err(error);
+        }
+
+        let info = EntryInfo { size, last_used: SystemTime::now() };
+        if let Some(previous) = self.entries.insert(key, info) {
+            self.total_size -= previous.size;
+        }
+        self.total_size += size;
+        self.evict();
+        Ok(())
+    }
+
+    fn remove(&mut self, key: u64) {
+        if let Some(info) = self.entries.remove(&key) {
+            self.total_size -= info.size;
+        }
+        let _ = fs::remove_file(self.entry_path(key));
+    }
+
+    /// 合計サイズが上限以下になるまで古いエントリーを削除
+    fn evict(&mut self) {
+        if self.total_size <= self.max_size {
+            return;
+        }
+        let mut by_age: Vec<(SystemTime, u64)> = self.entries.iter()
+            .map(|(&key, info)| (info.last_used, key))
+            .collect();
+        by_age.sort();
+        for (_, key) in by_age {
+            if self.total_size <= self.max_size {
+                break;
+            }
+            self.remove(key);
+        }
+    }
+
+    /// 上限を変更（超えている場合はすぐに削除）
+    pub fn set_max_size(&mut self, max_size: u64) {
+        self.max_size = max_size;
+        self.evict();
+    }
+
+    pub fn max_size(&self) -> u64 {
+        self.max_size
+    }
+
+    /// すべてのエントリーを削除
+    pub fn clear(&mut self) {
+        let keys: Vec<u64> = self.entries.keys().copied().collect();
+        for key in keys {
+            self.remove(key);
+        }
+    }
+
+    /// エントリーの合計サイズ（バイト）
+    pub fn size(&self) -> u64 {
+        self.total_size
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn contains(&self, key: u64) -> bool {
+        self.entries.contains_key(&key)
+    }
+
+    /// このセッションでのヒット数
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// このセッションでのミス数
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    /// バージョンごとのエントリーを保存するディレクトリ
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+fn encode_entry(key: u64, compiled: &CompiledShader) -> io::Result<Vec<u8>> {
+    let metadata = EntryMetadata {
+        format: compiled.format,
+        entry_point: compiled.entry_point.clone(),
+        stage: compiled.stage,
+        reflection: compiled.reflection.clone(),
+    };
+    let metadata = serde_json::to_vec(&metadata)?;
+
+    let mut bytes = Vec::with_capacity(HEADER_SIZE + metadata.len() + compiled.data.len());
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&SHADER_CACHE_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&key.to_le_bytes());
+    bytes.extend_from_slice(&(metadata.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(&xxh3_64(&compiled.data).to_le_bytes());
+    bytes.extend_from_slice(&metadata);
+    bytes.extend_from_slice(&compiled.data);
+    Ok(bytes)
+}
+
+fn decode_entry(key: u64, bytes: &[u8]) -> Option<CompiledShader> {
+    let header = bytes.get(..HEADER_SIZE)?;
+    let version = u32::from_le_bytes(header[4..8].try_into().ok()?);
+    let stored_key = u64::from_le_bytes(header[8..16].try_into().ok()?);
+    let metadata_len = u32::from_le_bytes(header[16..20].try_into().ok()?) as usize;
+    let checksum = u64::from_le_bytes(header[20..28].try_into().ok()?);
+    if &header[..4] != MAGIC || version != SHADER_CACHE_VERSION || stored_key != key {
+        return None;
+    }
+
+    let rest = &bytes[HEADER_SIZE..];
+    let metadata: EntryMetadata = serde_json::from_slice(rest.get(..metadata_len)?).ok()?;
+    let data = &rest[metadata_len..];
+    if xxh3_64(data) != checksum {
+        return None;
+    }
+
+    Some(CompiledShader {
+        data: data.to_vec(),
+        format: metadata.format,
+        entry_point: metadata.entry_point,
+        stage: metadata.stage,
+        timestamp: SystemTime::now(),
+        reflection: metadata.reflection,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::shader_include::SourceMap;
+    use super::super::shader_compiler::COMPILER_VERSION;
+
+    fn source(text: &str) -> ShaderSource {
+        ShaderSource {
+            source: text.to_string(),
+            type_: ShaderType::Wgsl,
+            entry_point: "main".to_string(),
+            stage: ShaderStage::Compute,
+            defines: HashMap::new(),
+            bytecode: None,
+            source_map: SourceMap::default(),
+        }
+    }
+
+    fn compiled(size: usize) -> CompiledShader {
+        CompiledShader {
+            data: vec![7; size],
+            format: ShaderType::SpirV,
+            entry_point: "main".to_string(),
+            stage: ShaderStage::Compute,
+            timestamp: SystemTime::now(),
+            reflection: ShaderReflection {
+                workgroup_size: [8, 8, 1],
+                ..ShaderReflection::default()
+            },
+        }
+    }
+
+    #[test]
+    fn test_key_covers_inputs() {
+        let device_info = HashMap::from([("device".to_string(), "Test GPU".to_string())]);
+        let base = ShaderCache::key(&source("a"), GraphicsApi::Vulkan, &device_info, COMPILER_VERSION);
+        assert_eq!(base, ShaderCache::key(&source("a"), GraphicsApi::Vulkan, &device_info, COMPILER_VERSION));
+        assert_ne!(base, ShaderCache::key(&source("b"), GraphicsApi::Vulkan, &device_info, COMPILER_VERSION));
+        assert_ne!(base, ShaderCache::key(&source("a"), GraphicsApi::Metal, &device_info, COMPILER_VERSION));
+        assert_ne!(base, ShaderCache::key(&source("a"), GraphicsApi::Vulkan, &HashMap::new(), COMPILER_VERSION));
+        assert_ne!(base, ShaderCache::key(&source("a"), GraphicsApi::Vulkan, &device_info, "naga-0.15"));
+
+        let mut defined = source("a");
+        defined.defines.insert("BLUR".to_string(), "1".to_string());
+        assert_ne!(base, ShaderCache::key(&defined, GraphicsApi::Vulkan, &device_info, COMPILER_VERSION));
+    }
+
+    #[test]
+    fn test_store_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut cache = ShaderCache::open(dir.path(), 1 << 20).unwrap();
+        assert!(cache.load(1).is_none());
+        cache.store(1, &compiled(64)).unwrap();
+
+        // 開き直しても読み込める
+        let mut cache = ShaderCache::open(dir.path(), 1 << 20).unwrap();
+        assert_eq!(cache.len(), 1);
+        let loaded = cache.load(1).unwrap();
+        assert_eq!(loaded.data, vec![7; 64]);
+        assert_eq!(loaded.reflection.workgroup_size, [8, 8, 1]);
+        assert_eq!((cache.hits(), cache.misses()), (1, 0));
+
+        // 壊れたエントリーは削除される
+        let path = cache.entry_path(1);
+        let mut bytes = fs::read(&path).unwrap();
+        *bytes.last_mut().unwrap() = 0;
+        fs::write(&path, bytes).unwrap();
+        assert!(cache.load(1).is_none());
+        assert!(!path.exists());
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_version_invalidation() {
+        let dir = tempfile::tempdir().unwrap();
+        let old = dir.path().join("v0");
+        fs::create_dir(&old).unwrap();
+        fs::write(old.join("0000000000000001.bin"), b"old").unwrap();
+
+        let mut cache = ShaderCache::open(dir.path(), 1 << 20).unwrap();
+        assert!(!old.exists());
+
+        // 現在のディレクトリにあっても別のバージョンで書かれたエントリーは使わない
+        cache.store(2, &compiled(16)).unwrap();
+        let path = cache.entry_path(2);
+        let mut bytes = fs::read(&path).unwrap();
+        bytes[4..8].copy_from_slice(&(SHADER_CACHE_VERSION + 1).to_le_bytes());
+        fs::write(&path, bytes).unwrap();
+        assert!(cache.load(2).is_none());
+    }
+
+    #[test]
+    fn test_size_limit_evicts_least_recently_used() {
+        let dir = tempfile::tempdir().unwrap();
+        let entry_size = encode_entry(1, &compiled(1000)).unwrap().len() as u64;
+        let mut cache = ShaderCache::open(dir.path(), entry_size * 2).unwrap();
+
+        cache.store(1, &compiled(1000)).unwrap();
+        cache.store(2, &compiled(1000)).unwrap();
+        assert!(cache.load(1).is_some());
+        cache.store(3, &compiled(1000)).unwrap();
+        assert!(cache.contains(1) && cache.contains(3));
+        assert!(!cache.contains(2));
+        assert_eq!(cache.size(), entry_size * 2);
+
+        // 上限を超えるエントリーは保存しない
+        cache.store(4, &compiled(10_000)).unwrap();
+        assert!(!cache.contains(4));
+
+        cache.set_max_size(entry_size);
+        assert_eq!(cache.len(), 1);
+        cache.clear();
+        assert_eq!(cache.size(), 0);
+    }
+
+    #[test]
+    fn test_scan_removes_only_stale_temp_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let versioned = dir.path().join(format!("v{}", SHADER_CACHE_VERSION));
+        fs::create_dir(&versioned).unwrap();
+
+        // 書き込み中の一時ファイル、終了したプロセスの一時ファイル、古い一時ファイル、無関係なファイル
+        let writing = versioned.join(format!("{:016x}.{}.tmp", 1, std::process::id()));
+        let orphaned = versioned.join(format!("{:016x}.{}.tmp", 2, u32::MAX));
+        let old = versioned.join(format!("{:016x}.{}.tmp", 3, std::process::id()));
+        let unrelated = versioned.join("notes.txt");
+        for path in [&writing, &orphaned, &old, &unrelated] {
+            fs::write(path, b"partial").unwrap();
+        }
+        fs::File::options().write(true).open(&old).unwrap()
+            .set_modified(SystemTime::now() - STALE_TEMP_AGE * 2).unwrap();
+
+        let cache = ShaderCache::open(dir.path(), 1 << 20).unwrap();
+        assert!(writing.exists());
+        assert!(!orphaned.exists());
+        assert!(!old.exists());
+        assert!(unrelated.exists());
+        assert_eq!(cache.len(), 0);
+    }
+}
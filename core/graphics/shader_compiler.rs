@@ -0,0 +1,525 @@
+// LumosDesktop シェーダーコンパイラ
+// nagaによるWGSL/GLSL/SPIR-Vの解析と検証、各バックエンド形式への変換、リフレクション
+
+use std::collections::HashMap;
+use std::error::Error;
+
+use naga::back::{glsl, hlsl, msl, spv, wgsl};
+use naga::front;
+use naga::valid::{Capabilities, ModuleInfo, ValidationFlags, Validator};
+use naga::{AddressSpace, Binding, ImageClass, Module, ScalarKind, Span, StorageAccess, TypeInner};
+use serde::{Deserialize, Serialize};
+
+use super::renderer::ShaderStage;
+use super::shader_manager::{ShaderCompilationError, ShaderDiagnostic, ShaderSource, ShaderType};
+
+/// バインディングの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum BindingType {
+    UniformBuffer,
+    StorageBuffer { read_only: bool },
+    Texture,
+    StorageTexture,
+    Sampler,
+}
+
+/// シェーダーが参照するリソースバインディング
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShaderBinding {
+    pub group: u32,
+    pub binding: u32,
+    pub name: String,
+    pub binding_type: BindingType,
+    /// バッファの最小サイズ（テクスチャとサンプラーは `None`）
+    pub size: Option<u32>,
+}
+
+/// 頂点シェーダーの入力属性
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VertexInput {
+    pub location: u32,
+    pub name: String,
+    /// `float32x3` などの頂点フォーマット名
+    pub format: String,
+    pub size: u32,
+}
+
+/// エントリーポイントのリフレクション情報
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShaderReflection {
+    /// グループ、バインディング番号順のリソース
+    pub bindings: Vec<ShaderBinding>,
+    /// ロケーション順の頂点入力（頂点シェーダー以外は空）
+    pub vertex_inputs: Vec<VertexInput>,
+    /// ユニフォームバッファのメンバー名と (オフセット, サイズ)
+    pub uniforms: HashMap<String, (u32, u32)>,
+    /// コンピュートシェーダーのワークグループサイズ（それ以外は0）
+    pub workgroup_size: [u32; 3],
+}
+
+/// 解析と検証が済んだシェーダーモジュール
+#[derive(Debug)]
+pub struct ParsedShader {
+    pub module: Module,
+    pub info: ModuleInfo,
+}
+
+/// シェーダーのコンパイル結果
+#[derive(Debug, Clone)]
+pub struct ShaderOutput {
+    /// SPIR-Vはリトルエンディアンのワード列、それ以外はUTF-8のソース
+    pub data: Vec<u8>,
+    /// 出力先での名前（予約語と衝突する場合は変更される）
+    pub entry_point: String,
+    pub reflection: ShaderReflection,
+}
+
+/// コンパイラの識別子（キャッシュのキーに含める）
+pub const COMPILER_VERSION: &str = "naga-0.14";
+
+/// グラフィックスAPIが受け付けるシェーダー形式
+pub fn target_format(api: super::GraphicsApi) -> ShaderType {
+    match api {
+        super::GraphicsApi::Vulkan => ShaderType::SpirV,
+        super::GraphicsApi::Metal => ShaderType::Metal,
+        super::GraphicsApi::DirectX => ShaderType::Hlsl,
+        super::GraphicsApi::OpenGL => ShaderType::Glsl,
+        // ソフトウェアバックエンドはシェーダーを実行しないため、検証済みのWGSLを保持する
+        super::GraphicsApi::Wgpu | super::GraphicsApi::Software => ShaderType::Wgsl,
+    }
+}
+
+/// nagaで解析できるソース形式か
+pub fn can_parse(type_: ShaderType) -> bool {
+    matches!(type_, ShaderType::Wgsl | ShaderType::Glsl | ShaderType::SpirV)
+}
+
+fn naga_stage(stage: ShaderStage) -> Result<naga::ShaderStage, ShaderCompilationError> {
+    match stage {
+        ShaderStage::Vertex => Ok(naga::ShaderStage::Vertex),
+        ShaderStage::Fragment => Ok(naga::ShaderStage::Fragment),
+        ShaderStage::Compute => Ok(naga::ShaderStage::Compute),
+        _ => Err(ShaderCompilationError::InvalidStage(format!(
+            "{:?} ステージはサポートされていません", stage
+        ))),
+    }
+}
+
+fn from_naga_stage(stage: naga::ShaderStage) -> ShaderStage {
+    match stage {
+        naga::ShaderStage::Vertex => ShaderStage::Vertex,
+        naga::ShaderStage::Fragment => ShaderStage::Fragment,
+        naga::ShaderStage::Compute => ShaderStage::Compute,
+    }
+}
+
+/// エラーの原因を連結したメッセージ
+fn error_chain(error: &dyn Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(cause) = source {
+        message.push_str(": ");
+        message.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    message
+}
+
+fn diagnostic(message: String, span: Option<Span>, source: &str) -> ShaderDiagnostic {
+    let location = span
+        .filter(|span| span.is_defined() && span.to_range().is_some_and(|range| range.end <= source.len()))
+        .map(|span| span.location(source));
+    ShaderDiagnostic {
+        message,
+        file: None,
+        line: location.map(|location| location.line_number),
+        column: location.map(|location| location.line_position),
+    }
+}
+
+fn with_label(message: String, label: &str) -> String {
+    if label.is_empty() {
+        message
+    } else {
+        format!("{} ({})", message, label)
+    }
+}
+
+/// ソースを解析してnagaのモジュールに変換（検証はしない）
+fn parse_module(source: &ShaderSource) -> Result<Module, ShaderCompilationError> {
+    match source.type_ {
+        ShaderType::Wgsl => front::wgsl::parse_str(&source.source).map_err(|error| {
+            let label = error.labels().next();
+            let message = with_label(error.message().to_string(), label.map_or("", |(_, text)| text));
+            ShaderCompilationError::ParseFailed(vec![diagnostic(message, label.map(|(span, _)| span), &source.source)])
+        }),
+        ShaderType::Glsl => {
+            let options = front::glsl::Options {
+                stage: naga_stage(source.stage)?,
+                defines: source.defines.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+            };
+            front::glsl::Frontend::default().parse(&options, &source.source).map_err(|errors| {
+                ShaderCompilationError::ParseFailed(
+                    errors.into_iter()
+                        .map(|error| diagnostic(error.kind.to_string(), Some(error.meta), &source.source))
+                        .collect(),
+                )
+            })
+        }
+        ShaderType::SpirV => {
+            let bytes = source.bytecode.as_deref().unwrap_or(source.source.as_bytes());
+            front::spv::parse_u8_slice(bytes, &front::spv::Options::default())
+                .map_err(|error| ShaderCompilationError::ValidationFailed(format!("SPIR-Vの解析に失敗: {}", error)))
+        }
+        ShaderType::Hlsl | ShaderType::Metal => Err(ShaderCompilationError::UnsupportedFormat(format!(
+            "{:?} ソースは解析できません", source.type_
+        ))),
+    }
+}
+
+/// ソースを解析して検証する
+pub fn parse(source: &ShaderSource) -> Result<ParsedShader, ShaderCompilationError> {
+    let module = parse_module(source)?;
+    let info = Validator::new(ValidationFlags::all(), Capabilities::all())
+        .validate(&module)
+        .map_err(|error| {
+            let span = error.spans().next();
+            let message = with_label(error_chain(error.as_inner()), span.map_or("", |(_, label)| label.as_str()));
+            // SPIR-Vにはソース上の位置がない
+            let text = if source.type_ == ShaderType::SpirV { "" } else { source.source.as_str() };
+            ShaderCompilationError::InvalidShader(vec![diagnostic(message, span.map(|(span, _)| *span), text)])
+        })?;
+    Ok(ParsedShader { module, info })
+}
+
+/// ソースに含まれるエントリーポイントのステージと名前
+pub fn entry_points(source: &ShaderSource) -> Result<Vec<(ShaderStage, String)>, ShaderCompilationError> {
+    let module = parse_module(source)?;
+    Ok(module.entry_points.iter()
+        .map(|entry| (from_naga_stage(entry.stage), entry.name.clone()))
+        .collect())
+}
+
+/// 解析済みのシェーダーを指定した形式に変換
+pub fn compile(
+    parsed: &ParsedShader,
+    stage: ShaderStage,
+    entry_point: &str,
+    target: ShaderType,
+) -> Result<ShaderOutput, ShaderCompilationError> {
+    let shader_stage = naga_stage(stage)?;
+    let index = parsed.module.entry_points.iter()
+        .position(|entry| entry.stage == shader_stage && entry.name == entry_point)
+        .ok_or_else(|| ShaderCompilationError::CompilationFailed(format!(
+            "エントリーポイントが見つかりません: {} ({:?})", entry_point, stage
+        )))?;
+    let failed = |error: &dyn Error| {
+        ShaderCompilationError::CompilationFailed(format!("{:?} への変換に失敗: {}", target, error_chain(error)))
+    };
+
+    let (data, entry_point) = match target {
+        ShaderType::SpirV => {
+            let pipeline = spv::PipelineOptions { shader_stage, entry_point: entry_point.to_string() };
+            let words = spv::write_vec(&parsed.module, &parsed.info, &spv::Options::default(), Some(&pipeline))
+                .map_err(|e| failed(&e))?;
+            (bytemuck::cast_slice(&words).to_vec(), entry_point.to_string())
+        }
+        ShaderType::Hlsl => {
+            let mut output = String::new();
+            let options = hlsl::Options::default();
+            let info = hlsl::Writer::new(&mut output, &options)
+                .write(&parsed.module, &parsed.info)
+                .map_err(|e| failed(&e))?;
+            let name = translated_name(info.entry_point_names, index).map_err(|e| failed(&e))?;
+            (output.into_bytes(), name.unwrap_or_else(|| entry_point.to_string()))
+        }
+        ShaderType::Metal => {
+            let options = msl::Options { lang_version: (2, 0), ..Default::default() };
+            let (output, info) = msl::write_string(&parsed.module, &parsed.info, &options, &msl::PipelineOptions::default())
+                .map_err(|e| failed(&e))?;
+            let name = translated_name(info.entry_point_names, index).map_err(|e| failed(&e))?;
+            (output.into_bytes(), name.unwrap_or_else(|| entry_point.to_string()))
+        }
+        ShaderType::Glsl => {
+            let mut output = String::new();
+            let options = glsl::Options { version: glsl::Version::Desktop(430), ..Default::default() };
+            let pipeline = glsl::PipelineOptions {
+                shader_stage,
+                entry_point: entry_point.to_string(),
+                multiview: None,
+            };
+            glsl::Writer::new(&mut output, &parsed.module, &parsed.info, &options, &pipeline, Default::default())
+                .and_then(|mut writer| writer.write())
+                .map_err(|e| failed(&e))?;
+            // GLSLのエントリーポイントは常にmain
+            (output.into_bytes(), "main".to_string())
+        }
+        ShaderType::Wgsl => {
+            let output = wgsl::write_string(&parsed.module, &parsed.info, wgsl::WriterFlags::empty())
+                .map_err(|e| failed(&e))?;
+            (output.into_bytes(), entry_point.to_string())
+        }
+    };
+
+    Ok(ShaderOutput {
+        data,
+        entry_point,
+        reflection: reflect(parsed, index),
+    })
+}
+
+/// バックエンドが変更したエントリーポイント名
+fn translated_name<E>(names: Vec<Result<String, E>>, index: usize) -> Result<Option<String>, E> {
+    names.into_iter().nth(index).transpose()
+}
+
+fn vertex_format(inner: &TypeInner) -> Option<(String, u32)> {
+    let (kind, width, count) = match *inner {
+        TypeInner::Scalar { kind, width } => (kind, width, 1),
+        TypeInner::Vector { size, kind, width } => (kind, width, size as u32),
+        _ => return None,
+    };
+    let kind = match kind {
+        ScalarKind::Float => "float",
+        ScalarKind::Sint => "sint",
+        ScalarKind::Uint => "uint",
+        ScalarKind::Bool => return None,
+    };
+    let bits = width as u32 * 8;
+    let format = if count == 1 {
+        format!("{}{}", kind, bits)
+    } else {
+        format!("{}{}x{}", kind, bits, count)
+    };
+    Some((format, width as u32 * count))
+}
+
+/// エントリーポイントが使用するリソースと入力を収集
+fn reflect(parsed: &ParsedShader, index: usize) -> ShaderReflection {
+    let module = &parsed.module;
+    let entry = &module.entry_points[index];
+    let usage = parsed.info.get_entry_point(index);
+    let mut reflection = ShaderReflection {
+        workgroup_size: if entry.stage == naga::ShaderStage::Compute { entry.workgroup_size } else { [0; 3] },
+        ..Default::default()
+    };
+
+    for (handle, variable) in module.global_variables.iter() {
+        if usage[handle].is_empty() {
+            continue;
+        }
+        let Some(binding) = &variable.binding else { continue };
+        let inner = &module.types[variable.ty].inner;
+        let name = variable.name.clone().unwrap_or_default();
+
+        let binding_type = match (variable.space, inner) {
+            (AddressSpace::Uniform, _) => BindingType::UniformBuffer,
+            (AddressSpace::Storage { access }, _) => BindingType::StorageBuffer {
+                read_only: !access.contains(StorageAccess::STORE),
+            },
+            (_, TypeInner::Image { class: ImageClass::Storage { .. }, .. }) => BindingType::StorageTexture,
+            (_, TypeInner::Image { .. }) => BindingType::Texture,
+            (_, TypeInner::Sampler { .. }) => BindingType::Sampler,
+            _ => continue,
+        };
+        let size = match binding_type {
+            BindingType::UniformBuffer | BindingType::StorageBuffer { .. } => Some(inner.size(module.to_ctx())),
+            _ => None,
+        };
+
+        if binding_type == BindingType::UniformBuffer {
+            match inner {
+                TypeInner::Struct { members, .. } => {
+                    for member in members {
+                        let member_size = module.types[member.ty].inner.size(module.to_ctx());
+                        let member_name = member.name.clone().unwrap_or_default();
+                        reflection.uniforms.insert(member_name, (member.offset, member_size));
+                    }
+                }
+                _ => {
+                    reflection.uniforms.insert(name.clone(), (0, inner.size(module.to_ctx())));
+                }
+            }
+        }
+
+        reflection.bindings.push(ShaderBinding {
+            group: binding.group,
+            binding: binding.binding,
+            name,
+            binding_type,
+            size,
+        });
+    }
+    reflection.bindings.sort_by_key(|binding| (binding.group, binding.binding));
+
+    if entry.stage == naga::ShaderStage::Vertex {
+        let mut push_input = |binding: &Option<Binding>, name: &Option<String>, ty| {
+            let Some(Binding::Location { location, .. }) = *binding else { return };
+            if let Some((format, size)) = vertex_format(&module.types[ty].inner) {
+                reflection.vertex_inputs.push(VertexInput {
+                    location,
+                    name: name.clone().unwrap_or_default(),
+                    format,
+                    size,
+                });
+            }
+        };
+        for argument in &entry.function.arguments {
+            match (&argument.binding, &module.types[argument.ty].inner) {
+                (None, TypeInner::Struct { members, .. }) => {
+                    for member in members {
+                        push_input(&member.binding, &member.name, member.ty);
+                    }
+                }
+                _ => push_input(&argument.binding, &argument.name, argument.ty),
+            }
+        }
+        reflection.vertex_inputs.sort_by_key(|input| input.location);
+    }
+
+    reflection
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPRITE_WGSL: &str = "\
+struct Globals {
+    transform: mat4x4<f32>,
+    tint: vec4<f32>,
+}
+
+@group(0) @binding(0) var<uniform> globals: Globals;
+@group(1) @binding(0) var sprite_texture: texture_2d<f32>;
+@group(1) @binding(1) var sprite_sampler: sampler;
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) uv: vec2<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_main(input: VertexInput, @location(2) color: vec4<f32>) -> VertexOutput {
+    var out: VertexOutput;
+    out.position = globals.transform * vec4<f32>(input.position, 0.0, 1.0);
+    out.uv = input.uv;
+    return out;
+}
+
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(sprite_texture, sprite_sampler, input.uv) * globals.tint;
+}
+";
+
+    fn source(type_: ShaderType, stage: ShaderStage, text: &str) -> ShaderSource {
+        ShaderSource {
+            source: text.to_string(),
+            type_,
+            entry_point: "main".to_string(),
+            stage,
+            defines: HashMap::new(),
+            bytecode: None,
+            source_map: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_compile_wgsl_to_all_targets() {
+        let parsed = parse(&source(ShaderType::Wgsl, ShaderStage::Vertex, SPRITE_WGSL)).unwrap();
+        for target in [ShaderType::SpirV, ShaderType::Hlsl, ShaderType::Metal, ShaderType::Glsl, ShaderType::Wgsl] {
+            let output = compile(&parsed, ShaderStage::Fragment, "fs_main", target).unwrap();
+            assert!(!output.data.is_empty(), "{:?}", target);
+        }
+
+        let spirv = compile(&parsed, ShaderStage::Vertex, "vs_main", ShaderType::SpirV).unwrap();
+        assert_eq!(&spirv.data[..4], &0x0723_0203u32.to_le_bytes());
+
+        // 出力したSPIR-Vを読み込み直せる
+        let mut reloaded = source(ShaderType::SpirV, ShaderStage::Vertex, "");
+        reloaded.bytecode = Some(spirv.data);
+        assert_eq!(entry_points(&reloaded).unwrap(), vec![(ShaderStage::Vertex, "vs_main".to_string())]);
+        assert!(parse(&reloaded).is_ok());
+
+        assert!(compile(&parsed, ShaderStage::Vertex, "missing", ShaderType::SpirV).is_err());
+    }
+
+    #[test]
+    fn test_reflection() {
+        let parsed = parse(&source(ShaderType::Wgsl, ShaderStage::Vertex, SPRITE_WGSL)).unwrap();
+
+        let vertex = compile(&parsed, ShaderStage::Vertex, "vs_main", ShaderType::Wgsl).unwrap().reflection;
+        let inputs: Vec<(u32, &str, &str)> = vertex.vertex_inputs.iter()
+            .map(|input| (input.location, input.name.as_str(), input.format.as_str()))
+            .collect();
+        assert_eq!(inputs, vec![(0, "position", "float32x2"), (1, "uv", "float32x2"), (2, "color", "float32x4")]);
+        // 頂点シェーダーはテクスチャを使っていない
+        assert_eq!(vertex.bindings.len(), 1);
+        assert_eq!(vertex.bindings[0].binding_type, BindingType::UniformBuffer);
+        assert_eq!(vertex.bindings[0].size, Some(80));
+        assert_eq!(vertex.uniforms.get("tint"), Some(&(64, 16)));
+
+        let fragment = compile(&parsed, ShaderStage::Fragment, "fs_main", ShaderType::Wgsl).unwrap().reflection;
+        let bindings: Vec<(u32, u32, BindingType)> = fragment.bindings.iter()
+            .map(|binding| (binding.group, binding.binding, binding.binding_type))
+            .collect();
+        assert_eq!(bindings, vec![
+            (0, 0, BindingType::UniformBuffer),
+            (1, 0, BindingType::Texture),
+            (1, 1, BindingType::Sampler),
+        ]);
+        assert!(fragment.vertex_inputs.is_empty());
+    }
+
+    #[test]
+    fn test_diagnostics_report_location() {
+        let broken = "@fragment\nfn main() -> @location(0) vec4<f32> {\n    return vec4<f32>(1.0, 0.0, 0.0, 1.0)\n}\n";
+        let error = parse(&source(ShaderType::Wgsl, ShaderStage::Fragment, broken)).unwrap_err();
+        let diagnostics = error.diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].line, Some(4));
+
+        // 構文は正しいが型が合わない
+        let invalid = "@fragment\nfn main() -> @location(0) vec4<f32> {\n    let x: f32 = 1.0;\n    return x;\n}\n";
+        let error = parse(&source(ShaderType::Wgsl, ShaderStage::Fragment, invalid)).unwrap_err();
+        assert!(matches!(error, ShaderCompilationError::InvalidShader(_)));
+        assert!(error.diagnostics()[0].line.is_some());
+
+        let glsl = "#version 450\nvoid main() {\n    float x = ;\n}\n";
+        let error = parse(&source(ShaderType::Glsl, ShaderStage::Fragment, glsl)).unwrap_err();
+        assert_eq!(error.diagnostics()[0].line, Some(3));
+        assert_eq!(error.diagnostics()[0].column, Some(15));
+    }
+
+    #[test]
+    fn test_glsl_to_spirv() {
+        let glsl = "\
+#version 450
+layout(location = 0) in vec3 position;
+layout(set = 0, binding = 0) uniform Camera { mat4 view_projection; float scale; };
+void main() {
+    gl_Position = view_projection * vec4(position * SCALE, 1.0);
+}
+";
+        let mut vertex = source(ShaderType::Glsl, ShaderStage::Vertex, glsl);
+        // 未定義のマクロは構文エラーになる
+        assert!(parse(&vertex).is_err());
+
+        vertex.defines.insert("SCALE".to_string(), "scale".to_string());
+        let parsed = parse(&vertex).unwrap();
+        let output = compile(&parsed, ShaderStage::Vertex, "main", ShaderType::SpirV).unwrap();
+        assert_eq!(output.reflection.vertex_inputs[0].format, "float32x3");
+        assert_eq!(output.reflection.uniforms.get("scale"), Some(&(64, 4)));
+
+        assert!(matches!(
+            parse(&source(ShaderType::Glsl, ShaderStage::Geometry, glsl)),
+            Err(ShaderCompilationError::InvalidStage(_))
+        ));
+    }
+}
@@ -0,0 +1,215 @@
+// LumosDesktop シェーダーインクルード
+// `#include` の展開と、展開後の行から元のファイルの行への対応付け
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::shader_manager::{ShaderCompilationError, ShaderDiagnostic};
+
+/// 展開後のソースの各行がどのファイルの何行目に由来するかの対応表
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceMap {
+    /// 先頭がメインのファイル、以降がインクルードされたファイル
+    files: Vec<PathBuf>,
+    /// 展開後の行ごとの (ファイル番号, 1始まりの行番号)
+    lines: Vec<(usize, u32)>,
+}
+
+impl SourceMap {
+    /// インクルードを含まないファイルの対応表
+    pub fn for_file<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            files: vec![path.as_ref().to_path_buf()],
+            lines: Vec::new(),
+        }
+    }
+
+    /// メインのファイルとインクルードされたすべてのファイル
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    pub fn main_file(&self) -> Option<&Path> {
+        self.files.first().map(PathBuf::as_path)
+    }
+
+    /// インクルードされたファイル
+    pub fn dependencies(&self) -> &[PathBuf] {
+        self.files.get(1..).unwrap_or(&[])
+    }
+
+    /// 展開後の行番号を元のファイルと行番号に変換
+    pub fn resolve(&self, line: u32) -> Option<(&Path, u32)> {
+        match self.lines.get((line as usize).checked_sub(1)?) {
+            Some(&(file, original)) => Some((self.files[file].as_path(), original)),
+            // 対応表がない場合は展開されていない
+            None if self.lines.is_empty() => self.main_file().map(|file| (file, line)),
+            None => None,
+        }
+    }
+
+    /// 診断メッセージの位置を元のファイルの位置に置き換える
+    pub fn remap(&self, error: ShaderCompilationError) -> ShaderCompilationError {
+        let remap_all = |diagnostics: Vec<ShaderDiagnostic>| -> Vec<ShaderDiagnostic> {
+            diagnostics.into_iter().map(|mut diagnostic| {
+                let resolved = diagnostic.line.and_then(|line| self.resolve(line));
+                match resolved {
+                    Some((file, line)) => {
+                        diagnostic.file = Some(file.to_path_buf());
+                        diagnostic.line = Some(line);
+                    }
+                    None => diagnostic.file = self.main_file().map(Path::to_path_buf),
+                }
+                diagnostic
+            }).collect()
+        };
+        match error {
+            ShaderCompilationError::ParseFailed(diagnostics) => ShaderCompilationError::ParseFailed(remap_all(diagnostics)),
+            ShaderCompilationError::InvalidShader(diagnostics) => ShaderCompilationError::InvalidShader(remap_all(diagnostics)),
+            other => other,
+        }
+    }
+}
+
+/// インクルードを展開したソース
+#[derive(Debug, Clone)]
+pub struct ExpandedSource {
+    pub source: String,
+    pub source_map: SourceMap,
+}
+
+/// `#include "name"` または `#include <name>` の行からファイル名と列を取り出す
+fn parse_include(line: &str) -> Option<(&str, u32)> {
+    let rest = line.trim_start().strip_prefix("#include")?;
+    let rest_trimmed = rest.trim_start();
+    let close = match rest_trimmed.chars().next()? {
+        '"' => '"',
+        '<' => '>',
+        _ => return None,
+    };
+    let name = &rest_trimmed[1..];
+    let end = name.find(close)?;
+    let column = (line.len() - name.len()) as u32 + 1;
+    Some((&name[..end], column))
+}
+
+/// インクルードするファイルを、インクルード元のディレクトリ、検索パスの順に探す
+fn resolve_include(name: &str, including_file: &Path, search_paths: &[PathBuf]) -> Option<PathBuf> {
+    let local = including_file.parent().map(|dir| dir.join(name));
+    local.into_iter()
+        .chain(search_paths.iter().map(|dir| dir.join(name)))
+        .find(|path| path.is_file())
+}
+
+struct Expander<'a> {
+    search_paths: &'a [PathBuf],
+    source: String,
+    source_map: SourceMap,
+    included: HashSet<PathBuf>,
+}
+
+impl Expander<'_> {
+    fn expand(&mut self, path: &Path, text: &str) -> Result<(), ShaderCompilationError> {
+        // 同じファイルは一度だけ展開する（循環インクルードも防ぐ）
+        let key = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        if !self.included.insert(key) {
+            return Ok(());
+        }
+        let file = self.source_map.files.len();
+        self.source_map.files.push(path.to_path_buf());
+
+        for (index, line) in text.lines().enumerate() {
+            let line_number = index as u32 + 1;
+            let Some((name, column)) = parse_include(line) else {
+                self.source.push_str(line);
+                self.source.push('\n');
+                self.source_map.lines.push((file, line_number));
+                continue;
+            };
+
+            let not_found = || ShaderCompilationError::ParseFailed(vec![ShaderDiagnostic {
+                message: format!("インクルードファイルが見つかりません: {}", name),
+                file: Some(path.to_path_buf()),
+                line: Some(line_number),
+                column: Some(column),
+            }]);
+            let include_path = resolve_include(name, path, self.search_paths).ok_or_else(not_found)?;
+            let include_text = fs::read_to_string(&include_path).map_err(|_| not_found())?;
+            self.expand(&include_path, &include_text)?;
+        }
+        Ok(())
+    }
+}
+
+/// ファイルを読み込んで `#include` を展開
+///
+/// インクルードは展開元のファイルと同じディレクトリ、`search_paths` の順に探します。
+/// 同じファイルは最初の1回だけ展開されます。
+pub fn expand_file(path: &Path, search_paths: &[PathBuf]) -> Result<ExpandedSource, ShaderCompilationError> {
+    let text = fs::read_to_string(path)?;
+    expand_source(path, &text, search_paths)
+}
+
+/// 読み込み済みのソースの `#include` を展開
+pub fn expand_source(path: &Path, text: &str, search_paths: &[PathBuf]) -> Result<ExpandedSource, ShaderCompilationError> {
+    let mut expander = Expander {
+        search_paths,
+        source: String::with_capacity(text.len()),
+        source_map: SourceMap::default(),
+        included: HashSet::new(),
+    };
+    expander.expand(path, text)?;
+    Ok(ExpandedSource {
+        source: expander.source,
+        source_map: expander.source_map,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_includes() {
+        let dir = tempfile::tempdir().unwrap();
+        let shared = dir.path().join("shared");
+        fs::create_dir(&shared).unwrap();
+        fs::write(shared.join("color.wgsl"), "#include \"../math.wgsl\"\nfn tint() -> f32 { return 0.5; }\n").unwrap();
+        fs::write(dir.path().join("math.wgsl"), "const PI: f32 = 3.14;\n").unwrap();
+        let main = dir.path().join("main.wgsl");
+        fs::write(&main, "#include <color.wgsl>\n#include \"math.wgsl\"\nfn main() {}\n").unwrap();
+
+        // color.wgslは検索パスから、math.wgslはインクルード元からの相対パスで見つかり、2回目は展開されない
+        let expanded = expand_file(&main, std::slice::from_ref(&shared)).unwrap();
+        assert_eq!(expanded.source, "const PI: f32 = 3.14;\nfn tint() -> f32 { return 0.5; }\nfn main() {}\n");
+        assert_eq!(expanded.source_map.dependencies(), &[shared.join("color.wgsl"), shared.join("../math.wgsl")]);
+        assert_eq!(expanded.source_map.resolve(1), Some((shared.join("../math.wgsl").as_path(), 1)));
+        assert_eq!(expanded.source_map.resolve(3), Some((main.as_path(), 3)));
+        assert_eq!(expanded.source_map.resolve(4), None);
+
+        let error = expand_file(&main, &[]).unwrap_err();
+        let diagnostic = &error.diagnostics()[0];
+        assert_eq!(diagnostic.file.as_deref(), Some(main.as_path()));
+        assert_eq!((diagnostic.line, diagnostic.column), (Some(1), Some(11)));
+    }
+
+    #[test]
+    fn test_remap_diagnostics() {
+        let source_map = SourceMap {
+            files: vec![PathBuf::from("main.wgsl"), PathBuf::from("common.wgsl")],
+            lines: vec![(1, 1), (1, 2), (0, 2)],
+        };
+        let error = ShaderCompilationError::ParseFailed(vec![ShaderDiagnostic {
+            message: "expected ';'".to_string(),
+            file: None,
+            line: Some(2),
+            column: Some(7),
+        }]);
+        let remapped = source_map.remap(error);
+        let diagnostic = &remapped.diagnostics()[0];
+        assert_eq!(diagnostic.file.as_deref(), Some(Path::new("common.wgsl")));
+        assert_eq!((diagnostic.line, diagnostic.column), (Some(2), Some(7)));
+        assert!(remapped.to_string().contains("common.wgsl"));
+    }
+}
@@ -1,16 +1,47 @@
 // LumosDesktop シェーダーマネージャー
 // シェーダーの管理、コンパイル、キャッシュを担当
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::fs;
 use std::io;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
 
 use super::{GraphicsApi, GraphicsError};
 use super::renderer::ShaderStage;
+use super::shader_cache::ShaderCache;
+use super::shader_compiler::{self, ShaderReflection};
+use super::shader_include::{self, SourceMap};
+use super::shader_watcher::ShaderWatcher;
+
+/// 位置情報付きのシェーダー診断メッセージ
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShaderDiagnostic {
+    pub message: String,
+    /// エラーのあるファイル（インクルードされたファイルの場合もある）
+    pub file: Option<PathBuf>,
+    /// 1始まりの行番号（位置が分からない場合は `None`）
+    pub line: Option<u32>,
+    /// 1始まりの列番号
+    pub column: Option<u32>,
+}
+
+impl fmt::Display for ShaderDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{} ", file.display())?;
+        }
+        match (self.line, self.column) {
+            (Some(line), Some(column)) => write!(f, "{}行{}列: {}", line, column, self.message),
+            (Some(line), None) => write!(f, "{}行: {}", line, self.message),
+            _ => write!(f, "{}", self.message),
+        }
+    }
+}
 
 /// シェーダーコンパイルエラー
 #[derive(Debug)]
@@ -20,6 +51,25 @@ pub enum ShaderCompilationError {
     UnsupportedFormat(String),
     InvalidStage(String),
     ValidationFailed(String),
+    /// ソースの構文エラー
+    ParseFailed(Vec<ShaderDiagnostic>),
+    /// 型やリソースの使い方が正しくない
+    InvalidShader(Vec<ShaderDiagnostic>),
+}
+
+impl ShaderCompilationError {
+    /// 位置情報付きの診断メッセージ（構文エラーと検証エラー以外は空）
+    pub fn diagnostics(&self) -> &[ShaderDiagnostic] {
+        match self {
+            ShaderCompilationError::ParseFailed(diagnostics)
+            | ShaderCompilationError::InvalidShader(diagnostics) => diagnostics,
+            _ => &[],
+        }
+    }
+}
+
+fn join_diagnostics(diagnostics: &[ShaderDiagnostic]) -> String {
+    diagnostics.iter().map(|d| d.to_string()).collect::<Vec<_>>().join("; ")
 }
 
 impl fmt::Display for ShaderCompilationError {
@@ -30,6 +80,8 @@ impl fmt::Display for ShaderCompilationError {
             ShaderCompilationError::UnsupportedFormat(msg) => write!(f, "サポートされていないフォーマット: {}", msg),
             ShaderCompilationError::InvalidStage(msg) => write!(f, "無効なシェーダーステージ: {}", msg),
             ShaderCompilationError::ValidationFailed(msg) => write!(f, "検証失敗: {}", msg),
+            ShaderCompilationError::ParseFailed(diagnostics) => write!(f, "構文エラー: {}", join_diagnostics(diagnostics)),
+            ShaderCompilationError::InvalidShader(diagnostics) => write!(f, "検証失敗: {}", join_diagnostics(diagnostics)),
         }
     }
 }
@@ -47,7 +99,7 @@ impl From<ShaderCompilationError> for GraphicsError {
 }
 
 /// シェーダー種類
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum ShaderType {
     Glsl,
     Hlsl,
@@ -88,7 +140,12 @@ pub struct ShaderSource {
     pub type_: ShaderType,
     pub entry_point: String,
     pub stage: ShaderStage,
+    /// GLSLのプリプロセッサ定義
     pub defines: HashMap<String, String>,
+    /// SPIR-Vなどバイナリ形式のソース
+    pub bytecode: Option<Vec<u8>>,
+    /// 読み込んだファイルとインクルードの対応表（ファイルから読み込んでいない場合は空）
+    pub source_map: SourceMap,
 }
 
 /// コンパイル済みシェーダー
@@ -96,9 +153,12 @@ pub struct ShaderSource {
 pub struct CompiledShader {
     pub data: Vec<u8>,
     pub format: ShaderType,
+    /// 出力形式でのエントリーポイント名
     pub entry_point: String,
     pub stage: ShaderStage,
     pub timestamp: SystemTime,
+    /// バインディングと頂点入力（HLSL、Metalのソースをそのまま使う場合は空）
+    pub reflection: ShaderReflection,
 }
 
 /// シェーダー情報
@@ -109,41 +169,47 @@ pub struct Shader {
     pub compiled: HashMap<(GraphicsApi, ShaderStage), CompiledShader>,
     pub uniforms: HashMap<String, (u32, u32)>, // (offset, size)
     pub last_modified: SystemTime,
+    /// ソースの最終更新時刻（再コンパイルの判定に使う）
+    pub source_modified: SystemTime,
     pub metadata: HashMap<String, String>,
 }
 
 impl Shader {
     pub fn new(name: &str) -> Self {
+        let now = SystemTime::now();
         Self {
             name: name.to_string(),
             sources: HashMap::new(),
             compiled: HashMap::new(),
             uniforms: HashMap::new(),
-            last_modified: SystemTime::now(),
+            last_modified: now,
+            source_modified: now,
             metadata: HashMap::new(),
         }
     }
     
     pub fn add_source(&mut self, stage: ShaderStage, source: ShaderSource) {
         self.sources.insert(stage, source);
-        self.last_modified = SystemTime::now();
+        self.source_modified = SystemTime::now();
+        self.last_modified = self.source_modified;
     }
     
     pub fn add_compiled(&mut self, api: GraphicsApi, stage: ShaderStage, compiled: CompiledShader) {
+        self.uniforms.extend(compiled.reflection.uniforms.iter().map(|(name, &range)| (name.clone(), range)));
         self.compiled.insert((api, stage), compiled);
         self.last_modified = SystemTime::now();
     }
     
     pub fn needs_recompile(&self, api: GraphicsApi, stage: ShaderStage) -> bool {
         if let Some(compiled) = self.compiled.get(&(api, stage)) {
-            if let Some(source) = self.sources.get(&stage) {
+            if self.sources.contains_key(&stage) {
                 // ソースのタイムスタンプがコンパイル済みシェーダーより新しい場合
-                if self.last_modified > compiled.timestamp {
+                if self.source_modified > compiled.timestamp {
                     return true;
                 }
                 
-                // シェーダータイプが互換性がない場合
-                if !source.type_.is_compatible_with(api) {
+                // コンパイル済みシェーダーの形式がAPIと互換性がない場合
+                if !compiled.format.is_compatible_with(api) {
                     return true;
                 }
             } else {
@@ -163,6 +229,17 @@ impl Shader {
     }
 }
 
+/// シェーダーの再読み込み結果
+#[derive(Debug)]
+pub enum ShaderReloadEvent {
+    /// 再コンパイルに成功した（パイプラインの再作成が必要）
+    Reloaded { name: String, stages: Vec<ShaderStage> },
+    /// 検索パスに追加されたファイルを新しいシェーダーとして読み込んだ
+    Added { name: String, stages: Vec<ShaderStage> },
+    /// 再コンパイルに失敗した（以前のバージョンを使い続ける）
+    Failed { name: String, error: GraphicsError },
+}
+
 /// シェーダーマネージャー
 #[derive(Debug)]
 pub struct ShaderManager {
@@ -171,35 +248,70 @@ pub struct ShaderManager {
     current_api: GraphicsApi,
     device_info: HashMap<String, String>,
     compiler_version: String,
+    watcher: ShaderWatcher,
+    hot_reload: bool,
+    /// ディスク上のコンパイル済みシェーダーキャッシュ（`None` の場合は毎回コンパイル）
+    disk_cache: Option<ShaderCache>,
 }
 
 impl ShaderManager {
     /// 新しいシェーダーマネージャーを作成
     pub fn new(api: GraphicsApi, device_info: HashMap<String, String>) -> Result<Self, GraphicsError> {
-        Ok(Self {
+        let mut manager = Self {
             shaders: HashMap::new(),
             search_paths: vec![PathBuf::from("shaders")],
             current_api: api,
             device_info,
-            compiler_version: "1.0.0".to_string(), // ダミーバージョン
-        })
+            compiler_version: shader_compiler::COMPILER_VERSION.to_string(),
+            watcher: ShaderWatcher::default(),
+            hot_reload: true,
+            disk_cache: None,
+        };
+        manager.update_watch_list();
+        Ok(manager)
     }
     
     /// 検索パスを追加
     pub fn add_search_path<P: AsRef<Path>>(&mut self, path: P) {
         self.search_paths.push(PathBuf::from(path.as_ref()));
+        self.update_watch_list();
     }
     
-    /// ファイルからシェーダーを読み込む
-    pub fn load_shader<P: AsRef<Path>>(&mut self, path: P, name: Option<&str>) -> Result<String, GraphicsError> {
-        let path = path.as_ref();
-        let shader_name = name.map(|s| s.to_string()).unwrap_or_else(|| {
-            path.file_stem()
-                .and_then(|s| s.to_str())
-                .map(|s| s.to_string())
-                .unwrap_or_else(|| "unnamed".to_string())
-        });
-        
+    /// ディスクキャッシュを設定（`None` で無効）
+    pub fn set_disk_cache(&mut self, cache: Option<ShaderCache>) {
+        self.disk_cache = cache;
+    }
+    
+    pub fn disk_cache(&self) -> Option<&ShaderCache> {
+        self.disk_cache.as_ref()
+    }
+    
+    /// ホットリロードを有効/無効にする
+    pub fn set_hot_reload(&mut self, enabled: bool) {
+        self.hot_reload = enabled;
+    }
+    
+    pub fn is_hot_reload_enabled(&self) -> bool {
+        self.hot_reload
+    }
+    
+    /// ファイルの変更を確認する間隔を設定
+    pub fn set_watch_interval(&mut self, interval: Duration) {
+        self.watcher.set_interval(interval);
+    }
+    
+    /// 相対パスが見つからない場合は検索パスから探す
+    fn resolve_path(&self, path: &Path) -> PathBuf {
+        if path.is_relative() && !path.exists() {
+            if let Some(found) = self.search_paths.iter().map(|dir| dir.join(path)).find(|path| path.is_file()) {
+                return found;
+            }
+        }
+        path.to_path_buf()
+    }
+    
+    /// ファイルを読み込み、含まれるステージごとのソースを作成
+    fn read_sources(&self, path: &Path) -> Result<Vec<ShaderSource>, GraphicsError> {
         let extension = path.extension()
             .and_then(|s| s.to_str())
             .ok_or_else(|| GraphicsError::Shader("シェーダーファイルに拡張子がありません".to_string()))?;
@@ -207,136 +319,338 @@ impl ShaderManager {
         let shader_type = ShaderType::from_extension(extension)
             .ok_or_else(|| GraphicsError::Shader(format!("未知のシェーダー拡張子: {}", extension)))?;
         
-        let stage = match extension {
-            "vert" | "vs" => ShaderStage::Vertex,
-            "frag" | "ps" => ShaderStage::Fragment,
-            "geom" | "gs" => ShaderStage::Geometry,
-            "comp" | "cs" => ShaderStage::Compute,
-            "tesc" | "hs" => ShaderStage::TessControl,
-            "tese" | "ds" => ShaderStage::TessEvaluation,
-            _ => {
-                return Err(GraphicsError::Shader(format!(
-                    "拡張子からシェーダーステージを判断できません: {}", extension
-                )));
-            }
+        let extension_stage = match extension {
+            "vert" | "vs" => Some(ShaderStage::Vertex),
+            "frag" | "ps" => Some(ShaderStage::Fragment),
+            "geom" | "gs" => Some(ShaderStage::Geometry),
+            "comp" | "cs" => Some(ShaderStage::Compute),
+            "tesc" | "hs" => Some(ShaderStage::TessControl),
+            "tese" | "ds" => Some(ShaderStage::TessEvaluation),
+            _ => None,
         };
         
-        let source = fs::read_to_string(path)
-            .map_err(|e| GraphicsError::Shader(format!("シェーダーファイルの読み込みに失敗: {}", e)))?;
-        
-        let mut shader = if let Some(existing) = self.shaders.get_mut(&shader_name) {
-            existing.clone()
+        let (source, bytecode, source_map) = if shader_type == ShaderType::SpirV {
+            let bytes = fs::read(path)
+                .map_err(|e| GraphicsError::Shader(format!("シェーダーファイルの読み込みに失敗: {}", e)))?;
+            (String::new(), Some(bytes), SourceMap::for_file(path))
         } else {
-            Shader::new(&shader_name)
+            let expanded = shader_include::expand_file(path, &self.search_paths)?;
+            (expanded.source, None, expanded.source_map)
         };
         
-        let shader_source = ShaderSource {
+        let base_source = ShaderSource {
             source,
             type_: shader_type,
             entry_point: "main".to_string(), // デフォルトエントリーポイント
-            stage,
+            stage: extension_stage.unwrap_or(ShaderStage::Vertex),
             defines: HashMap::new(),
+            bytecode,
+            source_map,
         };
         
-        shader.add_source(stage, shader_source);
-        self.shaders.insert(shader_name.clone(), shader);
+        // WGSLとSPIR-Vは1つのファイルに複数のステージを含むため、エントリーポイントからステージを決める
+        let entry_points = match (shader_type, extension_stage) {
+            (ShaderType::Wgsl | ShaderType::SpirV, _) => shader_compiler::entry_points(&base_source)
+                .map_err(|e| base_source.source_map.remap(e))?,
+            (_, Some(stage)) => vec![(stage, "main".to_string())],
+            (_, None) => {
+                return Err(GraphicsError::Shader(format!(
+                    "拡張子からシェーダーステージを判断できません: {}", extension
+                )));
+            }
+        };
+        if entry_points.is_empty() {
+            return Err(GraphicsError::Shader(format!("エントリーポイントがありません: {}", path.display())));
+        }
         
-        // この時点でコンパイルを試みる
-        let _ = self.compile_shader(&shader_name, stage);
+        Ok(entry_points.into_iter()
+            .map(|(stage, entry_point)| ShaderSource {
+                entry_point,
+                stage,
+                ..base_source.clone()
+            })
+            .collect())
+    }
+    
+    /// ファイルから読み込んだソースでシェーダーのソースを置き換え、置き換えたステージを返す
+    fn replace_sources(shader: &mut Shader, path: &Path, sources: Vec<ShaderSource>) -> Vec<ShaderStage> {
+        let stages: Vec<ShaderStage> = sources.iter().map(|source| source.stage).collect();
+        
+        // ファイルから削除されたエントリーポイントのステージを取り除く
+        let removed: Vec<ShaderStage> = shader.sources.iter()
+            .filter(|(stage, source)| source.source_map.main_file() == Some(path) && !stages.contains(stage))
+            .map(|(stage, _)| *stage)
+            .collect();
+        for stage in removed {
+            shader.sources.remove(&stage);
+            shader.compiled.retain(|(_, compiled_stage), _| *compiled_stage != stage);
+        }
+        
+        for source in sources {
+            shader.add_source(source.stage, source);
+        }
+        stages
+    }
+    
+    /// 読み込んだファイルとインクルード、検索パスのディレクトリをすべて監視する
+    fn update_watch_list(&mut self) {
+        let files: Vec<PathBuf> = self.used_files().into_iter().collect();
+        self.watcher.watch_only(files.iter());
+        for dir in &self.search_paths {
+            self.watcher.watch_dir(dir);
+        }
+    }
+    
+    /// 読み込んだシェーダーが使っているファイル
+    fn used_files(&self) -> HashSet<PathBuf> {
+        self.shaders.values()
+            .flat_map(|shader| shader.sources.values())
+            .flat_map(|source| source.source_map.files().iter().cloned())
+            .collect()
+    }
+    
+    fn shader_name_for(path: &Path) -> String {
+        path.file_stem()
+            .and_then(|s| s.to_str())
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| "unnamed".to_string())
+    }
+    
+    /// ファイルから読み込んだソースをシェーダーに登録し、置き換えたステージを返す
+    fn insert_sources(&mut self, name: &str, path: &Path, sources: Vec<ShaderSource>) -> Vec<ShaderStage> {
+        let mut shader = if let Some(existing) = self.shaders.get_mut(name) {
+            existing.clone()
+        } else {
+            Shader::new(name)
+        };
+        let stages = Self::replace_sources(&mut shader, path, sources);
+        self.shaders.insert(name.to_string(), shader);
+        self.update_watch_list();
+        stages
+    }
+    
+    /// ファイルからシェーダーを読み込む
+    ///
+    /// 相対パスが見つからない場合は検索パスから探します。読み込んだファイルとインクルードは
+    /// ホットリロードの監視対象になり、コンパイルに失敗した場合も修正されると再読み込みされます。
+    pub fn load_shader<P: AsRef<Path>>(&mut self, path: P, name: Option<&str>) -> Result<String, GraphicsError> {
+        let path = self.resolve_path(path.as_ref());
+        let shader_name = name.map(|s| s.to_string()).unwrap_or_else(|| Self::shader_name_for(&path));
+        
+        let sources = self.read_sources(&path)?;
+        let stages = self.insert_sources(&shader_name, &path, sources);
+        
+        // 描画時ではなく読み込み時にエラーを報告する
+        for stage in stages {
+            self.compile_shader(&shader_name, stage)?;
+        }
         
         Ok(shader_name)
     }
     
+    /// 変更されたファイルを使っているシェーダーを再読み込み
+    ///
+    /// レンダースレッドから毎フレーム呼び出すことを想定しています。ファイルの確認は
+    /// `set_watch_interval` で設定した間隔ごとに行います。再コンパイルに失敗した場合は
+    /// 以前のバージョンを使い続け、ファイルが再び変更されたときに再試行します。
+    ///
+    /// 検索パスのディレクトリに作成された（名前を変更されたものを含む）シェーダーファイルは
+    /// ファイル名をシェーダー名として読み込みます。エントリーポイントのないインクルード用の
+    /// ファイルなど、シェーダーとして読み込めないファイルは無視します。
+    pub fn poll_changes(&mut self) -> Vec<ShaderReloadEvent> {
+        if !self.hot_reload {
+            return Vec::new();
+        }
+        let changed = self.watcher.poll();
+        if changed.is_empty() {
+            return Vec::new();
+        }
+        
+        // どのシェーダーも使っていない、検索パス直下のシェーダーファイル
+        let used_files = self.used_files();
+        let added: Vec<PathBuf> = changed.iter()
+            .filter(|path| !used_files.contains(*path) && path.is_file())
+            .filter(|path| path.parent().is_some_and(|dir| self.search_paths.iter().any(|search| search == dir)))
+            .filter(|path| path.extension().and_then(|s| s.to_str()).and_then(ShaderType::from_extension).is_some())
+            .cloned()
+            .collect();
+        
+        // 変更されたファイルをインクルードしているソースも含めて、シェーダーごとに読み込み直すファイルを集める
+        let mut affected: Vec<(String, Vec<PathBuf>)> = Vec::new();
+        for (name, shader) in &self.shaders {
+            let mut files: Vec<PathBuf> = Vec::new();
+            for source in shader.sources.values() {
+                let Some(main_file) = source.source_map.main_file() else { continue };
+                let uses_changed = source.source_map.files().iter().any(|file| changed.contains(file));
+                if uses_changed && !files.iter().any(|file| file == main_file) {
+                    files.push(main_file.to_path_buf());
+                }
+            }
+            if !files.is_empty() {
+                affected.push((name.clone(), files));
+            }
+        }
+        affected.sort();
+        
+        let api = self.current_api;
+        let mut events = Vec::new();
+        for (name, files) in affected {
+            let Some(current) = self.shaders.get(&name) else { continue };
+            
+            // 新しいバージョンはすべてのステージのコンパイルに成功した場合だけ反映する
+            let mut candidate = current.clone();
+            let result = files.iter()
+                .try_fold(Vec::new(), |mut stages, file| {
+                    let sources = self.read_sources(file)?;
+                    stages.extend(Self::replace_sources(&mut candidate, file, sources));
+                    Ok(stages)
+                })
+                .and_then(|stages| {
+                    let all_stages: Vec<ShaderStage> = candidate.sources.keys().copied().collect();
+                    for stage in all_stages {
+                        Self::compile_stage(
+                            &mut candidate, api, stage, &self.device_info, &self.compiler_version, self.disk_cache.as_mut(),
+                        )?;
+                    }
+                    Ok(stages)
+                });
+            
+            match result {
+                Ok(stages) => {
+                    log::info!("シェーダーを再読み込みしました: {}", name);
+                    self.shaders.insert(name.clone(), candidate);
+                    events.push(ShaderReloadEvent::Reloaded { name, stages });
+                }
+                Err(error) => {
+                    log::warn!("シェーダーの再読み込みに失敗しました（以前のバージョンを使用します）: {}", error);
+                    events.push(ShaderReloadEvent::Failed { name, error });
+                }
+            }
+        }
+        
+        for path in added {
+            let sources = match self.read_sources(&path) {
+                Ok(sources) => sources,
+                Err(e) => {
+                    log::debug!("シェーダーとして読み込めないファイルを無視します: {}: {}", path.display(), e);
+                    continue;
+                }
+            };
+            let name = Self::shader_name_for(&path);
+            let existed = self.shaders.contains_key(&name);
+            let stages = self.insert_sources(&name, &path, sources);
+            let result = stages.iter().try_for_each(|stage| self.compile_shader(&name, *stage));
+            match result {
+                Ok(()) if existed => events.push(ShaderReloadEvent::Reloaded { name, stages }),
+                Ok(()) => {
+                    log::info!("シェーダーを追加しました: {}", name);
+                    events.push(ShaderReloadEvent::Added { name, stages });
+                }
+                Err(error) => {
+                    log::warn!("追加されたシェーダーのコンパイルに失敗しました: {}", error);
+                    events.push(ShaderReloadEvent::Failed { name, error });
+                }
+            }
+        }
+        
+        self.update_watch_list();
+        events
+    }
+    
     /// シェーダーをコンパイル
     pub fn compile_shader(&mut self, name: &str, stage: ShaderStage) -> Result<(), GraphicsError> {
+        let api = self.current_api;
         let shader = self.shaders.get_mut(name).ok_or_else(|| {
             GraphicsError::Shader(format!("シェーダーが見つかりません: {}", name))
         })?;
         
-        if !shader.needs_recompile(self.current_api, stage) {
+        Self::compile_stage(shader, api, stage, &self.device_info, &self.compiler_version, self.disk_cache.as_mut())
+    }
+    
+    fn compile_stage(
+        shader: &mut Shader,
+        api: GraphicsApi,
+        stage: ShaderStage,
+        device_info: &HashMap<String, String>,
+        compiler_version: &str,
+        disk_cache: Option<&mut ShaderCache>,
+    ) -> Result<(), GraphicsError> {
+        if !shader.needs_recompile(api, stage) {
             return Ok(());
         }
         
         let source = shader.sources.get(&stage).ok_or_else(|| {
-            GraphicsError::Shader(format!("シェーダーソースが見つかりません: {} (ステージ: {:?})", name, stage))
+            GraphicsError::Shader(format!("シェーダーソースが見つかりません: {} (ステージ: {:?})", shader.name, stage))
         })?;
         
-        let compiled = match (source.type_, self.current_api) {
-            (ShaderType::Glsl, GraphicsApi::Vulkan) => {
-                self.compile_glsl_to_spirv(source)?
-            }
-            (ShaderType::Hlsl, GraphicsApi::DirectX) => {
-                self.compile_hlsl(source)?
-            }
-            (ShaderType::Metal, GraphicsApi::Metal) => {
-                self.compile_metal(source)?
-            }
-            (ShaderType::SpirV, GraphicsApi::Vulkan) => {
-                // SPIRVはすでにコンパイル済み、検証のみ
-                self.validate_spirv(source)?
-            }
-            _ => {
-                return Err(GraphicsError::Shader(format!(
-                    "互換性のないシェーダー形式とAPI: {:?} と {:?}", 
-                    source.type_, self.current_api
-                )));
-            }
-        };
-        
-        shader.add_compiled(self.current_api, stage, compiled);
+        let compiled = Self::compile_cached(source, api, device_info, compiler_version, disk_cache)
+            .map_err(|e| GraphicsError::Shader(format!("{} (ステージ: {:?}): {}", shader.name, stage, e)))?;
+        shader.add_compiled(api, stage, compiled);
         Ok(())
     }
     
-    /// GLSLをSPIR-Vにコンパイル
-    fn compile_glsl_to_spirv(&self, source: &ShaderSource) -> Result<CompiledShader, GraphicsError> {
-        // 実際のコンパイルはシェーダーコンパイラライブラリを使用する
-        // ここではダミーの実装
-        Ok(CompiledShader {
-            data: source.source.as_bytes().to_vec(), // ダミーデータ
-            format: ShaderType::SpirV,
-            entry_point: source.entry_point.clone(),
-            stage: source.stage,
-            timestamp: SystemTime::now(),
-        })
-    }
-    
-    /// HLSLをコンパイル
-    fn compile_hlsl(&self, source: &ShaderSource) -> Result<CompiledShader, GraphicsError> {
-        // 実際のコンパイルはDXC等を使用する
-        // ここではダミーの実装
-        Ok(CompiledShader {
-            data: source.source.as_bytes().to_vec(), // ダミーデータ
-            format: ShaderType::Hlsl,
-            entry_point: source.entry_point.clone(),
-            stage: source.stage,
-            timestamp: SystemTime::now(),
-        })
+    /// ディスクキャッシュにあればそれを使い、なければコンパイルしてキャッシュに保存
+    ///
+    /// 解析できない形式のソースはそのまま渡すだけなのでキャッシュしません。
+    fn compile_cached(
+        source: &ShaderSource,
+        api: GraphicsApi,
+        device_info: &HashMap<String, String>,
+        compiler_version: &str,
+        disk_cache: Option<&mut ShaderCache>,
+    ) -> Result<CompiledShader, ShaderCompilationError> {
+        let Some(cache) = disk_cache.filter(|_| shader_compiler::can_parse(source.type_)) else {
+            return Self::compile_source(source, api);
+        };
+        
+        let key = ShaderCache::key(source, api, device_info, compiler_version);
+        if let Some(compiled) = cache.load(key) {
+            return Ok(compiled);
+        }
+        
+        let compiled = Self::compile_source(source, api)?;
+        if let Err(e) = cache.store(key, &compiled) {
+            log::warn!("シェーダーキャッシュへの保存に失敗しました: {}", e);
+        }
+        Ok(compiled)
     }
     
-    /// Metalをコンパイル
-    fn compile_metal(&self, source: &ShaderSource) -> Result<CompiledShader, GraphicsError> {
-        // 実際のコンパイルはmetal-lib等を使用する
-        // ここではダミーの実装
+    /// ソースをAPIが受け付ける形式に変換
+    fn compile_source(source: &ShaderSource, api: GraphicsApi) -> Result<CompiledShader, ShaderCompilationError> {
+        let target = shader_compiler::target_format(api);
+        
+        if !shader_compiler::can_parse(source.type_) {
+            // HLSLとMetalのソースは解析できないため、同じ形式を受け付けるAPIにのみそのまま渡す
+            if source.type_ != target {
+                return Err(ShaderCompilationError::UnsupportedFormat(format!(
+                    "互換性のないシェーダー形式とAPI: {:?} と {:?}", source.type_, api
+                )));
+            }
+            return Ok(CompiledShader {
+                data: source.source.as_bytes().to_vec(),
+                format: source.type_,
+                entry_point: source.entry_point.clone(),
+                stage: source.stage,
+                timestamp: SystemTime::now(),
+                reflection: ShaderReflection::default(),
+            });
+        }
+        
+        let parsed = shader_compiler::parse(source).map_err(|e| source.source_map.remap(e))?;
+        let output = shader_compiler::compile(&parsed, source.stage, &source.entry_point, target)?;
         Ok(CompiledShader {
-            data: source.source.as_bytes().to_vec(), // ダミーデータ
-            format: ShaderType::Metal,
-            entry_point: source.entry_point.clone(),
+            data: output.data,
+            format: target,
+            entry_point: output.entry_point,
             stage: source.stage,
             timestamp: SystemTime::now(),
+            reflection: output.reflection,
         })
     }
     
-    /// SPIR-Vを検証
-    fn validate_spirv(&self, source: &ShaderSource) -> Result<CompiledShader, GraphicsError> {
-        // 実際の検証はSPIRV-Toolsを使用する
-        // ここではダミーの実装
-        Ok(CompiledShader {
-            data: source.source.as_bytes().to_vec(), // ダミーデータ
-            format: ShaderType::SpirV,
-            entry_point: source.entry_point.clone(),
-            stage: source.stage,
-            timestamp: SystemTime::now(),
-        })
+    /// シェーダーを取得
+    pub fn get_shader(&self, name: &str) -> Option<&Shader> {
+        self.shaders.get(name)
     }
     
     /// コンパイル済みシェーダーを取得
@@ -353,17 +667,12 @@ impl ShaderManager {
         self.current_api = api;
         
         // APIが変更された場合、必要に応じてシェーダーを再コンパイル
-        for (name, _) in &self.shaders {
-            let name = name.clone();
+        let names: Vec<(String, Vec<ShaderStage>)> = self.shaders.iter()
+            .map(|(name, shader)| (name.clone(), shader.sources.keys().copied().collect()))
+            .collect();
+        for (name, stages) in names {
             // エラーは無視して続行
-            for stage in [
-                ShaderStage::Vertex,
-                ShaderStage::Fragment,
-                ShaderStage::Geometry,
-                ShaderStage::Compute,
-                ShaderStage::TessControl,
-                ShaderStage::TessEvaluation,
-            ] {
+            for stage in stages {
                 let _ = self.compile_shader(&name, stage);
             }
         }
@@ -371,12 +680,15 @@ impl ShaderManager {
     
     /// シェーダーを削除
     pub fn remove_shader(&mut self, name: &str) -> bool {
-        self.shaders.remove(name).is_some()
+        let removed = self.shaders.remove(name).is_some();
+        self.update_watch_list();
+        removed
     }
     
     /// すべてのシェーダーをクリア
     pub fn clear(&mut self) {
         self.shaders.clear();
+        self.update_watch_list();
     }
     
     /// クリーンアップ
@@ -430,11 +742,35 @@ mod tests {
             entry_point: "main".to_string(),
             stage: ShaderStage::Vertex,
             defines: HashMap::new(),
+            bytecode: None,
+            source_map: SourceMap::default(),
         };
         
-        shader.add_source(ShaderStage::Vertex, source);
+        shader.add_source(ShaderStage::Vertex, source.clone());
         assert_eq!(shader.sources.len(), 1);
         assert!(shader.needs_recompile(GraphicsApi::Vulkan, ShaderStage::Vertex));
+        
+        let compiled = CompiledShader {
+            data: Vec::new(),
+            format: ShaderType::SpirV,
+            entry_point: "main".to_string(),
+            stage: ShaderStage::Vertex,
+            timestamp: SystemTime::now(),
+            reflection: ShaderReflection::default(),
+        };
+        let before = shader.last_modified;
+        shader.add_compiled(GraphicsApi::Vulkan, ShaderStage::Vertex, compiled.clone());
+        assert!(shader.last_modified >= before);
+        assert!(!shader.needs_recompile(GraphicsApi::Vulkan, ShaderStage::Vertex));
+        
+        // APIが受け付けない形式のコンパイル結果は作り直す
+        shader.add_compiled(GraphicsApi::DirectX, ShaderStage::Vertex, compiled);
+        assert!(shader.needs_recompile(GraphicsApi::DirectX, ShaderStage::Vertex));
+        
+        // ソースを更新すると再コンパイルが必要になる
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        shader.add_source(ShaderStage::Vertex, source);
+        assert!(shader.needs_recompile(GraphicsApi::Vulkan, ShaderStage::Vertex));
     }
     
     #[test]
@@ -445,4 +781,146 @@ mod tests {
         assert_eq!(manager.shaders.len(), 0);
         assert_eq!(manager.search_paths.len(), 1);
     }
-} 
\ No newline at end of file
+    
+    #[test]
+    fn test_load_shader_compiles_at_load_time() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("solid.wgsl");
+        fs::write(&path, "\
+@vertex
+fn vs_main(@location(0) position: vec2<f32>) -> @builtin(position) vec4<f32> {
+    return vec4<f32>(position, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return vec4<f32>(1.0, 0.5, 0.0, 1.0);
+}
+").unwrap();
+        
+        let mut manager = ShaderManager::new(GraphicsApi::Vulkan, HashMap::new()).unwrap();
+        let name = manager.load_shader(&path, None).unwrap();
+        assert_eq!(name, "solid");
+        
+        let vertex = manager.get_compiled_shader("solid", ShaderStage::Vertex).unwrap().unwrap();
+        assert_eq!(vertex.format, ShaderType::SpirV);
+        assert_eq!(vertex.entry_point, "vs_main");
+        assert_eq!(vertex.reflection.vertex_inputs.len(), 1);
+        assert!(manager.get_compiled_shader("solid", ShaderStage::Fragment).unwrap().is_some());
+        
+        // 別のAPIに切り替えると再コンパイルされる
+        manager.set_current_api(GraphicsApi::Metal);
+        let fragment = manager.get_compiled_shader("solid", ShaderStage::Fragment).unwrap().unwrap();
+        assert_eq!(fragment.format, ShaderType::Metal);
+        assert!(String::from_utf8_lossy(&fragment.data).contains("fragment"));
+        
+        // 構文エラーは読み込み時に行番号付きで報告される
+        let broken = dir.path().join("broken.frag");
+        fs::write(&broken, "#version 450\nvoid main() {\n    vec4 color = vec4(1.0\n}\n").unwrap();
+        let error = manager.load_shader(&broken, None).unwrap_err();
+        assert!(error.to_string().contains("4行"), "{}", error);
+    }
+    
+    #[test]
+    fn test_disk_cache_skips_compilation_on_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fill.wgsl");
+        fs::write(&path, "@compute @workgroup_size(16, 16)\nfn main() {}\n").unwrap();
+        let cache_dir = dir.path().join("cache");
+        let device_info = HashMap::from([("device".to_string(), "Test GPU".to_string())]);
+        
+        let mut first = ShaderManager::new(GraphicsApi::Vulkan, device_info.clone()).unwrap();
+        first.set_disk_cache(Some(ShaderCache::open(&cache_dir, 1 << 20).unwrap()));
+        first.load_shader(&path, None).unwrap();
+        assert_eq!(first.disk_cache().unwrap().misses(), 1);
+        
+        // 2回目の起動ではキャッシュから読み込まれ、同じ結果になる
+        let mut second = ShaderManager::new(GraphicsApi::Vulkan, device_info).unwrap();
+        second.set_disk_cache(Some(ShaderCache::open(&cache_dir, 1 << 20).unwrap()));
+        second.load_shader(&path, None).unwrap();
+        assert_eq!(second.disk_cache().unwrap().hits(), 1);
+        let cached = second.get_compiled_shader("fill", ShaderStage::Compute).unwrap().unwrap();
+        let compiled = first.get_compiled_shader("fill", ShaderStage::Compute).unwrap().unwrap();
+        assert_eq!(cached.data, compiled.data);
+        assert_eq!(cached.reflection, compiled.reflection);
+        
+        // デバイスが変わるとキャッシュは使われない
+        let mut other = ShaderManager::new(GraphicsApi::Vulkan, HashMap::new()).unwrap();
+        other.set_disk_cache(Some(ShaderCache::open(&cache_dir, 1 << 20).unwrap()));
+        other.load_shader(&path, None).unwrap();
+        assert_eq!(other.disk_cache().unwrap().hits(), 0);
+    }
+    
+    #[test]
+    fn test_hot_reload_with_includes() {
+        let dir = tempfile::tempdir().unwrap();
+        let common = dir.path().join("common.wgsl");
+        let path = dir.path().join("glow.wgsl");
+        fs::write(&common, "fn glow_color() -> vec4<f32> {\n    return vec4<f32>(1.0, 0.8, 0.2, 1.0);\n}\n").unwrap();
+        fs::write(&path, "\
+#include \"common.wgsl\"
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return glow_color();
+}
+").unwrap();
+        
+        let mut manager = ShaderManager::new(GraphicsApi::Wgpu, HashMap::new()).unwrap();
+        manager.set_watch_interval(Duration::ZERO);
+        manager.load_shader(&path, None).unwrap();
+        assert!(manager.poll_changes().is_empty());
+        let compiled = |manager: &ShaderManager| {
+            let shader = manager.get_compiled_shader("glow", ShaderStage::Fragment).unwrap().unwrap();
+            String::from_utf8(shader.data.clone()).unwrap()
+        };
+        assert!(compiled(&manager).contains("0.8"));
+        
+        // インクルードしたファイルの変更で再コンパイルされる
+        fs::write(&common, "fn glow_color() -> vec4<f32> {\n    return vec4<f32>(1.0, 0.5, 0.2, 1.0);\n}\n").unwrap();
+        let events = manager.poll_changes();
+        assert!(matches!(&events[..], [ShaderReloadEvent::Reloaded { name, stages }] if name == "glow" && stages == &[ShaderStage::Fragment]));
+        assert!(compiled(&manager).contains("0.5"));
+        
+        // コンパイルに失敗した場合は以前のバージョンを使い続け、エラーはインクルード元の位置で報告される
+        fs::write(&common, "fn glow_color() -> vec4<f32> {\n    return 1.0;\n}\n").unwrap();
+        let events = manager.poll_changes();
+        let [ShaderReloadEvent::Failed { error, .. }] = &events[..] else { panic!("{:?}", events) };
+        assert!(error.to_string().contains("common.wgsl"), "{}", error);
+        assert!(compiled(&manager).contains("0.5"));
+        
+        fs::write(&common, "fn glow_color() -> vec4<f32> {\n    return vec4<f32>(0.0, 0.5, 1.0, 1.0);\n}\n").unwrap();
+        assert!(matches!(&manager.poll_changes()[..], [ShaderReloadEvent::Reloaded { .. }]));
+        assert!(compiled(&manager).contains("0.0, 0.5, 1.0"));
+        
+        // 無効にすると変更を確認しない
+        manager.set_hot_reload(false);
+        fs::write(&path, "").unwrap();
+        assert!(manager.poll_changes().is_empty());
+    }
+    
+    #[test]
+    fn test_hot_reload_picks_up_new_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut manager = ShaderManager::new(GraphicsApi::Wgpu, HashMap::new()).unwrap();
+        manager.set_watch_interval(Duration::ZERO);
+        manager.add_search_path(dir.path());
+        assert!(manager.poll_changes().is_empty());
+        
+        // 検索パスに作成されたシェーダーは読み込まれ、インクルード用のファイルは無視される
+        let fill = "@fragment\nfn fs_main() -> @location(0) vec4<f32> {\n    return vec4<f32>(1.0);\n}\n";
+        fs::write(dir.path().join("fill.wgsl"), fill).unwrap();
+        fs::write(dir.path().join("common.wgsl"), "fn helper() -> f32 {\n    return 1.0;\n}\n").unwrap();
+        let events = manager.poll_changes();
+        assert!(matches!(&events[..], [ShaderReloadEvent::Added { name, stages }] if name == "fill" && stages == &[ShaderStage::Fragment]));
+        assert!(manager.get_compiled_shader("fill", ShaderStage::Fragment).unwrap().is_some());
+        assert!(manager.get_shader("common").is_none());
+        
+        // 名前を変更したファイルは新しい名前で読み込まれる
+        fs::rename(dir.path().join("fill.wgsl"), dir.path().join("solid.wgsl")).unwrap();
+        let events = manager.poll_changes();
+        assert!(events.iter().any(|event| matches!(event, ShaderReloadEvent::Added { name, .. } if name == "solid")), "{:?}", events);
+        assert!(manager.get_compiled_shader("solid", ShaderStage::Fragment).unwrap().is_some());
+        assert!(manager.poll_changes().is_empty());
+    }
+}
@@ -0,0 +1,223 @@
+// LumosDesktop シェーダーウォッチャー
+// シェーダーファイルの更新時刻とサイズを定期的に確認して変更を検出
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// ファイルの更新時刻とサイズ
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FileStamp {
+    modified: SystemTime,
+    len: u64,
+}
+
+fn stamp(path: &Path) -> Option<FileStamp> {
+    let metadata = fs::metadata(path).ok()?;
+    Some(FileStamp {
+        modified: metadata.modified().ok()?,
+        len: metadata.len(),
+    })
+}
+
+/// ディレクトリ直下のファイルとその状態
+fn list_dir(dir: &Path) -> HashMap<PathBuf, FileStamp> {
+    let Ok(entries) = fs::read_dir(dir) else { return HashMap::new() };
+    entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter_map(|path| {
+            let stamp = stamp(&path).filter(|_| path.is_file())?;
+            Some((path, stamp))
+        })
+        .collect()
+}
+
+/// ポーリングによるファイルウォッチャー
+///
+/// 監視するファイルは数十個程度を想定しているため、OSの通知機構は使わず
+/// `poll` のたびにメタデータを比較します。
+#[derive(Debug)]
+pub struct ShaderWatcher {
+    /// 監視中のファイルと前回確認した状態（存在しない場合は `None`）
+    files: HashMap<PathBuf, Option<FileStamp>>,
+    /// 監視中のディレクトリと前回確認した直下のファイル
+    dirs: HashMap<PathBuf, HashMap<PathBuf, FileStamp>>,
+    interval: Duration,
+    last_poll: Option<Instant>,
+}
+
+impl ShaderWatcher {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            files: HashMap::new(),
+            dirs: HashMap::new(),
+            interval,
+            last_poll: None,
+        }
+    }
+
+    /// 確認の間隔を設定
+    pub fn set_interval(&mut self, interval: Duration) {
+        self.interval = interval;
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// ファイルを監視対象に追加（監視中のファイルは現在の状態を保持）
+    pub fn watch<P: AsRef<Path>>(&mut self, path: P) {
+        let path = path.as_ref();
+        if !self.files.contains_key(path) {
+            self.files.insert(path.to_path_buf(), stamp(path));
+        }
+    }
+
+    pub fn unwatch<P: AsRef<Path>>(&mut self, path: P) {
+        self.files.remove(path.as_ref());
+    }
+
+    /// 監視対象を指定したファイルだけにする
+    pub fn watch_only<'a>(&mut self, paths: impl IntoIterator<Item = &'a PathBuf>) {
+        let paths: HashSet<&PathBuf> = paths.into_iter().collect();
+        self.files.retain(|path, _| paths.contains(path));
+        for path in paths {
+            self.watch(path);
+        }
+    }
+
+    pub fn is_watching<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.files.contains_key(path.as_ref())
+    }
+
+    pub fn watched_count(&self) -> usize {
+        self.files.len()
+    }
+
+    /// ディレクトリを監視対象に追加（直下のファイルの作成、変更、削除を検出する）
+    ///
+    /// 存在しないディレクトリも監視でき、作成された時点のファイルは新しく作成されたものとして扱います。
+    pub fn watch_dir<P: AsRef<Path>>(&mut self, dir: P) {
+        let dir = dir.as_ref();
+        if !self.dirs.contains_key(dir) {
+            self.dirs.insert(dir.to_path_buf(), list_dir(dir));
+        }
+    }
+
+    pub fn unwatch_dir<P: AsRef<Path>>(&mut self, dir: P) {
+        self.dirs.remove(dir.as_ref());
+    }
+
+    pub fn is_watching_dir<P: AsRef<Path>>(&self, dir: P) -> bool {
+        self.dirs.contains_key(dir.as_ref())
+    }
+
+    /// 前回の確認から変更、作成、削除されたファイルを返す
+    ///
+    /// 前回の確認から `interval` が経過していない場合は何も確認しません。
+    pub fn poll(&mut self) -> Vec<PathBuf> {
+        let now = Instant::now();
+        if self.last_poll.is_some_and(|last| now.duration_since(last) < self.interval) {
+            return Vec::new();
+        }
+        self.last_poll = Some(now);
+
+        let mut changed = Vec::new();
+        for (path, previous) in self.files.iter_mut() {
+            let current = stamp(path);
+            if current != *previous {
+                *previous = current;
+                changed.push(path.clone());
+            }
+        }
+        for (dir, previous) in self.dirs.iter_mut() {
+            let current = list_dir(dir);
+            changed.extend(current.iter()
+                .filter(|(path, stamp)| previous.get(*path) != Some(*stamp))
+                .map(|(path, _)| path.clone()));
+            changed.extend(previous.keys()
+                .filter(|path| !current.contains_key(*path))
+                .cloned());
+            *previous = current;
+        }
+        changed.sort();
+        changed.dedup();
+        changed
+    }
+}
+
+impl Default for ShaderWatcher {
+    fn default() -> Self {
+        Self::new(Duration::from_millis(250))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detects_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let shader = dir.path().join("blur.wgsl");
+        let include = dir.path().join("common.wgsl");
+        fs::write(&shader, "fn main() {}").unwrap();
+
+        let mut watcher = ShaderWatcher::new(Duration::ZERO);
+        watcher.watch(&shader);
+        watcher.watch(&include);
+        assert!(watcher.poll().is_empty());
+
+        // サイズが変わる書き込みと、まだ存在しないファイルの作成
+        fs::write(&shader, "fn main() { }").unwrap();
+        fs::write(&include, "const A: f32 = 1.0;").unwrap();
+        assert_eq!(watcher.poll(), vec![shader.clone(), include.clone()]);
+        assert!(watcher.poll().is_empty());
+
+        fs::remove_file(&include).unwrap();
+        assert_eq!(watcher.poll(), vec![include.clone()]);
+
+        watcher.watch_only([&shader]);
+        assert!(!watcher.is_watching(&include));
+        assert_eq!(watcher.watched_count(), 1);
+
+        // 間隔が経過するまでは確認しない
+        watcher.set_interval(Duration::from_secs(3600));
+        fs::write(&shader, "").unwrap();
+        assert!(watcher.poll().is_empty());
+    }
+
+    #[test]
+    fn test_detects_files_in_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let shader = dir.path().join("blur.wgsl");
+        fs::write(&shader, "fn main() {}").unwrap();
+
+        let mut watcher = ShaderWatcher::new(Duration::ZERO);
+        watcher.watch_dir(dir.path());
+        watcher.watch(&shader);
+        assert!(watcher.poll().is_empty());
+
+        // 監視していないファイルの作成も検出し、監視中のファイルは一度だけ返す
+        let glow = dir.path().join("glow.wgsl");
+        fs::write(&glow, "fn main() {}").unwrap();
+        fs::write(&shader, "fn main() { }").unwrap();
+        assert_eq!(watcher.poll(), vec![shader.clone(), glow.clone()]);
+        assert!(watcher.poll().is_empty());
+
+        // 名前の変更は古いファイルの削除と新しいファイルの作成になる
+        let renamed = dir.path().join("halo.wgsl");
+        fs::rename(&glow, &renamed).unwrap();
+        assert_eq!(watcher.poll(), vec![glow.clone(), renamed.clone()]);
+
+        fs::create_dir(dir.path().join("nested")).unwrap();
+        assert!(watcher.poll().is_empty());
+
+        watcher.unwatch_dir(dir.path());
+        assert!(!watcher.is_watching_dir(dir.path()));
+        fs::write(dir.path().join("new.wgsl"), "").unwrap();
+        assert!(watcher.poll().is_empty());
+    }
+}
@@ -0,0 +1,1626 @@
+// LumosDesktop ソフトウェアバックエンド
+// CPUで三角形をラスタライズするレンダラー実装（GPUのない環境やヘッドレスCI向け）
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use super::{GraphicsConfig, GraphicsError};
+use super::readback::{PixelData, ReadbackFuture};
+use super::renderer::{
+    Renderer, RenderContext, RenderPass, RenderTarget, RenderCommandBuffer, OffscreenTarget,
+    TextureFormat, BufferTarget, BufferUsage, PipelineState, PrimitiveType, FillMode, CullMode,
+    BlendFactor, BlendOp, BlendState, CompareFunc, StencilOp, DepthStencilState,
+    VertexAttribute, VertexFormat, FilterMode, WrapMode,
+};
+
+/// 頂点色をそのまま出力する組み込みシェーダー
+pub const SHADER_VERTEX_COLOR: &str = "software/vertex_color";
+
+/// 頂点色とテクスチャ（スロット0）を乗算する組み込みシェーダー
+pub const SHADER_TEXTURED: &str = "software/textured";
+
+/// 頂点位置に適用する変換行列のユニフォーム名（列優先の4x4 f32行列）
+pub const UNIFORM_TRANSFORM: &str = "transform";
+
+/// 出力色に乗算する色のユニフォーム名（RGBA f32）
+pub const UNIFORM_TINT: &str = "tint";
+
+/// 名前を持たないレンダーターゲットの描画先テクスチャ名
+pub const BACKBUFFER: &str = "backbuffer";
+
+/// ソフトウェアレンダラーで扱える最大テクスチャサイズ
+const MAX_TEXTURE_SIZE: u32 = 8192;
+
+/// 近クリップ面で使うwの下限
+const CLIP_EPSILON: f32 = 1e-5;
+
+/// ソフトウェアテクスチャ
+///
+/// 色は元のフォーマットに関係なくRGBA8（行優先・上から下）で保持します。
+/// 深度・ステンシルはレンダーターゲットとして使われたときに確保されます。
+#[derive(Debug, Clone)]
+pub struct SoftwareTexture {
+    pub width: u32,
+    pub height: u32,
+    pub format: TextureFormat,
+    pub pixels: Vec<u8>,
+    pub filter: FilterMode,
+    pub wrap: WrapMode,
+    depth: Vec<f32>,
+    stencil: Vec<u8>,
+}
+
+impl SoftwareTexture {
+    fn new(width: u32, height: u32, format: TextureFormat) -> Self {
+        Self {
+            width,
+            height,
+            format,
+            pixels: vec![0; (width * height * 4) as usize],
+            filter: FilterMode::Point,
+            wrap: WrapMode::ClampToEdge,
+            depth: Vec::new(),
+            stencil: Vec::new(),
+        }
+    }
+
+    /// 指定フォーマットのデータからRGBA8テクスチャを作成
+    fn from_data(width: u32, height: u32, format: TextureFormat, data: &[u8]) -> Result<Self, GraphicsError> {
+        let count = (width * height) as usize;
+        let bytes_per_pixel = match format {
+            TextureFormat::R8 => 1,
+            TextureFormat::R8G8 => 2,
+            TextureFormat::R8G8B8 => 3,
+            TextureFormat::R8G8B8A8 | TextureFormat::B8G8R8A8 | TextureFormat::R32F => 4,
+            TextureFormat::R32G32B32A32F => 16,
+            _ => {
+                return Err(GraphicsError::Resource(format!(
+                    "ソフトウェアレンダラーでサポートされていないテクスチャフォーマット: {:?}", format
+                )));
+            }
+        };
+
+        if data.len() < count * bytes_per_pixel {
+            return Err(GraphicsError::Resource(format!(
+                "テクスチャデータが不足しています: {} バイト (必要: {} バイト)",
+                data.len(), count * bytes_per_pixel
+            )));
+        }
+
+        let mut texture = Self::new(width, height, format);
+        for i in 0..count {
+            let src = &data[i * bytes_per_pixel..(i + 1) * bytes_per_pixel];
+            let rgba = match format {
+                TextureFormat::R8 => [src[0], 0, 0, 255],
+                TextureFormat::R8G8 => [src[0], src[1], 0, 255],
+                TextureFormat::R8G8B8 => [src[0], src[1], src[2], 255],
+                TextureFormat::B8G8R8A8 => [src[2], src[1], src[0], src[3]],
+                TextureFormat::R32F => {
+                    let r = f32::from_le_bytes([src[0], src[1], src[2], src[3]]);
+                    [to_unorm8(r), 0, 0, 255]
+                }
+                TextureFormat::R32G32B32A32F => {
+                    let mut rgba = [0u8; 4];
+                    for (c, value) in rgba.iter_mut().enumerate() {
+                        let o = c * 4;
+                        *value = to_unorm8(f32::from_le_bytes([src[o], src[o + 1], src[o + 2], src[o + 3]]));
+                    }
+                    rgba
+                }
+                _ => [src[0], src[1], src[2], src[3]],
+            };
+            texture.pixels[i * 4..i * 4 + 4].copy_from_slice(&rgba);
+        }
+
+        Ok(texture)
+    }
+
+    /// 深度・ステンシルバッファを確保
+    fn ensure_depth_stencil(&mut self) {
+        let count = (self.width * self.height) as usize;
+        if self.depth.len() != count {
+            self.depth = vec![1.0; count];
+            self.stencil = vec![0; count];
+        }
+    }
+
+    fn texel(&self, x: i64, y: i64) -> [f32; 4] {
+        let (w, h) = (self.width as i64, self.height as i64);
+        let wrap = |v: i64, size: i64| -> Option<i64> {
+            match self.wrap {
+                WrapMode::Repeat => Some(v.rem_euclid(size)),
+                WrapMode::MirroredRepeat => {
+                    let period = v.rem_euclid(size * 2);
+                    Some(if period < size { period } else { size * 2 - 1 - period })
+                }
+                WrapMode::ClampToEdge => Some(v.clamp(0, size - 1)),
+                WrapMode::ClampToBorder => {
+                    if v < 0 || v >= size {
+                        None
+                    } else {
+                        Some(v)
+                    }
+                }
+            }
+        };
+
+        match (wrap(x, w), wrap(y, h)) {
+            (Some(x), Some(y)) => {
+                let i = ((y * w + x) * 4) as usize;
+                [
+                    self.pixels[i] as f32 / 255.0,
+                    self.pixels[i + 1] as f32 / 255.0,
+                    self.pixels[i + 2] as f32 / 255.0,
+                    self.pixels[i + 3] as f32 / 255.0,
+                ]
+            }
+            // ボーダー色は透明な黒
+            _ => [0.0; 4],
+        }
+    }
+
+    /// UV座標でサンプリング
+    fn sample(&self, u: f32, v: f32) -> [f32; 4] {
+        if self.width == 0 || self.height == 0 {
+            return [0.0; 4];
+        }
+
+        let x = u * self.width as f32 - 0.5;
+        let y = v * self.height as f32 - 0.5;
+
+        match self.filter {
+            FilterMode::Point => self.texel((x + 0.5).floor() as i64, (y + 0.5).floor() as i64),
+            // ミップマップを持たないため、それ以外はバイリニア補間
+            _ => {
+                let (x0, y0) = (x.floor(), y.floor());
+                let (fx, fy) = (x - x0, y - y0);
+                let (x0, y0) = (x0 as i64, y0 as i64);
+                let c00 = self.texel(x0, y0);
+                let c10 = self.texel(x0 + 1, y0);
+                let c01 = self.texel(x0, y0 + 1);
+                let c11 = self.texel(x0 + 1, y0 + 1);
+
+                let mut out = [0.0; 4];
+                for c in 0..4 {
+                    let top = c00[c] + (c10[c] - c00[c]) * fx;
+                    let bottom = c01[c] + (c11[c] - c01[c]) * fx;
+                    out[c] = top + (bottom - top) * fy;
+                }
+                out
+            }
+        }
+    }
+}
+
+/// ビューポート
+#[derive(Debug, Clone, Copy)]
+struct Viewport {
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+    min_depth: f32,
+    max_depth: f32,
+}
+
+/// アクティブなレンダーパスの状態
+#[derive(Debug, Clone)]
+struct PassState {
+    target: String,
+    viewport: Viewport,
+    scissor: Option<(i32, i32, u32, u32)>,
+}
+
+/// コンテキストにバインドされたリソース
+#[derive(Debug, Clone, Default)]
+struct Bindings {
+    pipeline: PipelineState,
+    shader: Option<String>,
+    vertex_buffers: HashMap<u32, String>,
+    index_buffer: Option<String>,
+    uniform_buffers: HashMap<u32, String>,
+    textures: HashMap<u32, String>,
+}
+
+/// 描画呼び出しのパラメーター
+#[derive(Debug, Clone, Copy)]
+struct DrawCall {
+    indexed: bool,
+    count: u32,
+    first: u32,
+    vertex_offset: i32,
+    instance_count: u32,
+    first_instance: u32,
+}
+
+/// 記録されたコマンド
+///
+/// 実際のGPUと同じく、記録時点のバインディングとパス状態を保持して送信時に実行します。
+#[derive(Debug, Clone)]
+enum Command {
+    Clear {
+        pass: PassState,
+        color: [f32; 4],
+        depth: f32,
+        stencil: u8,
+    },
+    Draw {
+        pass: PassState,
+        bindings: Box<Bindings>,
+        uniforms: HashMap<String, Vec<u8>>,
+        call: DrawCall,
+    },
+}
+
+/// レンダラー・コンテキスト・コマンドバッファで共有するデバイス状態
+#[derive(Debug, Default)]
+struct SoftwareDevice {
+    textures: HashMap<String, SoftwareTexture>,
+    buffers: HashMap<String, Vec<u8>>,
+    uniforms: HashMap<String, Vec<u8>>,
+    bindings: Bindings,
+    pass: Option<PassState>,
+}
+
+fn lock_device(device: &Mutex<SoftwareDevice>) -> Result<MutexGuard<'_, SoftwareDevice>, GraphicsError> {
+    device.lock().map_err(|_| {
+        GraphicsError::Backend("ソフトウェアデバイスのロックに失敗しました".to_string())
+    })
+}
+
+/// ソフトウェアレンダラー
+pub struct SoftwareRenderer {
+    device: Arc<Mutex<SoftwareDevice>>,
+    device_info: HashMap<String, String>,
+    capabilities: HashMap<String, String>,
+    config: GraphicsConfig,
+    initialized: bool,
+}
+
+impl fmt::Debug for SoftwareRenderer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SoftwareRenderer")
+            .field("initialized", &self.initialized)
+            .field("device_info", &self.device_info)
+            .finish()
+    }
+}
+
+impl SoftwareRenderer {
+    pub fn new(config: &GraphicsConfig) -> Result<Self, GraphicsError> {
+        let mut renderer = Self {
+            device: Arc::new(Mutex::new(SoftwareDevice::default())),
+            device_info: HashMap::new(),
+            capabilities: HashMap::new(),
+            config: config.clone(),
+            initialized: false,
+        };
+
+        renderer.device_info.insert("name".to_string(), "Software Rasterizer".to_string());
+        renderer.device_info.insert("version".to_string(), "1.0.0".to_string());
+        renderer.device_info.insert("api_version".to_string(), "Software".to_string());
+
+        let max_texture_size = config.max_texture_size.min(MAX_TEXTURE_SIZE);
+        renderer.capabilities.insert("max_texture_size".to_string(), max_texture_size.to_string());
+        renderer.capabilities.insert("max_msaa_samples".to_string(), "1".to_string());
+        renderer.capabilities.insert("depth_range".to_string(), "0..1".to_string());
+        renderer.capabilities.insert("index_format".to_string(), "u32".to_string());
+        renderer.capabilities.insert("texture_compression_bc".to_string(), "false".to_string());
+
+        Ok(renderer)
+    }
+
+    fn ensure_initialized(&self) -> Result<(), GraphicsError> {
+        if self.initialized {
+            Ok(())
+        } else {
+            Err(GraphicsError::Initialization(
+                "レンダラーが初期化されていません".to_string()
+            ))
+        }
+    }
+
+    /// テクスチャの画素をRGBA8で読み出す
+    pub fn read_pixels(&self, name: &str) -> Result<Vec<u8>, GraphicsError> {
+        let device = lock_device(&self.device)?;
+        device.textures.get(name)
+            .map(|texture| texture.pixels.clone())
+            .ok_or_else(|| GraphicsError::Resource(format!("テクスチャが見つかりません: {}", name)))
+    }
+
+    /// テクスチャのサイズを取得
+    pub fn texture_size(&self, name: &str) -> Option<(u32, u32)> {
+        let device = self.device.lock().ok()?;
+        device.textures.get(name).map(|texture| (texture.width, texture.height))
+    }
+
+    /// テクスチャのサンプリング方法を設定
+    pub fn set_sampler(&mut self, name: &str, filter: FilterMode, wrap: WrapMode) -> Result<(), GraphicsError> {
+        let mut device = lock_device(&self.device)?;
+        let texture = device.textures.get_mut(name)
+            .ok_or_else(|| GraphicsError::Resource(format!("テクスチャが見つかりません: {}", name)))?;
+        texture.filter = filter;
+        texture.wrap = wrap;
+        Ok(())
+    }
+
+    /// バッファの内容を部分的に更新
+    pub fn update_buffer(&mut self, name: &str, offset: usize, data: &[u8]) -> Result<(), GraphicsError> {
+        let mut device = lock_device(&self.device)?;
+        let buffer = device.buffers.get_mut(name)
+            .ok_or_else(|| GraphicsError::Resource(format!("バッファが見つかりません: {}", name)))?;
+        if offset + data.len() > buffer.len() {
+            return Err(GraphicsError::Resource(format!(
+                "バッファの範囲外への書き込みです: {} (サイズ: {})", name, buffer.len()
+            )));
+        }
+        buffer[offset..offset + data.len()].copy_from_slice(data);
+        Ok(())
+    }
+}
+
+impl Renderer for SoftwareRenderer {
+    fn name(&self) -> &str {
+        "SoftwareRenderer"
+    }
+
+    fn initialize(&mut self, config: &GraphicsConfig) -> Result<(), GraphicsError> {
+        if self.initialized {
+            return Ok(());
+        }
+
+        self.config = config.clone();
+        self.initialized = true;
+        Ok(())
+    }
+
+    fn shutdown(&mut self) -> Result<(), GraphicsError> {
+        if !self.initialized {
+            return Ok(());
+        }
+
+        let mut device = lock_device(&self.device)?;
+        *device = SoftwareDevice::default();
+
+        self.initialized = false;
+        Ok(())
+    }
+
+    fn update_config(&mut self, config: &GraphicsConfig) -> Result<(), GraphicsError> {
+        // CPUで描画するため再初期化が必要な設定はない
+        self.config = config.clone();
+        Ok(())
+    }
+
+    fn create_context(&mut self) -> Result<Box<dyn RenderContext>, GraphicsError> {
+        self.ensure_initialized()?;
+        Ok(Box::new(SoftwareRenderContext::new(Arc::clone(&self.device))))
+    }
+
+    fn create_texture(
+        &mut self,
+        name: &str,
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+        data: Option<&[u8]>,
+    ) -> Result<(), GraphicsError> {
+        self.ensure_initialized()?;
+
+        let max_size = self.config.max_texture_size.min(MAX_TEXTURE_SIZE);
+        if width > max_size || height > max_size {
+            return Err(GraphicsError::Resource(format!(
+                "テクスチャサイズが最大許容サイズを超えています: {}x{} (最大: {}x{})",
+                width, height, max_size, max_size
+            )));
+        }
+
+        let texture = match data {
+            Some(data) => SoftwareTexture::from_data(width, height, format, data)?,
+            None => SoftwareTexture::new(width, height, format),
+        };
+
+        let mut device = lock_device(&self.device)?;
+        device.textures.insert(name.to_string(), texture);
+        Ok(())
+    }
+
+    fn create_buffer(
+        &mut self,
+        name: &str,
+        _target: BufferTarget,
+        _usage: BufferUsage,
+        data: Option<&[u8]>,
+        size: usize,
+    ) -> Result<(), GraphicsError> {
+        self.ensure_initialized()?;
+
+        let mut buffer = vec![0; size.max(data.map_or(0, |d| d.len()))];
+        if let Some(data) = data {
+            buffer[..data.len()].copy_from_slice(data);
+        }
+
+        let mut device = lock_device(&self.device)?;
+        device.buffers.insert(name.to_string(), buffer);
+        Ok(())
+    }
+
+    fn destroy_texture(&mut self, name: &str) -> Result<(), GraphicsError> {
+        lock_device(&self.device)?.textures.remove(name);
+        Ok(())
+    }
+
+    fn destroy_buffer(&mut self, name: &str) -> Result<(), GraphicsError> {
+        lock_device(&self.device)?.buffers.remove(name);
+        Ok(())
+    }
+
+    fn get_device_info(&self) -> HashMap<String, String> {
+        self.device_info.clone()
+    }
+
+    fn get_capabilities(&self) -> HashMap<String, String> {
+        self.capabilities.clone()
+    }
+
+    fn create_render_target(&mut self, name: &str, width: u32, height: u32, format: TextureFormat) -> Result<Box<dyn RenderTarget>, GraphicsError> {
+        self.ensure_initialized()?;
+        if !matches!(format, TextureFormat::R8G8B8A8 | TextureFormat::B8G8R8A8) {
+            return Err(GraphicsError::Rendering(format!(
+                "ソフトウェアレンダラーで描画できないフォーマットです: {:?}", format
+            )));
+        }
+
+        self.create_texture(name, width, height, format, None)?;
+        let mut device = lock_device(&self.device)?;
+        if let Some(texture) = device.textures.get_mut(name) {
+            texture.ensure_depth_stencil();
+        }
+
+        Ok(Box::new(OffscreenTarget::new(name, width, height).with_format(format)))
+    }
+
+    fn read_render_target(&self, name: &str) -> ReadbackFuture {
+        // 送信時に描画が完了しているため、読み出しは即座に完了する
+        let result = lock_device(&self.device).and_then(|device| {
+            let texture = device.textures.get(name).ok_or_else(|| {
+                GraphicsError::Resource(format!("テクスチャが見つかりません: {}", name))
+            })?;
+            PixelData::new(texture.width, texture.height, texture.pixels.clone())
+        });
+        Box::pin(std::future::ready(result))
+    }
+}
+
+/// ソフトウェアレンダーコンテキスト
+pub struct SoftwareRenderContext {
+    device: Arc<Mutex<SoftwareDevice>>,
+}
+
+impl SoftwareRenderContext {
+    fn new(device: Arc<Mutex<SoftwareDevice>>) -> Self {
+        Self { device }
+    }
+
+    fn with_bindings<F>(&mut self, update: F) -> Result<(), GraphicsError>
+    where
+        F: FnOnce(&mut Bindings),
+    {
+        let mut device = lock_device(&self.device)?;
+        update(&mut device.bindings);
+        Ok(())
+    }
+}
+
+impl fmt::Debug for SoftwareRenderContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SoftwareRenderContext").finish()
+    }
+}
+
+impl RenderContext for SoftwareRenderContext {
+    fn create_render_pass(&mut self, target: Box<dyn RenderTarget>) -> Result<Box<dyn RenderPass>, GraphicsError> {
+        match target.format() {
+            TextureFormat::R8G8B8A8 | TextureFormat::B8G8R8A8 => {}
+            format => {
+                return Err(GraphicsError::Rendering(format!(
+                    "ソフトウェアレンダラーで描画できないフォーマットです: {:?}", format
+                )));
+            }
+        }
+
+        let name = target.texture_name().unwrap_or(BACKBUFFER).to_string();
+        Ok(Box::new(SoftwareRenderPass::new(
+            Arc::clone(&self.device),
+            name,
+            target.width(),
+            target.height(),
+            target.format(),
+        )))
+    }
+
+    fn create_command_buffer(&mut self) -> Result<Box<dyn RenderCommandBuffer>, GraphicsError> {
+        Ok(Box::new(SoftwareCommandBuffer::new(Arc::clone(&self.device))))
+    }
+
+    fn set_pipeline_state(&mut self, state: &PipelineState) -> Result<(), GraphicsError> {
+        self.with_bindings(|bindings| bindings.pipeline = state.clone())
+    }
+
+    fn set_shader(&mut self, shader: &str) -> Result<(), GraphicsError> {
+        if shader != SHADER_VERTEX_COLOR && shader != SHADER_TEXTURED {
+            return Err(GraphicsError::Shader(format!(
+                "ソフトウェアレンダラーで利用できないシェーダーです: {}", shader
+            )));
+        }
+        self.with_bindings(|bindings| bindings.shader = Some(shader.to_string()))
+    }
+
+    fn set_vertex_buffer(&mut self, buffer: &str, slot: u32) -> Result<(), GraphicsError> {
+        self.with_bindings(|bindings| {
+            bindings.vertex_buffers.insert(slot, buffer.to_string());
+        })
+    }
+
+    fn set_index_buffer(&mut self, buffer: &str) -> Result<(), GraphicsError> {
+        self.with_bindings(|bindings| bindings.index_buffer = Some(buffer.to_string()))
+    }
+
+    fn set_uniform_buffer(&mut self, buffer: &str, slot: u32) -> Result<(), GraphicsError> {
+        self.with_bindings(|bindings| {
+            bindings.uniform_buffers.insert(slot, buffer.to_string());
+        })
+    }
+
+    fn set_texture(&mut self, texture: &str, slot: u32) -> Result<(), GraphicsError> {
+        self.with_bindings(|bindings| {
+            bindings.textures.insert(slot, texture.to_string());
+        })
+    }
+
+    fn update_uniform(&mut self, name: &str, data: &[u8]) -> Result<(), GraphicsError> {
+        let mut device = lock_device(&self.device)?;
+        device.uniforms.insert(name.to_string(), data.to_vec());
+        Ok(())
+    }
+}
+
+/// ソフトウェアレンダーパス
+pub struct SoftwareRenderPass {
+    device: Arc<Mutex<SoftwareDevice>>,
+    state: PassState,
+    width: u32,
+    height: u32,
+    format: TextureFormat,
+    active: bool,
+}
+
+impl SoftwareRenderPass {
+    fn new(device: Arc<Mutex<SoftwareDevice>>, target: String, width: u32, height: u32, format: TextureFormat) -> Self {
+        Self {
+            device,
+            state: PassState {
+                target,
+                viewport: Viewport {
+                    x: 0.0,
+                    y: 0.0,
+                    width: width as f32,
+                    height: height as f32,
+                    min_depth: 0.0,
+                    max_depth: 1.0,
+                },
+                scissor: None,
+            },
+            width,
+            height,
+            format,
+            active: false,
+        }
+    }
+
+    fn sync_state(&self) -> Result<(), GraphicsError> {
+        if self.active {
+            let mut device = lock_device(&self.device)?;
+            device.pass = Some(self.state.clone());
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for SoftwareRenderPass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SoftwareRenderPass")
+            .field("target", &self.state.target)
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("active", &self.active)
+            .finish()
+    }
+}
+
+impl RenderPass for SoftwareRenderPass {
+    fn begin(&mut self) -> Result<(), GraphicsError> {
+        let mut device = lock_device(&self.device)?;
+
+        // 描画先テクスチャがなければ作成
+        let texture = device.textures
+            .entry(self.state.target.clone())
+            .or_insert_with(|| SoftwareTexture::new(self.width, self.height, self.format));
+        if texture.width != self.width || texture.height != self.height {
+            return Err(GraphicsError::Rendering(format!(
+                "レンダーターゲットのサイズが一致しません: {} ({}x{}, 指定: {}x{})",
+                self.state.target, texture.width, texture.height, self.width, self.height
+            )));
+        }
+        texture.ensure_depth_stencil();
+
+        device.pass = Some(self.state.clone());
+        self.active = true;
+        Ok(())
+    }
+
+    fn end(&mut self) -> Result<(), GraphicsError> {
+        let mut device = lock_device(&self.device)?;
+        device.pass = None;
+        self.active = false;
+        Ok(())
+    }
+
+    fn set_viewport(&mut self, x: f32, y: f32, width: f32, height: f32, min_depth: f32, max_depth: f32) -> Result<(), GraphicsError> {
+        self.state.viewport = Viewport { x, y, width, height, min_depth, max_depth };
+        self.sync_state()
+    }
+
+    fn set_scissor(&mut self, x: i32, y: i32, width: u32, height: u32) -> Result<(), GraphicsError> {
+        self.state.scissor = Some((x, y, width, height));
+        self.sync_state()
+    }
+}
+
+/// ソフトウェアコマンドバッファ
+pub struct SoftwareCommandBuffer {
+    device: Arc<Mutex<SoftwareDevice>>,
+    commands: Vec<Command>,
+    recording: bool,
+}
+
+impl SoftwareCommandBuffer {
+    fn new(device: Arc<Mutex<SoftwareDevice>>) -> Self {
+        Self {
+            device,
+            commands: Vec::new(),
+            recording: false,
+        }
+    }
+
+    fn current_pass(&self, device: &SoftwareDevice) -> Result<PassState, GraphicsError> {
+        if !self.recording {
+            return Err(GraphicsError::Rendering(
+                "コマンドバッファの記録が開始されていません".to_string()
+            ));
+        }
+        device.pass.clone().ok_or_else(|| {
+            GraphicsError::Rendering("レンダーパスが開始されていません".to_string())
+        })
+    }
+
+    fn record_draw(&mut self, call: DrawCall) -> Result<(), GraphicsError> {
+        let device = lock_device(&self.device)?;
+        let pass = self.current_pass(&device)?;
+        let command = Command::Draw {
+            pass,
+            bindings: Box::new(device.bindings.clone()),
+            uniforms: device.uniforms.clone(),
+            call,
+        };
+        drop(device);
+
+        self.commands.push(command);
+        Ok(())
+    }
+}
+
+impl fmt::Debug for SoftwareCommandBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SoftwareCommandBuffer")
+            .field("commands", &self.commands.len())
+            .field("recording", &self.recording)
+            .finish()
+    }
+}
+
+impl RenderCommandBuffer for SoftwareCommandBuffer {
+    fn begin(&mut self) -> Result<(), GraphicsError> {
+        self.commands.clear();
+        self.recording = true;
+        Ok(())
+    }
+
+    fn end(&mut self) -> Result<(), GraphicsError> {
+        self.recording = false;
+        Ok(())
+    }
+
+    fn submit(&mut self) -> Result<(), GraphicsError> {
+        if self.recording {
+            return Err(GraphicsError::Rendering(
+                "記録中のコマンドバッファは送信できません".to_string()
+            ));
+        }
+
+        let mut device = lock_device(&self.device)?;
+        for command in &self.commands {
+            execute(&mut device, command)?;
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self, color: [f32; 4], depth: f32, stencil: u8) -> Result<(), GraphicsError> {
+        let device = lock_device(&self.device)?;
+        let pass = self.current_pass(&device)?;
+        drop(device);
+
+        self.commands.push(Command::Clear { pass, color, depth, stencil });
+        Ok(())
+    }
+
+    fn draw(&mut self, vertex_count: u32, first_vertex: u32) -> Result<(), GraphicsError> {
+        self.draw_instanced(vertex_count, 1, first_vertex, 0)
+    }
+
+    fn draw_indexed(&mut self, index_count: u32, first_index: u32, vertex_offset: i32) -> Result<(), GraphicsError> {
+        self.draw_indexed_instanced(index_count, 1, first_index, vertex_offset, 0)
+    }
+
+    fn draw_instanced(&mut self, vertex_count: u32, instance_count: u32, first_vertex: u32, first_instance: u32) -> Result<(), GraphicsError> {
+        self.record_draw(DrawCall {
+            indexed: false,
+            count: vertex_count,
+            first: first_vertex,
+            vertex_offset: 0,
+            instance_count,
+            first_instance,
+        })
+    }
+
+    fn draw_indexed_instanced(&mut self, index_count: u32, instance_count: u32, first_index: u32, vertex_offset: i32, first_instance: u32) -> Result<(), GraphicsError> {
+        self.record_draw(DrawCall {
+            indexed: true,
+            count: index_count,
+            first: first_index,
+            vertex_offset,
+            instance_count,
+            first_instance,
+        })
+    }
+}
+
+// ---------------------------------------------------------------------------
+// ラスタライザ
+// ---------------------------------------------------------------------------
+
+/// 頂点シェーダーの出力（クリップ座標と補間する属性）
+#[derive(Debug, Clone, Copy)]
+struct ClipVertex {
+    position: [f32; 4],
+    color: [f32; 4],
+    uv: [f32; 2],
+}
+
+impl ClipVertex {
+    fn lerp(&self, other: &ClipVertex, t: f32) -> ClipVertex {
+        let mut out = *self;
+        for i in 0..4 {
+            out.position[i] += (other.position[i] - self.position[i]) * t;
+            out.color[i] += (other.color[i] - self.color[i]) * t;
+        }
+        for i in 0..2 {
+            out.uv[i] += (other.uv[i] - self.uv[i]) * t;
+        }
+        out
+    }
+}
+
+/// 画面座標に変換した頂点
+#[derive(Debug, Clone, Copy)]
+struct ScreenVertex {
+    x: f32,
+    y: f32,
+    z: f32,
+    inv_w: f32,
+    color: [f32; 4],
+    uv: [f32; 2],
+}
+
+fn to_unorm8(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0 + 0.5) as u8
+}
+
+fn execute(device: &mut SoftwareDevice, command: &Command) -> Result<(), GraphicsError> {
+    match command {
+        Command::Clear { pass, color, depth, stencil } => {
+            let texture = device.textures.get_mut(&pass.target).ok_or_else(|| {
+                GraphicsError::Rendering(format!("レンダーターゲットが見つかりません: {}", pass.target))
+            })?;
+            let rgba = color.map(to_unorm8);
+            for pixel in texture.pixels.chunks_exact_mut(4) {
+                pixel.copy_from_slice(&rgba);
+            }
+            texture.ensure_depth_stencil();
+            texture.depth.iter_mut().for_each(|d| *d = *depth);
+            texture.stencil.iter_mut().for_each(|s| *s = *stencil);
+            Ok(())
+        }
+        Command::Draw { pass, bindings, uniforms, call } => {
+            // 描画先を一時的に取り出して、他のリソースと同時に参照できるようにする
+            let mut target = device.textures.remove(&pass.target).ok_or_else(|| {
+                GraphicsError::Rendering(format!("レンダーターゲットが見つかりません: {}", pass.target))
+            })?;
+            target.ensure_depth_stencil();
+            let result = draw(device, &mut target, pass, bindings, uniforms, call);
+            device.textures.insert(pass.target.clone(), target);
+            result
+        }
+    }
+}
+
+fn read_f32(data: &[u8], offset: usize) -> Option<f32> {
+    data.get(offset..offset + 4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    data.get(offset..offset + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+/// 頂点属性を読み出して最大4要素のベクトルに展開
+fn fetch_attribute(data: &[u8], base: usize, attribute: &VertexAttribute, default: [f32; 4]) -> Result<[f32; 4], GraphicsError> {
+    let offset = base + attribute.offset as usize;
+    let out_of_range = || GraphicsError::Rendering(format!(
+        "頂点バッファの範囲外を参照しています: {} (オフセット: {})", attribute.name, offset
+    ));
+
+    let float_count = match attribute.format {
+        VertexFormat::Float => 1,
+        VertexFormat::Float2 => 2,
+        VertexFormat::Float3 => 3,
+        VertexFormat::Float4 => 4,
+        VertexFormat::UByte4 => {
+            let bytes = data.get(offset..offset + 4).ok_or_else(out_of_range)?;
+            return Ok([
+                bytes[0] as f32 / 255.0,
+                bytes[1] as f32 / 255.0,
+                bytes[2] as f32 / 255.0,
+                bytes[3] as f32 / 255.0,
+            ]);
+        }
+        format => {
+            return Err(GraphicsError::Rendering(format!(
+                "ソフトウェアレンダラーでサポートされていない頂点フォーマット: {:?}", format
+            )));
+        }
+    };
+
+    let mut out = default;
+    for (i, value) in out.iter_mut().enumerate().take(float_count) {
+        *value = read_f32(data, offset + i * 4).ok_or_else(out_of_range)?;
+    }
+    Ok(out)
+}
+
+fn find_attribute<'a>(attributes: &'a [VertexAttribute], name: &str, location: u32) -> Option<&'a VertexAttribute> {
+    attributes.iter()
+        .find(|a| a.name == name)
+        .or_else(|| attributes.iter().find(|a| a.location == location))
+}
+
+fn uniform_vec4(uniforms: &HashMap<String, Vec<u8>>, name: &str, default: [f32; 4]) -> [f32; 4] {
+    let mut out = default;
+    if let Some(data) = uniforms.get(name) {
+        for (i, value) in out.iter_mut().enumerate() {
+            if let Some(v) = read_f32(data, i * 4) {
+                *value = v;
+            }
+        }
+    }
+    out
+}
+
+fn transform_point(matrix: &[f32; 16], p: [f32; 4]) -> [f32; 4] {
+    let mut out = [0.0; 4];
+    for (row, value) in out.iter_mut().enumerate() {
+        *value = (0..4).map(|col| matrix[col * 4 + row] * p[col]).sum();
+    }
+    out
+}
+
+fn draw(
+    device: &SoftwareDevice,
+    target: &mut SoftwareTexture,
+    pass: &PassState,
+    bindings: &Bindings,
+    uniforms: &HashMap<String, Vec<u8>>,
+    call: &DrawCall,
+) -> Result<(), GraphicsError> {
+    let pipeline = &bindings.pipeline;
+
+    match pipeline.primitive_type {
+        PrimitiveType::Triangles | PrimitiveType::TriangleStrip | PrimitiveType::TriangleFan => {}
+        primitive => {
+            return Err(GraphicsError::Rendering(format!(
+                "ソフトウェアレンダラーは三角形プリミティブのみ対応しています: {:?}", primitive
+            )));
+        }
+    }
+    if pipeline.rasterizer_state.fill_mode != FillMode::Solid {
+        return Err(GraphicsError::Rendering(format!(
+            "ソフトウェアレンダラーは塗りつぶしモードのみ対応しています: {:?}",
+            pipeline.rasterizer_state.fill_mode
+        )));
+    }
+
+    // 頂点バッファ
+    let vertex_buffer_name = bindings.vertex_buffers.get(&0).ok_or_else(|| {
+        GraphicsError::Rendering("頂点バッファがバインドされていません".to_string())
+    })?;
+    let vertex_data = device.buffers.get(vertex_buffer_name).ok_or_else(|| {
+        GraphicsError::Rendering(format!("頂点バッファが見つかりません: {}", vertex_buffer_name))
+    })?;
+
+    let layout = &pipeline.vertex_layout;
+    let position_attr = find_attribute(&layout.attributes, "position", 0).ok_or_else(|| {
+        GraphicsError::Rendering("頂点レイアウトに位置属性がありません".to_string())
+    })?;
+    let color_attr = find_attribute(&layout.attributes, "color", 1);
+    let uv_attr = find_attribute(&layout.attributes, "texcoord", 2);
+
+    // シェーダーとユニフォーム
+    let textured = bindings.shader.as_deref() == Some(SHADER_TEXTURED);
+    let texture = if textured {
+        let name = bindings.textures.get(&0).ok_or_else(|| {
+            GraphicsError::Rendering("テクスチャがバインドされていません".to_string())
+        })?;
+        if name == &pass.target {
+            return Err(GraphicsError::Rendering(format!(
+                "描画先テクスチャはサンプリングできません: {}", name
+            )));
+        }
+        Some(device.textures.get(name).ok_or_else(|| {
+            GraphicsError::Rendering(format!("テクスチャが見つかりません: {}", name))
+        })?)
+    } else {
+        None
+    };
+
+    let transform: Option<[f32; 16]> = uniforms.get(UNIFORM_TRANSFORM).and_then(|data| {
+        let mut matrix = [0.0; 16];
+        for (i, value) in matrix.iter_mut().enumerate() {
+            *value = read_f32(data, i * 4)?;
+        }
+        Some(matrix)
+    });
+    let tint = uniform_vec4(uniforms, UNIFORM_TINT, [1.0; 4]);
+
+    // インデックス
+    let indices: Vec<u32> = if call.indexed {
+        let name = bindings.index_buffer.as_ref().ok_or_else(|| {
+            GraphicsError::Rendering("インデックスバッファがバインドされていません".to_string())
+        })?;
+        let data = device.buffers.get(name).ok_or_else(|| {
+            GraphicsError::Rendering(format!("インデックスバッファが見つかりません: {}", name))
+        })?;
+        (call.first..call.first + call.count)
+            .map(|i| {
+                read_u32(data, i as usize * 4)
+                    .map(|index| (index as i64 + call.vertex_offset as i64).max(0) as u32)
+                    .ok_or_else(|| GraphicsError::Rendering(format!(
+                        "インデックスバッファの範囲外を参照しています: {}", i
+                    )))
+            })
+            .collect::<Result<_, _>>()?
+    } else {
+        (call.first..call.first + call.count).collect()
+    };
+
+    let stride = if layout.stride > 0 {
+        layout.stride as usize
+    } else {
+        layout.attributes.iter()
+            .map(|a| a.offset as usize + 16)
+            .max()
+            .unwrap_or(16)
+    };
+
+    for instance in 0..call.instance_count {
+        let instance_index = call.first_instance + instance;
+
+        // 頂点シェーダー
+        let fetch_base = |attribute: &VertexAttribute, vertex: u32| -> usize {
+            // divisorが0の属性は頂点ごと、それ以外はインスタンスごとに進む
+            match instance_index.checked_div(attribute.divisor) {
+                Some(instance) => instance as usize * stride,
+                None => vertex as usize * stride,
+            }
+        };
+        let shade_vertex = |vertex: u32| -> Result<ClipVertex, GraphicsError> {
+            let mut position = fetch_attribute(vertex_data, fetch_base(position_attr, vertex), position_attr, [0.0, 0.0, 0.0, 1.0])?;
+            if let Some(matrix) = &transform {
+                position = transform_point(matrix, position);
+            }
+            let color = match color_attr {
+                Some(attr) => fetch_attribute(vertex_data, fetch_base(attr, vertex), attr, [0.0, 0.0, 0.0, 1.0])?,
+                None => [1.0; 4],
+            };
+            let uv = match uv_attr {
+                Some(attr) => {
+                    let uv = fetch_attribute(vertex_data, fetch_base(attr, vertex), attr, [0.0; 4])?;
+                    [uv[0], uv[1]]
+                }
+                None => [0.0; 2],
+            };
+            Ok(ClipVertex { position, color, uv })
+        };
+
+        let vertices = indices.iter()
+            .map(|&index| shade_vertex(index))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // プリミティブ組み立て
+        let mut triangles = Vec::new();
+        match pipeline.primitive_type {
+            PrimitiveType::Triangles => {
+                for tri in vertices.chunks_exact(3) {
+                    triangles.push([tri[0], tri[1], tri[2]]);
+                }
+            }
+            PrimitiveType::TriangleStrip => {
+                for i in 2..vertices.len() {
+                    // 奇数番目は巻き方向をそろえるため入れ替える
+                    if i % 2 == 0 {
+                        triangles.push([vertices[i - 2], vertices[i - 1], vertices[i]]);
+                    } else {
+                        triangles.push([vertices[i - 1], vertices[i - 2], vertices[i]]);
+                    }
+                }
+            }
+            PrimitiveType::TriangleFan => {
+                for i in 2..vertices.len() {
+                    triangles.push([vertices[0], vertices[i - 1], vertices[i]]);
+                }
+            }
+            _ => unreachable!(),
+        }
+
+        for triangle in &triangles {
+            for clipped in clip_triangle(triangle) {
+                rasterize_triangle(target, pass, pipeline, &clipped, texture, tint);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// 近クリップ面（w > 0 かつ z >= 0）で三角形をクリップ
+fn clip_triangle(triangle: &[ClipVertex; 3]) -> Vec<[ClipVertex; 3]> {
+    let planes: [fn(&ClipVertex) -> f32; 2] = [
+        |v| v.position[3] - CLIP_EPSILON,
+        |v| v.position[2],
+    ];
+
+    let mut polygon: Vec<ClipVertex> = triangle.to_vec();
+    for plane in planes.iter() {
+        if polygon.is_empty() {
+            break;
+        }
+        let mut output = Vec::with_capacity(polygon.len() + 1);
+        for i in 0..polygon.len() {
+            let current = polygon[i];
+            let next = polygon[(i + 1) % polygon.len()];
+            let (dc, dn) = (plane(&current), plane(&next));
+            if dc >= 0.0 {
+                output.push(current);
+            }
+            if (dc >= 0.0) != (dn >= 0.0) {
+                output.push(current.lerp(&next, dc / (dc - dn)));
+            }
+        }
+        polygon = output;
+    }
+
+    (2..polygon.len())
+        .map(|i| [polygon[0], polygon[i - 1], polygon[i]])
+        .collect()
+}
+
+fn edge(a: (f32, f32), b: (f32, f32), p: (f32, f32)) -> f32 {
+    (b.0 - a.0) * (p.1 - a.1) - (b.1 - a.1) * (p.0 - a.0)
+}
+
+fn rasterize_triangle(
+    target: &mut SoftwareTexture,
+    pass: &PassState,
+    pipeline: &PipelineState,
+    triangle: &[ClipVertex; 3],
+    texture: Option<&SoftwareTexture>,
+    tint: [f32; 4],
+) {
+    let vp = &pass.viewport;
+    let to_screen = |v: &ClipVertex| -> ScreenVertex {
+        let inv_w = 1.0 / v.position[3];
+        let (nx, ny, nz) = (v.position[0] * inv_w, v.position[1] * inv_w, v.position[2] * inv_w);
+        ScreenVertex {
+            x: vp.x + (nx + 1.0) * 0.5 * vp.width,
+            // NDCは上向き、画面座標は下向き
+            y: vp.y + (1.0 - ny) * 0.5 * vp.height,
+            z: vp.min_depth + nz * (vp.max_depth - vp.min_depth),
+            inv_w,
+            color: v.color,
+            uv: v.uv,
+        }
+    };
+    let mut v = [to_screen(&triangle[0]), to_screen(&triangle[1]), to_screen(&triangle[2])];
+
+    // カリング（画面座標はy下向きなので、NDCで反時計回りの面積は負になる）
+    let area = edge((v[0].x, v[0].y), (v[1].x, v[1].y), (v[2].x, v[2].y));
+    if area == 0.0 || !area.is_finite() {
+        return;
+    }
+    let rasterizer = &pipeline.rasterizer_state;
+    let front_facing = if rasterizer.front_face_ccw { area < 0.0 } else { area > 0.0 };
+    match rasterizer.cull_mode {
+        CullMode::Back if !front_facing => return,
+        CullMode::Front if front_facing => return,
+        _ => {}
+    }
+
+    // 面積が正になる向きにそろえる
+    if area < 0.0 {
+        v.swap(1, 2);
+    }
+    let area = area.abs();
+    let p = [(v[0].x, v[0].y), (v[1].x, v[1].y), (v[2].x, v[2].y)];
+
+    // 描画範囲（ターゲット・ビューポート・シザー）
+    let mut min_x = p.iter().map(|q| q.0).fold(f32::INFINITY, f32::min).floor().max(0.0) as i64;
+    let mut min_y = p.iter().map(|q| q.1).fold(f32::INFINITY, f32::min).floor().max(0.0) as i64;
+    let mut max_x = (p.iter().map(|q| q.0).fold(f32::NEG_INFINITY, f32::max).ceil() as i64).min(target.width as i64);
+    let mut max_y = (p.iter().map(|q| q.1).fold(f32::NEG_INFINITY, f32::max).ceil() as i64).min(target.height as i64);
+    min_x = min_x.max(vp.x.floor() as i64);
+    min_y = min_y.max(vp.y.floor() as i64);
+    max_x = max_x.min((vp.x + vp.width).ceil() as i64);
+    max_y = max_y.min((vp.y + vp.height).ceil() as i64);
+    if rasterizer.scissor_test {
+        if let Some((sx, sy, sw, sh)) = pass.scissor {
+            min_x = min_x.max(sx as i64);
+            min_y = min_y.max(sy as i64);
+            max_x = max_x.min(sx as i64 + sw as i64);
+            max_y = max_y.min(sy as i64 + sh as i64);
+        }
+    }
+    if min_x >= max_x || min_y >= max_y {
+        return;
+    }
+
+    // 深度バイアス
+    let dzdx = ((v[1].z - v[0].z) * (v[2].y - v[0].y) - (v[2].z - v[0].z) * (v[1].y - v[0].y)) / area;
+    let dzdy = ((v[2].z - v[0].z) * (v[1].x - v[0].x) - (v[1].z - v[0].z) * (v[2].x - v[0].x)) / area;
+    let depth_bias = rasterizer.depth_bias / 16_777_216.0
+        + rasterizer.depth_bias_slope_scale * dzdx.abs().max(dzdy.abs());
+
+    // 左上ルール：上辺（水平で右向き）と左辺（上向き）上の画素は含める
+    let is_top_left = |a: (f32, f32), b: (f32, f32)| -> bool {
+        (a.1 == b.1 && b.0 > a.0) || b.1 < a.1
+    };
+    let edges = [(p[1], p[2]), (p[2], p[0]), (p[0], p[1])];
+    let top_left = [
+        is_top_left(edges[0].0, edges[0].1),
+        is_top_left(edges[1].0, edges[1].1),
+        is_top_left(edges[2].0, edges[2].1),
+    ];
+
+    let (min_depth, max_depth) = (vp.min_depth.min(vp.max_depth), vp.min_depth.max(vp.max_depth));
+    let depth_state = &pipeline.depth_stencil_state;
+    let width = target.width as usize;
+
+    for y in min_y..max_y {
+        for x in min_x..max_x {
+            let sample = (x as f32 + 0.5, y as f32 + 0.5);
+            let mut weights = [0.0f32; 3];
+            let mut inside = true;
+            for (i, (a, b)) in edges.iter().enumerate() {
+                let e = edge(*a, *b, sample);
+                if e < 0.0 || (e == 0.0 && !top_left[i]) {
+                    inside = false;
+                    break;
+                }
+                weights[i] = e / area;
+            }
+            if !inside {
+                continue;
+            }
+
+            let z = weights[0] * v[0].z + weights[1] * v[1].z + weights[2] * v[2].z + depth_bias;
+            if z < min_depth || z > max_depth {
+                continue;
+            }
+
+            let index = y as usize * width + x as usize;
+
+            // ステンシルテスト
+            if depth_state.stencil_test {
+                let stored = target.stencil[index];
+                let reference = depth_state.stencil_ref & depth_state.stencil_read_mask;
+                let value = stored & depth_state.stencil_read_mask;
+                if !compare(depth_state.stencil_func, reference as f32, value as f32) {
+                    target.stencil[index] = apply_stencil(depth_state, depth_state.stencil_fail_op, stored);
+                    continue;
+                }
+            }
+
+            // 深度テスト
+            if depth_state.depth_test {
+                if !compare(depth_state.depth_func, z, target.depth[index]) {
+                    if depth_state.stencil_test {
+                        let stored = target.stencil[index];
+                        target.stencil[index] = apply_stencil(depth_state, depth_state.stencil_depth_fail_op, stored);
+                    }
+                    continue;
+                }
+                if depth_state.depth_write {
+                    target.depth[index] = z;
+                }
+            }
+            if depth_state.stencil_test {
+                let stored = target.stencil[index];
+                target.stencil[index] = apply_stencil(depth_state, depth_state.stencil_pass_op, stored);
+            }
+
+            // フラグメントシェーダー（パースペクティブ補正した属性）
+            let inv_w = weights[0] * v[0].inv_w + weights[1] * v[1].inv_w + weights[2] * v[2].inv_w;
+            let interpolate = |f: &dyn Fn(&ScreenVertex) -> f32| -> f32 {
+                (weights[0] * f(&v[0]) * v[0].inv_w
+                    + weights[1] * f(&v[1]) * v[1].inv_w
+                    + weights[2] * f(&v[2]) * v[2].inv_w) / inv_w
+            };
+            let mut color = [0.0f32; 4];
+            for (c, value) in color.iter_mut().enumerate() {
+                *value = interpolate(&|vertex| vertex.color[c]) * tint[c];
+            }
+            if let Some(texture) = texture {
+                let u = interpolate(&|vertex| vertex.uv[0]);
+                let t = interpolate(&|vertex| vertex.uv[1]);
+                let texel = texture.sample(u, t);
+                for c in 0..4 {
+                    color[c] *= texel[c];
+                }
+            }
+
+            // ブレンド
+            let offset = index * 4;
+            let dst = [
+                target.pixels[offset] as f32 / 255.0,
+                target.pixels[offset + 1] as f32 / 255.0,
+                target.pixels[offset + 2] as f32 / 255.0,
+                target.pixels[offset + 3] as f32 / 255.0,
+            ];
+            let out = if pipeline.blend_state.enabled {
+                blend(&pipeline.blend_state, color, dst)
+            } else {
+                color
+            };
+            for (c, value) in out.iter().enumerate() {
+                target.pixels[offset + c] = to_unorm8(*value);
+            }
+        }
+    }
+}
+
+fn compare(func: CompareFunc, value: f32, reference: f32) -> bool {
+    match func {
+        CompareFunc::Never => false,
+        CompareFunc::Less => value < reference,
+        CompareFunc::Equal => value == reference,
+        CompareFunc::LessEqual => value <= reference,
+        CompareFunc::Greater => value > reference,
+        CompareFunc::NotEqual => value != reference,
+        CompareFunc::GreaterEqual => value >= reference,
+        CompareFunc::Always => true,
+    }
+}
+
+fn apply_stencil(state: &DepthStencilState, op: StencilOp, stored: u8) -> u8 {
+    let value = match op {
+        StencilOp::Keep => stored,
+        StencilOp::Zero => 0,
+        StencilOp::Replace => state.stencil_ref,
+        StencilOp::Increment => stored.saturating_add(1),
+        StencilOp::IncrementWrap => stored.wrapping_add(1),
+        StencilOp::Decrement => stored.saturating_sub(1),
+        StencilOp::DecrementWrap => stored.wrapping_sub(1),
+        StencilOp::Invert => !stored,
+    };
+    (stored & !state.stencil_write_mask) | (value & state.stencil_write_mask)
+}
+
+fn blend_factor(factor: BlendFactor, src: [f32; 4], dst: [f32; 4], constant: [f32; 4], channel: usize) -> f32 {
+    match factor {
+        BlendFactor::Zero => 0.0,
+        BlendFactor::One => 1.0,
+        BlendFactor::SrcColor => src[channel],
+        BlendFactor::OneMinusSrcColor => 1.0 - src[channel],
+        BlendFactor::DstColor => dst[channel],
+        BlendFactor::OneMinusDstColor => 1.0 - dst[channel],
+        BlendFactor::SrcAlpha => src[3],
+        BlendFactor::OneMinusSrcAlpha => 1.0 - src[3],
+        BlendFactor::DstAlpha => dst[3],
+        BlendFactor::OneMinusDstAlpha => 1.0 - dst[3],
+        BlendFactor::ConstantColor => constant[channel],
+        BlendFactor::OneMinusConstantColor => 1.0 - constant[channel],
+        BlendFactor::ConstantAlpha => constant[3],
+        BlendFactor::OneMinusConstantAlpha => 1.0 - constant[3],
+        BlendFactor::SrcAlphaSaturate => {
+            if channel == 3 {
+                1.0
+            } else {
+                src[3].min(1.0 - dst[3])
+            }
+        }
+    }
+}
+
+fn blend(state: &BlendState, src: [f32; 4], dst: [f32; 4]) -> [f32; 4] {
+    let mut out = [0.0; 4];
+    for (channel, value) in out.iter_mut().enumerate() {
+        let (src_factor, dst_factor, op) = if channel < 3 {
+            (state.src_rgb, state.dst_rgb, state.op_rgb)
+        } else {
+            (state.src_alpha, state.dst_alpha, state.op_alpha)
+        };
+        let s = src[channel] * blend_factor(src_factor, src, dst, state.color, channel);
+        let d = dst[channel] * blend_factor(dst_factor, src, dst, state.color, channel);
+        *value = match op {
+            BlendOp::Add => s + d,
+            BlendOp::Subtract => s - d,
+            BlendOp::ReverseSubtract => d - s,
+            BlendOp::Min => src[channel].min(dst[channel]),
+            BlendOp::Max => src[channel].max(dst[channel]),
+        };
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::renderer::VertexLayout;
+
+    const SIZE: u32 = 16;
+
+    fn renderer() -> SoftwareRenderer {
+        let config = GraphicsConfig::default();
+        let mut renderer = SoftwareRenderer::new(&config).unwrap();
+        renderer.initialize(&config).unwrap();
+        renderer
+    }
+
+    fn layout(with_uv: bool) -> VertexLayout {
+        let mut attributes = vec![
+            VertexAttribute { name: "position".to_string(), format: VertexFormat::Float3, offset: 0, location: 0, divisor: 0 },
+            VertexAttribute { name: "color".to_string(), format: VertexFormat::Float4, offset: 12, location: 1, divisor: 0 },
+        ];
+        if with_uv {
+            attributes.push(VertexAttribute { name: "texcoord".to_string(), format: VertexFormat::Float2, offset: 28, location: 2, divisor: 0 });
+        }
+        VertexLayout { attributes, stride: if with_uv { 36 } else { 28 } }
+    }
+
+    fn vertex_bytes(vertices: &[[f32; 9]], with_uv: bool) -> Vec<u8> {
+        let count = if with_uv { 9 } else { 7 };
+        vertices.iter()
+            .flat_map(|v| v[..count].iter().flat_map(|f| f.to_le_bytes()).collect::<Vec<_>>())
+            .collect()
+    }
+
+    /// 画面全体を覆う四角形（2つの三角形）
+    fn quad(z: f32, color: [f32; 4], rect: [f32; 4]) -> Vec<[f32; 9]> {
+        let [x0, y0, x1, y1] = rect;
+        let v = |x: f32, y: f32, u: f32, t: f32| [x, y, z, color[0], color[1], color[2], color[3], u, t];
+        vec![
+            v(x0, y0, 0.0, 1.0), v(x1, y0, 1.0, 1.0), v(x1, y1, 1.0, 0.0),
+            v(x0, y0, 0.0, 1.0), v(x1, y1, 1.0, 0.0), v(x0, y1, 0.0, 0.0),
+        ]
+    }
+
+    /// 頂点列とシザー矩形（x, y, 幅, 高さ）の組
+    type TestDraw = (Vec<[f32; 9]>, Option<[i32; 4]>);
+
+    fn render(
+        renderer: &mut SoftwareRenderer,
+        pipeline: &PipelineState,
+        shader: &str,
+        draws: &[TestDraw],
+    ) -> Vec<u8> {
+        let with_uv = shader == SHADER_TEXTURED;
+        let mut context = renderer.create_context().unwrap();
+        let mut pass = context
+            .create_render_pass(Box::new(OffscreenTarget::new("target", SIZE, SIZE)))
+            .unwrap();
+        let mut commands = context.create_command_buffer().unwrap();
+
+        pass.begin().unwrap();
+        commands.begin().unwrap();
+        commands.clear([0.0, 0.0, 0.0, 1.0], 1.0, 0).unwrap();
+
+        context.set_shader(shader).unwrap();
+        context.set_vertex_buffer("vertices", 0).unwrap();
+        for (i, (vertices, scissor)) in draws.iter().enumerate() {
+            let name = format!("vertices{}", i);
+            let data = vertex_bytes(vertices, with_uv);
+            renderer.create_buffer(&name, BufferTarget::Vertex, BufferUsage::Static, Some(&data), data.len()).unwrap();
+            context.set_vertex_buffer(&name, 0).unwrap();
+
+            let mut state = pipeline.clone();
+            state.vertex_layout = layout(with_uv);
+            if let Some([x, y, w, h]) = scissor {
+                state.rasterizer_state.scissor_test = true;
+                pass.set_scissor(*x, *y, *w as u32, *h as u32).unwrap();
+            }
+            context.set_pipeline_state(&state).unwrap();
+            commands.draw(vertices.len() as u32, 0).unwrap();
+        }
+
+        commands.end().unwrap();
+        pass.end().unwrap();
+        commands.submit().unwrap();
+
+        renderer.read_pixels("target").unwrap()
+    }
+
+    fn pixel(pixels: &[u8], x: u32, y: u32) -> [u8; 4] {
+        let i = ((y * SIZE + x) * 4) as usize;
+        [pixels[i], pixels[i + 1], pixels[i + 2], pixels[i + 3]]
+    }
+
+    #[test]
+    fn test_triangle_coverage_and_culling() {
+        let mut renderer = renderer();
+        let mut pipeline = PipelineState::default();
+        pipeline.depth_stencil_state.depth_test = false;
+
+        // 左下半分を覆う反時計回りの三角形
+        let red = [1.0, 0.0, 0.0, 1.0];
+        let v = |x: f32, y: f32| [x, y, 0.5, red[0], red[1], red[2], red[3], 0.0, 0.0];
+        let ccw = vec![v(-1.0, 1.0), v(-1.0, -1.0), v(1.0, -1.0)];
+        let pixels = render(&mut renderer, &pipeline, SHADER_VERTEX_COLOR, &[(ccw.clone(), None)]);
+
+        assert_eq!(pixel(&pixels, 0, SIZE - 1), [255, 0, 0, 255]);
+        assert_eq!(pixel(&pixels, SIZE - 1, 0), [0, 0, 0, 255]);
+        // 対角線は右上側の辺なので、左上ルールにより対角線上の画素は含まれない
+        let covered = pixels.chunks_exact(4).filter(|p| p[0] == 255).count();
+        assert_eq!(covered, (SIZE * (SIZE - 1) / 2) as usize);
+
+        // 時計回りは背面としてカリング
+        let cw = vec![ccw[0], ccw[2], ccw[1]];
+        let pixels = render(&mut renderer, &pipeline, SHADER_VERTEX_COLOR, &[(cw, None)]);
+        assert!(pixels.chunks_exact(4).all(|p| p == [0, 0, 0, 255]));
+    }
+
+    #[test]
+    fn test_depth_test_and_blending() {
+        let mut renderer = renderer();
+        let full = [-1.0, -1.0, 1.0, 1.0];
+
+        // 手前の緑の後に奥の赤を描いても緑が残る
+        let pipeline = PipelineState::default();
+        let pixels = render(&mut renderer, &pipeline, SHADER_VERTEX_COLOR, &[
+            (quad(0.2, [0.0, 1.0, 0.0, 1.0], full), None),
+            (quad(0.8, [1.0, 0.0, 0.0, 1.0], full), None),
+        ]);
+        assert_eq!(pixel(&pixels, 8, 8), [0, 255, 0, 255]);
+
+        // 半透明の白をアルファブレンド
+        let mut pipeline = PipelineState::default();
+        pipeline.depth_stencil_state.depth_test = false;
+        pipeline.blend_state = BlendState {
+            enabled: true,
+            src_rgb: BlendFactor::SrcAlpha,
+            dst_rgb: BlendFactor::OneMinusSrcAlpha,
+            src_alpha: BlendFactor::One,
+            dst_alpha: BlendFactor::OneMinusSrcAlpha,
+            ..BlendState::default()
+        };
+        let pixels = render(&mut renderer, &pipeline, SHADER_VERTEX_COLOR, &[
+            (quad(0.5, [1.0, 1.0, 1.0, 0.5], full), None),
+        ]);
+        assert_eq!(pixel(&pixels, 3, 3), [128, 128, 128, 255]);
+    }
+
+    #[test]
+    fn test_scissor_and_stencil() {
+        let mut renderer = renderer();
+        let full = [-1.0, -1.0, 1.0, 1.0];
+
+        // シザー矩形の内側だけに描画し、ステンシルに1を書く
+        let mut pipeline = PipelineState::default();
+        pipeline.depth_stencil_state.depth_test = false;
+        pipeline.depth_stencil_state.stencil_test = true;
+        pipeline.depth_stencil_state.stencil_ref = 1;
+        pipeline.depth_stencil_state.stencil_pass_op = StencilOp::Replace;
+        let pixels = render(&mut renderer, &pipeline, SHADER_VERTEX_COLOR, &[
+            (quad(0.5, [0.0, 0.0, 1.0, 1.0], full), Some([4, 4, 8, 8])),
+        ]);
+        assert_eq!(pixel(&pixels, 4, 4), [0, 0, 255, 255]);
+        assert_eq!(pixel(&pixels, 11, 11), [0, 0, 255, 255]);
+        assert_eq!(pixel(&pixels, 3, 4), [0, 0, 0, 255]);
+        assert_eq!(pixel(&pixels, 12, 12), [0, 0, 0, 255]);
+
+        let device = renderer.device.lock().unwrap();
+        let stencil = &device.textures["target"].stencil;
+        assert_eq!(stencil[(4 * SIZE + 4) as usize], 1);
+        assert_eq!(stencil[0], 0);
+    }
+
+    #[test]
+    fn test_texturing() {
+        let mut renderer = renderer();
+
+        // 2x2のチェッカーテクスチャ（上段: 赤・緑、下段: 青・白）
+        let texels = [255, 0, 0, 255, 0, 255, 0, 255, 0, 0, 255, 255, 255, 255, 255, 255];
+        renderer.create_texture("checker", 2, 2, TextureFormat::R8G8B8A8, Some(&texels)).unwrap();
+
+        let mut pipeline = PipelineState::default();
+        pipeline.depth_stencil_state.depth_test = false;
+        {
+            let mut context = renderer.create_context().unwrap();
+            context.set_texture("checker", 0).unwrap();
+        }
+        let pixels = render(&mut renderer, &pipeline, SHADER_TEXTURED, &[
+            (quad(0.5, [1.0; 4], [-1.0, -1.0, 1.0, 1.0]), None),
+        ]);
+
+        assert_eq!(pixel(&pixels, 2, 2), [255, 0, 0, 255]);
+        assert_eq!(pixel(&pixels, 13, 2), [0, 255, 0, 255]);
+        assert_eq!(pixel(&pixels, 2, 13), [0, 0, 255, 255]);
+        assert_eq!(pixel(&pixels, 13, 13), [255, 255, 255, 255]);
+
+        // 未対応のシェーダーはエラー
+        let mut context = renderer.create_context().unwrap();
+        assert!(context.set_shader("custom.frag").is_err());
+    }
+
+    #[test]
+    fn test_offscreen_target_readback() {
+        let mut renderer = renderer();
+        let target = renderer.create_render_target("offscreen", 4, 2, TextureFormat::R8G8B8A8).unwrap();
+        assert_eq!(target.texture_name(), Some("offscreen"));
+
+        let mut context = renderer.create_context().unwrap();
+        let mut pass = context.create_render_pass(target).unwrap();
+        let mut commands = context.create_command_buffer().unwrap();
+        pass.begin().unwrap();
+        commands.begin().unwrap();
+        commands.clear([0.0, 0.0, 1.0, 1.0], 1.0, 0).unwrap();
+        commands.end().unwrap();
+        pass.end().unwrap();
+        commands.submit().unwrap();
+
+        let data = futures::executor::block_on(renderer.read_render_target("offscreen")).unwrap();
+        assert_eq!((data.width, data.height), (4, 2));
+        assert_eq!(data.pixel(3, 1), Some([0, 0, 255, 255]));
+
+        assert!(renderer.create_render_target("depth", 4, 4, TextureFormat::Depth32F).is_err());
+        assert!(futures::executor::block_on(renderer.read_render_target("missing")).is_err());
+    }
+
+    #[test]
+    fn test_command_buffer_requires_pass() {
+        let mut renderer = renderer();
+        let mut context = renderer.create_context().unwrap();
+        let mut commands = context.create_command_buffer().unwrap();
+
+        commands.begin().unwrap();
+        assert!(commands.clear([0.0; 4], 1.0, 0).is_err());
+        assert!(commands.draw(3, 0).is_err());
+    }
+}
@@ -0,0 +1,707 @@
+// LumosDesktop テクスチャコンテナ
+// DDS / KTX2 ファイルのヘッダー解析と圧縮テクスチャの展開
+
+use super::bc_decoder::{self, BlockFormat};
+use super::renderer::TextureFormat;
+use super::resource_manager::ResourceLoadError;
+use super::texture_loader::{ColorSpace, DecodedTexture};
+
+const DDS_MAGIC: &[u8; 4] = b"DDS ";
+const DDS_HEADER_SIZE: u32 = 124;
+const DDS_DX10_FOURCC: &[u8; 4] = b"DX10";
+
+const DDSD_MIPMAPCOUNT: u32 = 0x20000;
+const DDSD_DEPTH: u32 = 0x800000;
+const DDPF_ALPHAPIXELS: u32 = 0x1;
+const DDPF_FOURCC: u32 = 0x4;
+const DDPF_RGB: u32 = 0x40;
+const DDPF_LUMINANCE: u32 = 0x20000;
+const DDSCAPS2_CUBEMAP: u32 = 0x200;
+const DDSCAPS2_CUBEMAP_ALL_FACES: u32 = 0xfc00;
+const DDSCAPS2_VOLUME: u32 = 0x200000;
+const DDS_RESOURCE_MISC_TEXTURECUBE: u32 = 0x4;
+const DDS_DIMENSION_TEXTURE3D: u32 = 4;
+
+const KTX2_IDENTIFIER: [u8; 12] = [0xab, b'K', b'T', b'X', b' ', b'2', b'0', 0xbb, b'\r', b'\n', 0x1a, b'\n'];
+const KTX1_IDENTIFIER: [u8; 12] = [0xab, b'K', b'T', b'X', b' ', b'1', b'1', 0xbb, b'\r', b'\n', 0x1a, b'\n'];
+const KTX2_LEVEL_INDEX_OFFSET: usize = 80;
+
+/// ヘッダーで受け付ける最大の幅・高さ（D3D11 / Vulkanの一般的な上限）
+const MAX_DIMENSION: u32 = 16384;
+/// ヘッダーで受け付ける最大の配列レイヤー数
+const MAX_ARRAY_LAYERS: u32 = 2048;
+
+/// コンテナに格納された画素フォーマット
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SurfaceFormat {
+    Uncompressed(TextureFormat),
+    Compressed(BlockFormat),
+}
+
+impl SurfaceFormat {
+    /// 指定サイズの画像1枚のバイト数
+    pub fn image_size(self, width: u32, height: u32) -> usize {
+        match self {
+            SurfaceFormat::Uncompressed(format) => texel_size(format) * width as usize * height as usize,
+            SurfaceFormat::Compressed(format) => format.image_size(width, height),
+        }
+    }
+}
+
+/// 非圧縮フォーマットの1画素あたりのバイト数（コンテナから読み込めるもののみ）
+fn texel_size(format: TextureFormat) -> usize {
+    match format {
+        TextureFormat::R8 => 1,
+        TextureFormat::R8G8 | TextureFormat::R16F => 2,
+        TextureFormat::R8G8B8A8 | TextureFormat::B8G8R8A8 | TextureFormat::R16G16F | TextureFormat::R32F => 4,
+        TextureFormat::R16G16B16A16F | TextureFormat::R32G32F => 8,
+        TextureFormat::R32G32B32A32F => 16,
+        _ => 0,
+    }
+}
+
+/// DDS / KTX2 から読み込んだテクスチャ
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContainerTexture {
+    pub width: u32,
+    pub height: u32,
+    pub format: SurfaceFormat,
+    pub color_space: ColorSpace,
+    /// 配列レイヤー数（キューブマップの面は含まない）
+    pub array_layers: u32,
+    /// 面の数（キューブマップの場合は6）
+    pub faces: u32,
+    /// 基本レベルから順に並んだミップマップ（各レベルにレイヤー・面の順で全画像を連結）
+    pub levels: Vec<Vec<u8>>,
+}
+
+impl ContainerTexture {
+    pub fn mip_levels(&self) -> u32 {
+        self.levels.len() as u32
+    }
+
+    pub fn is_cubemap(&self) -> bool {
+        self.faces == 6
+    }
+
+    /// 指定ミップレベルのサイズ
+    pub fn level_size(&self, level: u32) -> (u32, u32) {
+        let shrink = |size: u32| size.checked_shr(level).unwrap_or(0).max(1);
+        (shrink(self.width), shrink(self.height))
+    }
+
+    /// 最大サイズに収まるまで上位のミップレベルを捨てる
+    pub fn fit_within(mut self, max_size: u32) -> Result<Self, ResourceLoadError> {
+        while self.width > max_size || self.height > max_size {
+            if self.levels.len() <= 1 {
+                return Err(ResourceLoadError::InvalidResource(format!(
+                    "テクスチャサイズが最大許容サイズを超えています: {}x{} (最大: {}x{})",
+                    self.width, self.height, max_size, max_size
+                )));
+            }
+            self.levels.remove(0);
+            (self.width, self.height) = self.level_size(1);
+        }
+        Ok(self)
+    }
+
+    /// レンダラーに登録できる形に変換
+    ///
+    /// 圧縮フォーマットは `native_compression` がfalseの場合や、対応する `TextureFormat` がない場合にCPUで展開します。
+    pub fn into_texture(self, native_compression: bool) -> Result<DecodedTexture, ResourceLoadError> {
+        let array_layers = self.array_layers * self.faces;
+        let cubemap = self.is_cubemap();
+
+        let (format, levels) = match self.format {
+            SurfaceFormat::Uncompressed(format) => (format, self.levels),
+            SurfaceFormat::Compressed(block) => match block.texture_format() {
+                Some(format) if native_compression => (format, self.levels),
+                _ => {
+                    let mut levels = Vec::with_capacity(self.levels.len());
+                    for (level, data) in self.levels.iter().enumerate() {
+                        let (width, height) = self.level_size(level as u32);
+                        let mut pixels = Vec::new();
+                        for image in data.chunks_exact(block.image_size(width, height)) {
+                            pixels.extend(bc_decoder::decompress_image(block, width, height, image)?);
+                        }
+                        levels.push(pixels);
+                    }
+                    (block.decompressed_format(), levels)
+                }
+            },
+        };
+
+        Ok(DecodedTexture {
+            width: self.width,
+            height: self.height,
+            format,
+            color_space: self.color_space,
+            array_layers,
+            cubemap,
+            levels,
+        })
+    }
+}
+
+/// 先頭のマジックナンバーからコンテナを判別して解析
+///
+/// 色空間がファイルに記録されていない場合（旧形式のDDS）は `default_color_space` を使用します。
+pub fn parse(bytes: &[u8], default_color_space: ColorSpace) -> Result<ContainerTexture, ResourceLoadError> {
+    if bytes.starts_with(DDS_MAGIC) {
+        parse_dds(bytes, default_color_space)
+    } else if bytes.starts_with(&KTX2_IDENTIFIER) {
+        parse_ktx2(bytes)
+    } else if bytes.starts_with(&KTX1_IDENTIFIER) {
+        Err(ResourceLoadError::UnsupportedFormat("KTX1 (KTX2に変換してください)".to_string()))
+    } else {
+        Err(ResourceLoadError::UnsupportedFormat("DDS / KTX2 ではないファイルです".to_string()))
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, ResourceLoadError> {
+    bytes.get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(truncated)
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Result<u64, ResourceLoadError> {
+    Ok(read_u32(bytes, offset)? as u64 | (read_u32(bytes, offset + 4)? as u64) << 32)
+}
+
+fn truncated() -> ResourceLoadError {
+    ResourceLoadError::InvalidResource("ファイルが途中で終わっています".to_string())
+}
+
+/// `offset` から `size` バイトを取り出す（範囲外や桁あふれは途中で終わったファイルとして扱う）
+fn read_bytes(bytes: &[u8], offset: usize, size: usize) -> Result<&[u8], ResourceLoadError> {
+    let end = offset.checked_add(size).ok_or_else(truncated)?;
+    bytes.get(offset..end).ok_or_else(truncated)
+}
+
+/// サイズから求まるミップレベル数の上限（1x1まで）
+fn max_mip_levels(width: u32, height: u32) -> u32 {
+    32 - width.max(height).max(1).leading_zeros()
+}
+
+/// ヘッダーのサイズ・ミップレベル数・レイヤー数を検証し、1レベルあたりの画像数（レイヤー×面）を返す
+///
+/// 不正なヘッダーで巨大な確保や桁あふれが起きないよう、データを読む前に上限を確認します。
+fn validate_layout(
+    width: u32,
+    height: u32,
+    mip_levels: u32,
+    array_layers: u32,
+    faces: u32,
+) -> Result<usize, ResourceLoadError> {
+    if width > MAX_DIMENSION || height > MAX_DIMENSION {
+        return Err(ResourceLoadError::InvalidResource(format!(
+            "テクスチャサイズが大きすぎます: {}x{} (最大: {}x{})", width, height, MAX_DIMENSION, MAX_DIMENSION
+        )));
+    }
+    let max_levels = max_mip_levels(width, height);
+    if mip_levels > max_levels {
+        return Err(ResourceLoadError::InvalidResource(format!(
+            "ミップレベル数が不正です: {} (最大: {})", mip_levels, max_levels
+        )));
+    }
+    if array_layers > MAX_ARRAY_LAYERS {
+        return Err(ResourceLoadError::InvalidResource(format!(
+            "配列レイヤー数が多すぎます: {} (最大: {})", array_layers, MAX_ARRAY_LAYERS
+        )));
+    }
+    array_layers
+        .checked_mul(faces)
+        .map(|images| images as usize)
+        .ok_or_else(|| ResourceLoadError::InvalidResource("画像数が不正です".to_string()))
+}
+
+/// レベル1枚分（全レイヤー・面）のバイト数
+fn level_data_size(format: SurfaceFormat, width: u32, height: u32, images: usize) -> Result<usize, ResourceLoadError> {
+    format
+        .image_size(width, height)
+        .checked_mul(images)
+        .ok_or_else(|| ResourceLoadError::InvalidResource("テクスチャデータのサイズが大きすぎます".to_string()))
+}
+
+/// DDSファイルを解析
+pub fn parse_dds(bytes: &[u8], default_color_space: ColorSpace) -> Result<ContainerTexture, ResourceLoadError> {
+    if !bytes.starts_with(DDS_MAGIC) || read_u32(bytes, 4)? != DDS_HEADER_SIZE {
+        return Err(ResourceLoadError::InvalidResource("DDSヘッダーが不正です".to_string()));
+    }
+
+    let flags = read_u32(bytes, 8)?;
+    let height = read_u32(bytes, 12)?;
+    let width = read_u32(bytes, 16)?;
+    let depth = read_u32(bytes, 24)?;
+    let mip_count = read_u32(bytes, 28)?;
+    let pf_flags = read_u32(bytes, 80)?;
+    let fourcc = bytes.get(84..88).ok_or_else(truncated)?;
+    let caps2 = read_u32(bytes, 112)?;
+
+    if caps2 & DDSCAPS2_VOLUME != 0 || (flags & DDSD_DEPTH != 0 && depth > 1) {
+        return Err(ResourceLoadError::UnsupportedFormat("ボリュームテクスチャ".to_string()));
+    }
+    if width == 0 || height == 0 {
+        return Err(ResourceLoadError::InvalidResource("テクスチャのサイズが0です".to_string()));
+    }
+
+    let (format, color_space, array_layers, cubemap, data_offset) =
+        if pf_flags & DDPF_FOURCC != 0 && fourcc == DDS_DX10_FOURCC {
+            let dxgi_format = read_u32(bytes, 128)?;
+            let dimension = read_u32(bytes, 132)?;
+            let misc_flags = read_u32(bytes, 136)?;
+            let array_size = read_u32(bytes, 140)?;
+            if dimension == DDS_DIMENSION_TEXTURE3D {
+                return Err(ResourceLoadError::UnsupportedFormat("ボリュームテクスチャ".to_string()));
+            }
+            let (format, color_space) = dxgi_format_to_surface(dxgi_format)?;
+            let cubemap = misc_flags & DDS_RESOURCE_MISC_TEXTURECUBE != 0;
+            (format, color_space, array_size.max(1), cubemap, 148)
+        } else {
+            let format = legacy_dds_format(bytes, pf_flags, fourcc)?;
+            let cubemap = caps2 & DDSCAPS2_CUBEMAP != 0;
+            if cubemap && caps2 & DDSCAPS2_CUBEMAP_ALL_FACES != DDSCAPS2_CUBEMAP_ALL_FACES {
+                return Err(ResourceLoadError::UnsupportedFormat("一部の面のみのキューブマップ".to_string()));
+            }
+            (format, default_color_space, 1, cubemap, 128)
+        };
+
+    let mip_levels = if flags & DDSD_MIPMAPCOUNT != 0 { mip_count.max(1) } else { 1 };
+    let faces = if cubemap { 6 } else { 1 };
+    let images = validate_layout(width, height, mip_levels, array_layers, faces)?;
+    let mut texture = ContainerTexture {
+        width,
+        height,
+        format,
+        color_space,
+        array_layers,
+        faces,
+        levels: vec![Vec::new(); mip_levels as usize],
+    };
+
+    // DDSは画像（レイヤー・面）ごとにミップチェーンが続くため、ミップレベルごとに並べ替える
+    let mut offset = data_offset;
+    for _ in 0..images {
+        for level in 0..mip_levels {
+            let (level_width, level_height) = texture.level_size(level);
+            let size = format.image_size(level_width, level_height);
+            let data = read_bytes(bytes, offset, size)?;
+            texture.levels[level as usize].extend_from_slice(data);
+            offset += size;
+        }
+    }
+
+    // アルファのないRGBフォーマットは不透明にする
+    if pf_flags & DDPF_RGB != 0 && pf_flags & DDPF_ALPHAPIXELS == 0 {
+        for level in &mut texture.levels {
+            for pixel in level.chunks_exact_mut(4) {
+                pixel[3] = 255;
+            }
+        }
+    }
+
+    Ok(texture)
+}
+
+/// DX10拡張ヘッダーのない旧形式DDSのピクセルフォーマット
+fn legacy_dds_format(bytes: &[u8], pf_flags: u32, fourcc: &[u8]) -> Result<SurfaceFormat, ResourceLoadError> {
+    if pf_flags & DDPF_FOURCC != 0 {
+        let format = match fourcc {
+            b"DXT1" => SurfaceFormat::Compressed(BlockFormat::Bc1),
+            b"DXT3" => SurfaceFormat::Compressed(BlockFormat::Bc2),
+            b"DXT5" => SurfaceFormat::Compressed(BlockFormat::Bc3),
+            // 乗算済みアルファのBC2/BC3はストレートアルファとして扱うと色が変わる
+            b"DXT2" | b"DXT4" => {
+                return Err(ResourceLoadError::UnsupportedFormat(format!(
+                    "乗算済みアルファのDDS ({})", String::from_utf8_lossy(fourcc)
+                )));
+            }
+            b"ATI1" | b"BC4U" => SurfaceFormat::Compressed(BlockFormat::Bc4),
+            b"ATI2" | b"BC5U" => SurfaceFormat::Compressed(BlockFormat::Bc5),
+            // D3DFORMATの数値が入る浮動小数点フォーマット
+            _ => match u32::from_le_bytes([fourcc[0], fourcc[1], fourcc[2], fourcc[3]]) {
+                111 => SurfaceFormat::Uncompressed(TextureFormat::R16F),
+                112 => SurfaceFormat::Uncompressed(TextureFormat::R16G16F),
+                113 => SurfaceFormat::Uncompressed(TextureFormat::R16G16B16A16F),
+                114 => SurfaceFormat::Uncompressed(TextureFormat::R32F),
+                115 => SurfaceFormat::Uncompressed(TextureFormat::R32G32F),
+                116 => SurfaceFormat::Uncompressed(TextureFormat::R32G32B32A32F),
+                _ => {
+                    return Err(ResourceLoadError::UnsupportedFormat(format!(
+                        "DDSのFourCC: {}", String::from_utf8_lossy(fourcc)
+                    )));
+                }
+            },
+        };
+        return Ok(format);
+    }
+
+    let bit_count = read_u32(bytes, 88)?;
+    let masks = [read_u32(bytes, 92)?, read_u32(bytes, 96)?, read_u32(bytes, 100)?];
+    let format = match (pf_flags & (DDPF_RGB | DDPF_LUMINANCE), bit_count, masks) {
+        (DDPF_RGB, 32, [0xff, 0xff00, 0xff0000]) => TextureFormat::R8G8B8A8,
+        (DDPF_RGB, 32, [0xff0000, 0xff00, 0xff]) => TextureFormat::B8G8R8A8,
+        (DDPF_LUMINANCE, 8, _) => TextureFormat::R8,
+        _ => {
+            return Err(ResourceLoadError::UnsupportedFormat(format!(
+                "DDSのピクセルフォーマット (フラグ: {:#x}, {}ビット, マスク: {:x?})", pf_flags, bit_count, masks
+            )));
+        }
+    };
+    Ok(SurfaceFormat::Uncompressed(format))
+}
+
+/// DXGI_FORMATの値を変換
+fn dxgi_format_to_surface(format: u32) -> Result<(SurfaceFormat, ColorSpace), ResourceLoadError> {
+    use ColorSpace::{Linear, Srgb};
+    use SurfaceFormat::{Compressed, Uncompressed};
+
+    let surface = match format {
+        2 => (Uncompressed(TextureFormat::R32G32B32A32F), Linear),
+        10 => (Uncompressed(TextureFormat::R16G16B16A16F), Linear),
+        16 => (Uncompressed(TextureFormat::R32G32F), Linear),
+        28 => (Uncompressed(TextureFormat::R8G8B8A8), Linear),
+        29 => (Uncompressed(TextureFormat::R8G8B8A8), Srgb),
+        34 => (Uncompressed(TextureFormat::R16G16F), Linear),
+        41 => (Uncompressed(TextureFormat::R32F), Linear),
+        49 => (Uncompressed(TextureFormat::R8G8), Linear),
+        54 => (Uncompressed(TextureFormat::R16F), Linear),
+        61 => (Uncompressed(TextureFormat::R8), Linear),
+        71 => (Compressed(BlockFormat::Bc1), Linear),
+        72 => (Compressed(BlockFormat::Bc1), Srgb),
+        74 => (Compressed(BlockFormat::Bc2), Linear),
+        75 => (Compressed(BlockFormat::Bc2), Srgb),
+        77 => (Compressed(BlockFormat::Bc3), Linear),
+        78 => (Compressed(BlockFormat::Bc3), Srgb),
+        80 => (Compressed(BlockFormat::Bc4), Linear),
+        83 => (Compressed(BlockFormat::Bc5), Linear),
+        87 => (Uncompressed(TextureFormat::B8G8R8A8), Linear),
+        91 => (Uncompressed(TextureFormat::B8G8R8A8), Srgb),
+        95 => (Compressed(BlockFormat::Bc6h { signed: false }), Linear),
+        96 => (Compressed(BlockFormat::Bc6h { signed: true }), Linear),
+        98 => (Compressed(BlockFormat::Bc7), Linear),
+        99 => (Compressed(BlockFormat::Bc7), Srgb),
+        _ => return Err(ResourceLoadError::UnsupportedFormat(format!("DXGI_FORMAT {}", format))),
+    };
+    Ok(surface)
+}
+
+/// KTX2ファイルを解析
+pub fn parse_ktx2(bytes: &[u8]) -> Result<ContainerTexture, ResourceLoadError> {
+    if !bytes.starts_with(&KTX2_IDENTIFIER) {
+        return Err(ResourceLoadError::InvalidResource("KTX2ヘッダーが不正です".to_string()));
+    }
+
+    let vk_format = read_u32(bytes, 12)?;
+    let width = read_u32(bytes, 20)?;
+    let height = read_u32(bytes, 24)?.max(1);
+    let depth = read_u32(bytes, 28)?;
+    let layer_count = read_u32(bytes, 32)?;
+    let face_count = read_u32(bytes, 36)?;
+    let level_count = read_u32(bytes, 40)?;
+    let supercompression = read_u32(bytes, 44)?;
+
+    if supercompression != 0 {
+        return Err(ResourceLoadError::UnsupportedFormat(format!("KTX2の超圧縮 (スキーム {})", supercompression)));
+    }
+    if vk_format == 0 {
+        return Err(ResourceLoadError::UnsupportedFormat("Basis Universal".to_string()));
+    }
+    if depth > 0 {
+        return Err(ResourceLoadError::UnsupportedFormat("ボリュームテクスチャ".to_string()));
+    }
+    if face_count != 1 && face_count != 6 {
+        return Err(ResourceLoadError::InvalidResource(format!("KTX2の面の数が不正です: {}", face_count)));
+    }
+    if width == 0 {
+        return Err(ResourceLoadError::InvalidResource("テクスチャのサイズが0です".to_string()));
+    }
+
+    let (format, color_space) = vk_format_to_surface(vk_format)?;
+    let level_count = level_count.max(1);
+    let images = validate_layout(width, height, level_count, layer_count.max(1), face_count)?;
+    let mut texture = ContainerTexture {
+        width,
+        height,
+        format,
+        color_space,
+        array_layers: layer_count.max(1),
+        faces: face_count,
+        levels: Vec::new(),
+    };
+
+    // レベルインデックスは基本レベルから順に並ぶ（データ自体は小さいレベルから格納される）
+    for level in 0..level_count {
+        let entry = KTX2_LEVEL_INDEX_OFFSET + level as usize * 24;
+        let offset = usize::try_from(read_u64(bytes, entry)?).map_err(|_| truncated())?;
+        let length = read_u64(bytes, entry + 8)?;
+
+        let (level_width, level_height) = texture.level_size(level);
+        let required = level_data_size(format, level_width, level_height, images)?;
+        if length < required as u64 {
+            return Err(ResourceLoadError::InvalidResource(format!(
+                "ミップレベル {} のデータが不足しています: {} バイト (必要: {} バイト)", level, length, required
+            )));
+        }
+        let data = read_bytes(bytes, offset, required)?;
+        texture.levels.push(data.to_vec());
+    }
+
+    Ok(texture)
+}
+
+/// VkFormatの値を変換
+fn vk_format_to_surface(format: u32) -> Result<(SurfaceFormat, ColorSpace), ResourceLoadError> {
+    use ColorSpace::{Linear, Srgb};
+    use SurfaceFormat::{Compressed, Uncompressed};
+
+    let surface = match format {
+        9 => (Uncompressed(TextureFormat::R8), Linear),
+        15 => (Uncompressed(TextureFormat::R8), Srgb),
+        16 => (Uncompressed(TextureFormat::R8G8), Linear),
+        37 => (Uncompressed(TextureFormat::R8G8B8A8), Linear),
+        43 => (Uncompressed(TextureFormat::R8G8B8A8), Srgb),
+        44 => (Uncompressed(TextureFormat::B8G8R8A8), Linear),
+        50 => (Uncompressed(TextureFormat::B8G8R8A8), Srgb),
+        76 => (Uncompressed(TextureFormat::R16F), Linear),
+        83 => (Uncompressed(TextureFormat::R16G16F), Linear),
+        97 => (Uncompressed(TextureFormat::R16G16B16A16F), Linear),
+        100 => (Uncompressed(TextureFormat::R32F), Linear),
+        103 => (Uncompressed(TextureFormat::R32G32F), Linear),
+        109 => (Uncompressed(TextureFormat::R32G32B32A32F), Linear),
+        131 | 133 => (Compressed(BlockFormat::Bc1), Linear),
+        132 | 134 => (Compressed(BlockFormat::Bc1), Srgb),
+        135 => (Compressed(BlockFormat::Bc2), Linear),
+        136 => (Compressed(BlockFormat::Bc2), Srgb),
+        137 => (Compressed(BlockFormat::Bc3), Linear),
+        138 => (Compressed(BlockFormat::Bc3), Srgb),
+        139 => (Compressed(BlockFormat::Bc4), Linear),
+        141 => (Compressed(BlockFormat::Bc5), Linear),
+        143 => (Compressed(BlockFormat::Bc6h { signed: false }), Linear),
+        144 => (Compressed(BlockFormat::Bc6h { signed: true }), Linear),
+        145 => (Compressed(BlockFormat::Bc7), Linear),
+        146 => (Compressed(BlockFormat::Bc7), Srgb),
+        _ => return Err(ResourceLoadError::UnsupportedFormat(format!("VkFormat {}", format))),
+    };
+    Ok(surface)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 赤一色のBC1ブロック
+    const RED_BC1: [u8; 8] = [0x00, 0xf8, 0x00, 0xf8, 0, 0, 0, 0];
+
+    fn put_u32(bytes: &mut [u8], offset: usize, value: u32) {
+        bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn dds_header(width: u32, height: u32, mip_levels: u32, fourcc: &[u8; 4], caps2: u32) -> Vec<u8> {
+        let mut bytes = vec![0u8; 128];
+        bytes[..4].copy_from_slice(DDS_MAGIC);
+        put_u32(&mut bytes, 4, DDS_HEADER_SIZE);
+        put_u32(&mut bytes, 8, 0x1007 | DDSD_MIPMAPCOUNT);
+        put_u32(&mut bytes, 12, height);
+        put_u32(&mut bytes, 16, width);
+        put_u32(&mut bytes, 28, mip_levels);
+        put_u32(&mut bytes, 76, 32);
+        put_u32(&mut bytes, 80, DDPF_FOURCC);
+        bytes[84..88].copy_from_slice(fourcc);
+        put_u32(&mut bytes, 112, caps2);
+        bytes
+    }
+
+    #[test]
+    fn test_parse_legacy_dds() {
+        // 8x4のDXT1: レベル0は2ブロック、レベル1とレベル2は1ブロック
+        let mut bytes = dds_header(8, 4, 3, b"DXT1", 0);
+        for _ in 0..4 {
+            bytes.extend_from_slice(&RED_BC1);
+        }
+
+        let texture = parse(&bytes, ColorSpace::Srgb).unwrap();
+        assert_eq!((texture.width, texture.height, texture.mip_levels()), (8, 4, 3));
+        assert_eq!(texture.format, SurfaceFormat::Compressed(BlockFormat::Bc1));
+        assert_eq!(texture.color_space, ColorSpace::Srgb);
+        assert_eq!(texture.levels.iter().map(Vec::len).collect::<Vec<_>>(), vec![16, 8, 8]);
+        assert!(!texture.is_cubemap());
+
+        // データが足りない場合はエラー
+        assert!(parse(&bytes[..bytes.len() - 1], ColorSpace::Srgb).is_err());
+    }
+
+    #[test]
+    fn test_parse_dds_premultiplied_rejected() {
+        // DXT2/DXT4は乗算済みアルファなのでBC2/BC3として読まない
+        for fourcc in [b"DXT2", b"DXT4"] {
+            let mut bytes = dds_header(4, 4, 1, fourcc, 0);
+            bytes.extend_from_slice(&[0u8; 16]);
+            assert!(matches!(parse(&bytes, ColorSpace::Srgb), Err(ResourceLoadError::UnsupportedFormat(_))));
+        }
+
+        let mut bytes = dds_header(4, 4, 1, b"DXT5", 0);
+        bytes.extend_from_slice(&[0u8; 16]);
+        assert_eq!(parse(&bytes, ColorSpace::Srgb).unwrap().format, SurfaceFormat::Compressed(BlockFormat::Bc3));
+    }
+
+    #[test]
+    fn test_parse_dds_cubemap_and_array() {
+        // 旧形式のキューブマップは6面それぞれにミップチェーンが続く
+        let mut bytes = dds_header(4, 4, 2, b"DXT1", DDSCAPS2_CUBEMAP | DDSCAPS2_CUBEMAP_ALL_FACES);
+        for face in 0..6u8 {
+            for level in 0..2u8 {
+                let mut block = RED_BC1;
+                block[4] = face * 2 + level;
+                bytes.extend_from_slice(&block);
+            }
+        }
+        let texture = parse_dds(&bytes, ColorSpace::Linear).unwrap();
+        assert!(texture.is_cubemap());
+        assert_eq!(texture.levels[0].len(), 6 * 8);
+        // レベルごとに並べ替えられている
+        assert_eq!(texture.levels[0][8 + 4], 2);
+        assert_eq!(texture.levels[1][4], 1);
+
+        // DX10ヘッダー付きのBC7 sRGBのテクスチャ配列
+        let mut bytes = dds_header(4, 4, 1, DDS_DX10_FOURCC, 0);
+        bytes.extend_from_slice(&[0u8; 20]);
+        put_u32(&mut bytes, 128, 99);
+        put_u32(&mut bytes, 132, 3);
+        put_u32(&mut bytes, 140, 3);
+        bytes.extend_from_slice(&[0u8; 16 * 3]);
+        let texture = parse_dds(&bytes, ColorSpace::Linear).unwrap();
+        assert_eq!(texture.format, SurfaceFormat::Compressed(BlockFormat::Bc7));
+        assert_eq!(texture.color_space, ColorSpace::Srgb);
+        assert_eq!((texture.array_layers, texture.faces), (3, 1));
+
+        // ボリュームテクスチャは未対応
+        put_u32(&mut bytes, 132, DDS_DIMENSION_TEXTURE3D);
+        assert!(matches!(parse_dds(&bytes, ColorSpace::Linear), Err(ResourceLoadError::UnsupportedFormat(_))));
+    }
+
+    #[test]
+    fn test_parse_dds_uncompressed() {
+        let mut bytes = dds_header(2, 1, 1, b"\0\0\0\0", 0);
+        put_u32(&mut bytes, 80, DDPF_RGB);
+        put_u32(&mut bytes, 88, 32);
+        put_u32(&mut bytes, 92, 0xff0000);
+        put_u32(&mut bytes, 96, 0xff00);
+        put_u32(&mut bytes, 100, 0xff);
+        bytes.extend_from_slice(&[1, 2, 3, 0, 4, 5, 6, 0]);
+
+        // アルファのないBGRXは不透明になる
+        let texture = parse_dds(&bytes, ColorSpace::Srgb).unwrap();
+        assert_eq!(texture.format, SurfaceFormat::Uncompressed(TextureFormat::B8G8R8A8));
+        assert_eq!(texture.levels[0], vec![1, 2, 3, 255, 4, 5, 6, 255]);
+    }
+
+    fn ktx2_file(vk_format: u32, width: u32, height: u32, layers: u32, faces: u32, levels: &[Vec<u8>]) -> Vec<u8> {
+        let mut bytes = vec![0u8; KTX2_LEVEL_INDEX_OFFSET + levels.len() * 24];
+        bytes[..12].copy_from_slice(&KTX2_IDENTIFIER);
+        put_u32(&mut bytes, 12, vk_format);
+        put_u32(&mut bytes, 20, width);
+        put_u32(&mut bytes, 24, height);
+        put_u32(&mut bytes, 32, layers);
+        put_u32(&mut bytes, 36, faces);
+        put_u32(&mut bytes, 40, levels.len() as u32);
+
+        // 小さいレベルから順に格納する
+        for (level, data) in levels.iter().enumerate().rev() {
+            let entry = KTX2_LEVEL_INDEX_OFFSET + level * 24;
+            let offset = bytes.len() as u64;
+            bytes[entry..entry + 8].copy_from_slice(&offset.to_le_bytes());
+            bytes[entry + 8..entry + 16].copy_from_slice(&(data.len() as u64).to_le_bytes());
+            bytes.extend_from_slice(data);
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_parse_ktx2() {
+        let levels = vec![RED_BC1.repeat(4 * 6), RED_BC1.repeat(6), RED_BC1.repeat(6)];
+        let bytes = ktx2_file(134, 8, 8, 0, 6, &levels);
+
+        let texture = parse(&bytes, ColorSpace::Linear).unwrap();
+        assert_eq!(texture.format, SurfaceFormat::Compressed(BlockFormat::Bc1));
+        assert_eq!(texture.color_space, ColorSpace::Srgb);
+        assert_eq!((texture.width, texture.height, texture.mip_levels()), (8, 8, 3));
+        assert_eq!((texture.array_layers, texture.faces), (1, 6));
+        assert_eq!(texture.levels, levels);
+
+        // 超圧縮は未対応
+        let mut compressed = bytes.clone();
+        put_u32(&mut compressed, 44, 2);
+        assert!(matches!(parse_ktx2(&compressed), Err(ResourceLoadError::UnsupportedFormat(_))));
+
+        // レベルのデータが不足している
+        let short = ktx2_file(134, 8, 8, 0, 1, &[RED_BC1.repeat(3)]);
+        assert!(parse_ktx2(&short).is_err());
+
+        let mut ktx1 = KTX1_IDENTIFIER.to_vec();
+        ktx1.extend_from_slice(&[0; 64]);
+        assert!(matches!(parse(&ktx1, ColorSpace::Srgb), Err(ResourceLoadError::UnsupportedFormat(_))));
+    }
+
+    #[test]
+    fn test_hostile_headers() {
+        let invalid = |result: Result<ContainerTexture, ResourceLoadError>| {
+            matches!(result, Err(ResourceLoadError::InvalidResource(_)))
+        };
+
+        // 巨大なミップレベル数・サイズ
+        let mut bytes = dds_header(4, 4, u32::MAX, b"DXT1", 0);
+        bytes.extend_from_slice(&RED_BC1.repeat(3));
+        assert!(invalid(parse_dds(&bytes, ColorSpace::Srgb)));
+        let bytes = dds_header(u32::MAX, u32::MAX, 1, b"DXT1", 0);
+        assert!(invalid(parse_dds(&bytes, ColorSpace::Srgb)));
+
+        // 配列サイズ×キューブマップの面数が桁あふれする
+        let mut bytes = dds_header(4, 4, 1, DDS_DX10_FOURCC, 0);
+        bytes.extend_from_slice(&[0u8; 20]);
+        put_u32(&mut bytes, 128, 71);
+        put_u32(&mut bytes, 136, DDS_RESOURCE_MISC_TEXTURECUBE);
+        put_u32(&mut bytes, 140, u32::MAX / 3);
+        assert!(invalid(parse_dds(&bytes, ColorSpace::Srgb)));
+
+        // レベル数の上限を超えるKTX2
+        let mut bytes = ktx2_file(131, 4, 4, 0, 1, &[RED_BC1.to_vec()]);
+        put_u32(&mut bytes, 40, 40);
+        assert!(invalid(parse_ktx2(&bytes)));
+
+        // オフセット + 長さが桁あふれする
+        let mut bytes = ktx2_file(131, 4, 4, 0, 1, &[RED_BC1.to_vec()]);
+        let entry = KTX2_LEVEL_INDEX_OFFSET;
+        bytes[entry..entry + 8].copy_from_slice(&(u64::MAX - 4).to_le_bytes());
+        assert!(invalid(parse_ktx2(&bytes)));
+
+        // 32以上のミップレベルでもパニックしない
+        let texture = parse_ktx2(&ktx2_file(131, 4, 4, 0, 1, &[RED_BC1.to_vec()])).unwrap();
+        assert_eq!(texture.level_size(40), (1, 1));
+    }
+
+    #[test]
+    fn test_into_texture() {
+        let levels = [RED_BC1.repeat(4), RED_BC1.to_vec(), RED_BC1.to_vec()];
+        let container = parse_ktx2(&ktx2_file(131, 8, 8, 2, 1, &[
+            levels[0].repeat(2), levels[1].repeat(2), levels[2].repeat(2),
+        ])).unwrap();
+
+        // ネイティブ対応の場合はそのまま
+        let native = container.clone().into_texture(true).unwrap();
+        assert_eq!(native.format, TextureFormat::BC1);
+        assert_eq!(native.array_layers, 2);
+        assert_eq!(native.first_layer(0), &levels[0][..]);
+
+        // 非対応の場合はRGBA8に展開
+        let decompressed = container.clone().into_texture(false).unwrap();
+        assert_eq!(decompressed.format, TextureFormat::R8G8B8A8);
+        assert_eq!(decompressed.levels.iter().map(Vec::len).collect::<Vec<_>>(), vec![8 * 8 * 4 * 2, 4 * 4 * 4 * 2, 2 * 2 * 4 * 2]);
+        assert_eq!(&decompressed.levels[2][..4], &[255, 0, 0, 255]);
+
+        // 最大サイズを超える場合は上位のミップレベルを捨てる
+        let fitted = container.clone().fit_within(4).unwrap();
+        assert_eq!((fitted.width, fitted.height, fitted.mip_levels()), (4, 4, 2));
+        assert!(container.fit_within(1).is_err());
+    }
+}
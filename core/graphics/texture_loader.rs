@@ -0,0 +1,439 @@
+// LumosDesktop テクスチャローダー
+// 画像ファイルのデコード、フォーマット変換、ミップマップ生成
+
+use image::{DynamicImage, ImageFormat, Rgba32FImage};
+use image::imageops::FilterType;
+
+use super::renderer::TextureFormat;
+use super::resource_manager::ResourceLoadError;
+
+/// テクスチャの色空間
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ColorSpace {
+    /// sRGBでエンコードされた色（壁紙、アイコンなど）
+    #[default]
+    Srgb,
+    /// 線形な値（法線マップ、マスクなど）
+    Linear,
+}
+
+/// テクスチャ読み込みオプション
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextureLoadOptions {
+    /// 変換先のテクスチャフォーマット
+    pub format: TextureFormat,
+    /// 画像の色空間
+    pub color_space: ColorSpace,
+    /// ミップマップを生成する
+    pub generate_mipmaps: bool,
+    /// 最大テクスチャサイズを超える画像を縮小する（falseの場合はエラー）
+    pub downscale_to_fit: bool,
+}
+
+impl Default for TextureLoadOptions {
+    fn default() -> Self {
+        Self {
+            format: TextureFormat::R8G8B8A8,
+            color_space: ColorSpace::Srgb,
+            generate_mipmaps: true,
+            downscale_to_fit: false,
+        }
+    }
+}
+
+/// デコード済みのテクスチャ
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedTexture {
+    pub width: u32,
+    pub height: u32,
+    pub format: TextureFormat,
+    pub color_space: ColorSpace,
+    /// 配列レイヤー数（キューブマップの場合は6面を含む）
+    pub array_layers: u32,
+    pub cubemap: bool,
+    /// 基本レベルから順に並んだミップマップ（各レベルに全レイヤーを連結）
+    pub levels: Vec<Vec<u8>>,
+}
+
+impl DecodedTexture {
+    pub fn mip_levels(&self) -> u32 {
+        self.levels.len() as u32
+    }
+
+    /// 全ミップレベルの合計サイズ（バイト）
+    pub fn size(&self) -> usize {
+        self.levels.iter().map(|level| level.len()).sum()
+    }
+
+    /// 指定ミップレベルの先頭レイヤーのデータ
+    pub fn first_layer(&self, level: usize) -> &[u8] {
+        let data = &self.levels[level];
+        &data[..data.len() / self.array_layers.max(1) as usize]
+    }
+
+    /// 全ミップレベルを連結したデータ
+    pub fn into_data(self) -> Vec<u8> {
+        self.levels.concat()
+    }
+}
+
+/// ピクセルの成分の型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Component {
+    Unorm8,
+    Float16,
+    Float32,
+}
+
+/// フォーマットごとの成分の型と、各成分に対応するRGBAのチャンネル
+fn pixel_layout(format: TextureFormat) -> Option<(Component, &'static [usize])> {
+    use TextureFormat::*;
+
+    let layout = match format {
+        R8 => (Component::Unorm8, &[0][..]),
+        R8G8 => (Component::Unorm8, &[0, 1][..]),
+        R8G8B8 => (Component::Unorm8, &[0, 1, 2][..]),
+        R8G8B8A8 => (Component::Unorm8, &[0, 1, 2, 3][..]),
+        B8G8R8A8 => (Component::Unorm8, &[2, 1, 0, 3][..]),
+        R16F => (Component::Float16, &[0][..]),
+        R16G16F => (Component::Float16, &[0, 1][..]),
+        R16G16B16F => (Component::Float16, &[0, 1, 2][..]),
+        R16G16B16A16F => (Component::Float16, &[0, 1, 2, 3][..]),
+        R32F => (Component::Float32, &[0][..]),
+        R32G32F => (Component::Float32, &[0, 1][..]),
+        R32G32B32F => (Component::Float32, &[0, 1, 2][..]),
+        R32G32B32A32F => (Component::Float32, &[0, 1, 2, 3][..]),
+        _ => return None,
+    };
+    Some(layout)
+}
+
+/// ミップマップの段数（1x1まで）
+pub fn mip_level_count(width: u32, height: u32) -> u32 {
+    32 - width.max(height).max(1).leading_zeros()
+}
+
+/// 指定したミップレベルのサイズ
+pub fn mip_level_size(width: u32, height: u32, level: u32) -> (u32, u32) {
+    ((width >> level).max(1), (height >> level).max(1))
+}
+
+/// 画像ファイルのデータをデコードしてテクスチャを作成
+///
+/// 拡張子が分かる場合はそのフォーマットとして、分からない場合は内容から判別してデコードします。
+pub fn decode_image(
+    bytes: &[u8],
+    extension: Option<&str>,
+    max_size: u32,
+    options: &TextureLoadOptions,
+) -> Result<DecodedTexture, ResourceLoadError> {
+    let image = match extension.and_then(ImageFormat::from_extension) {
+        Some(format) => image::load_from_memory_with_format(bytes, format),
+        None => image::load_from_memory(bytes),
+    }
+    .map_err(|e| ResourceLoadError::DecodingFailed(e.to_string()))?;
+
+    build_texture(image, max_size, options)
+}
+
+/// デコード済みの画像からテクスチャを作成
+///
+/// 縮小やミップマップの生成はsRGBの場合は線形化した値で行い、透明な画素の色が
+/// 混ざらないようアルファで重み付けします。浮動小数点フォーマットには線形の値を格納します。
+pub fn build_texture(
+    image: DynamicImage,
+    max_size: u32,
+    options: &TextureLoadOptions,
+) -> Result<DecodedTexture, ResourceLoadError> {
+    if pixel_layout(options.format).is_none() {
+        return Err(ResourceLoadError::UnsupportedFormat(format!(
+            "画像から変換できないテクスチャフォーマットです: {:?}", options.format
+        )));
+    }
+    if image.width() == 0 || image.height() == 0 {
+        return Err(ResourceLoadError::InvalidResource("画像のサイズが0です".to_string()));
+    }
+
+    let mut pixels = image.into_rgba32f();
+    if options.color_space == ColorSpace::Srgb {
+        for pixel in pixels.pixels_mut() {
+            for channel in &mut pixel.0[..3] {
+                *channel = srgb_to_linear(*channel);
+            }
+        }
+    }
+
+    let (width, height) = pixels.dimensions();
+    if width > max_size || height > max_size {
+        if !options.downscale_to_fit {
+            return Err(ResourceLoadError::InvalidResource(format!(
+                "テクスチャサイズが最大許容サイズを超えています: {}x{} (最大: {}x{})",
+                width, height, max_size, max_size
+            )));
+        }
+        let (width, height) = fit_within(width, height, max_size);
+        pixels = image::imageops::resize(&pixels, width, height, FilterType::Triangle);
+    }
+
+    let (width, height) = pixels.dimensions();
+    let mut levels = vec![encode_level(&pixels, options.format, options.color_space)];
+    if options.generate_mipmaps {
+        let mut current = pixels;
+        while current.width() > 1 || current.height() > 1 {
+            current = downsample(&current);
+            levels.push(encode_level(&current, options.format, options.color_space));
+        }
+    }
+
+    Ok(DecodedTexture {
+        width,
+        height,
+        format: options.format,
+        color_space: options.color_space,
+        array_layers: 1,
+        cubemap: false,
+        levels,
+    })
+}
+
+/// 縦横比を保って最大サイズに収まるサイズを計算
+fn fit_within(width: u32, height: u32, max_size: u32) -> (u32, u32) {
+    let scale = max_size as f64 / width.max(height) as f64;
+    let fit = |size: u32| ((size as f64 * scale).round() as u32).clamp(1, max_size.max(1));
+    (fit(width), fit(height))
+}
+
+/// 2x2の画素をアルファで重み付けして平均し、半分のサイズに縮小
+fn downsample(image: &Rgba32FImage) -> Rgba32FImage {
+    let (width, height) = image.dimensions();
+    let (next_width, next_height) = mip_level_size(width, height, 1);
+
+    Rgba32FImage::from_fn(next_width, next_height, |x, y| {
+        let xs = [(2 * x).min(width - 1), (2 * x + 1).min(width - 1)];
+        let ys = [(2 * y).min(height - 1), (2 * y + 1).min(height - 1)];
+
+        let mut color = [0.0f32; 3];
+        let mut weighted = [0.0f32; 3];
+        let mut alpha = 0.0f32;
+        for &sy in &ys {
+            for &sx in &xs {
+                let pixel = image.get_pixel(sx, sy).0;
+                for c in 0..3 {
+                    color[c] += pixel[c];
+                    weighted[c] += pixel[c] * pixel[3];
+                }
+                alpha += pixel[3];
+            }
+        }
+
+        // 完全に透明な場合は色をそのまま平均する
+        let rgb = if alpha > 0.0 {
+            weighted.map(|c| c / alpha)
+        } else {
+            color.map(|c| c / 4.0)
+        };
+        image::Rgba([rgb[0], rgb[1], rgb[2], alpha / 4.0])
+    })
+}
+
+/// 1つのミップレベルを指定フォーマットのバイト列に変換
+fn encode_level(image: &Rgba32FImage, format: TextureFormat, color_space: ColorSpace) -> Vec<u8> {
+    let Some((component, channels)) = pixel_layout(format) else {
+        return Vec::new();
+    };
+    let component_size = match component {
+        Component::Unorm8 => 1,
+        Component::Float16 => 2,
+        Component::Float32 => 4,
+    };
+
+    let mut data = Vec::with_capacity(image.width() as usize * image.height() as usize * channels.len() * component_size);
+    for pixel in image.pixels() {
+        for &channel in channels {
+            let value = pixel.0[channel];
+            match component {
+                Component::Unorm8 => {
+                    // 8ビットのフォーマットにはエンコードされた値を格納する
+                    let value = if channel < 3 && color_space == ColorSpace::Srgb {
+                        linear_to_srgb(value)
+                    } else {
+                        value
+                    };
+                    data.push((value.clamp(0.0, 1.0) * 255.0).round() as u8);
+                }
+                Component::Float16 => data.extend_from_slice(&f32_to_f16(value).to_le_bytes()),
+                Component::Float32 => data.extend_from_slice(&value.to_le_bytes()),
+            }
+        }
+    }
+    data
+}
+
+/// sRGBの値を線形に変換
+pub fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// 線形の値をsRGBに変換
+pub fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// f32を半精度浮動小数点数のビット列に変換
+fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+
+    // 無限大・NaN
+    if exponent == 0xff {
+        return sign | 0x7c00 | if mantissa != 0 { 0x200 } else { 0 };
+    }
+
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+    if exponent <= 0 {
+        if exponent < -10 {
+            return sign;
+        }
+        // 非正規化数
+        let mantissa = mantissa | 0x80_0000;
+        let shift = (14 - exponent) as u32;
+        let half = mantissa >> shift;
+        let round = (mantissa >> (shift - 1)) & 1;
+        return sign | (half + round) as u16;
+    }
+
+    let half = ((exponent as u32) << 10) | (mantissa >> 13);
+    let round = (mantissa >> 12) & 1;
+    sign | (half + round) as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    use image::RgbaImage;
+
+    fn checker(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageRgba8(RgbaImage::from_fn(width, height, |x, y| {
+            if (x + y) % 2 == 0 { image::Rgba([255, 255, 255, 255]) } else { image::Rgba([0, 0, 0, 255]) }
+        }))
+    }
+
+    #[test]
+    fn test_decode_png() {
+        let image = RgbaImage::from_fn(4, 2, |x, y| image::Rgba([x as u8 * 60, y as u8 * 100, 7, 200]));
+        let mut bytes = Vec::new();
+        image.write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png).unwrap();
+
+        let options = TextureLoadOptions { generate_mipmaps: false, ..TextureLoadOptions::default() };
+        let texture = decode_image(&bytes, Some("png"), 4096, &options).unwrap();
+        assert_eq!((texture.width, texture.height, texture.mip_levels()), (4, 2, 1));
+        assert_eq!(texture.levels[0], image.into_raw());
+
+        // 拡張子がなくても内容から判別できる
+        assert!(decode_image(&bytes, None, 4096, &options).is_ok());
+        assert!(matches!(
+            decode_image(b"not an image", Some("png"), 4096, &options),
+            Err(ResourceLoadError::DecodingFailed(_))
+        ));
+    }
+
+    #[test]
+    fn test_mip_chain() {
+        assert_eq!(mip_level_count(1, 1), 1);
+        assert_eq!(mip_level_count(256, 64), 9);
+        assert_eq!(mip_level_count(5, 3), 3);
+        assert_eq!(mip_level_size(5, 3, 2), (1, 1));
+
+        let texture = build_texture(checker(8, 4), 4096, &TextureLoadOptions::default()).unwrap();
+        assert_eq!(texture.mip_levels(), 4);
+        let sizes: Vec<_> = texture.levels.iter().map(|level| level.len()).collect();
+        assert_eq!(sizes, vec![8 * 4 * 4, 4 * 2 * 4, 2 * 4, 4]);
+        assert_eq!(texture.size(), texture.clone().into_data().len());
+    }
+
+    #[test]
+    fn test_srgb_aware_downsampling() {
+        // 白黒の平均は線形空間では0.5、sRGBでエンコードすると約188になる
+        let srgb = build_texture(checker(2, 2), 4096, &TextureLoadOptions::default()).unwrap();
+        assert_eq!(srgb.levels[1], vec![188, 188, 188, 255]);
+
+        let options = TextureLoadOptions { color_space: ColorSpace::Linear, ..TextureLoadOptions::default() };
+        let linear = build_texture(checker(2, 2), 4096, &options).unwrap();
+        assert_eq!(linear.levels[1], vec![128, 128, 128, 255]);
+
+        // 透明な画素の色は混ざらない
+        let icon = DynamicImage::ImageRgba8(RgbaImage::from_fn(2, 1, |x, _| {
+            if x == 0 { image::Rgba([255, 0, 0, 255]) } else { image::Rgba([0, 0, 0, 0]) }
+        }));
+        let texture = build_texture(icon, 4096, &TextureLoadOptions::default()).unwrap();
+        assert_eq!(texture.levels[1], vec![255, 0, 0, 128]);
+    }
+
+    #[test]
+    fn test_max_texture_size() {
+        let options = TextureLoadOptions { generate_mipmaps: false, ..TextureLoadOptions::default() };
+        assert!(matches!(
+            build_texture(checker(64, 16), 32, &options),
+            Err(ResourceLoadError::InvalidResource(_))
+        ));
+
+        let options = TextureLoadOptions { downscale_to_fit: true, ..options };
+        let texture = build_texture(checker(64, 16), 32, &options).unwrap();
+        assert_eq!((texture.width, texture.height), (32, 8));
+        assert_eq!(texture.levels[0].len(), 32 * 8 * 4);
+    }
+
+    #[test]
+    fn test_format_conversion() {
+        let image = DynamicImage::ImageRgba8(RgbaImage::from_pixel(1, 1, image::Rgba([255, 128, 0, 255])));
+        let convert = |format, color_space| {
+            let options = TextureLoadOptions { format, color_space, generate_mipmaps: false, ..TextureLoadOptions::default() };
+            build_texture(image.clone(), 4096, &options).unwrap().levels.remove(0)
+        };
+
+        assert_eq!(convert(TextureFormat::B8G8R8A8, ColorSpace::Srgb), vec![0, 128, 255, 255]);
+        assert_eq!(convert(TextureFormat::R8G8, ColorSpace::Srgb), vec![255, 128]);
+        assert_eq!(convert(TextureFormat::R8G8B8, ColorSpace::Linear), vec![255, 128, 0]);
+
+        // 浮動小数点フォーマットには線形化した値を格納する
+        let half = convert(TextureFormat::R16F, ColorSpace::Srgb);
+        assert_eq!(half, f32_to_f16(1.0).to_le_bytes().to_vec());
+        let float = convert(TextureFormat::R32G32F, ColorSpace::Srgb);
+        let green = f32::from_le_bytes([float[4], float[5], float[6], float[7]]);
+        assert!((green - srgb_to_linear(128.0 / 255.0)).abs() < 1e-5);
+
+        let options = TextureLoadOptions { format: TextureFormat::BC3, ..TextureLoadOptions::default() };
+        assert!(matches!(
+            build_texture(image.clone(), 4096, &options),
+            Err(ResourceLoadError::UnsupportedFormat(_))
+        ));
+    }
+
+    #[test]
+    fn test_f32_to_f16() {
+        assert_eq!(f32_to_f16(0.0), 0x0000);
+        assert_eq!(f32_to_f16(1.0), 0x3c00);
+        assert_eq!(f32_to_f16(-2.0), 0xc000);
+        assert_eq!(f32_to_f16(0.5), 0x3800);
+        assert_eq!(f32_to_f16(65504.0), 0x7bff);
+        assert_eq!(f32_to_f16(1.0e6), 0x7c00);
+        assert_eq!(f32_to_f16(f32::NAN) & 0x7c00, 0x7c00);
+        // 最小の非正規化数
+        assert_eq!(f32_to_f16(5.960_464_5e-8), 0x0001);
+    }
+}
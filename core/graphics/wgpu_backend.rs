@@ -0,0 +1,1902 @@
+// LumosDesktop wgpuバックエンド
+// wgpuを使用したクロスプラットフォームのレンダラー実装（Vulkan/Metal/DX12/GLを自動選択）
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use futures::executor::block_on;
+
+use super::{GraphicsConfig, GraphicsError};
+use super::readback::{PixelData, ReadbackFuture};
+use super::shader_manager::{CompiledShader, ShaderType};
+use super::renderer::{
+    Renderer, RenderContext, RenderPass, RenderTarget, RenderCommandBuffer, OffscreenTarget,
+    TextureFormat, BufferTarget, BufferUsage, PipelineState, PrimitiveType, FillMode, CullMode,
+    BlendFactor, BlendOp, CompareFunc, StencilOp, VertexFormat, FilterMode, WrapMode,
+};
+use super::software_backend::{
+    SHADER_VERTEX_COLOR, SHADER_TEXTURED, UNIFORM_TRANSFORM, UNIFORM_TINT, BACKBUFFER,
+};
+
+/// レンダーターゲットに付随する深度・ステンシルバッファのフォーマット
+const DEPTH_STENCIL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth24PlusStencil8;
+
+/// 組み込みシェーダーのユニフォームサイズ（変換行列 + 色）
+const GLOBALS_SIZE: u64 = 80;
+
+/// アダプターが対応していれば有効にする機能
+const OPTIONAL_FEATURES: wgpu::Features = wgpu::Features::POLYGON_MODE_LINE
+    .union(wgpu::Features::POLYGON_MODE_POINT)
+    .union(wgpu::Features::TEXTURE_COMPRESSION_BC)
+    .union(wgpu::Features::DEPTH32FLOAT_STENCIL8);
+
+/// wgpuテクスチャ
+#[derive(Debug)]
+struct WgpuTexture {
+    texture: wgpu::Texture,
+    view: Arc<wgpu::TextureView>,
+    sampler: Arc<wgpu::Sampler>,
+    /// レンダーターゲットとして使われたときに作成される深度・ステンシルバッファ
+    depth: Option<Arc<wgpu::TextureView>>,
+    format: wgpu::TextureFormat,
+    width: u32,
+    height: u32,
+}
+
+/// wgpuバッファ
+#[derive(Debug)]
+struct WgpuBuffer {
+    buffer: Arc<wgpu::Buffer>,
+    size: u64,
+}
+
+/// アクティブなレンダーパスの状態
+#[derive(Debug, Clone)]
+struct PassState {
+    target: String,
+    viewport: [f32; 6],
+    scissor: Option<(i32, i32, u32, u32)>,
+}
+
+/// コンテキストにバインドされたリソース
+#[derive(Debug, Clone, Default)]
+struct Bindings {
+    pipeline: PipelineState,
+    shader: Option<String>,
+    vertex_buffers: HashMap<u32, String>,
+    index_buffer: Option<String>,
+    uniform_buffers: HashMap<u32, String>,
+    textures: HashMap<u32, String>,
+}
+
+/// 描画呼び出しのパラメーター
+#[derive(Debug, Clone, Copy)]
+struct DrawCall {
+    indexed: bool,
+    count: u32,
+    first: u32,
+    vertex_offset: i32,
+    instance_count: u32,
+    first_instance: u32,
+}
+
+/// 記録されたコマンド
+#[derive(Debug, Clone)]
+enum Command {
+    Clear {
+        pass: PassState,
+        color: [f32; 4],
+        depth: f32,
+        stencil: u8,
+    },
+    Draw {
+        pass: PassState,
+        bindings: Box<Bindings>,
+        uniforms: HashMap<String, Vec<u8>>,
+        call: DrawCall,
+    },
+}
+
+/// エンコード可能な状態に変換した描画コマンド
+struct PreparedDraw {
+    pipeline: Arc<wgpu::RenderPipeline>,
+    bind_group: wgpu::BindGroup,
+    vertex_buffers: Vec<(u32, Arc<wgpu::Buffer>)>,
+    index_buffer: Option<Arc<wgpu::Buffer>>,
+    pass: PassState,
+    blend_constant: [f32; 4],
+    stencil_ref: u8,
+    scissor_test: bool,
+    call: DrawCall,
+}
+
+/// 同じターゲットに連続して描画するコマンドのまとまり
+struct PassSegment {
+    target: String,
+    color: Arc<wgpu::TextureView>,
+    depth: Arc<wgpu::TextureView>,
+    size: (u32, u32),
+    clear: Option<([f32; 4], f32, u8)>,
+    draws: Vec<PreparedDraw>,
+}
+
+/// レンダラー・コンテキスト・コマンドバッファで共有するデバイス状態
+struct WgpuDevice {
+    device: Arc<wgpu::Device>,
+    queue: wgpu::Queue,
+    textures: HashMap<String, WgpuTexture>,
+    buffers: HashMap<String, WgpuBuffer>,
+    /// `register_wgsl_shader` で登録されたシェーダー
+    shaders: HashMap<String, Arc<wgpu::ShaderModule>>,
+    pipelines: HashMap<String, Arc<wgpu::RenderPipeline>>,
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline_layout: wgpu::PipelineLayout,
+    /// テクスチャがバインドされていないときに使う1x1の白テクスチャ
+    white_view: Arc<wgpu::TextureView>,
+    default_sampler: Arc<wgpu::Sampler>,
+    bindings: Bindings,
+    uniforms: HashMap<String, Vec<u8>>,
+    pass: Option<PassState>,
+}
+
+impl fmt::Debug for WgpuDevice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WgpuDevice")
+            .field("textures", &self.textures.len())
+            .field("buffers", &self.buffers.len())
+            .field("pipelines", &self.pipelines.len())
+            .finish()
+    }
+}
+
+fn lock_device(device: &Mutex<WgpuDevice>) -> Result<MutexGuard<'_, WgpuDevice>, GraphicsError> {
+    device.lock().map_err(|_| {
+        GraphicsError::Backend("wgpuデバイスのロックに失敗しました".to_string())
+    })
+}
+
+impl WgpuDevice {
+    fn new(device: wgpu::Device, queue: wgpu::Queue) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("lumos_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("lumos_pipeline_layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let white = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("lumos_white"),
+            size: wgpu::Extent3d { width: 1, height: 1, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        queue.write_texture(
+            white.as_image_copy(),
+            &[255, 255, 255, 255],
+            wgpu::ImageDataLayout { offset: 0, bytes_per_row: Some(4), rows_per_image: Some(1) },
+            wgpu::Extent3d { width: 1, height: 1, depth_or_array_layers: 1 },
+        );
+        let white_view = Arc::new(white.create_view(&wgpu::TextureViewDescriptor::default()));
+        let default_sampler = Arc::new(create_sampler(&device, FilterMode::Point, WrapMode::ClampToEdge));
+
+        Self {
+            device: Arc::new(device),
+            queue,
+            textures: HashMap::new(),
+            buffers: HashMap::new(),
+            shaders: HashMap::new(),
+            pipelines: HashMap::new(),
+            bind_group_layout,
+            pipeline_layout,
+            white_view,
+            default_sampler,
+            bindings: Bindings::default(),
+            uniforms: HashMap::new(),
+            pass: None,
+        }
+    }
+
+    /// 検証エラーを捕捉しながら処理を実行
+    fn validated<T>(&self, context: &str, f: impl FnOnce(&Self) -> T) -> Result<T, GraphicsError> {
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let value = f(self);
+        match block_on(self.device.pop_error_scope()) {
+            Some(error) => Err(GraphicsError::Backend(format!("{}: {}", context, error))),
+            None => Ok(value),
+        }
+    }
+
+    fn create_texture(
+        &mut self,
+        name: &str,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        usage: wgpu::TextureUsages,
+        mip_level_count: u32,
+    ) -> Result<(), GraphicsError> {
+        let texture = self.validated("テクスチャの作成に失敗しました", |device| {
+            device.device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(name),
+                size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+                mip_level_count,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage,
+                view_formats: &[],
+            })
+        })?;
+        let view = Arc::new(texture.create_view(&wgpu::TextureViewDescriptor::default()));
+
+        self.textures.insert(name.to_string(), WgpuTexture {
+            texture,
+            view,
+            sampler: Arc::clone(&self.default_sampler),
+            depth: None,
+            format,
+            width,
+            height,
+        });
+        Ok(())
+    }
+
+    /// レンダーターゲット用のテクスチャと深度・ステンシルバッファを用意
+    fn ensure_render_target(&mut self, name: &str, width: u32, height: u32, format: wgpu::TextureFormat) -> Result<(), GraphicsError> {
+        if !self.textures.contains_key(name) {
+            self.create_texture(
+                name,
+                width,
+                height,
+                format,
+                wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::TEXTURE_BINDING
+                    | wgpu::TextureUsages::COPY_SRC
+                    | wgpu::TextureUsages::COPY_DST,
+                1,
+            )?;
+        }
+
+        let texture = self.textures.get_mut(name).ok_or_else(|| {
+            GraphicsError::Rendering(format!("レンダーターゲットが見つかりません: {}", name))
+        })?;
+        if texture.width != width || texture.height != height {
+            return Err(GraphicsError::Rendering(format!(
+                "レンダーターゲットのサイズが一致しません: {} ({}x{}, 指定: {}x{})",
+                name, texture.width, texture.height, width, height
+            )));
+        }
+        if !texture.texture.usage().contains(wgpu::TextureUsages::RENDER_ATTACHMENT) {
+            return Err(GraphicsError::Rendering(format!(
+                "レンダーターゲットとして使用できないテクスチャです: {}", name
+            )));
+        }
+
+        if texture.depth.is_none() {
+            let depth = self.device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("lumos_depth_stencil"),
+                size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: DEPTH_STENCIL_FORMAT,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[],
+            });
+            texture.depth = Some(Arc::new(depth.create_view(&wgpu::TextureViewDescriptor::default())));
+        }
+        Ok(())
+    }
+
+    /// シェーダーと頂点レイアウトからパイプラインを取得（なければ作成）
+    fn pipeline(&mut self, bindings: &Bindings, target_format: wgpu::TextureFormat) -> Result<Arc<wgpu::RenderPipeline>, GraphicsError> {
+        let shader = bindings.shader.as_deref().unwrap_or(SHADER_VERTEX_COLOR);
+        let state = &bindings.pipeline;
+
+        // PipelineStateは浮動小数点を含むためDebug表現をキーにする
+        let key = format!("{}|{:?}|{:?}", shader, target_format, state);
+        if let Some(pipeline) = self.pipelines.get(&key) {
+            return Ok(Arc::clone(pipeline));
+        }
+
+        let module = match shader {
+            SHADER_VERTEX_COLOR | SHADER_TEXTURED => {
+                let source = builtin_shader_source(state, shader == SHADER_TEXTURED)?;
+                Arc::new(self.validated("組み込みシェーダーのコンパイルに失敗しました", |device| {
+                    device.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                        label: Some(shader),
+                        source: wgpu::ShaderSource::Wgsl(Cow::Owned(source)),
+                    })
+                })?)
+            }
+            name => Arc::clone(self.shaders.get(name).ok_or_else(|| {
+                GraphicsError::Shader(format!("シェーダーが登録されていません: {}", name))
+            })?),
+        };
+
+        // divisorが0の属性はスロット0、それ以外はスロット1のインスタンスバッファから読む
+        let layout = &state.vertex_layout;
+        let mut per_vertex = Vec::new();
+        let mut per_instance = Vec::new();
+        for attribute in &layout.attributes {
+            let converted = wgpu::VertexAttribute {
+                format: vertex_format(attribute.format),
+                offset: attribute.offset as u64,
+                shader_location: attribute.location,
+            };
+            match attribute.divisor {
+                0 => per_vertex.push(converted),
+                1 => per_instance.push(converted),
+                divisor => {
+                    return Err(GraphicsError::Rendering(format!(
+                        "wgpuは1以外のインスタンス除数をサポートしていません: {} (除数: {})",
+                        attribute.name, divisor
+                    )));
+                }
+            }
+        }
+        let vertex_buffers = [
+            wgpu::VertexBufferLayout {
+                array_stride: layout.stride as u64,
+                step_mode: wgpu::VertexStepMode::Vertex,
+                attributes: &per_vertex,
+            },
+            wgpu::VertexBufferLayout {
+                array_stride: layout.stride as u64,
+                step_mode: wgpu::VertexStepMode::Instance,
+                attributes: &per_instance,
+            },
+        ];
+        let buffer_count = if per_instance.is_empty() { 1 } else { 2 };
+
+        let topology = primitive_topology(state.primitive_type)?;
+        let strip_index_format = match topology {
+            wgpu::PrimitiveTopology::LineStrip | wgpu::PrimitiveTopology::TriangleStrip => Some(wgpu::IndexFormat::Uint32),
+            _ => None,
+        };
+        let rasterizer = &state.rasterizer_state;
+        let depth_stencil = &state.depth_stencil_state;
+        let stencil_face = wgpu::StencilFaceState {
+            compare: if depth_stencil.stencil_test { compare_function(depth_stencil.stencil_func) } else { wgpu::CompareFunction::Always },
+            fail_op: if depth_stencil.stencil_test { stencil_operation(depth_stencil.stencil_fail_op) } else { wgpu::StencilOperation::Keep },
+            depth_fail_op: if depth_stencil.stencil_test { stencil_operation(depth_stencil.stencil_depth_fail_op) } else { wgpu::StencilOperation::Keep },
+            pass_op: if depth_stencil.stencil_test { stencil_operation(depth_stencil.stencil_pass_op) } else { wgpu::StencilOperation::Keep },
+        };
+        let blend = if state.blend_state.enabled {
+            let blend = &state.blend_state;
+            Some(wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: blend_factor(blend.src_rgb)?,
+                    dst_factor: blend_factor(blend.dst_rgb)?,
+                    operation: blend_operation(blend.op_rgb),
+                },
+                alpha: wgpu::BlendComponent {
+                    src_factor: blend_factor(blend.src_alpha)?,
+                    dst_factor: blend_factor(blend.dst_alpha)?,
+                    operation: blend_operation(blend.op_alpha),
+                },
+            })
+        } else {
+            None
+        };
+
+        let pipeline = self.validated("パイプラインの作成に失敗しました", |device| {
+            device.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(shader),
+                layout: Some(&device.pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &module,
+                    entry_point: "vs_main",
+                    buffers: &vertex_buffers[..buffer_count],
+                },
+                primitive: wgpu::PrimitiveState {
+                    topology,
+                    strip_index_format,
+                    front_face: if rasterizer.front_face_ccw { wgpu::FrontFace::Ccw } else { wgpu::FrontFace::Cw },
+                    cull_mode: match rasterizer.cull_mode {
+                        CullMode::None => None,
+                        CullMode::Front => Some(wgpu::Face::Front),
+                        CullMode::Back => Some(wgpu::Face::Back),
+                    },
+                    unclipped_depth: false,
+                    polygon_mode: match rasterizer.fill_mode {
+                        FillMode::Solid => wgpu::PolygonMode::Fill,
+                        FillMode::Wireframe => wgpu::PolygonMode::Line,
+                        FillMode::Point => wgpu::PolygonMode::Point,
+                    },
+                    conservative: false,
+                },
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: DEPTH_STENCIL_FORMAT,
+                    depth_write_enabled: depth_stencil.depth_test && depth_stencil.depth_write,
+                    depth_compare: if depth_stencil.depth_test {
+                        compare_function(depth_stencil.depth_func)
+                    } else {
+                        wgpu::CompareFunction::Always
+                    },
+                    stencil: wgpu::StencilState {
+                        front: stencil_face,
+                        back: stencil_face,
+                        read_mask: depth_stencil.stencil_read_mask as u32,
+                        write_mask: if depth_stencil.stencil_test { depth_stencil.stencil_write_mask as u32 } else { 0 },
+                    },
+                    bias: wgpu::DepthBiasState {
+                        constant: rasterizer.depth_bias as i32,
+                        slope_scale: rasterizer.depth_bias_slope_scale,
+                        clamp: 0.0,
+                    },
+                }),
+                multisample: wgpu::MultisampleState::default(),
+                fragment: Some(wgpu::FragmentState {
+                    module: &module,
+                    entry_point: "fs_main",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: target_format,
+                        blend,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                multiview: None,
+            })
+        })?;
+
+        let pipeline = Arc::new(pipeline);
+        self.pipelines.insert(key, Arc::clone(&pipeline));
+        Ok(pipeline)
+    }
+
+    /// 描画コマンドをエンコードできる形に変換
+    fn prepare_draw(
+        &mut self,
+        pass: &PassState,
+        bindings: &Bindings,
+        uniforms: &HashMap<String, Vec<u8>>,
+        call: &DrawCall,
+    ) -> Result<PreparedDraw, GraphicsError> {
+        let target_format = self.textures.get(&pass.target)
+            .map(|texture| texture.format)
+            .ok_or_else(|| GraphicsError::Rendering(format!("レンダーターゲットが見つかりません: {}", pass.target)))?;
+        let pipeline = self.pipeline(bindings, target_format)?;
+
+        let buffer = |name: &String| -> Result<Arc<wgpu::Buffer>, GraphicsError> {
+            self.buffers.get(name)
+                .map(|buffer| Arc::clone(&buffer.buffer))
+                .ok_or_else(|| GraphicsError::Rendering(format!("バッファが見つかりません: {}", name)))
+        };
+
+        let vertex_name = bindings.vertex_buffers.get(&0).ok_or_else(|| {
+            GraphicsError::Rendering("頂点バッファがバインドされていません".to_string())
+        })?;
+        let mut vertex_buffers = vec![(0, buffer(vertex_name)?)];
+        if bindings.pipeline.vertex_layout.attributes.iter().any(|a| a.divisor > 0) {
+            // インスタンス属性はスロット1がなければスロット0のバッファから読む
+            let instance_name = bindings.vertex_buffers.get(&1).unwrap_or(vertex_name);
+            vertex_buffers.push((1, buffer(instance_name)?));
+        }
+
+        let index_buffer = if call.indexed {
+            let name = bindings.index_buffer.as_ref().ok_or_else(|| {
+                GraphicsError::Rendering("インデックスバッファがバインドされていません".to_string())
+            })?;
+            Some(buffer(name)?)
+        } else {
+            None
+        };
+
+        // スロット0のユニフォームバッファがなければ組み込みシェーダー用の値から作成
+        let uniform_buffer = match bindings.uniform_buffers.get(&0) {
+            Some(name) => buffer(name)?,
+            None => {
+                let globals = self.device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("lumos_globals"),
+                    size: GLOBALS_SIZE,
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                });
+                self.queue.write_buffer(&globals, 0, &globals_bytes(uniforms));
+                Arc::new(globals)
+            }
+        };
+
+        let (view, sampler) = match bindings.textures.get(&0) {
+            Some(name) => {
+                let texture = self.textures.get(name).ok_or_else(|| {
+                    GraphicsError::Rendering(format!("テクスチャが見つかりません: {}", name))
+                })?;
+                if name == &pass.target {
+                    return Err(GraphicsError::Rendering(format!(
+                        "描画先テクスチャはサンプリングできません: {}", name
+                    )));
+                }
+                (Arc::clone(&texture.view), Arc::clone(&texture.sampler))
+            }
+            None => (Arc::clone(&self.white_view), Arc::clone(&self.default_sampler)),
+        };
+
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("lumos_bind_group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: uniform_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(&view) },
+                wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::Sampler(&sampler) },
+            ],
+        });
+
+        Ok(PreparedDraw {
+            pipeline,
+            bind_group,
+            vertex_buffers,
+            index_buffer,
+            pass: pass.clone(),
+            blend_constant: bindings.pipeline.blend_state.color,
+            stencil_ref: bindings.pipeline.depth_stencil_state.stencil_ref,
+            scissor_test: bindings.pipeline.rasterizer_state.scissor_test,
+            call: *call,
+        })
+    }
+
+    /// 記録されたコマンドをエンコードしてキューに送信
+    fn execute(&mut self, commands: &[Command]) -> Result<(), GraphicsError> {
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let result = self.encode(commands);
+        let error = block_on(self.device.pop_error_scope());
+        result?;
+        match error {
+            Some(error) => Err(GraphicsError::Rendering(format!("コマンドの実行に失敗しました: {}", error))),
+            None => Ok(()),
+        }
+    }
+
+    fn encode(&mut self, commands: &[Command]) -> Result<(), GraphicsError> {
+        let mut segments: Vec<PassSegment> = Vec::new();
+        for command in commands {
+            let (pass, clear) = match command {
+                Command::Clear { pass, color, depth, stencil } => (pass, Some((*color, *depth, *stencil))),
+                Command::Draw { pass, .. } => (pass, None),
+            };
+
+            // クリアまたはターゲットの切り替えで新しいパスを開始する
+            let continues = clear.is_none()
+                && segments.last().is_some_and(|segment| segment.target == pass.target);
+            if !continues {
+                let texture = self.textures.get(&pass.target).ok_or_else(|| {
+                    GraphicsError::Rendering(format!("レンダーターゲットが見つかりません: {}", pass.target))
+                })?;
+                let depth = texture.depth.clone().ok_or_else(|| {
+                    GraphicsError::Rendering(format!("深度バッファが作成されていません: {}", pass.target))
+                })?;
+                segments.push(PassSegment {
+                    target: pass.target.clone(),
+                    color: Arc::clone(&texture.view),
+                    depth,
+                    size: (texture.width, texture.height),
+                    clear,
+                    draws: Vec::new(),
+                });
+            }
+
+            if let Command::Draw { pass, bindings, uniforms, call } = command {
+                let draw = self.prepare_draw(pass, bindings, uniforms, call)?;
+                if let Some(segment) = segments.last_mut() {
+                    segment.draws.push(draw);
+                }
+            }
+        }
+
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("lumos_command_encoder"),
+        });
+        for segment in &segments {
+            let (color_load, depth_load, stencil_load) = match segment.clear {
+                Some((color, depth, stencil)) => (
+                    wgpu::LoadOp::Clear(wgpu::Color {
+                        r: color[0] as f64,
+                        g: color[1] as f64,
+                        b: color[2] as f64,
+                        a: color[3] as f64,
+                    }),
+                    wgpu::LoadOp::Clear(depth),
+                    wgpu::LoadOp::Clear(stencil as u32),
+                ),
+                None => (wgpu::LoadOp::Load, wgpu::LoadOp::Load, wgpu::LoadOp::Load),
+            };
+
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some(&segment.target),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &segment.color,
+                    resolve_target: None,
+                    ops: wgpu::Operations { load: color_load, store: wgpu::StoreOp::Store },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &segment.depth,
+                    depth_ops: Some(wgpu::Operations { load: depth_load, store: wgpu::StoreOp::Store }),
+                    stencil_ops: Some(wgpu::Operations { load: stencil_load, store: wgpu::StoreOp::Store }),
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+            let (width, height) = segment.size;
+            for draw in &segment.draws {
+                let [x, y, w, h, min_depth, max_depth] = draw.pass.viewport;
+                render_pass.set_viewport(x, y, w, h, min_depth, max_depth);
+
+                // シザー矩形はターゲットの範囲内に収める必要がある
+                let (sx, sy, sw, sh) = match (draw.scissor_test, draw.pass.scissor) {
+                    (true, Some((sx, sy, sw, sh))) => {
+                        let x0 = sx.clamp(0, width as i32) as u32;
+                        let y0 = sy.clamp(0, height as i32) as u32;
+                        let x1 = (sx as i64 + sw as i64).clamp(0, width as i64) as u32;
+                        let y1 = (sy as i64 + sh as i64).clamp(0, height as i64) as u32;
+                        (x0, y0, x1.saturating_sub(x0), y1.saturating_sub(y0))
+                    }
+                    _ => (0, 0, width, height),
+                };
+                if sw == 0 || sh == 0 {
+                    continue;
+                }
+                render_pass.set_scissor_rect(sx, sy, sw, sh);
+
+                render_pass.set_pipeline(&draw.pipeline);
+                render_pass.set_bind_group(0, &draw.bind_group, &[]);
+                render_pass.set_blend_constant(wgpu::Color {
+                    r: draw.blend_constant[0] as f64,
+                    g: draw.blend_constant[1] as f64,
+                    b: draw.blend_constant[2] as f64,
+                    a: draw.blend_constant[3] as f64,
+                });
+                render_pass.set_stencil_reference(draw.stencil_ref as u32);
+                for (slot, buffer) in &draw.vertex_buffers {
+                    render_pass.set_vertex_buffer(*slot, buffer.slice(..));
+                }
+
+                let call = &draw.call;
+                let instances = call.first_instance..call.first_instance + call.instance_count;
+                match &draw.index_buffer {
+                    Some(index_buffer) => {
+                        render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                        render_pass.draw_indexed(call.first..call.first + call.count, call.vertex_offset, instances);
+                    }
+                    None => render_pass.draw(call.first..call.first + call.count, instances),
+                }
+            }
+        }
+
+        self.queue.submit(Some(encoder.finish()));
+        Ok(())
+    }
+}
+
+/// 組み込みシェーダーのユニフォーム（変換行列と色）をバイト列にする
+fn globals_bytes(uniforms: &HashMap<String, Vec<u8>>) -> Vec<u8> {
+    let mut transform: Vec<u8> = [
+        1.0f32, 0.0, 0.0, 0.0,
+        0.0, 1.0, 0.0, 0.0,
+        0.0, 0.0, 1.0, 0.0,
+        0.0, 0.0, 0.0, 1.0,
+    ].iter().flat_map(|f| f.to_le_bytes()).collect();
+    let mut tint: Vec<u8> = [1.0f32; 4].iter().flat_map(|f| f.to_le_bytes()).collect();
+
+    if let Some(data) = uniforms.get(UNIFORM_TRANSFORM).filter(|data| data.len() >= 64) {
+        transform.copy_from_slice(&data[..64]);
+    }
+    if let Some(data) = uniforms.get(UNIFORM_TINT).filter(|data| data.len() >= 16) {
+        tint.copy_from_slice(&data[..16]);
+    }
+
+    transform.extend(tint);
+    transform
+}
+
+/// 頂点レイアウトに合わせた組み込みシェーダーのWGSLを生成
+///
+/// ソフトウェアバックエンドと同じく、属性は名前（position/color/texcoord）で対応付けます。
+fn builtin_shader_source(state: &PipelineState, textured: bool) -> Result<String, GraphicsError> {
+    let attributes = &state.vertex_layout.attributes;
+    let location = |name: &str| attributes.iter().find(|a| a.name == name).map(|a| a.location);
+
+    let position = location("position").ok_or_else(|| {
+        GraphicsError::Rendering("頂点レイアウトに位置属性がありません".to_string())
+    })?;
+    let color = location("color");
+    let texcoord = location("texcoord");
+
+    let mut inputs = format!("    @location({}) position: vec4<f32>,\n", position);
+    if let Some(location) = color {
+        inputs.push_str(&format!("    @location({}) color: vec4<f32>,\n", location));
+    }
+    if let Some(location) = texcoord {
+        inputs.push_str(&format!("    @location({}) texcoord: vec2<f32>,\n", location));
+    }
+
+    let color_expr = if color.is_some() { "input.color" } else { "vec4<f32>(1.0)" };
+    let uv_expr = if texcoord.is_some() { "input.texcoord" } else { "vec2<f32>(0.0)" };
+    let sample_expr = if textured { " * textureSample(t_texture, s_texture, input.uv)" } else { "" };
+
+    Ok(format!(r#"
+struct Globals {{
+    transform: mat4x4<f32>,
+    tint: vec4<f32>,
+}}
+
+@group(0) @binding(0) var<uniform> globals: Globals;
+@group(0) @binding(1) var t_texture: texture_2d<f32>;
+@group(0) @binding(2) var s_texture: sampler;
+
+struct VertexInput {{
+{inputs}}}
+
+struct VertexOutput {{
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+    @location(1) uv: vec2<f32>,
+}}
+
+@vertex
+fn vs_main(input: VertexInput) -> VertexOutput {{
+    var output: VertexOutput;
+    output.position = globals.transform * input.position;
+    output.color = {color_expr};
+    output.uv = {uv_expr};
+    return output;
+}}
+
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {{
+    return input.color * globals.tint{sample_expr};
+}}
+"#))
+}
+
+fn create_sampler(device: &wgpu::Device, filter: FilterMode, wrap: WrapMode) -> wgpu::Sampler {
+    let address_mode = match wrap {
+        WrapMode::Repeat => wgpu::AddressMode::Repeat,
+        WrapMode::MirroredRepeat => wgpu::AddressMode::MirrorRepeat,
+        WrapMode::ClampToEdge => wgpu::AddressMode::ClampToEdge,
+        WrapMode::ClampToBorder => wgpu::AddressMode::ClampToBorder,
+    };
+    let (mag_filter, mipmap_filter) = match filter {
+        FilterMode::Point => (wgpu::FilterMode::Nearest, wgpu::FilterMode::Nearest),
+        FilterMode::Bilinear => (wgpu::FilterMode::Linear, wgpu::FilterMode::Nearest),
+        FilterMode::Trilinear | FilterMode::Anisotropic(_) => (wgpu::FilterMode::Linear, wgpu::FilterMode::Linear),
+    };
+    let anisotropy_clamp = match filter {
+        FilterMode::Anisotropic(level) => level.clamp(1, 16) as u16,
+        _ => 1,
+    };
+
+    device.create_sampler(&wgpu::SamplerDescriptor {
+        label: Some("lumos_sampler"),
+        address_mode_u: address_mode,
+        address_mode_v: address_mode,
+        address_mode_w: address_mode,
+        mag_filter,
+        min_filter: mag_filter,
+        mipmap_filter,
+        anisotropy_clamp,
+        border_color: (wrap == WrapMode::ClampToBorder).then_some(wgpu::SamplerBorderColor::TransparentBlack),
+        ..Default::default()
+    })
+}
+
+fn vertex_format(format: VertexFormat) -> wgpu::VertexFormat {
+    match format {
+        VertexFormat::Float => wgpu::VertexFormat::Float32,
+        VertexFormat::Float2 => wgpu::VertexFormat::Float32x2,
+        VertexFormat::Float3 => wgpu::VertexFormat::Float32x3,
+        VertexFormat::Float4 => wgpu::VertexFormat::Float32x4,
+        VertexFormat::Int => wgpu::VertexFormat::Sint32,
+        VertexFormat::Int2 => wgpu::VertexFormat::Sint32x2,
+        VertexFormat::Int3 => wgpu::VertexFormat::Sint32x3,
+        VertexFormat::Int4 => wgpu::VertexFormat::Sint32x4,
+        VertexFormat::UInt => wgpu::VertexFormat::Uint32,
+        VertexFormat::UInt2 => wgpu::VertexFormat::Uint32x2,
+        VertexFormat::UInt3 => wgpu::VertexFormat::Uint32x3,
+        VertexFormat::UInt4 => wgpu::VertexFormat::Uint32x4,
+        VertexFormat::Short2 => wgpu::VertexFormat::Sint16x2,
+        VertexFormat::Short4 => wgpu::VertexFormat::Sint16x4,
+        VertexFormat::Byte4 => wgpu::VertexFormat::Sint8x4,
+        // ソフトウェアバックエンドと同じく正規化して読む
+        VertexFormat::UByte4 => wgpu::VertexFormat::Unorm8x4,
+    }
+}
+
+fn primitive_topology(primitive: PrimitiveType) -> Result<wgpu::PrimitiveTopology, GraphicsError> {
+    match primitive {
+        PrimitiveType::Points => Ok(wgpu::PrimitiveTopology::PointList),
+        PrimitiveType::Lines => Ok(wgpu::PrimitiveTopology::LineList),
+        PrimitiveType::LineStrip => Ok(wgpu::PrimitiveTopology::LineStrip),
+        PrimitiveType::Triangles => Ok(wgpu::PrimitiveTopology::TriangleList),
+        PrimitiveType::TriangleStrip => Ok(wgpu::PrimitiveTopology::TriangleStrip),
+        PrimitiveType::TriangleFan => Err(GraphicsError::Rendering(
+            "wgpuはトライアングルファンをサポートしていません".to_string()
+        )),
+    }
+}
+
+fn compare_function(func: CompareFunc) -> wgpu::CompareFunction {
+    match func {
+        CompareFunc::Never => wgpu::CompareFunction::Never,
+        CompareFunc::Less => wgpu::CompareFunction::Less,
+        CompareFunc::Equal => wgpu::CompareFunction::Equal,
+        CompareFunc::LessEqual => wgpu::CompareFunction::LessEqual,
+        CompareFunc::Greater => wgpu::CompareFunction::Greater,
+        CompareFunc::NotEqual => wgpu::CompareFunction::NotEqual,
+        CompareFunc::GreaterEqual => wgpu::CompareFunction::GreaterEqual,
+        CompareFunc::Always => wgpu::CompareFunction::Always,
+    }
+}
+
+fn stencil_operation(op: StencilOp) -> wgpu::StencilOperation {
+    match op {
+        StencilOp::Keep => wgpu::StencilOperation::Keep,
+        StencilOp::Zero => wgpu::StencilOperation::Zero,
+        StencilOp::Replace => wgpu::StencilOperation::Replace,
+        StencilOp::Increment => wgpu::StencilOperation::IncrementClamp,
+        StencilOp::IncrementWrap => wgpu::StencilOperation::IncrementWrap,
+        StencilOp::Decrement => wgpu::StencilOperation::DecrementClamp,
+        StencilOp::DecrementWrap => wgpu::StencilOperation::DecrementWrap,
+        StencilOp::Invert => wgpu::StencilOperation::Invert,
+    }
+}
+
+fn blend_factor(factor: BlendFactor) -> Result<wgpu::BlendFactor, GraphicsError> {
+    Ok(match factor {
+        BlendFactor::Zero => wgpu::BlendFactor::Zero,
+        BlendFactor::One => wgpu::BlendFactor::One,
+        BlendFactor::SrcColor => wgpu::BlendFactor::Src,
+        BlendFactor::OneMinusSrcColor => wgpu::BlendFactor::OneMinusSrc,
+        BlendFactor::DstColor => wgpu::BlendFactor::Dst,
+        BlendFactor::OneMinusDstColor => wgpu::BlendFactor::OneMinusDst,
+        BlendFactor::SrcAlpha => wgpu::BlendFactor::SrcAlpha,
+        BlendFactor::OneMinusSrcAlpha => wgpu::BlendFactor::OneMinusSrcAlpha,
+        BlendFactor::DstAlpha => wgpu::BlendFactor::DstAlpha,
+        BlendFactor::OneMinusDstAlpha => wgpu::BlendFactor::OneMinusDstAlpha,
+        BlendFactor::ConstantColor => wgpu::BlendFactor::Constant,
+        BlendFactor::OneMinusConstantColor => wgpu::BlendFactor::OneMinusConstant,
+        BlendFactor::SrcAlphaSaturate => wgpu::BlendFactor::SrcAlphaSaturated,
+        // wgpuの定数ブレンドは色のみで、アルファだけを取り出すことはできない
+        BlendFactor::ConstantAlpha | BlendFactor::OneMinusConstantAlpha => {
+            return Err(GraphicsError::Rendering(format!(
+                "wgpuでサポートされていないブレンド係数: {:?}", factor
+            )));
+        }
+    })
+}
+
+fn blend_operation(op: BlendOp) -> wgpu::BlendOperation {
+    match op {
+        BlendOp::Add => wgpu::BlendOperation::Add,
+        BlendOp::Subtract => wgpu::BlendOperation::Subtract,
+        BlendOp::ReverseSubtract => wgpu::BlendOperation::ReverseSubtract,
+        BlendOp::Min => wgpu::BlendOperation::Min,
+        BlendOp::Max => wgpu::BlendOperation::Max,
+    }
+}
+
+/// テクスチャフォーマットを変換
+///
+/// wgpuには3要素のフォーマットがないため、RGBフォーマットはアップロード時に
+/// アルファを補ってRGBAに展開します（戻り値の2番目の値が `true` の場合）。
+fn texture_format(format: TextureFormat, features: wgpu::Features) -> Result<(wgpu::TextureFormat, bool), GraphicsError> {
+    let require = |feature: wgpu::Features, converted: wgpu::TextureFormat| {
+        if features.contains(feature) {
+            Ok((converted, false))
+        } else {
+            Err(GraphicsError::Resource(format!(
+                "このアダプターはテクスチャフォーマットをサポートしていません: {:?}", format
+            )))
+        }
+    };
+
+    match format {
+        TextureFormat::R8 => Ok((wgpu::TextureFormat::R8Unorm, false)),
+        TextureFormat::R8G8 => Ok((wgpu::TextureFormat::Rg8Unorm, false)),
+        TextureFormat::R8G8B8 => Ok((wgpu::TextureFormat::Rgba8Unorm, true)),
+        TextureFormat::R8G8B8A8 => Ok((wgpu::TextureFormat::Rgba8Unorm, false)),
+        TextureFormat::B8G8R8A8 => Ok((wgpu::TextureFormat::Bgra8Unorm, false)),
+        TextureFormat::R16F => Ok((wgpu::TextureFormat::R16Float, false)),
+        TextureFormat::R16G16F => Ok((wgpu::TextureFormat::Rg16Float, false)),
+        TextureFormat::R16G16B16F => Ok((wgpu::TextureFormat::Rgba16Float, true)),
+        TextureFormat::R16G16B16A16F => Ok((wgpu::TextureFormat::Rgba16Float, false)),
+        TextureFormat::R32F => Ok((wgpu::TextureFormat::R32Float, false)),
+        TextureFormat::R32G32F => Ok((wgpu::TextureFormat::Rg32Float, false)),
+        TextureFormat::R32G32B32F => Ok((wgpu::TextureFormat::Rgba32Float, true)),
+        TextureFormat::R32G32B32A32F => Ok((wgpu::TextureFormat::Rgba32Float, false)),
+        TextureFormat::BC1 => require(wgpu::Features::TEXTURE_COMPRESSION_BC, wgpu::TextureFormat::Bc1RgbaUnorm),
+        TextureFormat::BC2 => require(wgpu::Features::TEXTURE_COMPRESSION_BC, wgpu::TextureFormat::Bc2RgbaUnorm),
+        TextureFormat::BC3 => require(wgpu::Features::TEXTURE_COMPRESSION_BC, wgpu::TextureFormat::Bc3RgbaUnorm),
+        TextureFormat::BC7 => require(wgpu::Features::TEXTURE_COMPRESSION_BC, wgpu::TextureFormat::Bc7RgbaUnorm),
+        TextureFormat::Depth16 => Ok((wgpu::TextureFormat::Depth16Unorm, false)),
+        TextureFormat::Depth24 => Ok((wgpu::TextureFormat::Depth24Plus, false)),
+        TextureFormat::Depth32F => Ok((wgpu::TextureFormat::Depth32Float, false)),
+        TextureFormat::Depth24Stencil8 => Ok((wgpu::TextureFormat::Depth24PlusStencil8, false)),
+        TextureFormat::Depth32FStencil8 => require(wgpu::Features::DEPTH32FLOAT_STENCIL8, wgpu::TextureFormat::Depth32FloatStencil8),
+    }
+}
+
+/// レンダーターゲットに使用できるフォーマットを変換
+fn render_target_format(format: TextureFormat) -> Result<wgpu::TextureFormat, GraphicsError> {
+    match format {
+        TextureFormat::R8G8B8A8 => Ok(wgpu::TextureFormat::Rgba8Unorm),
+        TextureFormat::B8G8R8A8 => Ok(wgpu::TextureFormat::Bgra8Unorm),
+        TextureFormat::R16G16B16A16F => Ok(wgpu::TextureFormat::Rgba16Float),
+        format => Err(GraphicsError::Rendering(format!(
+            "レンダーターゲットに使用できないフォーマットです: {:?}", format
+        ))),
+    }
+}
+
+/// RGBデータにアルファ（1.0）を補ってRGBAに展開
+fn expand_rgb(data: &[u8], component_size: usize) -> Vec<u8> {
+    let one: &[u8] = match component_size {
+        1 => &[255],
+        2 => &[0x00, 0x3c], // f16の1.0
+        _ => &[0x00, 0x00, 0x80, 0x3f], // f32の1.0
+    };
+
+    data.chunks_exact(component_size * 3)
+        .flat_map(|pixel| pixel.iter().chain(one.iter()).copied())
+        .collect()
+}
+
+/// バックエンドの組み合わせごとにプロセス内で共有するwgpuインスタンスを取得
+///
+/// GLESバックエンドはインスタンスの破棄時にEGLディスプレイを終了するため、
+/// レンダラーごとにインスタンスを作り直すと、別のレンダラーの初期化や
+/// `shutdown` 後の再初期化が失敗することがあります。
+fn shared_instance(backends: wgpu::Backends) -> Arc<wgpu::Instance> {
+    static INSTANCES: Mutex<Vec<(wgpu::Backends, Arc<wgpu::Instance>)>> = Mutex::new(Vec::new());
+
+    let mut instances = INSTANCES.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some((_, instance)) = instances.iter().find(|(b, _)| *b == backends) {
+        return Arc::clone(instance);
+    }
+
+    let instance = Arc::new(wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends,
+        ..Default::default()
+    }));
+    instances.push((backends, Arc::clone(&instance)));
+    instance
+}
+
+/// wgpuレンダラー
+pub struct WgpuRenderer {
+    device: Option<Arc<Mutex<WgpuDevice>>>,
+    features: wgpu::Features,
+    backends: wgpu::Backends,
+    device_info: HashMap<String, String>,
+    capabilities: HashMap<String, String>,
+    config: GraphicsConfig,
+    initialized: bool,
+}
+
+impl fmt::Debug for WgpuRenderer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WgpuRenderer")
+            .field("initialized", &self.initialized)
+            .field("device_info", &self.device_info)
+            .finish()
+    }
+}
+
+impl WgpuRenderer {
+    pub fn new(config: &GraphicsConfig) -> Result<Self, GraphicsError> {
+        let mut renderer = Self {
+            device: None,
+            features: wgpu::Features::empty(),
+            // WGPU_BACKEND 環境変数で使用するバックエンドを上書きできる
+            backends: wgpu::util::backend_bits_from_env().unwrap_or_else(wgpu::Backends::all),
+            device_info: HashMap::new(),
+            capabilities: HashMap::new(),
+            config: config.clone(),
+            initialized: false,
+        };
+
+        renderer.device_info.insert("name".to_string(), "wgpu Renderer".to_string());
+        renderer.device_info.insert("version".to_string(), "1.0.0".to_string());
+        renderer.device_info.insert("api_version".to_string(), "wgpu 0.18".to_string());
+
+        Ok(renderer)
+    }
+
+    /// 使用するwgpuバックエンドを指定して作成
+    pub fn with_backends(config: &GraphicsConfig, backends: wgpu::Backends) -> Result<Self, GraphicsError> {
+        let mut renderer = Self::new(config)?;
+        renderer.backends = backends;
+        Ok(renderer)
+    }
+
+    fn device(&self) -> Result<&Arc<Mutex<WgpuDevice>>, GraphicsError> {
+        self.device.as_ref().ok_or_else(|| {
+            GraphicsError::Initialization("レンダラーが初期化されていません".to_string())
+        })
+    }
+
+    /// アダプターを選択（見つからなければソフトウェアアダプターにフォールバック）
+    fn request_adapter(&self, instance: &wgpu::Instance) -> Result<wgpu::Adapter, GraphicsError> {
+        let power_preference = if self.config.power_saving_mode {
+            wgpu::PowerPreference::LowPower
+        } else {
+            wgpu::PowerPreference::HighPerformance
+        };
+        let request = |force_fallback_adapter: bool| {
+            block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference,
+                force_fallback_adapter,
+                compatible_surface: None,
+            }))
+        };
+
+        let adapter = if self.config.hardware_acceleration {
+            request(false).or_else(|| request(true))
+        } else {
+            // ハードウェアアクセラレーションが無効な場合はlavapipe/llvmpipe/WARPなどを使う
+            request(true)
+        };
+
+        adapter.ok_or_else(|| {
+            GraphicsError::Initialization("利用可能なwgpuアダプターが見つかりません".to_string())
+        })
+    }
+
+    /// WGSLシェーダーを登録
+    ///
+    /// エントリーポイントは `vs_main` と `fs_main` で、グループ0に
+    /// ユニフォーム（バインディング0）・テクスチャ（1）・サンプラー（2）を宣言します。
+    pub fn register_wgsl_shader(&mut self, name: &str, source: &str) -> Result<(), GraphicsError> {
+        let mut device = lock_device(self.device()?)?;
+        let module = device.validated("シェーダーのコンパイルに失敗しました", |device| {
+            device.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some(name),
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(source)),
+            })
+        }).map_err(|e| GraphicsError::Shader(e.to_string()))?;
+
+        device.shaders.insert(name.to_string(), Arc::new(module));
+        // 古いモジュールで作成したパイプラインを破棄
+        device.pipelines.retain(|key, _| !key.starts_with(&format!("{}|", name)));
+        Ok(())
+    }
+
+    /// テクスチャのサンプリング方法を設定
+    pub fn set_sampler(&mut self, name: &str, filter: FilterMode, wrap: WrapMode) -> Result<(), GraphicsError> {
+        let mut device = lock_device(self.device()?)?;
+        let sampler = Arc::new(create_sampler(&device.device, filter, wrap));
+        let texture = device.textures.get_mut(name)
+            .ok_or_else(|| GraphicsError::Resource(format!("テクスチャが見つかりません: {}", name)))?;
+        texture.sampler = sampler;
+        Ok(())
+    }
+
+    /// バッファの内容を部分的に更新
+    pub fn update_buffer(&mut self, name: &str, offset: usize, data: &[u8]) -> Result<(), GraphicsError> {
+        let device = lock_device(self.device()?)?;
+        let buffer = device.buffers.get(name)
+            .ok_or_else(|| GraphicsError::Resource(format!("バッファが見つかりません: {}", name)))?;
+        if (offset + data.len()) as u64 > buffer.size {
+            return Err(GraphicsError::Resource(format!(
+                "バッファの範囲外への書き込みです: {} (サイズ: {})", name, buffer.size
+            )));
+        }
+
+        // 書き込みサイズは4バイト単位である必要がある
+        let mut padded = data.to_vec();
+        padded.resize(data.len().next_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT as usize), 0);
+        device.queue.write_buffer(&buffer.buffer, offset as u64, &padded);
+        Ok(())
+    }
+
+    /// テクスチャのサイズを取得
+    pub fn texture_size(&self, name: &str) -> Option<(u32, u32)> {
+        let device = self.device.as_ref()?.lock().ok()?;
+        device.textures.get(name).map(|texture| (texture.width, texture.height))
+    }
+
+    /// 8ビットRGBA/BGRAテクスチャの画素をRGBA8で読み出す（完了まで待機）
+    pub fn read_pixels(&self, name: &str) -> Result<Vec<u8>, GraphicsError> {
+        block_on(self.read_render_target(name)).map(|data| data.pixels)
+    }
+
+    /// テクスチャをステージングバッファにコピーして読み出しを開始
+    fn start_readback(&self, name: &str) -> Result<ReadbackFuture, GraphicsError> {
+        let device = lock_device(self.device()?)?;
+        let texture = device.textures.get(name)
+            .ok_or_else(|| GraphicsError::Resource(format!("テクスチャが見つかりません: {}", name)))?;
+        let bgra = match texture.format {
+            wgpu::TextureFormat::Rgba8Unorm => false,
+            wgpu::TextureFormat::Bgra8Unorm => true,
+            format => {
+                return Err(GraphicsError::Resource(format!(
+                    "読み出しに対応していないテクスチャフォーマット: {:?}", format
+                )));
+            }
+        };
+        let (width, height) = (texture.width, texture.height);
+
+        // 行のサイズはCOPY_BYTES_PER_ROW_ALIGNMENTの倍数である必要がある
+        let row_bytes = width * 4;
+        let padded_row_bytes = row_bytes.next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let staging = device.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("lumos_readback"),
+            size: padded_row_bytes as u64 * height as u64,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = device.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("lumos_readback"),
+        });
+        encoder.copy_texture_to_buffer(
+            texture.texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &staging,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row_bytes),
+                    rows_per_image: Some(height),
+                },
+            },
+            wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+        );
+        let submission = device.queue.submit(Some(encoder.finish()));
+
+        let (sender, receiver) = futures::channel::oneshot::channel();
+        staging.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+
+        // マップの完了はデバイスをポーリングするまで通知されないため、別スレッドで待つ
+        let poll_device = Arc::clone(&device.device);
+        std::thread::spawn(move || {
+            poll_device.poll(wgpu::Maintain::WaitForSubmissionIndex(submission));
+        });
+
+        Ok(Box::pin(async move {
+            receiver.await
+                .map_err(|_| GraphicsError::Backend("読み出しが中断されました".to_string()))?
+                .map_err(|e| GraphicsError::Backend(format!("バッファのマップに失敗しました: {}", e)))?;
+
+            let mut pixels = Vec::with_capacity((row_bytes * height) as usize);
+            {
+                let mapped = staging.slice(..).get_mapped_range();
+                for row in mapped.chunks_exact(padded_row_bytes as usize) {
+                    pixels.extend_from_slice(&row[..row_bytes as usize]);
+                }
+            }
+            staging.unmap();
+
+            if bgra {
+                for pixel in pixels.chunks_exact_mut(4) {
+                    pixel.swap(0, 2);
+                }
+            }
+            PixelData::new(width, height, pixels)
+        }))
+    }
+}
+
+impl Renderer for WgpuRenderer {
+    fn name(&self) -> &str {
+        "WgpuRenderer"
+    }
+
+    fn initialize(&mut self, config: &GraphicsConfig) -> Result<(), GraphicsError> {
+        if self.initialized {
+            return Ok(());
+        }
+
+        self.config = config.clone();
+
+        let instance = shared_instance(self.backends);
+        let adapter = self.request_adapter(&instance)?;
+        let info = adapter.get_info();
+
+        self.features = adapter.features() & OPTIONAL_FEATURES;
+        let (device, queue) = block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                label: Some("lumos_device"),
+                features: self.features,
+                limits: adapter.limits(),
+            },
+            None,
+        ))
+        .map_err(|e| GraphicsError::Initialization(format!("wgpuデバイスの作成に失敗しました: {}", e)))?;
+
+        // 捕捉されなかった検証エラーでパニックしないようにする
+        device.on_uncaptured_error(Box::new(|error| {
+            log::error!("wgpuエラー: {}", error);
+        }));
+
+        let limits = device.limits();
+        self.device_info.insert("name".to_string(), info.name.clone());
+        self.device_info.insert("backend".to_string(), format!("{:?}", info.backend));
+        self.device_info.insert("device_type".to_string(), format!("{:?}", info.device_type));
+        self.device_info.insert("driver".to_string(), format!("{} {}", info.driver, info.driver_info).trim().to_string());
+        self.device_info.insert("vendor_id".to_string(), format!("0x{:04x}", info.vendor));
+
+        let max_texture_size = limits.max_texture_dimension_2d.min(config.max_texture_size);
+        self.capabilities.insert("max_texture_size".to_string(), max_texture_size.to_string());
+        self.capabilities.insert("max_uniform_buffer_range".to_string(), limits.max_uniform_buffer_binding_size.to_string());
+        self.capabilities.insert("max_storage_buffer_range".to_string(), limits.max_storage_buffer_binding_size.to_string());
+        self.capabilities.insert("max_compute_work_group_size".to_string(), limits.max_compute_invocations_per_workgroup.to_string());
+        self.capabilities.insert("software_adapter".to_string(), (info.device_type == wgpu::DeviceType::Cpu).to_string());
+        self.capabilities.insert("texture_compression_bc".to_string(), self.features.contains(wgpu::Features::TEXTURE_COMPRESSION_BC).to_string());
+
+        self.device = Some(Arc::new(Mutex::new(WgpuDevice::new(device, queue))));
+        self.initialized = true;
+        Ok(())
+    }
+
+    fn shutdown(&mut self) -> Result<(), GraphicsError> {
+        if !self.initialized {
+            return Ok(());
+        }
+
+        // デバイスを破棄すると全リソースが解放される
+        if let Some(device) = self.device.take() {
+            let device = lock_device(&device)?;
+            device.device.poll(wgpu::Maintain::Wait);
+        }
+
+        self.initialized = false;
+        Ok(())
+    }
+
+    fn update_config(&mut self, config: &GraphicsConfig) -> Result<(), GraphicsError> {
+        // アダプターの選択に関わる設定は再初期化時に反映される
+        self.config = config.clone();
+        Ok(())
+    }
+
+    fn create_context(&mut self) -> Result<Box<dyn RenderContext>, GraphicsError> {
+        Ok(Box::new(WgpuRenderContext { device: Arc::clone(self.device()?) }))
+    }
+
+    fn create_texture(
+        &mut self,
+        name: &str,
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+        data: Option<&[u8]>,
+    ) -> Result<(), GraphicsError> {
+        let levels: Vec<&[u8]> = data.into_iter().collect();
+        self.create_texture_with_mips(name, width, height, format, &levels)
+    }
+
+    fn create_texture_with_mips(
+        &mut self,
+        name: &str,
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+        levels: &[&[u8]],
+    ) -> Result<(), GraphicsError> {
+        let max_size = self.capabilities.get("max_texture_size")
+            .and_then(|size| size.parse::<u32>().ok())
+            .unwrap_or(self.config.max_texture_size);
+        if width > max_size || height > max_size {
+            return Err(GraphicsError::Resource(format!(
+                "テクスチャサイズが最大許容サイズを超えています: {}x{} (最大: {}x{})",
+                width, height, max_size, max_size
+            )));
+        }
+
+        let max_levels = 32 - width.max(height).max(1).leading_zeros();
+        if levels.len() > max_levels as usize {
+            return Err(GraphicsError::Resource(format!(
+                "ミップレベルが多すぎます: {} (最大: {})", levels.len(), max_levels
+            )));
+        }
+
+        let (wgpu_format, expand) = texture_format(format, self.features)?;
+        let mut usage = wgpu::TextureUsages::TEXTURE_BINDING
+            | wgpu::TextureUsages::COPY_DST
+            | wgpu::TextureUsages::COPY_SRC;
+        if !wgpu_format.is_compressed() {
+            usage |= wgpu::TextureUsages::RENDER_ATTACHMENT;
+        }
+
+        let mut device = lock_device(self.device()?)?;
+        device.create_texture(name, width, height, wgpu_format, usage, levels.len().max(1) as u32)?;
+
+        let (block_width, block_height) = wgpu_format.block_dimensions();
+        let block_size = wgpu_format.block_size(None).ok_or_else(|| {
+            GraphicsError::Resource(format!("データを書き込めないテクスチャフォーマット: {:?}", format))
+        })?;
+
+        for (level, data) in levels.iter().enumerate() {
+            let data: Cow<[u8]> = if expand {
+                let component_size = block_size as usize / 4;
+                Cow::Owned(expand_rgb(data, component_size))
+            } else {
+                Cow::Borrowed(data)
+            };
+
+            let level_width = (width >> level).max(1);
+            let level_height = (height >> level).max(1);
+            let bytes_per_row = level_width.div_ceil(block_width) * block_size;
+            let rows = level_height.div_ceil(block_height);
+            if data.len() < (bytes_per_row * rows) as usize {
+                return Err(GraphicsError::Resource(format!(
+                    "テクスチャデータが不足しています: {} バイト (必要: {} バイト, ミップレベル {})",
+                    data.len(), bytes_per_row * rows, level
+                )));
+            }
+
+            // 圧縮フォーマットのミップはブロック単位に切り上げたサイズで書き込む
+            let texture = &device.textures[name].texture;
+            device.queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture,
+                    mip_level: level as u32,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                &data,
+                wgpu::ImageDataLayout { offset: 0, bytes_per_row: Some(bytes_per_row), rows_per_image: Some(rows) },
+                wgpu::Extent3d {
+                    width: level_width.next_multiple_of(block_width),
+                    height: level_height.next_multiple_of(block_height),
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+        Ok(())
+    }
+
+    fn create_buffer(
+        &mut self,
+        name: &str,
+        target: BufferTarget,
+        _usage: BufferUsage,
+        data: Option<&[u8]>,
+        size: usize,
+    ) -> Result<(), GraphicsError> {
+        let usage = match target {
+            BufferTarget::Vertex => wgpu::BufferUsages::VERTEX,
+            BufferTarget::Index => wgpu::BufferUsages::INDEX,
+            BufferTarget::Uniform => wgpu::BufferUsages::UNIFORM,
+            BufferTarget::Storage => wgpu::BufferUsages::STORAGE,
+        } | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC;
+
+        // マップして作成するバッファのサイズは4バイト単位である必要がある
+        let size = size.max(data.map_or(0, |d| d.len()))
+            .max(1)
+            .next_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT as usize) as u64;
+
+        let mut device = lock_device(self.device()?)?;
+        let buffer = device.validated("バッファの作成に失敗しました", |device| {
+            device.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(name),
+                size,
+                usage,
+                mapped_at_creation: true,
+            })
+        })?;
+        if let Some(data) = data {
+            buffer.slice(..).get_mapped_range_mut()[..data.len()].copy_from_slice(data);
+        }
+        buffer.unmap();
+
+        device.buffers.insert(name.to_string(), WgpuBuffer { buffer: Arc::new(buffer), size });
+        Ok(())
+    }
+
+    fn destroy_texture(&mut self, name: &str) -> Result<(), GraphicsError> {
+        if let Some(device) = &self.device {
+            lock_device(device)?.textures.remove(name);
+        }
+        Ok(())
+    }
+
+    fn destroy_buffer(&mut self, name: &str) -> Result<(), GraphicsError> {
+        if let Some(device) = &self.device {
+            lock_device(device)?.buffers.remove(name);
+        }
+        Ok(())
+    }
+
+    fn reload_shader(&mut self, name: &str, compiled: &[&CompiledShader]) -> Result<(), GraphicsError> {
+        // WGSLへの変換結果は全エントリーポイントを含む1つのモジュールになる
+        let Some(shader) = compiled.iter().find(|shader| shader.format == ShaderType::Wgsl) else {
+            return Ok(());
+        };
+        let source = std::str::from_utf8(&shader.data)
+            .map_err(|e| GraphicsError::Shader(format!("WGSLソースが不正です: {}", e)))?;
+        self.register_wgsl_shader(name, source)
+    }
+
+    fn get_device_info(&self) -> HashMap<String, String> {
+        self.device_info.clone()
+    }
+
+    fn get_capabilities(&self) -> HashMap<String, String> {
+        self.capabilities.clone()
+    }
+
+    fn create_render_target(&mut self, name: &str, width: u32, height: u32, format: TextureFormat) -> Result<Box<dyn RenderTarget>, GraphicsError> {
+        let wgpu_format = render_target_format(format)?;
+        let mut device = lock_device(self.device()?)?;
+
+        // 同名のテクスチャは作り直す
+        device.textures.remove(name);
+        device.ensure_render_target(name, width, height, wgpu_format)?;
+
+        Ok(Box::new(OffscreenTarget::new(name, width, height).with_format(format)))
+    }
+
+    fn read_render_target(&self, name: &str) -> ReadbackFuture {
+        match self.start_readback(name) {
+            Ok(future) => future,
+            Err(e) => Box::pin(std::future::ready(Err(e))),
+        }
+    }
+}
+
+/// wgpuレンダーコンテキスト
+pub struct WgpuRenderContext {
+    device: Arc<Mutex<WgpuDevice>>,
+}
+
+impl WgpuRenderContext {
+    fn with_bindings<F>(&mut self, update: F) -> Result<(), GraphicsError>
+    where
+        F: FnOnce(&mut Bindings),
+    {
+        let mut device = lock_device(&self.device)?;
+        update(&mut device.bindings);
+        Ok(())
+    }
+}
+
+impl fmt::Debug for WgpuRenderContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WgpuRenderContext").finish()
+    }
+}
+
+impl RenderContext for WgpuRenderContext {
+    fn create_render_pass(&mut self, target: Box<dyn RenderTarget>) -> Result<Box<dyn RenderPass>, GraphicsError> {
+        let format = render_target_format(target.format())?;
+        if target.samples() > 1 {
+            return Err(GraphicsError::Rendering(
+                "マルチサンプルのレンダーターゲットは現在サポートされていません".to_string()
+            ));
+        }
+
+        let (width, height) = (target.width(), target.height());
+        Ok(Box::new(WgpuRenderPass {
+            device: Arc::clone(&self.device),
+            state: PassState {
+                target: target.texture_name().unwrap_or(BACKBUFFER).to_string(),
+                viewport: [0.0, 0.0, width as f32, height as f32, 0.0, 1.0],
+                scissor: None,
+            },
+            width,
+            height,
+            format,
+            active: false,
+        }))
+    }
+
+    fn create_command_buffer(&mut self) -> Result<Box<dyn RenderCommandBuffer>, GraphicsError> {
+        Ok(Box::new(WgpuCommandBuffer {
+            device: Arc::clone(&self.device),
+            commands: Vec::new(),
+            recording: false,
+        }))
+    }
+
+    fn set_pipeline_state(&mut self, state: &PipelineState) -> Result<(), GraphicsError> {
+        self.with_bindings(|bindings| bindings.pipeline = state.clone())
+    }
+
+    fn set_shader(&mut self, shader: &str) -> Result<(), GraphicsError> {
+        let mut device = lock_device(&self.device)?;
+        if shader != SHADER_VERTEX_COLOR && shader != SHADER_TEXTURED && !device.shaders.contains_key(shader) {
+            return Err(GraphicsError::Shader(format!(
+                "シェーダーが登録されていません: {}", shader
+            )));
+        }
+        device.bindings.shader = Some(shader.to_string());
+        Ok(())
+    }
+
+    fn set_vertex_buffer(&mut self, buffer: &str, slot: u32) -> Result<(), GraphicsError> {
+        self.with_bindings(|bindings| {
+            bindings.vertex_buffers.insert(slot, buffer.to_string());
+        })
+    }
+
+    fn set_index_buffer(&mut self, buffer: &str) -> Result<(), GraphicsError> {
+        self.with_bindings(|bindings| bindings.index_buffer = Some(buffer.to_string()))
+    }
+
+    fn set_uniform_buffer(&mut self, buffer: &str, slot: u32) -> Result<(), GraphicsError> {
+        self.with_bindings(|bindings| {
+            bindings.uniform_buffers.insert(slot, buffer.to_string());
+        })
+    }
+
+    fn set_texture(&mut self, texture: &str, slot: u32) -> Result<(), GraphicsError> {
+        self.with_bindings(|bindings| {
+            bindings.textures.insert(slot, texture.to_string());
+        })
+    }
+
+    fn update_uniform(&mut self, name: &str, data: &[u8]) -> Result<(), GraphicsError> {
+        let mut device = lock_device(&self.device)?;
+        device.uniforms.insert(name.to_string(), data.to_vec());
+        Ok(())
+    }
+}
+
+/// wgpuレンダーパス
+pub struct WgpuRenderPass {
+    device: Arc<Mutex<WgpuDevice>>,
+    state: PassState,
+    width: u32,
+    height: u32,
+    format: wgpu::TextureFormat,
+    active: bool,
+}
+
+impl WgpuRenderPass {
+    fn sync_state(&self) -> Result<(), GraphicsError> {
+        if self.active {
+            let mut device = lock_device(&self.device)?;
+            device.pass = Some(self.state.clone());
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for WgpuRenderPass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WgpuRenderPass")
+            .field("target", &self.state.target)
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("active", &self.active)
+            .finish()
+    }
+}
+
+impl RenderPass for WgpuRenderPass {
+    fn begin(&mut self) -> Result<(), GraphicsError> {
+        let mut device = lock_device(&self.device)?;
+        device.ensure_render_target(&self.state.target, self.width, self.height, self.format)?;
+        device.pass = Some(self.state.clone());
+        self.active = true;
+        Ok(())
+    }
+
+    fn end(&mut self) -> Result<(), GraphicsError> {
+        let mut device = lock_device(&self.device)?;
+        device.pass = None;
+        self.active = false;
+        Ok(())
+    }
+
+    fn set_viewport(&mut self, x: f32, y: f32, width: f32, height: f32, min_depth: f32, max_depth: f32) -> Result<(), GraphicsError> {
+        self.state.viewport = [x, y, width, height, min_depth, max_depth];
+        self.sync_state()
+    }
+
+    fn set_scissor(&mut self, x: i32, y: i32, width: u32, height: u32) -> Result<(), GraphicsError> {
+        self.state.scissor = Some((x, y, width, height));
+        self.sync_state()
+    }
+}
+
+/// wgpuコマンドバッファ
+///
+/// コマンドは記録時点のバインディングとともに保持し、送信時にまとめてエンコードします。
+pub struct WgpuCommandBuffer {
+    device: Arc<Mutex<WgpuDevice>>,
+    commands: Vec<Command>,
+    recording: bool,
+}
+
+impl WgpuCommandBuffer {
+    fn current_pass(&self, device: &WgpuDevice) -> Result<PassState, GraphicsError> {
+        if !self.recording {
+            return Err(GraphicsError::Rendering(
+                "コマンドバッファの記録が開始されていません".to_string()
+            ));
+        }
+        device.pass.clone().ok_or_else(|| {
+            GraphicsError::Rendering("レンダーパスが開始されていません".to_string())
+        })
+    }
+
+    fn record_draw(&mut self, call: DrawCall) -> Result<(), GraphicsError> {
+        let device = lock_device(&self.device)?;
+        let pass = self.current_pass(&device)?;
+        let command = Command::Draw {
+            pass,
+            bindings: Box::new(device.bindings.clone()),
+            uniforms: device.uniforms.clone(),
+            call,
+        };
+        drop(device);
+
+        self.commands.push(command);
+        Ok(())
+    }
+}
+
+impl fmt::Debug for WgpuCommandBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WgpuCommandBuffer")
+            .field("commands", &self.commands.len())
+            .field("recording", &self.recording)
+            .finish()
+    }
+}
+
+impl RenderCommandBuffer for WgpuCommandBuffer {
+    fn begin(&mut self) -> Result<(), GraphicsError> {
+        self.commands.clear();
+        self.recording = true;
+        Ok(())
+    }
+
+    fn end(&mut self) -> Result<(), GraphicsError> {
+        self.recording = false;
+        Ok(())
+    }
+
+    fn submit(&mut self) -> Result<(), GraphicsError> {
+        if self.recording {
+            return Err(GraphicsError::Rendering(
+                "記録中のコマンドバッファは送信できません".to_string()
+            ));
+        }
+
+        let mut device = lock_device(&self.device)?;
+        device.execute(&self.commands)
+    }
+
+    fn clear(&mut self, color: [f32; 4], depth: f32, stencil: u8) -> Result<(), GraphicsError> {
+        let device = lock_device(&self.device)?;
+        let pass = self.current_pass(&device)?;
+        drop(device);
+
+        self.commands.push(Command::Clear { pass, color, depth, stencil });
+        Ok(())
+    }
+
+    fn draw(&mut self, vertex_count: u32, first_vertex: u32) -> Result<(), GraphicsError> {
+        self.draw_instanced(vertex_count, 1, first_vertex, 0)
+    }
+
+    fn draw_indexed(&mut self, index_count: u32, first_index: u32, vertex_offset: i32) -> Result<(), GraphicsError> {
+        self.draw_indexed_instanced(index_count, 1, first_index, vertex_offset, 0)
+    }
+
+    fn draw_instanced(&mut self, vertex_count: u32, instance_count: u32, first_vertex: u32, first_instance: u32) -> Result<(), GraphicsError> {
+        self.record_draw(DrawCall {
+            indexed: false,
+            count: vertex_count,
+            first: first_vertex,
+            vertex_offset: 0,
+            instance_count,
+            first_instance,
+        })
+    }
+
+    fn draw_indexed_instanced(&mut self, index_count: u32, instance_count: u32, first_index: u32, vertex_offset: i32, first_instance: u32) -> Result<(), GraphicsError> {
+        self.record_draw(DrawCall {
+            indexed: true,
+            count: index_count,
+            first: first_index,
+            vertex_offset,
+            instance_count,
+            first_instance,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::renderer::{VertexAttribute, VertexLayout};
+
+    /// 初期化済みのレンダラー
+    ///
+    /// GPUがない環境でもソフトウェアアダプター（lavapipe/llvmpipe/WARP）に
+    /// フォールバックするため、アダプターが見つからない場合はテストを失敗させる。
+    fn renderer() -> WgpuRenderer {
+        let config = GraphicsConfig::default();
+        let mut renderer = WgpuRenderer::new(&config).unwrap();
+        if let Err(e) = renderer.initialize(&config) {
+            panic!("wgpuアダプターを初期化できません（ソフトウェアアダプターも見つかりません）: {}", e);
+        }
+        renderer
+    }
+
+    fn color_layout() -> VertexLayout {
+        VertexLayout {
+            attributes: vec![
+                VertexAttribute { name: "position".to_string(), format: VertexFormat::Float3, offset: 0, location: 0, divisor: 0 },
+                VertexAttribute { name: "color".to_string(), format: VertexFormat::Float4, offset: 12, location: 1, divisor: 0 },
+            ],
+            stride: 28,
+        }
+    }
+
+    fn quad_bytes(z: f32, color: [f32; 4], rect: [f32; 4]) -> Vec<u8> {
+        let [x0, y0, x1, y1] = rect;
+        [[x0, y0], [x1, y0], [x1, y1], [x0, y0], [x1, y1], [x0, y1]]
+            .iter()
+            .flat_map(|[x, y]| [*x, *y, z, color[0], color[1], color[2], color[3]])
+            .flat_map(|f| f.to_le_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn test_conversions() {
+        assert_eq!(vertex_format(VertexFormat::UByte4), wgpu::VertexFormat::Unorm8x4);
+        assert!(primitive_topology(PrimitiveType::TriangleFan).is_err());
+        assert!(blend_factor(BlendFactor::ConstantAlpha).is_err());
+        assert_eq!(stencil_operation(StencilOp::Increment), wgpu::StencilOperation::IncrementClamp);
+
+        let (format, expand) = texture_format(TextureFormat::R8G8B8, wgpu::Features::empty()).unwrap();
+        assert_eq!(format, wgpu::TextureFormat::Rgba8Unorm);
+        assert!(expand);
+        assert!(texture_format(TextureFormat::BC7, wgpu::Features::empty()).is_err());
+        assert_eq!(expand_rgb(&[1, 2, 3, 4, 5, 6], 1), vec![1, 2, 3, 255, 4, 5, 6, 255]);
+
+        // 属性の名前から組み込みシェーダーの入力が生成される
+        let state = PipelineState { vertex_layout: color_layout(), ..PipelineState::default() };
+        let source = builtin_shader_source(&state, false).unwrap();
+        assert!(source.contains("@location(1) color: vec4<f32>"));
+        assert!(!source.contains("textureSample("));
+        assert!(builtin_shader_source(&PipelineState::default(), false).is_err());
+    }
+
+    #[test]
+    fn test_globals_layout() {
+        let mut uniforms = HashMap::new();
+        uniforms.insert(UNIFORM_TINT.to_string(), [0.5f32, 0.25, 1.0, 1.0].iter().flat_map(|f| f.to_le_bytes()).collect());
+        let bytes = globals_bytes(&uniforms);
+
+        assert_eq!(bytes.len() as u64, GLOBALS_SIZE);
+        assert_eq!(f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]), 1.0);
+        assert_eq!(f32::from_le_bytes([bytes[64], bytes[65], bytes[66], bytes[67]]), 0.5);
+    }
+
+    #[test]
+    fn test_reinitialize_with_shared_instance() {
+        let backends = wgpu::Backends::all();
+        assert!(Arc::ptr_eq(&shared_instance(backends), &shared_instance(backends)));
+
+        // 別のレンダラーを作っても、shutdown後に初期化し直してもデバイスを取得できる
+        let mut first = renderer();
+        let second = renderer();
+        assert!(second.device().is_ok());
+
+        let config = GraphicsConfig::default();
+        first.shutdown().unwrap();
+        first.initialize(&config).unwrap();
+        assert!(first.device().is_ok());
+    }
+
+    #[test]
+    fn test_render_and_read_back() {
+        let mut renderer = renderer();
+
+        let background = quad_bytes(0.8, [1.0, 0.0, 0.0, 1.0], [-1.0, -1.0, 1.0, 1.0]);
+        let foreground = quad_bytes(0.2, [0.0, 1.0, 0.0, 1.0], [-1.0, -1.0, 0.0, 1.0]);
+        renderer.create_buffer("background", BufferTarget::Vertex, BufferUsage::Static, Some(&background), background.len()).unwrap();
+        renderer.create_buffer("foreground", BufferTarget::Vertex, BufferUsage::Static, Some(&foreground), foreground.len()).unwrap();
+
+        let mut context = renderer.create_context().unwrap();
+        let target = renderer.create_render_target("target", 16, 16, TextureFormat::B8G8R8A8).unwrap();
+        let mut pass = context.create_render_pass(target).unwrap();
+        let mut commands = context.create_command_buffer().unwrap();
+
+        pass.begin().unwrap();
+        commands.begin().unwrap();
+        commands.clear([0.0, 0.0, 0.0, 1.0], 1.0, 0).unwrap();
+        context.set_shader(SHADER_VERTEX_COLOR).unwrap();
+        context.set_pipeline_state(&PipelineState { vertex_layout: color_layout(), ..PipelineState::default() }).unwrap();
+
+        // 手前の緑を先に描き、奥の赤は深度テストで左半分が隠れる
+        context.set_vertex_buffer("foreground", 0).unwrap();
+        commands.draw(6, 0).unwrap();
+        context.set_vertex_buffer("background", 0).unwrap();
+        commands.draw(6, 0).unwrap();
+
+        commands.end().unwrap();
+        pass.end().unwrap();
+        commands.submit().unwrap();
+
+        let pixels = renderer.read_pixels("target").unwrap();
+        let pixel = |x: usize, y: usize| &pixels[(y * 16 + x) * 4..(y * 16 + x) * 4 + 4];
+        assert_eq!(pixel(4, 8), [0, 255, 0, 255]);
+        assert_eq!(pixel(12, 8), [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_texture_with_mips() {
+        let mut renderer = renderer();
+
+        let base = [255u8; 4 * 4 * 4];
+        let mip1 = [128u8; 2 * 2 * 4];
+        let mip2 = [64u8; 4];
+        renderer.create_texture_with_mips("mipmapped", 4, 4, TextureFormat::R8G8B8A8, &[&base, &mip1, &mip2]).unwrap();
+        assert_eq!(renderer.texture_size("mipmapped"), Some((4, 4)));
+
+        // 1x1より小さいミップレベルやデータ不足はエラー
+        let levels: [&[u8]; 4] = [&base, &mip1, &mip2, &mip2];
+        assert!(renderer.create_texture_with_mips("too_many", 4, 4, TextureFormat::R8G8B8A8, &levels).is_err());
+        assert!(renderer.create_texture_with_mips("short", 4, 4, TextureFormat::R8G8B8A8, &[&base, &mip2]).is_err());
+    }
+
+    #[test]
+    fn test_custom_shader_registration() {
+        let mut renderer = renderer();
+
+        assert!(renderer.register_wgsl_shader("broken", "fn vs_main( {").is_err());
+
+        let source = r#"
+            @vertex
+            fn vs_main(@location(0) position: vec3<f32>) -> @builtin(position) vec4<f32> {
+                return vec4<f32>(position, 1.0);
+            }
+
+            @fragment
+            fn fs_main() -> @location(0) vec4<f32> {
+                return vec4<f32>(1.0, 1.0, 1.0, 1.0);
+            }
+        "#;
+        renderer.register_wgsl_shader("white", source).unwrap();
+
+        let mut context = renderer.create_context().unwrap();
+        assert!(context.set_shader("white").is_ok());
+        assert!(context.set_shader("missing").is_err());
+    }
+}
@@ -0,0 +1,809 @@
+// LumosDesktop カラーマネジメント
+// ICCプロファイルを解析し、ウィンドウの色空間から出力のプロファイルへ色を変換します
+
+use std::sync::OnceLock;
+
+use thiserror::Error;
+
+/// 3x3行列（行優先）
+pub type Matrix3 = [[f64; 3]; 3];
+
+/// ICCのプロファイル接続空間（PCS）の白色点 D50
+const D50: [f64; 3] = [0.9642, 1.0, 0.8249];
+/// D65の色度座標
+const D65_XY: (f64, f64) = (0.3127, 0.3290);
+
+/// Bradford変換の錐体応答行列
+const BRADFORD: Matrix3 = [
+    [0.8951, 0.2664, -0.1614],
+    [-0.7502, 1.7135, 0.0367],
+    [0.0389, -0.0685, 1.0296],
+];
+
+const HEADER_SIZE: usize = 128;
+/// 出力側のトーンカーブの逆変換テーブルの大きさ
+const ENCODE_LUT_SIZE: usize = 4096;
+
+/// ICCプロファイルの解析エラー
+#[derive(Debug, Error, PartialEq)]
+pub enum IccError {
+    #[error("ICCプロファイルが短すぎます: {0}バイト")]
+    TooShort(usize),
+
+    #[error("ICCプロファイルの署名がありません")]
+    InvalidSignature,
+
+    #[error("対応していないICCプロファイルのバージョンです: {0}")]
+    UnsupportedVersion(u8),
+
+    #[error("対応していない色空間です: {0}")]
+    UnsupportedColorSpace(String),
+
+    #[error("対応していないプロファイル接続空間です: {0}")]
+    UnsupportedPcs(String),
+
+    #[error("行列とトーンカーブのタグがありません: {0}")]
+    MissingTag(String),
+
+    #[error("タグの形式が不正です: {0}")]
+    InvalidTag(String),
+
+    #[error("色の変換行列が逆行列を持ちません")]
+    SingularMatrix,
+}
+
+fn mul(a: &Matrix3, b: &Matrix3) -> Matrix3 {
+    let mut out = [[0.0; 3]; 3];
+    for (i, row) in out.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = (0..3).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+    out
+}
+
+fn mul_vec(m: &Matrix3, v: [f64; 3]) -> [f64; 3] {
+    [0, 1, 2].map(|i| m[i][0] * v[0] + m[i][1] * v[1] + m[i][2] * v[2])
+}
+
+fn diagonal(v: [f64; 3]) -> Matrix3 {
+    [[v[0], 0.0, 0.0], [0.0, v[1], 0.0], [0.0, 0.0, v[2]]]
+}
+
+fn invert(m: &Matrix3) -> Option<Matrix3> {
+    let cofactor = |r0: usize, r1: usize, c0: usize, c1: usize| m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0];
+    let det = m[0][0] * cofactor(1, 2, 1, 2) - m[0][1] * cofactor(1, 2, 0, 2) + m[0][2] * cofactor(1, 2, 0, 1);
+    if det.abs() < 1e-12 {
+        return None;
+    }
+    Some([
+        [cofactor(1, 2, 1, 2) / det, -cofactor(0, 2, 1, 2) / det, cofactor(0, 1, 1, 2) / det],
+        [-cofactor(1, 2, 0, 2) / det, cofactor(0, 2, 0, 2) / det, -cofactor(0, 1, 0, 2) / det],
+        [cofactor(1, 2, 0, 1) / det, -cofactor(0, 2, 0, 1) / det, cofactor(0, 1, 0, 1) / det],
+    ])
+}
+
+/// 色度座標をY=1のXYZに変換
+fn xy_to_xyz((x, y): (f64, f64)) -> [f64; 3] {
+    [x / y, 1.0, (1.0 - x - y) / y]
+}
+
+/// 原色と白色点の色度座標から、線形RGBをXYZに変換する行列を求める
+fn rgb_to_xyz(primaries: [(f64, f64); 3], white: (f64, f64)) -> Matrix3 {
+    let [r, g, b] = primaries.map(xy_to_xyz);
+    let columns = [[r[0], g[0], b[0]], [r[1], g[1], b[1]], [r[2], g[2], b[2]]];
+    let inverse = invert(&columns).expect("原色が一直線上にある");
+    mul(&columns, &diagonal(mul_vec(&inverse, xy_to_xyz(white))))
+}
+
+/// 白色点 `from` を `to` に合わせるBradford変換
+fn bradford(from: [f64; 3], to: [f64; 3]) -> Matrix3 {
+    let (source, target) = (mul_vec(&BRADFORD, from), mul_vec(&BRADFORD, to));
+    let scale = diagonal([0, 1, 2].map(|i| target[i] / source[i]));
+    let inverse = invert(&BRADFORD).expect("Bradford行列は正則");
+    mul(&inverse, &mul(&scale, &BRADFORD))
+}
+
+/// D65の原色から、線形RGBをPCS（D50に順応したXYZ）に変換する行列を求める
+fn d65_rgb_to_pcs(primaries: [(f64, f64); 3]) -> Matrix3 {
+    mul(&bradford(xy_to_xyz(D65_XY), D50), &rgb_to_xyz(primaries, D65_XY))
+}
+
+/// トーンカーブ（符号化された値から線形の値への変換）
+#[derive(Debug, Clone, PartialEq)]
+pub enum ToneCurve {
+    /// 単純なガンマ（1.0は恒等変換）
+    Gamma(f64),
+    /// 等間隔にサンプリングした16ビットのテーブル
+    Table(Vec<u16>),
+    /// ICCのパラメトリックカーブ [g, a, b, c, d, e, f]
+    ///
+    /// `x >= d` では `(a*x + b)^g + e`、それ以外では `c*x + f` です。
+    Parametric([f64; 7]),
+}
+
+impl ToneCurve {
+    /// sRGBとDisplay P3のトーンカーブ
+    pub fn srgb() -> Self {
+        ToneCurve::Parametric([2.4, 1.0 / 1.055, 0.055 / 1.055, 1.0 / 12.92, 0.04045, 0.0, 0.0])
+    }
+
+    /// 符号化された値（0〜1）を線形の値に変換
+    pub fn eval(&self, x: f64) -> f64 {
+        let x = x.clamp(0.0, 1.0);
+        let y = match self {
+            ToneCurve::Gamma(gamma) => x.powf(*gamma),
+            ToneCurve::Table(table) => match table.len() {
+                0 => x,
+                1 => table[0] as f64 / 65535.0,
+                len => {
+                    let position = x * (len - 1) as f64;
+                    let index = (position.floor() as usize).min(len - 2);
+                    let t = position - index as f64;
+                    (table[index] as f64 * (1.0 - t) + table[index + 1] as f64 * t) / 65535.0
+                }
+            },
+            ToneCurve::Parametric([g, a, b, c, d, e, f]) => {
+                if x >= *d {
+                    (a * x + b).max(0.0).powf(*g) + e
+                } else {
+                    c * x + f
+                }
+            }
+        };
+        y.clamp(0.0, 1.0)
+    }
+
+    /// 線形の値（0〜1）を符号化された値に変換（単調なカーブを二分探索）
+    pub fn inverse(&self, y: f64) -> f64 {
+        let (mut low, mut high) = (0.0, 1.0);
+        for _ in 0..32 {
+            let mid = (low + high) / 2.0;
+            if self.eval(mid) < y {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        (low + high) / 2.0
+    }
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(data.get(offset..offset + 2)?.try_into().ok()?))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+/// s15Fixed16Number
+fn read_s15(data: &[u8], offset: usize) -> Option<f64> {
+    Some(read_u32(data, offset)? as i32 as f64 / 65536.0)
+}
+
+fn signature(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).trim_end().to_string()
+}
+
+/// タグテーブル
+struct TagTable<'a> {
+    data: &'a [u8],
+    entries: Vec<([u8; 4], usize, usize)>,
+}
+
+impl<'a> TagTable<'a> {
+    fn parse(data: &'a [u8]) -> Result<Self, IccError> {
+        let count = read_u32(data, HEADER_SIZE).ok_or(IccError::TooShort(data.len()))? as usize;
+        let mut entries = Vec::with_capacity(count.min(64));
+        for i in 0..count {
+            let base = HEADER_SIZE + 4 + i * 12;
+            let entry = data.get(base..base + 12).ok_or(IccError::TooShort(data.len()))?;
+            let sig: [u8; 4] = entry[0..4].try_into().unwrap();
+            let offset = read_u32(entry, 4).unwrap() as usize;
+            let size = read_u32(entry, 8).unwrap() as usize;
+            entries.push((sig, offset, size));
+        }
+        Ok(Self { data, entries })
+    }
+
+    fn find(&self, sig: &[u8; 4]) -> Result<Option<&'a [u8]>, IccError> {
+        let Some(&(_, offset, size)) = self.entries.iter().find(|(entry, _, _)| entry == sig) else { return Ok(None) };
+        let tag = offset.checked_add(size).and_then(|end| self.data.get(offset..end));
+        tag.map(Some).ok_or_else(|| IccError::InvalidTag(signature(sig)))
+    }
+
+    fn get(&self, sig: &[u8; 4]) -> Result<&'a [u8], IccError> {
+        self.find(sig)?.ok_or_else(|| IccError::MissingTag(signature(sig)))
+    }
+}
+
+fn parse_xyz(tag: &[u8], sig: &[u8; 4]) -> Result<[f64; 3], IccError> {
+    let invalid = || IccError::InvalidTag(signature(sig));
+    if tag.get(0..4) != Some(b"XYZ ") {
+        return Err(invalid());
+    }
+    Ok([read_s15(tag, 8).ok_or_else(invalid)?, read_s15(tag, 12).ok_or_else(invalid)?, read_s15(tag, 16).ok_or_else(invalid)?])
+}
+
+fn parse_curve(tag: &[u8], sig: &[u8; 4]) -> Result<ToneCurve, IccError> {
+    let invalid = || IccError::InvalidTag(signature(sig));
+    match tag.get(0..4) {
+        Some(b"curv") => {
+            let count = read_u32(tag, 8).ok_or_else(invalid)? as usize;
+            match count {
+                0 => Ok(ToneCurve::Gamma(1.0)),
+                // u8Fixed8Number
+                1 => Ok(ToneCurve::Gamma(read_u16(tag, 12).ok_or_else(invalid)? as f64 / 256.0)),
+                _ => (0..count)
+                    .map(|i| read_u16(tag, 12 + i * 2))
+                    .collect::<Option<Vec<u16>>>()
+                    .map(ToneCurve::Table)
+                    .ok_or_else(invalid),
+            }
+        }
+        Some(b"para") => {
+            let function = read_u16(tag, 8).ok_or_else(invalid)?;
+            let count = match function {
+                0 => 1,
+                1 => 3,
+                2 => 4,
+                3 => 5,
+                4 => 7,
+                _ => return Err(invalid()),
+            };
+            let params = (0..count)
+                .map(|i| read_s15(tag, 12 + i * 4))
+                .collect::<Option<Vec<f64>>>()
+                .ok_or_else(invalid)?;
+            let p = |i: usize| params[i];
+            // すべての関数型を [g, a, b, c, d, e, f] の形にそろえる
+            let params = match function {
+                0 => [p(0), 1.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+                1 => [p(0), p(1), p(2), 0.0, -p(2) / p(1), 0.0, 0.0],
+                2 => [p(0), p(1), p(2), 0.0, -p(2) / p(1), p(3), p(3)],
+                3 => [p(0), p(1), p(2), p(3), p(4), 0.0, 0.0],
+                _ => [p(0), p(1), p(2), p(3), p(4), p(5), p(6)],
+            };
+            Ok(ToneCurve::Parametric(params))
+        }
+        _ => Err(invalid()),
+    }
+}
+
+/// v2の `desc`（textDescriptionType）またはv4の `mluc` から説明を読む
+fn parse_description(tag: &[u8]) -> Option<String> {
+    match tag.get(0..4)? {
+        b"desc" => {
+            let count = read_u32(tag, 8)? as usize;
+            let text = tag.get(12..12 + count)?;
+            Some(String::from_utf8_lossy(text).trim_end_matches('\0').to_string())
+        }
+        b"mluc" => {
+            let record_size = read_u32(tag, 12)? as usize;
+            // 記録は言語・国・長さ・オフセットの12バイト以上
+            if record_size < 12 {
+                return None;
+            }
+            // 宣言された件数はタグに収まる分までしか信用しない
+            let count = (read_u32(tag, 8)? as usize).min(tag.len().saturating_sub(16) / record_size);
+            // 英語の記録を優先し、なければ最初の記録を使う
+            let record = (0..count)
+                .map(|i| 16 + i * record_size)
+                .find(|&base| tag.get(base..base + 2) == Some(b"en"))
+                .or((count > 0).then_some(16))?;
+            let length = read_u32(tag, record + 4)? as usize;
+            let offset = read_u32(tag, record + 8)? as usize;
+            let units: Vec<u16> = tag.get(offset..offset.checked_add(length)?)?
+                .chunks_exact(2)
+                .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                .collect();
+            Some(String::from_utf16_lossy(&units).trim_end_matches('\0').to_string())
+        }
+        _ => None,
+    }
+}
+
+/// 行列とトーンカーブで表されるRGBのICCプロファイル
+#[derive(Debug, Clone, PartialEq)]
+pub struct IccProfile {
+    /// メジャーバージョンとマイナーバージョン
+    version: (u8, u8),
+    description: String,
+    /// 線形RGBからPCSへの行列
+    to_pcs: Matrix3,
+    from_pcs: Matrix3,
+    curves: [ToneCurve; 3],
+}
+
+impl IccProfile {
+    /// 線形RGBからPCS（D50のXYZ）への行列と、チャンネルごとのトーンカーブからプロファイルを作成
+    pub fn new(description: &str, to_pcs: Matrix3, curves: [ToneCurve; 3]) -> Result<Self, IccError> {
+        let from_pcs = invert(&to_pcs).ok_or(IccError::SingularMatrix)?;
+        Ok(Self {
+            version: (4, 3),
+            description: description.to_string(),
+            to_pcs,
+            from_pcs,
+            curves,
+        })
+    }
+
+    /// sRGBのプロファイル
+    pub fn srgb() -> Self {
+        ColorSpace::Srgb.profile()
+    }
+
+    /// Display P3のプロファイル
+    pub fn display_p3() -> Self {
+        ColorSpace::DisplayP3.profile()
+    }
+
+    /// ICC v2/v4のプロファイルを解析
+    ///
+    /// 行列とトーンカーブ（rXYZ/gXYZ/bXYZ と rTRC/gTRC/bTRC）を持つRGBの
+    /// プロファイルに対応しています。LUTのみのプロファイルは `MissingTag` になります。
+    pub fn parse(data: &[u8]) -> Result<Self, IccError> {
+        if data.len() < HEADER_SIZE + 4 {
+            return Err(IccError::TooShort(data.len()));
+        }
+        if &data[36..40] != b"acsp" {
+            return Err(IccError::InvalidSignature);
+        }
+        let major = data[8];
+        if major != 2 && major != 4 {
+            return Err(IccError::UnsupportedVersion(major));
+        }
+        if &data[16..20] != b"RGB " {
+            return Err(IccError::UnsupportedColorSpace(signature(&data[16..20])));
+        }
+        if &data[20..24] != b"XYZ " {
+            return Err(IccError::UnsupportedPcs(signature(&data[20..24])));
+        }
+
+        let tags = TagTable::parse(data)?;
+        let colorant = |sig: &[u8; 4]| tags.get(sig).and_then(|tag| parse_xyz(tag, sig));
+        let (r, g, b) = (colorant(b"rXYZ")?, colorant(b"gXYZ")?, colorant(b"bXYZ")?);
+        let curve = |sig: &[u8; 4]| tags.get(sig).and_then(|tag| parse_curve(tag, sig));
+        let curves = [curve(b"rTRC")?, curve(b"gTRC")?, curve(b"bTRC")?];
+        let description = tags.find(b"desc")?.and_then(parse_description).unwrap_or_default();
+
+        let to_pcs = [[r[0], g[0], b[0]], [r[1], g[1], b[1]], [r[2], g[2], b[2]]];
+        Ok(Self {
+            version: (major, data[9] >> 4),
+            ..Self::new(&description, to_pcs, curves)?
+        })
+    }
+
+    /// ICC v4のプロファイルとして書き出す
+    pub fn encode(&self) -> Vec<u8> {
+        let xyz = |v: [f64; 3]| {
+            let mut tag = b"XYZ \0\0\0\0".to_vec();
+            for value in v {
+                tag.extend_from_slice(&((value * 65536.0).round() as i32).to_be_bytes());
+            }
+            tag
+        };
+        let curve = |curve: &ToneCurve| {
+            let mut tag = Vec::new();
+            match curve {
+                ToneCurve::Gamma(gamma) => {
+                    tag.extend_from_slice(b"curv\0\0\0\0");
+                    tag.extend_from_slice(&1u32.to_be_bytes());
+                    tag.extend_from_slice(&((gamma * 256.0).round() as u16).to_be_bytes());
+                }
+                ToneCurve::Table(table) => {
+                    tag.extend_from_slice(b"curv\0\0\0\0");
+                    tag.extend_from_slice(&(table.len() as u32).to_be_bytes());
+                    for value in table {
+                        tag.extend_from_slice(&value.to_be_bytes());
+                    }
+                }
+                ToneCurve::Parametric(params) => {
+                    tag.extend_from_slice(b"para\0\0\0\0");
+                    tag.extend_from_slice(&4u16.to_be_bytes());
+                    tag.extend_from_slice(&[0, 0]);
+                    for value in params {
+                        tag.extend_from_slice(&((value * 65536.0).round() as i32).to_be_bytes());
+                    }
+                }
+            }
+            tag
+        };
+        let description = {
+            let text: Vec<u8> = self.description.encode_utf16().flat_map(u16::to_be_bytes).collect();
+            let mut tag = b"mluc\0\0\0\0".to_vec();
+            for value in [1u32, 12] {
+                tag.extend_from_slice(&value.to_be_bytes());
+            }
+            tag.extend_from_slice(b"enUS");
+            tag.extend_from_slice(&(text.len() as u32).to_be_bytes());
+            tag.extend_from_slice(&28u32.to_be_bytes());
+            tag.extend_from_slice(&text);
+            tag
+        };
+        let column = |i: usize| [self.to_pcs[0][i], self.to_pcs[1][i], self.to_pcs[2][i]];
+        let tags: [(&[u8; 4], Vec<u8>); 8] = [
+            (b"desc", description),
+            (b"wtpt", xyz(D50)),
+            (b"rXYZ", xyz(column(0))),
+            (b"gXYZ", xyz(column(1))),
+            (b"bXYZ", xyz(column(2))),
+            (b"rTRC", curve(&self.curves[0])),
+            (b"gTRC", curve(&self.curves[1])),
+            (b"bTRC", curve(&self.curves[2])),
+        ];
+
+        let mut table = (tags.len() as u32).to_be_bytes().to_vec();
+        let mut body = Vec::new();
+        let data_start = HEADER_SIZE + 4 + tags.len() * 12;
+        for (sig, tag) in &tags {
+            table.extend_from_slice(*sig);
+            table.extend_from_slice(&((data_start + body.len()) as u32).to_be_bytes());
+            table.extend_from_slice(&(tag.len() as u32).to_be_bytes());
+            body.extend_from_slice(tag);
+            // タグは4バイト境界にそろえる
+            body.resize(body.len().next_multiple_of(4), 0);
+        }
+
+        let mut header = vec![0u8; HEADER_SIZE];
+        header[0..4].copy_from_slice(&((HEADER_SIZE + table.len() + body.len()) as u32).to_be_bytes());
+        header[8] = 4;
+        header[9] = 0x30;
+        header[12..16].copy_from_slice(b"mntr");
+        header[16..20].copy_from_slice(b"RGB ");
+        header[20..24].copy_from_slice(b"XYZ ");
+        header[36..40].copy_from_slice(b"acsp");
+        for (i, value) in D50.iter().enumerate() {
+            header[68 + i * 4..72 + i * 4].copy_from_slice(&((value * 65536.0).round() as i32).to_be_bytes());
+        }
+        [header, table, body].concat()
+    }
+
+    pub fn version(&self) -> (u8, u8) {
+        self.version
+    }
+
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    /// 線形RGBからPCS（D50のXYZ）への行列
+    pub fn to_pcs(&self) -> &Matrix3 {
+        &self.to_pcs
+    }
+
+    /// チャンネル（0=赤, 1=緑, 2=青）のトーンカーブ
+    pub fn curve(&self, channel: usize) -> &ToneCurve {
+        &self.curves[channel]
+    }
+}
+
+/// ウィンドウの内容の色空間
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum ColorSpace {
+    #[default]
+    Srgb,
+    DisplayP3,
+}
+
+impl ColorSpace {
+    /// 色空間を表すプロファイル（どちらもsRGBのトーンカーブを使う）
+    pub fn profile(self) -> IccProfile {
+        let (description, primaries) = match self {
+            ColorSpace::Srgb => ("sRGB", [(0.64, 0.33), (0.30, 0.60), (0.15, 0.06)]),
+            ColorSpace::DisplayP3 => ("Display P3", [(0.680, 0.320), (0.265, 0.690), (0.150, 0.060)]),
+        };
+        let curves = [ToneCurve::srgb(), ToneCurve::srgb(), ToneCurve::srgb()];
+        IccProfile::new(description, d65_rgb_to_pcs(primaries), curves).expect("標準の色空間の行列は正則")
+    }
+
+    fn index(self) -> usize {
+        match self {
+            ColorSpace::Srgb => 0,
+            ColorSpace::DisplayP3 => 1,
+        }
+    }
+}
+
+/// 夜間モード（色温度による白色点の調整）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NightLight {
+    /// 色温度（ケルビン）
+    temperature: u32,
+}
+
+impl NightLight {
+    /// 調整しない色温度（D65に相当）
+    pub const NEUTRAL_TEMPERATURE: u32 = 6500;
+
+    /// 近似式が有効な範囲
+    const MIN_TEMPERATURE: u32 = 1667;
+    const MAX_TEMPERATURE: u32 = 25000;
+
+    pub fn new(temperature: u32) -> Self {
+        Self {
+            temperature: temperature.clamp(Self::MIN_TEMPERATURE, Self::MAX_TEMPERATURE),
+        }
+    }
+
+    pub fn temperature(&self) -> u32 {
+        self.temperature
+    }
+
+    /// 黒体放射の色度座標（Kimらの近似式）
+    fn planckian_xy(temperature: u32) -> (f64, f64) {
+        let t = temperature as f64;
+        let x = if t <= 4000.0 {
+            -0.2661239e9 / t.powi(3) - 0.2343589e6 / t.powi(2) + 0.8776956e3 / t + 0.179910
+        } else {
+            -3.0258469e9 / t.powi(3) + 2.1070379e6 / t.powi(2) + 0.2226347e3 / t + 0.240390
+        };
+        let y = if t <= 2222.0 {
+            -1.1063814 * x.powi(3) - 1.34811020 * x.powi(2) + 2.18555832 * x - 0.20219683
+        } else if t <= 4000.0 {
+            -0.9549476 * x.powi(3) - 1.37418593 * x.powi(2) + 2.09137015 * x - 0.16748867
+        } else {
+            3.0817580 * x.powi(3) - 5.87338670 * x.powi(2) + 3.75112997 * x - 0.37001483
+        };
+        (x, y)
+    }
+
+    /// 線形RGBのチャンネルごとの倍率（最大のチャンネルが1）
+    ///
+    /// 基準の色温度で1になるように、黒体放射の色をsRGBの原色で表した比を使います。
+    pub fn gains(&self) -> [f64; 3] {
+        let from_xyz = invert(&rgb_to_xyz([(0.64, 0.33), (0.30, 0.60), (0.15, 0.06)], D65_XY)).expect("sRGBの行列は正則");
+        let rgb = |temperature| mul_vec(&from_xyz, xy_to_xyz(Self::planckian_xy(temperature)));
+        let (target, neutral) = (rgb(self.temperature), rgb(Self::NEUTRAL_TEMPERATURE));
+        let gains = [0, 1, 2].map(|i| (target[i] / neutral[i]).max(0.0));
+        let max = gains.iter().cloned().fold(f64::MIN, f64::max);
+        gains.map(|gain| gain / max)
+    }
+}
+
+/// 出力ごとの色変換
+///
+/// ウィンドウの色空間から出力のプロファイルへの変換です。線形RGBの行列に夜間モードの
+/// 倍率を含め、入力側は256段のテーブル、出力側はトーンカーブの逆変換テーブルで変換します。
+#[derive(Debug)]
+pub struct OutputColorTransform {
+    profile: IccProfile,
+    /// 出力にプロファイルや夜間モードがなく、sRGBをそのまま表示できる
+    srgb_identity: bool,
+    /// 色空間ごとの、入力の線形RGBから出力の線形RGBへの行列
+    matrices: [[[f32; 3]; 3]; 2],
+    decode: [f32; 256],
+    /// 出力のトーンカーブの逆変換（初めて使うときに作る）
+    encode: OnceLock<[Vec<u8>; 3]>,
+}
+
+impl OutputColorTransform {
+    /// 出力のプロファイル（`None` の場合はsRGB）と夜間モードから変換を作成
+    pub fn new(profile: Option<&IccProfile>, night_light: Option<NightLight>) -> Self {
+        let gains = night_light.map_or([1.0; 3], |night_light| night_light.gains());
+        let srgb_identity = profile.is_none() && gains == [1.0; 3];
+        let profile = profile.cloned().unwrap_or_else(IccProfile::srgb);
+        let adjust = mul(&diagonal(gains), &profile.from_pcs);
+        let matrices = [ColorSpace::Srgb, ColorSpace::DisplayP3]
+            .map(|space| mul(&adjust, &space.profile().to_pcs).map(|row| row.map(|value| value as f32)));
+        let srgb = ToneCurve::srgb();
+        let decode = std::array::from_fn(|i| srgb.eval(i as f64 / 255.0) as f32);
+        Self {
+            profile,
+            srgb_identity,
+            matrices,
+            decode,
+            encode: OnceLock::new(),
+        }
+    }
+
+    /// 出力のプロファイル
+    pub fn profile(&self) -> &IccProfile {
+        &self.profile
+    }
+
+    /// 色空間の内容を変換せずに表示できるかどうか
+    pub fn is_identity(&self, space: ColorSpace) -> bool {
+        space == ColorSpace::Srgb && self.srgb_identity
+    }
+
+    /// 入力の線形RGBから出力の線形RGBへの行列（GPUのシェーダーに渡す）
+    pub fn matrix(&self, space: ColorSpace) -> [[f32; 3]; 3] {
+        self.matrices[space.index()]
+    }
+
+    fn encode_table(&self) -> &[Vec<u8>; 3] {
+        self.encode.get_or_init(|| {
+            std::array::from_fn(|channel| {
+                let curve = self.profile.curve(channel);
+                (0..ENCODE_LUT_SIZE)
+                    .map(|i| (curve.inverse(i as f64 / (ENCODE_LUT_SIZE - 1) as f64) * 255.0).round() as u8)
+                    .collect()
+            })
+        })
+    }
+
+    /// 符号化されたRGB（0〜1）を変換（テーブルを使わない精密な計算）
+    pub fn convert(&self, space: ColorSpace, rgb: [f64; 3]) -> [f64; 3] {
+        let source = space.profile();
+        let linear = [0, 1, 2].map(|i| source.curve(i).eval(rgb[i]));
+        let matrix = self.matrices[space.index()].map(|row| row.map(f64::from));
+        let output = mul_vec(&matrix, linear);
+        [0, 1, 2].map(|i| self.profile.curve(i).inverse(output[i].clamp(0.0, 1.0)))
+    }
+
+    /// 乗算済みアルファの0xAARRGGBBを変換
+    pub fn apply(&self, space: ColorSpace, argb: u32) -> u32 {
+        if self.is_identity(space) {
+            return argb;
+        }
+        let alpha = argb >> 24;
+        if alpha == 0 {
+            return argb;
+        }
+        // 乗算済みアルファを戻してから変換する
+        let channel = |shift: u32| {
+            let value = (argb >> shift) & 0xff;
+            if alpha == 255 { value } else { ((value * 255 + alpha / 2) / alpha).min(255) }
+        };
+        let linear = [16, 8, 0].map(|shift| self.decode[channel(shift) as usize]);
+        let matrix = &self.matrices[space.index()];
+        let encode = self.encode_table();
+        let mut out = alpha << 24;
+        for (i, row) in matrix.iter().enumerate() {
+            let value = row[0] * linear[0] + row[1] * linear[1] + row[2] * linear[2];
+            let index = (value.clamp(0.0, 1.0) * (ENCODE_LUT_SIZE - 1) as f32).round() as usize;
+            let mut encoded = encode[i][index] as u32;
+            if alpha < 255 {
+                encoded = (encoded * alpha + 127) / 255;
+            }
+            out |= encoded << (16 - i * 8);
+        }
+        out
+    }
+}
+
+impl Default for OutputColorTransform {
+    fn default() -> Self {
+        Self::new(None, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn channels(argb: u32) -> [i32; 3] {
+        [16, 8, 0].map(|shift| ((argb >> shift) & 0xff) as i32)
+    }
+
+    /// v2の `desc` とガンマの `curv` を持つプロファイル
+    fn v2_profile(gamma: f64) -> Vec<u8> {
+        let mut data = IccProfile::new("", IccProfile::srgb().to_pcs, [0, 1, 2].map(|_| ToneCurve::Gamma(gamma)))
+            .unwrap()
+            .encode();
+        data[8] = 2;
+        data[9] = 0x10;
+        // 説明のタグをv2の形式に置き換える（同じ大きさに収める）
+        let text = b"Gamma v2\0";
+        let desc = HEADER_SIZE + 4;
+        let offset = read_u32(&data, desc + 4).unwrap() as usize;
+        let mut tag = b"desc\0\0\0\0".to_vec();
+        tag.extend_from_slice(&(text.len() as u32).to_be_bytes());
+        tag.extend_from_slice(text);
+        data[desc + 8..desc + 12].copy_from_slice(&(tag.len() as u32).to_be_bytes());
+        data[offset..offset + tag.len()].copy_from_slice(&tag);
+        data
+    }
+
+    #[test]
+    fn test_parse_matrix_trc_profiles() {
+        // v4: パラメトリックカーブとmluc
+        let p3 = IccProfile::parse(&IccProfile::display_p3().encode()).unwrap();
+        assert_eq!(p3.version(), (4, 3));
+        assert_eq!(p3.description(), "Display P3");
+        let expected = IccProfile::display_p3();
+        for (row, expected_row) in p3.to_pcs().iter().zip(expected.to_pcs()) {
+            for (value, expected) in row.iter().zip(expected_row) {
+                assert!((value - expected).abs() < 1e-4);
+            }
+        }
+        // 白（RGB=1）はPCSの白色点D50になる
+        let white = mul_vec(p3.to_pcs(), [1.0; 3]);
+        for (value, d50) in white.iter().zip(D50) {
+            assert!((value - d50).abs() < 1e-3, "{:?}", white);
+        }
+        assert!((p3.curve(0).eval(0.5) - 0.2140).abs() < 1e-3);
+
+        // v2: textDescriptionとガンマのみのcurv
+        let v2 = IccProfile::parse(&v2_profile(2.2)).unwrap();
+        assert_eq!(v2.version(), (2, 1));
+        assert_eq!(v2.description(), "Gamma v2");
+        assert_eq!(*v2.curve(1), ToneCurve::Gamma(2.19921875));
+
+        // 不正なプロファイル
+        let mut data = v2_profile(2.2);
+        data[36] = b'x';
+        assert_eq!(IccProfile::parse(&data), Err(IccError::InvalidSignature));
+        let mut data = v2_profile(2.2);
+        data[HEADER_SIZE + 4 + 12 * 2..HEADER_SIZE + 4 + 12 * 2 + 4].copy_from_slice(b"A2B0");
+        assert_eq!(IccProfile::parse(&data), Err(IccError::MissingTag("rXYZ".to_string())));
+        assert_eq!(IccProfile::parse(&data[..64]), Err(IccError::TooShort(64)));
+    }
+
+    #[test]
+    fn test_parse_description_rejects_bad_mluc() {
+        let mluc = |count: u32, record_size: u32| {
+            let mut tag = b"mluc\0\0\0\0".to_vec();
+            for value in [count, record_size] {
+                tag.extend_from_slice(&value.to_be_bytes());
+            }
+            tag.extend_from_slice(b"deDE");
+            for value in [2u32, 28] {
+                tag.extend_from_slice(&value.to_be_bytes());
+            }
+            tag.extend_from_slice(&[0, b'A']);
+            tag
+        };
+        assert_eq!(parse_description(&mluc(1, 12)).as_deref(), Some("A"));
+        // 件数がタグより多くても記録の範囲だけを探す
+        assert_eq!(parse_description(&mluc(u32::MAX, 12)).as_deref(), Some("A"));
+        // 記録の大きさが足りない場合は読まない
+        assert_eq!(parse_description(&mluc(1, 0)), None);
+        assert_eq!(parse_description(&mluc(1, 11)), None);
+        assert_eq!(parse_description(&mluc(u32::MAX, u32::MAX)), None);
+    }
+
+    #[test]
+    fn test_output_color_transforms() {
+        let gray = 0xff80_8080;
+        let red = 0xffff_0000;
+
+        // プロファイルのない出力ではsRGBをそのまま表示する
+        let plain = OutputColorTransform::default();
+        assert_eq!(plain.apply(ColorSpace::Srgb, gray), gray);
+
+        // sRGBと同じプロファイルではほぼ変わらない
+        let srgb = IccProfile::parse(&IccProfile::srgb().encode()).unwrap();
+        let transform = OutputColorTransform::new(Some(&srgb), None);
+        for color in [gray, red, 0xff12_3456] {
+            let (a, b) = (channels(transform.apply(ColorSpace::Srgb, color)), channels(color));
+            assert!(a.iter().zip(b).all(|(a, b)| (a - b).abs() <= 1), "{:x}", color);
+        }
+
+        // 広色域の出力ではsRGBの赤は彩度が下がって見える値になる
+        let wide = OutputColorTransform::new(Some(&IccProfile::display_p3()), None);
+        let [r, g, b] = channels(wide.apply(ColorSpace::Srgb, red));
+        assert!(r < 255 && g > 0 && b > 0, "{:?}", [r, g, b]);
+        // Display P3の赤はsRGBの出力では範囲外になり切り詰められる
+        assert_eq!(channels(plain.apply(ColorSpace::DisplayP3, red)), [255, 0, 0]);
+        let exact = plain.convert(ColorSpace::DisplayP3, [0.5, 0.5, 0.5]);
+        assert!(exact.iter().all(|value| (value - 0.5).abs() < 1e-3), "{:?}", exact);
+
+        // ガンマ1.0の出力では線形の値がそのまま出る
+        let linear = IccProfile::parse(&v2_profile(1.0)).unwrap();
+        let transform = OutputColorTransform::new(Some(&linear), None);
+        assert_eq!(channels(transform.apply(ColorSpace::Srgb, gray)), [55, 55, 55]);
+        // 乗算済みアルファは戻してから変換する
+        assert_eq!(transform.apply(ColorSpace::Srgb, 0x8040_4040), 0x801c_1c1c);
+    }
+
+    #[test]
+    fn test_night_light_warms_white_point() {
+        assert_eq!(NightLight::new(NightLight::NEUTRAL_TEMPERATURE).gains(), [1.0; 3]);
+
+        let [r, g, b] = NightLight::new(3400).gains();
+        assert_eq!(r, 1.0);
+        assert!(g < 1.0 && b < g, "{:?}", [r, g, b]);
+        let [_, g_low, b_low] = NightLight::new(2000).gains();
+        assert!(g_low < g && b_low < b);
+        assert_eq!(NightLight::new(100).temperature(), 1667);
+
+        // 夜間モードではsRGBの出力でも白が暖色になる
+        let transform = OutputColorTransform::new(None, Some(NightLight::new(3400)));
+        assert!(!transform.is_identity(ColorSpace::Srgb));
+        let [r, g, b] = channels(transform.apply(ColorSpace::Srgb, 0xffff_ffff));
+        assert!(r == 255 && g < 255 && b < g, "{:?}", [r, g, b]);
+    }
+}
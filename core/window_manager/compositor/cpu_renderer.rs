@@ -0,0 +1,344 @@
+// LumosDesktop CPUレンダラー
+// ウィンドウのバッファをCPUで合成する参照実装（GPUバックエンドの比較やテスト用）
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use super::color_management::ColorSpace;
+use super::damage::{OutputRepaint, Region};
+use super::wayland_compositor::{OutputDevice, PixelFormat, Window};
+
+/// 出力のフレームバッファ（乗算済みアルファの0xAARRGGBB）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Framebuffer {
+    width: u32,
+    height: u32,
+    pixels: Vec<u32>,
+}
+
+impl Framebuffer {
+    pub fn new(width: u32, height: u32, color: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![color; width as usize * height as usize],
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[u32] {
+        &self.pixels
+    }
+
+    pub fn pixels_mut(&mut self) -> &mut [u32] {
+        &mut self.pixels
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> Option<u32> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        Some(self.pixels[y as usize * self.width as usize + x as usize])
+    }
+
+    /// 指定したフォーマットのバイト列に変換
+    pub fn to_bytes(&self, format: PixelFormat) -> Vec<u8> {
+        let bpp = format.bytes_per_pixel() as usize;
+        let mut bytes = vec![0; self.pixels.len() * bpp];
+        for (pixel, out) in self.pixels.iter().zip(bytes.chunks_exact_mut(bpp)) {
+            format.encode(*pixel, out);
+        }
+        bytes
+    }
+
+    fn fill(&mut self, region: &Region, color: u32) {
+        for rect in region.rects() {
+            for y in rect.y.max(0)..rect.bottom().min(self.height as i32) {
+                let row = y as usize * self.width as usize;
+                let start = rect.x.max(0) as usize;
+                let end = (rect.right().max(0) as usize).min(self.width as usize);
+                if start < end {
+                    self.pixels[row + start..row + end].fill(color);
+                }
+            }
+        }
+    }
+}
+
+/// 乗算済みアルファの8ビット値の積（255で割って丸める）
+fn mul_channel(a: u32, b: u32) -> u32 {
+    let product = a * b + 128;
+    (product + (product >> 8)) >> 8
+}
+
+/// `src` に不透明度 `opacity`（0〜255）を掛けて `dst` の上に重ねる（乗算済みアルファのsource-over）
+pub fn blend(dst: u32, src: u32, opacity: u32) -> u32 {
+    let src = if opacity >= 255 {
+        src
+    } else {
+        (0..4).fold(0, |acc, i| acc | (mul_channel((src >> (i * 8)) & 0xff, opacity) << (i * 8)))
+    };
+    let inverse_alpha = 255 - (src >> 24);
+    if inverse_alpha == 0 {
+        return src;
+    }
+    (0..4).fold(0, |acc, i| {
+        let shift = i * 8;
+        let channel = ((src >> shift) & 0xff) + mul_channel((dst >> shift) & 0xff, inverse_alpha);
+        acc | (channel.min(255) << shift)
+    })
+}
+
+/// ウィンドウのバッファから論理座標の点のピクセルを取得（最近傍）
+pub(super) fn sample(window: &Window, x: f64, y: f64) -> Option<u32> {
+    let buffer = window.buffer()?;
+    let geometry = window.geometry();
+    let (sx, sy) = (x - geometry.x as f64, y - geometry.y as f64);
+    if sx < 0.0 || sy < 0.0 || sx >= geometry.width as f64 || sy >= geometry.height as f64 {
+        return None;
+    }
+    // バッファとウィンドウの大きさが異なる場合（バッファスケールなど）は拡大縮小する
+    let bx = (sx * buffer.width() as f64 / geometry.width as f64) as u32;
+    let by = (sy * buffer.height() as f64 / geometry.height as f64) as u32;
+    buffer.pixel(bx, by)
+}
+
+/// CPUでウィンドウを合成するレンダラー
+///
+/// 出力ごとにフレームバッファを保持し、コンポジターが決めた再描画領域だけを描き直します。
+/// フレームバッファは1枚なので、バッファの経過フレーム数は常に1です。
+#[derive(Debug)]
+pub struct CpuRenderer {
+    framebuffers: HashMap<u32, Framebuffer>,
+    background: u32,
+}
+
+impl CpuRenderer {
+    pub fn new() -> Self {
+        Self {
+            framebuffers: HashMap::new(),
+            background: 0xff00_0000,
+        }
+    }
+
+    /// ウィンドウに覆われていない領域の色（0xAARRGGBB）
+    pub fn set_background(&mut self, color: u32) {
+        self.background = color;
+    }
+
+    pub fn background(&self) -> u32 {
+        self.background
+    }
+
+    pub fn framebuffer(&self, output_id: u32) -> Option<&Framebuffer> {
+        self.framebuffers.get(&output_id)
+    }
+
+    pub fn remove_output(&mut self, output_id: u32) {
+        self.framebuffers.remove(&output_id);
+    }
+
+    /// 再描画領域に背景とウィンドウを奥から順に合成
+    ///
+    /// ウィンドウの色は重ねる前に出力のカラープロファイルと夜間モードに合わせて変換します。
+    pub fn render(&mut self, output: &OutputDevice, repaint: &OutputRepaint, windows: &HashMap<u64, Rc<RefCell<Window>>>) {
+        let (width, height) = output.size();
+        let transform = output.color_transform();
+        let background = transform.apply(ColorSpace::Srgb, self.background);
+        let framebuffer = self.framebuffers.entry(output.id())
+            .or_insert_with(|| Framebuffer::new(width, height, background));
+        if framebuffer.width != width || framebuffer.height != height {
+            *framebuffer = Framebuffer::new(width, height, background);
+        }
+
+        framebuffer.fill(&repaint.background, background);
+
+        for draw in &repaint.draws {
+            let Some(window) = windows.get(&draw.window_id) else { continue };
+            let window = window.borrow();
+            let opacity = (window.opacity() * 255.0).round() as u32;
+            if opacity == 0 {
+                continue;
+            }
+            let color_space = window.color_space();
+
+            for rect in draw.clip.rects() {
+                for y in rect.y.max(0)..rect.bottom().min(height as i32) {
+                    for x in rect.x.max(0)..rect.right().min(width as i32) {
+                        // ピクセルの中心を論理座標に戻してウィンドウのバッファから読む
+                        let (lx, ly) = output.output_to_logical(x as f32 + 0.5, y as f32 + 0.5);
+                        let Some(src) = sample(&window, lx, ly) else { continue };
+                        let src = transform.apply(color_space, src);
+                        let index = y as usize * width as usize + x as usize;
+                        framebuffer.pixels[index] = blend(framebuffer.pixels[index], src, opacity);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Default for CpuRenderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use super::super::color_management::{IccProfile, NightLight, ToneCurve};
+    use super::super::wayland_compositor::{Buffer, ColorProfile, LumosCompositor, Rectangle, TransformMatrix};
+
+    fn solid_buffer(width: u32, height: u32, format: PixelFormat, argb: u32) -> Arc<Buffer> {
+        let bpp = format.bytes_per_pixel() as usize;
+        let mut data = vec![0; width as usize * height as usize * bpp];
+        for pixel in data.chunks_exact_mut(bpp) {
+            format.encode(argb, pixel);
+        }
+        Arc::new(Buffer::new(width, height, format, data))
+    }
+
+    fn window(id: u64, geometry: Rectangle, buffer: Arc<Buffer>) -> Window {
+        let mut window = Window::new(id, "test", geometry);
+        window.attach_buffer(buffer);
+        window
+    }
+
+    #[test]
+    fn test_pixel_format_conversion() {
+        let argb = 0x80_40_20_10;
+        for format in [PixelFormat::ARGB8888, PixelFormat::RGBA8888, PixelFormat::ABGR8888] {
+            let mut bytes = [0; 4];
+            format.encode(argb, &mut bytes);
+            assert_eq!(format.decode(&bytes), argb, "{:?}", format);
+        }
+        for format in [PixelFormat::XRGB8888, PixelFormat::RGBX8888, PixelFormat::XBGR8888] {
+            let mut bytes = [0; 4];
+            format.encode(argb, &mut bytes);
+            assert_eq!(format.decode(&bytes), 0xff_40_20_10, "{:?}", format);
+        }
+        assert_eq!(PixelFormat::ARGB8888.decode(&[0x10, 0x20, 0x40, 0x80]), argb);
+        assert_eq!(PixelFormat::ABGR8888.decode(&[0x40, 0x20, 0x10, 0x80]), argb);
+        // RGB565は各チャンネルを8ビットに拡張
+        assert_eq!(PixelFormat::RGB565.decode(&0xf800u16.to_le_bytes()), 0xff_ff_00_00);
+        assert_eq!(PixelFormat::RGB565.decode(&0x07e0u16.to_le_bytes()), 0xff_00_ff_00);
+    }
+
+    #[test]
+    fn test_blend() {
+        assert_eq!(blend(0xff_00_00_ff, 0xff_ff_00_00, 255), 0xff_ff_00_00);
+        assert_eq!(blend(0xff_00_00_ff, 0x00_00_00_00, 255), 0xff_00_00_ff);
+        // 乗算済みの半透明の白を青の上に重ねる
+        assert_eq!(blend(0xff_00_00_ff, 0x80_80_80_80, 255), 0xff_80_80_ff);
+        // 不透明な赤を半分の不透明度で重ねる
+        assert_eq!(blend(0xff_00_00_00, 0xff_ff_00_00, 128), 0xff_80_00_00);
+    }
+
+    #[test]
+    fn test_composite_windows_in_z_order() {
+        let mut compositor = LumosCompositor::new();
+        compositor.enable_cpu_rendering();
+        compositor.add_output(OutputDevice::new(1, "HEADLESS-1", 8, 4, 60.0));
+        compositor.add_window(window(1, Rectangle::new(0, 0, 4, 4), solid_buffer(4, 4, PixelFormat::XRGB8888, 0xff_ff_00_00)));
+        let mut overlay = window(2, Rectangle::new(2, 0, 4, 2), solid_buffer(2, 1, PixelFormat::RGB565, 0xff_00_00_ff));
+        overlay.set_opacity(0.5);
+        compositor.add_window(overlay);
+        // 出力からはみ出したウィンドウは切り取られる
+        compositor.add_window(window(3, Rectangle::new(6, 3, 4, 4), solid_buffer(4, 4, PixelFormat::ABGR8888, 0xff_00_ff_00)));
+        compositor.render_frame();
+
+        let framebuffer = compositor.framebuffer(1).unwrap();
+        assert_eq!(framebuffer.pixel(0, 0), Some(0xff_ff_00_00));
+        assert_eq!(framebuffer.pixel(3, 1), Some(0xff_7f_00_80));
+        assert_eq!(framebuffer.pixel(5, 0), Some(0xff_00_00_80));
+        assert_eq!(framebuffer.pixel(3, 3), Some(0xff_ff_00_00));
+        assert_eq!(framebuffer.pixel(7, 3), Some(0xff_00_ff_00));
+        assert_eq!(framebuffer.pixel(7, 0), Some(0xff_00_00_00));
+    }
+
+    #[test]
+    fn test_rotated_and_scaled_output() {
+        let mut output = OutputDevice::new(1, "DSI-1", 8, 4, 60.0);
+        output.set_scale_factor(2.0);
+        output.set_transform(TransformMatrix::rotate_90_degrees());
+        let expected = output.damage_to_output(&Rectangle::new(0, 0, 1, 1)).unwrap();
+
+        let mut compositor = LumosCompositor::new();
+        compositor.enable_cpu_rendering();
+        compositor.add_output(output);
+        compositor.add_window(window(1, Rectangle::new(0, 0, 1, 1), solid_buffer(1, 1, PixelFormat::ARGB8888, 0xff_ff_ff_ff)));
+        compositor.render_frame();
+
+        // ダメージの変換と同じ位置に描画される
+        let framebuffer = compositor.framebuffer(1).unwrap();
+        let lit: Vec<(u32, u32)> = (0..4).flat_map(|y| (0..8).map(move |x| (x, y)))
+            .filter(|&(x, y)| framebuffer.pixel(x, y) == Some(0xff_ff_ff_ff))
+            .collect();
+        assert_eq!(lit.len(), 4);
+        assert!(lit.iter().all(|&(x, y)| expected.contains(x as i32, y as i32)));
+    }
+
+    #[test]
+    fn test_partial_redraw_matches_full_redraw() {
+        let build = |damage_tracking: bool| {
+            let mut compositor = LumosCompositor::new();
+            compositor.enable_cpu_rendering();
+            compositor.set_damage_tracking(damage_tracking);
+            compositor.add_output(OutputDevice::new(1, "HEADLESS-1", 16, 16, 60.0));
+            compositor.add_window(window(1, Rectangle::new(0, 0, 16, 16), solid_buffer(16, 16, PixelFormat::XRGB8888, 0xff_20_20_20)));
+            compositor.add_window(window(2, Rectangle::new(4, 4, 6, 6), solid_buffer(6, 6, PixelFormat::ARGB8888, 0x80_00_80_00)));
+            compositor.render_frame();
+
+            compositor.get_window(2).unwrap().borrow_mut().set_geometry(Rectangle::new(8, 2, 6, 6));
+            compositor.damage_window(1, Rectangle::new(0, 0, 3, 3));
+            compositor.render_frame();
+            compositor
+        };
+
+        let partial = build(true);
+        let full = build(false);
+        assert!(partial.last_repaint_stats()[0].repainted_area < 16 * 16);
+        assert_eq!(partial.framebuffer(1), full.framebuffer(1));
+    }
+
+    #[test]
+    fn test_color_managed_output() {
+        let mut compositor = LumosCompositor::new();
+        compositor.enable_cpu_rendering();
+        compositor.add_output(OutputDevice::new(1, "DP-1", 4, 1, 60.0));
+        compositor.add_output(OutputDevice::new(2, "DP-2", 4, 1, 60.0));
+        compositor.add_window(window(1, Rectangle::new(0, 0, 2, 1), solid_buffer(2, 1, PixelFormat::XRGB8888, 0xff_80_80_80)));
+        let mut p3 = window(2, Rectangle::new(2, 0, 2, 1), solid_buffer(2, 1, PixelFormat::XRGB8888, 0xff_ff_00_00));
+        p3.set_color_space(ColorSpace::DisplayP3);
+        compositor.add_window(p3);
+
+        // 線形のトーンカーブを持つプロファイルの出力ではsRGBの灰色が線形の値になる
+        let srgb = IccProfile::srgb();
+        let curves = [0, 1, 2].map(|_| ToneCurve::Gamma(1.0));
+        let linear = IccProfile::new("Linear", *srgb.to_pcs(), curves).unwrap();
+        let profile = ColorProfile::from_icc(linear.encode()).unwrap();
+        assert!(compositor.set_output_color_profile(1, Some(profile)));
+        compositor.render_frame();
+        assert_eq!(compositor.framebuffer(1).unwrap().pixel(0, 0), Some(0xff_37_37_37));
+        assert_eq!(compositor.framebuffer(2).unwrap().pixel(0, 0), Some(0xff_80_80_80));
+        // Display P3の赤はsRGBの出力の範囲に切り詰められる
+        assert_eq!(compositor.framebuffer(2).unwrap().pixel(3, 0), Some(0xff_ff_00_00));
+
+        // 夜間モードはすべての出力を描き直して暖色にする
+        compositor.set_night_light(Some(NightLight::new(3400)));
+        compositor.render_frame();
+        let warm = compositor.framebuffer(2).unwrap().pixel(0, 0).unwrap();
+        let [r, g, b] = [16, 8, 0].map(|shift| (warm >> shift) & 0xff);
+        assert!(r == 0x80 && g < r && b < g, "{:08x}", warm);
+    }
+}
@@ -0,0 +1,333 @@
+// LumosDesktop ダメージトラッキング
+// 更新された領域を出力ごとに蓄積し、再描画する範囲を決定
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use super::wayland_compositor::Rectangle;
+
+/// 履歴として保持するフレーム数（これより古いバッファは全体を再描画）
+pub const MAX_BUFFER_AGE: u32 = 4;
+
+/// 再描画領域がこれより多くの矩形に分かれた場合は外接矩形にまとめる
+const MAX_REPAINT_RECTS: usize = 32;
+
+/// 重なりのない矩形の集合で表した領域
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Region {
+    rects: Vec<Rectangle>,
+}
+
+/// `a` から `b` を除いた部分を最大4つの矩形で返す
+fn subtract_rects(a: &Rectangle, b: &Rectangle) -> Vec<Rectangle> {
+    let Some(overlap) = a.intersect(b) else {
+        return vec![*a];
+    };
+
+    let mut pieces = Vec::with_capacity(4);
+    // 上下の帯
+    if overlap.y > a.y {
+        pieces.push(Rectangle::new(a.x, a.y, a.width, (overlap.y - a.y) as u32));
+    }
+    if overlap.bottom() < a.bottom() {
+        pieces.push(Rectangle::new(a.x, overlap.bottom(), a.width, (a.bottom() - overlap.bottom()) as u32));
+    }
+    // 重なりと同じ高さの左右
+    if overlap.x > a.x {
+        pieces.push(Rectangle::new(a.x, overlap.y, (overlap.x - a.x) as u32, overlap.height));
+    }
+    if overlap.right() < a.right() {
+        pieces.push(Rectangle::new(overlap.right(), overlap.y, (a.right() - overlap.right()) as u32, overlap.height));
+    }
+    pieces
+}
+
+impl Region {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_rect(rect: Rectangle) -> Self {
+        let mut region = Self::new();
+        region.add_rect(rect);
+        region
+    }
+
+    pub fn rects(&self) -> &[Rectangle] {
+        &self.rects
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rects.is_empty()
+    }
+
+    /// 面積（ピクセル数）
+    pub fn area(&self) -> u64 {
+        self.rects.iter().map(Rectangle::area).sum()
+    }
+
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        self.rects.iter().any(|rect| rect.contains(x, y))
+    }
+
+    /// すべての矩形を囲む矩形
+    pub fn extents(&self) -> Option<Rectangle> {
+        self.rects.iter().copied().reduce(|a, b| a.union(&b))
+    }
+
+    /// 矩形を加える（既存の矩形と重なる部分は除いて追加）
+    pub fn add_rect(&mut self, rect: Rectangle) {
+        if rect.is_empty() {
+            return;
+        }
+        let mut pieces = vec![rect];
+        for existing in &self.rects {
+            pieces = pieces.iter().flat_map(|piece| subtract_rects(piece, existing)).collect();
+            if pieces.is_empty() {
+                return;
+            }
+        }
+        self.rects.extend(pieces);
+    }
+
+    pub fn union(&mut self, other: &Region) {
+        for rect in &other.rects {
+            self.add_rect(*rect);
+        }
+    }
+
+    pub fn subtract_rect(&mut self, rect: &Rectangle) {
+        if rect.is_empty() {
+            return;
+        }
+        self.rects = self.rects.iter().flat_map(|existing| subtract_rects(existing, rect)).collect();
+    }
+
+    pub fn subtract(&mut self, other: &Region) {
+        for rect in &other.rects {
+            self.subtract_rect(rect);
+        }
+    }
+
+    pub fn intersect_rect(&self, rect: &Rectangle) -> Region {
+        Region {
+            rects: self.rects.iter().filter_map(|existing| existing.intersect(rect)).collect(),
+        }
+    }
+
+    pub fn intersect(&self, other: &Region) -> Region {
+        // どちらも重なりがないため、交差した矩形同士も重ならない
+        Region {
+            rects: self.rects.iter()
+                .flat_map(|a| other.rects.iter().filter_map(move |b| a.intersect(b)))
+                .collect(),
+        }
+    }
+
+    pub fn translate(&mut self, dx: i32, dy: i32) {
+        for rect in &mut self.rects {
+            *rect = rect.translated(dx, dy);
+        }
+    }
+
+    /// 矩形が多すぎる場合は外接矩形にまとめる（描画のオーバーヘッドを抑えるため）
+    pub fn simplify(&mut self, max_rects: usize) {
+        if self.rects.len() > max_rects {
+            self.rects = self.extents().into_iter().collect();
+        }
+    }
+}
+
+/// 出力ごとのダメージ
+///
+/// まだ描画していないダメージと、直近のフレームで描画したダメージの履歴を保持します。
+/// バックバッファの内容が `buffer_age` フレーム前のものである場合、その間に描画した
+/// ダメージも再描画する必要があります。
+#[derive(Debug, Clone)]
+pub struct OutputDamage {
+    /// 出力のバッファ座標での未描画のダメージ
+    pending: Region,
+    /// 描画済みのダメージ（先頭が直前のフレーム）
+    history: VecDeque<Region>,
+    /// 次に描画するバッファの経過フレーム数（0=内容が不明）
+    buffer_age: u32,
+    /// 出力全体（バッファ座標）
+    bounds: Rectangle,
+}
+
+impl OutputDamage {
+    /// 最初のフレームは全体を描画する
+    pub fn new(width: u32, height: u32, buffer_age: u32) -> Self {
+        let bounds = Rectangle::new(0, 0, width, height);
+        Self {
+            pending: Region::from_rect(bounds),
+            history: VecDeque::new(),
+            buffer_age,
+            bounds,
+        }
+    }
+
+    pub fn add(&mut self, region: &Region) {
+        self.pending.union(&region.intersect_rect(&self.bounds));
+    }
+
+    pub fn add_rect(&mut self, rect: Rectangle) {
+        if let Some(rect) = rect.intersect(&self.bounds) {
+            self.pending.add_rect(rect);
+        }
+    }
+
+    /// 出力全体をダメージにする（モード変更時など）
+    pub fn damage_all(&mut self) {
+        self.pending = Region::from_rect(self.bounds);
+    }
+
+    /// 出力のサイズを変更し、履歴を破棄する
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.bounds = Rectangle::new(0, 0, width, height);
+        self.history.clear();
+        self.damage_all();
+    }
+
+    pub fn has_damage(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    pub fn pending(&self) -> &Region {
+        &self.pending
+    }
+
+    pub fn set_buffer_age(&mut self, age: u32) {
+        self.buffer_age = age;
+    }
+
+    pub fn buffer_age(&self) -> u32 {
+        self.buffer_age
+    }
+
+    pub fn bounds(&self) -> Rectangle {
+        self.bounds
+    }
+
+    /// 次のフレームで再描画する領域
+    ///
+    /// バッファの内容が古いほど、その間のフレームのダメージも含めます。
+    /// 履歴が足りない場合は出力全体を返します。
+    pub fn repaint_region(&self) -> Region {
+        let age = self.buffer_age as usize;
+        if age == 0 || age - 1 > self.history.len() {
+            return Region::from_rect(self.bounds);
+        }
+        let mut region = self.pending.clone();
+        for previous in self.history.iter().take(age - 1) {
+            region.union(previous);
+        }
+        region.simplify(MAX_REPAINT_RECTS);
+        region
+    }
+
+    /// 描画が完了したダメージを履歴に移す
+    pub fn commit(&mut self) {
+        let pending = std::mem::take(&mut self.pending);
+        self.history.push_front(pending);
+        self.history.truncate(MAX_BUFFER_AGE as usize);
+    }
+}
+
+/// ウィンドウの描画指示
+#[derive(Debug, Clone)]
+pub struct WindowDraw {
+    pub window_id: u64,
+    /// 描画する範囲（出力のバッファ座標）
+    pub clip: Region,
+}
+
+/// 1つの出力で1フレームに再描画する内容
+#[derive(Debug, Clone)]
+pub struct OutputRepaint {
+    pub output_id: u32,
+    /// 再描画する領域（出力のバッファ座標）
+    pub region: Region,
+    /// 奥から手前の順のウィンドウ
+    pub draws: Vec<WindowDraw>,
+    /// どのウィンドウにも覆われず、背景で塗りつぶす領域
+    pub background: Region,
+}
+
+/// 出力ごとの再描画の統計
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RepaintStats {
+    pub output_id: u32,
+    /// 再描画したピクセル数
+    pub repainted_area: u64,
+    /// 出力全体のピクセル数
+    pub output_area: u64,
+    /// 再描画領域の矩形の数
+    pub rects: usize,
+    pub windows_drawn: usize,
+    /// 再描画領域と重ならないか、不透明なウィンドウに隠れて描画しなかったウィンドウの数
+    pub windows_culled: usize,
+    /// 合成にかかった時間
+    pub render_time: Duration,
+}
+
+impl RepaintStats {
+    /// 出力全体に対する再描画した面積の割合
+    pub fn repainted_ratio(&self) -> f64 {
+        if self.output_area == 0 {
+            return 0.0;
+        }
+        self.repainted_area as f64 / self.output_area as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_region_operations() {
+        let mut region = Region::from_rect(Rectangle::new(0, 0, 10, 10));
+        region.add_rect(Rectangle::new(5, 5, 10, 10));
+        assert_eq!(region.area(), 175);
+        assert_eq!(region.extents(), Some(Rectangle::new(0, 0, 15, 15)));
+
+        region.subtract_rect(&Rectangle::new(2, 2, 6, 6));
+        assert_eq!(region.area(), 175 - 36);
+        assert!(!region.contains(4, 4));
+        assert!(region.contains(12, 12));
+
+        let clipped = region.intersect_rect(&Rectangle::new(10, 0, 10, 20));
+        assert_eq!(clipped.area(), 50);
+        region.translate(100, 0);
+        assert!(region.contains(112, 12));
+    }
+
+    #[test]
+    fn test_repaint_region_uses_buffer_age() {
+        let mut damage = OutputDamage::new(100, 100, 2);
+        assert_eq!(damage.repaint_region().area(), 10_000);
+        damage.commit();
+        assert!(!damage.has_damage());
+
+        // 2フレーム前のバッファには直前のフレームのダメージも描画する
+        damage.add_rect(Rectangle::new(0, 0, 10, 10));
+        damage.commit();
+        damage.add_rect(Rectangle::new(50, 50, 10, 10));
+        assert_eq!(damage.repaint_region().area(), 200);
+
+        damage.set_buffer_age(1);
+        assert_eq!(damage.repaint_region().area(), 100);
+
+        // 内容が不明なバッファや履歴より古いバッファは全体を描画
+        damage.set_buffer_age(0);
+        assert_eq!(damage.repaint_region().area(), 10_000);
+        damage.set_buffer_age(MAX_BUFFER_AGE + 1);
+        assert_eq!(damage.repaint_region().area(), 10_000);
+
+        // 出力の外のダメージは無視する
+        damage.commit();
+        damage.add_rect(Rectangle::new(200, 200, 10, 10));
+        assert!(!damage.has_damage());
+    }
+}
@@ -0,0 +1,473 @@
+// LumosDesktop フレームスケジューラ
+// 合成にかかる時間を予測し、垂直同期の直前に合成を始めて入力の遅延を抑える
+
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+
+/// 予測に使う合成時間の履歴の数
+const HISTORY_LEN: usize = 64;
+
+/// 予測に使うパーセンタイル
+const PREDICTION_PERCENTILE: usize = 95;
+
+/// 予測した合成時間に加える余裕（合成の開始の遅れやページフリップの準備）
+const SAFETY_MARGIN: Duration = Duration::from_millis(1);
+
+/// 垂直同期に間に合わなかったときに予測へ加える量（間に合ったフレームごとに減衰）
+const MISS_PENALTY: Duration = Duration::from_millis(2);
+
+/// 可変リフレッシュレート（VRR）の範囲
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VrrRange {
+    pub min_hz: f64,
+    pub max_hz: f64,
+}
+
+impl VrrRange {
+    pub fn new(min_hz: f64, max_hz: f64) -> Self {
+        Self { min_hz, max_hz }
+    }
+
+    /// 垂直同期の最短間隔
+    pub fn min_interval(&self) -> Duration {
+        Duration::from_secs_f64(1.0 / self.max_hz.max(1.0))
+    }
+
+    /// 垂直同期の最長間隔（これを過ぎると前のフレームが繰り返し表示される）
+    pub fn max_interval(&self) -> Duration {
+        Duration::from_secs_f64(1.0 / self.min_hz.max(1.0))
+    }
+}
+
+/// フレームの表示のフィードバック（presentation-time プロトコルに相当）
+///
+/// 時刻はバックエンドのモノトニック時計（ヘッドレスでは仮想時計）での値です。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PresentationFeedback {
+    pub output_id: u32,
+    /// 表示した垂直同期の通し番号
+    pub sequence: u64,
+    /// 表示した時刻
+    pub presented_at: Duration,
+    /// 次の垂直同期までの間隔（VRRや垂直同期なしの場合はゼロ）
+    pub refresh: Duration,
+    /// 合成にかかった時間
+    pub render_time: Duration,
+    /// 合成を始めてから表示されるまでの時間
+    pub latency: Duration,
+    /// 垂直同期に合わせて表示したかどうか（偽の場合はティアリングの可能性がある）
+    pub vsync: bool,
+}
+
+/// 垂直同期の処理結果
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct VblankResult {
+    /// この垂直同期で表示したフレーム
+    pub presented: Option<PresentationFeedback>,
+    /// 間に合わなかったフレームの目標の通し番号
+    pub missed: Option<u64>,
+}
+
+/// 合成時間の履歴
+#[derive(Debug, Clone, Default)]
+pub struct RenderTimeHistory {
+    samples: VecDeque<Duration>,
+    penalty: Duration,
+}
+
+impl RenderTimeHistory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, render_time: Duration) {
+        if self.samples.len() == HISTORY_LEN {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(render_time);
+    }
+
+    /// 間に合わなかったフレームの分だけ予測を長くする
+    pub fn record_miss(&mut self) {
+        self.penalty += MISS_PENALTY;
+    }
+
+    /// 間に合ったフレームごとに追加分を減らす
+    fn decay(&mut self) {
+        self.penalty = self.penalty * 7 / 8;
+    }
+
+    /// 次のフレームの合成時間の予測（履歴がない場合は `limit`）
+    ///
+    /// 外れ値に引きずられないよう最大値ではなくパーセンタイルを使い、余裕を加えます。
+    pub fn predict(&self, limit: Duration) -> Duration {
+        if self.samples.is_empty() {
+            return limit;
+        }
+        let mut sorted: Vec<Duration> = self.samples.iter().copied().collect();
+        sorted.sort_unstable();
+        let percentile = sorted[(sorted.len() - 1) * PREDICTION_PERCENTILE / 100];
+        (percentile + SAFETY_MARGIN + self.penalty).min(limit)
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+}
+
+/// 出力のフレームの状態
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FrameState {
+    Idle,
+    /// `start` に合成を始め、`target_sequence` の垂直同期で表示する予定
+    Scheduled { start: Duration, target: Duration, target_sequence: u64 },
+    /// 合成が終わり、垂直同期を待っている
+    Rendered { started: Duration, ready_at: Duration, render_time: Duration, target_sequence: u64 },
+}
+
+/// 出力ごとのタイミング
+#[derive(Debug, Clone)]
+struct OutputTiming {
+    interval: Duration,
+    vrr_range: Option<VrrRange>,
+    last_vblank: Option<Duration>,
+    sequence: u64,
+    history: RenderTimeHistory,
+    state: FrameState,
+}
+
+/// フレームスケジューラ
+///
+/// 出力ごとに合成時間の履歴から次のフレームの合成時間を予測し、垂直同期に間に合う
+/// 範囲でできるだけ遅く合成を始めます。合成を遅らせるほど、その間に届いた入力が
+/// フレームに反映されます。
+///
+/// バックエンドは次の順に呼び出します。
+/// 1. `request_frame` でダメージのある出力のフレームを予約する
+/// 2. `due_outputs` の出力を合成し、`frame_rendered` で合成時間を報告する
+/// 3. 垂直同期ごとに `vblank` を呼び、表示のフィードバックを受け取る
+#[derive(Debug, Clone)]
+pub struct FrameScheduler {
+    vsync: bool,
+    vrr: bool,
+    max_render_time: Duration,
+    outputs: HashMap<u32, OutputTiming>,
+}
+
+impl FrameScheduler {
+    pub fn new(vsync: bool, vrr: bool, max_render_time: Duration) -> Self {
+        Self {
+            vsync,
+            vrr,
+            max_render_time,
+            outputs: HashMap::new(),
+        }
+    }
+
+    pub fn vsync(&self) -> bool {
+        self.vsync
+    }
+
+    pub fn set_vsync(&mut self, enabled: bool) {
+        self.vsync = enabled;
+    }
+
+    pub fn vrr(&self) -> bool {
+        self.vrr
+    }
+
+    pub fn set_vrr(&mut self, enabled: bool) {
+        self.vrr = enabled;
+    }
+
+    pub fn set_max_render_time(&mut self, max_render_time: Duration) {
+        self.max_render_time = max_render_time;
+    }
+
+    pub fn add_output(&mut self, id: u32, refresh_rate: f64, vrr_range: Option<VrrRange>) {
+        self.outputs.insert(id, OutputTiming {
+            interval: Duration::from_secs_f64(1.0 / refresh_rate.max(1.0)),
+            vrr_range,
+            last_vblank: None,
+            sequence: 0,
+            history: RenderTimeHistory::new(),
+            state: FrameState::Idle,
+        });
+    }
+
+    pub fn remove_output(&mut self, id: u32) {
+        self.outputs.remove(&id);
+    }
+
+    pub fn set_refresh_rate(&mut self, id: u32, refresh_rate: f64) {
+        if let Some(output) = self.outputs.get_mut(&id) {
+            output.interval = Duration::from_secs_f64(1.0 / refresh_rate.max(1.0));
+        }
+    }
+
+    pub fn set_vrr_range(&mut self, id: u32, vrr_range: Option<VrrRange>) {
+        if let Some(output) = self.outputs.get_mut(&id) {
+            output.vrr_range = vrr_range;
+        }
+    }
+
+    /// 出力で実際に使うVRRの範囲（VRRが無効なら `None`）
+    pub fn active_vrr_range(&self, id: u32) -> Option<VrrRange> {
+        self.outputs.get(&id).and_then(|output| output.vrr_range).filter(|_| self.vrr && self.vsync)
+    }
+
+    /// 予測の上限（未設定ならリフレッシュ間隔）
+    fn render_time_limit(&self, output: &OutputTiming) -> Duration {
+        if self.max_render_time.is_zero() {
+            output.interval
+        } else {
+            self.max_render_time
+        }
+    }
+
+    /// 出力の次のフレームの合成時間の予測
+    pub fn predicted_render_time(&self, id: u32) -> Option<Duration> {
+        let output = self.outputs.get(&id)?;
+        Some(output.history.predict(self.render_time_limit(output)))
+    }
+
+    /// フレームを予約し、合成を始める時刻を返す（すでに予約済みの場合はその時刻）
+    pub fn request_frame(&mut self, id: u32, now: Duration) -> Option<Duration> {
+        let vrr_range = self.active_vrr_range(id);
+        let vsync = self.vsync;
+        let limit = self.render_time_limit(self.outputs.get(&id)?);
+        let output = self.outputs.get_mut(&id)?;
+        match output.state {
+            FrameState::Scheduled { start, .. } => return Some(start),
+            FrameState::Rendered { .. } => return None,
+            FrameState::Idle => {}
+        }
+
+        let predicted = output.history.predict(limit);
+        let earliest = now + predicted;
+        let (target, target_sequence) = match (vsync, vrr_range, output.last_vblank) {
+            // 垂直同期がない場合や位相が分からない場合はすぐに合成する
+            (false, _, _) | (true, _, None) => (earliest, output.sequence + 1),
+            // VRRでは最短間隔を過ぎていれば、合成が終わりしだい表示できる
+            (true, Some(range), Some(last)) => (earliest.max(last + range.min_interval()), output.sequence + 1),
+            (true, None, Some(last)) => {
+                let interval = output.interval.as_nanos().max(1);
+                let frames = if earliest <= last {
+                    1
+                } else {
+                    (earliest - last).as_nanos().div_ceil(interval).max(1)
+                };
+                (last + output.interval * frames as u32, output.sequence + frames as u64)
+            }
+        };
+        let start = target.saturating_sub(predicted).max(now);
+        output.state = FrameState::Scheduled { start, target, target_sequence };
+        Some(start)
+    }
+
+    /// 予約を取り消す（ダメージがなくなった場合など）
+    pub fn cancel(&mut self, id: u32) {
+        if let Some(output) = self.outputs.get_mut(&id) {
+            if matches!(output.state, FrameState::Scheduled { .. }) {
+                output.state = FrameState::Idle;
+            }
+        }
+    }
+
+    /// 合成を始める時刻になった出力
+    pub fn due_outputs(&self, now: Duration) -> Vec<u32> {
+        let mut due: Vec<u32> = self.outputs.iter()
+            .filter(|(_, output)| matches!(output.state, FrameState::Scheduled { start, .. } if start <= now))
+            .map(|(id, _)| *id)
+            .collect();
+        due.sort_unstable();
+        due
+    }
+
+    /// 次に合成を始める時刻
+    pub fn next_wakeup(&self) -> Option<Duration> {
+        self.outputs.values()
+            .filter_map(|output| match output.state {
+                FrameState::Scheduled { start, .. } => Some(start),
+                _ => None,
+            })
+            .min()
+    }
+
+    /// 予約したフレームの表示予定時刻
+    pub fn target_time(&self, id: u32) -> Option<Duration> {
+        match self.outputs.get(&id)?.state {
+            FrameState::Scheduled { target, .. } => Some(target),
+            _ => None,
+        }
+    }
+
+    /// 合成が終わって垂直同期を待っているフレームの、合成が終わった時刻
+    pub fn ready_time(&self, id: u32) -> Option<Duration> {
+        match self.outputs.get(&id)?.state {
+            FrameState::Rendered { ready_at, .. } => Some(ready_at),
+            _ => None,
+        }
+    }
+
+    /// 合成が終わったことを報告する
+    pub fn frame_rendered(&mut self, id: u32, started: Duration, render_time: Duration) {
+        let Some(output) = self.outputs.get_mut(&id) else { return };
+        output.history.record(render_time);
+        let target_sequence = match output.state {
+            FrameState::Scheduled { target_sequence, .. } => target_sequence,
+            // 予約せずに合成した場合は次の垂直同期に表示する
+            _ => output.sequence + 1,
+        };
+        output.state = FrameState::Rendered {
+            started,
+            ready_at: started + render_time,
+            render_time,
+            target_sequence,
+        };
+    }
+
+    /// 最後の垂直同期の時刻と通し番号
+    pub fn last_vblank(&self, id: u32) -> Option<(Duration, u64)> {
+        let output = self.outputs.get(&id)?;
+        output.last_vblank.map(|time| (time, output.sequence))
+    }
+
+    /// 垂直同期（垂直同期がない場合はバッファの切り替え）を処理する
+    pub fn vblank(&mut self, id: u32, time: Duration) -> VblankResult {
+        let vsync = self.vsync;
+        let vrr = self.active_vrr_range(id).is_some();
+        let Some(output) = self.outputs.get_mut(&id) else { return VblankResult::default() };
+        output.sequence += 1;
+        output.last_vblank = Some(time);
+
+        let mut result = VblankResult::default();
+        match output.state {
+            FrameState::Rendered { started, ready_at, render_time, target_sequence } if ready_at <= time => {
+                if output.sequence > target_sequence {
+                    result.missed = Some(target_sequence);
+                    output.history.record_miss();
+                } else {
+                    output.history.decay();
+                }
+                result.presented = Some(PresentationFeedback {
+                    output_id: id,
+                    sequence: output.sequence,
+                    presented_at: time,
+                    refresh: if vsync && !vrr { output.interval } else { Duration::ZERO },
+                    render_time,
+                    latency: time.saturating_sub(started),
+                    vsync,
+                });
+                output.state = FrameState::Idle;
+            }
+            FrameState::Scheduled { target_sequence, .. } if output.sequence >= target_sequence => {
+                // 合成を始める前に目標の垂直同期が過ぎた
+                result.missed = Some(target_sequence);
+                output.history.record_miss();
+                output.state = FrameState::Idle;
+            }
+            _ => {}
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(value: f64) -> Duration {
+        Duration::from_secs_f64(value / 1000.0)
+    }
+
+    fn scheduler_with_history(render_time: Duration) -> FrameScheduler {
+        let mut scheduler = FrameScheduler::new(true, true, ms(16.0));
+        scheduler.add_output(1, 60.0, None);
+        for _ in 0..10 {
+            scheduler.outputs.get_mut(&1).unwrap().history.record(render_time);
+        }
+        scheduler.vblank(1, Duration::ZERO);
+        scheduler
+    }
+
+    #[test]
+    fn test_compositing_starts_just_before_vblank() {
+        let mut scheduler = scheduler_with_history(ms(2.0));
+        assert_eq!(scheduler.predicted_render_time(1), Some(ms(3.0)));
+
+        // 履歴の合成時間と余裕の分だけ垂直同期より前に始める
+        let interval = Duration::from_secs_f64(1.0 / 60.0);
+        let start = scheduler.request_frame(1, ms(1.0)).unwrap();
+        assert_eq!(start, interval - ms(3.0));
+        assert!(scheduler.due_outputs(ms(10.0)).is_empty());
+        assert_eq!(scheduler.due_outputs(start), vec![1]);
+
+        scheduler.frame_rendered(1, start, ms(2.0));
+        let result = scheduler.vblank(1, interval);
+        let feedback = result.presented.unwrap();
+        assert_eq!(result.missed, None);
+        assert_eq!(feedback.sequence, 2);
+        assert_eq!(feedback.refresh, interval);
+        assert_eq!(feedback.latency, ms(3.0));
+    }
+
+    #[test]
+    fn test_missed_frames_increase_prediction() {
+        let mut scheduler = scheduler_with_history(ms(2.0));
+        let interval = Duration::from_secs_f64(1.0 / 60.0);
+        scheduler.request_frame(1, ms(1.0)).unwrap();
+
+        // 合成する前に目標の垂直同期が過ぎた
+        let result = scheduler.vblank(1, interval);
+        assert_eq!(result, VblankResult { presented: None, missed: Some(2) });
+        assert_eq!(scheduler.predicted_render_time(1), Some(ms(5.0)));
+
+        // 合成が垂直同期に間に合わず、次の垂直同期で表示された
+        let start = scheduler.request_frame(1, interval + ms(1.0)).unwrap();
+        scheduler.frame_rendered(1, start, ms(8.0));
+        assert!(scheduler.vblank(1, interval * 2).presented.is_none());
+        let result = scheduler.vblank(1, interval * 3);
+        assert_eq!(result.missed, Some(3));
+        assert_eq!(result.presented.unwrap().sequence, 4);
+
+        // 上限を超えて予測することはない
+        for frame in 3..13 {
+            scheduler.request_frame(1, interval * frame);
+            scheduler.vblank(1, interval * (frame + 1));
+        }
+        assert_eq!(scheduler.predicted_render_time(1), Some(ms(16.0)));
+    }
+
+    #[test]
+    fn test_vrr_and_disabled_vsync() {
+        let mut scheduler = scheduler_with_history(ms(2.0));
+        scheduler.set_vrr_range(1, Some(VrrRange::new(48.0, 144.0)));
+        let min_interval = VrrRange::new(48.0, 144.0).min_interval();
+
+        // 最短間隔より前には表示できない
+        assert_eq!(scheduler.request_frame(1, ms(1.0)), Some(min_interval - ms(3.0)));
+        scheduler.cancel(1);
+        // 最短間隔を過ぎていればすぐに合成して表示する
+        assert_eq!(scheduler.request_frame(1, ms(12.0)), Some(ms(12.0)));
+        assert_eq!(scheduler.target_time(1), Some(ms(15.0)));
+        scheduler.frame_rendered(1, ms(12.0), ms(2.0));
+        let feedback = scheduler.vblank(1, ms(14.0)).presented.unwrap();
+        assert_eq!(feedback.refresh, Duration::ZERO);
+
+        // VRRを無効にすると固定のリフレッシュ間隔に戻る
+        scheduler.set_vrr(false);
+        assert_eq!(scheduler.active_vrr_range(1), None);
+        let start = scheduler.request_frame(1, ms(15.0)).unwrap();
+        assert_eq!(start, ms(14.0) + Duration::from_secs_f64(1.0 / 60.0) - ms(3.0));
+
+        // 垂直同期なしではすぐに合成する
+        scheduler.cancel(1);
+        scheduler.set_vsync(false);
+        assert_eq!(scheduler.request_frame(1, ms(15.0)), Some(ms(15.0)));
+    }
+}
@@ -0,0 +1,460 @@
+// LumosDesktop ヘッドレスバックエンド
+// ディスプレイのない環境（CI）でコンポジターを動かすための仮想出力と仮想時計
+
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use crate::core::window_manager::input_translator::{InputEvent, InputManager};
+
+use super::cpu_renderer::Framebuffer;
+use super::frame_scheduler::VrrRange;
+use super::wayland_compositor::{CompositorEvent, LumosCompositor, OutputDevice};
+
+/// 仮想時計で1フレームの合成にかかったものとする時間の既定値
+pub const DEFAULT_RENDER_COST: Duration = Duration::from_millis(2);
+
+/// 仮想出力の設定
+#[derive(Debug, Clone)]
+pub struct HeadlessOutputConfig {
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    pub scale_factor: f64,
+    /// リフレッシュレート（Hz）
+    pub refresh_rate: f64,
+    /// 論理座標系での位置
+    pub position: (i32, i32),
+    /// 可変リフレッシュレートの範囲（`None` は固定のリフレッシュレート）
+    pub vrr_range: Option<VrrRange>,
+}
+
+impl HeadlessOutputConfig {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for HeadlessOutputConfig {
+    fn default() -> Self {
+        Self {
+            name: "HEADLESS".to_string(),
+            width: 1920,
+            height: 1080,
+            scale_factor: 1.0,
+            refresh_rate: 60.0,
+            position: (0, 0),
+            vrr_range: None,
+        }
+    }
+}
+
+/// 垂直同期ごとに取得したフレーム
+#[derive(Debug, Clone)]
+pub struct CapturedFrame {
+    pub output_id: u32,
+    /// 出力の垂直同期の通し番号
+    pub sequence: u64,
+    /// 仮想時計での表示時刻
+    pub time: Duration,
+    pub framebuffer: Framebuffer,
+}
+
+/// 仮想出力の垂直同期の状態
+#[derive(Debug)]
+struct VirtualOutput {
+    id: u32,
+    interval: Duration,
+    /// 固定のリフレッシュレートでの次の垂直同期
+    next_vblank: Duration,
+    last_vblank: Duration,
+}
+
+impl VirtualOutput {
+    /// 次の垂直同期の時刻
+    ///
+    /// VRRが有効な場合、フレームの合成が終わっていれば最短間隔を過ぎしだい、
+    /// そうでなければ最長間隔で垂直同期が発生します。
+    fn next_vblank(&self, compositor: &LumosCompositor, now: Duration) -> Duration {
+        let scheduler = compositor.frame_scheduler();
+        match scheduler.active_vrr_range(self.id) {
+            Some(range) => match scheduler.ready_time(self.id) {
+                Some(ready) => (self.last_vblank + range.min_interval()).max(ready).max(now),
+                None => self.last_vblank + range.max_interval(),
+            },
+            None => self.next_vblank,
+        }
+    }
+}
+
+/// ヘッドレスバックエンド
+///
+/// コンポジターをCPUでの合成に切り替え、仮想出力の垂直同期を仮想時計で発生させます。
+/// テストではウィンドウの作成や入力イベントの注入のあと `advance` で時間を進め、
+/// フレームバッファのピクセルと発生した `CompositorEvent` を確認します。
+pub struct HeadlessBackend {
+    compositor: Arc<Mutex<LumosCompositor>>,
+    input_manager: Option<Arc<Mutex<InputManager>>>,
+    outputs: Vec<VirtualOutput>,
+    next_output_id: u32,
+    /// 仮想時計（イベントハンドラと共有）
+    clock: Rc<Cell<Duration>>,
+    /// 仮想時計での1フレームの合成時間
+    render_cost: Duration,
+    events: Rc<RefCell<Vec<(Duration, CompositorEvent)>>>,
+    capture_frames: bool,
+    captured: Vec<CapturedFrame>,
+}
+
+/// ロックが毒されていても中身を使う（テストのパニック後も状態を確認できるように）
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+impl HeadlessBackend {
+    /// 新しいコンポジターでヘッドレスバックエンドを作成
+    // ウィンドウマネージャと同じ共有方法にそろえる（コンポジターはRcを含むためSendではない）
+    #[allow(clippy::arc_with_non_send_sync)]
+    pub fn new() -> Self {
+        Self::with_compositor(Arc::new(Mutex::new(LumosCompositor::new())))
+    }
+
+    /// 既存のコンポジター（ウィンドウマネージャのものなど）をヘッドレスで動かす
+    pub fn with_compositor(compositor: Arc<Mutex<LumosCompositor>>) -> Self {
+        let clock = Rc::new(Cell::new(Duration::ZERO));
+        let events = Rc::new(RefCell::new(Vec::new()));
+        {
+            let mut compositor = lock(&compositor);
+            compositor.enable_cpu_rendering();
+            compositor.set_fixed_render_time(Some(DEFAULT_RENDER_COST));
+            let (clock, events) = (clock.clone(), events.clone());
+            compositor.add_event_handler(move |event: &CompositorEvent| {
+                events.borrow_mut().push((clock.get(), event.clone()));
+                true
+            });
+        }
+
+        Self {
+            compositor,
+            input_manager: None,
+            outputs: Vec::new(),
+            next_output_id: 1,
+            clock,
+            render_cost: DEFAULT_RENDER_COST,
+            events,
+            capture_frames: false,
+            captured: Vec::new(),
+        }
+    }
+
+    /// 入力イベントを注入する入力マネージャを設定
+    pub fn with_input_manager(mut self, input_manager: Arc<Mutex<InputManager>>) -> Self {
+        self.input_manager = Some(input_manager);
+        self
+    }
+
+    /// 仮想時計での1フレームの合成時間を設定
+    ///
+    /// 実際にかかった時間は使わないため、フレームの予約と表示時刻は実行環境によらず決まります。
+    pub fn set_render_cost(&mut self, render_cost: Duration) {
+        self.render_cost = render_cost;
+        lock(&self.compositor).set_fixed_render_time(Some(render_cost));
+    }
+
+    pub fn render_cost(&self) -> Duration {
+        self.render_cost
+    }
+
+    pub fn compositor(&self) -> MutexGuard<'_, LumosCompositor> {
+        lock(&self.compositor)
+    }
+
+    /// 仮想出力を追加し、IDを返す
+    pub fn add_output(&mut self, config: HeadlessOutputConfig) -> u32 {
+        let id = self.next_output_id;
+        self.next_output_id += 1;
+
+        let mut output = OutputDevice::new(id, &format!("{}-{}", config.name, id), config.width, config.height, config.refresh_rate);
+        output.set_position(config.position.0, config.position.1);
+        output.set_scale_factor(config.scale_factor);
+        output.set_vrr_range(config.vrr_range);
+        lock(&self.compositor).add_output(output);
+
+        let interval = Duration::from_secs_f64(1.0 / config.refresh_rate.max(1.0));
+        let now = self.clock.get();
+        self.outputs.push(VirtualOutput {
+            id,
+            interval,
+            next_vblank: now + interval,
+            last_vblank: now,
+        });
+        id
+    }
+
+    pub fn remove_output(&mut self, id: u32) -> bool {
+        self.outputs.retain(|output| output.id != id);
+        lock(&self.compositor).remove_output(id)
+    }
+
+    /// 入力イベントを注入（次の垂直同期の前に処理される）
+    pub fn inject_input(&mut self, event: InputEvent) -> bool {
+        match &self.input_manager {
+            Some(input_manager) => {
+                lock(input_manager).push_event(event);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 仮想時計の現在時刻
+    pub fn now(&self) -> Duration {
+        self.clock.get()
+    }
+
+    /// 次の垂直同期の時刻
+    pub fn next_vblank(&self) -> Option<Duration> {
+        let compositor = lock(&self.compositor);
+        let now = self.clock.get();
+        self.outputs.iter().map(|output| output.next_vblank(&compositor, now)).min()
+    }
+
+    /// 時間を進め、予約された時刻に合成して垂直同期ごとに表示し、表示したフレーム数を返す
+    pub fn advance(&mut self, duration: Duration) -> usize {
+        let target = self.clock.get() + duration;
+        let mut presented = 0;
+        loop {
+            let now = self.clock.get();
+            let wakeup = lock(&self.compositor).schedule_frames(now);
+            let next = [wakeup, self.next_vblank()].into_iter().flatten().min();
+            let Some(time) = next.filter(|time| *time <= target) else { break };
+            presented += self.step(time.max(now));
+        }
+        self.clock.set(target);
+        presented
+    }
+
+    /// 次の垂直同期まで時間を進める
+    pub fn advance_to_next_vblank(&mut self) -> usize {
+        match self.next_vblank() {
+            Some(vblank) => self.advance(vblank - self.clock.get()),
+            None => 0,
+        }
+    }
+
+    /// 時刻 `time` に合成を始める出力を描画し、垂直同期を迎える出力のフレームを表示
+    fn step(&mut self, time: Duration) -> usize {
+        self.clock.set(time);
+
+        // 実機のメインループと同じく、入力を処理してから合成する
+        if let Some(input_manager) = &self.input_manager {
+            lock(input_manager).process_events();
+        }
+
+        let mut compositor = lock(&self.compositor);
+        compositor.render_due(time);
+
+        let mut presented = 0;
+        for output in &mut self.outputs {
+            if output.next_vblank(&compositor, time) != time {
+                continue;
+            }
+            // モードが変わった場合は新しいリフレッシュレートで数える
+            if let Some(device) = compositor.get_output(output.id) {
+                output.interval = Duration::from_secs_f64(1.0 / device.refresh_rate().max(1.0));
+            }
+            // VRRで可変の間隔になった場合は、固定の間隔をその時刻から数え直す
+            if output.next_vblank == time {
+                output.next_vblank += output.interval;
+            } else {
+                output.next_vblank = time + output.interval;
+            }
+            output.last_vblank = time;
+
+            let Some(feedback) = compositor.present(output.id, time).presented else { continue };
+            presented += 1;
+            if self.capture_frames {
+                let Some(framebuffer) = compositor.framebuffer(output.id) else { continue };
+                self.captured.push(CapturedFrame {
+                    output_id: output.id,
+                    sequence: feedback.sequence,
+                    time,
+                    framebuffer: framebuffer.clone(),
+                });
+            }
+        }
+        presented
+    }
+
+    /// 出力の現在の内容を取得
+    pub fn capture(&self, output_id: u32) -> Option<Framebuffer> {
+        lock(&self.compositor).framebuffer(output_id).cloned()
+    }
+
+    /// 表示したフレームをすべて保存するかどうか
+    pub fn set_frame_capture(&mut self, enabled: bool) {
+        self.capture_frames = enabled;
+    }
+
+    /// 保存したフレームを取り出す
+    pub fn take_captured_frames(&mut self) -> Vec<CapturedFrame> {
+        std::mem::take(&mut self.captured)
+    }
+
+    /// 発生したコンポジターイベントを仮想時計の時刻とともに取り出す
+    pub fn take_events(&mut self) -> Vec<(Duration, CompositorEvent)> {
+        std::mem::take(&mut *self.events.borrow_mut())
+    }
+}
+
+impl Default for HeadlessBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::window_manager::input_translator::{InputEventType, MouseButton};
+    use super::super::frame_scheduler::PresentationFeedback;
+    use super::super::wayland_compositor::{Buffer, PixelFormat, Rectangle, Window};
+
+    fn solid_window(id: u64, geometry: Rectangle, argb: u32) -> Window {
+        let mut data = vec![0; geometry.width as usize * geometry.height as usize * 4];
+        for pixel in data.chunks_exact_mut(4) {
+            PixelFormat::ARGB8888.encode(argb, pixel);
+        }
+        let mut window = Window::new(id, "test", geometry);
+        window.attach_buffer(Arc::new(Buffer::new(geometry.width, geometry.height, PixelFormat::ARGB8888, data)));
+        window
+    }
+
+    #[test]
+    fn test_vblank_clock_and_capture() {
+        let mut backend = HeadlessBackend::new();
+        let primary = backend.add_output(HeadlessOutputConfig::new(64, 32));
+        let secondary = backend.add_output(HeadlessOutputConfig {
+            refresh_rate: 30.0,
+            position: (64, 0),
+            ..HeadlessOutputConfig::new(32, 32)
+        });
+        backend.set_frame_capture(true);
+
+        // 両方の出力が最初のフレームを描画し、その後はダメージがないので描画しない
+        assert_eq!(backend.advance(Duration::from_millis(100)), 2);
+        assert_eq!(backend.now(), Duration::from_millis(100));
+        let frames = backend.take_captured_frames();
+        assert_eq!(frames.iter().map(|frame| (frame.output_id, frame.sequence)).collect::<Vec<_>>(),
+            vec![(primary, 1), (secondary, 1)]);
+
+        // 直後の垂直同期には合成が間に合わないため、その次の垂直同期で表示する
+        backend.compositor().add_window(solid_window(1, Rectangle::new(60, 0, 8, 8), 0xff_ff_ff_ff));
+        backend.advance(Duration::from_millis(20));
+        let frames = backend.take_captured_frames();
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].output_id, primary);
+        assert_eq!(frames[0].framebuffer.pixel(63, 0), Some(0xff_ff_ff_ff));
+
+        // 30Hzの出力は次の垂直同期でウィンドウのはみ出した部分を描画
+        backend.advance(Duration::from_millis(20));
+        let secondary_frame = backend.capture(secondary).unwrap();
+        assert_eq!(secondary_frame.pixel(0, 0), Some(0xff_ff_ff_ff));
+        assert_eq!(secondary_frame.pixel(4, 0), Some(0xff_00_00_00));
+
+        let events = backend.take_events();
+        assert_eq!(events[0], (Duration::ZERO, CompositorEvent::OutputAdded(primary)));
+        assert!(events.iter().any(|(_, event)| *event == CompositorEvent::WindowCreated(1)));
+        let presented: Vec<Duration> = events.iter()
+            .filter(|(_, event)| matches!(event, CompositorEvent::FramePresented(_)))
+            .map(|(time, _)| *time)
+            .collect();
+        assert_eq!(presented.len(), 4);
+        assert!(presented.windows(2).all(|pair| pair[0] <= pair[1]));
+    }
+
+    fn presented(events: &[(Duration, CompositorEvent)]) -> Vec<PresentationFeedback> {
+        events.iter()
+            .filter_map(|(_, event)| match event {
+                CompositorEvent::FramePresented(feedback) => Some(*feedback),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_frames_are_composited_just_before_vblank() {
+        let mut backend = HeadlessBackend::new();
+        let fixed = backend.add_output(HeadlessOutputConfig::new(16, 16));
+        let vrr = backend.add_output(HeadlessOutputConfig {
+            position: (16, 0),
+            vrr_range: Some(VrrRange::new(30.0, 120.0)),
+            ..HeadlessOutputConfig::new(16, 16)
+        });
+        // 最初のフレームで合成時間の履歴ができる。VRRの出力の最後の垂直同期は108.3ms
+        backend.advance(Duration::from_millis(130));
+        backend.take_events();
+
+        backend.compositor().add_window(solid_window(1, Rectangle::new(8, 0, 16, 4), 0xff_ff_ff_ff));
+        backend.advance(Duration::from_millis(20));
+        let feedback = presented(&backend.take_events());
+        assert_eq!(feedback.len(), 2);
+
+        // VRRの出力は最短間隔を過ぎていれば合成が終わりしだい表示する
+        let vrr_frame = feedback.iter().find(|feedback| feedback.output_id == vrr).unwrap();
+        assert_eq!(vrr_frame.render_time, DEFAULT_RENDER_COST);
+        assert_eq!(vrr_frame.presented_at, Duration::from_millis(130) + DEFAULT_RENDER_COST);
+        assert_eq!(vrr_frame.refresh, Duration::ZERO);
+
+        // 固定のリフレッシュレートの出力は、次の垂直同期の直前まで合成を遅らせる
+        let interval = Duration::from_secs_f64(1.0 / 60.0);
+        let fixed_frame = feedback.iter().find(|feedback| feedback.output_id == fixed).unwrap();
+        assert_eq!(fixed_frame.refresh, interval);
+        assert!(fixed_frame.presented_at > Duration::from_millis(130));
+        assert!(fixed_frame.latency < interval / 4, "latency {:?}", fixed_frame.latency);
+        assert!(fixed_frame.vsync);
+        assert_eq!(fixed_frame.render_time, DEFAULT_RENDER_COST);
+    }
+
+    #[test]
+    fn test_render_cost_drives_frame_timing() {
+        let mut backend = HeadlessBackend::new();
+        backend.set_render_cost(Duration::from_millis(6));
+        let output = backend.add_output(HeadlessOutputConfig::new(16, 16));
+        backend.advance(Duration::from_millis(100));
+        backend.take_events();
+
+        // 合成時間の予測は仮想の合成時間から決まり、合成は垂直同期の少なくともその分だけ前に始まる
+        backend.compositor().add_window(solid_window(1, Rectangle::new(0, 0, 4, 4), 0xff_ff_ff_ff));
+        backend.advance(Duration::from_millis(40));
+        let feedback = presented(&backend.take_events());
+        assert_eq!(feedback.len(), 1);
+        assert_eq!(feedback[0].output_id, output);
+        assert_eq!(feedback[0].render_time, backend.render_cost());
+        assert!(feedback[0].latency >= backend.render_cost());
+        assert!(feedback[0].latency < Duration::from_secs_f64(1.0 / 60.0));
+    }
+
+    #[test]
+    fn test_injected_input_is_processed_before_frame() {
+        let input_manager = Arc::new(Mutex::new(InputManager::new()));
+        let mut backend = HeadlessBackend::new().with_input_manager(input_manager.clone());
+        backend.add_output(HeadlessOutputConfig::new(16, 16));
+
+        assert!(backend.inject_input(InputEvent::new(InputEventType::MousePress {
+            button: MouseButton::Left,
+            x: 4.0,
+            y: 6.0,
+            modifiers: Default::default(),
+            timestamp: 0,
+        })));
+        assert!(lock(&input_manager).get_pressed_buttons().is_empty());
+        backend.advance_to_next_vblank();
+        assert!(lock(&input_manager).get_pressed_buttons().contains(&MouseButton::Left));
+    }
+}
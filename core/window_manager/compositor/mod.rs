@@ -0,0 +1,40 @@
+// LumosDesktop コンポジターモジュール
+// ウィンドウのバッファを出力デバイスに合成します
+
+//! コンポジターモジュール
+//!
+//! ウィンドウと出力デバイスを管理し、重なり順に従ってウィンドウを合成します。
+//! 各ウィンドウのダメージは出力ごとに蓄積され、変更された領域だけが再描画されます。
+//! 出力構成マネージャはすべての出力のモードや配置をまとめて変更し、モニターの組ごとに保存します。
+//! フレームスケジューラは合成時間を予測し、垂直同期の直前に合成を始めます。
+//! カラーマネジメントはICCプロファイルを解析し、ウィンドウの色を出力の色と夜間モードに合わせて変換します。
+//! 画面キャプチャは権限を確認した上で出力・領域・ウィンドウを静止画またはダメージ付きのストリームとして取得します。
+//! GPUを使わずにCPUで合成する参照実装も含まれています。
+//! ヘッドレスバックエンドを使うと、ディスプレイのない環境で仮想出力に描画できます。
+//! Waylandサーバーはクライアントのサーフェスをウィンドウとしてコンポジターに渡します。
+
+pub mod wayland_compositor;
+pub mod damage;
+pub mod color_management;
+pub mod frame_scheduler;
+pub mod output_manager;
+pub mod screen_capture;
+pub mod cpu_renderer;
+pub mod headless;
+pub mod wayland_server;
+
+// 主要な型の再エクスポート
+pub use wayland_compositor::{
+    LumosCompositor, Window, Rectangle, OutputDevice, TransformMatrix, CompositorEvent,
+    Buffer, PixelFormat, ColorProfile,
+};
+pub use damage::{Region, OutputDamage, OutputRepaint, WindowDraw, RepaintStats};
+pub use color_management::{ColorSpace, IccError, IccProfile, NightLight, OutputColorTransform, ToneCurve};
+pub use frame_scheduler::{FrameScheduler, PresentationFeedback, RenderTimeHistory, VblankResult, VrrRange};
+pub use output_manager::{
+    OutputConfigError, OutputConfiguration, OutputIdentity, OutputManager, OutputMode, OutputRotation, OutputState,
+};
+pub use screen_capture::{CaptureError, CaptureFrame, CaptureSource, SCREEN_CAPTURE_PERMISSION};
+pub use cpu_renderer::{CpuRenderer, Framebuffer};
+pub use headless::{HeadlessBackend, HeadlessOutputConfig, CapturedFrame};
+pub use wayland_server::{WaylandServer, WaylandServerError};
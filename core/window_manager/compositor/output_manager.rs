@@ -0,0 +1,609 @@
+// LumosDesktop 出力構成の管理
+// すべての出力のモード・スケール・回転・配置をまとめて変更し、接続されたモニターの組ごとに保存します
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::wayland_compositor::{LumosCompositor, OutputDevice, Rectangle, TransformMatrix};
+
+/// 確認されなかった構成を元に戻すまでの既定の時間
+pub const DEFAULT_CONFIRM_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// 小数スケールの刻み（wp_fractional_scale_v1 と同じく1/120単位）
+const SCALE_DENOMINATOR: f64 = 120.0;
+const MIN_SCALE: f64 = 0.25;
+const MAX_SCALE: f64 = 8.0;
+
+/// リフレッシュレートが同じとみなす誤差（Hz）
+const REFRESH_TOLERANCE: f64 = 0.01;
+
+/// 出力構成のエラー
+#[derive(Debug, Error)]
+pub enum OutputConfigError {
+    #[error("出力が見つかりません: {0}")]
+    UnknownOutput(u32),
+
+    #[error("出力 {output} はモード {mode} に対応していません")]
+    UnsupportedMode { output: u32, mode: OutputMode },
+
+    #[error("出力 {0} のスケールが不正です: {1}")]
+    InvalidScale(u32, f64),
+
+    #[error("有効な出力がありません")]
+    NoEnabledOutput,
+
+    #[error("出力 {0} と {1} が重なっています")]
+    Overlap(u32, u32),
+
+    #[error("出力構成の読み書きに失敗しました: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("出力構成の形式が不正です: {0}")]
+    Format(#[from] serde_json::Error),
+}
+
+/// モニターの識別情報（EDIDの製造元・型番・シリアル番号）
+///
+/// コネクター名ではなくモニター自体を識別するため、同じモニターを別の端子に
+/// 挿し直しても保存した構成が使われます。
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct OutputIdentity {
+    pub make: String,
+    pub model: String,
+    pub serial: String,
+}
+
+impl OutputIdentity {
+    pub fn new(make: &str, model: &str, serial: &str) -> Self {
+        Self {
+            make: make.to_string(),
+            model: model.to_string(),
+            serial: serial.to_string(),
+        }
+    }
+}
+
+/// 出力のモード
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct OutputMode {
+    pub width: u32,
+    pub height: u32,
+    /// リフレッシュレート（Hz）
+    pub refresh_rate: f64,
+}
+
+impl OutputMode {
+    pub fn new(width: u32, height: u32, refresh_rate: f64) -> Self {
+        Self { width, height, refresh_rate }
+    }
+
+    /// 解像度が同じで、リフレッシュレートが誤差の範囲内かどうか
+    pub fn matches(&self, other: &OutputMode) -> bool {
+        self.width == other.width
+            && self.height == other.height
+            && (self.refresh_rate - other.refresh_rate).abs() < REFRESH_TOLERANCE
+    }
+}
+
+impl fmt::Display for OutputMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}x{}@{:.3}Hz", self.width, self.height, self.refresh_rate)
+    }
+}
+
+/// 出力の回転（反時計回り）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OutputRotation {
+    #[default]
+    Normal,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+}
+
+impl OutputRotation {
+    pub fn transform(self) -> TransformMatrix {
+        match self {
+            OutputRotation::Normal => TransformMatrix::identity(),
+            OutputRotation::Rotate90 => TransformMatrix::rotate_90_degrees(),
+            OutputRotation::Rotate180 => TransformMatrix::rotate_180_degrees(),
+            OutputRotation::Rotate270 => TransformMatrix::rotate_270_degrees(),
+        }
+    }
+
+    /// 変換行列に対応する回転（回転以外の変換は `Normal` とみなす）
+    pub fn from_transform(transform: &TransformMatrix) -> Self {
+        [OutputRotation::Rotate90, OutputRotation::Rotate180, OutputRotation::Rotate270]
+            .into_iter()
+            .find(|rotation| rotation.transform() == *transform)
+            .unwrap_or_default()
+    }
+
+    /// 幅と高さが入れ替わるかどうか
+    pub fn is_transposed(self) -> bool {
+        matches!(self, OutputRotation::Rotate90 | OutputRotation::Rotate270)
+    }
+}
+
+/// 1つの出力の構成
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OutputState {
+    pub mode: OutputMode,
+    /// スケール（1/120単位の小数スケールに丸められる）
+    pub scale: f64,
+    pub rotation: OutputRotation,
+    /// 論理座標系での位置
+    pub position: (i32, i32),
+    pub enabled: bool,
+}
+
+impl OutputState {
+    /// 出力デバイスの現在の構成
+    pub fn of(output: &OutputDevice) -> Self {
+        let (width, height) = output.size();
+        Self {
+            mode: OutputMode::new(width, height, output.refresh_rate()),
+            scale: output.scale_factor(),
+            rotation: output.rotation(),
+            position: output.position(),
+            enabled: output.is_enabled(),
+        }
+    }
+
+    /// 論理座標系で出力が占める矩形（`OutputDevice::logical_rect` と同じ丸め）
+    pub fn logical_rect(&self) -> Rectangle {
+        let (width, height) = if self.rotation.is_transposed() {
+            (self.mode.height, self.mode.width)
+        } else {
+            (self.mode.width, self.mode.height)
+        };
+        Rectangle::new(
+            self.position.0,
+            self.position.1,
+            (width as f64 / self.scale).ceil() as u32,
+            (height as f64 / self.scale).ceil() as u32,
+        )
+    }
+
+    fn normalized(&self) -> Self {
+        Self {
+            scale: (self.scale * SCALE_DENOMINATOR).round() / SCALE_DENOMINATOR,
+            ..self.clone()
+        }
+    }
+}
+
+/// すべての出力の構成（出力IDごと）
+///
+/// 含まれない出力は現在の構成のまま変更されません。
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OutputConfiguration {
+    outputs: BTreeMap<u32, OutputState>,
+}
+
+impl OutputConfiguration {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// コンポジターのすべての出力の現在の構成
+    pub fn current(compositor: &LumosCompositor) -> Self {
+        let outputs = compositor.output_ids().into_iter()
+            .filter_map(|id| Some((id, OutputState::of(compositor.get_output(id)?))))
+            .collect();
+        Self { outputs }
+    }
+
+    pub fn get(&self, id: u32) -> Option<&OutputState> {
+        self.outputs.get(&id)
+    }
+
+    pub fn get_mut(&mut self, id: u32) -> Option<&mut OutputState> {
+        self.outputs.get_mut(&id)
+    }
+
+    pub fn set(&mut self, id: u32, state: OutputState) {
+        self.outputs.insert(id, state);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (u32, &OutputState)> {
+        self.outputs.iter().map(|(id, state)| (*id, state))
+    }
+
+    pub fn len(&self) -> usize {
+        self.outputs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.outputs.is_empty()
+    }
+}
+
+/// 保存された1つの出力の構成
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct SavedOutput {
+    identity: OutputIdentity,
+    state: OutputState,
+}
+
+/// 接続されたモニターの組に対して保存された構成
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct OutputProfile {
+    outputs: Vec<SavedOutput>,
+}
+
+impl OutputProfile {
+    fn monitors(&self) -> Vec<&OutputIdentity> {
+        let mut monitors: Vec<&OutputIdentity> = self.outputs.iter().map(|output| &output.identity).collect();
+        monitors.sort();
+        monitors
+    }
+}
+
+/// 確認待ちの構成
+struct PendingConfiguration {
+    /// 元に戻すときの構成
+    previous: OutputConfiguration,
+    deadline: Duration,
+}
+
+/// 出力構成マネージャ
+///
+/// 構成はまず検証され、すべての出力に一度に適用されます。適用した構成は
+/// `confirm` が呼ばれるまで仮のもので、期限までに確認されなければ `tick` で
+/// 元の構成に戻ります。確認された構成は接続されたモニターの組ごとに保存され、
+/// 同じ組が接続されたときに `restore` で復元されます。
+///
+/// 時刻はフレームスケジューラと同じく、バックエンドのモノトニック時計での値です。
+pub struct OutputManager {
+    confirm_timeout: Duration,
+    pending: Option<PendingConfiguration>,
+    profiles: Vec<OutputProfile>,
+    /// 保存先（`None` の場合はメモリ上にのみ保持）
+    store_path: Option<PathBuf>,
+}
+
+impl Default for OutputManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OutputManager {
+    /// 構成をメモリ上にのみ保持するマネージャを作成
+    pub fn new() -> Self {
+        Self {
+            confirm_timeout: DEFAULT_CONFIRM_TIMEOUT,
+            pending: None,
+            profiles: Vec::new(),
+            store_path: None,
+        }
+    }
+
+    /// 保存先のファイルを開く（ファイルがなければ空の状態で始める）
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, OutputConfigError> {
+        let path = path.as_ref().to_path_buf();
+        let profiles = match fs::read(&path) {
+            Ok(data) => serde_json::from_slice(&data)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self {
+            profiles,
+            store_path: Some(path),
+            ..Self::new()
+        })
+    }
+
+    /// 既定の保存先
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("lumos").join("outputs.json"))
+    }
+
+    pub fn set_confirm_timeout(&mut self, timeout: Duration) {
+        self.confirm_timeout = timeout;
+    }
+
+    pub fn confirm_timeout(&self) -> Duration {
+        self.confirm_timeout
+    }
+
+    /// 構成が確認待ちかどうか
+    pub fn is_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// 確認待ちの構成が元に戻る時刻
+    pub fn deadline(&self) -> Option<Duration> {
+        self.pending.as_ref().map(|pending| pending.deadline)
+    }
+
+    /// 構成を適用せずに検証
+    pub fn test(&self, compositor: &LumosCompositor, config: &OutputConfiguration) -> Result<(), OutputConfigError> {
+        let mut merged = OutputConfiguration::current(compositor);
+        for (id, state) in config.iter() {
+            let Some(output) = compositor.get_output(id) else {
+                return Err(OutputConfigError::UnknownOutput(id));
+            };
+            if !output.modes().iter().any(|mode| mode.matches(&state.mode)) {
+                return Err(OutputConfigError::UnsupportedMode { output: id, mode: state.mode });
+            }
+            if !state.scale.is_finite() || !(MIN_SCALE..=MAX_SCALE).contains(&state.scale) {
+                return Err(OutputConfigError::InvalidScale(id, state.scale));
+            }
+            merged.set(id, state.normalized());
+        }
+
+        let enabled: Vec<(u32, Rectangle)> = merged.iter()
+            .filter(|(_, state)| state.enabled)
+            .map(|(id, state)| (id, state.logical_rect()))
+            .collect();
+        if enabled.is_empty() {
+            return Err(OutputConfigError::NoEnabledOutput);
+        }
+        for (i, (a, rect_a)) in enabled.iter().enumerate() {
+            if let Some((b, _)) = enabled[i + 1..].iter().find(|(_, rect_b)| rect_a.intersect(rect_b).is_some()) {
+                return Err(OutputConfigError::Overlap(*a, *b));
+            }
+        }
+        Ok(())
+    }
+
+    /// 構成を検証してすべての出力に適用し、確認待ちにする
+    ///
+    /// 検証に失敗した場合はどの出力も変更されません。確認待ちの間に再度適用した場合、
+    /// 元に戻す先は最後に確認された構成のままです。
+    pub fn apply(&mut self, compositor: &mut LumosCompositor, config: &OutputConfiguration, now: Duration) -> Result<(), OutputConfigError> {
+        self.test(compositor, config)?;
+        let previous = match self.pending.take() {
+            Some(pending) => pending.previous,
+            None => OutputConfiguration::current(compositor),
+        };
+        Self::apply_unchecked(compositor, config);
+        self.pending = Some(PendingConfiguration {
+            previous,
+            deadline: now + self.confirm_timeout,
+        });
+        Ok(())
+    }
+
+    fn apply_unchecked(compositor: &mut LumosCompositor, config: &OutputConfiguration) {
+        for (id, state) in config.iter() {
+            compositor.configure_output(id, &state.normalized());
+        }
+    }
+
+    /// 確認待ちの構成を確定し、接続されたモニターの組に対して保存する
+    ///
+    /// 確認待ちの構成がなければ偽を返します。
+    pub fn confirm(&mut self, compositor: &LumosCompositor) -> Result<bool, OutputConfigError> {
+        if self.pending.take().is_none() {
+            return Ok(false);
+        }
+        self.save(compositor)?;
+        Ok(true)
+    }
+
+    /// 確認待ちの構成を元に戻す
+    pub fn revert(&mut self, compositor: &mut LumosCompositor) -> bool {
+        let Some(pending) = self.pending.take() else { return false };
+        // 確認待ちの間に外された出力は無視する
+        let mut previous = OutputConfiguration::new();
+        for (id, state) in pending.previous.iter() {
+            if compositor.get_output(id).is_some() {
+                previous.set(id, state.clone());
+            }
+        }
+        Self::apply_unchecked(compositor, &previous);
+        true
+    }
+
+    /// 確認の期限を過ぎていれば元に戻し、戻した場合は真を返す
+    pub fn tick(&mut self, compositor: &mut LumosCompositor, now: Duration) -> bool {
+        match &self.pending {
+            Some(pending) if now >= pending.deadline => self.revert(compositor),
+            _ => false,
+        }
+    }
+
+    /// 現在の構成を接続されたモニターの組に対して保存
+    pub fn save(&mut self, compositor: &LumosCompositor) -> Result<(), OutputConfigError> {
+        let config = OutputConfiguration::current(compositor);
+        let profile = OutputProfile {
+            outputs: config.iter()
+                .filter_map(|(id, state)| Some(SavedOutput {
+                    identity: compositor.get_output(id)?.identity().clone(),
+                    state: state.clone(),
+                }))
+                .collect(),
+        };
+        let monitors = profile.monitors();
+        match self.profiles.iter_mut().find(|saved| saved.monitors() == monitors) {
+            Some(saved) => *saved = profile,
+            None => self.profiles.push(profile),
+        }
+        self.write()
+    }
+
+    fn write(&self) -> Result<(), OutputConfigError> {
+        let Some(path) = &self.store_path else { return Ok(()) };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(&self.profiles)?)?;
+        Ok(())
+    }
+
+    /// 接続されたモニターの組に対して保存された構成
+    pub fn saved_configuration(&self, compositor: &LumosCompositor) -> Option<OutputConfiguration> {
+        let ids = compositor.output_ids();
+        let mut monitors: Vec<&OutputIdentity> = ids.iter()
+            .filter_map(|id| compositor.get_output(*id).map(|output| output.identity()))
+            .collect();
+        monitors.sort();
+        let profile = self.profiles.iter().find(|profile| profile.monitors() == monitors)?;
+
+        // 同じ識別情報のモニターが複数ある場合は出力IDの順に割り当てる
+        let mut unassigned = ids;
+        let mut config = OutputConfiguration::new();
+        for saved in &profile.outputs {
+            let index = unassigned.iter()
+                .position(|id| compositor.get_output(*id).is_some_and(|output| *output.identity() == saved.identity))?;
+            config.set(unassigned.remove(index), saved.state.clone());
+        }
+        Some(config)
+    }
+
+    /// 接続されたモニターの組に対して保存された構成を確認なしで適用
+    ///
+    /// モニターの接続や切断の後に呼び出します。保存された構成がなければ偽を返します。
+    pub fn restore(&mut self, compositor: &mut LumosCompositor) -> Result<bool, OutputConfigError> {
+        let Some(config) = self.saved_configuration(compositor) else { return Ok(false) };
+        self.test(compositor, &config)?;
+        self.pending = None;
+        Self::apply_unchecked(compositor, &config);
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::wayland_compositor::CompositorEvent;
+    use std::sync::{Arc, Mutex};
+
+    fn output(id: u32, serial: &str, width: u32, height: u32) -> OutputDevice {
+        let mut output = OutputDevice::new(id, &format!("DP-{}", id), width, height, 60.0);
+        output.set_identity(OutputIdentity::new("LMS", "Panel", serial));
+        output.set_modes(vec![
+            OutputMode::new(width, height, 60.0),
+            OutputMode::new(width, height, 144.0),
+            OutputMode::new(1280, 720, 60.0),
+        ]);
+        output
+    }
+
+    fn compositor_with_events() -> (LumosCompositor, Arc<Mutex<Vec<CompositorEvent>>>) {
+        let mut compositor = LumosCompositor::new();
+        compositor.add_output(output(1, "A", 1920, 1080));
+        let mut second = output(2, "B", 2560, 1440);
+        second.set_position(1920, 0);
+        compositor.add_output(second);
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = events.clone();
+        compositor.add_event_handler(move |event: &CompositorEvent| {
+            sink.lock().unwrap().push(event.clone());
+            true
+        });
+        (compositor, events)
+    }
+
+    #[test]
+    fn test_apply_is_atomic_and_emits_events() {
+        let (mut compositor, events) = compositor_with_events();
+        let mut manager = OutputManager::new();
+        let before = OutputConfiguration::current(&compositor);
+
+        // 2つ目の出力が1つ目と重なるため、1つ目のモード変更も適用されない
+        let mut config = before.clone();
+        config.get_mut(1).unwrap().mode = OutputMode::new(1920, 1080, 144.0);
+        config.get_mut(2).unwrap().position = (1000, 0);
+        assert!(matches!(manager.apply(&mut compositor, &config, Duration::ZERO), Err(OutputConfigError::Overlap(1, 2))));
+        assert_eq!(OutputConfiguration::current(&compositor), before);
+        assert!(events.lock().unwrap().is_empty());
+
+        let mut unsupported = before.clone();
+        unsupported.get_mut(1).unwrap().mode = OutputMode::new(800, 600, 60.0);
+        assert!(matches!(manager.test(&compositor, &unsupported), Err(OutputConfigError::UnsupportedMode { output: 1, .. })));
+        let mut all_disabled = before.clone();
+        all_disabled.get_mut(1).unwrap().enabled = false;
+        all_disabled.get_mut(2).unwrap().enabled = false;
+        assert!(matches!(manager.test(&compositor, &all_disabled), Err(OutputConfigError::NoEnabledOutput)));
+
+        // 1.5倍の小数スケールで2つ目を回転させ、1つ目の右に並べる
+        config.get_mut(2).unwrap().position = (1920, 0);
+        config.get_mut(2).unwrap().scale = 1.5;
+        config.get_mut(2).unwrap().rotation = OutputRotation::Rotate90;
+        manager.apply(&mut compositor, &config, Duration::ZERO).unwrap();
+
+        let second = compositor.get_output(2).unwrap();
+        assert_eq!(second.logical_rect(), Rectangle::new(1920, 0, 960, 1707));
+        assert_eq!(second.rotation(), OutputRotation::Rotate90);
+        assert_eq!(compositor.get_output(1).unwrap().refresh_rate(), 144.0);
+        assert_eq!(*events.lock().unwrap(), vec![
+            CompositorEvent::OutputModeChanged(1, 1920, 1080, 144.0),
+            CompositorEvent::OutputModeChanged(2, 2560, 1440, 60.0),
+        ]);
+    }
+
+    #[test]
+    fn test_unconfirmed_configuration_is_rolled_back() {
+        let (mut compositor, events) = compositor_with_events();
+        let mut manager = OutputManager::new();
+        manager.set_confirm_timeout(Duration::from_secs(10));
+        let before = OutputConfiguration::current(&compositor);
+
+        let mut config = before.clone();
+        config.get_mut(1).unwrap().mode = OutputMode::new(1280, 720, 60.0);
+        manager.apply(&mut compositor, &config, Duration::from_secs(1)).unwrap();
+        let mut disabled = config.clone();
+        disabled.get_mut(2).unwrap().enabled = false;
+        manager.apply(&mut compositor, &disabled, Duration::from_secs(2)).unwrap();
+        assert_eq!(manager.deadline(), Some(Duration::from_secs(12)));
+
+        assert!(!manager.tick(&mut compositor, Duration::from_secs(11)));
+        assert!(!compositor.get_output(2).unwrap().is_enabled());
+
+        // 期限を過ぎると、最後に確認された構成に戻る
+        assert!(manager.tick(&mut compositor, Duration::from_secs(12)));
+        assert!(!manager.is_pending());
+        assert_eq!(OutputConfiguration::current(&compositor), before);
+        assert_eq!(events.lock().unwrap().last(), Some(&CompositorEvent::OutputEnabled(2, true)));
+
+        // 確認した構成は元に戻らない
+        manager.apply(&mut compositor, &config, Duration::from_secs(20)).unwrap();
+        assert!(manager.confirm(&compositor).unwrap());
+        assert!(!manager.tick(&mut compositor, Duration::from_secs(60)));
+        assert_eq!(compositor.get_output(1).unwrap().size(), (1280, 720));
+    }
+
+    #[test]
+    fn test_configurations_are_saved_per_monitor_set() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("outputs.json");
+
+        let (mut compositor, _) = compositor_with_events();
+        let mut manager = OutputManager::open(&path).unwrap();
+        let mut config = OutputConfiguration::current(&compositor);
+        config.get_mut(2).unwrap().scale = 1.25;
+        config.get_mut(2).unwrap().position = (0, -1152);
+        manager.apply(&mut compositor, &config, Duration::ZERO).unwrap();
+        manager.confirm(&compositor).unwrap();
+
+        // 同じモニターが別の出力IDで接続された場合も識別情報で復元する
+        let mut compositor = LumosCompositor::new();
+        compositor.add_output(output(5, "B", 2560, 1440));
+        let mut first = output(7, "A", 1920, 1080);
+        first.set_position(2560, 0);
+        compositor.add_output(first);
+        let mut manager = OutputManager::open(&path).unwrap();
+        assert!(manager.restore(&mut compositor).unwrap());
+        let restored = compositor.get_output(5).unwrap();
+        assert_eq!(restored.scale_factor(), 1.25);
+        assert_eq!(restored.position(), (0, -1152));
+        assert_eq!(compositor.get_output(7).unwrap().position(), (0, 0));
+
+        // モニターの組が違えば保存された構成は使わない
+        let mut compositor = LumosCompositor::new();
+        compositor.add_output(output(1, "A", 1920, 1080));
+        assert!(!manager.restore(&mut compositor).unwrap());
+    }
+}
@@ -0,0 +1,452 @@
+// LumosDesktop 画面キャプチャ
+// 出力・領域・ウィンドウを権限の確認の上でキャプチャし、録画中は通知で知らせます
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+
+use thiserror::Error;
+
+use crate::core::system::notification_service::{
+    Notification, NotificationCategory, NotificationId, NotificationPriority, NotificationService,
+};
+use crate::core::system::security_context::SecurityContext;
+
+use super::color_management::OutputColorTransform;
+use super::cpu_renderer::{blend, sample, Framebuffer};
+use super::damage::Region;
+use super::wayland_compositor::{Rectangle, Window};
+
+/// 画面をキャプチャするのに必要な権限
+pub const SCREEN_CAPTURE_PERMISSION: &str = "screen_capture";
+
+/// 録画中の通知の「停止」アクション
+const STOP_ACTION: &str = "stop-screen-capture";
+/// 通知のメタデータに入れるストリームID
+const STREAM_METADATA: &str = "capture_stream";
+
+/// 画面キャプチャのエラー
+#[derive(Debug, Error, PartialEq)]
+pub enum CaptureError {
+    #[error("画面をキャプチャする権限がありません: {0}")]
+    PermissionDenied(String),
+
+    #[error("セキュリティコンテキストが無効です")]
+    InvalidContext,
+
+    #[error("出力が見つかりません: {0}")]
+    UnknownOutput(u32),
+
+    #[error("ウィンドウが見つかりません: {0}")]
+    UnknownWindow(u64),
+
+    #[error("キャプチャする領域が空です")]
+    EmptyRegion,
+
+    #[error("録画中であることを通知できないため、録画を開始できません")]
+    NoNotificationService,
+}
+
+/// キャプチャの対象
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureSource {
+    /// 出力全体（出力のスケールの解像度）
+    Output(u32),
+    /// 論理座標の領域（論理ピクセルの解像度）
+    Region(Rectangle),
+    /// 1つのウィンドウ（バッファの解像度、ほかのウィンドウに隠れた部分も含む）
+    Window(u64),
+}
+
+/// キャプチャしたフレーム
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaptureFrame {
+    pub source: CaptureSource,
+    /// ストリームでのフレームの通し番号（1回だけのキャプチャでは0）
+    pub sequence: u64,
+    /// 乗算済みアルファの0xAARRGGBB（sRGB）
+    pub framebuffer: Framebuffer,
+    /// 前のフレームから変わった領域（フレームの座標）
+    pub damage: Vec<Rectangle>,
+}
+
+/// 要求元の表示名（アプリケーションIDがなければユーザーID）
+fn requester(context: &SecurityContext) -> String {
+    context.credentials.app_id.as_deref().unwrap_or(context.user_id()).to_string()
+}
+
+/// セキュリティコンテキストが画面をキャプチャできるか確認
+pub fn check_permission(context: &SecurityContext) -> Result<(), CaptureError> {
+    if !context.is_valid() {
+        return Err(CaptureError::InvalidContext);
+    }
+    if !context.has_permission(SCREEN_CAPTURE_PERMISSION) {
+        log::warn!("画面キャプチャを拒否しました: {}", requester(context));
+        return Err(CaptureError::PermissionDenied(requester(context)));
+    }
+    Ok(())
+}
+
+/// 共有のセキュリティコンテキストを確認（ロックが毒されていても中身を使う）
+pub fn check_shared_permission(context: &Mutex<SecurityContext>) -> Result<(), CaptureError> {
+    check_permission(&context.lock().unwrap_or_else(|poisoned| poisoned.into_inner()))
+}
+
+/// 論理座標の `bounds` を `scale` 倍した解像度のフレームの大きさ
+fn frame_size(bounds: &Rectangle, scale: f64) -> (u32, u32) {
+    (
+        (bounds.width as f64 * scale).ceil() as u32,
+        (bounds.height as f64 * scale).ceil() as u32,
+    )
+}
+
+/// 論理座標の矩形をフレームの座標に変換（部分的に重なるピクセルも含む）
+fn to_frame(rect: &Rectangle, bounds: &Rectangle, scale: f64) -> Option<Rectangle> {
+    let clipped = rect.intersect(bounds)?;
+    let (width, height) = frame_size(bounds, scale);
+    let x0 = ((clipped.x - bounds.x) as f64 * scale).floor() as u32;
+    let y0 = ((clipped.y - bounds.y) as f64 * scale).floor() as u32;
+    let x1 = (((clipped.right() - bounds.x) as f64 * scale).ceil() as u32).min(width);
+    let y1 = (((clipped.bottom() - bounds.y) as f64 * scale).ceil() as u32).min(height);
+    (x0 < x1 && y0 < y1).then(|| Rectangle::new(x0 as i32, y0 as i32, x1 - x0, y1 - y0))
+}
+
+/// 論理座標の `bounds` を `scale` 倍の解像度で、奥から順にウィンドウを合成
+///
+/// `only` を指定した場合はそのウィンドウだけを不透明度を掛けずに描きます。
+/// 出力のカラープロファイルや夜間モードは適用せず、sRGBで返します。
+pub(super) fn render<'a>(
+    windows: impl Iterator<Item = &'a Rc<RefCell<Window>>>,
+    bounds: Rectangle,
+    scale: f64,
+    only: Option<u64>,
+    background: u32,
+) -> Framebuffer {
+    let (width, height) = frame_size(&bounds, scale);
+    let mut framebuffer = Framebuffer::new(width, height, background);
+    let srgb = OutputColorTransform::default();
+    for window in windows {
+        let window = window.borrow();
+        if !window.is_mapped() || only.is_some_and(|id| id != window.id()) {
+            continue;
+        }
+        let opacity = if only.is_some() { 255 } else { (window.opacity() * 255.0).round() as u32 };
+        if opacity == 0 {
+            continue;
+        }
+        let Some(rect) = to_frame(&window.geometry(), &bounds, scale) else { continue };
+        let color_space = window.color_space();
+        let pixels = framebuffer.pixels_mut();
+        for y in rect.y..rect.bottom() {
+            for x in rect.x..rect.right() {
+                let lx = bounds.x as f64 + (x as f64 + 0.5) / scale;
+                let ly = bounds.y as f64 + (y as f64 + 0.5) / scale;
+                let Some(src) = sample(&window, lx, ly) else { continue };
+                let index = y as usize * width as usize + x as usize;
+                pixels[index] = blend(pixels[index], srgb.apply(color_space, src), opacity);
+            }
+        }
+    }
+    framebuffer
+}
+
+/// キャプチャストリーム
+struct CaptureStream {
+    source: CaptureSource,
+    /// 録画を始めたセキュリティコンテキスト（権限が取り消されたら停止する）
+    context: Arc<Mutex<SecurityContext>>,
+    /// 前のフレームの範囲とスケール（変わった場合はフレーム全体がダメージになる）
+    last_bounds: Option<(Rectangle, f64)>,
+    /// 前のフレームから蓄積した論理座標のダメージ
+    damage: Region,
+    sequence: u64,
+    notification: NotificationId,
+}
+
+/// 画面キャプチャのストリームと録画中の通知の管理
+pub(super) struct ScreenCapture {
+    streams: BTreeMap<u64, CaptureStream>,
+    next_stream_id: u64,
+    notifications: Option<Arc<NotificationService>>,
+    /// 通知の「停止」アクションで停止を求められたストリーム
+    stop_requests: Arc<Mutex<Vec<u64>>>,
+}
+
+impl ScreenCapture {
+    pub(super) fn new() -> Self {
+        Self {
+            streams: BTreeMap::new(),
+            next_stream_id: 1,
+            notifications: None,
+            stop_requests: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// 録画中の通知を送る通知サービスを設定
+    pub(super) fn set_notification_service(&mut self, service: Arc<NotificationService>) {
+        let stop_requests = self.stop_requests.clone();
+        service.register_action_handler(STOP_ACTION, move |notification: &Notification, _: &str| {
+            let stream = notification.metadata.get(STREAM_METADATA).and_then(|id| id.parse().ok());
+            if let Some(stream) = stream {
+                stop_requests.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).push(stream);
+            }
+            true
+        });
+        self.notifications = Some(service);
+    }
+
+    /// ストリームを開始し、録画中であることを通知する
+    ///
+    /// 通知サービスが設定されていない場合は、ユーザーが気付かないまま録画されないように開始しません。
+    pub(super) fn start(
+        &mut self,
+        context: &Arc<Mutex<SecurityContext>>,
+        source: CaptureSource,
+        description: &str,
+    ) -> Result<u64, CaptureError> {
+        let Some(service) = &self.notifications else {
+            return Err(CaptureError::NoNotificationService);
+        };
+        let id = self.next_stream_id;
+        self.next_stream_id += 1;
+
+        let requester = requester(&context.lock().unwrap_or_else(|poisoned| poisoned.into_inner()));
+        log::info!("画面の録画を開始しました: {} ({})", requester, description);
+        let body = format!("{}が{}を録画しています", requester, description);
+        // おやすみモードでも録画中であることは隠さない
+        let notification = service.send(
+            Notification::new("画面を録画しています".to_string(), body)
+                .with_category(NotificationCategory::Security)
+                .with_priority(NotificationPriority::Critical)
+                .from_app(requester.clone())
+                .add_default_action(STOP_ACTION, "停止")
+                .with_metadata(STREAM_METADATA.to_string(), id.to_string()),
+        );
+        self.streams.insert(id, CaptureStream {
+            source,
+            context: context.clone(),
+            last_bounds: None,
+            damage: Region::new(),
+            sequence: 0,
+            notification,
+        });
+        Ok(id)
+    }
+
+    /// ストリームを停止し、録画中の通知を閉じる
+    pub(super) fn stop(&mut self, id: u64) -> bool {
+        let Some(stream) = self.streams.remove(&id) else { return false };
+        if let Some(service) = &self.notifications {
+            service.dismiss(&stream.notification);
+        }
+        log::info!("画面の録画を停止しました: {}", id);
+        true
+    }
+
+    /// 通知から停止を求められたストリーム
+    pub(super) fn take_stop_requests(&self) -> Vec<u64> {
+        std::mem::take(&mut *self.stop_requests.lock().unwrap_or_else(|poisoned| poisoned.into_inner()))
+    }
+
+    /// 権限が取り消されたか、コンテキストが無効になったストリーム
+    pub(super) fn revoked_streams(&self) -> Vec<u64> {
+        self.streams.iter()
+            .filter(|(_, stream)| check_shared_permission(&stream.context).is_err())
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    pub(super) fn stream_ids(&self) -> Vec<u64> {
+        self.streams.keys().copied().collect()
+    }
+
+    pub(super) fn source(&self, id: u64) -> Option<CaptureSource> {
+        self.streams.get(&id).map(|stream| stream.source)
+    }
+
+    pub(super) fn is_recording(&self) -> bool {
+        !self.streams.is_empty()
+    }
+
+    /// コンポジターが集めた論理座標のダメージを出力と領域のストリームに蓄積
+    pub(super) fn add_damage(&mut self, damage: &Region) {
+        for stream in self.streams.values_mut() {
+            if !matches!(stream.source, CaptureSource::Window(_)) {
+                stream.damage.union(damage);
+            }
+        }
+    }
+
+    /// ウィンドウのダメージをそのウィンドウのストリームに蓄積
+    ///
+    /// ウィンドウのキャプチャは隠れた部分も含むので、手前のウィンドウで隠す前のダメージを使います。
+    pub(super) fn add_window_damage(&mut self, window_id: u64, damage: &Region) {
+        for stream in self.streams.values_mut() {
+            if stream.source == CaptureSource::Window(window_id) {
+                stream.damage.union(damage);
+            }
+        }
+    }
+
+    /// ストリームの次のフレームのダメージ（フレームの座標）を取り出す
+    ///
+    /// 最初のフレームと、範囲やスケールが変わった後のフレームはフレーム全体がダメージです。
+    /// 何も変わっていなければ `None` を返します。
+    pub(super) fn take_damage(&mut self, id: u64, bounds: Rectangle, scale: f64) -> Option<Vec<Rectangle>> {
+        let stream = self.streams.get_mut(&id)?;
+        let damage = std::mem::take(&mut stream.damage);
+        let rects: Vec<Rectangle> = if stream.last_bounds != Some((bounds, scale)) {
+            let (width, height) = frame_size(&bounds, scale);
+            vec![Rectangle::new(0, 0, width, height)]
+        } else {
+            damage.rects().iter().filter_map(|rect| to_frame(rect, &bounds, scale)).collect()
+        };
+        stream.last_bounds = Some((bounds, scale));
+        if rects.is_empty() {
+            return None;
+        }
+        stream.sequence += 1;
+        Some(rects)
+    }
+
+    pub(super) fn sequence(&self, id: u64) -> u64 {
+        self.streams.get(&id).map_or(0, |stream| stream.sequence)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::wayland_compositor::{Buffer, LumosCompositor, OutputDevice, PixelFormat};
+    use crate::core::system::security_context::{Credentials, SecurityLevel};
+
+    fn context(granted: bool) -> SecurityContext {
+        let mut credentials = Credentials::new("user".to_string(), SecurityLevel::Normal);
+        credentials.app_id = Some("org.lumos.Meeting".to_string());
+        let mut context = SecurityContext::new(credentials, None);
+        if granted {
+            context.grant_permission(SCREEN_CAPTURE_PERMISSION.to_string());
+        }
+        context
+    }
+
+    fn shared_context(granted: bool) -> Arc<Mutex<SecurityContext>> {
+        Arc::new(Mutex::new(context(granted)))
+    }
+
+    fn solid_window(id: u64, geometry: Rectangle, buffer_scale: u32, argb: u32) -> Window {
+        let (width, height) = (geometry.width * buffer_scale, geometry.height * buffer_scale);
+        let data = argb.to_le_bytes().repeat(width as usize * height as usize);
+        let mut window = Window::new(id, "Document", geometry);
+        window.attach_buffer(Arc::new(Buffer::new(width, height, PixelFormat::ARGB8888, data)));
+        window
+    }
+
+    fn compositor() -> LumosCompositor {
+        let mut compositor = LumosCompositor::new();
+        let mut output = OutputDevice::new(1, "DP-1", 16, 8, 60.0);
+        output.set_scale_factor(2.0);
+        compositor.add_output(output);
+        compositor.add_window(solid_window(1, Rectangle::new(0, 0, 4, 4), 2, 0xff_ff_00_00));
+        compositor.add_window(solid_window(2, Rectangle::new(2, 2, 4, 2), 1, 0xff_00_00_ff));
+        compositor
+    }
+
+    #[test]
+    fn test_one_shot_capture_requires_permission() {
+        let mut compositor = compositor();
+        let denied = compositor.capture(&context(false), CaptureSource::Output(1));
+        assert_eq!(denied, Err(CaptureError::PermissionDenied("org.lumos.Meeting".to_string())));
+
+        // 出力全体は出力のスケールの解像度になる
+        let frame = compositor.capture(&context(true), CaptureSource::Output(1)).unwrap();
+        assert_eq!((frame.framebuffer.width(), frame.framebuffer.height()), (16, 8));
+        assert_eq!(frame.damage, vec![Rectangle::new(0, 0, 16, 8)]);
+        assert_eq!(frame.framebuffer.pixel(0, 0), Some(0xff_ff_00_00));
+        assert_eq!(frame.framebuffer.pixel(5, 5), Some(0xff_00_00_ff));
+        assert_eq!(frame.framebuffer.pixel(15, 0), Some(0xff_00_00_00));
+
+        // 領域は論理ピクセルの解像度
+        let frame = compositor.capture(&context(true), CaptureSource::Region(Rectangle::new(1, 1, 3, 2))).unwrap();
+        assert_eq!(frame.framebuffer.pixels(), &[
+            0xff_ff_00_00, 0xff_ff_00_00, 0xff_ff_00_00,
+            0xff_ff_00_00, 0xff_00_00_ff, 0xff_00_00_ff,
+        ]);
+
+        // ウィンドウは隠れた部分も含めてバッファの解像度で
+        let frame = compositor.capture(&context(true), CaptureSource::Window(1)).unwrap();
+        assert_eq!((frame.framebuffer.width(), frame.framebuffer.height()), (8, 8));
+        assert!(frame.framebuffer.pixels().iter().all(|pixel| *pixel == 0xff_ff_00_00));
+
+        assert_eq!(compositor.capture(&context(true), CaptureSource::Window(9)), Err(CaptureError::UnknownWindow(9)));
+        let empty = CaptureSource::Region(Rectangle::new(0, 0, 0, 4));
+        assert_eq!(compositor.capture(&context(true), empty), Err(CaptureError::EmptyRegion));
+    }
+
+    #[test]
+    fn test_stream_reports_damage_and_notifies_while_recording() {
+        let service = Arc::new(NotificationService::new());
+        let mut compositor = compositor();
+        compositor.set_notification_service(service.clone());
+        assert!(compositor.start_capture_stream(&shared_context(false), CaptureSource::Output(1)).is_err());
+        assert!(service.get_all_notifications().is_empty());
+
+        let stream = compositor.start_capture_stream(&shared_context(true), CaptureSource::Output(1)).unwrap();
+        assert!(compositor.is_recording());
+        let notification = service.get_unread_notifications().pop().unwrap();
+        assert_eq!(notification.category, NotificationCategory::Security);
+        assert_eq!(notification.app_id.as_deref(), Some("org.lumos.Meeting"));
+
+        // 最初のフレームは全体がダメージ
+        let frames = compositor.poll_capture_streams();
+        assert_eq!(frames.len(), 1);
+        assert_eq!((frames[0].0, frames[0].1.sequence), (stream, 1));
+        assert_eq!(frames[0].1.damage, vec![Rectangle::new(0, 0, 16, 8)]);
+        // 何も変わらなければフレームは出ない
+        assert!(compositor.poll_capture_streams().is_empty());
+
+        // ウィンドウの一部の更新は、その領域だけがダメージになる
+        compositor.damage_window(2, Rectangle::new(0, 0, 1, 1));
+        let frames = compositor.poll_capture_streams();
+        assert_eq!(frames[0].1.sequence, 2);
+        assert_eq!(frames[0].1.damage, vec![Rectangle::new(4, 4, 2, 2)]);
+
+        // 通知の「停止」で録画を止め、通知を閉じる
+        assert!(service.trigger_action(&notification.id, STOP_ACTION));
+        assert!(compositor.poll_capture_streams().is_empty());
+        assert!(!compositor.is_recording());
+        assert!(service.get_unread_notifications().is_empty());
+
+        // キャプチャしているウィンドウが閉じられるとストリームも止まる
+        compositor.start_capture_stream(&shared_context(true), CaptureSource::Window(2)).unwrap();
+        assert_eq!(compositor.poll_capture_streams().len(), 1);
+        compositor.remove_window(2);
+        assert!(compositor.poll_capture_streams().is_empty());
+        assert!(!compositor.is_recording());
+    }
+
+    #[test]
+    fn test_stream_stops_when_permission_is_revoked() {
+        // 通知できなければ録画を始めない
+        let mut compositor = compositor();
+        let context = shared_context(true);
+        assert_eq!(
+            compositor.start_capture_stream(&context, CaptureSource::Output(1)),
+            Err(CaptureError::NoNotificationService)
+        );
+        assert!(!compositor.is_recording());
+
+        let service = Arc::new(NotificationService::new());
+        compositor.set_notification_service(service.clone());
+        compositor.start_capture_stream(&context, CaptureSource::Output(1)).unwrap();
+        assert_eq!(compositor.poll_capture_streams().len(), 1);
+
+        // 録画中に権限を取り消すと、次のフレームを出さずに停止して通知を閉じる
+        context.lock().unwrap().revoke_permission(SCREEN_CAPTURE_PERMISSION);
+        compositor.damage_window(1, Rectangle::new(0, 0, 1, 1));
+        assert!(compositor.poll_capture_streams().is_empty());
+        assert!(!compositor.is_recording());
+        assert!(service.get_unread_notifications().is_empty());
+    }
+}
@@ -7,6 +7,15 @@ use std::cell::RefCell;
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
+use super::color_management::{ColorSpace, IccError, IccProfile, NightLight, OutputColorTransform};
+use super::cpu_renderer::{CpuRenderer, Framebuffer};
+use super::damage::{OutputDamage, OutputRepaint, Region, RepaintStats, WindowDraw};
+use super::frame_scheduler::{FrameScheduler, PresentationFeedback, VblankResult, VrrRange};
+use super::output_manager::{OutputIdentity, OutputMode, OutputRotation, OutputState};
+use super::screen_capture::{self, CaptureError, CaptureFrame, CaptureSource, ScreenCapture};
+use crate::core::system::notification_service::NotificationService;
+use crate::core::system::security_context::SecurityContext;
+
 // 将来的にはWaylandクレートをインポート
 // use wayland_server::{Display, EventLoop, GlobalEvent, protocol::*, Client};
 
@@ -14,20 +23,207 @@ use std::time::{Duration, Instant};
 pub struct OutputDevice {
     id: u32,
     name: String,
+    /// モニターの識別情報（EDIDから取得）
+    identity: OutputIdentity,
     width: u32,
     height: u32,
     refresh_rate: f64,
+    /// 対応しているモード
+    modes: Vec<OutputMode>,
     scale_factor: f64,
     enabled: bool,
     primary: bool,
     physical_size: (u32, u32), // mm単位
     position: (i32, i32),      // 論理座標系での位置
+    vrr_range: Option<VrrRange>,
     transform: TransformMatrix,
     gamma_lut: Option<Vec<u16>>,
     color_profile: Option<ColorProfile>,
+    /// カラープロファイルと夜間モードから作った色変換
+    color_transform: OutputColorTransform,
+}
+
+impl OutputDevice {
+    pub fn new(id: u32, name: &str, width: u32, height: u32, refresh_rate: f64) -> Self {
+        Self {
+            id,
+            name: name.to_string(),
+            // EDIDが読めない場合はコネクター名で識別する
+            identity: OutputIdentity::new("", name, ""),
+            width,
+            height,
+            refresh_rate,
+            modes: vec![OutputMode::new(width, height, refresh_rate)],
+            scale_factor: 1.0,
+            enabled: true,
+            primary: false,
+            physical_size: (0, 0),
+            position: (0, 0),
+            vrr_range: None,
+            transform: TransformMatrix::identity(),
+            gamma_lut: None,
+            color_profile: None,
+            color_transform: OutputColorTransform::default(),
+        }
+    }
+    
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+    
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    
+    pub fn identity(&self) -> &OutputIdentity {
+        &self.identity
+    }
+    
+    pub fn set_identity(&mut self, identity: OutputIdentity) {
+        self.identity = identity;
+    }
+    
+    pub fn refresh_rate(&self) -> f64 {
+        self.refresh_rate
+    }
+    
+    /// 対応しているモード
+    pub fn modes(&self) -> &[OutputMode] {
+        &self.modes
+    }
+    
+    pub fn set_modes(&mut self, modes: Vec<OutputMode>) {
+        self.modes = modes;
+    }
+    
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+    
+    pub fn scale_factor(&self) -> f64 {
+        self.scale_factor
+    }
+    
+    /// 物理サイズ（mm）
+    pub fn physical_size(&self) -> (u32, u32) {
+        self.physical_size
+    }
+    
+    /// 可変リフレッシュレートに対応している場合の範囲
+    pub fn vrr_range(&self) -> Option<VrrRange> {
+        self.vrr_range
+    }
+    
+    pub fn set_vrr_range(&mut self, vrr_range: Option<VrrRange>) {
+        self.vrr_range = vrr_range;
+    }
+    
+    pub fn position(&self) -> (i32, i32) {
+        self.position
+    }
+    
+    pub fn set_position(&mut self, x: i32, y: i32) {
+        self.position = (x, y);
+    }
+    
+    pub fn set_scale_factor(&mut self, scale_factor: f64) {
+        self.scale_factor = scale_factor;
+    }
+    
+    pub fn set_transform(&mut self, transform: TransformMatrix) {
+        self.transform = transform;
+    }
+    
+    pub fn rotation(&self) -> OutputRotation {
+        OutputRotation::from_transform(&self.transform)
+    }
+    
+    /// 回転を適用した後のピクセルサイズ
+    pub fn transformed_size(&self) -> (u32, u32) {
+        if self.transform.is_transposed() {
+            (self.height, self.width)
+        } else {
+            (self.width, self.height)
+        }
+    }
+    
+    /// 論理座標系で出力が占める矩形
+    pub fn logical_rect(&self) -> Rectangle {
+        let (width, height) = self.transformed_size();
+        Rectangle::new(
+            self.position.0,
+            self.position.1,
+            (width as f64 / self.scale_factor).ceil() as u32,
+            (height as f64 / self.scale_factor).ceil() as u32,
+        )
+    }
+    
+    /// 論理座標の矩形を出力のバッファ座標に変換
+    ///
+    /// `outward` が真の場合は端数のピクセルを含むように、偽の場合は含まないように丸めます。
+    fn map_rect(&self, rect: &Rectangle, outward: bool) -> Option<Rectangle> {
+        let logical = self.logical_rect();
+        let clipped = rect.intersect(&logical)?;
+        let (width, height) = self.transformed_size();
+        let scale = |start: i32, end: i32, origin: i32, limit: u32| -> Option<(u32, u32)> {
+            let start = (start - origin) as f64 * self.scale_factor;
+            let end = (end - origin) as f64 * self.scale_factor;
+            let (start, end) = if outward {
+                (start.floor(), end.ceil())
+            } else {
+                (start.ceil(), end.floor())
+            };
+            let (start, end) = (start.max(0.0) as u32, (end as u32).min(limit));
+            (start < end).then_some((start, end - start))
+        };
+        let (x, w) = scale(clipped.x, clipped.right(), logical.x, width)?;
+        let (y, h) = scale(clipped.y, clipped.bottom(), logical.y, height)?;
+        Some(self.transform.transform_rect(&Rectangle::new(x as i32, y as i32, w, h), width, height))
+    }
+    
+    /// ダメージを出力のバッファ座標に変換（部分的に重なるピクセルも含む）
+    pub fn damage_to_output(&self, rect: &Rectangle) -> Option<Rectangle> {
+        self.map_rect(rect, true)
+    }
+    
+    /// 不透明な領域を出力のバッファ座標に変換（完全に覆われるピクセルのみ）
+    pub fn opaque_to_output(&self, rect: &Rectangle) -> Option<Rectangle> {
+        self.map_rect(rect, false)
+    }
+    
+    /// 出力のバッファ座標の点を論理座標に変換（`damage_to_output` の逆変換）
+    pub fn output_to_logical(&self, x: f32, y: f32) -> (f64, f64) {
+        let (width, height) = self.transformed_size();
+        let (origin_x, origin_y) = self.transform.origin(width, height);
+        let (x, y) = self.transform.inverse().apply(x + origin_x, y + origin_y);
+        (
+            self.position.0 as f64 + x as f64 / self.scale_factor,
+            self.position.1 as f64 + y as f64 / self.scale_factor,
+        )
+    }
+    
+    pub fn color_profile(&self) -> Option<&ColorProfile> {
+        self.color_profile.as_ref()
+    }
+    
+    /// ウィンドウの内容を出力の色に変換する
+    pub fn color_transform(&self) -> &OutputColorTransform {
+        &self.color_transform
+    }
+    
+    fn update_color_transform(&mut self, night_light: Option<NightLight>) {
+        let profile = self.color_profile.as_ref().map(|profile| &profile.parsed);
+        self.color_transform = OutputColorTransform::new(profile, night_light);
+    }
+    
+    /// バッファのピクセルサイズ
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
 }
 
 /// 変換行列
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct TransformMatrix {
     matrix: [[f32; 3]; 3],
 }
@@ -72,12 +268,88 @@ impl TransformMatrix {
             ],
         }
     }
+    
+    /// 点を変換
+    pub fn apply(&self, x: f32, y: f32) -> (f32, f32) {
+        let m = &self.matrix;
+        (m[0][0] * x + m[0][1] * y + m[0][2], m[1][0] * x + m[1][1] * y + m[1][2])
+    }
+    
+    /// 90度または270度の回転で幅と高さが入れ替わるかどうか
+    pub fn is_transposed(&self) -> bool {
+        self.matrix[0][1].abs() > self.matrix[0][0].abs()
+    }
+    
+    /// 逆変換
+    pub fn inverse(&self) -> Self {
+        let [[a, b, c], [d, e, f], _] = self.matrix;
+        let det = a * e - b * d;
+        let (ia, ib, id, ie) = (e / det, -b / det, -d / det, a / det);
+        Self {
+            matrix: [
+                [ia, ib, -(ia * c + ib * f)],
+                [id, ie, -(id * c + ie * f)],
+                [0.0, 0.0, 1.0],
+            ],
+        }
+    }
+    
+    /// 4つの角を変換した点を囲む範囲 (最小x, 最小y, 最大x, 最大y)
+    fn bounds(&self, x0: f32, y0: f32, x1: f32, y1: f32) -> (f32, f32, f32, f32) {
+        [(x0, y0), (x1, y0), (x0, y1), (x1, y1)].iter()
+            .map(|&(x, y)| self.apply(x, y))
+            .fold((f32::MAX, f32::MAX, f32::MIN, f32::MIN), |(min_x, min_y, max_x, max_y), (x, y)| {
+                (min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y))
+            })
+    }
+    
+    /// `width`x`height` の領域を変換した後の左上の点
+    fn origin(&self, width: u32, height: u32) -> (f32, f32) {
+        let (x, y, _, _) = self.bounds(0.0, 0.0, width as f32, height as f32);
+        (x, y)
+    }
+    
+    /// `width`x`height` の領域内の点を変換し、変換後の領域の左上を原点とした点を返す
+    pub fn transform_point(&self, x: f32, y: f32, width: u32, height: u32) -> (f32, f32) {
+        let (origin_x, origin_y) = self.origin(width, height);
+        let (x, y) = self.apply(x, y);
+        (x - origin_x, y - origin_y)
+    }
+    
+    /// `width`x`height` の領域内の矩形を変換し、変換後の領域の左上を原点とした矩形を返す
+    pub fn transform_rect(&self, rect: &Rectangle, width: u32, height: u32) -> Rectangle {
+        let (origin_x, origin_y) = self.origin(width, height);
+        let (x0, y0, x1, y1) = self.bounds(rect.x as f32, rect.y as f32, rect.right() as f32, rect.bottom() as f32);
+        Rectangle::new(
+            (x0 - origin_x).round() as i32,
+            (y0 - origin_y).round() as i32,
+            (x1 - x0).round() as u32,
+            (y1 - y0).round() as u32,
+        )
+    }
 }
 
 /// カラープロファイル
 pub struct ColorProfile {
     icc_profile: Vec<u8>,
-    // 色空間情報や他のカラーマネジメント関連データ
+    /// 解析したICCプロファイル
+    parsed: IccProfile,
+}
+
+impl ColorProfile {
+    /// ICCプロファイル（v2/v4の行列とトーンカーブ）を読み込む
+    pub fn from_icc(icc_profile: Vec<u8>) -> Result<Self, IccError> {
+        let parsed = IccProfile::parse(&icc_profile)?;
+        Ok(Self { icc_profile, parsed })
+    }
+    
+    pub fn icc_profile(&self) -> &[u8] {
+        &self.icc_profile
+    }
+    
+    pub fn profile(&self) -> &IccProfile {
+        &self.parsed
+    }
 }
 
 /// ウィンドウ構造体
@@ -95,6 +367,8 @@ pub struct Window {
     movable: bool,
     closable: bool,
     opacity: f32,
+    /// バッファの内容の色空間
+    color_space: ColorSpace,
     z_order: i32,
     parent: Option<Weak<RefCell<Window>>>,
     children: Vec<Rc<RefCell<Window>>>,
@@ -106,6 +380,184 @@ pub struct Window {
     last_frame_time: Instant,
 }
 
+impl Window {
+    pub fn new(id: u64, title: &str, geometry: Rectangle) -> Self {
+        Self {
+            id,
+            title: title.to_string(),
+            app_id: String::new(),
+            geometry,
+            visible: true,
+            focused: false,
+            minimized: false,
+            maximized: false,
+            fullscreen: false,
+            resizable: true,
+            movable: true,
+            closable: true,
+            opacity: 1.0,
+            color_space: ColorSpace::Srgb,
+            z_order: 0,
+            parent: None,
+            children: Vec::new(),
+            surface_id: id,
+            buffer: None,
+            damage: Vec::new(),
+            input_region: Vec::new(),
+            opacity_regions: Vec::new(),
+            last_frame_time: Instant::now(),
+        }
+    }
+    
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+    
+    pub fn geometry(&self) -> Rectangle {
+        self.geometry
+    }
+    
+    pub fn set_geometry(&mut self, geometry: Rectangle) {
+        self.geometry = geometry;
+    }
+    
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+    
+    pub fn set_title(&mut self, title: &str) {
+        self.title = title.to_string();
+    }
+    
+    pub fn app_id(&self) -> &str {
+        &self.app_id
+    }
+    
+    pub fn set_app_id(&mut self, app_id: &str) {
+        self.app_id = app_id.to_string();
+    }
+    
+    pub fn surface_id(&self) -> u64 {
+        self.surface_id
+    }
+    
+    pub fn set_surface_id(&mut self, surface_id: u64) {
+        self.surface_id = surface_id;
+    }
+    
+    pub fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+    
+    pub fn color_space(&self) -> ColorSpace {
+        self.color_space
+    }
+    
+    pub fn set_color_space(&mut self, color_space: ColorSpace) {
+        if self.color_space != color_space {
+            self.color_space = color_space;
+            self.damage_all();
+        }
+    }
+    
+    pub fn set_opacity(&mut self, opacity: f32) {
+        self.opacity = opacity.clamp(0.0, 1.0);
+    }
+    
+    pub fn opacity(&self) -> f32 {
+        self.opacity
+    }
+    
+    pub fn buffer(&self) -> Option<&Arc<Buffer>> {
+        self.buffer.as_ref()
+    }
+    
+    /// 合成の対象になるかどうか
+    pub fn is_mapped(&self) -> bool {
+        self.visible && !self.minimized
+    }
+    
+    /// サーフェス座標で更新された領域を追加
+    pub fn add_damage(&mut self, rect: Rectangle) {
+        self.damage.push(rect);
+    }
+    
+    /// サーフェス全体を更新された領域にする
+    pub fn damage_all(&mut self) {
+        self.damage.push(Rectangle::new(0, 0, self.geometry.width, self.geometry.height));
+    }
+    
+    /// 新しいバッファを設定
+    pub fn attach_buffer(&mut self, buffer: Arc<Buffer>) {
+        self.buffer = Some(buffer);
+        self.damage_all();
+    }
+    
+    /// ダメージを加えずにバッファを差し替える（クライアントが更新された領域を通知する場合）
+    pub fn replace_buffer(&mut self, buffer: Arc<Buffer>) {
+        self.buffer = Some(buffer);
+    }
+    
+    /// サーフェス座標で入力を受け付ける領域を設定（空の場合はウィンドウ全体）
+    pub fn set_input_region(&mut self, region: Vec<Rectangle>) {
+        self.input_region = region;
+    }
+    
+    /// 論理座標の点がウィンドウの入力領域に含まれるかどうか
+    pub fn accepts_input(&self, x: i32, y: i32) -> bool {
+        if !self.is_mapped() || !self.geometry.contains(x, y) {
+            return false;
+        }
+        let (x, y) = (x - self.geometry.x, y - self.geometry.y);
+        self.input_region.is_empty() || self.input_region.iter().any(|rect| rect.contains(x, y))
+    }
+    
+    /// サーフェス座標での領域ごとの不透明度を設定（1.0の領域は下のウィンドウを隠す）
+    pub fn set_opacity_regions(&mut self, regions: Vec<(Rectangle, f32)>) {
+        self.opacity_regions = regions;
+        // 下のウィンドウの見え方が変わるため、ウィンドウ全体を描画し直す
+        self.damage_all();
+    }
+    
+    /// 論理座標で下のウィンドウを完全に隠す領域
+    pub fn opaque_region(&self) -> Region {
+        let mut region = Region::new();
+        if !self.is_mapped() || self.opacity < 1.0 {
+            return region;
+        }
+        if self.buffer.as_ref().is_some_and(|buffer| !buffer.format.has_alpha()) {
+            region.add_rect(self.geometry);
+            return region;
+        }
+        for (rect, alpha) in &self.opacity_regions {
+            if *alpha >= 1.0 {
+                if let Some(rect) = rect.translated(self.geometry.x, self.geometry.y).intersect(&self.geometry) {
+                    region.add_rect(rect);
+                }
+            }
+        }
+        region
+    }
+}
+
+/// 前回のフレームで描画したときのウィンドウの状態
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct WindowSnapshot {
+    geometry: Rectangle,
+    mapped: bool,
+    opacity: f32,
+}
+
+impl WindowSnapshot {
+    fn of(window: &Window) -> Self {
+        Self {
+            geometry: window.geometry,
+            mapped: window.is_mapped(),
+            opacity: window.opacity,
+        }
+    }
+}
+
 /// バッファ構造体
 pub struct Buffer {
     width: u32,
@@ -117,6 +569,47 @@ pub struct Buffer {
     // DMAバッファや共有メモリ関連の情報
 }
 
+impl Buffer {
+    /// 行の間に余白のない共有メモリバッファを作成
+    pub fn new(width: u32, height: u32, format: PixelFormat, data: Vec<u8>) -> Self {
+        let stride = width * format.bytes_per_pixel();
+        Self::with_stride(width, height, format, stride, data)
+    }
+    
+    pub fn with_stride(width: u32, height: u32, format: PixelFormat, stride: u32, data: Vec<u8>) -> Self {
+        Self {
+            width,
+            height,
+            format,
+            stride,
+            data: Arc::new(data),
+            dmabuf_fd: None,
+        }
+    }
+    
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+    
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+    
+    pub fn format(&self) -> PixelFormat {
+        self.format
+    }
+    
+    /// ピクセルを乗算済みアルファの0xAARRGGBBとして読み込む（範囲外やデータ不足の場合は `None`）
+    pub fn pixel(&self, x: u32, y: u32) -> Option<u32> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let bpp = self.format.bytes_per_pixel() as usize;
+        let offset = y as usize * self.stride as usize + x as usize * bpp;
+        self.data.get(offset..offset + bpp).map(|bytes| self.format.decode(bytes))
+    }
+}
+
 /// ピクセルフォーマット
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PixelFormat {
@@ -130,8 +623,69 @@ pub enum PixelFormat {
     // 他のフォーマットも追加
 }
 
+/// 5ビットまたは6ビットの値を8ビットに拡張
+fn expand_bits(value: u16, bits: u32) -> u32 {
+    let value = value as u32;
+    (value << (8 - bits)) | (value >> (2 * bits - 8))
+}
+
+impl PixelFormat {
+    /// アルファチャンネルを持つかどうか
+    pub fn has_alpha(&self) -> bool {
+        matches!(self, PixelFormat::ARGB8888 | PixelFormat::RGBA8888 | PixelFormat::ABGR8888)
+    }
+    
+    pub fn bytes_per_pixel(&self) -> u32 {
+        match self {
+            PixelFormat::RGB565 => 2,
+            _ => 4,
+        }
+    }
+    
+    /// 1ピクセル分のバイト列を0xAARRGGBBに変換
+    ///
+    /// wl_shmと同じく、フォーマット名はリトルエンディアンの整数としての並び
+    /// （ARGB8888ならメモリ上はB, G, R, A）を表します。アルファのない形式は不透明です。
+    pub fn decode(&self, bytes: &[u8]) -> u32 {
+        if *self == PixelFormat::RGB565 {
+            let value = u16::from_le_bytes([bytes[0], bytes[1]]);
+            let r = expand_bits(value >> 11, 5);
+            let g = expand_bits((value >> 5) & 0x3f, 6);
+            let b = expand_bits(value & 0x1f, 5);
+            return 0xff00_0000 | (r << 16) | (g << 8) | b;
+        }
+        
+        let value = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        match self {
+            PixelFormat::ARGB8888 => value,
+            PixelFormat::XRGB8888 => value | 0xff00_0000,
+            PixelFormat::RGBA8888 => value.rotate_right(8),
+            PixelFormat::RGBX8888 => (value >> 8) | 0xff00_0000,
+            PixelFormat::ABGR8888 | PixelFormat::XBGR8888 => {
+                let alpha = if *self == PixelFormat::ABGR8888 { value & 0xff00_0000 } else { 0xff00_0000 };
+                alpha | ((value & 0xff) << 16) | (value & 0xff00) | ((value >> 16) & 0xff)
+            }
+            PixelFormat::RGB565 => unreachable!(),
+        }
+    }
+    
+    /// 0xAARRGGBBをこのフォーマットのバイト列に変換
+    pub fn encode(&self, argb: u32, out: &mut [u8]) {
+        let [b, g, r, a] = argb.to_le_bytes();
+        match self {
+            PixelFormat::ARGB8888 | PixelFormat::XRGB8888 => out[..4].copy_from_slice(&[b, g, r, a]),
+            PixelFormat::RGBA8888 | PixelFormat::RGBX8888 => out[..4].copy_from_slice(&[a, b, g, r]),
+            PixelFormat::ABGR8888 | PixelFormat::XBGR8888 => out[..4].copy_from_slice(&[r, g, b, a]),
+            PixelFormat::RGB565 => {
+                let value = ((r as u16 >> 3) << 11) | ((g as u16 >> 2) << 5) | (b as u16 >> 3);
+                out[..2].copy_from_slice(&value.to_le_bytes());
+            }
+        }
+    }
+}
+
 /// 矩形領域
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub struct Rectangle {
     pub x: i32,
     pub y: i32,
@@ -161,6 +715,35 @@ impl Rectangle {
             None
         }
     }
+    
+    pub fn right(&self) -> i32 {
+        self.x + self.width as i32
+    }
+    
+    pub fn bottom(&self) -> i32 {
+        self.y + self.height as i32
+    }
+    
+    pub fn area(&self) -> u64 {
+        self.width as u64 * self.height as u64
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+    
+    pub fn translated(&self, dx: i32, dy: i32) -> Rectangle {
+        Rectangle::new(self.x + dx, self.y + dy, self.width, self.height)
+    }
+    
+    /// 両方を囲む矩形
+    pub fn union(&self, other: &Rectangle) -> Rectangle {
+        let x1 = self.x.min(other.x);
+        let y1 = self.y.min(other.y);
+        let x2 = self.right().max(other.right());
+        let y2 = self.bottom().max(other.bottom());
+        Rectangle::new(x1, y1, (x2 - x1) as u32, (y2 - y1) as u32)
+    }
 }
 
 /// コンポジターの設定
@@ -193,6 +776,25 @@ pub struct LumosCompositor {
     config: CompositorConfig,
     render_queue: Vec<Rc<RefCell<Window>>>,
     damage_tracking: bool,
+    /// 出力ごとのダメージ
+    output_damage: HashMap<u32, OutputDamage>,
+    /// 前回のフレームを描画したときのウィンドウの状態
+    rendered: HashMap<u64, WindowSnapshot>,
+    /// 直前のフレームで再描画した内容と統計
+    repaints: Vec<OutputRepaint>,
+    repaint_stats: Vec<RepaintStats>,
+    /// ダメージがなく描画を省略したフレーム数
+    idle_frames: u64,
+    /// `allocate_window_id` で次に試すID
+    next_window_id: u64,
+    /// CPUで合成する場合のレンダラー
+    cpu_renderer: Option<CpuRenderer>,
+    /// 実際にかかった時間の代わりに記録する合成時間（仮想時計で動かす場合）
+    fixed_render_time: Option<Duration>,
+    scheduler: FrameScheduler,
+    /// 夜間モード（すべての出力に適用）
+    night_light: Option<NightLight>,
+    capture: ScreenCapture,
     last_frame_time: Instant,
     frame_count: u64,
     fps_counter: FpsCounter,
@@ -233,6 +835,7 @@ impl FpsCounter {
 }
 
 /// コンポジターイベント
+#[derive(Debug, Clone, PartialEq)]
 pub enum CompositorEvent {
     WindowCreated(u64),
     WindowDestroyed(u64),
@@ -247,9 +850,18 @@ pub enum CompositorEvent {
     OutputAdded(u32),
     OutputRemoved(u32),
     OutputEnabled(u32, bool),
+    /// 出力のモード・スケール・回転・位置のいずれかが変わった（ID、幅、高さ、リフレッシュレート）
     OutputModeChanged(u32, u32, u32, f64),
-    FramePresented,
-    FrameDropped,
+    /// フレームを表示した
+    FramePresented(PresentationFeedback),
+    /// 出力IDと、間に合わなかったフレームの目標の垂直同期の通し番号
+    FrameDropped(u32, u64),
+}
+
+impl Default for LumosCompositor {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl LumosCompositor {
@@ -265,6 +877,11 @@ impl LumosCompositor {
             tear_free: true,
             independent_updates: true,
         };
+        let scheduler = FrameScheduler::new(
+            config.vsync_enabled,
+            config.vrr_enabled,
+            Duration::from_millis(config.max_render_time_ms as u64),
+        );
         
         Self {
             windows: HashMap::new(),
@@ -273,6 +890,17 @@ impl LumosCompositor {
             config,
             render_queue: Vec::new(),
             damage_tracking: true,
+            output_damage: HashMap::new(),
+            rendered: HashMap::new(),
+            repaints: Vec::new(),
+            repaint_stats: Vec::new(),
+            idle_frames: 0,
+            next_window_id: 1,
+            cpu_renderer: None,
+            fixed_render_time: None,
+            scheduler,
+            night_light: None,
+            capture: ScreenCapture::new(),
             last_frame_time: Instant::now(),
             frame_count: 0,
             fps_counter: FpsCounter::new(100),
@@ -307,7 +935,7 @@ impl LumosCompositor {
     pub fn remove_window(&mut self, id: u64) -> bool {
         if let Some(window) = self.windows.remove(&id) {
             // レンダーキューからも削除
-            self.render_queue.retain(|w| Rc::ptr_eq(w, &window) == false);
+            self.render_queue.retain(|w| !Rc::ptr_eq(w, &window));
             
             // イベント発火
             self.emit_event(CompositorEvent::WindowDestroyed(id));
@@ -330,6 +958,31 @@ impl LumosCompositor {
         }
     }
     
+    /// ウィンドウを取得
+    pub fn get_window(&self, id: u64) -> Option<Rc<RefCell<Window>>> {
+        self.windows.get(&id).cloned()
+    }
+    
+    /// 使われていないウィンドウIDを割り当てる
+    pub fn allocate_window_id(&mut self) -> u64 {
+        // 削除したウィンドウのIDは再利用しない（描画済みの状態と取り違えないように）
+        while self.windows.contains_key(&self.next_window_id) || self.rendered.contains_key(&self.next_window_id) {
+            self.next_window_id += 1;
+        }
+        let id = self.next_window_id;
+        self.next_window_id += 1;
+        id
+    }
+    
+    /// 論理座標の点で入力を受け付ける最前面のウィンドウ
+    pub fn window_at(&self, x: f64, y: f64) -> Option<u64> {
+        let (x, y) = (x.floor() as i32, y.floor() as i32);
+        self.render_queue.iter().rev()
+            .map(|window| window.borrow())
+            .find(|window| window.accepts_input(x, y))
+            .map(|window| window.id)
+    }
+    
     /// 最前面のウィンドウを取得
     fn get_topmost_window(&self) -> Option<Rc<RefCell<Window>>> {
         self.render_queue.last().cloned()
@@ -358,30 +1011,513 @@ impl LumosCompositor {
         }
     }
     
+    /// ウィンドウを最前面に移動
+    pub fn raise_window(&mut self, id: u64) -> bool {
+        let Some(index) = self.render_queue.iter().position(|w| w.borrow().id == id) else {
+            return false;
+        };
+        let window = self.render_queue.remove(index);
+        window.borrow_mut().damage_all();
+        self.render_queue.push(window);
+        true
+    }
+    
+    /// ウィンドウのサーフェス座標で更新された領域を追加
+    pub fn damage_window(&mut self, id: u64, rect: Rectangle) -> bool {
+        match self.windows.get(&id) {
+            Some(window) => {
+                window.borrow_mut().add_damage(rect);
+                true
+            }
+            None => false,
+        }
+    }
+    
+    /// 出力全体を次のフレームで描画し直す
+    pub fn damage_output(&mut self, id: u32) -> bool {
+        match self.output_damage.get_mut(&id) {
+            Some(damage) => {
+                damage.damage_all();
+                true
+            }
+            None => false,
+        }
+    }
+    
+    /// 次に描画するバッファの経過フレーム数を設定（0=内容が不明）
+    ///
+    /// バックエンドはバッファを取得するたびに、EGL_EXT_buffer_ageなどで得た値を設定します。
+    pub fn set_buffer_age(&mut self, output_id: u32, age: u32) -> bool {
+        match self.output_damage.get_mut(&output_id) {
+            Some(damage) => {
+                damage.set_buffer_age(age);
+                true
+            }
+            None => false,
+        }
+    }
+    
+    /// ダメージトラッキングの有効/無効（無効の場合は毎フレーム全体を描画）
+    pub fn set_damage_tracking(&mut self, enabled: bool) {
+        self.damage_tracking = enabled;
+    }
+    
+    /// ウィンドウの変更とダメージを集め、出力ごとのダメージに加える
+    fn collect_damage(&mut self) {
+        // 論理座標でのダメージ
+        let mut damage = Region::new();
+        // 手前のウィンドウの不透明な領域（これに隠れるダメージは見えない）
+        let mut occluded = Region::new();
+        let mut current = HashMap::new();
+        
+        for window in self.render_queue.iter().rev() {
+            let mut win = window.borrow_mut();
+            let snapshot = WindowSnapshot::of(&win);
+            let surface_damage = std::mem::take(&mut win.damage);
+            
+            let mut window_damage = Region::new();
+            match self.rendered.get(&win.id) {
+                Some(previous) if *previous == snapshot => {
+                    if snapshot.mapped {
+                        for rect in surface_damage {
+                            if let Some(rect) = rect.translated(win.geometry.x, win.geometry.y).intersect(&win.geometry) {
+                                window_damage.add_rect(rect);
+                            }
+                        }
+                    }
+                }
+                previous => {
+                    // 移動、サイズ変更、表示状態の変更は前後の領域全体がダメージになる
+                    if let Some(previous) = previous.filter(|previous| previous.mapped) {
+                        window_damage.add_rect(previous.geometry);
+                    }
+                    if snapshot.mapped {
+                        window_damage.add_rect(snapshot.geometry);
+                    }
+                }
+            }
+            if self.capture.is_recording() {
+                self.capture.add_window_damage(win.id, &window_damage);
+            }
+            window_damage.subtract(&occluded);
+            damage.union(&window_damage);
+            occluded.union(&win.opaque_region());
+            current.insert(win.id, snapshot);
+        }
+        
+        // 削除されたウィンドウがあった領域
+        for (id, previous) in &self.rendered {
+            if previous.mapped && !current.contains_key(id) {
+                damage.add_rect(previous.geometry);
+            }
+        }
+        self.rendered = current;
+        self.capture.add_damage(&damage);
+        
+        for (id, output) in &self.outputs {
+            let Some(output_damage) = self.output_damage.get_mut(id) else { continue };
+            for rect in damage.rects() {
+                if let Some(rect) = output.damage_to_output(rect) {
+                    output_damage.add_rect(rect);
+                }
+            }
+        }
+    }
+    
+    /// 出力の再描画領域に描画するウィンドウを決める
+    fn plan_repaint(&self, output: &OutputDevice, region: Region) -> (OutputRepaint, RepaintStats) {
+        let mut remaining = region.clone();
+        let mut draws = Vec::new();
+        let mut culled = 0;
+        
+        // 手前から順に、不透明な領域で隠れた部分を再描画領域から除いていく
+        for window in self.render_queue.iter().rev() {
+            let win = window.borrow();
+            if !win.is_mapped() {
+                continue;
+            }
+            let Some(bounds) = output.damage_to_output(&win.geometry) else { continue };
+            let clip = remaining.intersect_rect(&bounds);
+            if clip.is_empty() {
+                culled += 1;
+                continue;
+            }
+            for rect in win.opaque_region().rects() {
+                if let Some(rect) = output.opaque_to_output(rect) {
+                    remaining.subtract_rect(&rect);
+                }
+            }
+            draws.push(WindowDraw { window_id: win.id, clip });
+        }
+        draws.reverse();
+        
+        let (width, height) = (output.width, output.height);
+        let stats = RepaintStats {
+            output_id: output.id,
+            repainted_area: region.area(),
+            output_area: width as u64 * height as u64,
+            rects: region.rects().len(),
+            windows_drawn: draws.len(),
+            windows_culled: culled,
+            render_time: Duration::ZERO,
+        };
+        let repaint = OutputRepaint {
+            output_id: output.id,
+            region,
+            draws,
+            background: remaining,
+        };
+        (repaint, stats)
+    }
+    
     /// フレームの描画
-    pub fn render_frame(&mut self) {
+    ///
+    /// 前回のフレームから変更された領域だけを再描画します。どの出力にもダメージがない
+    /// 場合は何も描画せずに `false` を返すため、アイドル状態のデスクトップでは描画が
+    /// 止まります。フレームスケジューラを通さないため、表示のフィードバックは発火しません
+    /// （メインループでは `dispatch_frames` を使います）。
+    pub fn render_frame(&mut self) -> bool {
+        let mut output_ids: Vec<u32> = self.outputs.keys().copied().collect();
+        output_ids.sort();
+        self.render_outputs(&output_ids)
+    }
+    
+    /// 指定した出力だけフレームを描画（出力ごとに垂直同期のタイミングが異なる場合）
+    ///
+    /// ほかの出力のダメージは次に描画するときまで保持されます。
+    pub fn render_outputs(&mut self, output_ids: &[u32]) -> bool {
         let now = Instant::now();
-        let frame_delta = now.duration_since(self.last_frame_time);
         
-        // レンダリングの開始
+        self.collect_damage();
+        if !self.damage_tracking {
+            for damage in self.output_damage.values_mut() {
+                damage.damage_all();
+            }
+        }
         
-        // ウィンドウの描画順に従ってレンダリング
-        for window in &self.render_queue {
-            let win = window.borrow();
-            if win.visible && !win.minimized {
-                // ウィンドウのレンダリング（実際のレンダリングロジックはここに）
+        self.repaints.clear();
+        self.repaint_stats.clear();
+        for &id in output_ids {
+            let Some(output) = self.outputs.get(&id) else { continue };
+            if !output.enabled {
+                continue;
+            }
+            let Some(damage) = self.output_damage.get(&id) else { continue };
+            if !damage.has_damage() {
+                continue;
+            }
+            
+            let render_start = Instant::now();
+            let (repaint, mut stats) = self.plan_repaint(output, damage.repaint_region());
+            if let Some(renderer) = self.cpu_renderer.as_mut() {
+                renderer.render(output, &repaint, &self.windows);
+            }
+            stats.render_time = self.fixed_render_time.unwrap_or_else(|| render_start.elapsed());
+            
+            if let Some(damage) = self.output_damage.get_mut(&id) {
+                damage.commit();
             }
+            self.repaints.push(repaint);
+            self.repaint_stats.push(stats);
         }
         
-        // フレームの完了とVSync
+        if self.repaints.is_empty() {
+            self.idle_frames += 1;
+            return false;
+        }
         
-        // 統計情報の更新
+        // 統計情報の更新（表示のフィードバックは `present` で発火する）
         self.frame_count += 1;
         self.fps_counter.add_frame(now);
         self.last_frame_time = now;
+        true
+    }
+    
+    /// ダメージのある出力のフレームを予約し、次に合成を始める時刻を返す
+    ///
+    /// 時刻はバックエンドのモノトニック時計での値です。合成はフレームスケジューラが
+    /// 予測した合成時間だけ垂直同期より前に始まるため、それまでに届いた入力が反映されます。
+    pub fn schedule_frames(&mut self, now: Duration) -> Option<Duration> {
+        self.collect_damage();
+        let mut ids: Vec<u32> = self.output_damage.iter()
+            .filter(|(id, damage)| damage.has_damage() && self.outputs.get(id).is_some_and(|output| output.enabled))
+            .map(|(id, _)| *id)
+            .collect();
+        ids.sort_unstable();
+        for id in ids {
+            self.scheduler.request_frame(id, now);
+        }
+        self.scheduler.next_wakeup()
+    }
+    
+    /// 合成を始める時刻になった出力を描画
+    pub fn render_due(&mut self, now: Duration) -> bool {
+        let due = self.scheduler.due_outputs(now);
+        if due.is_empty() {
+            return false;
+        }
+        let rendered = self.render_outputs(&due);
+        for id in due {
+            match self.repaint_stats.iter().find(|stats| stats.output_id == id) {
+                Some(stats) => self.scheduler.frame_rendered(id, now, stats.render_time),
+                None => self.scheduler.cancel(id),
+            }
+        }
+        // 垂直同期なしではすぐにバッファを切り替える
+        if rendered && !self.scheduler.vsync() {
+            let ids: Vec<u32> = self.repaint_stats.iter().map(|stats| stats.output_id).collect();
+            for id in ids {
+                self.present(id, now);
+            }
+        }
+        rendered
+    }
+    
+    /// 垂直同期を通知するバックエンドがない場合にフレームを進め、次に呼び出す時刻を返す
+    ///
+    /// ダメージのある出力のフレームを予約して合成を始める時刻になったものを描画し、
+    /// リフレッシュ間隔ごとに表示したものとして `FramePresented` を発火します。
+    pub fn dispatch_frames(&mut self, now: Duration) -> Option<Duration> {
+        self.schedule_frames(now);
+        self.render_due(now);
         
-        // イベント発火
-        self.emit_event(CompositorEvent::FramePresented);
+        let mut next_vblank: Option<Duration> = None;
+        for id in self.output_ids() {
+            let interval = Duration::from_secs_f64(1.0 / self.outputs[&id].refresh_rate.max(1.0));
+            let due = self.scheduler.last_vblank(id).is_none_or(|(last, _)| now >= last + interval);
+            if due {
+                self.present(id, now);
+            }
+            let vblank = self.scheduler.last_vblank(id).map_or(now, |(last, _)| last + interval);
+            next_vblank = Some(next_vblank.map_or(vblank, |next| next.min(vblank)));
+        }
+        
+        [self.schedule_frames(now), next_vblank].into_iter().flatten().min()
+    }
+    
+    /// 出力の垂直同期を処理し、表示したフレームのフィードバックを発火する（バックエンドが呼び出す）
+    pub fn present(&mut self, output_id: u32, time: Duration) -> VblankResult {
+        let result = self.scheduler.vblank(output_id, time);
+        if let Some(sequence) = result.missed {
+            self.emit_event(CompositorEvent::FrameDropped(output_id, sequence));
+        }
+        if let Some(feedback) = result.presented {
+            self.emit_event(CompositorEvent::FramePresented(feedback));
+        }
+        result
+    }
+    
+    pub fn frame_scheduler(&self) -> &FrameScheduler {
+        &self.scheduler
+    }
+    
+    pub fn set_vsync_enabled(&mut self, enabled: bool) {
+        self.config.vsync_enabled = enabled;
+        self.scheduler.set_vsync(enabled);
+    }
+    
+    pub fn set_vrr_enabled(&mut self, enabled: bool) {
+        self.config.vrr_enabled = enabled;
+        self.scheduler.set_vrr(enabled);
+    }
+    
+    /// 合成時間の予測の上限（0の場合はリフレッシュ間隔）
+    pub fn set_max_render_time_ms(&mut self, max_render_time_ms: u32) {
+        self.config.max_render_time_ms = max_render_time_ms;
+        self.scheduler.set_max_render_time(Duration::from_millis(max_render_time_ms as u64));
+    }
+    
+    /// 出力の可変リフレッシュレートの範囲を設定
+    pub fn set_output_vrr_range(&mut self, id: u32, vrr_range: Option<VrrRange>) -> bool {
+        let Some(output) = self.outputs.get_mut(&id) else { return false };
+        output.set_vrr_range(vrr_range);
+        self.scheduler.set_vrr_range(id, vrr_range);
+        true
+    }
+    
+    /// 出力のカラープロファイルを設定
+    pub fn set_output_color_profile(&mut self, id: u32, profile: Option<ColorProfile>) -> bool {
+        let night_light = self.night_light;
+        let Some(output) = self.outputs.get_mut(&id) else { return false };
+        output.color_profile = profile;
+        output.update_color_transform(night_light);
+        self.damage_output(id)
+    }
+    
+    /// 夜間モードを設定（`None` で解除）
+    ///
+    /// 動的テーマの色温度に合わせて呼び出すことを想定しています。
+    pub fn set_night_light(&mut self, night_light: Option<NightLight>) {
+        if self.night_light == night_light {
+            return;
+        }
+        self.night_light = night_light;
+        for id in self.output_ids() {
+            if let Some(output) = self.outputs.get_mut(&id) {
+                output.update_color_transform(night_light);
+            }
+            self.damage_output(id);
+        }
+    }
+    
+    pub fn night_light(&self) -> Option<NightLight> {
+        self.night_light
+    }
+    
+    /// 録画中であることを知らせる通知サービスを設定
+    pub fn set_notification_service(&mut self, service: Arc<NotificationService>) {
+        self.capture.set_notification_service(service);
+    }
+    
+    /// 出力・領域・ウィンドウを1回だけキャプチャ
+    pub fn capture(&mut self, context: &SecurityContext, source: CaptureSource) -> Result<CaptureFrame, CaptureError> {
+        screen_capture::check_permission(context)?;
+        let (bounds, scale) = self.capture_bounds(source)?;
+        let framebuffer = self.render_capture(source, bounds, scale);
+        let damage = vec![Rectangle::new(0, 0, framebuffer.width(), framebuffer.height())];
+        Ok(CaptureFrame { source, sequence: 0, framebuffer, damage })
+    }
+    
+    /// 画面の録画（画面共有など）を開始し、ストリームIDを返す
+    ///
+    /// 録画中は通知サービスに通知を出し、通知の「停止」で録画を止められます。通知サービスが
+    /// 設定されていない場合は開始しません。フレームは `poll_capture_streams` で受け取ります。
+    /// コンテキストは録画中も参照し、権限が取り消された時点で録画を停止します。
+    pub fn start_capture_stream(
+        &mut self,
+        context: &Arc<Mutex<SecurityContext>>,
+        source: CaptureSource,
+    ) -> Result<u64, CaptureError> {
+        screen_capture::check_shared_permission(context)?;
+        self.capture_bounds(source)?;
+        let description = match source {
+            CaptureSource::Output(id) => format!("画面「{}」", self.outputs[&id].name()),
+            CaptureSource::Region(_) => "画面の一部".to_string(),
+            CaptureSource::Window(id) => format!("ウィンドウ「{}」", self.windows[&id].borrow().title()),
+        };
+        // 録画を始める前のダメージを含めないように、ここまでの変更を集めておく
+        if !self.capture.is_recording() {
+            self.collect_damage();
+        }
+        self.capture.start(context, source, &description)
+    }
+    
+    /// 画面の録画を停止
+    pub fn stop_capture_stream(&mut self, id: u64) -> bool {
+        self.capture.stop(id)
+    }
+    
+    pub fn is_recording(&self) -> bool {
+        self.capture.is_recording()
+    }
+    
+    /// 前回から変わったストリームの次のフレームを返す
+    ///
+    /// 出力が外されたりウィンドウが閉じられたりしたストリームと、権限が取り消された
+    /// ストリームは停止します。
+    pub fn poll_capture_streams(&mut self) -> Vec<(u64, CaptureFrame)> {
+        for id in self.capture.take_stop_requests() {
+            self.capture.stop(id);
+        }
+        for id in self.capture.revoked_streams() {
+            log::warn!("権限が取り消されたため画面の録画を停止します: {}", id);
+            self.capture.stop(id);
+        }
+        if !self.capture.is_recording() {
+            return Vec::new();
+        }
+        self.collect_damage();
+        
+        let mut frames = Vec::new();
+        for id in self.capture.stream_ids() {
+            let Some(source) = self.capture.source(id) else { continue };
+            let Ok((bounds, scale)) = self.capture_bounds(source) else {
+                self.capture.stop(id);
+                continue;
+            };
+            let Some(damage) = self.capture.take_damage(id, bounds, scale) else { continue };
+            let framebuffer = self.render_capture(source, bounds, scale);
+            let sequence = self.capture.sequence(id);
+            frames.push((id, CaptureFrame { source, sequence, framebuffer, damage }));
+        }
+        frames
+    }
+    
+    /// キャプチャする論理座標の範囲とスケール
+    fn capture_bounds(&self, source: CaptureSource) -> Result<(Rectangle, f64), CaptureError> {
+        let (bounds, scale) = match source {
+            CaptureSource::Output(id) => {
+                // 無効な出力には何も表示されていない
+                let output = self.outputs.get(&id).filter(|output| output.enabled).ok_or(CaptureError::UnknownOutput(id))?;
+                (output.logical_rect(), output.scale_factor)
+            }
+            CaptureSource::Region(rect) => (rect, 1.0),
+            CaptureSource::Window(id) => {
+                let window = self.windows.get(&id).ok_or(CaptureError::UnknownWindow(id))?.borrow();
+                // バッファスケールのあるウィンドウはバッファの解像度でキャプチャする
+                let scale = match window.buffer() {
+                    Some(buffer) if window.geometry.width > 0 => buffer.width() as f64 / window.geometry.width as f64,
+                    _ => 1.0,
+                };
+                (window.geometry, scale)
+            }
+        };
+        if bounds.is_empty() {
+            return Err(CaptureError::EmptyRegion);
+        }
+        Ok((bounds, scale))
+    }
+    
+    fn render_capture(&self, source: CaptureSource, bounds: Rectangle, scale: f64) -> Framebuffer {
+        match source {
+            // ウィンドウの外側は透明にする
+            CaptureSource::Window(id) => screen_capture::render(self.render_queue.iter(), bounds, scale, Some(id), 0),
+            _ => screen_capture::render(self.render_queue.iter(), bounds, scale, None, 0xff00_0000),
+        }
+    }
+    
+    /// CPUでの合成を有効にする
+    ///
+    /// GPUを使わずに出力ごとのフレームバッファへ合成します。GPUバックエンドとの比較や
+    /// テストの参照として使います。
+    pub fn enable_cpu_rendering(&mut self) {
+        self.cpu_renderer = Some(CpuRenderer::new());
+        // フレームバッファは1枚なので直前のフレームの内容が残っている
+        for damage in self.output_damage.values_mut() {
+            damage.set_buffer_age(1);
+            damage.damage_all();
+        }
+    }
+    
+    /// 合成時間を実際の経過時間ではなく固定の値にする（`None` で実際の経過時間）
+    ///
+    /// フレームスケジューラの予測と表示時刻が実行環境の速さに左右されなくなります。
+    pub fn set_fixed_render_time(&mut self, render_time: Option<Duration>) {
+        self.fixed_render_time = render_time;
+    }
+    
+    pub fn cpu_renderer_mut(&mut self) -> Option<&mut CpuRenderer> {
+        self.cpu_renderer.as_mut()
+    }
+    
+    /// CPUで合成した出力のフレームバッファ
+    pub fn framebuffer(&self, output_id: u32) -> Option<&Framebuffer> {
+        self.cpu_renderer.as_ref()?.framebuffer(output_id)
+    }
+    
+    /// 直前のフレームで再描画した内容（出力ごと）
+    pub fn last_repaints(&self) -> &[OutputRepaint] {
+        &self.repaints
+    }
+    
+    /// 直前のフレームで再描画した面積などの統計（出力ごと）
+    pub fn last_repaint_stats(&self) -> &[RepaintStats] {
+        &self.repaint_stats
+    }
+    
+    /// ダメージがなく描画を省略したフレーム数
+    pub fn idle_frame_count(&self) -> u64 {
+        self.idle_frames
     }
     
     /// イベントハンドラの登録
@@ -404,12 +1540,13 @@ impl LumosCompositor {
     /// メインループの実行
     pub fn run(&mut self) -> Result<(), String> {
         self.running = true;
+        let clock = Instant::now();
         
         while self.running {
             // イベントの処理
             
-            // フレームの描画
-            self.render_frame();
+            // フレームの予約、描画、表示
+            self.dispatch_frames(clock.elapsed());
             
             // 適切なタイミングで休止
             std::thread::sleep(Duration::from_millis(1));
@@ -428,9 +1565,22 @@ impl LumosCompositor {
         self.fps_counter.get_fps()
     }
     
+    /// バッファの経過フレーム数はバックエンドが設定するまでスワップチェーンの長さとみなす
+    fn initial_buffer_age(&self) -> u32 {
+        match (&self.cpu_renderer, self.config.triple_buffering) {
+            (Some(_), _) => 1,
+            (None, true) => 3,
+            (None, false) => 2,
+        }
+    }
+    
     /// 出力デバイスの追加
-    pub fn add_output(&mut self, output: OutputDevice) -> u32 {
+    pub fn add_output(&mut self, mut output: OutputDevice) -> u32 {
         let id = output.id;
+        output.update_color_transform(self.night_light);
+        let buffer_age = self.initial_buffer_age();
+        self.output_damage.insert(id, OutputDamage::new(output.width, output.height, buffer_age));
+        self.scheduler.add_output(id, output.refresh_rate, output.vrr_range);
         self.outputs.insert(id, output);
         
         // イベント発火
@@ -439,9 +1589,67 @@ impl LumosCompositor {
         id
     }
     
+    pub fn get_output(&self, id: u32) -> Option<&OutputDevice> {
+        self.outputs.get(&id)
+    }
+    
+    /// 出力のモード・スケール・回転・位置・有効状態を変更し、変わった場合は真を返す
+    ///
+    /// 値は検証しません。複数の出力をまとめて変更する場合は `OutputManager` を使います。
+    pub fn configure_output(&mut self, id: u32, state: &OutputState) -> bool {
+        let buffer_age = self.initial_buffer_age();
+        let Some(output) = self.outputs.get_mut(&id) else { return false };
+        let previous = OutputState::of(output);
+        if previous == *state {
+            return false;
+        }
+        
+        output.width = state.mode.width;
+        output.height = state.mode.height;
+        output.refresh_rate = state.mode.refresh_rate;
+        output.scale_factor = state.scale;
+        output.transform = state.rotation.transform();
+        output.position = state.position;
+        output.enabled = state.enabled;
+        
+        if !previous.mode.matches(&state.mode) {
+            self.output_damage.insert(id, OutputDamage::new(state.mode.width, state.mode.height, buffer_age));
+            self.scheduler.set_refresh_rate(id, state.mode.refresh_rate);
+        }
+        if let Some(damage) = self.output_damage.get_mut(&id) {
+            damage.damage_all();
+        }
+        if !state.enabled {
+            self.scheduler.cancel(id);
+        }
+        
+        // イベント発火
+        if previous.enabled != state.enabled {
+            self.emit_event(CompositorEvent::OutputEnabled(id, state.enabled));
+        }
+        let previous = OutputState { enabled: state.enabled, ..previous };
+        if previous != *state {
+            let mode = state.mode;
+            self.emit_event(CompositorEvent::OutputModeChanged(id, mode.width, mode.height, mode.refresh_rate));
+        }
+        true
+    }
+    
+    /// 出力デバイスのID（昇順）
+    pub fn output_ids(&self) -> Vec<u32> {
+        let mut ids: Vec<u32> = self.outputs.keys().copied().collect();
+        ids.sort_unstable();
+        ids
+    }
+    
     /// 出力デバイスの削除
     pub fn remove_output(&mut self, id: u32) -> bool {
         if self.outputs.remove(&id).is_some() {
+            self.output_damage.remove(&id);
+            self.scheduler.remove_output(id);
+            if let Some(renderer) = self.cpu_renderer.as_mut() {
+                renderer.remove_output(id);
+            }
             // イベント発火
             self.emit_event(CompositorEvent::OutputRemoved(id));
             true
@@ -474,4 +1682,140 @@ mod tests {
         
         assert!(r1.intersect(&r2).is_none());
     }
+    
+    fn compositor_with_output(width: u32, height: u32) -> LumosCompositor {
+        let mut compositor = LumosCompositor::new();
+        compositor.add_output(OutputDevice::new(1, "HEADLESS-1", width, height, 60.0));
+        compositor.set_buffer_age(1, 1);
+        compositor
+    }
+    
+    fn opaque_window(id: u64, geometry: Rectangle) -> Window {
+        let mut window = Window::new(id, "test", geometry);
+        window.set_opacity_regions(vec![(Rectangle::new(0, 0, geometry.width, geometry.height), 1.0)]);
+        window
+    }
+    
+    #[test]
+    fn test_idle_frames_are_skipped() {
+        let mut compositor = compositor_with_output(200, 100);
+        compositor.add_window(Window::new(1, "terminal", Rectangle::new(10, 10, 50, 50)));
+        
+        assert!(compositor.render_frame());
+        assert_eq!(compositor.last_repaint_stats()[0].repainted_area, 200 * 100);
+        assert_eq!(compositor.last_repaints()[0].draws.len(), 1);
+        
+        // 変更がなければ描画しない
+        assert!(!compositor.render_frame());
+        assert_eq!(compositor.idle_frame_count(), 1);
+        assert_eq!(compositor.frame_count, 1);
+        
+        // ウィンドウのダメージはサーフェス座標からの変換後の領域だけを描画
+        compositor.damage_window(1, Rectangle::new(5, 5, 10, 4));
+        assert!(compositor.render_frame());
+        let repaint = &compositor.last_repaints()[0];
+        assert_eq!(repaint.region.rects(), &[Rectangle::new(15, 15, 10, 4)]);
+        assert_eq!(compositor.last_repaint_stats()[0].repainted_area, 40);
+        
+        // 移動すると前後の領域を描画
+        compositor.windows[&1].borrow_mut().set_geometry(Rectangle::new(100, 10, 50, 50));
+        assert!(compositor.render_frame());
+        assert_eq!(compositor.last_repaint_stats()[0].repainted_area, 2 * 50 * 50);
+        
+        // 削除するとあった領域を描画
+        compositor.remove_window(1);
+        assert!(compositor.render_frame());
+        assert_eq!(compositor.last_repaint_stats()[0].repainted_area, 50 * 50);
+        assert!(compositor.last_repaints()[0].draws.is_empty());
+    }
+    
+    #[test]
+    fn test_dispatch_frames_emits_presentation_feedback() {
+        let mut compositor = compositor_with_output(64, 64);
+        let presented = Rc::new(RefCell::new(Vec::new()));
+        let sink = presented.clone();
+        compositor.add_event_handler(move |event: &CompositorEvent| {
+            if let CompositorEvent::FramePresented(feedback) = event {
+                sink.borrow_mut().push(*feedback);
+            }
+            true
+        });
+        compositor.add_window(Window::new(1, "terminal", Rectangle::new(0, 0, 32, 32)));
+        
+        // 予約した時刻に合成し、次のリフレッシュで表示する
+        let mut now = Duration::ZERO;
+        for _ in 0..10 {
+            if !presented.borrow().is_empty() {
+                break;
+            }
+            now = compositor.dispatch_frames(now).expect("出力があれば次の時刻がある");
+        }
+        let feedback = presented.borrow()[0];
+        assert_eq!(feedback.output_id, 1);
+        assert!(feedback.vsync);
+        assert_eq!(compositor.frame_count, 1);
+        
+        // ダメージがなければ合成しない
+        let next = compositor.dispatch_frames(now + Duration::from_millis(50)).unwrap();
+        assert!(next > now);
+        assert_eq!(compositor.frame_count, 1);
+        assert_eq!(presented.borrow().len(), 1);
+    }
+    
+    #[test]
+    fn test_occluded_damage_is_culled() {
+        let mut compositor = compositor_with_output(200, 100);
+        compositor.add_window(Window::new(1, "video", Rectangle::new(0, 0, 100, 100)));
+        compositor.add_window(opaque_window(2, Rectangle::new(0, 0, 60, 100)));
+        compositor.render_frame();
+        assert_eq!(compositor.last_repaints()[0].draws.len(), 2);
+        
+        // 不透明なウィンドウに完全に隠れたダメージは描画しない
+        compositor.damage_window(1, Rectangle::new(0, 0, 50, 50));
+        assert!(!compositor.render_frame());
+        
+        // 一部が見える場合は見える部分だけ
+        compositor.damage_window(1, Rectangle::new(40, 0, 40, 10));
+        assert!(compositor.render_frame());
+        assert_eq!(compositor.last_repaint_stats()[0].repainted_area, 20 * 10);
+        
+        // 再描画領域を覆う不透明なウィンドウの下のウィンドウは描画しない
+        compositor.damage_window(2, Rectangle::new(0, 0, 10, 10));
+        assert!(compositor.render_frame());
+        let repaint = &compositor.last_repaints()[0];
+        assert_eq!(repaint.draws.iter().map(|draw| draw.window_id).collect::<Vec<_>>(), vec![2]);
+        assert_eq!(compositor.last_repaint_stats()[0].windows_culled, 1);
+        assert!(repaint.background.is_empty());
+        
+        // 半透明にすると下のウィンドウも描画する
+        compositor.windows[&2].borrow_mut().set_opacity(0.5);
+        assert!(compositor.render_frame());
+        assert_eq!(compositor.last_repaints()[0].draws.len(), 2);
+    }
+    
+    #[test]
+    fn test_damage_in_output_space() {
+        let mut compositor = LumosCompositor::new();
+        // 論理座標 (1000, 0) に置いた2倍スケールで90度回転した出力
+        let mut output = OutputDevice::new(2, "DP-1", 400, 200, 60.0);
+        output.set_position(1000, 0);
+        output.set_scale_factor(2.0);
+        output.set_transform(TransformMatrix::rotate_90_degrees());
+        assert_eq!(output.logical_rect(), Rectangle::new(1000, 0, 100, 200));
+        assert_eq!(output.damage_to_output(&Rectangle::new(1000, 0, 10, 20)), Some(Rectangle::new(360, 0, 40, 20)));
+        assert_eq!(output.damage_to_output(&Rectangle::new(0, 0, 10, 10)), None);
+        compositor.add_output(output);
+        compositor.set_buffer_age(2, 2);
+        
+        compositor.add_window(Window::new(1, "editor", Rectangle::new(990, 0, 20, 20)));
+        compositor.render_frame();
+        compositor.damage_window(1, Rectangle::new(10, 0, 5, 5));
+        assert!(compositor.render_frame());
+        // 2フレーム前のバッファなので、前のフレームで描画した出力全体も含まれる
+        assert_eq!(compositor.last_repaint_stats()[0].repainted_area, 400 * 200);
+        
+        compositor.damage_window(1, Rectangle::new(10, 0, 5, 5));
+        assert!(compositor.render_frame());
+        assert_eq!(compositor.last_repaint_stats()[0].repainted_area, 10 * 10);
+    }
 } 
\ No newline at end of file
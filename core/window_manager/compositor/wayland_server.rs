@@ -0,0 +1,1702 @@
+// LumosDesktop Waylandサーバー
+// ローカルのUnixソケットでクライアントを受け付け、サーフェスをコンポジターのウィンドウに対応付ける
+
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::Write;
+use std::os::unix::fs::FileExt;
+use std::os::unix::io::{AsFd, BorrowedFd};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Instant;
+
+use log::{debug, warn};
+use thiserror::Error;
+use wayland_protocols::xdg::shell::server::{xdg_popup, xdg_positioner, xdg_surface, xdg_toplevel, xdg_wm_base};
+use wayland_server::backend::{ClientData, ClientId, GlobalId, ObjectId};
+use wayland_server::protocol::{
+    wl_buffer, wl_callback, wl_compositor, wl_keyboard, wl_output, wl_pointer, wl_region, wl_seat, wl_shm,
+    wl_shm_pool, wl_surface, wl_touch,
+};
+use wayland_server::{
+    Client, DataInit, Dispatch, Display, DisplayHandle, GlobalDispatch, ListeningSocket, New, Resource,
+};
+
+use crate::core::window_manager::input_translator::{
+    InputEvent, InputEventType, InputManager, KeyModifier, KeyboardLayout, KeyboardLayoutManager, MouseButton,
+};
+
+use super::damage::Region;
+use super::wayland_compositor::{Buffer, LumosCompositor, PixelFormat, Rectangle, Window};
+
+// 各グローバルのバージョン
+const COMPOSITOR_VERSION: u32 = 5;
+const SHM_VERSION: u32 = 1;
+const SEAT_VERSION: u32 = 5;
+const OUTPUT_VERSION: u32 = 4;
+const XDG_WM_BASE_VERSION: u32 = 3;
+
+/// 新しいトップレベルを前のものから少しずらして配置する量
+const CASCADE_STEP: i32 = 32;
+
+/// 受け付けるバッファの最大の幅と高さ
+const MAX_BUFFER_DIMENSION: i64 = 16384;
+/// 受け付けるバッファの最大サイズ（コミットのたびにこの大きさまでコピーする）
+const MAX_BUFFER_SIZE: i64 = 256 << 20;
+
+/// Waylandサーバーのエラー
+#[derive(Error, Debug)]
+pub enum WaylandServerError {
+    /// ディスプレイの初期化に失敗
+    #[error("Waylandディスプレイの初期化に失敗しました: {0}")]
+    Init(String),
+
+    /// ソケットの作成に失敗
+    #[error("Waylandソケットの作成に失敗しました: {0}")]
+    Bind(String),
+
+    /// クライアントとの通信に失敗
+    #[error("クライアントとの通信に失敗しました: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// ロックが毒されていても中身を使う
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// wl_shmのフォーマットをピクセルフォーマットに変換
+fn pixel_format(format: wl_shm::Format) -> Option<PixelFormat> {
+    match format {
+        wl_shm::Format::Argb8888 => Some(PixelFormat::ARGB8888),
+        wl_shm::Format::Xrgb8888 => Some(PixelFormat::XRGB8888),
+        wl_shm::Format::Rgba8888 => Some(PixelFormat::RGBA8888),
+        wl_shm::Format::Rgbx8888 => Some(PixelFormat::RGBX8888),
+        wl_shm::Format::Abgr8888 => Some(PixelFormat::ABGR8888),
+        wl_shm::Format::Xbgr8888 => Some(PixelFormat::XBGR8888),
+        wl_shm::Format::Rgb565 => Some(PixelFormat::RGB565),
+        _ => None,
+    }
+}
+
+/// 対応するwl_shmのフォーマット（ARGB8888とXRGB8888はすべての実装で必須）
+const SHM_FORMATS: [wl_shm::Format; 7] = [
+    wl_shm::Format::Argb8888,
+    wl_shm::Format::Xrgb8888,
+    wl_shm::Format::Rgba8888,
+    wl_shm::Format::Rgbx8888,
+    wl_shm::Format::Abgr8888,
+    wl_shm::Format::Xbgr8888,
+    wl_shm::Format::Rgb565,
+];
+
+/// 共有メモリプール
+///
+/// 内容はコミット時に `pread` でコピーするため、mmapせずにファイルとして保持します。
+#[derive(Debug)]
+struct ShmPool {
+    file: File,
+    size: Mutex<usize>,
+}
+
+/// 共有メモリバッファ（プールが破棄されてもバッファは有効）
+#[derive(Debug)]
+struct ShmBuffer {
+    pool: Arc<ShmPool>,
+    offset: usize,
+    width: u32,
+    height: u32,
+    stride: u32,
+    format: PixelFormat,
+}
+
+impl ShmBuffer {
+    /// プールから内容をコピーしてバッファを作成
+    fn read(&self) -> std::io::Result<Buffer> {
+        let size = self.stride as i64 * self.height as i64;
+        if size > MAX_BUFFER_SIZE {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "buffer is too large"));
+        }
+        let mut data = vec![0; size as usize];
+        self.pool.file.read_exact_at(&mut data, self.offset as u64)?;
+        Ok(Buffer::with_stride(self.width, self.height, self.format, self.stride, data))
+    }
+}
+
+/// xdg_positionerの状態
+#[derive(Debug, Clone, Copy, Default)]
+struct Positioner {
+    size: (u32, u32),
+    anchor_rect: Rectangle,
+    anchor: u32,
+    gravity: u32,
+    offset: (i32, i32),
+}
+
+/// anchor/gravityの値を水平・垂直の向き（-1, 0, 1）に変換
+///
+/// xdg_positioner.anchor と gravity は同じ値の並び（none, top, bottom, left, right,
+/// top_left, bottom_left, top_right, bottom_right）です。
+fn edges(value: u32) -> (i32, i32) {
+    match value {
+        1 => (0, -1),
+        2 => (0, 1),
+        3 => (-1, 0),
+        4 => (1, 0),
+        5 => (-1, -1),
+        6 => (-1, 1),
+        7 => (1, -1),
+        8 => (1, 1),
+        _ => (0, 0),
+    }
+}
+
+impl Positioner {
+    /// 親サーフェスの座標でのポップアップの位置（制約による調整は行わない）
+    fn geometry(&self) -> Rectangle {
+        let rect = self.anchor_rect;
+        let (anchor_x, anchor_y) = edges(self.anchor);
+        let x = rect.x + (anchor_x + 1) * rect.width as i32 / 2;
+        let y = rect.y + (anchor_y + 1) * rect.height as i32 / 2;
+
+        let (width, height) = (self.size.0 as i32, self.size.1 as i32);
+        let (gravity_x, gravity_y) = edges(self.gravity);
+        let x = x + (gravity_x - 1) * width / 2 + self.offset.0;
+        let y = y + (gravity_y - 1) * height / 2 + self.offset.1;
+        Rectangle::new(x, y, self.size.0, self.size.1)
+    }
+}
+
+/// 次のコミットで適用する状態
+#[derive(Debug, Default)]
+struct PendingState {
+    /// `Some(None)` はバッファを外す（アンマップ）
+    buffer: Option<Option<wl_buffer::WlBuffer>>,
+    offset: (i32, i32),
+    /// サーフェス座標のダメージ
+    damage: Vec<Rectangle>,
+    /// バッファ座標のダメージ
+    buffer_damage: Vec<Rectangle>,
+    opaque_region: Option<Vec<Rectangle>>,
+    input_region: Option<Vec<Rectangle>>,
+    buffer_scale: Option<i32>,
+    frame_callbacks: Vec<wl_callback::WlCallback>,
+}
+
+/// トップレベルの状態
+#[derive(Debug)]
+struct ToplevelState {
+    toplevel: xdg_toplevel::XdgToplevel,
+    title: String,
+    app_id: String,
+    maximized: bool,
+    fullscreen: bool,
+    activated: bool,
+}
+
+/// ポップアップの状態
+#[derive(Debug)]
+struct PopupState {
+    popup: xdg_popup::XdgPopup,
+    /// 親のwl_surface
+    parent: Option<ObjectId>,
+    /// 親サーフェスの座標での位置
+    geometry: Rectangle,
+    grab: bool,
+}
+
+/// サーフェスの役割
+#[derive(Debug)]
+enum SurfaceRole {
+    None,
+    Toplevel(ToplevelState),
+    Popup(PopupState),
+}
+
+/// サーフェスの状態
+#[derive(Debug)]
+struct SurfaceState {
+    surface: wl_surface::WlSurface,
+    pending: PendingState,
+    role: SurfaceRole,
+    xdg_surface: Option<xdg_surface::XdgSurface>,
+    buffer_scale: i32,
+    opaque_region: Vec<Rectangle>,
+    input_region: Vec<Rectangle>,
+    /// 最初のconfigureを送ったかどうか
+    configure_sent: bool,
+    /// クライアントがconfigureに応答したかどうか（応答前にバッファを付けることはできない）
+    configured: bool,
+    /// 対応するコンポジターのウィンドウ（マップ中のみ）
+    window_id: Option<u64>,
+}
+
+impl SurfaceState {
+    fn new(surface: wl_surface::WlSurface) -> Self {
+        Self {
+            surface,
+            pending: PendingState::default(),
+            role: SurfaceRole::None,
+            xdg_surface: None,
+            buffer_scale: 1,
+            opaque_region: Vec::new(),
+            input_region: Vec::new(),
+            configure_sent: false,
+            configured: false,
+            window_id: None,
+        }
+    }
+}
+
+/// シートの状態（ポインタとキーボードのフォーカス）
+#[derive(Debug, Default)]
+struct SeatState {
+    pointers: Vec<wl_pointer::WlPointer>,
+    keyboards: Vec<wl_keyboard::WlKeyboard>,
+    pointer_focus: Option<wl_surface::WlSurface>,
+    keyboard_focus: Option<wl_surface::WlSurface>,
+    /// (depressed, locked)
+    modifiers: (u32, u32),
+}
+
+/// クライアントごとのデータ
+#[derive(Debug, Default)]
+struct ClientState;
+
+impl ClientData for ClientState {}
+
+/// プロトコルの処理で共有する状態
+struct ServerState {
+    compositor: Arc<Mutex<LumosCompositor>>,
+    surfaces: HashMap<ObjectId, SurfaceState>,
+    /// ウィンドウIDからサーフェスへの対応
+    window_surfaces: HashMap<u64, ObjectId>,
+    seat: SeatState,
+    /// 次のフレームで完了を通知するコールバック
+    frame_callbacks: Vec<wl_callback::WlCallback>,
+    output_globals: HashMap<u32, GlobalId>,
+    serial: u32,
+    placed_toplevels: i32,
+    /// クライアントに送っているキーボードレイアウト
+    layout: Option<KeyboardLayout>,
+    /// 入力マネージャで切り替わり、まだクライアントに送っていないレイアウト
+    pending_layout: Arc<Mutex<Option<KeyboardLayout>>>,
+}
+
+impl ServerState {
+    fn next_serial(&mut self) -> u32 {
+        self.serial = self.serial.wrapping_add(1);
+        self.serial
+    }
+
+    /// 最初の出力の論理座標での矩形
+    fn primary_output_rect(&self) -> Option<Rectangle> {
+        let compositor = lock(&self.compositor);
+        let id = *compositor.output_ids().first()?;
+        compositor.get_output(id).map(|output| output.logical_rect())
+    }
+
+    /// トップレベルの状態を送り、xdg_surface.configureで確定する
+    fn send_toplevel_configure(&mut self, id: &ObjectId) {
+        let output = self.primary_output_rect();
+        let serial = self.next_serial();
+        let Some(surface) = self.surfaces.get_mut(id) else { return };
+        let SurfaceRole::Toplevel(toplevel) = &surface.role else { return };
+
+        let mut states = Vec::new();
+        let mut size = (0, 0);
+        if toplevel.maximized {
+            states.push(xdg_toplevel::State::Maximized);
+        }
+        if toplevel.fullscreen {
+            states.push(xdg_toplevel::State::Fullscreen);
+        }
+        if toplevel.activated {
+            states.push(xdg_toplevel::State::Activated);
+        }
+        if toplevel.maximized || toplevel.fullscreen {
+            if let Some(output) = output {
+                size = (output.width as i32, output.height as i32);
+            }
+        }
+        let states: Vec<u8> = states.into_iter().flat_map(|state| (state as u32).to_ne_bytes()).collect();
+        toplevel.toplevel.configure(size.0, size.1, states);
+        if let Some(xdg_surface) = &surface.xdg_surface {
+            xdg_surface.configure(serial);
+        }
+        surface.configure_sent = true;
+    }
+
+    fn send_popup_configure(&mut self, id: &ObjectId) {
+        let serial = self.next_serial();
+        let Some(surface) = self.surfaces.get_mut(id) else { return };
+        let SurfaceRole::Popup(popup) = &surface.role else { return };
+        let rect = popup.geometry;
+        popup.popup.configure(rect.x, rect.y, rect.width as i32, rect.height as i32);
+        if let Some(xdg_surface) = &surface.xdg_surface {
+            xdg_surface.configure(serial);
+        }
+        surface.configure_sent = true;
+    }
+
+    /// 役割に応じた最初のconfigureを送る
+    fn send_configure(&mut self, id: &ObjectId) {
+        match self.surfaces.get(id).map(|surface| &surface.role) {
+            Some(SurfaceRole::Toplevel(_)) => self.send_toplevel_configure(id),
+            Some(SurfaceRole::Popup(_)) => self.send_popup_configure(id),
+            _ => {}
+        }
+    }
+
+    /// 保留中の状態を適用する
+    fn commit(&mut self, id: &ObjectId) {
+        let Some(surface) = self.surfaces.get_mut(id) else { return };
+        let pending = std::mem::take(&mut surface.pending);
+        self.frame_callbacks.extend(pending.frame_callbacks);
+
+        if let Some(scale) = pending.buffer_scale {
+            surface.buffer_scale = scale.max(1);
+        }
+        if let Some(region) = &pending.opaque_region {
+            surface.opaque_region = region.clone();
+        }
+        if let Some(region) = pending.input_region {
+            surface.input_region = region;
+        }
+
+        let has_role = !matches!(surface.role, SurfaceRole::None);
+        if has_role && !surface.configured && matches!(pending.buffer, Some(Some(_))) {
+            if let Some(xdg_surface) = &surface.xdg_surface {
+                xdg_surface.post_error(xdg_surface::Error::UnconfiguredBuffer, "buffer attached before configure");
+            }
+            return;
+        }
+        if has_role && !surface.configure_sent {
+            // 最初のコミット（バッファなし）にconfigureで応答する
+            self.send_configure(id);
+            return;
+        }
+
+        match pending.buffer {
+            Some(Some(wl_buffer)) => {
+                let contents = match wl_buffer.data::<ShmBuffer>() {
+                    Some(shm) => shm.read(),
+                    None => Err(std::io::Error::other("not a wl_shm buffer")),
+                };
+                // 内容はコピー済みなので、すぐにクライアントへ返す
+                wl_buffer.release();
+                match contents {
+                    Ok(buffer) if has_role => self.update_window(id, Arc::new(buffer), &pending.damage, &pending.buffer_damage, pending.offset),
+                    Ok(_) => {}
+                    Err(err) => warn!("共有メモリバッファの読み込みに失敗しました: {}", err),
+                }
+            }
+            Some(None) => self.unmap(id),
+            None => self.update_window_state(id, &pending.damage, &pending.buffer_damage),
+        }
+
+        // 不透明な領域は変更された場合だけ設定する（ウィンドウ全体が描画し直されるため）
+        if let Some(region) = pending.opaque_region {
+            if let Some(window) = self.window_of(id) {
+                window.borrow_mut().set_opacity_regions(region.into_iter().map(|rect| (rect, 1.0)).collect());
+            }
+        }
+    }
+
+    fn window_of(&self, id: &ObjectId) -> Option<Rc<RefCell<Window>>> {
+        let window_id = self.surfaces.get(id)?.window_id?;
+        lock(&self.compositor).get_window(window_id)
+    }
+
+    /// 親ウィンドウの位置とポップアップの相対位置から、論理座標での位置を求める
+    fn popup_origin(&self, popup: &PopupState) -> (i32, i32) {
+        let parent = popup.parent.as_ref()
+            .and_then(|parent| self.window_of(parent))
+            .map(|window| window.borrow().geometry())
+            .unwrap_or_default();
+        (parent.x + popup.geometry.x, parent.y + popup.geometry.y)
+    }
+
+    /// 新しいウィンドウの論理座標での位置
+    fn placement(&mut self, id: &ObjectId, width: u32, height: u32) -> Rectangle {
+        let output = self.primary_output_rect().unwrap_or_default();
+        let surface = &self.surfaces[id];
+        match &surface.role {
+            SurfaceRole::Popup(popup) => {
+                let (x, y) = self.popup_origin(popup);
+                Rectangle::new(x, y, width, height)
+            }
+            SurfaceRole::Toplevel(toplevel) if toplevel.maximized || toplevel.fullscreen => {
+                Rectangle::new(output.x, output.y, width, height)
+            }
+            _ => {
+                let step = CASCADE_STEP * (self.placed_toplevels % 8);
+                self.placed_toplevels += 1;
+                Rectangle::new(output.x + step, output.y + step, width, height)
+            }
+        }
+    }
+
+    /// バッファをウィンドウに設定し、必要ならウィンドウを作成する
+    fn update_window(&mut self, id: &ObjectId, buffer: Arc<Buffer>, damage: &[Rectangle], buffer_damage: &[Rectangle], offset: (i32, i32)) {
+        let scale = self.surfaces[id].buffer_scale as u32;
+        let (width, height) = (buffer.width() / scale, buffer.height() / scale);
+
+        let Some(window_id) = self.surfaces[id].window_id else {
+            let geometry = self.placement(id, width, height);
+            let surface = self.surfaces.get_mut(id).unwrap();
+            let mut compositor = lock(&self.compositor);
+            let window_id = compositor.allocate_window_id();
+
+            let mut window = Window::new(window_id, "", geometry);
+            window.set_surface_id(surface.surface.id().protocol_id() as u64);
+            if let SurfaceRole::Toplevel(toplevel) = &surface.role {
+                window.set_title(&toplevel.title);
+                window.set_app_id(&toplevel.app_id);
+            }
+            window.set_input_region(surface.input_region.clone());
+            window.set_opacity_regions(surface.opaque_region.iter().map(|rect| (*rect, 1.0)).collect());
+            window.attach_buffer(buffer);
+            compositor.add_window(window);
+            drop(compositor);
+
+            surface.window_id = Some(window_id);
+            let is_toplevel = matches!(surface.role, SurfaceRole::Toplevel(_));
+            self.window_surfaces.insert(window_id, id.clone());
+            debug!("サーフェス {} をウィンドウ {} としてマップしました", id, window_id);
+            if is_toplevel {
+                self.focus(id);
+            }
+            return;
+        };
+
+        let surface = &self.surfaces[id];
+        let Some(window) = lock(&self.compositor).get_window(window_id) else { return };
+        let mut window = window.borrow_mut();
+        let mut geometry = window.geometry().translated(offset.0, offset.1);
+        if (geometry.width, geometry.height) != (width, height) {
+            geometry.width = width;
+            geometry.height = height;
+            window.set_geometry(geometry);
+            window.attach_buffer(buffer);
+        } else {
+            window.set_geometry(geometry);
+            window.replace_buffer(buffer);
+            add_surface_damage(&mut window, damage, buffer_damage, surface.buffer_scale);
+        }
+        window.set_input_region(surface.input_region.clone());
+    }
+
+    /// バッファを変えずにダメージと領域だけを適用する
+    fn update_window_state(&mut self, id: &ObjectId, damage: &[Rectangle], buffer_damage: &[Rectangle]) {
+        let surface = &self.surfaces[id];
+        let Some(window_id) = surface.window_id else { return };
+        let Some(window) = lock(&self.compositor).get_window(window_id) else { return };
+        let mut window = window.borrow_mut();
+        add_surface_damage(&mut window, damage, buffer_damage, surface.buffer_scale);
+        window.set_input_region(surface.input_region.clone());
+    }
+
+    /// サーフェスに対応するウィンドウを取り除く
+    fn unmap(&mut self, id: &ObjectId) {
+        let Some(surface) = self.surfaces.get_mut(id) else { return };
+        // 再びマップするには最初のコミットからやり直す
+        surface.configure_sent = false;
+        surface.configured = false;
+        let Some(window_id) = surface.window_id.take() else { return };
+        self.window_surfaces.remove(&window_id);
+        lock(&self.compositor).remove_window(window_id);
+
+        if self.seat.pointer_focus.as_ref().is_some_and(|focus| focus.id() == *id) {
+            self.set_pointer_focus(None, 0.0, 0.0);
+        }
+        if self.seat.keyboard_focus.as_ref().is_some_and(|focus| focus.id() == *id) {
+            self.set_keyboard_focus(None);
+        }
+    }
+
+    /// サーフェスを破棄する
+    fn destroy_surface(&mut self, id: &ObjectId) {
+        self.unmap(id);
+        self.surfaces.remove(id);
+    }
+
+    /// キーボードフォーカスを移し、ウィンドウを最前面にする
+    fn focus(&mut self, id: &ObjectId) {
+        let Some(window_id) = self.surfaces.get(id).and_then(|surface| surface.window_id) else { return };
+        {
+            let mut compositor = lock(&self.compositor);
+            compositor.raise_window(window_id);
+            compositor.set_active_window(window_id);
+        }
+        let surface = self.surfaces[id].surface.clone();
+        self.set_keyboard_focus(Some(surface));
+    }
+
+    fn set_keyboard_focus(&mut self, surface: Option<wl_surface::WlSurface>) {
+        if self.seat.keyboard_focus == surface {
+            return;
+        }
+        let serial = self.next_serial();
+        if let Some(old) = self.seat.keyboard_focus.take() {
+            for keyboard in self.seat.keyboards.iter().filter(|keyboard| keyboard.id().same_client_as(&old.id())) {
+                keyboard.leave(serial, &old);
+            }
+            self.set_activated(&old.id(), false);
+        }
+        if let Some(new) = &surface {
+            for keyboard in self.seat.keyboards.iter().filter(|keyboard| keyboard.id().same_client_as(&new.id())) {
+                keyboard.enter(serial, new, Vec::new());
+                self.send_modifiers(keyboard, serial);
+            }
+            self.set_activated(&new.id(), true);
+        }
+        self.seat.keyboard_focus = surface;
+    }
+
+    /// トップレベルのアクティブ状態を変え、クライアントに通知する
+    fn set_activated(&mut self, id: &ObjectId, activated: bool) {
+        let Some(SurfaceRole::Toplevel(toplevel)) = self.surfaces.get_mut(id).map(|surface| &mut surface.role) else { return };
+        if toplevel.activated != activated {
+            toplevel.activated = activated;
+            self.send_toplevel_configure(id);
+        }
+    }
+
+    fn set_pointer_focus(&mut self, surface: Option<wl_surface::WlSurface>, x: f64, y: f64) {
+        let serial = self.next_serial();
+        if let Some(old) = self.seat.pointer_focus.take() {
+            for pointer in self.pointers_of(&old) {
+                pointer.leave(serial, &old);
+                pointer_frame(&pointer);
+            }
+        }
+        if let Some(new) = &surface {
+            for pointer in self.pointers_of(new) {
+                pointer.enter(serial, new, x, y);
+                pointer_frame(&pointer);
+            }
+        }
+        self.seat.pointer_focus = surface;
+    }
+
+    fn pointers_of(&self, surface: &wl_surface::WlSurface) -> Vec<wl_pointer::WlPointer> {
+        self.seat.pointers.iter().filter(|pointer| pointer.id().same_client_as(&surface.id())).cloned().collect()
+    }
+
+    /// 論理座標の点にあるサーフェスと、サーフェス座標での位置
+    fn surface_at(&self, x: f64, y: f64) -> Option<(wl_surface::WlSurface, f64, f64)> {
+        let compositor = lock(&self.compositor);
+        let window_id = compositor.window_at(x, y)?;
+        let geometry = compositor.get_window(window_id)?.borrow().geometry();
+        let surface = self.surfaces.get(self.window_surfaces.get(&window_id)?)?;
+        Some((surface.surface.clone(), x - geometry.x as f64, y - geometry.y as f64))
+    }
+
+    /// ポインタの位置を更新し、フォーカスが変わった場合はenter/leaveを送る
+    fn pointer_motion(&mut self, x: f64, y: f64, time: u32) {
+        let target = self.surface_at(x, y);
+        let focus = target.as_ref().map(|(surface, _, _)| surface.clone());
+        if focus != self.seat.pointer_focus {
+            let (local_x, local_y) = target.map_or((0.0, 0.0), |(_, x, y)| (x, y));
+            self.set_pointer_focus(focus, local_x, local_y);
+        } else if let Some((surface, local_x, local_y)) = target {
+            for pointer in self.pointers_of(&surface) {
+                pointer.motion(time, local_x, local_y);
+                pointer_frame(&pointer);
+            }
+        }
+    }
+
+    fn pointer_button(&mut self, button: MouseButton, pressed: bool, x: f64, y: f64, time: u32) -> bool {
+        self.pointer_motion(x, y, time);
+        if pressed {
+            self.dismiss_popups();
+            // クリックしたトップレベルにキーボードフォーカスを移す
+            if let Some(focus) = self.seat.pointer_focus.clone() {
+                if matches!(self.surfaces.get(&focus.id()).map(|surface| &surface.role), Some(SurfaceRole::Toplevel(_))) {
+                    self.focus(&focus.id());
+                }
+            }
+        }
+
+        let Some(focus) = self.seat.pointer_focus.clone() else { return false };
+        let serial = self.next_serial();
+        let state = if pressed { wl_pointer::ButtonState::Pressed } else { wl_pointer::ButtonState::Released };
+        for pointer in self.pointers_of(&focus) {
+            pointer.button(serial, time, button_code(button), state);
+            pointer_frame(&pointer);
+        }
+        true
+    }
+
+    /// ポインタのフォーカスがグラブ中のポップアップの外にある場合はポップアップを閉じる
+    fn dismiss_popups(&mut self) {
+        let focus = self.seat.pointer_focus.as_ref().map(|surface| surface.id());
+        for (id, surface) in &mut self.surfaces {
+            if let SurfaceRole::Popup(popup) = &mut surface.role {
+                if popup.grab && Some(id) != focus.as_ref() {
+                    popup.grab = false;
+                    popup.popup.popup_done();
+                }
+            }
+        }
+    }
+
+    fn keyboard_key(&mut self, key_code: u32, modifiers: &HashSet<KeyModifier>, pressed: bool, time: u32) -> bool {
+        let Some(focus) = self.seat.keyboard_focus.clone() else { return false };
+        let serial = self.next_serial();
+        let keyboards: Vec<_> = self.seat.keyboards.iter().filter(|keyboard| keyboard.id().same_client_as(&focus.id())).cloned().collect();
+
+        // XKBのキーコードはevdevのキーコードに8を足したもの
+        let state = if pressed { wl_keyboard::KeyState::Pressed } else { wl_keyboard::KeyState::Released };
+        for keyboard in &keyboards {
+            keyboard.key(serial, time, key_code.saturating_sub(8), state);
+        }
+
+        let mask = modifier_mask(modifiers);
+        if mask != self.seat.modifiers {
+            self.seat.modifiers = mask;
+            for keyboard in &keyboards {
+                self.send_modifiers(keyboard, serial);
+            }
+        }
+        true
+    }
+
+    /// 押下・ロック中のモディファイアと現在のレイアウトのグループを送る
+    fn send_modifiers(&self, keyboard: &wl_keyboard::WlKeyboard, serial: u32) {
+        let (depressed, locked) = self.seat.modifiers;
+        let group = self.layout.as_ref().map_or(0, |layout| layout.group() as u32);
+        keyboard.modifiers(serial, depressed, 0, locked, group);
+    }
+
+    /// 現在のレイアウトのキーマップを送る
+    fn send_keymap(&self, keyboard: &wl_keyboard::WlKeyboard) {
+        match keymap_file(self.layout.as_ref()) {
+            Ok((file, size)) => keyboard.keymap(wl_keyboard::KeymapFormat::XkbV1, file.as_fd(), size),
+            Err(err) => warn!("キーマップを作成できませんでした: {}", err),
+        }
+    }
+
+    /// 入力マネージャで切り替わったレイアウトを反映する
+    ///
+    /// 同じキーマップ内のグループの切り替えはモディファイアのグループだけを送り、
+    /// 別のキーマップに切り替わった場合はすべてのキーボードにキーマップを送り直します。
+    fn sync_layout(&mut self) {
+        let Some(layout) = lock(&self.pending_layout).take() else { return };
+        let keymap_changed = !matches!(&self.layout, Some(current) if std::ptr::eq(current.keymap(), layout.keymap()));
+        self.layout = Some(layout);
+
+        if keymap_changed {
+            for keyboard in &self.seat.keyboards {
+                self.send_keymap(keyboard);
+            }
+        }
+        let Some(focus) = self.seat.keyboard_focus.clone() else { return };
+        let serial = self.next_serial();
+        for keyboard in self.seat.keyboards.iter().filter(|keyboard| keyboard.id().same_client_as(&focus.id())) {
+            self.send_modifiers(keyboard, serial);
+        }
+    }
+
+    fn pointer_axis(&mut self, x: f64, y: f64, dx: f64, dy: f64, time: u32) -> bool {
+        self.pointer_motion(x, y, time);
+        let Some(focus) = self.seat.pointer_focus.clone() else { return false };
+        for pointer in self.pointers_of(&focus) {
+            if dy != 0.0 {
+                pointer.axis(time, wl_pointer::Axis::VerticalScroll, dy);
+            }
+            if dx != 0.0 {
+                pointer.axis(time, wl_pointer::Axis::HorizontalScroll, dx);
+            }
+            pointer_frame(&pointer);
+        }
+        true
+    }
+}
+
+/// wl_pointer.frameはバージョン5から
+fn pointer_frame(pointer: &wl_pointer::WlPointer) {
+    if pointer.version() >= 5 {
+        pointer.frame();
+    }
+}
+
+/// Linuxのボタンコード（linux/input-event-codes.h）
+fn button_code(button: MouseButton) -> u32 {
+    match button {
+        MouseButton::Left => 0x110,
+        MouseButton::Right => 0x111,
+        MouseButton::Middle => 0x112,
+        MouseButton::Back => 0x116,
+        MouseButton::Forward => 0x115,
+        MouseButton::Extra(n) => 0x113 + n as u32,
+    }
+}
+
+/// モディファイアをXKBの(押下, ロック)マスクに変換
+fn modifier_mask(modifiers: &HashSet<KeyModifier>) -> (u32, u32) {
+    let (mut depressed, mut locked) = (0, 0);
+    for modifier in modifiers {
+        match modifier {
+            KeyModifier::Shift => depressed |= 1 << 0,
+            KeyModifier::CapsLock => locked |= 1 << 1,
+            KeyModifier::Ctrl => depressed |= 1 << 2,
+            KeyModifier::Alt | KeyModifier::Meta => depressed |= 1 << 3,
+            KeyModifier::NumLock => locked |= 1 << 4,
+            KeyModifier::Super | KeyModifier::Hyper => depressed |= 1 << 6,
+            KeyModifier::LevelThree => depressed |= 1 << 7,
+        }
+    }
+    (depressed, locked)
+}
+
+/// サーフェス座標とバッファ座標のダメージをウィンドウに加える
+fn add_surface_damage(window: &mut Window, damage: &[Rectangle], buffer_damage: &[Rectangle], scale: i32) {
+    for rect in damage {
+        window.add_damage(*rect);
+    }
+    let scale = scale.max(1);
+    for rect in buffer_damage {
+        // 端数のピクセルも含むように丸める
+        let x = rect.x.div_euclid(scale);
+        let y = rect.y.div_euclid(scale);
+        let right = (rect.right() + scale - 1).div_euclid(scale);
+        let bottom = (rect.bottom() + scale - 1).div_euclid(scale);
+        window.add_damage(Rectangle::new(x, y, (right - x) as u32, (bottom - y) as u32));
+    }
+}
+
+/// Waylandサーバー
+///
+/// ローカルのUnixソケットでクライアントを受け付け、wl_compositor / wl_shm / wl_seat /
+/// wl_output / xdg_wm_base を提供します。トップレベルとポップアップのサーフェスは
+/// コンポジターの `Window` に、wl_shmのバッファは `Buffer` にコピーして対応付けます。
+/// GPUは使わないため、ヘッドレスバックエンドと組み合わせてテストできます。
+///
+/// メインループでは `dispatch` でリクエストを処理し、フレームを表示したあとに
+/// `send_frame_callbacks` を呼び出します。
+pub struct WaylandServer {
+    display: Display<ServerState>,
+    socket: ListeningSocket,
+    socket_path: PathBuf,
+    state: ServerState,
+    start_time: Instant,
+}
+
+impl WaylandServer {
+    /// 指定したパスにソケットを作成
+    pub fn bind<P: AsRef<Path>>(path: P, compositor: Arc<Mutex<LumosCompositor>>) -> Result<Self, WaylandServerError> {
+        let path = path.as_ref().to_path_buf();
+        let socket = ListeningSocket::bind_absolute(path.clone())
+            .map_err(|err| WaylandServerError::Bind(format!("{}: {}", path.display(), err)))?;
+        Self::with_socket(socket, path, compositor)
+    }
+
+    /// `$XDG_RUNTIME_DIR` に空いている名前（wayland-1, wayland-2, ...）でソケットを作成
+    pub fn bind_auto(compositor: Arc<Mutex<LumosCompositor>>) -> Result<Self, WaylandServerError> {
+        let runtime_dir = std::env::var_os("XDG_RUNTIME_DIR")
+            .ok_or_else(|| WaylandServerError::Bind("XDG_RUNTIME_DIR が設定されていません".to_string()))?;
+        let socket = ListeningSocket::bind_auto("wayland", 1..33)
+            .map_err(|err| WaylandServerError::Bind(err.to_string()))?;
+        let name = socket.socket_name().map(|name| name.to_os_string()).unwrap_or_default();
+        let path = PathBuf::from(runtime_dir).join(name);
+        Self::with_socket(socket, path, compositor)
+    }
+
+    fn with_socket(socket: ListeningSocket, socket_path: PathBuf, compositor: Arc<Mutex<LumosCompositor>>) -> Result<Self, WaylandServerError> {
+        let display = Display::<ServerState>::new().map_err(|err| WaylandServerError::Init(err.to_string()))?;
+        let handle = display.handle();
+        handle.create_global::<ServerState, wl_compositor::WlCompositor, ()>(COMPOSITOR_VERSION, ());
+        handle.create_global::<ServerState, wl_shm::WlShm, ()>(SHM_VERSION, ());
+        handle.create_global::<ServerState, wl_seat::WlSeat, ()>(SEAT_VERSION, ());
+        handle.create_global::<ServerState, xdg_wm_base::XdgWmBase, ()>(XDG_WM_BASE_VERSION, ());
+
+        let mut server = Self {
+            display,
+            socket,
+            socket_path,
+            state: ServerState {
+                compositor,
+                surfaces: HashMap::new(),
+                window_surfaces: HashMap::new(),
+                seat: SeatState::default(),
+                frame_callbacks: Vec::new(),
+                output_globals: HashMap::new(),
+                serial: 0,
+                placed_toplevels: 0,
+                layout: KeyboardLayoutManager::new().active_layout().cloned(),
+                pending_layout: Arc::new(Mutex::new(None)),
+            },
+            start_time: Instant::now(),
+        };
+        server.sync_outputs();
+        Ok(server)
+    }
+
+    /// 入力マネージャのアクティブなキーボードレイアウトをクライアントに送る
+    ///
+    /// レイアウトが切り替わると、次の `dispatch` か `handle_input` でキーマップとグループを送り直します。
+    pub fn with_input_manager(mut self, input_manager: Arc<Mutex<InputManager>>) -> Self {
+        let mut input_manager = lock(&input_manager);
+        let layouts = input_manager.keyboard_layouts_mut();
+        self.state.layout = layouts.active_layout().cloned();
+        let pending = self.state.pending_layout.clone();
+        layouts.add_layout_listener(move |layout| *lock(&pending) = Some(layout.clone()));
+        drop(input_manager);
+        self
+    }
+
+    /// ソケットのパス（クライアントの `WAYLAND_DISPLAY` に設定する）
+    pub fn socket_path(&self) -> &Path {
+        &self.socket_path
+    }
+
+    /// 新しい接続を待つソケットのファイルディスクリプタ（イベントループへの登録用）
+    pub fn socket_fd(&self) -> BorrowedFd<'_> {
+        self.socket.as_fd()
+    }
+
+    /// クライアントのリクエストを待つファイルディスクリプタ（イベントループへの登録用）
+    pub fn display_fd(&mut self) -> BorrowedFd<'_> {
+        self.display.backend().poll_fd()
+    }
+
+    /// 新しい接続を受け付け、届いたリクエストを処理して、イベントを送信する
+    pub fn dispatch(&mut self) -> Result<usize, WaylandServerError> {
+        while let Some(stream) = self.socket.accept()? {
+            let client = self.display.handle().insert_client(stream, Arc::new(ClientState))?;
+            debug!("Waylandクライアントが接続しました: {:?}", client);
+        }
+        self.sync_outputs();
+        self.state.sync_layout();
+        let dispatched = self.display.dispatch_clients(&mut self.state)?;
+        self.flush()?;
+        Ok(dispatched)
+    }
+
+    /// 送信待ちのイベントをクライアントに送る
+    pub fn flush(&mut self) -> Result<(), WaylandServerError> {
+        self.display.flush_clients()?;
+        Ok(())
+    }
+
+    /// コンポジターの出力とwl_outputグローバルを一致させる
+    fn sync_outputs(&mut self) {
+        let ids = lock(&self.state.compositor).output_ids();
+        let handle = self.display.handle();
+        self.state.output_globals.retain(|id, global| {
+            let keep = ids.contains(id);
+            if !keep {
+                handle.remove_global::<ServerState>(global.clone());
+            }
+            keep
+        });
+        for id in ids {
+            self.state.output_globals.entry(id).or_insert_with(|| {
+                handle.create_global::<ServerState, wl_output::WlOutput, u32>(OUTPUT_VERSION, id)
+            });
+        }
+    }
+
+    /// フレームを表示したことをクライアントに通知する
+    pub fn send_frame_callbacks(&mut self) {
+        let time = self.start_time.elapsed().as_millis() as u32;
+        for callback in self.state.frame_callbacks.drain(..) {
+            callback.done(time);
+        }
+        let _ = self.flush();
+    }
+
+    /// 入力イベントをフォーカスのあるクライアントに送り、送った場合は真を返す
+    pub fn handle_input(&mut self, event: &InputEvent) -> bool {
+        let state = &mut self.state;
+        state.sync_layout();
+        let delivered = match &event.event_type {
+            InputEventType::MouseMove { x, y, timestamp, .. } => {
+                state.pointer_motion(*x, *y, *timestamp as u32);
+                state.seat.pointer_focus.is_some()
+            }
+            InputEventType::MousePress { button, x, y, timestamp, .. } => state.pointer_button(*button, true, *x, *y, *timestamp as u32),
+            InputEventType::MouseRelease { button, x, y, timestamp, .. } => state.pointer_button(*button, false, *x, *y, *timestamp as u32),
+            InputEventType::MouseScroll { x, y, dx, dy, timestamp, .. } => state.pointer_axis(*x, *y, *dx, *dy, *timestamp as u32),
+            // キーリピートはクライアントが行う
+            InputEventType::KeyPress { repeat: true, .. } => false,
+            InputEventType::KeyPress { key_code, modifiers, timestamp, .. } => state.keyboard_key(key_code.0, modifiers, true, *timestamp as u32),
+            InputEventType::KeyRelease { key_code, modifiers, timestamp, .. } => state.keyboard_key(key_code.0, modifiers, false, *timestamp as u32),
+            _ => false,
+        };
+        let _ = self.flush();
+        delivered
+    }
+
+    /// ウィンドウに対応するサーフェスのプロトコル上のID
+    pub fn surface_of_window(&self, window_id: u64) -> Option<u32> {
+        self.state.window_surfaces.get(&window_id).map(|id| id.protocol_id())
+    }
+
+    /// マップされているウィンドウの数
+    pub fn mapped_window_count(&self) -> usize {
+        self.state.window_surfaces.len()
+    }
+}
+
+// --- wl_compositor / wl_surface / wl_region ---
+
+impl GlobalDispatch<wl_compositor::WlCompositor, ()> for ServerState {
+    fn bind(_: &mut Self, _: &DisplayHandle, _: &Client, resource: New<wl_compositor::WlCompositor>, _: &(), data_init: &mut DataInit<'_, Self>) {
+        data_init.init(resource, ());
+    }
+}
+
+impl Dispatch<wl_compositor::WlCompositor, ()> for ServerState {
+    fn request(state: &mut Self, _: &Client, _: &wl_compositor::WlCompositor, request: wl_compositor::Request, _: &(), _: &DisplayHandle, data_init: &mut DataInit<'_, Self>) {
+        match request {
+            wl_compositor::Request::CreateSurface { id } => {
+                let surface = data_init.init(id, ());
+                state.surfaces.insert(surface.id(), SurfaceState::new(surface));
+            }
+            wl_compositor::Request::CreateRegion { id } => {
+                data_init.init(id, Mutex::new(Region::new()));
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<wl_region::WlRegion, Mutex<Region>> for ServerState {
+    fn request(_: &mut Self, _: &Client, _: &wl_region::WlRegion, request: wl_region::Request, data: &Mutex<Region>, _: &DisplayHandle, _: &mut DataInit<'_, Self>) {
+        let rect = |x: i32, y: i32, width: i32, height: i32| Rectangle::new(x, y, width.max(0) as u32, height.max(0) as u32);
+        match request {
+            wl_region::Request::Add { x, y, width, height } => lock(data).add_rect(rect(x, y, width, height)),
+            wl_region::Request::Subtract { x, y, width, height } => lock(data).subtract_rect(&rect(x, y, width, height)),
+            _ => {}
+        }
+    }
+}
+
+/// wl_regionの内容（`None` は無限の領域）
+fn region_rects(region: Option<wl_region::WlRegion>) -> Vec<Rectangle> {
+    region.and_then(|region| region.data::<Mutex<Region>>().map(|data| lock(data).rects().to_vec()))
+        .unwrap_or_default()
+}
+
+impl Dispatch<wl_surface::WlSurface, ()> for ServerState {
+    fn request(state: &mut Self, _: &Client, resource: &wl_surface::WlSurface, request: wl_surface::Request, _: &(), _: &DisplayHandle, data_init: &mut DataInit<'_, Self>) {
+        let id = resource.id();
+        if let wl_surface::Request::Commit = request {
+            state.commit(&id);
+            return;
+        }
+        let Some(surface) = state.surfaces.get_mut(&id) else { return };
+        let pending = &mut surface.pending;
+        let rect = |x: i32, y: i32, width: i32, height: i32| Rectangle::new(x, y, width.max(0) as u32, height.max(0) as u32);
+        match request {
+            wl_surface::Request::Attach { buffer, x, y } => {
+                pending.buffer = Some(buffer);
+                if resource.version() < 5 {
+                    pending.offset = (x, y);
+                }
+            }
+            wl_surface::Request::Offset { x, y } => pending.offset = (x, y),
+            wl_surface::Request::Damage { x, y, width, height } => pending.damage.push(rect(x, y, width, height)),
+            wl_surface::Request::DamageBuffer { x, y, width, height } => pending.buffer_damage.push(rect(x, y, width, height)),
+            wl_surface::Request::Frame { callback } => pending.frame_callbacks.push(data_init.init(callback, ())),
+            wl_surface::Request::SetOpaqueRegion { region } => pending.opaque_region = Some(region_rects(region)),
+            wl_surface::Request::SetInputRegion { region } => pending.input_region = Some(region_rects(region)),
+            wl_surface::Request::SetBufferScale { scale } => pending.buffer_scale = Some(scale),
+            _ => {}
+        }
+    }
+
+    fn destroyed(state: &mut Self, _: ClientId, resource: &wl_surface::WlSurface, _: &()) {
+        state.destroy_surface(&resource.id());
+    }
+}
+
+impl Dispatch<wl_callback::WlCallback, ()> for ServerState {
+    fn request(_: &mut Self, _: &Client, _: &wl_callback::WlCallback, _: wl_callback::Request, _: &(), _: &DisplayHandle, _: &mut DataInit<'_, Self>) {}
+}
+
+// --- wl_shm / wl_shm_pool / wl_buffer ---
+
+impl GlobalDispatch<wl_shm::WlShm, ()> for ServerState {
+    fn bind(_: &mut Self, _: &DisplayHandle, _: &Client, resource: New<wl_shm::WlShm>, _: &(), data_init: &mut DataInit<'_, Self>) {
+        let shm = data_init.init(resource, ());
+        for format in SHM_FORMATS {
+            shm.format(format);
+        }
+    }
+}
+
+impl Dispatch<wl_shm::WlShm, ()> for ServerState {
+    fn request(_: &mut Self, _: &Client, resource: &wl_shm::WlShm, request: wl_shm::Request, _: &(), _: &DisplayHandle, data_init: &mut DataInit<'_, Self>) {
+        if let wl_shm::Request::CreatePool { id, fd, size } = request {
+            if size <= 0 {
+                resource.post_error(wl_shm::Error::InvalidStride, "pool size must be positive");
+                return;
+            }
+            data_init.init(id, Arc::new(ShmPool {
+                file: File::from(fd),
+                size: Mutex::new(size as usize),
+            }));
+        }
+    }
+}
+
+impl Dispatch<wl_shm_pool::WlShmPool, Arc<ShmPool>> for ServerState {
+    fn request(_: &mut Self, _: &Client, resource: &wl_shm_pool::WlShmPool, request: wl_shm_pool::Request, pool: &Arc<ShmPool>, _: &DisplayHandle, data_init: &mut DataInit<'_, Self>) {
+        match request {
+            wl_shm_pool::Request::CreateBuffer { id, offset, width, height, stride, format } => {
+                let Some(format) = format.into_result().ok().and_then(pixel_format) else {
+                    resource.post_error(wl_shm::Error::InvalidFormat, "unsupported format");
+                    return;
+                };
+                let bytes_per_pixel = format.bytes_per_pixel() as i64;
+                let (offset, width, height, stride) = (offset as i64, width as i64, height as i64, stride as i64);
+                let pool_size = *lock(&pool.size) as i64;
+                if offset < 0 || width <= 0 || height <= 0 || stride < width * bytes_per_pixel || offset + stride * height > pool_size {
+                    resource.post_error(wl_shm::Error::InvalidStride, "buffer does not fit in the pool");
+                    return;
+                }
+                if width > MAX_BUFFER_DIMENSION || height > MAX_BUFFER_DIMENSION || stride * height > MAX_BUFFER_SIZE {
+                    resource.post_error(wl_shm::Error::InvalidStride, "buffer is too large");
+                    return;
+                }
+                data_init.init(id, ShmBuffer {
+                    pool: pool.clone(),
+                    offset: offset as usize,
+                    width: width as u32,
+                    height: height as u32,
+                    stride: stride as u32,
+                    format,
+                });
+            }
+            wl_shm_pool::Request::Resize { size } => {
+                let mut current = lock(&pool.size);
+                if (size as i64) < *current as i64 {
+                    resource.post_error(wl_shm::Error::InvalidStride, "pool cannot shrink");
+                    return;
+                }
+                *current = size as usize;
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<wl_buffer::WlBuffer, ShmBuffer> for ServerState {
+    fn request(_: &mut Self, _: &Client, _: &wl_buffer::WlBuffer, _: wl_buffer::Request, _: &ShmBuffer, _: &DisplayHandle, _: &mut DataInit<'_, Self>) {}
+}
+
+// --- wl_seat / wl_pointer / wl_keyboard / wl_touch ---
+
+impl GlobalDispatch<wl_seat::WlSeat, ()> for ServerState {
+    fn bind(_: &mut Self, _: &DisplayHandle, _: &Client, resource: New<wl_seat::WlSeat>, _: &(), data_init: &mut DataInit<'_, Self>) {
+        let seat = data_init.init(resource, ());
+        seat.capabilities(wl_seat::Capability::Pointer | wl_seat::Capability::Keyboard);
+        if seat.version() >= 2 {
+            seat.name("seat0".to_string());
+        }
+    }
+}
+
+/// キーマップを書き込んだ一時ファイル
+fn keymap_file(layout: Option<&KeyboardLayout>) -> std::io::Result<(File, u32)> {
+    let layout = layout.ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::NotFound, "キーボードレイアウトが設定されていません")
+    })?;
+    let text = layout.keymap().to_text();
+    let mut file = tempfile::tempfile()?;
+    // キーマップはNUL終端の文字列として渡す
+    file.write_all(text.as_bytes())?;
+    file.write_all(&[0])?;
+    Ok((file, text.len() as u32 + 1))
+}
+
+impl Dispatch<wl_seat::WlSeat, ()> for ServerState {
+    fn request(state: &mut Self, _: &Client, _: &wl_seat::WlSeat, request: wl_seat::Request, _: &(), _: &DisplayHandle, data_init: &mut DataInit<'_, Self>) {
+        match request {
+            wl_seat::Request::GetPointer { id } => {
+                let pointer = data_init.init(id, ());
+                state.seat.pointers.push(pointer);
+            }
+            wl_seat::Request::GetKeyboard { id } => {
+                let keyboard = data_init.init(id, ());
+                state.send_keymap(&keyboard);
+                if keyboard.version() >= 4 {
+                    keyboard.repeat_info(25, 600);
+                }
+                // すでにフォーカスのあるクライアントにはenterを送る
+                if let Some(focus) = state.seat.keyboard_focus.clone().filter(|focus| focus.id().same_client_as(&keyboard.id())) {
+                    let serial = state.next_serial();
+                    keyboard.enter(serial, &focus, Vec::new());
+                    state.send_modifiers(&keyboard, serial);
+                }
+                state.seat.keyboards.push(keyboard);
+            }
+            wl_seat::Request::GetTouch { id } => {
+                // タッチには対応していないが、プロトコル上はオブジェクトを作成する
+                data_init.init(id, ());
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<wl_pointer::WlPointer, ()> for ServerState {
+    fn request(_: &mut Self, _: &Client, _: &wl_pointer::WlPointer, _: wl_pointer::Request, _: &(), _: &DisplayHandle, _: &mut DataInit<'_, Self>) {
+        // カーソル画像の設定は無視する
+    }
+
+    fn destroyed(state: &mut Self, _: ClientId, resource: &wl_pointer::WlPointer, _: &()) {
+        state.seat.pointers.retain(|pointer| pointer != resource);
+    }
+}
+
+impl Dispatch<wl_keyboard::WlKeyboard, ()> for ServerState {
+    fn request(_: &mut Self, _: &Client, _: &wl_keyboard::WlKeyboard, _: wl_keyboard::Request, _: &(), _: &DisplayHandle, _: &mut DataInit<'_, Self>) {}
+
+    fn destroyed(state: &mut Self, _: ClientId, resource: &wl_keyboard::WlKeyboard, _: &()) {
+        state.seat.keyboards.retain(|keyboard| keyboard != resource);
+    }
+}
+
+impl Dispatch<wl_touch::WlTouch, ()> for ServerState {
+    fn request(_: &mut Self, _: &Client, _: &wl_touch::WlTouch, _: wl_touch::Request, _: &(), _: &DisplayHandle, _: &mut DataInit<'_, Self>) {}
+}
+
+// --- wl_output ---
+
+impl GlobalDispatch<wl_output::WlOutput, u32> for ServerState {
+    fn bind(state: &mut Self, _: &DisplayHandle, _: &Client, resource: New<wl_output::WlOutput>, output_id: &u32, data_init: &mut DataInit<'_, Self>) {
+        let output = data_init.init(resource, *output_id);
+        let compositor = lock(&state.compositor);
+        if let Some(device) = compositor.get_output(*output_id) {
+            let rect = device.logical_rect();
+            let (physical_width, physical_height) = device.physical_size();
+            let (width, height) = device.size();
+            output.geometry(
+                rect.x, rect.y, physical_width as i32, physical_height as i32,
+                wl_output::Subpixel::Unknown, "LumosDesktop".to_string(), device.name().to_string(),
+                wl_output::Transform::Normal,
+            );
+            output.mode(wl_output::Mode::Current, width as i32, height as i32, (device.refresh_rate() * 1000.0) as i32);
+            if output.version() >= 2 {
+                output.scale(device.scale_factor().ceil() as i32);
+            }
+            if output.version() >= 4 {
+                output.name(device.name().to_string());
+            }
+        }
+        if output.version() >= 2 {
+            output.done();
+        }
+    }
+}
+
+impl Dispatch<wl_output::WlOutput, u32> for ServerState {
+    fn request(_: &mut Self, _: &Client, _: &wl_output::WlOutput, _: wl_output::Request, _: &u32, _: &DisplayHandle, _: &mut DataInit<'_, Self>) {}
+}
+
+// --- xdg_wm_base / xdg_positioner / xdg_surface / xdg_toplevel / xdg_popup ---
+
+impl GlobalDispatch<xdg_wm_base::XdgWmBase, ()> for ServerState {
+    fn bind(_: &mut Self, _: &DisplayHandle, _: &Client, resource: New<xdg_wm_base::XdgWmBase>, _: &(), data_init: &mut DataInit<'_, Self>) {
+        data_init.init(resource, ());
+    }
+}
+
+impl Dispatch<xdg_wm_base::XdgWmBase, ()> for ServerState {
+    fn request(state: &mut Self, _: &Client, resource: &xdg_wm_base::XdgWmBase, request: xdg_wm_base::Request, _: &(), _: &DisplayHandle, data_init: &mut DataInit<'_, Self>) {
+        match request {
+            xdg_wm_base::Request::CreatePositioner { id } => {
+                data_init.init(id, Mutex::new(Positioner::default()));
+            }
+            xdg_wm_base::Request::GetXdgSurface { id, surface } => {
+                let Some(state_surface) = state.surfaces.get_mut(&surface.id()) else { return };
+                if state_surface.window_id.is_some() || matches!(state_surface.pending.buffer, Some(Some(_))) {
+                    resource.post_error(xdg_wm_base::Error::InvalidSurfaceState, "surface already has a buffer");
+                    return;
+                }
+                state_surface.xdg_surface = Some(data_init.init(id, surface.id()));
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<xdg_positioner::XdgPositioner, Mutex<Positioner>> for ServerState {
+    fn request(_: &mut Self, _: &Client, resource: &xdg_positioner::XdgPositioner, request: xdg_positioner::Request, data: &Mutex<Positioner>, _: &DisplayHandle, _: &mut DataInit<'_, Self>) {
+        let mut positioner = lock(data);
+        match request {
+            xdg_positioner::Request::SetSize { width, height } => {
+                if width <= 0 || height <= 0 {
+                    resource.post_error(xdg_positioner::Error::InvalidInput, "size must be positive");
+                    return;
+                }
+                positioner.size = (width as u32, height as u32);
+            }
+            xdg_positioner::Request::SetAnchorRect { x, y, width, height } => {
+                if width < 0 || height < 0 {
+                    resource.post_error(xdg_positioner::Error::InvalidInput, "anchor rect must not be negative");
+                    return;
+                }
+                positioner.anchor_rect = Rectangle::new(x, y, width as u32, height as u32);
+            }
+            xdg_positioner::Request::SetAnchor { anchor } => positioner.anchor = u32::from(anchor),
+            xdg_positioner::Request::SetGravity { gravity } => positioner.gravity = u32::from(gravity),
+            xdg_positioner::Request::SetOffset { x, y } => positioner.offset = (x, y),
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<xdg_surface::XdgSurface, ObjectId> for ServerState {
+    fn request(state: &mut Self, _: &Client, resource: &xdg_surface::XdgSurface, request: xdg_surface::Request, surface_id: &ObjectId, _: &DisplayHandle, data_init: &mut DataInit<'_, Self>) {
+        match request {
+            xdg_surface::Request::GetToplevel { id } => {
+                let toplevel = data_init.init(id, surface_id.clone());
+                let Some(surface) = state.surfaces.get_mut(surface_id) else { return };
+                if !matches!(surface.role, SurfaceRole::None) {
+                    resource.post_error(xdg_surface::Error::AlreadyConstructed, "surface already has a role");
+                    return;
+                }
+                surface.role = SurfaceRole::Toplevel(ToplevelState {
+                    toplevel,
+                    title: String::new(),
+                    app_id: String::new(),
+                    maximized: false,
+                    fullscreen: false,
+                    activated: false,
+                });
+            }
+            xdg_surface::Request::GetPopup { id, parent, positioner } => {
+                let popup = data_init.init(id, surface_id.clone());
+                let geometry = positioner.data::<Mutex<Positioner>>().map(|data| lock(data).geometry()).unwrap_or_default();
+                let parent = parent.and_then(|parent| parent.data::<ObjectId>().cloned());
+                let Some(surface) = state.surfaces.get_mut(surface_id) else { return };
+                if !matches!(surface.role, SurfaceRole::None) {
+                    resource.post_error(xdg_surface::Error::AlreadyConstructed, "surface already has a role");
+                    return;
+                }
+                surface.role = SurfaceRole::Popup(PopupState { popup, parent, geometry, grab: false });
+            }
+            xdg_surface::Request::AckConfigure { .. } => {
+                if let Some(surface) = state.surfaces.get_mut(surface_id) {
+                    surface.configured = true;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<xdg_toplevel::XdgToplevel, ObjectId> for ServerState {
+    fn request(state: &mut Self, _: &Client, _: &xdg_toplevel::XdgToplevel, request: xdg_toplevel::Request, surface_id: &ObjectId, _: &DisplayHandle, _: &mut DataInit<'_, Self>) {
+        let Some(surface) = state.surfaces.get_mut(surface_id) else { return };
+        let window_id = surface.window_id;
+        let SurfaceRole::Toplevel(toplevel) = &mut surface.role else { return };
+        let window = window_id.and_then(|id| lock(&state.compositor).get_window(id));
+        match request {
+            xdg_toplevel::Request::SetTitle { title } => {
+                if let Some(window) = window {
+                    window.borrow_mut().set_title(&title);
+                }
+                toplevel.title = title;
+            }
+            xdg_toplevel::Request::SetAppId { app_id } => {
+                if let Some(window) = window {
+                    window.borrow_mut().set_app_id(&app_id);
+                }
+                toplevel.app_id = app_id;
+            }
+            xdg_toplevel::Request::SetMaximized => toplevel.maximized = true,
+            xdg_toplevel::Request::UnsetMaximized => toplevel.maximized = false,
+            xdg_toplevel::Request::SetFullscreen { .. } => toplevel.fullscreen = true,
+            xdg_toplevel::Request::UnsetFullscreen => toplevel.fullscreen = false,
+            _ => return,
+        }
+        // 状態の変更にはconfigureで応答する（最初のコミットの前は最初のconfigureに含める）
+        if surface.configure_sent {
+            state.send_toplevel_configure(surface_id);
+        }
+    }
+
+    fn destroyed(state: &mut Self, _: ClientId, _: &xdg_toplevel::XdgToplevel, surface_id: &ObjectId) {
+        state.unmap(surface_id);
+    }
+}
+
+impl Dispatch<xdg_popup::XdgPopup, ObjectId> for ServerState {
+    fn request(state: &mut Self, _: &Client, _: &xdg_popup::XdgPopup, request: xdg_popup::Request, surface_id: &ObjectId, _: &DisplayHandle, _: &mut DataInit<'_, Self>) {
+        let Some(surface) = state.surfaces.get_mut(surface_id) else { return };
+        let SurfaceRole::Popup(popup) = &mut surface.role else { return };
+        match request {
+            xdg_popup::Request::Grab { .. } => popup.grab = true,
+            xdg_popup::Request::Reposition { positioner, token } => {
+                popup.geometry = positioner.data::<Mutex<Positioner>>().map(|data| lock(data).geometry()).unwrap_or_default();
+                popup.popup.repositioned(token);
+                // マップ中のポップアップはすぐに移動する
+                let SurfaceRole::Popup(popup) = &state.surfaces[surface_id].role else { return };
+                let (x, y) = state.popup_origin(popup);
+                if let Some(window) = state.window_of(surface_id) {
+                    let geometry = window.borrow().geometry();
+                    window.borrow_mut().set_geometry(Rectangle { x, y, ..geometry });
+                }
+                state.send_popup_configure(surface_id);
+            }
+            _ => {}
+        }
+    }
+
+    fn destroyed(state: &mut Self, _: ClientId, _: &xdg_popup::XdgPopup, surface_id: &ObjectId) {
+        state.unmap(surface_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixStream;
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+
+    use wayland_client::protocol::{
+        wl_buffer as client_buffer, wl_callback as client_callback, wl_compositor as client_compositor,
+        wl_keyboard as client_keyboard, wl_pointer as client_pointer, wl_registry, wl_seat as client_seat,
+        wl_shm as client_shm, wl_shm_pool as client_shm_pool, wl_surface as client_surface,
+    };
+    use wayland_client::{delegate_noop, Connection, QueueHandle};
+    use wayland_protocols::xdg::shell::client::{
+        xdg_surface as client_xdg_surface, xdg_toplevel as client_xdg_toplevel, xdg_wm_base as client_wm_base,
+    };
+
+    use crate::core::window_manager::input_translator::xkb_keymap::XkbKeymap;
+    use crate::core::window_manager::input_translator::{KeyCode, KeySym};
+    use super::super::headless::{HeadlessBackend, HeadlessOutputConfig};
+
+    /// テスト用のクライアントの状態（受け取ったイベントを文字列で記録する）
+    #[derive(Default)]
+    struct TestClient {
+        compositor: Option<client_compositor::WlCompositor>,
+        shm: Option<client_shm::WlShm>,
+        seat: Option<client_seat::WlSeat>,
+        wm_base: Option<client_wm_base::XdgWmBase>,
+        configured: bool,
+        frame_done: bool,
+        released: bool,
+        events: Vec<String>,
+    }
+
+    impl wayland_client::Dispatch<wl_registry::WlRegistry, ()> for TestClient {
+        fn event(state: &mut Self, registry: &wl_registry::WlRegistry, event: wl_registry::Event, _: &(), _: &Connection, qh: &QueueHandle<Self>) {
+            if let wl_registry::Event::Global { name, interface, version } = event {
+                match interface.as_str() {
+                    "wl_compositor" => state.compositor = Some(registry.bind(name, version, qh, ())),
+                    "wl_shm" => state.shm = Some(registry.bind(name, 1, qh, ())),
+                    "wl_seat" => state.seat = Some(registry.bind(name, version, qh, ())),
+                    "xdg_wm_base" => state.wm_base = Some(registry.bind(name, version, qh, ())),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    impl wayland_client::Dispatch<client_xdg_surface::XdgSurface, ()> for TestClient {
+        fn event(state: &mut Self, xdg_surface: &client_xdg_surface::XdgSurface, event: client_xdg_surface::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {
+            if let client_xdg_surface::Event::Configure { serial } = event {
+                xdg_surface.ack_configure(serial);
+                state.configured = true;
+            }
+        }
+    }
+
+    impl wayland_client::Dispatch<client_callback::WlCallback, ()> for TestClient {
+        fn event(state: &mut Self, _: &client_callback::WlCallback, _: client_callback::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {
+            state.frame_done = true;
+        }
+    }
+
+    impl wayland_client::Dispatch<client_buffer::WlBuffer, ()> for TestClient {
+        fn event(state: &mut Self, _: &client_buffer::WlBuffer, _: client_buffer::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {
+            state.released = true;
+        }
+    }
+
+    impl wayland_client::Dispatch<client_pointer::WlPointer, ()> for TestClient {
+        fn event(state: &mut Self, _: &client_pointer::WlPointer, event: client_pointer::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {
+            match event {
+                client_pointer::Event::Enter { surface_x, surface_y, .. } => state.events.push(format!("pointer enter {} {}", surface_x, surface_y)),
+                client_pointer::Event::Button { button, state: pressed, .. } => state.events.push(format!("button {} {:?}", button, pressed.into_result().unwrap())),
+                _ => {}
+            }
+        }
+    }
+
+    impl wayland_client::Dispatch<client_keyboard::WlKeyboard, ()> for TestClient {
+        fn event(state: &mut Self, _: &client_keyboard::WlKeyboard, event: client_keyboard::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {
+            match event {
+                client_keyboard::Event::Keymap { fd, size, .. } => {
+                    // 送られたテキストを読み戻し、キーマップとして解釈できることを確かめる
+                    let mut text = vec![0; size as usize];
+                    File::from(fd).read_exact_at(&mut text, 0).unwrap();
+                    let text = String::from_utf8(text).unwrap();
+                    for section in ["xkb_keycodes", "xkb_types", "xkb_compatibility", "xkb_symbols"] {
+                        assert!(text.contains(section), "{} がありません", section);
+                    }
+                    let keymap = XkbKeymap::parse(text.trim_end_matches('\0')).unwrap();
+                    state.events.push(format!("keymap {}", keymap.group_name(0).unwrap()));
+                }
+                client_keyboard::Event::Enter { .. } => state.events.push("keyboard enter".to_string()),
+                client_keyboard::Event::Key { key, state: pressed, .. } => state.events.push(format!("key {} {:?}", key, pressed.into_result().unwrap())),
+                _ => {}
+            }
+        }
+    }
+
+    impl wayland_client::Dispatch<client_wm_base::XdgWmBase, ()> for TestClient {
+        fn event(_: &mut Self, wm_base: &client_wm_base::XdgWmBase, event: client_wm_base::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {
+            if let client_wm_base::Event::Ping { serial } = event {
+                wm_base.pong(serial);
+            }
+        }
+    }
+
+    delegate_noop!(TestClient: client_compositor::WlCompositor);
+    delegate_noop!(TestClient: client_shm_pool::WlShmPool);
+    delegate_noop!(TestClient: ignore client_shm::WlShm);
+    delegate_noop!(TestClient: ignore client_seat::WlSeat);
+    delegate_noop!(TestClient: ignore client_surface::WlSurface);
+    delegate_noop!(TestClient: ignore client_xdg_toplevel::XdgToplevel);
+
+    /// クライアントからの通知を待つ間、サーバーのリクエストを処理する
+    fn pump_until(server: &mut WaylandServer, rx: &mpsc::Receiver<&'static str>, expected: &str) {
+        for _ in 0..5000 {
+            server.dispatch().unwrap();
+            match rx.try_recv() {
+                Ok(message) => {
+                    assert_eq!(message, expected);
+                    return;
+                }
+                Err(mpsc::TryRecvError::Empty) => thread::sleep(Duration::from_millis(1)),
+                Err(err) => panic!("client thread stopped: {}", err),
+            }
+        }
+        panic!("timed out waiting for {}", expected);
+    }
+
+    /// 4x4の単色のトップレベルを表示し、入力イベントを記録するクライアント
+    fn run_client(path: PathBuf, tx: mpsc::Sender<&'static str>, go: mpsc::Receiver<()>) -> Vec<String> {
+        let connection = Connection::from_socket(UnixStream::connect(path).unwrap()).unwrap();
+        let mut queue = connection.new_event_queue();
+        let qh = queue.handle();
+        let mut client = TestClient::default();
+        connection.display().get_registry(&qh, ());
+        queue.roundtrip(&mut client).unwrap();
+
+        let seat = client.seat.clone().unwrap();
+        seat.get_pointer(&qh, ());
+        seat.get_keyboard(&qh, ());
+
+        let surface = client.compositor.as_ref().unwrap().create_surface(&qh, ());
+        let xdg_surface = client.wm_base.as_ref().unwrap().get_xdg_surface(&surface, &qh, ());
+        let toplevel = xdg_surface.get_toplevel(&qh, ());
+        toplevel.set_title("テスト".to_string());
+        surface.commit();
+        queue.roundtrip(&mut client).unwrap();
+        assert!(client.configured);
+
+        // 赤で塗りつぶしたARGB8888のバッファ
+        let mut file = tempfile::tempfile().unwrap();
+        let pixels: Vec<u8> = std::iter::repeat([0x00, 0x00, 0xff, 0xff]).take(16).flatten().collect();
+        file.write_all(&pixels).unwrap();
+        let pool = client.shm.as_ref().unwrap().create_pool(file.as_fd(), pixels.len() as i32, &qh, ());
+        let buffer = pool.create_buffer(0, 4, 4, 16, client_shm::Format::Argb8888, &qh, ());
+        surface.attach(Some(&buffer), 0, 0);
+        surface.damage_buffer(0, 0, 4, 4);
+        surface.frame(&qh, ());
+        surface.commit();
+        queue.roundtrip(&mut client).unwrap();
+        assert!(client.released);
+        tx.send("mapped").unwrap();
+
+        while !client.frame_done {
+            queue.blocking_dispatch(&mut client).unwrap();
+        }
+        tx.send("frame").unwrap();
+
+        go.recv().unwrap();
+        queue.roundtrip(&mut client).unwrap();
+        tx.send("input").unwrap();
+        client.events
+    }
+
+    #[test]
+    fn test_shm_toplevel_is_composited_and_receives_input() {
+        let dir = tempfile::tempdir().unwrap();
+        let compositor = Arc::new(Mutex::new(LumosCompositor::new()));
+        let mut backend = HeadlessBackend::with_compositor(compositor.clone());
+        backend.add_output(HeadlessOutputConfig::new(32, 32));
+        let mut server = WaylandServer::bind(dir.path().join("wayland-test"), compositor.clone()).unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        let (go_tx, go_rx) = mpsc::channel();
+        let path = server.socket_path().to_path_buf();
+        let client = thread::spawn(move || run_client(path, tx, go_rx));
+
+        pump_until(&mut server, &rx, "mapped");
+        assert_eq!(server.mapped_window_count(), 1);
+        let window_id = *server.state.window_surfaces.keys().next().unwrap();
+        {
+            let window = lock(&compositor).get_window(window_id).unwrap();
+            let window = window.borrow();
+            assert_eq!(window.title(), "テスト");
+            assert_eq!(window.geometry(), Rectangle::new(0, 0, 4, 4));
+        }
+
+        // コピーしたバッファがCPUで合成され、表示後にフレームコールバックが届く
+        backend.advance_to_next_vblank();
+        let frame = backend.capture(1).unwrap();
+        assert_eq!(frame.pixel(1, 1), Some(0xff_ff_00_00));
+        assert_eq!(frame.pixel(4, 4), Some(0xff_00_00_00));
+        server.send_frame_callbacks();
+        pump_until(&mut server, &rx, "frame");
+
+        let press = InputEvent::new(InputEventType::MousePress {
+            button: MouseButton::Left,
+            x: 1.0,
+            y: 2.0,
+            modifiers: Default::default(),
+            timestamp: 10,
+        });
+        assert!(server.handle_input(&press));
+        // XKBのキーコード38（AC01）はevdevの30
+        let key = InputEvent::new(InputEventType::KeyPress {
+            key_code: KeyCode(38),
+            key_sym: KeySym("a".to_string()),
+            modifiers: Default::default(),
+            timestamp: 11,
+            repeat: false,
+        });
+        assert!(server.handle_input(&key));
+        // ウィンドウの外では誰にも届かない
+        assert!(!server.handle_input(&InputEvent::new(InputEventType::MouseMove {
+            x: 20.0,
+            y: 20.0,
+            dx: 19.0,
+            dy: 18.0,
+            modifiers: Default::default(),
+            timestamp: 12,
+        })));
+        go_tx.send(()).unwrap();
+        pump_until(&mut server, &rx, "input");
+
+        let events = client.join().unwrap();
+        for expected in ["keymap English (US)", "keyboard enter", "pointer enter 1 2", "button 272 Pressed", "key 30 Pressed"] {
+            assert!(events.iter().any(|event| event == expected), "{} not in {:?}", expected, events);
+        }
+    }
+
+    #[test]
+    fn test_oversized_shm_buffer_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let compositor = Arc::new(Mutex::new(LumosCompositor::new()));
+        let mut server = WaylandServer::bind(dir.path().join("wayland-test"), compositor).unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        let path = server.socket_path().to_path_buf();
+        let client = thread::spawn(move || {
+            let connection = Connection::from_socket(UnixStream::connect(path).unwrap()).unwrap();
+            let mut queue = connection.new_event_queue();
+            let qh = queue.handle();
+            let mut client = TestClient::default();
+            connection.display().get_registry(&qh, ());
+            queue.roundtrip(&mut client).unwrap();
+
+            // プールの大きさはクライアントが申告した値なので、実際のファイルは小さくてもよい
+            let file = tempfile::tempfile().unwrap();
+            let pool = client.shm.as_ref().unwrap().create_pool(file.as_fd(), i32::MAX, &qh, ());
+            pool.create_buffer(0, 16384, 16384, 16384 * 4, client_shm::Format::Argb8888, &qh, ());
+            assert!(queue.roundtrip(&mut client).is_err());
+            let error = connection.protocol_error().unwrap();
+            assert_eq!(error.code, u32::from(client_shm::Error::InvalidStride));
+            tx.send("rejected").unwrap();
+        });
+
+        pump_until(&mut server, &rx, "rejected");
+        client.join().unwrap();
+    }
+
+    #[test]
+    fn test_keymap_follows_active_layout() {
+        let dir = tempfile::tempdir().unwrap();
+        let compositor = Arc::new(Mutex::new(LumosCompositor::new()));
+        let input_manager = Arc::new(Mutex::new(InputManager::new()));
+        lock(&input_manager).set_keyboard_layouts(KeyboardLayoutManager::with_layouts(&["de", "us"]).unwrap());
+        let mut server = WaylandServer::bind(dir.path().join("wayland-test"), compositor)
+            .unwrap()
+            .with_input_manager(input_manager.clone());
+
+        let (tx, rx) = mpsc::channel();
+        let (go_tx, go_rx) = mpsc::channel();
+        let path = server.socket_path().to_path_buf();
+        let client = thread::spawn(move || {
+            let connection = Connection::from_socket(UnixStream::connect(path).unwrap()).unwrap();
+            let mut queue = connection.new_event_queue();
+            let qh = queue.handle();
+            let mut client = TestClient::default();
+            connection.display().get_registry(&qh, ());
+            queue.roundtrip(&mut client).unwrap();
+            client.seat.clone().unwrap().get_keyboard(&qh, ());
+            queue.roundtrip(&mut client).unwrap();
+            tx.send("bound").unwrap();
+
+            go_rx.recv().unwrap();
+            queue.roundtrip(&mut client).unwrap();
+            tx.send("switched").unwrap();
+            client.events
+        });
+
+        pump_until(&mut server, &rx, "bound");
+        lock(&input_manager).keyboard_layouts_mut().next_layout();
+        go_tx.send(()).unwrap();
+        pump_until(&mut server, &rx, "switched");
+
+        let events = client.join().unwrap();
+        assert_eq!(events, ["keymap German", "keymap English (US)"]);
+    }
+
+    #[test]
+    fn test_modifier_mask_maps_level_three_to_mod5() {
+        let modifiers: HashSet<_> = [KeyModifier::Shift, KeyModifier::LevelThree, KeyModifier::NumLock].into_iter().collect();
+        // Shift（ビット0）とMod5（ビット7）が押下中、Mod2（ビット4）がロック中
+        assert_eq!(modifier_mask(&modifiers), (0x81, 0x10));
+    }
+
+    #[test]
+    fn test_positioner_places_popup_relative_to_anchor() {
+        let mut positioner = Positioner {
+            size: (40, 20),
+            anchor_rect: Rectangle::new(10, 10, 30, 10),
+            ..Default::default()
+        };
+        // 既定ではアンカー矩形の中心に中央をそろえる
+        assert_eq!(positioner.geometry(), Rectangle::new(5, 5, 40, 20));
+
+        // アンカー矩形の左下から右下方向に開くメニュー
+        positioner.anchor = u32::from(xdg_positioner::Anchor::BottomLeft);
+        positioner.gravity = u32::from(xdg_positioner::Gravity::BottomRight);
+        positioner.offset = (0, 2);
+        assert_eq!(positioner.geometry(), Rectangle::new(10, 22, 40, 20));
+
+        positioner.anchor = u32::from(xdg_positioner::Anchor::Top);
+        positioner.gravity = u32::from(xdg_positioner::Gravity::Top);
+        positioner.offset = (0, 0);
+        assert_eq!(positioner.geometry(), Rectangle::new(5, -10, 40, 20));
+    }
+}
@@ -7,7 +7,7 @@ use std::sync::{Arc, Mutex};
 
 use crate::core::window_manager::scene_graph::NodeId;
 use crate::core::window_manager::input_translator::{
-    InputEvent, InputEventType, MouseButton, KeyModifier,
+    InputClock, InputEvent, InputEventType, MouseButton, KeyModifier,
 };
 
 /// ジェスチャー種類
@@ -317,6 +317,9 @@ pub trait GestureRecognizer: Send + Sync {
     fn update(&mut self, event: &InputEvent) -> Option<GestureInfo>;
     fn reset(&mut self);
     fn is_active(&self) -> bool;
+
+    /// 経過時間の計測に使う時刻源を設定（時間を使わない認識器では何もしない）
+    fn set_clock(&mut self, _clock: Arc<dyn InputClock>) {}
 }
 
 /// タップ認識器
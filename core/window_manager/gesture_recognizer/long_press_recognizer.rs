@@ -2,11 +2,12 @@
 // 一定時間以上のタッチやクリックを長押しとして認識する
 
 use std::collections::HashSet;
-use std::time::{Duration, Instant};
+use std::sync::Arc;
+use std::time::Duration;
 
 use crate::core::window_manager::scene_graph::NodeId;
 use crate::core::window_manager::input_translator::{
-    InputEvent, InputEventType, MouseButton, KeyModifier,
+    InputClock, InputEvent, InputEventType, MouseButton, KeyModifier, SystemClock,
 };
 use crate::core::window_manager::gesture_recognizer::{
     GestureRecognizer, GestureType, GestureState, GestureInfo, SwipeDirection,
@@ -24,9 +25,11 @@ pub struct LongPressRecognizer {
     movement_threshold: f64,
     long_press_time: Duration,
     feedback_interval: Duration,
-    start_time: Option<Instant>,
-    last_feedback_time: Option<Instant>,
+    /// 押下時刻と最後の更新イベントの時刻（時刻源の基準からの経過時間）
+    start_time: Option<Duration>,
+    last_feedback_time: Option<Duration>,
     touch_id: Option<u64>,
+    clock: Arc<dyn InputClock>,
 }
 
 impl LongPressRecognizer {
@@ -45,6 +48,7 @@ impl LongPressRecognizer {
             start_time: None,
             last_feedback_time: None,
             touch_id: None,
+            clock: Arc::new(SystemClock::new()),
         }
     }
     
@@ -63,6 +67,11 @@ impl LongPressRecognizer {
         self
     }
     
+    pub fn with_clock(mut self, clock: Arc<dyn InputClock>) -> Self {
+        self.clock = clock;
+        self
+    }
+    
     /// 長押し時間を確認し、認識イベントを生成
     fn check_long_press(&mut self, current_position: (f64, f64), timestamp: u64) -> Option<GestureInfo> {
         if let (Some(start_pos), Some(start_time)) = (self.press_position, self.start_time) {
@@ -77,14 +86,14 @@ impl LongPressRecognizer {
                 return None;
             }
             
-            let elapsed = Instant::now().duration_since(start_time);
+            let elapsed = self.clock.now().saturating_sub(start_time);
             
             // 長押し時間に達したかチェック
             if elapsed >= self.long_press_time {
                 if !self.is_recognized {
                     // 初回認識
                     self.is_recognized = true;
-                    self.last_feedback_time = Some(Instant::now());
+                    self.last_feedback_time = Some(self.clock.now());
                     
                     let mut gesture = GestureInfo::new(
                         GestureType::LongPress,
@@ -110,10 +119,10 @@ impl LongPressRecognizer {
                     return Some(gesture);
                 } else if let Some(last_time) = self.last_feedback_time {
                     // 継続中の長押し - 定期的な更新
-                    let since_last = Instant::now().duration_since(last_time);
+                    let since_last = self.clock.now().saturating_sub(last_time);
                     
                     if since_last >= self.feedback_interval {
-                        self.last_feedback_time = Some(Instant::now());
+                        self.last_feedback_time = Some(self.clock.now());
                         
                         let mut gesture = GestureInfo::new(
                             GestureType::LongPress,
@@ -172,7 +181,7 @@ impl GestureRecognizer for LongPressRecognizer {
                 self.source_device = event.source_device.clone();
                 self.is_active = true;
                 self.is_recognized = false;
-                self.start_time = Some(Instant::now());
+                self.start_time = Some(self.clock.now());
                 self.last_feedback_time = None;
                 
                 None
@@ -202,7 +211,7 @@ impl GestureRecognizer for LongPressRecognizer {
                     }
                     
                     if let Some(start_time) = self.start_time {
-                        let elapsed = Instant::now().duration_since(start_time);
+                        let elapsed = self.clock.now().saturating_sub(start_time);
                         gesture = gesture.with_long_press_duration(elapsed);
                     }
                     
@@ -241,7 +250,7 @@ impl GestureRecognizer for LongPressRecognizer {
                     self.source_device = event.source_device.clone();
                     self.is_active = true;
                     self.is_recognized = false;
-                    self.start_time = Some(Instant::now());
+                    self.start_time = Some(self.clock.now());
                     self.last_feedback_time = None;
                     self.touch_id = Some(*id);
                 }
@@ -280,7 +289,7 @@ impl GestureRecognizer for LongPressRecognizer {
                     }
                     
                     if let Some(start_time) = self.start_time {
-                        let elapsed = Instant::now().duration_since(start_time);
+                        let elapsed = self.clock.now().saturating_sub(start_time);
                         gesture = gesture.with_long_press_duration(elapsed);
                     }
                     
@@ -328,17 +337,23 @@ impl GestureRecognizer for LongPressRecognizer {
     fn is_active(&self) -> bool {
         self.is_active
     }
+    
+    fn set_clock(&mut self, clock: Arc<dyn InputClock>) {
+        self.clock = clock;
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::thread;
+    use crate::core::window_manager::input_translator::ManualClock;
     
     #[test]
     fn test_long_press_recognizer() {
+        let clock = Arc::new(ManualClock::new());
         let mut recognizer = LongPressRecognizer::new()
-            .with_long_press_time(Duration::from_millis(100)); // テスト用に短い時間
+            .with_long_press_time(Duration::from_millis(100)) // テスト用に短い時間
+            .with_clock(clock.clone());
             
         // プレス開始
         let timestamp = 1000;
@@ -354,8 +369,8 @@ mod tests {
         assert!(result.is_none());
         assert!(recognizer.is_active());
         
-        // 待機（長押し時間）
-        thread::sleep(Duration::from_millis(150));
+        // 長押し時間だけ時刻を進める
+        clock.advance(Duration::from_millis(150));
         
         // 移動イベント（長押し認識トリガー）
         let timestamp = 1150;
@@ -378,7 +393,7 @@ mod tests {
         }
         
         // さらに少し動かす（更新イベント）
-        thread::sleep(Duration::from_millis(150));
+        clock.advance(Duration::from_millis(150));
         
         let timestamp = 1300;
         let event = InputEvent::new(InputEventType::MouseMove {
@@ -422,9 +437,11 @@ mod tests {
     
     #[test]
     fn test_long_press_cancel_on_move() {
+        let clock = Arc::new(ManualClock::new());
         let mut recognizer = LongPressRecognizer::new()
             .with_long_press_time(Duration::from_millis(200))
-            .with_movement_threshold(10.0);
+            .with_movement_threshold(10.0)
+            .with_clock(clock.clone());
             
         // プレス開始
         let timestamp = 1000;
@@ -453,7 +470,7 @@ mod tests {
         assert!(result.is_none());
         
         // 長押し時間が過ぎても認識されないことを確認
-        thread::sleep(Duration::from_millis(250));
+        clock.advance(Duration::from_millis(250));
         
         let timestamp = 1300;
         let event = InputEvent::new(InputEventType::MouseMove {
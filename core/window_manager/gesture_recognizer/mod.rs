@@ -24,14 +24,17 @@ pub use gesture_recognizer::{
 pub use rotate_recognizer::RotationDirection;
 
 use std::collections::HashMap;
-use std::time::Instant;
+use std::sync::Arc;
+use std::time::Duration;
 
-use crate::core::window_manager::input_translator::InputEvent;
+use crate::core::window_manager::input_translator::{InputClock, InputEvent, SystemClock};
 
 /// マルチジェスチャー処理を担当するジェスチャーマネージャー
 pub struct GestureManager {
     recognizers: HashMap<GestureType, Box<dyn GestureRecognizer + Send + Sync>>,
-    last_update: Instant,
+    /// 認識器が経過時間の計測に使う時刻源
+    clock: Arc<dyn InputClock>,
+    last_update: Duration,
     active_recognizers: Vec<GestureType>,
     gesture_callbacks: Vec<Box<dyn Fn(&GestureInfo) -> bool + Send + Sync>>,
 }
@@ -39,16 +42,31 @@ pub struct GestureManager {
 impl GestureManager {
     /// 新しいジェスチャーマネージャーを作成
     pub fn new() -> Self {
+        let clock: Arc<dyn InputClock> = Arc::new(SystemClock::new());
         Self {
             recognizers: HashMap::new(),
-            last_update: Instant::now(),
+            last_update: clock.now(),
+            clock,
             active_recognizers: Vec::new(),
             gesture_callbacks: Vec::new(),
         }
     }
     
-    /// 認識器を登録
-    pub fn register_recognizer(&mut self, recognizer: Box<dyn GestureRecognizer + Send + Sync>) {
+    /// 時刻源を設定し、登録済みの認識器にも適用
+    ///
+    /// 入力マネージャーと同じ時刻源を設定すると、トレース再生時にも長押しなどの
+    /// 経過時間が記録時と同じになります。
+    pub fn set_clock(&mut self, clock: Arc<dyn InputClock>) {
+        for recognizer in self.recognizers.values_mut() {
+            recognizer.set_clock(clock.clone());
+        }
+        self.last_update = clock.now();
+        self.clock = clock;
+    }
+    
+    /// 認識器を登録（マネージャーの時刻源が設定される）
+    pub fn register_recognizer(&mut self, mut recognizer: Box<dyn GestureRecognizer + Send + Sync>) {
+        recognizer.set_clock(self.clock.clone());
         let gesture_type = recognizer.gesture_type();
         self.recognizers.insert(gesture_type, recognizer);
     }
@@ -133,7 +151,7 @@ impl GestureManager {
             }
         }
         
-        self.last_update = Instant::now();
+        self.last_update = self.clock.now();
         detected_gestures
     }
     
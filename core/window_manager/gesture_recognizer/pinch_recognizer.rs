@@ -2,11 +2,12 @@
 // 二本指でのピンチイン・ピンチアウト操作を認識する
 
 use std::collections::{HashMap, HashSet};
-use std::time::Instant;
+use std::sync::Arc;
+use std::time::Duration;
 
 use crate::core::window_manager::scene_graph::NodeId;
 use crate::core::window_manager::input_translator::{
-    InputEvent, InputEventType, MouseButton, KeyModifier,
+    InputClock, InputEvent, InputEventType, MouseButton, KeyModifier, SystemClock,
 };
 use crate::core::window_manager::gesture_recognizer::{
     GestureRecognizer, GestureType, GestureState, GestureInfo, SwipeDirection,
@@ -45,8 +46,9 @@ pub struct PinchRecognizer {
     min_distance_threshold: f64,
     min_scale_change_threshold: f64,
     modifiers: HashSet<KeyModifier>,
-    start_time: Option<Instant>,
+    start_time: Option<Duration>,
     last_gesture_pattern: Option<PinchPattern>,
+    clock: Arc<dyn InputClock>,
 }
 
 impl PinchRecognizer {
@@ -68,6 +70,7 @@ impl PinchRecognizer {
             modifiers: HashSet::new(),
             start_time: None,
             last_gesture_pattern: None,
+            clock: Arc::new(SystemClock::new()),
         }
     }
     
@@ -227,7 +230,7 @@ impl GestureRecognizer for PinchRecognizer {
                     self.target = event.target;
                     self.source_device = event.source_device.clone();
                     self.modifiers = HashSet::new();
-                    self.start_time = Some(Instant::now());
+                    self.start_time = Some(self.clock.now());
                 }
                 
                 None
@@ -349,7 +352,7 @@ impl GestureRecognizer for PinchRecognizer {
                         self.target = event.target;
                         self.source_device = event.source_device.clone();
                         self.modifiers = modifiers.clone();
-                        self.start_time = Some(Instant::now());
+                        self.start_time = Some(self.clock.now());
                         self.start_timestamp = Some(*timestamp);
                         self.last_timestamp = Some(*timestamp);
                         
@@ -446,9 +449,9 @@ impl GestureRecognizer for PinchRecognizer {
                     if self.is_active && self.is_recognized && 
                        event.source_device.as_deref() == Some("touchpad") {
                         // 一定時間経過後に自動終了
-                        let now = Instant::now();
+                        let now = self.clock.now();
                         if let Some(start) = self.start_time {
-                            if now.duration_since(start).as_millis() > 200 {
+                            if now.saturating_sub(start).as_millis() > 200 {
                                 let result = if let Some(center) = self.center_position {
                                     let mut gesture = GestureInfo::new(
                                         GestureType::Pinch,
@@ -517,11 +520,16 @@ impl GestureRecognizer for PinchRecognizer {
     fn is_active(&self) -> bool {
         self.is_active
     }
+    
+    fn set_clock(&mut self, clock: Arc<dyn InputClock>) {
+        self.clock = clock;
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::core::window_manager::input_translator::ManualClock;
     
     #[test]
     fn test_pinch_recognizer() {
@@ -648,7 +656,9 @@ mod tests {
     
     #[test]
     fn test_pinch_recognizer_with_touchpad() {
+        let clock = Arc::new(ManualClock::new());
         let mut recognizer = PinchRecognizer::new();
+        recognizer.set_clock(clock.clone());
         
         // トラックパッドからのCtrl+ホイールイベント（ピンチイン）
         let mut modifiers = HashSet::new();
@@ -714,8 +724,8 @@ mod tests {
         });
         event.source_device = Some("touchpad".to_string());
         
-        // 長時間経過したとみなす
-        recognizer.start_time = Some(Instant::now() - std::time::Duration::from_millis(300));
+        // 開始から300ミリ秒経過させる
+        clock.advance(Duration::from_millis(300));
         
         let result = recognizer.update(&event);
         assert!(result.is_some());
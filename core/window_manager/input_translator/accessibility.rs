@@ -0,0 +1,929 @@
+// LumosDesktop 入力アクセシビリティ
+// 固定キー・スローキー・バウンスキー・マウスキーの入力フィルター
+
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::core::settings::{SettingsError, SettingsManager};
+use super::input_manager::{InputEvent, InputEventType, KeyCode, KeyModifier, KeySym, MouseButton};
+
+/// アクセシビリティ設定の設定キー
+pub const ACCESSIBILITY_SETTINGS_PATH: &str = "input.accessibility";
+
+/// マウスキーで生成したイベントの入力ソース名
+pub const MOUSE_KEYS_SOURCE: &str = "mouse-keys";
+
+/// 固定キー（モディファイアを押し続けなくても組み合わせ入力できる）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StickyKeysSettings {
+    pub enabled: bool,
+    /// 同じモディファイアを2回押すとロック
+    pub lock_on_double_press: bool,
+    /// モディファイアと他のキーが同時に押されたら固定キーを無効化
+    pub disable_on_chord: bool,
+}
+
+impl Default for StickyKeysSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            lock_on_double_press: true,
+            disable_on_chord: false,
+        }
+    }
+}
+
+/// スローキー（一定時間押し続けたキーだけを受け付ける）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SlowKeysSettings {
+    pub enabled: bool,
+    pub delay_ms: u64,
+}
+
+impl Default for SlowKeysSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            delay_ms: 300,
+        }
+    }
+}
+
+/// バウンスキー（離した直後の同じキーの再入力を無視する）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BounceKeysSettings {
+    pub enabled: bool,
+    pub delay_ms: u64,
+}
+
+impl Default for BounceKeysSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            delay_ms: 300,
+        }
+    }
+}
+
+/// マウスキー（テンキーでポインターを操作する）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MouseKeysSettings {
+    pub enabled: bool,
+    /// キーを押した瞬間の移動量（ピクセル）
+    pub initial_step: f64,
+    /// 押し始めの速度（ピクセル/秒）
+    pub initial_speed: f64,
+    /// 最高速度（ピクセル/秒）
+    pub max_speed: f64,
+    /// 最高速度に達するまでの時間
+    pub acceleration_time_ms: u64,
+}
+
+impl Default for MouseKeysSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            initial_step: 1.0,
+            initial_speed: 50.0,
+            max_speed: 800.0,
+            acceleration_time_ms: 1000,
+        }
+    }
+}
+
+/// 入力アクセシビリティ設定（`input.accessibility` に保存される）
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AccessibilitySettings {
+    pub sticky_keys: StickyKeysSettings,
+    pub slow_keys: SlowKeysSettings,
+    pub bounce_keys: BounceKeysSettings,
+    pub mouse_keys: MouseKeysSettings,
+}
+
+/// アクセシビリティ機能の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AccessibilityFeature {
+    StickyKeys,
+    SlowKeys,
+    BounceKeys,
+    MouseKeys,
+}
+
+/// 固定キーのモディファイア状態
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StickyState {
+    /// 次のキー入力1回だけに適用
+    Latched,
+    /// 解除されるまで適用
+    Locked,
+}
+
+/// アクセシビリティフィルターの状態変化（画面上のインジケーター用）
+#[derive(Debug, Clone, PartialEq)]
+pub enum AccessibilityEvent {
+    FeatureToggled { feature: AccessibilityFeature, enabled: bool },
+    ModifierLatched(KeyModifier),
+    ModifierLocked(KeyModifier),
+    ModifierCleared(KeyModifier),
+    SlowKeyPending(KeyCode),
+    SlowKeyAccepted(KeyCode),
+    SlowKeyRejected(KeyCode),
+    KeyBounced(KeyCode),
+    MouseKeysButtonSelected(MouseButton),
+}
+
+/// アクセシビリティ状態変化リスナー
+pub type AccessibilityListener = Box<dyn Fn(&AccessibilityEvent) + Send + Sync>;
+
+/// キーシンボルに対応するモディファイア
+pub fn modifier_for_keysym(key_sym: &KeySym) -> Option<KeyModifier> {
+    match key_sym.0.as_str() {
+        "Shift_L" | "Shift_R" => Some(KeyModifier::Shift),
+        "Control_L" | "Control_R" => Some(KeyModifier::Ctrl),
+        "Alt_L" | "Alt_R" => Some(KeyModifier::Alt),
+        "Meta_L" | "Meta_R" => Some(KeyModifier::Meta),
+        "Super_L" | "Super_R" => Some(KeyModifier::Super),
+        "Hyper_L" | "Hyper_R" => Some(KeyModifier::Hyper),
+        "ISO_Level3_Shift" => Some(KeyModifier::LevelThree),
+        _ => None,
+    }
+}
+
+/// マウスキーでのテンキー操作
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum MouseKeyAction {
+    Move(f64, f64),
+    Click,
+    DoubleClick,
+    Press,
+    Release,
+    SelectButton(MouseButton),
+}
+
+fn mouse_key_action(key_sym: &KeySym) -> Option<MouseKeyAction> {
+    // NumLock の状態によってキーシンボルが変わるため両方を受け付ける
+    let action = match key_sym.0.as_str() {
+        "KP_8" | "KP_Up" => MouseKeyAction::Move(0.0, -1.0),
+        "KP_2" | "KP_Down" => MouseKeyAction::Move(0.0, 1.0),
+        "KP_4" | "KP_Left" => MouseKeyAction::Move(-1.0, 0.0),
+        "KP_6" | "KP_Right" => MouseKeyAction::Move(1.0, 0.0),
+        "KP_7" | "KP_Home" => MouseKeyAction::Move(-1.0, -1.0),
+        "KP_9" | "KP_Prior" | "KP_Page_Up" => MouseKeyAction::Move(1.0, -1.0),
+        "KP_1" | "KP_End" => MouseKeyAction::Move(-1.0, 1.0),
+        "KP_3" | "KP_Next" | "KP_Page_Down" => MouseKeyAction::Move(1.0, 1.0),
+        "KP_5" | "KP_Begin" => MouseKeyAction::Click,
+        "KP_Add" => MouseKeyAction::DoubleClick,
+        "KP_0" | "KP_Insert" => MouseKeyAction::Press,
+        "KP_Decimal" | "KP_Delete" => MouseKeyAction::Release,
+        "KP_Divide" => MouseKeyAction::SelectButton(MouseButton::Left),
+        "KP_Multiply" => MouseKeyAction::SelectButton(MouseButton::Middle),
+        "KP_Subtract" => MouseKeyAction::SelectButton(MouseButton::Right),
+        _ => return None,
+    };
+    Some(action)
+}
+
+/// スローキーで受付待ちのキー
+struct PendingSlowKey {
+    event: InputEvent,
+    pressed_at: u64,
+    held_ms: u64,
+    last_tick: Option<u64>,
+}
+
+/// 入力アクセシビリティフィルター
+///
+/// ショートカット照合の前段で、バウンスキー → スローキー → マウスキー → 固定キーの
+/// 順にキー入力を変換します。1つの入力から0個以上のイベントが出力されます。
+pub struct AccessibilityFilter {
+    settings: AccessibilitySettings,
+
+    // 固定キー
+    sticky: HashMap<KeyModifier, StickyState>,
+    held_modifiers: HashMap<KeyModifier, bool>,
+    latch_consumers: HashSet<KeyCode>,
+
+    // スローキー
+    slow_pending: HashMap<KeyCode, PendingSlowKey>,
+
+    // バウンスキー
+    last_release: HashMap<KeyCode, u64>,
+    bounced: HashSet<KeyCode>,
+
+    // マウスキー
+    mouse_button: MouseButton,
+    mouse_held: HashMap<KeyCode, (f64, f64)>,
+    mouse_consumed: HashSet<KeyCode>,
+    mouse_elapsed_ms: u64,
+    mouse_last_tick: Option<u64>,
+    mouse_dragging: bool,
+
+    listeners: Vec<AccessibilityListener>,
+}
+
+impl AccessibilityFilter {
+    pub fn new() -> Self {
+        Self::with_settings(AccessibilitySettings::default())
+    }
+
+    pub fn with_settings(settings: AccessibilitySettings) -> Self {
+        Self {
+            settings,
+            sticky: HashMap::new(),
+            held_modifiers: HashMap::new(),
+            latch_consumers: HashSet::new(),
+            slow_pending: HashMap::new(),
+            last_release: HashMap::new(),
+            bounced: HashSet::new(),
+            mouse_button: MouseButton::Left,
+            mouse_held: HashMap::new(),
+            mouse_consumed: HashSet::new(),
+            mouse_elapsed_ms: 0,
+            mouse_last_tick: None,
+            mouse_dragging: false,
+            listeners: Vec::new(),
+        }
+    }
+
+    pub fn settings(&self) -> &AccessibilitySettings {
+        &self.settings
+    }
+
+    /// 設定を置き換え（無効になった機能の状態はリセット）
+    pub fn set_settings(&mut self, settings: AccessibilitySettings) {
+        let features = [
+            (AccessibilityFeature::StickyKeys, settings.sticky_keys.enabled),
+            (AccessibilityFeature::SlowKeys, settings.slow_keys.enabled),
+            (AccessibilityFeature::BounceKeys, settings.bounce_keys.enabled),
+            (AccessibilityFeature::MouseKeys, settings.mouse_keys.enabled),
+        ];
+        self.settings = settings;
+        for (feature, enabled) in features {
+            if !enabled {
+                self.reset_feature(feature);
+            }
+        }
+    }
+
+    /// 設定マネージャーから `input.accessibility` を読み込む
+    pub fn load_settings(&mut self, settings: &SettingsManager) -> Result<(), SettingsError> {
+        let accessibility: AccessibilitySettings = settings.get(ACCESSIBILITY_SETTINGS_PATH)?;
+        self.set_settings(accessibility);
+        Ok(())
+    }
+
+    /// 機能が有効かどうか
+    pub fn is_enabled(&self, feature: AccessibilityFeature) -> bool {
+        match feature {
+            AccessibilityFeature::StickyKeys => self.settings.sticky_keys.enabled,
+            AccessibilityFeature::SlowKeys => self.settings.slow_keys.enabled,
+            AccessibilityFeature::BounceKeys => self.settings.bounce_keys.enabled,
+            AccessibilityFeature::MouseKeys => self.settings.mouse_keys.enabled,
+        }
+    }
+
+    /// 機能の有効・無効を実行時に切り替え
+    pub fn set_enabled(&mut self, feature: AccessibilityFeature, enabled: bool) {
+        if self.is_enabled(feature) == enabled {
+            return;
+        }
+
+        match feature {
+            AccessibilityFeature::StickyKeys => self.settings.sticky_keys.enabled = enabled,
+            AccessibilityFeature::SlowKeys => self.settings.slow_keys.enabled = enabled,
+            AccessibilityFeature::BounceKeys => self.settings.bounce_keys.enabled = enabled,
+            AccessibilityFeature::MouseKeys => self.settings.mouse_keys.enabled = enabled,
+        }
+        if !enabled {
+            self.reset_feature(feature);
+        }
+
+        self.notify(AccessibilityEvent::FeatureToggled { feature, enabled });
+    }
+
+    /// 機能の有効・無効を反転
+    pub fn toggle(&mut self, feature: AccessibilityFeature) -> bool {
+        let enabled = !self.is_enabled(feature);
+        self.set_enabled(feature, enabled);
+        enabled
+    }
+
+    fn reset_feature(&mut self, feature: AccessibilityFeature) {
+        match feature {
+            AccessibilityFeature::StickyKeys => {
+                let cleared: Vec<KeyModifier> = self.sticky.keys().copied().collect();
+                self.sticky.clear();
+                self.held_modifiers.clear();
+                self.latch_consumers.clear();
+                for modifier in cleared {
+                    self.notify(AccessibilityEvent::ModifierCleared(modifier));
+                }
+            }
+            AccessibilityFeature::SlowKeys => {
+                self.slow_pending.clear();
+            }
+            AccessibilityFeature::BounceKeys => {
+                self.last_release.clear();
+            }
+            AccessibilityFeature::MouseKeys => {
+                // 押下中のキーの解放は引き続き消費する
+                self.mouse_held.clear();
+                self.mouse_last_tick = None;
+                self.mouse_elapsed_ms = 0;
+            }
+        }
+    }
+
+    /// 固定キーのモディファイア状態
+    pub fn sticky_state(&self, modifier: KeyModifier) -> Option<StickyState> {
+        self.sticky.get(&modifier).copied()
+    }
+
+    /// 固定キーで有効になっているモディファイア
+    pub fn sticky_modifiers(&self) -> HashSet<KeyModifier> {
+        self.sticky.keys().copied().collect()
+    }
+
+    /// マウスキーで操作するボタン
+    pub fn mouse_keys_button(&self) -> MouseButton {
+        self.mouse_button
+    }
+
+    /// スローキーやマウスキーの時間経過処理が必要か
+    pub fn has_pending(&self) -> bool {
+        !self.slow_pending.is_empty() || !self.mouse_held.is_empty()
+    }
+
+    /// 状態変化リスナーを追加
+    pub fn add_listener<F>(&mut self, listener: F)
+    where
+        F: Fn(&AccessibilityEvent) + Send + Sync + 'static,
+    {
+        self.listeners.push(Box::new(listener));
+    }
+
+    fn notify(&self, event: AccessibilityEvent) {
+        for listener in &self.listeners {
+            listener(&event);
+        }
+    }
+
+    /// 入力イベントをフィルターに通す
+    ///
+    /// `pointer` はマウスキーで生成するイベントの基準となる現在のポインター位置です。
+    pub fn filter(&mut self, event: InputEvent, pointer: (f64, f64)) -> Vec<InputEvent> {
+        let event = match self.bounce_keys(event) {
+            Some(event) => event,
+            None => return Vec::new(),
+        };
+
+        self.slow_keys(event)
+            .into_iter()
+            .flat_map(|event| self.downstream(event, pointer))
+            .collect()
+    }
+
+    /// 時刻 `now`（ミリ秒）まで時間経過処理を行い、生成されたイベントを返す
+    pub fn tick(&mut self, now: u64, pointer: (f64, f64)) -> Vec<InputEvent> {
+        let mut output = Vec::new();
+
+        // スローキーの受付
+        let delay = self.settings.slow_keys.delay_ms;
+        let mut accepted = Vec::new();
+        for (key_code, pending) in self.slow_pending.iter_mut() {
+            if let Some(last) = pending.last_tick {
+                pending.held_ms += now.saturating_sub(last);
+            }
+            pending.last_tick = Some(now);
+            if pending.held_ms >= delay {
+                accepted.push(*key_code);
+            }
+        }
+        accepted.sort_by_key(|key_code| key_code.0);
+        for key_code in accepted {
+            if let Some(pending) = self.slow_pending.remove(&key_code) {
+                self.notify(AccessibilityEvent::SlowKeyAccepted(key_code));
+                output.extend(self.downstream(pending.event, pointer));
+            }
+        }
+
+        // マウスキーによる移動
+        if let Some(motion) = self.mouse_keys_motion(now, pointer) {
+            output.push(motion);
+        }
+
+        output
+    }
+
+    fn downstream(&mut self, event: InputEvent, pointer: (f64, f64)) -> Vec<InputEvent> {
+        self.mouse_keys(event, pointer)
+            .into_iter()
+            .map(|event| self.sticky_keys(event))
+            .collect()
+    }
+
+    /// バウンスキー
+    fn bounce_keys(&mut self, event: InputEvent) -> Option<InputEvent> {
+        match &event.event_type {
+            InputEventType::KeyPress { key_code, timestamp, repeat: false, .. } => {
+                if !self.settings.bounce_keys.enabled {
+                    return Some(event);
+                }
+                if let Some(released) = self.last_release.get(key_code) {
+                    if timestamp.saturating_sub(*released) < self.settings.bounce_keys.delay_ms {
+                        self.bounced.insert(*key_code);
+                        self.notify(AccessibilityEvent::KeyBounced(*key_code));
+                        return None;
+                    }
+                }
+                Some(event)
+            }
+            InputEventType::KeyPress { key_code, repeat: true, .. } => {
+                if self.bounced.contains(key_code) {
+                    None
+                } else {
+                    Some(event)
+                }
+            }
+            InputEventType::KeyRelease { key_code, timestamp, .. } => {
+                if self.bounced.remove(key_code) {
+                    return None;
+                }
+                if self.settings.bounce_keys.enabled {
+                    self.last_release.insert(*key_code, *timestamp);
+                }
+                Some(event)
+            }
+            _ => Some(event),
+        }
+    }
+
+    /// スローキー
+    fn slow_keys(&mut self, event: InputEvent) -> Vec<InputEvent> {
+        match &event.event_type {
+            InputEventType::KeyPress { key_code, timestamp, repeat, .. } => {
+                if self.slow_pending.contains_key(key_code) {
+                    // 受付前のリピートは捨てる
+                    return Vec::new();
+                }
+                if !self.settings.slow_keys.enabled || *repeat {
+                    return vec![event];
+                }
+
+                let key_code = *key_code;
+                let pressed_at = *timestamp;
+                self.slow_pending.insert(key_code, PendingSlowKey {
+                    event,
+                    pressed_at,
+                    held_ms: 0,
+                    last_tick: None,
+                });
+                self.notify(AccessibilityEvent::SlowKeyPending(key_code));
+                Vec::new()
+            }
+            InputEventType::KeyRelease { key_code, timestamp, .. } => {
+                let pending = match self.slow_pending.remove(key_code) {
+                    Some(pending) => pending,
+                    None => return vec![event],
+                };
+
+                let held = pending.held_ms.max(timestamp.saturating_sub(pending.pressed_at));
+                if held >= self.settings.slow_keys.delay_ms {
+                    self.notify(AccessibilityEvent::SlowKeyAccepted(*key_code));
+                    vec![pending.event, event]
+                } else {
+                    self.notify(AccessibilityEvent::SlowKeyRejected(*key_code));
+                    Vec::new()
+                }
+            }
+            _ => vec![event],
+        }
+    }
+
+    /// マウスキー
+    fn mouse_keys(&mut self, event: InputEvent, pointer: (f64, f64)) -> Vec<InputEvent> {
+        let (key_code, key_sym, modifiers, timestamp, pressed, repeat) = match &event.event_type {
+            InputEventType::KeyPress { key_code, key_sym, modifiers, timestamp, repeat } => {
+                (*key_code, key_sym, modifiers, *timestamp, true, *repeat)
+            }
+            InputEventType::KeyRelease { key_code, key_sym, modifiers, timestamp } => {
+                (*key_code, key_sym, modifiers, *timestamp, false, false)
+            }
+            _ => return vec![event],
+        };
+
+        if !pressed {
+            self.mouse_held.remove(&key_code);
+            if self.mouse_held.is_empty() {
+                self.mouse_last_tick = None;
+                self.mouse_elapsed_ms = 0;
+            }
+            return if self.mouse_consumed.remove(&key_code) {
+                Vec::new()
+            } else {
+                vec![event]
+            };
+        }
+
+        if !self.settings.mouse_keys.enabled {
+            return vec![event];
+        }
+        let action = match mouse_key_action(key_sym) {
+            Some(action) => action,
+            None => return vec![event],
+        };
+
+        self.mouse_consumed.insert(key_code);
+        if repeat {
+            return Vec::new();
+        }
+
+        let modifiers = modifiers.clone();
+        let (x, y) = pointer;
+        let button = self.mouse_button;
+        let press = |modifiers: &HashSet<KeyModifier>| {
+            InputEvent::new(InputEventType::MousePress {
+                button,
+                x,
+                y,
+                modifiers: modifiers.clone(),
+                timestamp,
+            })
+            .with_source(MOUSE_KEYS_SOURCE.to_string())
+        };
+        let release = |modifiers: &HashSet<KeyModifier>| {
+            InputEvent::new(InputEventType::MouseRelease {
+                button,
+                x,
+                y,
+                modifiers: modifiers.clone(),
+                timestamp,
+            })
+            .with_source(MOUSE_KEYS_SOURCE.to_string())
+        };
+
+        match action {
+            MouseKeyAction::Move(dx, dy) => {
+                self.mouse_held.insert(key_code, (dx, dy));
+                let step = self.settings.mouse_keys.initial_step;
+                let (dx, dy) = (dx * step, dy * step);
+                vec![InputEvent::new(InputEventType::MouseMove {
+                    x: x + dx,
+                    y: y + dy,
+                    dx,
+                    dy,
+                    modifiers,
+                    timestamp,
+                })
+                .with_source(MOUSE_KEYS_SOURCE.to_string())]
+            }
+            MouseKeyAction::Click => vec![press(&modifiers), release(&modifiers)],
+            MouseKeyAction::DoubleClick => vec![
+                press(&modifiers),
+                release(&modifiers),
+                press(&modifiers),
+                release(&modifiers),
+            ],
+            MouseKeyAction::Press => {
+                if self.mouse_dragging {
+                    return Vec::new();
+                }
+                self.mouse_dragging = true;
+                vec![press(&modifiers)]
+            }
+            MouseKeyAction::Release => {
+                if !self.mouse_dragging {
+                    return Vec::new();
+                }
+                self.mouse_dragging = false;
+                vec![release(&modifiers)]
+            }
+            MouseKeyAction::SelectButton(button) => {
+                self.mouse_button = button;
+                self.notify(AccessibilityEvent::MouseKeysButtonSelected(button));
+                Vec::new()
+            }
+        }
+    }
+
+    /// 押下中のマウスキーによる移動イベントを生成
+    fn mouse_keys_motion(&mut self, now: u64, pointer: (f64, f64)) -> Option<InputEvent> {
+        if self.mouse_held.is_empty() {
+            return None;
+        }
+
+        // 最初の tick は基準時刻の記録のみ
+        let dt = match self.mouse_last_tick {
+            Some(last) => now.saturating_sub(last),
+            None => {
+                self.mouse_last_tick = Some(now);
+                return None;
+            }
+        };
+        self.mouse_last_tick = Some(now);
+        if dt == 0 {
+            return None;
+        }
+        self.mouse_elapsed_ms += dt;
+
+        let (mut dir_x, mut dir_y) = self
+            .mouse_held
+            .values()
+            .fold((0.0, 0.0), |(ax, ay), (x, y)| (ax + x, ay + y));
+        let length = dir_x.hypot(dir_y);
+        if length == 0.0 {
+            return None;
+        }
+        dir_x /= length;
+        dir_y /= length;
+
+        let settings = &self.settings.mouse_keys;
+        let ratio = if settings.acceleration_time_ms == 0 {
+            1.0
+        } else {
+            (self.mouse_elapsed_ms as f64 / settings.acceleration_time_ms as f64).min(1.0)
+        };
+        // 押し始めは遅く、なめらかに最高速度まで加速
+        let speed = settings.initial_speed + (settings.max_speed - settings.initial_speed) * ratio * ratio;
+        let distance = speed * dt as f64 / 1000.0;
+        let (dx, dy) = (dir_x * distance, dir_y * distance);
+
+        Some(
+            InputEvent::new(InputEventType::MouseMove {
+                x: pointer.0 + dx,
+                y: pointer.1 + dy,
+                dx,
+                dy,
+                modifiers: self.sticky_modifiers(),
+                timestamp: now,
+            })
+            .with_source(MOUSE_KEYS_SOURCE.to_string()),
+        )
+    }
+
+    /// 固定キー
+    fn sticky_keys(&mut self, mut event: InputEvent) -> InputEvent {
+        if !self.settings.sticky_keys.enabled {
+            return event;
+        }
+
+        let sticky: HashSet<KeyModifier> = self.sticky.keys().copied().collect();
+
+        match &mut event.event_type {
+            InputEventType::KeyPress { key_code, key_sym, modifiers, repeat, .. } => {
+                if let Some(modifier) = modifier_for_keysym(key_sym) {
+                    if !*repeat {
+                        self.held_modifiers.insert(modifier, false);
+                    }
+                } else {
+                    if !self.held_modifiers.is_empty() {
+                        for chorded in self.held_modifiers.values_mut() {
+                            *chorded = true;
+                        }
+                        if self.settings.sticky_keys.disable_on_chord {
+                            self.set_enabled(AccessibilityFeature::StickyKeys, false);
+                            return event;
+                        }
+                    }
+                    if self.sticky.values().any(|state| *state == StickyState::Latched) {
+                        self.latch_consumers.insert(*key_code);
+                    }
+                }
+                modifiers.extend(sticky);
+            }
+            InputEventType::KeyRelease { key_code, key_sym, modifiers, .. } => {
+                modifiers.extend(sticky);
+                if let Some(modifier) = modifier_for_keysym(key_sym) {
+                    // 単独で押して離したモディファイアだけを固定
+                    if self.held_modifiers.remove(&modifier) == Some(false) {
+                        self.advance_sticky(modifier);
+                    }
+                } else if self.latch_consumers.remove(key_code) {
+                    self.clear_latched();
+                }
+            }
+            InputEventType::MousePress { modifiers, .. } => {
+                for chorded in self.held_modifiers.values_mut() {
+                    *chorded = true;
+                }
+                modifiers.extend(sticky);
+            }
+            InputEventType::MouseRelease { modifiers, .. } => {
+                modifiers.extend(sticky);
+                self.clear_latched();
+            }
+            InputEventType::MouseScroll { modifiers, .. } => {
+                modifiers.extend(sticky);
+            }
+            _ => {}
+        }
+
+        event
+    }
+
+    fn advance_sticky(&mut self, modifier: KeyModifier) {
+        let next = match self.sticky.get(&modifier) {
+            None => Some(StickyState::Latched),
+            Some(StickyState::Latched) if self.settings.sticky_keys.lock_on_double_press => {
+                Some(StickyState::Locked)
+            }
+            _ => None,
+        };
+
+        match next {
+            Some(state) => {
+                self.sticky.insert(modifier, state);
+                self.notify(match state {
+                    StickyState::Latched => AccessibilityEvent::ModifierLatched(modifier),
+                    StickyState::Locked => AccessibilityEvent::ModifierLocked(modifier),
+                });
+            }
+            None => {
+                self.sticky.remove(&modifier);
+                self.notify(AccessibilityEvent::ModifierCleared(modifier));
+            }
+        }
+    }
+
+    fn clear_latched(&mut self) {
+        let latched: Vec<KeyModifier> = self
+            .sticky
+            .iter()
+            .filter(|(_, state)| **state == StickyState::Latched)
+            .map(|(modifier, _)| *modifier)
+            .collect();
+
+        for modifier in latched {
+            self.sticky.remove(&modifier);
+            self.notify(AccessibilityEvent::ModifierCleared(modifier));
+        }
+    }
+}
+
+impl Default for AccessibilityFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(name: &str, code: u32, pressed: bool, timestamp: u64) -> InputEvent {
+        let key_code = KeyCode(code);
+        let key_sym = KeySym(name.to_string());
+        let modifiers = HashSet::new();
+        InputEvent::new(if pressed {
+            InputEventType::KeyPress { key_code, key_sym, modifiers, timestamp, repeat: false }
+        } else {
+            InputEventType::KeyRelease { key_code, key_sym, modifiers, timestamp }
+        })
+    }
+
+    fn modifiers_of(event: &InputEvent) -> HashSet<KeyModifier> {
+        match &event.event_type {
+            InputEventType::KeyPress { modifiers, .. }
+            | InputEventType::KeyRelease { modifiers, .. } => modifiers.clone(),
+            _ => HashSet::new(),
+        }
+    }
+
+    #[test]
+    fn test_sticky_keys_latch_and_lock() {
+        let mut filter = AccessibilityFilter::new();
+        filter.set_enabled(AccessibilityFeature::StickyKeys, true);
+
+        // Ctrl を単独で押して離すとラッチ
+        filter.filter(key("Control_L", 37, true, 0), (0.0, 0.0));
+        filter.filter(key("Control_L", 37, false, 10), (0.0, 0.0));
+        assert_eq!(filter.sticky_state(KeyModifier::Ctrl), Some(StickyState::Latched));
+
+        // 次のキーにだけ適用される
+        let out = filter.filter(key("c", 54, true, 20), (0.0, 0.0));
+        assert!(modifiers_of(&out[0]).contains(&KeyModifier::Ctrl));
+        filter.filter(key("c", 54, false, 30), (0.0, 0.0));
+        assert_eq!(filter.sticky_state(KeyModifier::Ctrl), None);
+        let out = filter.filter(key("c", 54, true, 40), (0.0, 0.0));
+        assert!(modifiers_of(&out[0]).is_empty());
+        filter.filter(key("c", 54, false, 50), (0.0, 0.0));
+
+        // 2回押すとロック、3回目で解除
+        for (i, state) in [Some(StickyState::Latched), Some(StickyState::Locked), None].iter().enumerate() {
+            let t = 100 + i as u64 * 20;
+            filter.filter(key("Shift_L", 50, true, t), (0.0, 0.0));
+            filter.filter(key("Shift_L", 50, false, t + 10), (0.0, 0.0));
+            assert_eq!(filter.sticky_state(KeyModifier::Shift), *state);
+        }
+
+        // 他のキーと同時押ししたモディファイアは固定しない
+        filter.filter(key("Alt_L", 64, true, 200), (0.0, 0.0));
+        filter.filter(key("Tab", 23, true, 210), (0.0, 0.0));
+        filter.filter(key("Tab", 23, false, 220), (0.0, 0.0));
+        filter.filter(key("Alt_L", 64, false, 230), (0.0, 0.0));
+        assert_eq!(filter.sticky_state(KeyModifier::Alt), None);
+    }
+
+    #[test]
+    fn test_slow_keys() {
+        let mut filter = AccessibilityFilter::new();
+        filter.set_enabled(AccessibilityFeature::SlowKeys, true);
+
+        // 短い押下は無視
+        assert!(filter.filter(key("a", 38, true, 0), (0.0, 0.0)).is_empty());
+        assert!(filter.filter(key("a", 38, false, 100), (0.0, 0.0)).is_empty());
+
+        // 十分に押し続けると受け付け
+        assert!(filter.filter(key("b", 56, true, 1000), (0.0, 0.0)).is_empty());
+        assert!(filter.tick(5000, (0.0, 0.0)).is_empty());
+        let accepted = filter.tick(5350, (0.0, 0.0));
+        assert_eq!(accepted.len(), 1);
+        assert!(matches!(accepted[0].event_type, InputEventType::KeyPress { .. }));
+        assert_eq!(filter.filter(key("b", 56, false, 1400), (0.0, 0.0)).len(), 1);
+
+        // tick がなくても解放時刻で判定
+        filter.filter(key("c", 54, true, 2000), (0.0, 0.0));
+        assert_eq!(filter.filter(key("c", 54, false, 2400), (0.0, 0.0)).len(), 2);
+    }
+
+    #[test]
+    fn test_bounce_keys() {
+        let mut filter = AccessibilityFilter::new();
+        filter.set_enabled(AccessibilityFeature::BounceKeys, true);
+
+        let bounced = std::sync::Arc::new(std::sync::Mutex::new(0));
+        let counter = bounced.clone();
+        filter.add_listener(move |event| {
+            if let AccessibilityEvent::KeyBounced(_) = event {
+                *counter.lock().unwrap() += 1;
+            }
+        });
+
+        assert_eq!(filter.filter(key("a", 38, true, 0), (0.0, 0.0)).len(), 1);
+        assert_eq!(filter.filter(key("a", 38, false, 50), (0.0, 0.0)).len(), 1);
+        // チャタリングによる再入力は押下・解放ともに捨てる
+        assert!(filter.filter(key("a", 38, true, 100), (0.0, 0.0)).is_empty());
+        assert!(filter.filter(key("a", 38, false, 120), (0.0, 0.0)).is_empty());
+        // 別のキーや十分時間が経った入力は通す
+        assert_eq!(filter.filter(key("b", 56, true, 130), (0.0, 0.0)).len(), 1);
+        assert_eq!(filter.filter(key("a", 38, true, 500), (0.0, 0.0)).len(), 1);
+
+        assert_eq!(*bounced.lock().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_mouse_keys() {
+        let mut filter = AccessibilityFilter::new();
+        filter.set_enabled(AccessibilityFeature::MouseKeys, true);
+
+        // 押した瞬間に1ステップ移動
+        let out = filter.filter(key("KP_Right", 85, true, 0), (10.0, 10.0));
+        assert!(matches!(
+            out[0].event_type,
+            InputEventType::MouseMove { x, dx, .. } if x == 11.0 && dx == 1.0
+        ));
+        assert_eq!(out[0].source_device.as_deref(), Some(MOUSE_KEYS_SOURCE));
+
+        // 押し続けると加速
+        assert!(filter.tick(0, (11.0, 10.0)).is_empty());
+        let mut distances = Vec::new();
+        for i in 1..=10 {
+            let out = filter.tick(i * 100, (11.0, 10.0));
+            if let InputEventType::MouseMove { dx, dy, .. } = out[0].event_type {
+                assert_eq!(dy, 0.0);
+                distances.push(dx);
+            }
+        }
+        assert!(distances.windows(2).all(|pair| pair[1] > pair[0]));
+        assert!((distances[9] - 80.0).abs() < 1e-9);
+
+        // 解放イベントも消費
+        assert!(filter.filter(key("KP_Right", 85, false, 1000), (0.0, 0.0)).is_empty());
+        assert!(filter.tick(1100, (0.0, 0.0)).is_empty());
+
+        // ボタン選択とクリック
+        assert!(filter.filter(key("KP_Subtract", 82, true, 1200), (5.0, 5.0)).is_empty());
+        let out = filter.filter(key("KP_Begin", 84, true, 1300), (5.0, 5.0));
+        assert!(matches!(
+            out[0].event_type,
+            InputEventType::MousePress { button: MouseButton::Right, .. }
+        ));
+        assert!(matches!(
+            out[1].event_type,
+            InputEventType::MouseRelease { button: MouseButton::Right, .. }
+        ));
+
+        // テンキー以外は素通し
+        assert_eq!(filter.filter(key("a", 38, true, 1400), (0.0, 0.0)).len(), 1);
+
+        // 無効にするとテンキーも素通し
+        filter.set_enabled(AccessibilityFeature::MouseKeys, false);
+        assert_eq!(filter.filter(key("KP_Up", 80, true, 1500), (0.0, 0.0)).len(), 1);
+    }
+}
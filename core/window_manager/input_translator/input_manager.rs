@@ -4,11 +4,19 @@
 use std::collections::{HashMap, HashSet, VecDeque};
 use std::time::{Duration, Instant};
 use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use serde::{Serialize, Deserialize};
 
 use crate::core::window_manager::scene_graph::NodeId;
+use super::xkb_keymap::{KeyboardLayoutManager, KeyTranslation, EVDEV_OFFSET};
+use super::text_input::{ImeKeyEvent, TextInputManager};
+use super::input_recorder::{InputRecorder, InputTrace};
+use super::pointer_profile::PointerPipeline;
+use super::accessibility::AccessibilityFilter;
 
 /// キーボードのモディファイア
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum KeyModifier {
     Shift,
     Ctrl,
@@ -16,20 +24,22 @@ pub enum KeyModifier {
     Super,
     Hyper,
     Meta,
+    /// AltGr（ISO_Level3_Shift）による第3レベルのシフト
+    LevelThree,
     CapsLock,
     NumLock,
 }
 
 /// キーボードのキーコード
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct KeyCode(pub u32);
 
 /// キーボードのキーシンボル
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct KeySym(pub String);
 
 /// マウスボタン
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum MouseButton {
     Left,
     Right,
@@ -40,7 +50,7 @@ pub enum MouseButton {
 }
 
 /// 入力イベントの種類
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum InputEventType {
     KeyPress {
         key_code: KeyCode,
@@ -140,7 +150,7 @@ pub enum InputEventType {
 }
 
 /// 入力イベント
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InputEvent {
     pub target: Option<NodeId>,
     pub event_type: InputEventType,
@@ -266,6 +276,67 @@ impl ShortcutDefinition {
     }
 }
 
+/// 入力処理の時刻源
+///
+/// キーリピート・スローキー・慣性スクロールなどの時間経過処理と、
+/// 内部で生成されるイベントのタイムスタンプはこの時刻を基準にします。
+pub trait InputClock: Send + Sync {
+    /// 基準時点からの経過時間
+    fn now(&self) -> Duration;
+}
+
+/// 実時間の時刻源（生成時点からの経過時間）
+#[derive(Debug, Clone, Copy)]
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl SystemClock {
+    pub fn new() -> Self {
+        Self { start: Instant::now() }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InputClock for SystemClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+/// 手動で進める仮想時刻源（トレース再生やテストで使用）
+#[derive(Debug, Default)]
+pub struct ManualClock {
+    now_ms: AtomicU64,
+}
+
+impl ManualClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 時刻を設定（逆行はしない）
+    pub fn set(&self, now: Duration) {
+        self.now_ms.fetch_max(now.as_millis() as u64, Ordering::SeqCst);
+    }
+
+    /// 時刻を進める
+    pub fn advance(&self, delta: Duration) {
+        self.now_ms.fetch_add(delta.as_millis() as u64, Ordering::SeqCst);
+    }
+}
+
+impl InputClock for ManualClock {
+    fn now(&self) -> Duration {
+        Duration::from_millis(self.now_ms.load(Ordering::SeqCst))
+    }
+}
+
 /// ショートカットアクション
 pub type ShortcutAction = Box<dyn Fn() -> bool + Send + Sync>;
 
@@ -284,10 +355,12 @@ pub struct InputManager {
     // キーボード状態
     pressed_keys: HashSet<KeyCode>,
     pressed_modifiers: HashSet<KeyModifier>,
-    repeat_info: HashMap<KeyCode, (Instant, Duration)>,
+    repeat_info: HashMap<KeyCode, (Duration, Duration)>,
     
     // マウス状態
     mouse_position: (f64, f64),
+    // マウスイベントでポインター位置が確定しているか
+    mouse_position_known: bool,
     pressed_buttons: HashSet<MouseButton>,
     dragging: Option<(MouseButton, NodeId, (f64, f64))>,
     
@@ -301,14 +374,29 @@ pub struct InputManager {
     // タッチ状態
     active_touches: HashMap<u64, (f64, f64)>,
     
+    // キーボードレイアウト（XKBキーマップ）
+    keyboard_layouts: KeyboardLayoutManager,
+    
+    // テキスト入力（IME）
+    text_input: TextInputManager,
+    
+    // 入力記録
+    recorder: Option<InputRecorder>,
+    
+    // ポインター加速・スクロール
+    pointer: PointerPipeline,
+    
+    // アクセシビリティ（固定キー・スローキー・バウンスキー・マウスキー）
+    accessibility: AccessibilityFilter,
+    
     // 入力設定
     key_repeat_delay: Duration,
     key_repeat_interval: Duration,
     double_click_timeout: Duration,
     drag_threshold: f64,
     
-    // タイムスタンプ生成用
-    start_time: Instant,
+    // 時刻源（タイムスタンプ生成と時間経過処理に使用）
+    clock: Arc<dyn InputClock>,
 }
 
 impl InputManager {
@@ -321,17 +409,23 @@ impl InputManager {
             pressed_modifiers: HashSet::new(),
             repeat_info: HashMap::new(),
             mouse_position: (0.0, 0.0),
+            mouse_position_known: false,
             pressed_buttons: HashSet::new(),
             dragging: None,
             shortcuts: HashMap::new(),
             keyboard_focus: None,
             mouse_focus: None,
             active_touches: HashMap::new(),
+            keyboard_layouts: KeyboardLayoutManager::new(),
+            text_input: TextInputManager::new(),
+            recorder: None,
+            pointer: PointerPipeline::new(),
+            accessibility: AccessibilityFilter::new(),
             key_repeat_delay: Duration::from_millis(500),
             key_repeat_interval: Duration::from_millis(50),
             double_click_timeout: Duration::from_millis(500),
             drag_threshold: 5.0,
-            start_time: Instant::now(),
+            clock: Arc::new(SystemClock::new()),
         }
     }
     
@@ -377,24 +471,131 @@ impl InputManager {
     
     /// 入力イベントの追加
     pub fn push_event(&mut self, event: InputEvent) {
+        if let Some(recorder) = &mut self.recorder {
+            recorder.record(&event);
+        }
         self.event_queue.push_back(event);
     }
     
+    /// 時刻源を差し替える
+    ///
+    /// トレースの再生では `ManualClock` を設定して、記録時の時刻で
+    /// キーリピートなどの時間経過処理を再現します。
+    pub fn set_clock(&mut self, clock: Arc<dyn InputClock>) {
+        self.clock = clock;
+    }
+    
+    /// 入力イベントの記録を開始
+    ///
+    /// `push_event` に渡されたイベントが記録されます。フォーカス変更や
+    /// キーリピートのように内部で生成されるイベントは再生時に再生成されるため記録しません。
+    pub fn start_recording(&mut self, description: &str) {
+        self.recorder = Some(InputRecorder::new(description));
+    }
+    
+    /// 記録を終了してトレースを取得
+    pub fn stop_recording(&mut self) -> Option<InputTrace> {
+        self.recorder.take().map(|recorder| recorder.finish())
+    }
+    
+    /// 記録中かどうか
+    pub fn is_recording(&self) -> bool {
+        self.recorder.is_some()
+    }
+    
+    /// evdevスキャンコードからキーイベントを生成して追加
+    ///
+    /// 現在のキーボードレイアウトでキーシンボルとモディファイアを解決します。
+    /// 生成されるイベントの `KeyCode` はXKBキーコード（スキャンコード + 8）です。
+    /// 変換結果（入力文字列を含む）を返します。
+    /// 固定キーでラッチされたモディファイアは `process_events` でイベントに反映されます。
+    pub fn push_scancode(&mut self, scancode: u32, pressed: bool) -> Option<KeyTranslation> {
+        let key_code = KeyCode(scancode + EVDEV_OFFSET);
+        let translation = self.keyboard_layouts.process_key(key_code.0, pressed)?;
+        let modifiers = self.keyboard_layouts.modifiers();
+        let timestamp = self.generate_timestamp();
+        
+        let event_type = if pressed {
+            InputEventType::KeyPress {
+                key_code,
+                key_sym: translation.key_sym.clone(),
+                modifiers,
+                timestamp,
+                repeat: false,
+            }
+        } else {
+            InputEventType::KeyRelease {
+                key_code,
+                key_sym: translation.key_sym.clone(),
+                modifiers,
+                timestamp,
+            }
+        };
+        
+        self.push_event(InputEvent::new(event_type));
+        Some(translation)
+    }
+    
     /// 全ての入力イベントを処理
     pub fn process_events(&mut self) {
-        let now = Instant::now();
+        let now = self.clock.now();
         
         // キーリピートの処理
         self.process_key_repeats(now);
         
-        // イベント処理
-        while let Some(event) = self.event_queue.pop_front() {
+        // スローキーの受付とマウスキーによる移動
+        let timestamp = self.generate_timestamp();
+        let sticky = self.accessibility.sticky_modifiers();
+        for mut event in self.accessibility.tick(timestamp, self.mouse_position) {
+            self.resolve_sticky_keysym(&mut event, &sticky);
             self.process_event(event);
         }
+        
+        // イベント処理（アクセシビリティフィルターを通してからショートカットを照合）
+        while let Some(event) = self.event_queue.pop_front() {
+            let sticky = self.accessibility.sticky_modifiers();
+            for mut filtered in self.accessibility.filter(event, self.mouse_position) {
+                self.resolve_sticky_keysym(&mut filtered, &sticky);
+                self.process_event(filtered);
+            }
+        }
+        
+        // 平滑化・慣性スクロールの処理
+        self.process_scroll_animations();
+    }
+    
+    /// 固定キーのモディファイアが付加されたキーイベントのキーシンボルを解決し直す
+    ///
+    /// キーシンボルは `push_scancode` の時点でXKBにより解決されているため、
+    /// フィルターでラッチ・ロック中のモディファイアが付加された場合は
+    /// 付加後のモディファイアで引き直します（ラッチされたShift + a は A になる）。
+    fn resolve_sticky_keysym(&self, event: &mut InputEvent, sticky: &HashSet<KeyModifier>) {
+        if sticky.is_empty() {
+            return;
+        }
+        
+        match &mut event.event_type {
+            InputEventType::KeyPress { key_code, key_sym, modifiers, .. }
+            | InputEventType::KeyRelease { key_code, key_sym, modifiers, .. } => {
+                if let Some(resolved) = self.keyboard_layouts.lookup_keysym_with(key_code.0, modifiers) {
+                    *key_sym = resolved;
+                }
+            }
+            _ => {}
+        }
     }
     
     /// 単一の入力イベントを処理
     fn process_event(&mut self, mut event: InputEvent) {
+        // ポインター加速・スクロール変換
+        if !self.apply_pointer_profile(&mut event) {
+            return;
+        }
+        
+        if event.is_mouse_event() {
+            self.mouse_position_known = true;
+        }
+        
         // イベントタイプに基づいて状態を更新
         match &event.event_type {
             InputEventType::KeyPress {
@@ -413,7 +614,7 @@ impl InputManager {
                     self.repeat_info.insert(
                         *key_code,
                         (
-                            Instant::now(),
+                            self.clock.now(),
                             self.key_repeat_delay,
                         ),
                     );
@@ -423,6 +624,10 @@ impl InputManager {
                 if self.process_shortcut(key_sym, modifiers) {
                     event.mark_handled();
                     event.stop_propagation();
+                } else if self.process_text_input(key_sym, modifiers, true) {
+                    // IMEが消費したキーはアプリケーションに配送しない
+                    event.mark_handled();
+                    event.stop_propagation();
                 }
                 
                 // ターゲットが未設定の場合はキーボードフォーカスを設定
@@ -432,7 +637,7 @@ impl InputManager {
             }
             InputEventType::KeyRelease {
                 key_code,
-                key_sym: _,
+                key_sym,
                 modifiers,
                 timestamp: _,
             } => {
@@ -443,6 +648,11 @@ impl InputManager {
                 // リピート情報の削除
                 self.repeat_info.remove(key_code);
                 
+                if self.process_text_input(key_sym, modifiers, false) {
+                    event.mark_handled();
+                    event.stop_propagation();
+                }
+                
                 // ターゲットが未設定の場合はキーボードフォーカスを設定
                 if event.target.is_none() {
                     event.target = self.keyboard_focus;
@@ -562,14 +772,14 @@ impl InputManager {
             InputEventType::FocusIn { timestamp: _ } => {
                 // ターゲットをキーボードフォーカスとして設定
                 if let Some(target) = event.target {
-                    self.keyboard_focus = Some(target);
+                    self.apply_keyboard_focus(Some(target));
                 }
             }
             InputEventType::FocusOut { timestamp: _ } => {
                 // ターゲットがキーボードフォーカスと一致する場合はフォーカスを削除
                 if let Some(target) = event.target {
                     if self.keyboard_focus == Some(target) {
-                        self.keyboard_focus = None;
+                        self.apply_keyboard_focus(None);
                     }
                 }
             }
@@ -580,32 +790,98 @@ impl InputManager {
         self.dispatch_event(&mut event);
     }
     
+    /// デバイスのポインタープロファイルをマウスイベントに適用
+    ///
+    /// スクロールが平滑化などで後から出力される場合は `false` を返します。
+    fn apply_pointer_profile(&mut self, event: &mut InputEvent) -> bool {
+        let source = event.source_device.as_deref();
+        
+        match &mut event.event_type {
+            InputEventType::MouseMove { x, y, dx, dy, timestamp, .. } => {
+                if *dx != 0.0 || *dy != 0.0 {
+                    let (ax, ay) = self.pointer.accelerate(source, *dx, *dy, *timestamp);
+                    // 絶対座標は直前の（加速済みの）ポインター位置に加速後の移動量を足して求める
+                    // （デバイスの生座標を補正すると過去の加速分が失われる）
+                    // 位置が未確定の最初の移動ではデバイス座標の移動前の位置を起点にする
+                    let origin = if self.mouse_position_known {
+                        self.mouse_position
+                    } else {
+                        (*x - *dx, *y - *dy)
+                    };
+                    *x = origin.0 + ax;
+                    *y = origin.1 + ay;
+                    *dx = ax;
+                    *dy = ay;
+                }
+                true
+            }
+            InputEventType::MouseScroll { x, y, dx, dy, timestamp, .. } => {
+                match self.pointer.process_scroll(source, *x, *y, *dx, *dy, *timestamp) {
+                    Some((sx, sy)) => {
+                        *dx = sx;
+                        *dy = sy;
+                        true
+                    }
+                    None => false,
+                }
+            }
+            InputEventType::MousePress { .. } => {
+                // クリックで慣性スクロールを止める
+                self.pointer.cancel_scroll_animation(source);
+                true
+            }
+            _ => true,
+        }
+    }
+    
+    /// 平滑化・慣性スクロールで生成されたスクロールイベントを配送
+    fn process_scroll_animations(&mut self) {
+        let now = self.generate_timestamp();
+        
+        for scroll in self.pointer.tick(now) {
+            let mut event = InputEvent::new(InputEventType::MouseScroll {
+                x: scroll.x,
+                y: scroll.y,
+                dx: scroll.dx,
+                dy: scroll.dy,
+                modifiers: self.pressed_modifiers.clone(),
+                timestamp: now,
+            });
+            event.source_device = scroll.source;
+            event.target = self.mouse_focus;
+            
+            self.dispatch_event(&mut event);
+        }
+    }
+    
     /// キーリピートの処理
-    fn process_key_repeats(&mut self, now: Instant) {
+    fn process_key_repeats(&mut self, now: Duration) {
         let mut repeat_events = Vec::new();
         
-        // リピート情報を確認
-        for (&key_code, (start_time, delay)) in &mut self.repeat_info {
-            let elapsed = now.duration_since(*start_time);
-            
-            if elapsed >= *delay {
-                // リピートイベントの作成
-                if let Some(key_sym) = self.key_code_to_sym(&key_code) {
-                    let timestamp = self.generate_timestamp();
-                    let repeat_event = InputEvent::new(InputEventType::KeyPress {
-                        key_code,
-                        key_sym: key_sym.clone(),
-                        modifiers: self.pressed_modifiers.clone(),
-                        timestamp,
-                        repeat: true,
-                    });
-                    
-                    repeat_events.push(repeat_event);
-                    
-                    // 次のリピートのための更新
-                    *start_time = now;
-                    *delay = self.key_repeat_interval;
-                }
+        // リピート対象のキーを確認
+        let due_keys: Vec<KeyCode> = self
+            .repeat_info
+            .iter()
+            .filter(|(_, (start_time, delay))| now.saturating_sub(*start_time) >= *delay)
+            .map(|(key_code, _)| *key_code)
+            .collect();
+        
+        for key_code in due_keys {
+            // リピートイベントの作成
+            if let Some(key_sym) = self.key_code_to_sym(&key_code) {
+                let timestamp = self.generate_timestamp();
+                let repeat_event = InputEvent::new(InputEventType::KeyPress {
+                    key_code,
+                    key_sym,
+                    modifiers: self.pressed_modifiers.clone(),
+                    timestamp,
+                    repeat: true,
+                });
+                
+                repeat_events.push(repeat_event);
+                
+                // 次のリピートのための更新
+                self.repeat_info.insert(key_code, (now, self.key_repeat_interval));
             }
         }
         
@@ -630,6 +906,21 @@ impl InputManager {
         false
     }
     
+    /// キーイベントをテキスト入力（IME）に渡す
+    fn process_text_input(
+        &mut self,
+        key_sym: &KeySym,
+        modifiers: &HashSet<KeyModifier>,
+        pressed: bool,
+    ) -> bool {
+        if !self.text_input.is_active() && !pressed {
+            return false;
+        }
+        
+        let ime_event = ImeKeyEvent::new(key_sym.clone(), modifiers.clone(), pressed);
+        self.text_input.process_key(&ime_event)
+    }
+    
     /// イベントを適切なハンドラに配送
     fn dispatch_event(&self, event: &mut InputEvent) {
         // グローバルハンドラで処理
@@ -673,10 +964,21 @@ impl InputManager {
                 self.event_queue.push_back(focus_in_event);
             }
             
-            self.keyboard_focus = node_id;
+            self.apply_keyboard_focus(node_id);
         }
     }
     
+    /// キーボードフォーカスを更新し、レイアウトとIMEに通知
+    fn apply_keyboard_focus(&mut self, node_id: Option<NodeId>) {
+        self.keyboard_focus = node_id;
+        
+        // ウィンドウごとのキーボードレイアウトを復元
+        self.keyboard_layouts.focus_changed(node_id);
+        
+        // 未確定文字列の確定とIMEの切り替え
+        self.text_input.focus_changed(node_id);
+    }
+    
     /// マウスフォーカスを設定
     pub fn set_mouse_focus(&mut self, node_id: Option<NodeId>) {
         self.mouse_focus = node_id;
@@ -684,14 +986,15 @@ impl InputManager {
     
     /// タイムスタンプを生成
     fn generate_timestamp(&self) -> u64 {
-        self.start_time.elapsed().as_millis() as u64
+        self.clock.now().as_millis() as u64
     }
     
-    /// キーコードからキーシンボルを取得（実際の実装では適切なマッピング）
+    /// キーコードからキーシンボルを取得
     fn key_code_to_sym(&self, key_code: &KeyCode) -> Option<KeySym> {
-        // 実際の実装ではXKBなどを使用してマッピング
-        // ここでは簡略化のため単純な変換
-        Some(KeySym(format!("KEY_{}", key_code.0)))
+        // キーマップに存在しないキーコードは従来通りの名前で扱う
+        self.keyboard_layouts
+            .lookup_keysym(key_code.0)
+            .or_else(|| Some(KeySym(format!("KEY_{}", key_code.0))))
     }
     
     /// 現在押されているキーの取得
@@ -753,6 +1056,57 @@ impl InputManager {
     pub fn set_drag_threshold(&mut self, threshold: f64) {
         self.drag_threshold = threshold;
     }
+    
+    /// キーボードレイアウトマネージャーの取得
+    pub fn keyboard_layouts(&self) -> &KeyboardLayoutManager {
+        &self.keyboard_layouts
+    }
+    
+    /// キーボードレイアウトマネージャーの取得（変更用）
+    pub fn keyboard_layouts_mut(&mut self) -> &mut KeyboardLayoutManager {
+        &mut self.keyboard_layouts
+    }
+    
+    /// テキスト入力マネージャーの取得
+    pub fn text_input(&self) -> &TextInputManager {
+        &self.text_input
+    }
+    
+    /// テキスト入力マネージャーの取得（変更用）
+    pub fn text_input_mut(&mut self) -> &mut TextInputManager {
+        &mut self.text_input
+    }
+    
+    /// ポインタープロファイルの取得
+    pub fn pointer_pipeline(&self) -> &PointerPipeline {
+        &self.pointer
+    }
+    
+    /// ポインタープロファイルの取得（変更用）
+    pub fn pointer_pipeline_mut(&mut self) -> &mut PointerPipeline {
+        &mut self.pointer
+    }
+    
+    /// アクセシビリティフィルターの取得
+    pub fn accessibility(&self) -> &AccessibilityFilter {
+        &self.accessibility
+    }
+    
+    /// アクセシビリティフィルターの取得（変更用）
+    pub fn accessibility_mut(&mut self) -> &mut AccessibilityFilter {
+        &mut self.accessibility
+    }
+    
+    /// 平滑化・慣性スクロール中かどうか
+    pub fn is_scroll_animating(&self) -> bool {
+        self.pointer.is_animating()
+    }
+    
+    /// キーボードレイアウトマネージャーを置き換え
+    pub fn set_keyboard_layouts(&mut self, layouts: KeyboardLayoutManager) {
+        self.keyboard_layouts = layouts;
+        self.keyboard_layouts.focus_changed(self.keyboard_focus);
+    }
 }
 
 /// キー情報ヘルパー - わかりやすいキー名
@@ -871,6 +1225,7 @@ impl KeyInfo {
             "SUPER" | "WIN" | "WINDOWS" | "CMD" | "COMMAND" => Some(KeyModifier::Super),
             "HYPER" => Some(KeyModifier::Hyper),
             "META" => Some(KeyModifier::Meta),
+            "ALTGR" | "LEVEL3" | "LEVELTHREE" => Some(KeyModifier::LevelThree),
             "CAPSLOCK" | "CAPS" => Some(KeyModifier::CapsLock),
             "NUMLOCK" | "NUM" => Some(KeyModifier::NumLock),
             _ => None,
@@ -1005,4 +1360,204 @@ mod tests {
         // アクションが呼ばれたかの確認
         assert!(action_called);
     }
+    
+    #[test]
+    fn test_scancode_translation() {
+        let mut manager = InputManager::new();
+        
+        // evdev: 42 = 左Shift, 30 = A
+        manager.push_scancode(42, true);
+        let translation = manager.push_scancode(30, true).unwrap();
+        assert_eq!(translation.text.as_deref(), Some("A"));
+        
+        manager.process_events();
+        assert!(manager.get_pressed_keys().contains(&KeyCode(38)));
+        assert!(manager.get_pressed_modifiers().contains(&KeyModifier::Shift));
+    }
+    
+    #[test]
+    fn test_ime_follows_keyboard_focus() {
+        use super::super::text_input::{ContentType, RomajiKanaEngine};
+        
+        let mut manager = InputManager::new();
+        let node_id = NodeId(5);
+        
+        manager.text_input_mut().register_engine(Box::new(RomajiKanaEngine::new()));
+        manager.text_input_mut().enable_for_node(node_id, ContentType::Normal);
+        manager.text_input_mut().set_enabled(true);
+        manager.set_keyboard_focus(Some(node_id));
+        assert!(manager.text_input().is_active());
+        
+        // evdev: 37 = K, 30 = A
+        manager.push_scancode(37, true);
+        manager.push_scancode(37, false);
+        manager.push_scancode(30, true);
+        manager.push_scancode(30, false);
+        manager.process_events();
+        
+        assert_eq!(manager.text_input().preedit(node_id).unwrap().text, "か");
+    }
+    
+    #[test]
+    fn test_focus_events_notify_layouts_and_ime() {
+        use super::super::text_input::{ContentType, RomajiKanaEngine};
+        
+        let mut manager = InputManager::new();
+        let (first, second) = (NodeId(1), NodeId(2));
+        manager.set_keyboard_layouts(KeyboardLayoutManager::with_layouts(&["us", "de"]).unwrap());
+        manager.text_input_mut().register_engine(Box::new(RomajiKanaEngine::new()));
+        manager.text_input_mut().enable_for_node(first, ContentType::Normal);
+        manager.text_input_mut().set_enabled(true);
+        
+        let focus_in = |node| InputEvent::new(InputEventType::FocusIn { timestamp: 0 }).with_target(node);
+        
+        // FocusInイベントでも直接設定した場合と同じくIMEが切り替わる
+        manager.push_event(focus_in(first));
+        manager.process_events();
+        assert_eq!(manager.get_keyboard_focus(), Some(first));
+        assert!(manager.text_input().is_active());
+        manager.keyboard_layouts_mut().set_active_layout(1).unwrap();
+        
+        // ウィンドウごとのレイアウトも復元される
+        manager.push_event(focus_in(second));
+        manager.process_events();
+        assert!(!manager.text_input().is_active());
+        assert_eq!(manager.keyboard_layouts().active_index(), 0);
+        
+        manager.push_event(focus_in(first));
+        manager.process_events();
+        assert_eq!(manager.keyboard_layouts().active_index(), 1);
+        
+        manager.push_event(InputEvent::new(InputEventType::FocusOut { timestamp: 0 }).with_target(first));
+        manager.process_events();
+        assert_eq!(manager.get_keyboard_focus(), None);
+        assert!(!manager.text_input().is_active());
+    }
+    
+    #[test]
+    fn test_pointer_profile_per_device() {
+        use super::super::pointer_profile::{AccelProfile, PointerProfile, ScrollSettings};
+        
+        let mut manager = InputManager::new();
+        let profile = PointerProfile {
+            accel: AccelProfile::Flat { factor: 2.0 },
+            scroll: ScrollSettings {
+                natural_scroll: true,
+                ..ScrollSettings::default()
+            },
+            ..PointerProfile::default()
+        };
+        manager.pointer_pipeline_mut().set_device_profile("mouse-0", profile);
+        
+        let scrolls = Arc::new(Mutex::new(Vec::new()));
+        let captured = scrolls.clone();
+        manager.register_global_handler(move |event| {
+            if let InputEventType::MouseScroll { dy, .. } = event.event_type {
+                captured.lock().unwrap().push(dy);
+            }
+            true
+        });
+        
+        // 加速後の移動量で位置が更新される
+        manager.push_event(InputEvent::new(InputEventType::MouseMove {
+            x: 10.0,
+            y: 10.0,
+            dx: 10.0,
+            dy: 10.0,
+            modifiers: HashSet::new(),
+            timestamp: 1000,
+        }).with_source("mouse-0".to_string()));
+        manager.push_event(InputEvent::new(InputEventType::MouseScroll {
+            x: 20.0,
+            y: 20.0,
+            dx: 0.0,
+            dy: 1.0,
+            modifiers: HashSet::new(),
+            timestamp: 1010,
+        }).with_source("mouse-0".to_string()));
+        manager.process_events();
+        
+        assert_eq!(manager.get_mouse_position(), (20.0, 20.0));
+        assert_eq!(*scrolls.lock().unwrap(), vec![-1.0]);
+        
+        // 連続した移動でも加速分が累積される（デバイスの生座標は 20, 30, 40）
+        for (i, raw) in [20.0, 30.0, 40.0].into_iter().enumerate() {
+            manager.push_event(InputEvent::new(InputEventType::MouseMove {
+                x: raw,
+                y: raw,
+                dx: 10.0,
+                dy: 10.0,
+                modifiers: HashSet::new(),
+                timestamp: 1020 + i as u64 * 10,
+            }).with_source("mouse-0".to_string()));
+        }
+        manager.process_events();
+        
+        assert_eq!(manager.get_mouse_position(), (80.0, 80.0));
+    }
+    
+    #[test]
+    fn test_sticky_keys_before_shortcuts() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use super::super::accessibility::AccessibilityFeature;
+        
+        let mut manager = InputManager::new();
+        manager.accessibility_mut().set_enabled(AccessibilityFeature::StickyKeys, true);
+        
+        let called = Arc::new(AtomicBool::new(false));
+        let flag = called.clone();
+        let mut modifiers = HashSet::new();
+        modifiers.insert(KeyModifier::Ctrl);
+        manager.register_shortcut(
+            ShortcutDefinition::new(KeySym("c".to_string()), modifiers, "コピー".to_string()),
+            move || {
+                flag.store(true, Ordering::SeqCst);
+                true
+            },
+        );
+        
+        // evdev: 29 = 左Ctrl, 46 = C（Ctrlを離してからCを押す）
+        manager.push_scancode(29, true);
+        manager.push_scancode(29, false);
+        manager.push_scancode(46, true);
+        manager.process_events();
+        
+        assert!(called.load(Ordering::SeqCst));
+    }
+    
+    #[test]
+    fn test_sticky_shift_resolves_keysym() {
+        use super::super::accessibility::AccessibilityFeature;
+        
+        let mut manager = InputManager::new();
+        manager.accessibility_mut().set_enabled(AccessibilityFeature::StickyKeys, true);
+        
+        let pressed = Arc::new(Mutex::new(Vec::new()));
+        let captured = pressed.clone();
+        manager.register_global_handler(move |event| {
+            if let InputEventType::KeyPress { key_sym, modifiers, .. } = &event.event_type {
+                captured.lock().unwrap().push((key_sym.0.clone(), modifiers.contains(&KeyModifier::Shift)));
+            }
+            true
+        });
+        
+        // evdev: 42 = 左Shift, 30 = A（Shiftを離してからAを押す）
+        manager.push_scancode(42, true);
+        manager.push_scancode(42, false);
+        manager.push_scancode(30, true);
+        manager.push_scancode(30, false);
+        // ラッチは1打鍵で解除される
+        manager.push_scancode(30, true);
+        manager.process_events();
+        
+        let pressed = pressed.lock().unwrap();
+        assert_eq!(
+            *pressed,
+            vec![
+                ("Shift_L".to_string(), true),
+                ("A".to_string(), true),
+                ("a".to_string(), false),
+            ]
+        );
+    }
 } 
\ No newline at end of file
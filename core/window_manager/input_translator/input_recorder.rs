@@ -0,0 +1,646 @@
+// LumosDesktop 入力記録・再生
+// InputEvent ストリームのタイムスタンプ付き記録と決定的な再生を担当
+
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use super::input_manager::{InputEvent, InputManager, ManualClock};
+
+/// トレースファイル形式のバージョン
+pub const TRACE_FORMAT_VERSION: u32 = 1;
+
+/// 記録・再生のエラー
+#[derive(Debug, Clone, PartialEq)]
+pub enum TraceError {
+    /// ファイル入出力エラー
+    Io(String),
+    /// トレースの形式エラー（行番号は1始まり）
+    Format { line: usize, message: String },
+    /// 未対応のトレースバージョン
+    UnsupportedVersion(u32),
+    /// 再生中のアサーション失敗
+    AssertionFailed {
+        index: usize,
+        offset_ms: u64,
+        message: String,
+    },
+}
+
+impl fmt::Display for TraceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TraceError::Io(msg) => write!(f, "トレース入出力エラー: {}", msg),
+            TraceError::Format { line, message } => {
+                write!(f, "トレース形式エラー ({}行目): {}", line, message)
+            }
+            TraceError::UnsupportedVersion(version) => {
+                write!(f, "未対応のトレースバージョン: {}", version)
+            }
+            TraceError::AssertionFailed { index, offset_ms, message } => write!(
+                f,
+                "再生アサーション失敗 (イベント#{}, {}ms): {}",
+                index, offset_ms, message
+            ),
+        }
+    }
+}
+
+impl Error for TraceError {}
+
+impl From<std::io::Error> for TraceError {
+    fn from(error: std::io::Error) -> Self {
+        TraceError::Io(error.to_string())
+    }
+}
+
+/// トレースのヘッダー（ファイルの1行目）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TraceHeader {
+    pub version: u32,
+    pub description: String,
+    /// 記録開始時刻（UNIXエポックからのミリ秒）
+    pub recorded_at_ms: u64,
+}
+
+/// 記録された1イベント
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedEvent {
+    /// 記録開始からの経過時間
+    pub offset_ms: u64,
+    pub event: InputEvent,
+}
+
+/// 記録された入力トレース
+///
+/// ファイル形式はJSON Linesで、1行目がヘッダー、以降の各行が1イベントです。
+#[derive(Debug, Clone)]
+pub struct InputTrace {
+    pub header: TraceHeader,
+    pub events: Vec<RecordedEvent>,
+}
+
+impl InputTrace {
+    pub fn new(description: &str) -> Self {
+        let recorded_at_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+
+        Self {
+            header: TraceHeader {
+                version: TRACE_FORMAT_VERSION,
+                description: description.to_string(),
+                recorded_at_ms,
+            },
+            events: Vec::new(),
+        }
+    }
+
+    /// 指定オフセットにイベントを追加（テスト用トレースの組み立てに使用）
+    pub fn push(&mut self, offset_ms: u64, event: InputEvent) {
+        self.events.push(RecordedEvent { offset_ms, event });
+    }
+
+    /// トレース全体の長さ
+    pub fn duration(&self) -> Duration {
+        Duration::from_millis(self.events.last().map(|e| e.offset_ms).unwrap_or(0))
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// ライターへ書き出し
+    pub fn write_to<W: Write>(&self, writer: W) -> Result<(), TraceError> {
+        let mut writer = BufWriter::new(writer);
+        let to_format_error = |e: serde_json::Error| TraceError::Format {
+            line: 0,
+            message: e.to_string(),
+        };
+
+        serde_json::to_writer(&mut writer, &self.header).map_err(to_format_error)?;
+        writer.write_all(b"\n")?;
+        for event in &self.events {
+            serde_json::to_writer(&mut writer, event).map_err(to_format_error)?;
+            writer.write_all(b"\n")?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// リーダーから読み込み
+    pub fn read_from<R: Read>(reader: R) -> Result<Self, TraceError> {
+        let reader = BufReader::new(reader);
+        let mut header: Option<TraceHeader> = None;
+        let mut events = Vec::new();
+
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line_number = index + 1;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let to_format_error = |e: serde_json::Error| TraceError::Format {
+                line: line_number,
+                message: e.to_string(),
+            };
+
+            match header {
+                None => {
+                    let parsed: TraceHeader = serde_json::from_str(&line).map_err(to_format_error)?;
+                    if parsed.version > TRACE_FORMAT_VERSION {
+                        return Err(TraceError::UnsupportedVersion(parsed.version));
+                    }
+                    header = Some(parsed);
+                }
+                Some(_) => {
+                    let event: RecordedEvent = serde_json::from_str(&line).map_err(to_format_error)?;
+                    if let Some(previous) = events.last().map(|e: &RecordedEvent| e.offset_ms) {
+                        if event.offset_ms < previous {
+                            return Err(TraceError::Format {
+                                line: line_number,
+                                message: "イベントのオフセットが逆行しています".to_string(),
+                            });
+                        }
+                    }
+                    events.push(event);
+                }
+            }
+        }
+
+        let header = header.ok_or(TraceError::Format {
+            line: 1,
+            message: "ヘッダーがありません".to_string(),
+        })?;
+
+        Ok(Self { header, events })
+    }
+
+    /// ファイルへ保存
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), TraceError> {
+        let file = File::create(path.as_ref())?;
+        self.write_to(file)
+    }
+
+    /// ファイルから読み込み
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, TraceError> {
+        let file = File::open(path.as_ref())?;
+        Self::read_from(file)
+    }
+}
+
+/// 入力レコーダー - `InputManager::push_event` に渡されたイベントを記録
+pub struct InputRecorder {
+    trace: InputTrace,
+    start: Instant,
+    max_events: Option<usize>,
+}
+
+impl InputRecorder {
+    pub fn new(description: &str) -> Self {
+        Self {
+            trace: InputTrace::new(description),
+            start: Instant::now(),
+            max_events: None,
+        }
+    }
+
+    /// 記録するイベント数の上限を設定（超えた分は破棄）
+    pub fn with_max_events(mut self, max_events: usize) -> Self {
+        self.max_events = Some(max_events);
+        self
+    }
+
+    /// イベントを現在時刻で記録
+    pub fn record(&mut self, event: &InputEvent) {
+        let offset_ms = self.start.elapsed().as_millis() as u64;
+        self.record_at(offset_ms, event);
+    }
+
+    /// イベントを指定オフセットで記録
+    pub fn record_at(&mut self, offset_ms: u64, event: &InputEvent) {
+        if let Some(max) = self.max_events {
+            if self.trace.events.len() >= max {
+                return;
+            }
+        }
+
+        // 配送状態は再生時に改めて決まるため初期状態で記録する
+        let mut event = event.clone();
+        event.handled = false;
+        event.propagate = true;
+
+        // オフセットは単調増加を保証する
+        let offset_ms = self
+            .trace
+            .events
+            .last()
+            .map(|e| e.offset_ms.max(offset_ms))
+            .unwrap_or(offset_ms);
+        self.trace.push(offset_ms, event);
+    }
+
+    pub fn event_count(&self) -> usize {
+        self.trace.events.len()
+    }
+
+    /// 記録を終了してトレースを取得
+    pub fn finish(self) -> InputTrace {
+        self.trace
+    }
+}
+
+/// 再生速度
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReplaySpeed {
+    /// 記録時と同じ間隔
+    Original,
+    /// 指定倍率で加速（2.0で2倍速）
+    Accelerated(f64),
+    /// 待ち時間なし
+    Immediate,
+}
+
+/// アサーションに渡される再生状況
+pub struct ReplayContext<'a> {
+    /// 直前に処理したイベントの番号
+    pub index: usize,
+    pub offset_ms: u64,
+    pub event: &'a InputEvent,
+    pub manager: &'a InputManager,
+}
+
+/// 再生中に評価されるアサーション
+pub type ReplayAssertion = Box<dyn FnMut(&ReplayContext) -> Result<(), String>>;
+
+/// 入力リプレイヤー - 記録されたトレースを `InputManager` へ決定的に再投入
+///
+/// 再生中の `InputManager` には記録時刻を刻む仮想時刻源が設定されるため、
+/// 再生速度に関係なくキーリピートなどの時間経過処理が記録時と同じになります。
+pub struct InputReplayer {
+    trace: InputTrace,
+    position: usize,
+    speed: ReplaySpeed,
+    /// 再生用の仮想時刻源
+    clock: Arc<ManualClock>,
+    /// オフセット0に対応する時刻（ミリ秒）- 記録されたイベントのタイムスタンプに合わせる
+    base_ms: u64,
+    /// (対象イベント番号, アサーション) - 番号が None なら毎イベント評価
+    assertions: Vec<(Option<usize>, ReplayAssertion)>,
+}
+
+impl InputReplayer {
+    pub fn new(trace: InputTrace) -> Self {
+        let base_ms = trace
+            .events
+            .first()
+            .map(|e| e.event.timestamp().saturating_sub(e.offset_ms))
+            .unwrap_or(0);
+
+        Self {
+            trace,
+            position: 0,
+            speed: ReplaySpeed::Immediate,
+            clock: Arc::new(ManualClock::new()),
+            base_ms,
+            assertions: Vec::new(),
+        }
+    }
+
+    pub fn with_speed(mut self, speed: ReplaySpeed) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    /// 各イベントの処理後に評価するアサーションを追加
+    pub fn add_assertion<F>(&mut self, assertion: F)
+    where
+        F: FnMut(&ReplayContext) -> Result<(), String> + 'static,
+    {
+        self.assertions.push((None, Box::new(assertion)));
+    }
+
+    /// 指定番号のイベント処理後に評価するアサーションを追加
+    pub fn add_checkpoint<F>(&mut self, index: usize, assertion: F)
+    where
+        F: FnMut(&ReplayContext) -> Result<(), String> + 'static,
+    {
+        self.assertions.push((Some(index), Box::new(assertion)));
+    }
+
+    pub fn trace(&self) -> &InputTrace {
+        &self.trace
+    }
+
+    /// 再生用の仮想時刻源
+    pub fn clock(&self) -> Arc<ManualClock> {
+        Arc::clone(&self.clock)
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.position >= self.trace.events.len()
+    }
+
+    /// 最初から再生し直す
+    pub fn rewind(&mut self) {
+        self.position = 0;
+    }
+
+    /// 次のイベントを1つだけ投入して処理（待ち時間なし）
+    ///
+    /// 投入するイベントがなければ `Ok(false)` を返します。
+    pub fn step(&mut self, manager: &mut InputManager) -> Result<bool, TraceError> {
+        if self.is_finished() {
+            return Ok(false);
+        }
+
+        let index = self.position;
+        let recorded = self.trace.events[index].clone();
+        self.position += 1;
+
+        self.set_time(recorded.offset_ms, manager);
+        manager.push_event(recorded.event.clone());
+        manager.process_events();
+
+        let context = ReplayContext {
+            index,
+            offset_ms: recorded.offset_ms,
+            event: &recorded.event,
+            manager,
+        };
+        for (target, assertion) in self.assertions.iter_mut() {
+            if target.map(|t| t == index).unwrap_or(true) {
+                assertion(&context).map_err(|message| TraceError::AssertionFailed {
+                    index,
+                    offset_ms: recorded.offset_ms,
+                    message,
+                })?;
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// 仮想時刻 `offset_ms` までのイベントを待ち時間なしで処理
+    ///
+    /// イベントの投入後、仮想時刻を `offset_ms` まで進めて時間経過処理を行います。
+    pub fn advance_to(&mut self, offset_ms: u64, manager: &mut InputManager) -> Result<usize, TraceError> {
+        let mut processed = 0;
+        while let Some(next) = self.trace.events.get(self.position) {
+            if next.offset_ms > offset_ms {
+                break;
+            }
+            self.step(manager)?;
+            processed += 1;
+        }
+
+        self.set_time(offset_ms, manager);
+        manager.process_events();
+        Ok(processed)
+    }
+
+    /// 残りのイベントを設定された速度で再生
+    pub fn run(&mut self, manager: &mut InputManager) -> Result<usize, TraceError> {
+        let mut processed = 0;
+        let mut previous_offset = self.previous_offset();
+
+        while let Some(offset) = self.trace.events.get(self.position).map(|e| e.offset_ms) {
+            self.wait(previous_offset, offset);
+            self.step(manager)?;
+            previous_offset = offset;
+            processed += 1;
+        }
+
+        Ok(processed)
+    }
+
+    /// 残りのイベントを任意の処理系（ジェスチャーマネージャーなど）へ再生
+    ///
+    /// 各イベントを渡す前に仮想時刻を記録時刻まで進めるため、処理系に `clock` を
+    /// 設定しておけば再生速度に関係なく経過時間が記録時と同じになります。
+    /// `sink` がエラーを返すと再生を中断して `AssertionFailed` を返します。
+    pub fn run_with<F>(&mut self, mut sink: F) -> Result<usize, TraceError>
+    where
+        F: FnMut(usize, &InputEvent) -> Result<(), String>,
+    {
+        let mut processed = 0;
+        let mut previous_offset = self.previous_offset();
+
+        while let Some(recorded) = self.trace.events.get(self.position).cloned() {
+            self.wait(previous_offset, recorded.offset_ms);
+
+            let index = self.position;
+            self.position += 1;
+            self.clock.set(Duration::from_millis(self.base_ms + recorded.offset_ms));
+            sink(index, &recorded.event).map_err(|message| TraceError::AssertionFailed {
+                index,
+                offset_ms: recorded.offset_ms,
+                message,
+            })?;
+
+            previous_offset = recorded.offset_ms;
+            processed += 1;
+        }
+
+        Ok(processed)
+    }
+
+    /// 仮想時刻を記録時のオフセットに合わせ、マネージャーの時刻源に設定
+    fn set_time(&self, offset_ms: u64, manager: &mut InputManager) {
+        self.clock.set(Duration::from_millis(self.base_ms + offset_ms));
+        manager.set_clock(self.clock.clone());
+    }
+
+    fn previous_offset(&self) -> u64 {
+        self.position
+            .checked_sub(1)
+            .and_then(|index| self.trace.events.get(index))
+            .map(|e| e.offset_ms)
+            .unwrap_or(0)
+    }
+
+    /// 再生速度に応じてイベント間隔だけ待機
+    fn wait(&self, previous_offset: u64, offset: u64) {
+        let interval = Duration::from_millis(offset.saturating_sub(previous_offset));
+        let delay = match self.speed {
+            ReplaySpeed::Original => interval,
+            ReplaySpeed::Accelerated(factor) if factor > 0.0 => interval.div_f64(factor),
+            ReplaySpeed::Accelerated(_) | ReplaySpeed::Immediate => Duration::ZERO,
+        };
+
+        if !delay.is_zero() {
+            thread::sleep(delay);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::window_manager::input_translator::input_manager::{InputEventType, MouseButton};
+    use std::collections::HashSet;
+
+    fn mouse_move(x: f64, y: f64, timestamp: u64) -> InputEvent {
+        InputEvent::new(InputEventType::MouseMove {
+            x,
+            y,
+            dx: 0.0,
+            dy: 0.0,
+            modifiers: HashSet::new(),
+            timestamp,
+        })
+    }
+
+    fn sample_trace() -> InputTrace {
+        let mut trace = InputTrace::new("テスト");
+        trace.push(0, mouse_move(10.0, 10.0, 100));
+        trace.push(16, mouse_move(20.0, 15.0, 116));
+        trace.push(40, InputEvent::new(InputEventType::MousePress {
+            button: MouseButton::Left,
+            x: 20.0,
+            y: 15.0,
+            modifiers: HashSet::new(),
+            timestamp: 140,
+        }));
+        trace
+    }
+
+    #[test]
+    fn test_trace_round_trip() {
+        let trace = sample_trace();
+        let mut buffer = Vec::new();
+        trace.write_to(&mut buffer).unwrap();
+
+        let loaded = InputTrace::read_from(buffer.as_slice()).unwrap();
+        assert_eq!(loaded.header, trace.header);
+        assert_eq!(loaded.len(), 3);
+        assert_eq!(loaded.events[1].offset_ms, 16);
+        assert_eq!(loaded.events[2].event.timestamp(), 140);
+        assert_eq!(loaded.duration(), Duration::from_millis(40));
+    }
+
+    #[test]
+    fn test_invalid_trace() {
+        let result = InputTrace::read_from("{\"version\":99,\"description\":\"\",\"recorded_at_ms\":0}\n".as_bytes());
+        assert_eq!(result.unwrap_err(), TraceError::UnsupportedVersion(99));
+
+        let result = InputTrace::read_from("not json\n".as_bytes());
+        assert!(matches!(result, Err(TraceError::Format { line: 1, .. })));
+    }
+
+    #[test]
+    fn test_manager_recording() {
+        let mut manager = InputManager::new();
+        manager.start_recording("記録テスト");
+        manager.push_event(mouse_move(1.0, 2.0, 10));
+        manager.push_event(mouse_move(3.0, 4.0, 20));
+        manager.process_events();
+
+        let trace = manager.stop_recording().unwrap();
+        assert_eq!(trace.len(), 2);
+        assert!(!manager.is_recording());
+    }
+
+    #[test]
+    fn test_replay_with_assertions() {
+        let mut manager = InputManager::new();
+        let mut replayer = InputReplayer::new(sample_trace());
+
+        replayer.add_checkpoint(1, |ctx| {
+            if ctx.manager.get_mouse_position() == (20.0, 15.0) {
+                Ok(())
+            } else {
+                Err(format!("位置が不正: {:?}", ctx.manager.get_mouse_position()))
+            }
+        });
+
+        assert_eq!(replayer.advance_to(16, &mut manager).unwrap(), 2);
+        assert_eq!(replayer.position(), 2);
+
+        assert_eq!(replayer.run(&mut manager).unwrap(), 1);
+        assert!(replayer.is_finished());
+        assert!(manager.get_pressed_buttons().contains(&MouseButton::Left));
+
+        // 失敗するアサーション
+        let mut replayer = InputReplayer::new(sample_trace());
+        replayer.add_assertion(|ctx| if ctx.index < 2 { Ok(()) } else { Err("停止".to_string()) });
+        let error = replayer.run(&mut InputManager::new()).unwrap_err();
+        assert!(matches!(error, TraceError::AssertionFailed { index: 2, offset_ms: 40, .. }));
+    }
+
+    #[test]
+    fn test_replay_uses_recorded_time() {
+        use crate::core::window_manager::input_translator::input_manager::{InputClock, KeyCode, KeySym};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let key = |timestamp, pressed| {
+            let (key_code, key_sym) = (KeyCode(38), KeySym("a".to_string()));
+            InputEvent::new(if pressed {
+                InputEventType::KeyPress { key_code, key_sym, modifiers: HashSet::new(), timestamp, repeat: false }
+            } else {
+                InputEventType::KeyRelease { key_code, key_sym, modifiers: HashSet::new(), timestamp }
+            })
+        };
+        let mut trace = InputTrace::new("キーリピート");
+        trace.push(0, key(1000, true));
+        trace.push(700, key(1700, false));
+
+        let repeats = Arc::new(AtomicUsize::new(0));
+        let mut manager = InputManager::new();
+        let counter = Arc::clone(&repeats);
+        manager.register_global_handler(move |event| {
+            if let InputEventType::KeyPress { repeat: true, .. } = event.event_type {
+                counter.fetch_add(1, Ordering::SeqCst);
+            }
+            false
+        });
+
+        // 待ち時間なしでも記録時刻に従って500ms後に1回だけリピートされる
+        let mut replayer = InputReplayer::new(trace);
+        assert_eq!(replayer.run(&mut manager).unwrap(), 2);
+        assert_eq!(repeats.load(Ordering::SeqCst), 1);
+        assert_eq!(replayer.clock().now(), Duration::from_millis(1700));
+    }
+
+    #[test]
+    fn test_run_with_advances_clock() {
+        use crate::core::window_manager::input_translator::input_manager::InputClock;
+
+        let mut replayer = InputReplayer::new(sample_trace());
+        let clock = replayer.clock();
+        let mut times = Vec::new();
+        replayer.run_with(|_, _| {
+            times.push(clock.now().as_millis());
+            Ok(())
+        }).unwrap();
+
+        assert_eq!(times, vec![100, 116, 140]);
+    }
+
+    #[test]
+    fn test_accelerated_replay_timing() {
+        let mut replayer = InputReplayer::new(sample_trace()).with_speed(ReplaySpeed::Accelerated(4.0));
+        let start = Instant::now();
+        let count = replayer.run_with(|_, _| Ok(())).unwrap();
+
+        assert_eq!(count, 3);
+        assert!(start.elapsed() >= Duration::from_millis(10));
+    }
+}
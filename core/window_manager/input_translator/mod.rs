@@ -0,0 +1,42 @@
+// LumosDesktop 入力変換モジュール
+// 物理デバイスからの入力をウィンドウマネージャのイベントに変換します
+
+//! 入力変換モジュール
+//!
+//! キーボード・マウス・タッチ・タブレットからの入力を `InputEvent` に変換し、
+//! フォーカスされたノードへ配送する機能を提供します。
+//! キーボード入力はXKB互換のキーマップでスキャンコードからキーシンボルへ変換され、
+//! テキスト入力欄にフォーカスがある場合はインプットメソッド（IME）を経由します。
+
+pub mod input_manager;
+pub mod xkb_keymap;
+pub mod text_input;
+pub mod input_recorder;
+pub mod pointer_profile;
+pub mod accessibility;
+
+// 主要な型の再エクスポート
+pub use input_manager::{
+    InputManager, InputEvent, InputEventType, InputHandler,
+    KeyModifier, KeyCode, KeySym, KeyInfo, MouseButton,
+    ShortcutDefinition, ShortcutAction, InputClock, SystemClock, ManualClock,
+};
+pub use xkb_keymap::{
+    XkbKeymap, XkbState, KeyboardLayout, KeyboardLayoutManager,
+    KeyTranslation, KeymapError, LayoutScope,
+};
+pub use text_input::{
+    TextInputManager, TextInputEvent, InputMethodEngine, ImeKeyEvent, ImeAction,
+    ImeResponse, Preedit, CandidateList, ContentType, RomajiKanaEngine,
+};
+pub use input_recorder::{
+    InputRecorder, InputReplayer, InputTrace, RecordedEvent, ReplaySpeed, ReplayContext, TraceError,
+};
+pub use pointer_profile::{
+    PointerPipeline, PointerProfile, PointerSettings, PointerDeviceKind, AccelProfile,
+    ScrollSettings, SyntheticScroll,
+};
+pub use accessibility::{
+    AccessibilityFilter, AccessibilitySettings, AccessibilityFeature, AccessibilityEvent,
+    StickyState,
+};
@@ -0,0 +1,623 @@
+// LumosDesktop ポインタープロファイル
+// ポインター加速・スクロールの平滑化/慣性・ナチュラルスクロールをデバイスごとに適用
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::core::settings::{SettingsError, SettingsManager};
+
+/// ポインター設定の設定キー
+pub const POINTER_SETTINGS_PATH: &str = "input.pointer";
+
+/// 速度計算をリセットするまでの無操作時間（ミリ秒）
+const MOTION_TIMEOUT_MS: u64 = 100;
+
+/// ホイール蓄積をリセットするまでの無操作時間（ミリ秒）
+const WHEEL_ACCUMULATION_TIMEOUT_MS: u64 = 500;
+
+/// 慣性スクロールを開始・継続する最小速度（単位/ミリ秒）
+const KINETIC_MIN_VELOCITY: f64 = 0.01;
+
+/// 平滑化スクロールの残量がこれ未満になったら一度に出力
+const SMOOTH_SCROLL_EPSILON: f64 = 0.01;
+
+/// ポインター加速プロファイル
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AccelProfile {
+    /// 速度に関係なく一定倍率
+    Flat { factor: f64 },
+    /// 速度に応じて倍率を上げる（speed は -1.0〜1.0）
+    Adaptive { speed: f64 },
+    /// (速度[単位/ms], 倍率) の点列を線形補間するカスタムカーブ
+    Custom { points: Vec<(f64, f64)> },
+}
+
+impl AccelProfile {
+    /// 指定速度での倍率
+    pub fn factor(&self, velocity: f64) -> f64 {
+        match self {
+            AccelProfile::Flat { factor } => *factor,
+            AccelProfile::Adaptive { speed } => {
+                let speed = speed.clamp(-1.0, 1.0);
+                let baseline = 1.0 + speed * 0.5;
+                let threshold = 0.4 - speed * 0.2;
+                let max_factor = 2.5 + speed * 1.5;
+                let incline = 1.5;
+
+                if velocity <= threshold {
+                    baseline
+                } else {
+                    (baseline + (velocity - threshold) * incline).min(max_factor.max(baseline))
+                }
+            }
+            AccelProfile::Custom { points } => {
+                let (first, last) = match (points.first(), points.last()) {
+                    (Some(first), Some(last)) => (first, last),
+                    _ => return 1.0,
+                };
+
+                if velocity <= first.0 {
+                    return first.1;
+                }
+                if velocity >= last.0 {
+                    return last.1;
+                }
+
+                points
+                    .windows(2)
+                    .find(|pair| velocity >= pair[0].0 && velocity <= pair[1].0)
+                    .map(|pair| {
+                        let (v0, f0) = pair[0];
+                        let (v1, f1) = pair[1];
+                        if v1 - v0 <= f64::EPSILON {
+                            f1
+                        } else {
+                            f0 + (f1 - f0) * (velocity - v0) / (v1 - v0)
+                        }
+                    })
+                    .unwrap_or(last.1)
+            }
+        }
+    }
+}
+
+/// ポインターデバイスの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PointerDeviceKind {
+    Mouse,
+    Touchpad,
+    Trackball,
+    Trackpoint,
+}
+
+/// スクロール設定
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScrollSettings {
+    /// スクロール方向を反転（ナチュラルスクロール）
+    pub natural_scroll: bool,
+    /// スクロール量の倍率
+    pub scroll_factor: f64,
+    /// ホイールのクリック単位スクロールを時間的に分散させる（マウスのみ）
+    pub smooth_scrolling: bool,
+    /// 平滑化にかける時間
+    pub smooth_duration_ms: u64,
+    /// 指を離した後もスクロールを継続する（タッチパッドのみ）
+    pub kinetic_scrolling: bool,
+    /// 慣性スクロールの減衰率（1秒あたり）
+    pub kinetic_friction: f64,
+    /// 高解像度ホイールの端数をクリック単位まで蓄積してから出力
+    pub wheel_accumulation: bool,
+}
+
+impl Default for ScrollSettings {
+    fn default() -> Self {
+        Self {
+            natural_scroll: false,
+            scroll_factor: 1.0,
+            smooth_scrolling: false,
+            smooth_duration_ms: 120,
+            kinetic_scrolling: false,
+            kinetic_friction: 4.0,
+            wheel_accumulation: false,
+        }
+    }
+}
+
+/// デバイスごとのポインタープロファイル
+///
+/// 既定値は入力をそのまま通すプロファイルです。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PointerProfile {
+    pub device_kind: PointerDeviceKind,
+    pub accel: AccelProfile,
+    pub scroll: ScrollSettings,
+}
+
+impl Default for PointerProfile {
+    fn default() -> Self {
+        Self {
+            device_kind: PointerDeviceKind::Mouse,
+            accel: AccelProfile::Flat { factor: 1.0 },
+            scroll: ScrollSettings::default(),
+        }
+    }
+}
+
+impl PointerProfile {
+    /// 一般的なマウス向けプロファイル
+    pub fn mouse() -> Self {
+        Self {
+            device_kind: PointerDeviceKind::Mouse,
+            accel: AccelProfile::Adaptive { speed: 0.0 },
+            scroll: ScrollSettings {
+                smooth_scrolling: true,
+                wheel_accumulation: true,
+                ..ScrollSettings::default()
+            },
+        }
+    }
+
+    /// タッチパッド向けプロファイル
+    pub fn touchpad() -> Self {
+        Self {
+            device_kind: PointerDeviceKind::Touchpad,
+            accel: AccelProfile::Adaptive { speed: 0.2 },
+            scroll: ScrollSettings {
+                natural_scroll: true,
+                kinetic_scrolling: true,
+                ..ScrollSettings::default()
+            },
+        }
+    }
+}
+
+/// ポインター設定全体（`input.pointer` に保存される）
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PointerSettings {
+    /// デバイス別の設定がない入力ソースに適用するプロファイル
+    pub default_profile: PointerProfile,
+    /// 入力ソース名（`InputEvent.source_device`）ごとのプロファイル
+    pub devices: HashMap<String, PointerProfile>,
+}
+
+/// 平滑化・慣性スクロールによって生成されたスクロール
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntheticScroll {
+    pub source: Option<String>,
+    pub x: f64,
+    pub y: f64,
+    pub dx: f64,
+    pub dy: f64,
+}
+
+/// デバイスごとの内部状態
+#[derive(Debug, Clone, Default)]
+struct DeviceState {
+    last_motion: Option<u64>,
+    velocity: f64,
+    last_scroll: Option<u64>,
+    scroll_position: (f64, f64),
+    scroll_velocity: (f64, f64),
+    wheel_accumulator: (f64, f64),
+    smooth_pending: (f64, f64),
+    kinetic_velocity: Option<(f64, f64)>,
+    last_tick: Option<u64>,
+}
+
+impl DeviceState {
+    fn is_animating(&self) -> bool {
+        self.kinetic_velocity.is_some() || self.smooth_pending != (0.0, 0.0)
+    }
+}
+
+/// ポインター入力パイプライン
+pub struct PointerPipeline {
+    settings: PointerSettings,
+    devices: HashMap<Option<String>, DeviceState>,
+}
+
+impl PointerPipeline {
+    pub fn new() -> Self {
+        Self::with_settings(PointerSettings::default())
+    }
+
+    pub fn with_settings(settings: PointerSettings) -> Self {
+        Self {
+            settings,
+            devices: HashMap::new(),
+        }
+    }
+
+    pub fn settings(&self) -> &PointerSettings {
+        &self.settings
+    }
+
+    /// 設定を置き換え（デバイスの内部状態はリセット）
+    pub fn set_settings(&mut self, settings: PointerSettings) {
+        self.settings = settings;
+        self.devices.clear();
+    }
+
+    /// 設定マネージャーから `input.pointer` を読み込む
+    pub fn load_settings(&mut self, settings: &SettingsManager) -> Result<(), SettingsError> {
+        let pointer_settings: PointerSettings = settings.get(POINTER_SETTINGS_PATH)?;
+        self.set_settings(pointer_settings);
+        Ok(())
+    }
+
+    /// 入力ソースのプロファイルを設定
+    pub fn set_device_profile(&mut self, source: &str, profile: PointerProfile) {
+        self.settings.devices.insert(source.to_string(), profile);
+        self.devices.remove(&Some(source.to_string()));
+    }
+
+    /// 入力ソースに適用されるプロファイル
+    pub fn profile_for(&self, source: Option<&str>) -> &PointerProfile {
+        source
+            .and_then(|name| self.settings.devices.get(name))
+            .unwrap_or(&self.settings.default_profile)
+    }
+
+    fn state_mut(&mut self, source: Option<&str>) -> &mut DeviceState {
+        self.devices.entry(source.map(|s| s.to_string())).or_default()
+    }
+
+    /// 相対移動量に加速を適用
+    pub fn accelerate(&mut self, source: Option<&str>, dx: f64, dy: f64, timestamp: u64) -> (f64, f64) {
+        let accel = self.profile_for(source).accel.clone();
+        let state = self.state_mut(source);
+
+        let distance = (dx * dx + dy * dy).sqrt();
+        let elapsed = state.last_motion.map(|last| timestamp.saturating_sub(last));
+        state.last_motion = Some(timestamp);
+
+        let velocity = match elapsed {
+            Some(elapsed) if elapsed <= MOTION_TIMEOUT_MS => {
+                let instantaneous = distance / elapsed.max(1) as f64;
+                // 急激な変化を抑えるため直前の速度と平均する
+                (state.velocity + instantaneous) / 2.0
+            }
+            // 動き始めは前回の速度を使わない
+            _ => distance / MOTION_TIMEOUT_MS as f64,
+        };
+        state.velocity = velocity;
+
+        let factor = accel.factor(velocity);
+        (dx * factor, dy * factor)
+    }
+
+    /// スクロール量を変換
+    ///
+    /// 平滑化やホイール蓄積によって今回は出力しない場合は `None` を返します。
+    /// 移動量ゼロのスクロールはスクロール終了（指を離した）として扱います。
+    pub fn process_scroll(
+        &mut self,
+        source: Option<&str>,
+        x: f64,
+        y: f64,
+        dx: f64,
+        dy: f64,
+        timestamp: u64,
+    ) -> Option<(f64, f64)> {
+        let profile = self.profile_for(source).clone();
+        let scroll = &profile.scroll;
+        let state = self.state_mut(source);
+
+        // 新しい入力があれば慣性スクロールは止める
+        state.kinetic_velocity = None;
+        state.scroll_position = (x, y);
+
+        let elapsed = state.last_scroll.map(|last| timestamp.saturating_sub(last));
+        state.last_scroll = Some(timestamp);
+
+        // 倍率と方向
+        let direction = if scroll.natural_scroll { -1.0 } else { 1.0 };
+        let dx = dx * scroll.scroll_factor * direction;
+        let dy = dy * scroll.scroll_factor * direction;
+
+        // 慣性スクロール
+        if scroll.kinetic_scrolling && profile.device_kind == PointerDeviceKind::Touchpad {
+            if dx == 0.0 && dy == 0.0 {
+                let (vx, vy) = state.scroll_velocity;
+                if vx.hypot(vy) >= KINETIC_MIN_VELOCITY {
+                    state.kinetic_velocity = Some((vx, vy));
+                    state.last_tick = None;
+                }
+                state.scroll_velocity = (0.0, 0.0);
+                return None;
+            }
+
+            let dt = match elapsed {
+                Some(elapsed) if elapsed <= MOTION_TIMEOUT_MS => elapsed.max(1) as f64,
+                _ => MOTION_TIMEOUT_MS as f64,
+            };
+            let (vx, vy) = state.scroll_velocity;
+            state.scroll_velocity = ((vx + dx / dt) / 2.0, (vy + dy / dt) / 2.0);
+        }
+
+        let (mut dx, mut dy) = (dx, dy);
+
+        // 高解像度ホイールの端数蓄積
+        if scroll.wheel_accumulation {
+            let (mut ax, mut ay) = state.wheel_accumulator;
+            let timed_out = elapsed.map(|e| e > WHEEL_ACCUMULATION_TIMEOUT_MS).unwrap_or(true);
+            if timed_out || ax * dx < 0.0 {
+                ax = 0.0;
+            }
+            if timed_out || ay * dy < 0.0 {
+                ay = 0.0;
+            }
+            ax += dx;
+            ay += dy;
+
+            dx = ax.trunc();
+            dy = ay.trunc();
+            state.wheel_accumulator = (ax - dx, ay - dy);
+
+            if dx == 0.0 && dy == 0.0 {
+                return None;
+            }
+        }
+
+        // ホイールの平滑化はアニメーションとして tick で出力
+        if scroll.smooth_scrolling && profile.device_kind == PointerDeviceKind::Mouse {
+            state.smooth_pending.0 += dx;
+            state.smooth_pending.1 += dy;
+            return None;
+        }
+
+        Some((dx, dy))
+    }
+
+    /// 入力ソースの慣性・平滑化スクロールを停止
+    pub fn cancel_scroll_animation(&mut self, source: Option<&str>) {
+        if let Some(state) = self.devices.get_mut(&source.map(|s| s.to_string())) {
+            state.kinetic_velocity = None;
+            state.smooth_pending = (0.0, 0.0);
+            state.last_tick = None;
+        }
+    }
+
+    /// 平滑化・慣性スクロール中のデバイスがあるか
+    pub fn is_animating(&self) -> bool {
+        self.devices.values().any(|state| state.is_animating())
+    }
+
+    /// 時刻 `now`（ミリ秒）まで平滑化・慣性スクロールを進め、生成されたスクロールを返す
+    pub fn tick(&mut self, now: u64) -> Vec<SyntheticScroll> {
+        let mut output = Vec::new();
+
+        for (source, state) in self.devices.iter_mut() {
+            if !state.is_animating() {
+                state.last_tick = None;
+                continue;
+            }
+
+            let profile = source
+                .as_ref()
+                .and_then(|name| self.settings.devices.get(name))
+                .unwrap_or(&self.settings.default_profile);
+
+            // 最初の tick は基準時刻の記録のみ
+            let dt = match state.last_tick {
+                Some(last) => now.saturating_sub(last) as f64,
+                None => {
+                    state.last_tick = Some(now);
+                    continue;
+                }
+            };
+            state.last_tick = Some(now);
+            if dt <= 0.0 {
+                continue;
+            }
+
+            let (mut dx, mut dy) = (0.0, 0.0);
+
+            if state.smooth_pending != (0.0, 0.0) {
+                let tau = (profile.scroll.smooth_duration_ms.max(1) as f64) / 3.0;
+                let fraction = 1.0 - (-dt / tau).exp();
+                let (px, py) = state.smooth_pending;
+                let (mut ex, mut ey) = (px * fraction, py * fraction);
+                if (px - ex).hypot(py - ey) < SMOOTH_SCROLL_EPSILON {
+                    ex = px;
+                    ey = py;
+                }
+                state.smooth_pending = (px - ex, py - ey);
+                dx += ex;
+                dy += ey;
+            }
+
+            if let Some((vx, vy)) = state.kinetic_velocity {
+                dx += vx * dt;
+                dy += vy * dt;
+
+                let decay = (-profile.scroll.kinetic_friction * dt / 1000.0).exp();
+                let (vx, vy) = (vx * decay, vy * decay);
+                state.kinetic_velocity = if vx.hypot(vy) >= KINETIC_MIN_VELOCITY {
+                    Some((vx, vy))
+                } else {
+                    None
+                };
+            }
+
+            if dx != 0.0 || dy != 0.0 {
+                output.push(SyntheticScroll {
+                    source: source.clone(),
+                    x: state.scroll_position.0,
+                    y: state.scroll_position.1,
+                    dx,
+                    dy,
+                });
+            }
+        }
+
+        output
+    }
+}
+
+impl Default for PointerPipeline {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accel_profiles() {
+        assert_eq!(AccelProfile::Flat { factor: 1.5 }.factor(10.0), 1.5);
+
+        let adaptive = AccelProfile::Adaptive { speed: 0.0 };
+        assert_eq!(adaptive.factor(0.1), 1.0);
+        assert!(adaptive.factor(1.0) > 1.0);
+        assert!(adaptive.factor(100.0) <= 2.5);
+
+        let custom = AccelProfile::Custom {
+            points: vec![(0.0, 0.5), (1.0, 1.0), (2.0, 3.0)],
+        };
+        assert_eq!(custom.factor(-1.0), 0.5);
+        assert!((custom.factor(1.5) - 2.0).abs() < 1e-9);
+        assert_eq!(custom.factor(5.0), 3.0);
+    }
+
+    #[test]
+    fn test_default_profile_is_passthrough() {
+        let mut pipeline = PointerPipeline::new();
+        assert_eq!(pipeline.accelerate(None, 10.0, 20.0, 1000), (10.0, 20.0));
+        assert_eq!(pipeline.process_scroll(None, 0.0, 0.0, 0.0, 1.0, 1000), Some((0.0, 1.0)));
+        assert!(pipeline.tick(1016).is_empty());
+    }
+
+    #[test]
+    fn test_per_device_acceleration_and_natural_scroll() {
+        let mut pipeline = PointerPipeline::new();
+        let profile = PointerProfile {
+            accel: AccelProfile::Flat { factor: 2.0 },
+            scroll: ScrollSettings {
+                natural_scroll: true,
+                scroll_factor: 3.0,
+                ..ScrollSettings::default()
+            },
+            ..PointerProfile::default()
+        };
+        pipeline.set_device_profile("trackball-0", profile);
+
+        assert_eq!(pipeline.accelerate(Some("trackball-0"), 1.0, -1.0, 0), (2.0, -2.0));
+        assert_eq!(pipeline.accelerate(Some("mouse-0"), 1.0, -1.0, 0), (1.0, -1.0));
+        assert_eq!(
+            pipeline.process_scroll(Some("trackball-0"), 0.0, 0.0, 0.0, 1.0, 0),
+            Some((-0.0, -3.0))
+        );
+    }
+
+    #[test]
+    fn test_high_resolution_wheel_accumulation() {
+        let profile = PointerProfile {
+            scroll: ScrollSettings {
+                wheel_accumulation: true,
+                ..ScrollSettings::default()
+            },
+            ..PointerProfile::default()
+        };
+        let mut pipeline = PointerPipeline::new();
+        pipeline.set_device_profile("wheel", profile);
+
+        // 1/4クリックずつの高解像度ホイール
+        let source = Some("wheel");
+        assert_eq!(pipeline.process_scroll(source, 0.0, 0.0, 0.0, 0.25, 0), None);
+        assert_eq!(pipeline.process_scroll(source, 0.0, 0.0, 0.0, 0.25, 10), None);
+        assert_eq!(pipeline.process_scroll(source, 0.0, 0.0, 0.0, 0.25, 20), None);
+        assert_eq!(pipeline.process_scroll(source, 0.0, 0.0, 0.0, 0.25, 30), Some((0.0, 1.0)));
+
+        // 方向が変わると端数はリセット
+        pipeline.process_scroll(source, 0.0, 0.0, 0.0, 0.5, 40);
+        assert_eq!(pipeline.process_scroll(source, 0.0, 0.0, 0.0, -0.75, 50), None);
+    }
+
+    #[test]
+    fn test_smooth_scrolling_spreads_wheel_clicks() {
+        let mut pipeline = PointerPipeline::new();
+        pipeline.set_device_profile("mouse", PointerProfile::mouse());
+
+        assert_eq!(pipeline.process_scroll(Some("mouse"), 5.0, 5.0, 0.0, 1.0, 0), None);
+        assert!(pipeline.is_animating());
+
+        assert!(pipeline.tick(0).is_empty());
+        let mut total = 0.0;
+        let mut frames = 0;
+        let mut now = 0;
+        while pipeline.is_animating() && frames < 100 {
+            now += 16;
+            for scroll in pipeline.tick(now) {
+                assert_eq!((scroll.x, scroll.y), (5.0, 5.0));
+                total += scroll.dy;
+            }
+            frames += 1;
+        }
+
+        assert!(frames > 1);
+        assert!((total - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_kinetic_scrolling_decays() {
+        let mut pipeline = PointerPipeline::new();
+        pipeline.set_device_profile("touchpad", PointerProfile::touchpad());
+        let source = Some("touchpad");
+
+        for i in 0..5 {
+            let scroll = pipeline.process_scroll(source, 0.0, 0.0, 0.0, 2.0, i * 10);
+            // ナチュラルスクロールで反転
+            assert_eq!(scroll, Some((-0.0, -2.0)));
+        }
+
+        // 指を離すと慣性スクロールが開始
+        assert_eq!(pipeline.process_scroll(source, 0.0, 0.0, 0.0, 0.0, 50), None);
+        assert!(pipeline.is_animating());
+
+        pipeline.tick(1000);
+        let first = pipeline.tick(1016);
+        assert!(first[0].dy < 0.0);
+
+        let mut now = 1016;
+        let mut last = first[0].dy.abs();
+        while pipeline.is_animating() {
+            now += 16;
+            if let Some(scroll) = pipeline.tick(now).first() {
+                assert!(scroll.dy.abs() <= last);
+                last = scroll.dy.abs();
+            }
+        }
+
+        // 新しい入力で慣性は止まる
+        pipeline.process_scroll(source, 0.0, 0.0, 0.0, 2.0, 2000);
+        pipeline.process_scroll(source, 0.0, 0.0, 0.0, 0.0, 2010);
+        pipeline.cancel_scroll_animation(source);
+        assert!(!pipeline.is_animating());
+    }
+
+    #[test]
+    fn test_settings_deserialization() {
+        let json = r#"{
+            "default_profile": { "accel": { "type": "adaptive", "speed": 0.5 } },
+            "devices": {
+                "touchpad-0": { "device_kind": "touchpad", "scroll": { "natural_scroll": true } }
+            }
+        }"#;
+        let settings: PointerSettings = serde_json::from_str(json).unwrap();
+
+        assert_eq!(settings.default_profile.accel, AccelProfile::Adaptive { speed: 0.5 });
+        let touchpad = &settings.devices["touchpad-0"];
+        assert_eq!(touchpad.device_kind, PointerDeviceKind::Touchpad);
+        assert!(touchpad.scroll.natural_scroll);
+        assert_eq!(touchpad.scroll.scroll_factor, 1.0);
+    }
+}
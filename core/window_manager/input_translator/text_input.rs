@@ -0,0 +1,1007 @@
+// LumosDesktop テキスト入力・インプットメソッド
+// 未確定文字列（プリエディット）・確定・候補ウィンドウ配置を扱うIMEフレームワーク
+
+use std::collections::{HashMap, HashSet};
+
+use crate::core::window_manager::compositor::wayland_compositor::Rectangle;
+use crate::core::window_manager::scene_graph::NodeId;
+use super::input_manager::{KeyModifier, KeySym};
+use super::xkb_keymap::keysym_to_char;
+
+/// IMEに渡されるキーイベント
+#[derive(Debug, Clone)]
+pub struct ImeKeyEvent {
+    pub key_sym: KeySym,
+    /// キーによって入力される文字列（キーマップで解決済みの場合）
+    pub text: Option<String>,
+    pub modifiers: HashSet<KeyModifier>,
+    pub pressed: bool,
+}
+
+impl ImeKeyEvent {
+    pub fn new(key_sym: KeySym, modifiers: HashSet<KeyModifier>, pressed: bool) -> Self {
+        let text = keysym_to_char(&key_sym.0).map(|c| c.to_string());
+        Self {
+            key_sym,
+            text,
+            modifiers,
+            pressed,
+        }
+    }
+
+    /// Ctrl/Alt/Super を伴うキー（ショートカット扱いでIMEに渡さない）
+    pub fn has_command_modifier(&self) -> bool {
+        self.modifiers.contains(&KeyModifier::Ctrl)
+            || self.modifiers.contains(&KeyModifier::Alt)
+            || self.modifiers.contains(&KeyModifier::Super)
+    }
+}
+
+/// 未確定文字列
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Preedit {
+    pub text: String,
+    /// カーソル位置（文字単位）
+    pub cursor: usize,
+}
+
+impl Preedit {
+    pub fn new(text: String) -> Self {
+        let cursor = text.chars().count();
+        Self { text, cursor }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+}
+
+/// 変換候補の一覧
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CandidateList {
+    pub candidates: Vec<String>,
+    pub selected: usize,
+}
+
+impl CandidateList {
+    pub fn selected_text(&self) -> Option<&str> {
+        self.candidates.get(self.selected).map(|s| s.as_str())
+    }
+}
+
+/// エンジンからフレームワークへの要求
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImeAction {
+    /// 未確定文字列の更新（空文字列で消去）
+    UpdatePreedit(Preedit),
+    /// 文字列の確定
+    Commit(String),
+    /// 候補ウィンドウの表示・更新
+    ShowCandidates(CandidateList),
+    /// 候補ウィンドウを閉じる
+    HideCandidates,
+}
+
+/// キー処理の結果
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ImeResponse {
+    /// キーをIMEが消費したか（消費した場合アプリケーションには配送しない）
+    pub consumed: bool,
+    pub actions: Vec<ImeAction>,
+}
+
+impl ImeResponse {
+    pub fn pass_through() -> Self {
+        Self::default()
+    }
+
+    pub fn consumed(actions: Vec<ImeAction>) -> Self {
+        Self {
+            consumed: true,
+            actions,
+        }
+    }
+}
+
+/// インプットメソッドエンジン
+pub trait InputMethodEngine: Send + Sync {
+    /// エンジン名
+    fn name(&self) -> &str;
+
+    /// キーイベントを処理
+    fn process_key(&mut self, event: &ImeKeyEvent) -> ImeResponse;
+
+    /// 未確定の入力を確定用文字列として取り出し、状態を初期化
+    fn flush(&mut self) -> Option<String>;
+
+    /// 未確定の入力を破棄
+    fn reset(&mut self);
+}
+
+/// テキスト入力欄の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentType {
+    Normal,
+    Password,
+    Number,
+    Email,
+    Url,
+    Terminal,
+}
+
+/// ノードごとのテキスト入力状態
+#[derive(Debug, Clone)]
+struct TextInputClient {
+    content_type: ContentType,
+    preedit: Preedit,
+    cursor_rect: Option<Rectangle>,
+    candidates: Option<CandidateList>,
+}
+
+/// テキスト入力イベント
+#[derive(Debug, Clone)]
+pub enum TextInputEvent {
+    /// IMEがノードに対して有効化された
+    Activated { node: NodeId, engine: String },
+    /// IMEがノードに対して無効化された
+    Deactivated { node: NodeId },
+    /// 未確定文字列が変化した
+    PreeditChanged { node: NodeId, preedit: Preedit },
+    /// 文字列が確定した
+    Commit { node: NodeId, text: String },
+    /// 候補ウィンドウの表示・移動
+    CandidateWindowShown {
+        node: NodeId,
+        candidates: CandidateList,
+        placement: Rectangle,
+    },
+    /// 候補ウィンドウを閉じた
+    CandidateWindowHidden { node: NodeId },
+}
+
+/// テキスト入力イベントのリスナー
+pub type TextInputListener = Box<dyn Fn(&TextInputEvent) + Send + Sync>;
+
+/// 候補ウィンドウ配置の設定
+#[derive(Debug, Clone, Copy)]
+pub struct CandidateWindowStyle {
+    /// 候補1件あたりの高さ
+    pub row_height: u32,
+    /// 候補ウィンドウの幅
+    pub width: u32,
+    /// カーソル矩形からの間隔
+    pub margin: i32,
+}
+
+impl Default for CandidateWindowStyle {
+    fn default() -> Self {
+        Self {
+            row_height: 24,
+            width: 240,
+            margin: 4,
+        }
+    }
+}
+
+/// テキスト入力マネージャー - キーボードフォーカスに追従してIMEエンジンを駆動
+pub struct TextInputManager {
+    engines: HashMap<String, Box<dyn InputMethodEngine>>,
+    active_engine: Option<String>,
+    enabled: bool,
+    clients: HashMap<NodeId, TextInputClient>,
+    focused: Option<NodeId>,
+    screen_bounds: Rectangle,
+    candidate_style: CandidateWindowStyle,
+    listeners: Vec<TextInputListener>,
+}
+
+impl TextInputManager {
+    pub fn new() -> Self {
+        Self {
+            engines: HashMap::new(),
+            active_engine: None,
+            enabled: false,
+            clients: HashMap::new(),
+            focused: None,
+            screen_bounds: Rectangle::new(0, 0, 1920, 1080),
+            candidate_style: CandidateWindowStyle::default(),
+            listeners: Vec::new(),
+        }
+    }
+
+    /// エンジンを登録（最初に登録したエンジンがアクティブになる）
+    pub fn register_engine(&mut self, engine: Box<dyn InputMethodEngine>) {
+        let name = engine.name().to_string();
+        if self.active_engine.is_none() {
+            self.active_engine = Some(name.clone());
+        }
+        self.engines.insert(name, engine);
+    }
+
+    /// アクティブなエンジンを切り替え
+    pub fn set_active_engine(&mut self, name: &str) -> Result<(), String> {
+        if !self.engines.contains_key(name) {
+            return Err(format!("インプットメソッドエンジン '{}' は登録されていません", name));
+        }
+
+        if self.active_engine.as_deref() != Some(name) {
+            self.finish_composition(true);
+            self.active_engine = Some(name.to_string());
+        }
+        Ok(())
+    }
+
+    pub fn active_engine(&self) -> Option<&str> {
+        self.active_engine.as_deref()
+    }
+
+    /// IMEのオン/オフ
+    pub fn set_enabled(&mut self, enabled: bool) {
+        if self.enabled == enabled {
+            return;
+        }
+
+        if !enabled {
+            self.finish_composition(true);
+        }
+        self.enabled = enabled;
+
+        if let Some(node) = self.focused.filter(|node| self.clients.contains_key(node)) {
+            if enabled {
+                self.emit_activated(node);
+            } else {
+                self.emit(TextInputEvent::Deactivated { node });
+            }
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// ノードのテキスト入力を有効化（テキスト入力欄を持つノードが呼び出す）
+    pub fn enable_for_node(&mut self, node: NodeId, content_type: ContentType) {
+        self.clients.insert(
+            node,
+            TextInputClient {
+                content_type,
+                preedit: Preedit::default(),
+                cursor_rect: None,
+                candidates: None,
+            },
+        );
+
+        if self.focused == Some(node) && self.is_active_for(node) {
+            self.emit_activated(node);
+        }
+    }
+
+    /// ノードのテキスト入力を無効化
+    pub fn disable_for_node(&mut self, node: NodeId) {
+        if self.focused == Some(node) {
+            self.finish_composition(false);
+        }
+        if self.clients.remove(&node).is_some() && self.focused == Some(node) && self.enabled {
+            self.emit(TextInputEvent::Deactivated { node });
+        }
+    }
+
+    /// フォーカス中のノードからカーソル矩形（スクリーン座標）を報告
+    pub fn set_cursor_rect(&mut self, node: NodeId, rect: Rectangle) {
+        let candidates = match self.clients.get_mut(&node) {
+            Some(client) => {
+                client.cursor_rect = Some(rect);
+                client.candidates.clone()
+            }
+            None => return,
+        };
+
+        // 候補ウィンドウ表示中はカーソルに追従させる
+        if let Some(candidates) = candidates {
+            self.show_candidates(node, candidates);
+        }
+    }
+
+    /// 候補ウィンドウを配置できる画面領域を設定
+    pub fn set_screen_bounds(&mut self, bounds: Rectangle) {
+        self.screen_bounds = bounds;
+    }
+
+    pub fn set_candidate_style(&mut self, style: CandidateWindowStyle) {
+        self.candidate_style = style;
+    }
+
+    /// キーボードフォーカスの変更を通知
+    pub fn focus_changed(&mut self, node: Option<NodeId>) {
+        if self.focused == node {
+            return;
+        }
+
+        // フォーカスを失うノードの未確定文字列は確定させる
+        self.finish_composition(true);
+        if let Some(old) = self.focused {
+            if self.enabled && self.clients.contains_key(&old) {
+                self.emit(TextInputEvent::Deactivated { node: old });
+            }
+        }
+
+        self.focused = node;
+        if let Some(new) = node {
+            if self.is_active_for(new) {
+                self.emit_activated(new);
+            }
+        }
+    }
+
+    /// フォーカス中のノードに対してIMEが動作しているか
+    pub fn is_active(&self) -> bool {
+        self.focused.map(|node| self.is_active_for(node)).unwrap_or(false)
+    }
+
+    fn is_active_for(&self, node: NodeId) -> bool {
+        self.enabled
+            && self.active_engine.is_some()
+            && self
+                .clients
+                .get(&node)
+                .map(|client| client.content_type != ContentType::Password)
+                .unwrap_or(false)
+    }
+
+    /// 現在の未確定文字列
+    pub fn preedit(&self, node: NodeId) -> Option<&Preedit> {
+        self.clients.get(&node).map(|client| &client.preedit)
+    }
+
+    /// キーイベントを処理し、IMEが消費した場合は true を返す
+    pub fn process_key(&mut self, event: &ImeKeyEvent) -> bool {
+        // 全角/半角キーなどによるオン/オフ切り替え
+        if event.pressed && self.focused.map(|n| self.clients.contains_key(&n)).unwrap_or(false) {
+            match event.key_sym.0.as_str() {
+                "Zenkaku_Hankaku" | "Hiragana_Katakana" => {
+                    let enabled = !self.enabled;
+                    self.set_enabled(enabled);
+                    return true;
+                }
+                "Henkan_Mode" if !self.enabled => {
+                    self.set_enabled(true);
+                    return true;
+                }
+                "Muhenkan" if self.enabled => {
+                    self.set_enabled(false);
+                    return true;
+                }
+                _ => {}
+            }
+        }
+
+        let node = match self.focused {
+            Some(node) if self.is_active_for(node) => node,
+            _ => return false,
+        };
+
+        // 未確定文字列がない状態のショートカットはアプリケーションへ
+        let composing = self
+            .clients
+            .get(&node)
+            .map(|client| !client.preedit.is_empty())
+            .unwrap_or(false);
+        if event.has_command_modifier() && !composing {
+            return false;
+        }
+
+        let response = match self.engine_mut() {
+            Some(engine) => engine.process_key(event),
+            None => return false,
+        };
+
+        self.apply_actions(node, response.actions);
+        response.consumed
+    }
+
+    fn engine_mut(&mut self) -> Option<&mut Box<dyn InputMethodEngine>> {
+        let name = self.active_engine.as_ref()?;
+        self.engines.get_mut(name)
+    }
+
+    /// 未確定の入力を終了（commit が true なら確定、false なら破棄）
+    fn finish_composition(&mut self, commit: bool) {
+        let node = match self.focused {
+            Some(node) if self.clients.contains_key(&node) => node,
+            _ => return,
+        };
+
+        let text = match self.engine_mut() {
+            Some(engine) if commit => engine.flush(),
+            Some(engine) => {
+                engine.reset();
+                None
+            }
+            None => None,
+        };
+
+        let mut actions = Vec::new();
+        if let Some(text) = text.filter(|t| !t.is_empty()) {
+            actions.push(ImeAction::Commit(text));
+        }
+        actions.push(ImeAction::UpdatePreedit(Preedit::default()));
+        actions.push(ImeAction::HideCandidates);
+        self.apply_actions(node, actions);
+    }
+
+    fn apply_actions(&mut self, node: NodeId, actions: Vec<ImeAction>) {
+        for action in actions {
+            match action {
+                ImeAction::UpdatePreedit(preedit) => {
+                    let changed = match self.clients.get_mut(&node) {
+                        Some(client) if client.preedit != preedit => {
+                            client.preedit = preedit.clone();
+                            true
+                        }
+                        _ => false,
+                    };
+                    if changed {
+                        self.emit(TextInputEvent::PreeditChanged { node, preedit });
+                    }
+                }
+                ImeAction::Commit(text) => {
+                    self.emit(TextInputEvent::Commit { node, text });
+                }
+                ImeAction::ShowCandidates(candidates) => {
+                    self.show_candidates(node, candidates);
+                }
+                ImeAction::HideCandidates => {
+                    let was_shown = self
+                        .clients
+                        .get_mut(&node)
+                        .and_then(|client| client.candidates.take())
+                        .is_some();
+                    if was_shown {
+                        self.emit(TextInputEvent::CandidateWindowHidden { node });
+                    }
+                }
+            }
+        }
+    }
+
+    fn show_candidates(&mut self, node: NodeId, candidates: CandidateList) {
+        let cursor_rect = match self.clients.get_mut(&node) {
+            Some(client) => {
+                client.candidates = Some(candidates.clone());
+                client.cursor_rect
+            }
+            None => return,
+        };
+
+        let placement = self.candidate_placement(cursor_rect, candidates.candidates.len());
+        self.emit(TextInputEvent::CandidateWindowShown {
+            node,
+            candidates,
+            placement,
+        });
+    }
+
+    /// 候補ウィンドウの位置を計算
+    ///
+    /// カーソル矩形の直下に配置し、画面下端に収まらない場合は上側に反転します。
+    /// 水平方向は画面内に収まるよう調整します。
+    pub fn candidate_placement(&self, cursor_rect: Option<Rectangle>, count: usize) -> Rectangle {
+        let style = &self.candidate_style;
+        let bounds = &self.screen_bounds;
+        let width = style.width.min(bounds.width);
+        let height = (style.row_height * count.max(1) as u32).min(bounds.height);
+
+        // カーソル位置が不明な場合は画面中央下部に表示
+        let cursor = cursor_rect.unwrap_or_else(|| {
+            Rectangle::new(
+                bounds.x + (bounds.width as i32 - width as i32) / 2,
+                bounds.y + bounds.height as i32 * 3 / 4,
+                0,
+                0,
+            )
+        });
+
+        let right_limit = bounds.x + bounds.width as i32 - width as i32;
+        let x = cursor.x.min(right_limit).max(bounds.x);
+
+        let below = cursor.y + cursor.height as i32 + style.margin;
+        let bottom_limit = bounds.y + bounds.height as i32;
+        let y = if below + height as i32 <= bottom_limit {
+            below
+        } else {
+            (cursor.y - style.margin - height as i32).max(bounds.y)
+        };
+
+        Rectangle::new(x, y, width, height)
+    }
+
+    /// リスナーを登録
+    pub fn add_listener<F>(&mut self, listener: F)
+    where
+        F: Fn(&TextInputEvent) + Send + Sync + 'static,
+    {
+        self.listeners.push(Box::new(listener));
+    }
+
+    fn emit_activated(&self, node: NodeId) {
+        if let Some(engine) = &self.active_engine {
+            self.emit(TextInputEvent::Activated {
+                node,
+                engine: engine.clone(),
+            });
+        }
+    }
+
+    fn emit(&self, event: TextInputEvent) {
+        for listener in &self.listeners {
+            listener(&event);
+        }
+    }
+}
+
+impl Default for TextInputManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// ローマ字かな変換エンジン（テスト用の簡易日本語入力）
+///
+/// ローマ字をひらがなに逐次変換し、スペースでひらがな/カタカナ/英字の候補を
+/// 切り替え、Enterで確定します。辞書による漢字変換は行いません。
+pub struct RomajiKanaEngine {
+    /// 変換済みのかな
+    kana: String,
+    /// かなに変換されていないローマ字
+    pending: String,
+    /// 入力されたローマ字全体（英字候補用）
+    raw: String,
+    candidates: Option<CandidateList>,
+}
+
+impl RomajiKanaEngine {
+    pub fn new() -> Self {
+        Self {
+            kana: String::new(),
+            pending: String::new(),
+            raw: String::new(),
+            candidates: None,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.kana.is_empty() && self.pending.is_empty()
+    }
+
+    fn clear(&mut self) {
+        self.kana.clear();
+        self.pending.clear();
+        self.raw.clear();
+        self.candidates = None;
+    }
+
+    fn preedit_text(&self) -> String {
+        match &self.candidates {
+            Some(list) => list.selected_text().unwrap_or_default().to_string(),
+            None => format!("{}{}", self.kana, self.pending),
+        }
+    }
+
+    fn preedit_action(&self) -> ImeAction {
+        ImeAction::UpdatePreedit(Preedit::new(self.preedit_text()))
+    }
+
+    /// ローマ字を1文字追加して変換
+    fn push_char(&mut self, c: char) {
+        self.raw.push(c);
+        self.pending.push(c.to_ascii_lowercase());
+
+        loop {
+            if self.pending.is_empty() {
+                break;
+            }
+
+            if let Some(kana) = romaji_to_kana(&self.pending) {
+                self.kana.push_str(kana);
+                self.pending.clear();
+                break;
+            }
+
+            let chars: Vec<char> = self.pending.chars().collect();
+            let first = chars[0];
+
+            // 子音の重複は促音
+            if chars.len() >= 2 && first == chars[1] && is_consonant(first) && first != 'n' {
+                self.kana.push('っ');
+                self.pending.remove(0);
+                continue;
+            }
+
+            // 母音・y 以外が続く n は撥音
+            if first == 'n' && chars.len() >= 2 && !"aiueoy".contains(chars[1]) {
+                self.kana.push('ん');
+                self.pending.remove(0);
+                continue;
+            }
+
+            if has_romaji_prefix(&self.pending) {
+                break;
+            }
+
+            // 変換できない文字はそのまま残す
+            self.kana.push(first);
+            self.pending.remove(0);
+        }
+    }
+
+    /// 未変換のローマ字を確定用に変換
+    fn flush_pending(&mut self) {
+        if self.pending == "n" {
+            self.kana.push('ん');
+        } else {
+            let pending = std::mem::take(&mut self.pending);
+            self.kana.push_str(&pending);
+        }
+        self.pending.clear();
+    }
+
+    fn commit(&mut self) -> ImeResponse {
+        let text = match self.candidates.take() {
+            Some(list) => list.selected_text().unwrap_or_default().to_string(),
+            None => {
+                self.flush_pending();
+                self.kana.clone()
+            }
+        };
+        self.clear();
+
+        ImeResponse::consumed(vec![
+            ImeAction::Commit(text),
+            ImeAction::UpdatePreedit(Preedit::default()),
+            ImeAction::HideCandidates,
+        ])
+    }
+
+    fn convert(&mut self) -> ImeResponse {
+        match &mut self.candidates {
+            Some(list) => {
+                list.selected = (list.selected + 1) % list.candidates.len().max(1);
+            }
+            None => {
+                self.flush_pending();
+                let hiragana = self.kana.clone();
+                let mut candidates = vec![hiragana.clone()];
+                for candidate in [hiragana_to_katakana(&hiragana), self.raw.clone()] {
+                    if !candidates.contains(&candidate) {
+                        candidates.push(candidate);
+                    }
+                }
+                // 最初のスペースで次の候補を選択
+                let selected = if candidates.len() > 1 { 1 } else { 0 };
+                self.candidates = Some(CandidateList { candidates, selected });
+            }
+        }
+
+        let list = self.candidates.clone().unwrap_or_default();
+        ImeResponse::consumed(vec![self.preedit_action(), ImeAction::ShowCandidates(list)])
+    }
+}
+
+impl Default for RomajiKanaEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InputMethodEngine for RomajiKanaEngine {
+    fn name(&self) -> &str {
+        "romaji-kana"
+    }
+
+    fn process_key(&mut self, event: &ImeKeyEvent) -> ImeResponse {
+        // キー解放は押下を消費したかどうかに合わせる
+        if !event.pressed {
+            return if self.is_empty() {
+                ImeResponse::pass_through()
+            } else {
+                ImeResponse::consumed(Vec::new())
+            };
+        }
+
+        match event.key_sym.0.as_str() {
+            "Return" | "KP_Enter" if !self.is_empty() => return self.commit(),
+            "space" if !self.is_empty() => return self.convert(),
+            "Escape" if !self.is_empty() => {
+                if self.candidates.take().is_some() {
+                    return ImeResponse::consumed(vec![self.preedit_action(), ImeAction::HideCandidates]);
+                }
+                self.clear();
+                return ImeResponse::consumed(vec![ImeAction::UpdatePreedit(Preedit::default())]);
+            }
+            "BackSpace" if !self.is_empty() => {
+                let mut actions = Vec::new();
+                if self.candidates.take().is_some() {
+                    actions.push(ImeAction::HideCandidates);
+                } else if self.pending.pop().is_none() {
+                    self.kana.pop();
+                }
+                if self.is_empty() {
+                    self.raw.clear();
+                }
+                actions.insert(0, self.preedit_action());
+                return ImeResponse::consumed(actions);
+            }
+            _ => {}
+        }
+
+        let ch = match event.text.as_deref().and_then(|t| t.chars().next()) {
+            Some(ch) if ch.is_ascii_graphic() => ch,
+            _ => return ImeResponse::pass_through(),
+        };
+
+        // 候補選択中に文字が入力されたら選択中の候補を確定して新しい入力を開始
+        let mut actions = Vec::new();
+        if let Some(list) = self.candidates.take() {
+            actions.push(ImeAction::Commit(list.selected_text().unwrap_or_default().to_string()));
+            actions.push(ImeAction::HideCandidates);
+            self.clear();
+        }
+
+        if ch.is_ascii_alphabetic() || ch == '\'' {
+            self.push_char(ch);
+        } else if let Some(symbol) = ascii_to_japanese_symbol(ch) {
+            self.flush_pending();
+            self.kana.push(symbol);
+            self.raw.push(ch);
+        } else {
+            if self.is_empty() && actions.is_empty() {
+                return ImeResponse::pass_through();
+            }
+            self.flush_pending();
+            self.kana.push(ch);
+            self.raw.push(ch);
+        }
+
+        actions.push(self.preedit_action());
+        ImeResponse::consumed(actions)
+    }
+
+    fn flush(&mut self) -> Option<String> {
+        if self.is_empty() && self.candidates.is_none() {
+            return None;
+        }
+
+        let text = match self.candidates.take() {
+            Some(list) => list.selected_text().unwrap_or_default().to_string(),
+            None => {
+                self.flush_pending();
+                self.kana.clone()
+            }
+        };
+        self.clear();
+        Some(text)
+    }
+
+    fn reset(&mut self) {
+        self.clear();
+    }
+}
+
+fn is_consonant(c: char) -> bool {
+    c.is_ascii_lowercase() && !"aiueo".contains(c)
+}
+
+/// ひらがなをカタカナに変換
+pub fn hiragana_to_katakana(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            'ぁ'..='ゖ' => char::from_u32(c as u32 + 0x60).unwrap_or(c),
+            _ => c,
+        })
+        .collect()
+}
+
+fn ascii_to_japanese_symbol(c: char) -> Option<char> {
+    match c {
+        '-' => Some('ー'),
+        ',' => Some('、'),
+        '.' => Some('。'),
+        '[' => Some('「'),
+        ']' => Some('」'),
+        _ => None,
+    }
+}
+
+/// ローマ字かな対応表
+const ROMAJI_TABLE: &[(&str, &str)] = &[
+    ("a", "あ"), ("i", "い"), ("u", "う"), ("e", "え"), ("o", "お"),
+    ("ka", "か"), ("ki", "き"), ("ku", "く"), ("ke", "け"), ("ko", "こ"),
+    ("ga", "が"), ("gi", "ぎ"), ("gu", "ぐ"), ("ge", "げ"), ("go", "ご"),
+    ("sa", "さ"), ("si", "し"), ("shi", "し"), ("su", "す"), ("se", "せ"), ("so", "そ"),
+    ("za", "ざ"), ("zi", "じ"), ("ji", "じ"), ("zu", "ず"), ("ze", "ぜ"), ("zo", "ぞ"),
+    ("ta", "た"), ("ti", "ち"), ("chi", "ち"), ("tu", "つ"), ("tsu", "つ"), ("te", "て"), ("to", "と"),
+    ("da", "だ"), ("di", "ぢ"), ("du", "づ"), ("de", "で"), ("do", "ど"),
+    ("na", "な"), ("ni", "に"), ("nu", "ぬ"), ("ne", "ね"), ("no", "の"),
+    ("ha", "は"), ("hi", "ひ"), ("hu", "ふ"), ("fu", "ふ"), ("he", "へ"), ("ho", "ほ"),
+    ("ba", "ば"), ("bi", "び"), ("bu", "ぶ"), ("be", "べ"), ("bo", "ぼ"),
+    ("pa", "ぱ"), ("pi", "ぴ"), ("pu", "ぷ"), ("pe", "ぺ"), ("po", "ぽ"),
+    ("ma", "ま"), ("mi", "み"), ("mu", "む"), ("me", "め"), ("mo", "も"),
+    ("ya", "や"), ("yu", "ゆ"), ("yo", "よ"),
+    ("ra", "ら"), ("ri", "り"), ("ru", "る"), ("re", "れ"), ("ro", "ろ"),
+    ("wa", "わ"), ("wo", "を"), ("nn", "ん"), ("n'", "ん"),
+    ("kya", "きゃ"), ("kyu", "きゅ"), ("kyo", "きょ"),
+    ("gya", "ぎゃ"), ("gyu", "ぎゅ"), ("gyo", "ぎょ"),
+    ("sha", "しゃ"), ("shu", "しゅ"), ("she", "しぇ"), ("sho", "しょ"),
+    ("sya", "しゃ"), ("syu", "しゅ"), ("syo", "しょ"),
+    ("ja", "じゃ"), ("ju", "じゅ"), ("je", "じぇ"), ("jo", "じょ"),
+    ("jya", "じゃ"), ("jyu", "じゅ"), ("jyo", "じょ"),
+    ("zya", "じゃ"), ("zyu", "じゅ"), ("zyo", "じょ"),
+    ("cha", "ちゃ"), ("chu", "ちゅ"), ("che", "ちぇ"), ("cho", "ちょ"),
+    ("tya", "ちゃ"), ("tyu", "ちゅ"), ("tyo", "ちょ"),
+    ("nya", "にゃ"), ("nyu", "にゅ"), ("nyo", "にょ"),
+    ("hya", "ひゃ"), ("hyu", "ひゅ"), ("hyo", "ひょ"),
+    ("bya", "びゃ"), ("byu", "びゅ"), ("byo", "びょ"),
+    ("pya", "ぴゃ"), ("pyu", "ぴゅ"), ("pyo", "ぴょ"),
+    ("mya", "みゃ"), ("myu", "みゅ"), ("myo", "みょ"),
+    ("rya", "りゃ"), ("ryu", "りゅ"), ("ryo", "りょ"),
+    ("fa", "ふぁ"), ("fi", "ふぃ"), ("fe", "ふぇ"), ("fo", "ふぉ"),
+    ("thi", "てぃ"), ("dhi", "でぃ"),
+    ("xa", "ぁ"), ("xi", "ぃ"), ("xu", "ぅ"), ("xe", "ぇ"), ("xo", "ぉ"),
+    ("la", "ぁ"), ("li", "ぃ"), ("lu", "ぅ"), ("le", "ぇ"), ("lo", "ぉ"),
+    ("xya", "ゃ"), ("xyu", "ゅ"), ("xyo", "ょ"),
+    ("lya", "ゃ"), ("lyu", "ゅ"), ("lyo", "ょ"),
+    ("xtu", "っ"), ("ltu", "っ"), ("xtsu", "っ"),
+];
+
+fn romaji_to_kana(romaji: &str) -> Option<&'static str> {
+    ROMAJI_TABLE
+        .iter()
+        .find(|(key, _)| *key == romaji)
+        .map(|(_, kana)| *kana)
+}
+
+fn has_romaji_prefix(prefix: &str) -> bool {
+    ROMAJI_TABLE.iter().any(|(key, _)| key.starts_with(prefix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    fn key(sym: &str) -> ImeKeyEvent {
+        ImeKeyEvent::new(KeySym(sym.to_string()), HashSet::new(), true)
+    }
+
+    fn type_romaji(engine: &mut RomajiKanaEngine, text: &str) {
+        for c in text.chars() {
+            engine.process_key(&key(&c.to_string()));
+        }
+    }
+
+    fn setup() -> (TextInputManager, Arc<Mutex<Vec<TextInputEvent>>>) {
+        let mut manager = TextInputManager::new();
+        manager.register_engine(Box::new(RomajiKanaEngine::new()));
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&events);
+        manager.add_listener(move |event| sink.lock().unwrap().push(event.clone()));
+
+        (manager, events)
+    }
+
+    #[test]
+    fn test_romaji_conversion() {
+        let mut engine = RomajiKanaEngine::new();
+        type_romaji(&mut engine, "nihongo");
+        assert_eq!(engine.preedit_text(), "にほんご");
+        engine.reset();
+
+        type_romaji(&mut engine, "kitte");
+        assert_eq!(engine.preedit_text(), "きって");
+        engine.reset();
+
+        type_romaji(&mut engine, "kyouhasamui");
+        assert_eq!(engine.preedit_text(), "きょうはさむい");
+        engine.reset();
+
+        type_romaji(&mut engine, "hon");
+        assert_eq!(engine.flush().as_deref(), Some("ほん"));
+    }
+
+    #[test]
+    fn test_preedit_and_commit_follow_focus() {
+        let (mut manager, events) = setup();
+        let field = NodeId(10);
+
+        manager.enable_for_node(field, ContentType::Normal);
+        manager.focus_changed(Some(field));
+        manager.set_enabled(true);
+        assert!(manager.is_active());
+
+        for c in "sushi".chars() {
+            assert!(manager.process_key(&key(&c.to_string())));
+        }
+        assert_eq!(manager.preedit(field).unwrap().text, "すし");
+        assert_eq!(manager.preedit(field).unwrap().cursor, 2);
+
+        assert!(manager.process_key(&key("Return")));
+        assert!(manager.preedit(field).unwrap().is_empty());
+
+        let commits: Vec<String> = events
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|e| match e {
+                TextInputEvent::Commit { text, .. } => Some(text.clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(commits, vec!["すし".to_string()]);
+
+        // フォーカス移動で未確定文字列は確定される
+        manager.process_key(&key("k"));
+        manager.process_key(&key("a"));
+        manager.focus_changed(None);
+        let last_commit = events.lock().unwrap().iter().rev().find_map(|e| match e {
+            TextInputEvent::Commit { text, .. } => Some(text.clone()),
+            _ => None,
+        });
+        assert_eq!(last_commit.as_deref(), Some("か"));
+        assert!(!manager.is_active());
+    }
+
+    #[test]
+    fn test_candidate_window_placement() {
+        let (mut manager, events) = setup();
+        let field = NodeId(20);
+        manager.set_screen_bounds(Rectangle::new(0, 0, 800, 600));
+        manager.enable_for_node(field, ContentType::Normal);
+        manager.focus_changed(Some(field));
+        manager.set_enabled(true);
+        manager.set_cursor_rect(field, Rectangle::new(700, 100, 2, 20));
+
+        for c in "kana".chars() {
+            manager.process_key(&key(&c.to_string()));
+        }
+        assert!(manager.process_key(&key("space")));
+        assert_eq!(manager.preedit(field).unwrap().text, "カナ");
+
+        let placement = events.lock().unwrap().iter().rev().find_map(|e| match e {
+            TextInputEvent::CandidateWindowShown { placement, candidates, .. } => {
+                assert_eq!(candidates.candidates, vec!["かな", "カナ", "kana"]);
+                Some(*placement)
+            }
+            _ => None,
+        });
+        let placement = placement.expect("候補ウィンドウが表示されていません");
+        // 右端に収まるよう左へずらし、カーソルの下に配置
+        assert_eq!(placement.x, 800 - 240);
+        assert_eq!(placement.y, 124);
+
+        // 画面下端付近では上側に反転
+        manager.set_cursor_rect(field, Rectangle::new(10, 580, 2, 20));
+        let placement = events.lock().unwrap().iter().rev().find_map(|e| match e {
+            TextInputEvent::CandidateWindowShown { placement, .. } => Some(*placement),
+            _ => None,
+        });
+        assert_eq!(placement.unwrap().y, 580 - 4 - 72);
+    }
+
+    #[test]
+    fn test_passthrough_for_password_and_shortcuts() {
+        let (mut manager, _events) = setup();
+        let password = NodeId(30);
+        manager.set_enabled(true);
+        manager.enable_for_node(password, ContentType::Password);
+        manager.focus_changed(Some(password));
+        assert!(!manager.process_key(&key("a")));
+
+        let field = NodeId(31);
+        manager.enable_for_node(field, ContentType::Normal);
+        manager.focus_changed(Some(field));
+        let mut ctrl_c = key("c");
+        ctrl_c.modifiers.insert(KeyModifier::Ctrl);
+        assert!(!manager.process_key(&ctrl_c));
+    }
+}
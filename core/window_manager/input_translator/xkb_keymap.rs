@@ -0,0 +1,2369 @@
+// LumosDesktop XKBキーマップ
+// XKB互換キーマップの解析、スキャンコードからキーシンボルへの変換、レイアウト切り替えを担当
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::error::Error;
+use std::fmt::{self, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::core::window_manager::scene_graph::NodeId;
+use super::input_manager::{KeyModifier, KeySym};
+
+/// evdevスキャンコードとXKBキーコードの差分
+pub const EVDEV_OFFSET: u32 = 8;
+
+/// 実モディファイアのビットマスク
+pub type ModMask = u32;
+
+pub const MOD_SHIFT: ModMask = 1 << 0;
+pub const MOD_LOCK: ModMask = 1 << 1;
+pub const MOD_CONTROL: ModMask = 1 << 2;
+pub const MOD_MOD1: ModMask = 1 << 3;
+pub const MOD_MOD2: ModMask = 1 << 4;
+pub const MOD_MOD3: ModMask = 1 << 5;
+pub const MOD_MOD4: ModMask = 1 << 6;
+pub const MOD_MOD5: ModMask = 1 << 7;
+const MOD_ALL: ModMask = 0xff;
+
+// 仮想モディファイアは標準的な割り当てで実モディファイアに解決する
+const MOD_ALT: ModMask = MOD_MOD1;
+const MOD_NUM_LOCK: ModMask = MOD_MOD2;
+const MOD_SUPER: ModMask = MOD_MOD4;
+const MOD_LEVEL_THREE: ModMask = MOD_MOD5;
+
+/// キーマップ関連のエラー
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeymapError {
+    /// キーマップテキストの構文エラー
+    Parse {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    /// ファイル読み込みエラー
+    Io(String),
+    /// 組み込みレイアウトが見つからない
+    UnknownLayout(String),
+    /// 存在しないレイアウトインデックス
+    InvalidLayoutIndex(usize),
+    /// グループを一つも含まないキーマップ
+    EmptyKeymap,
+}
+
+impl fmt::Display for KeymapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeymapError::Parse { line, column, message } => {
+                write!(f, "キーマップ構文エラー ({}:{}): {}", line, column, message)
+            }
+            KeymapError::Io(msg) => write!(f, "キーマップ読み込みエラー: {}", msg),
+            KeymapError::UnknownLayout(name) => write!(f, "不明なキーボードレイアウト: {}", name),
+            KeymapError::InvalidLayoutIndex(index) => {
+                write!(f, "無効なレイアウトインデックス: {}", index)
+            }
+            KeymapError::EmptyKeymap => write!(f, "キーマップにレイアウトが含まれていません"),
+        }
+    }
+}
+
+impl Error for KeymapError {}
+
+/// キータイプ - アクティブなモディファイアからシフトレベルを決定する
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyType {
+    pub name: String,
+    /// レベル決定に関与するモディファイア
+    pub modifiers: ModMask,
+    /// (モディファイアの組み合わせ, レベル) の対応表（レベルは0始まり）
+    pub map: Vec<(ModMask, usize)>,
+}
+
+impl KeyType {
+    /// 指定したモディファイア状態でのレベルを取得
+    pub fn level(&self, mods: ModMask) -> usize {
+        let relevant = mods & self.modifiers;
+        self.map
+            .iter()
+            .find(|(mask, _)| *mask == relevant)
+            .map(|(_, level)| *level)
+            .unwrap_or(0)
+    }
+}
+
+/// 1グループ分のキー定義
+#[derive(Debug, Clone, Default, PartialEq)]
+struct KeyGroup {
+    type_name: Option<String>,
+    levels: Vec<Option<String>>,
+}
+
+/// キーコードに割り当てられたシンボル定義
+#[derive(Debug, Clone, PartialEq)]
+struct KeyEntry {
+    name: String,
+    groups: Vec<KeyGroup>,
+}
+
+/// キーが押されたときの動作（xkb_compatibility の interpret に相当）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum KeyAction {
+    SetMods(ModMask),
+    LockMods(ModMask),
+    NextGroup,
+    PrevGroup,
+}
+
+/// コンパイル済みXKBキーマップ
+#[derive(Debug, Clone)]
+pub struct XkbKeymap {
+    keycodes: HashMap<String, u32>,
+    keys: HashMap<u32, KeyEntry>,
+    types: HashMap<String, KeyType>,
+    group_names: Vec<String>,
+    modifier_map: HashMap<u32, ModMask>,
+}
+
+impl XkbKeymap {
+    /// xkb_keymap 形式のテキストからキーマップをコンパイル
+    ///
+    /// xkb_keycodes / xkb_types / xkb_symbols を解釈します。
+    /// xkb_compatibility と xkb_geometry は読み飛ばし、モディファイアの動作は
+    /// キーシンボルと modifier_map から標準的な規則で導出します。
+    pub fn parse(text: &str) -> Result<Self, KeymapError> {
+        let mut builder = KeymapBuilder::default();
+
+        // 標準キータイプを先に読み込み、キーマップ側の定義で上書きできるようにする
+        Parser::new(BUILTIN_TYPES)?.parse_file(&mut builder)?;
+        Parser::new(text)?.parse_file(&mut builder)?;
+
+        builder.build()
+    }
+
+    /// キーマップファイルを読み込んでコンパイル
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, KeymapError> {
+        let text = std::fs::read_to_string(path.as_ref())
+            .map_err(|e| KeymapError::Io(format!("{}: {}", path.as_ref().display(), e)))?;
+        Self::parse(&text)
+    }
+
+    /// 組み込みレイアウト（"us", "de", "jp"）からキーマップを作成
+    pub fn builtin(layout: &str) -> Result<Self, KeymapError> {
+        let text = builtin_keymap_text(layout)
+            .ok_or_else(|| KeymapError::UnknownLayout(layout.to_string()))?;
+        Self::parse(&text)
+    }
+
+    /// グループ（レイアウト）数
+    pub fn group_count(&self) -> usize {
+        self.group_names.len()
+    }
+
+    /// グループ名
+    pub fn group_name(&self, group: usize) -> Option<&str> {
+        self.group_names.get(group).map(|s| s.as_str())
+    }
+
+    /// キー名（例: "AC01"）からキーコードを取得
+    pub fn keycode(&self, name: &str) -> Option<u32> {
+        self.keycodes.get(name).copied()
+    }
+
+    /// キーコードに対応するキー名を取得
+    pub fn key_name(&self, keycode: u32) -> Option<&str> {
+        self.keys.get(&keycode).map(|entry| entry.name.as_str())
+    }
+
+    /// キータイプを名前で取得
+    pub fn key_type(&self, name: &str) -> Option<&KeyType> {
+        self.types.get(name)
+    }
+
+    /// 指定グループ・モディファイア状態でのキーシンボルとレベルを解決
+    fn resolve(&self, keycode: u32, group: usize, mods: ModMask) -> Option<(String, usize, usize)> {
+        let entry = self.keys.get(&keycode)?;
+        if entry.groups.is_empty() {
+            return None;
+        }
+
+        // 定義されていないグループはXKBと同様に折り返す
+        let group = group % entry.groups.len();
+        let key_group = &entry.groups[group];
+        let level = match self.group_type(key_group) {
+            Some(key_type) => key_type.level(mods),
+            None => 0,
+        };
+
+        key_group
+            .levels
+            .get(level)
+            .and_then(|sym| sym.clone())
+            .map(|sym| (sym, level, group))
+    }
+
+    /// キーシンボルを取得（状態は変更しない）
+    pub fn keysym(&self, keycode: u32, group: usize, mods: ModMask) -> Option<KeySym> {
+        self.resolve(keycode, group, mods).map(|(sym, _, _)| KeySym(sym))
+    }
+
+    /// クライアントに渡す完全な xkb_keymap テキストを生成
+    ///
+    /// キーコードはシンボルが定義されたキーのものだけを書き出し、キータイプは
+    /// 実モディファイアで表します。モディファイアの動作は組み込みの xkb_compatibility を使用します。
+    pub fn to_text(&self) -> String {
+        let mut keys: Vec<(u32, &KeyEntry)> = self.keys.iter().map(|(code, entry)| (*code, entry)).collect();
+        keys.sort_by_key(|(code, _)| *code);
+        let mut types: Vec<&KeyType> = self.types.values().collect();
+        types.sort_by(|a, b| a.name.cmp(&b.name));
+
+        // String への書き込みは失敗しない
+        let mut text = String::from("xkb_keymap {\nxkb_keycodes \"lumos\" {\n    minimum = 8;\n    maximum = 255;\n");
+        for (code, entry) in &keys {
+            let _ = writeln!(text, "    <{}> = {};", entry.name, code);
+        }
+        text.push_str("};\n\nxkb_types \"lumos\" {\n    virtual_modifiers NumLock,Alt,LevelThree,Super;\n");
+        for key_type in types {
+            let _ = writeln!(text, "    type \"{}\" {{", key_type.name);
+            let _ = writeln!(text, "        modifiers = {};", modifier_mask_text(key_type.modifiers));
+            for (mask, level) in &key_type.map {
+                let _ = writeln!(text, "        map[{}] = Level{};", modifier_mask_text(*mask), level + 1);
+            }
+            text.push_str("    };\n");
+        }
+        text.push_str("};\n");
+        text.push_str(BUILTIN_COMPAT);
+
+        text.push_str("\nxkb_symbols \"lumos\" {\n");
+        for (index, name) in self.group_names.iter().enumerate() {
+            // libxkbcommon の文字列は \" のエスケープに対応していないため、引用符は置き換える
+            let name = name.replace('\\', "\\\\").replace('"', "'");
+            let _ = writeln!(text, "    name[Group{}] = \"{}\";", index + 1, name);
+        }
+        for (_, entry) in &keys {
+            let groups: Vec<String> = entry
+                .groups
+                .iter()
+                .enumerate()
+                .filter(|(_, group)| !group.levels.is_empty())
+                .map(|(index, group)| {
+                    let symbols: Vec<&str> = group
+                        .levels
+                        .iter()
+                        .map(|sym| sym.as_deref().unwrap_or("NoSymbol"))
+                        .collect();
+                    let symbols = format!("symbols[Group{}] = [ {} ]", index + 1, symbols.join(", "));
+                    match self.group_type(group) {
+                        Some(key_type) => format!("type[Group{}] = \"{}\", {}", index + 1, key_type.name, symbols),
+                        None => symbols,
+                    }
+                })
+                .collect();
+            if !groups.is_empty() {
+                let _ = writeln!(text, "    key <{}> {{ {} }};", entry.name, groups.join(", "));
+            }
+        }
+
+        let mut modifier_map: Vec<(u32, ModMask)> = self.modifier_map.iter().map(|(code, mask)| (*code, *mask)).collect();
+        modifier_map.sort_unstable();
+        for (code, mask) in modifier_map {
+            let Some(entry) = self.keys.get(&code) else { continue };
+            for bit in 0..8 {
+                if mask & (1 << bit) != 0 {
+                    let _ = writeln!(text, "    modifier_map {} {{ <{}> }};", modifier_mask_text(1 << bit), entry.name);
+                }
+            }
+        }
+        text.push_str("};\n};\n");
+        text
+    }
+
+    /// グループに適用されるキータイプ
+    fn group_type(&self, group: &KeyGroup) -> Option<&KeyType> {
+        let name = match &group.type_name {
+            Some(name) => name.as_str(),
+            None => automatic_type_name(&group.levels),
+        };
+        self.types.get(name)
+    }
+
+    /// キーの動作を決定
+    fn key_action(&self, keycode: u32, keysym: &str) -> Option<KeyAction> {
+        match keysym {
+            "ISO_Next_Group" => return Some(KeyAction::NextGroup),
+            "ISO_Prev_Group" => return Some(KeyAction::PrevGroup),
+            _ => {}
+        }
+
+        let mask = match self.modifier_map.get(&keycode) {
+            Some(mask) => *mask,
+            None => default_modifier_for_keysym(keysym)?,
+        };
+
+        match keysym {
+            "Caps_Lock" | "Shift_Lock" | "Num_Lock" => Some(KeyAction::LockMods(mask)),
+            _ => Some(KeyAction::SetMods(mask)),
+        }
+    }
+}
+
+/// キーボード状態 - 押下中・ロック中のモディファイアとデッドキーを追跡
+#[derive(Debug, Clone, Default)]
+pub struct XkbState {
+    depressed: HashMap<u32, ModMask>,
+    locked: ModMask,
+    pending_dead: Option<String>,
+}
+
+/// キー変換の結果
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyTranslation {
+    /// XKBキーコード
+    pub keycode: u32,
+    /// 解決されたキーシンボル
+    pub key_sym: KeySym,
+    /// 入力された文字列（デッドキーの合成結果を含む）
+    pub text: Option<String>,
+    /// シフトレベル（0始まり）
+    pub level: usize,
+    /// 使用したグループ
+    pub group: usize,
+    /// 押下イベントかどうか
+    pub pressed: bool,
+    /// デッドキーかどうか
+    pub dead: bool,
+}
+
+impl XkbState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 押下中とロック中を合わせた有効モディファイア
+    pub fn effective_mods(&self) -> ModMask {
+        self.depressed.values().fold(self.locked, |acc, mask| acc | mask)
+    }
+
+    /// ロック中のモディファイア
+    pub fn locked_mods(&self) -> ModMask {
+        self.locked
+    }
+
+    /// キーイベントを処理して状態を更新し、変換結果を返す
+    pub fn process_key(
+        &mut self,
+        keymap: &XkbKeymap,
+        group: usize,
+        keycode: u32,
+        pressed: bool,
+    ) -> Option<KeyTranslation> {
+        // モディファイアキー自身の押下は変換結果に影響させない
+        let mods = self.effective_mods();
+
+        if !pressed {
+            self.depressed.remove(&keycode);
+        }
+
+        let (sym, level, group) = keymap.resolve(keycode, group, mods)?;
+        let mut text = None;
+        let mut dead = false;
+
+        if pressed {
+            match keymap.key_action(keycode, &sym) {
+                Some(KeyAction::SetMods(mask)) => {
+                    self.depressed.insert(keycode, mask);
+                }
+                Some(KeyAction::LockMods(mask)) => {
+                    self.locked ^= mask;
+                }
+                Some(KeyAction::NextGroup) | Some(KeyAction::PrevGroup) | None => {}
+            }
+
+            if dead_key_accent(&sym).is_some() {
+                dead = true;
+                self.pending_dead = Some(sym.clone());
+            } else if let Some(ch) = keysym_to_char(&sym) {
+                text = Some(match self.pending_dead.take() {
+                    Some(dead_sym) => compose_dead_key(&dead_sym, ch),
+                    None => ch.to_string(),
+                });
+            }
+        }
+
+        Some(KeyTranslation {
+            keycode,
+            key_sym: KeySym(sym),
+            text,
+            level,
+            group,
+            pressed,
+            dead,
+        })
+    }
+
+    /// 現在の状態を `KeyModifier` の集合に変換
+    pub fn modifiers(&self) -> HashSet<KeyModifier> {
+        let mods = self.effective_mods();
+        let mut result = HashSet::new();
+
+        if mods & MOD_SHIFT != 0 {
+            result.insert(KeyModifier::Shift);
+        }
+        if mods & MOD_CONTROL != 0 {
+            result.insert(KeyModifier::Ctrl);
+        }
+        if mods & MOD_ALT != 0 {
+            result.insert(KeyModifier::Alt);
+        }
+        if mods & MOD_SUPER != 0 {
+            result.insert(KeyModifier::Super);
+        }
+        if mods & MOD_LEVEL_THREE != 0 {
+            result.insert(KeyModifier::LevelThree);
+        }
+        if self.locked & MOD_LOCK != 0 {
+            result.insert(KeyModifier::CapsLock);
+        }
+        if self.locked & MOD_NUM_LOCK != 0 {
+            result.insert(KeyModifier::NumLock);
+        }
+
+        result
+    }
+
+    /// Caps Lock が有効か
+    pub fn caps_lock_active(&self) -> bool {
+        self.locked & MOD_LOCK != 0
+    }
+
+    /// Num Lock が有効か
+    pub fn num_lock_active(&self) -> bool {
+        self.locked & MOD_NUM_LOCK != 0
+    }
+
+    /// ロックモディファイアを直接設定
+    pub fn set_locked(&mut self, mask: ModMask, enabled: bool) {
+        if enabled {
+            self.locked |= mask;
+        } else {
+            self.locked &= !mask;
+        }
+    }
+
+    /// 押下中のキーと未確定のデッドキーをクリア（ロック状態は保持）
+    pub fn release_all(&mut self) {
+        self.depressed.clear();
+        self.pending_dead = None;
+    }
+}
+
+/// 設定済みのキーボードレイアウト
+#[derive(Debug, Clone)]
+pub struct KeyboardLayout {
+    pub name: String,
+    keymap: Arc<XkbKeymap>,
+    group: usize,
+}
+
+impl KeyboardLayout {
+    pub fn keymap(&self) -> &XkbKeymap {
+        &self.keymap
+    }
+
+    pub fn group(&self) -> usize {
+        self.group
+    }
+}
+
+/// レイアウトの記憶範囲
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LayoutScope {
+    /// 全ウィンドウで共通のレイアウト
+    Global,
+    /// ウィンドウごとに最後に使ったレイアウトを記憶
+    PerWindow,
+}
+
+/// レイアウト変更リスナー
+pub type LayoutListener = Box<dyn Fn(&KeyboardLayout) + Send + Sync>;
+
+/// キーボードレイアウトマネージャー - 複数レイアウトの切り替えとウィンドウごとの記憶を担当
+pub struct KeyboardLayoutManager {
+    layouts: Vec<KeyboardLayout>,
+    active: usize,
+    default_layout: usize,
+    scope: LayoutScope,
+    state: XkbState,
+    focused: Option<NodeId>,
+    window_layouts: HashMap<NodeId, usize>,
+    listeners: Vec<LayoutListener>,
+}
+
+impl KeyboardLayoutManager {
+    /// USレイアウトのみを持つマネージャーを作成
+    pub fn new() -> Self {
+        let mut manager = Self::empty();
+        // 組み込みキーマップはテストで検証済みのため失敗しない
+        if let Ok(keymap) = XkbKeymap::builtin("us") {
+            manager.add_keymap(keymap);
+        }
+        manager
+    }
+
+    fn empty() -> Self {
+        Self {
+            layouts: Vec::new(),
+            active: 0,
+            default_layout: 0,
+            scope: LayoutScope::PerWindow,
+            state: XkbState::new(),
+            focused: None,
+            window_layouts: HashMap::new(),
+            listeners: Vec::new(),
+        }
+    }
+
+    /// 組み込みレイアウト名のリストからマネージャーを作成
+    pub fn with_layouts(names: &[&str]) -> Result<Self, KeymapError> {
+        let mut manager = Self::empty();
+        for name in names {
+            manager.add_keymap(XkbKeymap::builtin(name)?);
+        }
+
+        if manager.layouts.is_empty() {
+            return Err(KeymapError::EmptyKeymap);
+        }
+
+        Ok(manager)
+    }
+
+    /// キーマップを追加（キーマップ内の各グループが個別のレイアウトになる）
+    pub fn add_keymap(&mut self, keymap: XkbKeymap) {
+        let keymap = Arc::new(keymap);
+        for group in 0..keymap.group_count() {
+            let name = keymap
+                .group_name(group)
+                .map(|s| s.to_string())
+                .unwrap_or_else(|| format!("Group{}", group + 1));
+
+            self.layouts.push(KeyboardLayout {
+                name,
+                keymap: Arc::clone(&keymap),
+                group,
+            });
+        }
+    }
+
+    /// 登録済みレイアウトの一覧
+    pub fn layouts(&self) -> &[KeyboardLayout] {
+        &self.layouts
+    }
+
+    /// 現在のレイアウト
+    pub fn active_layout(&self) -> Option<&KeyboardLayout> {
+        self.layouts.get(self.active)
+    }
+
+    /// 現在のレイアウトのインデックス
+    pub fn active_index(&self) -> usize {
+        self.active
+    }
+
+    /// レイアウトを切り替え
+    pub fn set_active_layout(&mut self, index: usize) -> Result<(), KeymapError> {
+        if index >= self.layouts.len() {
+            return Err(KeymapError::InvalidLayoutIndex(index));
+        }
+
+        if self.active != index {
+            self.active = index;
+            // 別レイアウトで押されたキーのデッドキー状態は持ち越さない
+            self.state.pending_dead = None;
+            self.notify_layout_changed();
+        }
+
+        if self.scope == LayoutScope::PerWindow {
+            if let Some(focused) = self.focused {
+                self.window_layouts.insert(focused, index);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 次のレイアウトへ切り替え
+    pub fn next_layout(&mut self) {
+        if !self.layouts.is_empty() {
+            let next = (self.active + 1) % self.layouts.len();
+            let _ = self.set_active_layout(next);
+        }
+    }
+
+    /// 前のレイアウトへ切り替え
+    pub fn previous_layout(&mut self) {
+        if !self.layouts.is_empty() {
+            let len = self.layouts.len();
+            let prev = (self.active + len - 1) % len;
+            let _ = self.set_active_layout(prev);
+        }
+    }
+
+    /// 新しいウィンドウに適用するレイアウトを設定
+    pub fn set_default_layout(&mut self, index: usize) -> Result<(), KeymapError> {
+        if index >= self.layouts.len() {
+            return Err(KeymapError::InvalidLayoutIndex(index));
+        }
+        self.default_layout = index;
+        Ok(())
+    }
+
+    /// レイアウトの記憶範囲を設定
+    pub fn set_scope(&mut self, scope: LayoutScope) {
+        self.scope = scope;
+        if scope == LayoutScope::Global {
+            self.window_layouts.clear();
+        }
+    }
+
+    pub fn scope(&self) -> LayoutScope {
+        self.scope
+    }
+
+    /// キーボードフォーカスの変更を通知
+    pub fn focus_changed(&mut self, node_id: Option<NodeId>) {
+        if self.focused == node_id {
+            return;
+        }
+
+        // フォーカス移動時に押下状態が残らないようにする
+        self.state.release_all();
+        self.focused = node_id;
+
+        if self.scope != LayoutScope::PerWindow {
+            return;
+        }
+
+        if let Some(node_id) = node_id {
+            let index = self
+                .window_layouts
+                .get(&node_id)
+                .copied()
+                .filter(|index| *index < self.layouts.len())
+                .unwrap_or(self.default_layout);
+            let _ = self.set_active_layout(index);
+        }
+    }
+
+    /// 破棄されたウィンドウのレイアウト記憶を削除
+    pub fn forget_window(&mut self, node_id: NodeId) {
+        self.window_layouts.remove(&node_id);
+        if self.focused == Some(node_id) {
+            self.focused = None;
+        }
+    }
+
+    /// キーイベントを現在のレイアウトで変換
+    pub fn process_key(&mut self, keycode: u32, pressed: bool) -> Option<KeyTranslation> {
+        let layout = self.layouts.get(self.active)?.clone();
+        let translation = self.state.process_key(&layout.keymap, layout.group, keycode, pressed)?;
+
+        if pressed {
+            match layout.keymap.key_action(keycode, &translation.key_sym.0) {
+                Some(KeyAction::NextGroup) => self.next_layout(),
+                Some(KeyAction::PrevGroup) => self.previous_layout(),
+                _ => {}
+            }
+        }
+
+        Some(translation)
+    }
+
+    /// 現在の状態でキーシンボルを参照（状態は変更しない）
+    pub fn lookup_keysym(&self, keycode: u32) -> Option<KeySym> {
+        let layout = self.layouts.get(self.active)?;
+        layout.keymap.keysym(keycode, layout.group, self.state.effective_mods())
+    }
+
+    /// 指定したモディファイアの組み合わせでキーシンボルを参照（状態は変更しない）
+    ///
+    /// 固定キーでラッチされたモディファイアのように、キーボード状態の外で
+    /// 付加されたモディファイアを反映してキーシンボルを解決し直すときに使用します。
+    pub fn lookup_keysym_with(&self, keycode: u32, modifiers: &HashSet<KeyModifier>) -> Option<KeySym> {
+        let layout = self.layouts.get(self.active)?;
+        layout.keymap.keysym(keycode, layout.group, modifier_set_mask(modifiers))
+    }
+
+    /// 現在のモディファイア
+    pub fn modifiers(&self) -> HashSet<KeyModifier> {
+        self.state.modifiers()
+    }
+
+    pub fn caps_lock_active(&self) -> bool {
+        self.state.caps_lock_active()
+    }
+
+    pub fn num_lock_active(&self) -> bool {
+        self.state.num_lock_active()
+    }
+
+    /// Num Lock の状態を設定（起動時の初期状態など）
+    pub fn set_num_lock(&mut self, enabled: bool) {
+        self.state.set_locked(MOD_NUM_LOCK, enabled);
+    }
+
+    /// キーボード状態への参照
+    pub fn state(&self) -> &XkbState {
+        &self.state
+    }
+
+    /// レイアウト変更リスナーを登録
+    pub fn add_layout_listener<F>(&mut self, listener: F)
+    where
+        F: Fn(&KeyboardLayout) + Send + Sync + 'static,
+    {
+        self.listeners.push(Box::new(listener));
+    }
+
+    fn notify_layout_changed(&self) {
+        if let Some(layout) = self.layouts.get(self.active) {
+            for listener in &self.listeners {
+                listener(layout);
+            }
+        }
+    }
+}
+
+impl Default for KeyboardLayoutManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// ---------------------------------------------------------------------------
+// キーマップの字句解析・構文解析
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    KeyName(String),
+    Number(i64),
+    Punct(char),
+}
+
+#[derive(Debug, Clone)]
+struct Spanned {
+    token: Token,
+    line: usize,
+    column: usize,
+}
+
+fn tokenize(text: &str) -> Result<Vec<Spanned>, KeymapError> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    let mut line = 1;
+    let mut column = 1;
+
+    let error = |line, column, message: &str| KeymapError::Parse {
+        line,
+        column,
+        message: message.to_string(),
+    };
+
+    while i < chars.len() {
+        let c = chars[i];
+        let (start_line, start_column) = (line, column);
+
+        // 改行と空白
+        if c == '\n' {
+            i += 1;
+            line += 1;
+            column = 1;
+            continue;
+        }
+        if c.is_whitespace() {
+            i += 1;
+            column += 1;
+            continue;
+        }
+
+        // コメント
+        if c == '#' || (c == '/' && chars.get(i + 1) == Some(&'/')) {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+            continue;
+        }
+        if c == '/' && chars.get(i + 1) == Some(&'*') {
+            i += 2;
+            column += 2;
+            loop {
+                if i >= chars.len() {
+                    return Err(error(start_line, start_column, "閉じられていないコメント"));
+                }
+                if chars[i] == '*' && chars.get(i + 1) == Some(&'/') {
+                    i += 2;
+                    column += 2;
+                    break;
+                }
+                if chars[i] == '\n' {
+                    line += 1;
+                    column = 1;
+                } else {
+                    column += 1;
+                }
+                i += 1;
+            }
+            continue;
+        }
+
+        let token = if c == '"' {
+            let mut value = String::new();
+            i += 1;
+            column += 1;
+            loop {
+                match chars.get(i) {
+                    None | Some('\n') => {
+                        return Err(error(start_line, start_column, "閉じられていない文字列"));
+                    }
+                    Some('"') => {
+                        i += 1;
+                        column += 1;
+                        break;
+                    }
+                    Some('\\') if i + 1 < chars.len() => {
+                        value.push(chars[i + 1]);
+                        i += 2;
+                        column += 2;
+                    }
+                    Some(ch) => {
+                        value.push(*ch);
+                        i += 1;
+                        column += 1;
+                    }
+                }
+            }
+            Token::Str(value)
+        } else if c == '<' {
+            let mut name = String::new();
+            i += 1;
+            column += 1;
+            loop {
+                match chars.get(i) {
+                    Some('>') => {
+                        i += 1;
+                        column += 1;
+                        break;
+                    }
+                    Some(ch) if !ch.is_whitespace() => {
+                        name.push(*ch);
+                        i += 1;
+                        column += 1;
+                    }
+                    _ => return Err(error(start_line, start_column, "不正なキー名")),
+                }
+            }
+            Token::KeyName(name)
+        } else if c.is_ascii_digit() {
+            let start = i;
+            while i < chars.len() && chars[i].is_ascii_alphanumeric() {
+                i += 1;
+            }
+            column += i - start;
+            let literal: String = chars[start..i].iter().collect();
+            let value = if let Some(hex) = literal.strip_prefix("0x").or_else(|| literal.strip_prefix("0X")) {
+                i64::from_str_radix(hex, 16)
+            } else {
+                literal.parse::<i64>()
+            };
+            match value {
+                Ok(value) => Token::Number(value),
+                Err(_) => return Err(error(start_line, start_column, "不正な数値")),
+            }
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            column += i - start;
+            Token::Ident(chars[start..i].iter().collect())
+        } else if "{}[]();,=+-!.|".contains(c) {
+            i += 1;
+            column += 1;
+            Token::Punct(c)
+        } else {
+            return Err(error(start_line, start_column, &format!("予期しない文字 '{}'", c)));
+        };
+
+        tokens.push(Spanned {
+            token,
+            line: start_line,
+            column: start_column,
+        });
+    }
+
+    Ok(tokens)
+}
+
+/// modifier_map のエントリ
+#[derive(Debug, Clone)]
+enum ModMapEntry {
+    Key(String),
+    Sym(String),
+}
+
+/// 構文解析結果を集約してキーマップを組み立てる
+#[derive(Debug, Default)]
+struct KeymapBuilder {
+    keycodes: HashMap<String, u32>,
+    aliases: Vec<(String, String)>,
+    types: HashMap<String, KeyType>,
+    keys: Vec<(String, Vec<KeyGroup>)>,
+    group_names: BTreeMap<usize, String>,
+    modifier_map: Vec<(ModMask, ModMapEntry)>,
+}
+
+impl KeymapBuilder {
+    fn build(self) -> Result<XkbKeymap, KeymapError> {
+        let mut keycodes = self.keycodes;
+        for (alias, target) in &self.aliases {
+            if let Some(code) = keycodes.get(target).copied() {
+                keycodes.insert(alias.clone(), code);
+            }
+        }
+
+        // 同じキーが複数回定義された場合はグループ単位で後の定義を優先
+        let mut keys: HashMap<u32, KeyEntry> = HashMap::new();
+        for (name, groups) in self.keys {
+            let code = match keycodes.get(&name) {
+                Some(code) => *code,
+                None => continue,
+            };
+            let entry = keys.entry(code).or_insert_with(|| KeyEntry {
+                name: name.clone(),
+                groups: Vec::new(),
+            });
+            for (index, group) in groups.into_iter().enumerate() {
+                if group.levels.is_empty() && group.type_name.is_none() {
+                    continue;
+                }
+                if entry.groups.len() <= index {
+                    entry.groups.resize(index + 1, KeyGroup::default());
+                }
+                entry.groups[index] = group;
+            }
+        }
+
+        let mut modifier_map: HashMap<u32, ModMask> = HashMap::new();
+        for (mask, entry) in &self.modifier_map {
+            match entry {
+                ModMapEntry::Key(name) => {
+                    if let Some(code) = keycodes.get(name) {
+                        *modifier_map.entry(*code).or_insert(0) |= mask;
+                    }
+                }
+                ModMapEntry::Sym(sym) => {
+                    for (code, key) in &keys {
+                        let has_sym = key.groups.iter().any(|group| {
+                            group.levels.first().and_then(|s| s.as_ref()) == Some(sym)
+                        });
+                        if has_sym {
+                            *modifier_map.entry(*code).or_insert(0) |= mask;
+                        }
+                    }
+                }
+            }
+        }
+
+        let group_count = keys
+            .values()
+            .map(|key| key.groups.len())
+            .chain(self.group_names.keys().map(|index| index + 1))
+            .max()
+            .unwrap_or(0);
+
+        if group_count == 0 {
+            return Err(KeymapError::EmptyKeymap);
+        }
+
+        let group_names = (0..group_count)
+            .map(|index| {
+                self.group_names
+                    .get(&index)
+                    .cloned()
+                    .unwrap_or_else(|| format!("Group{}", index + 1))
+            })
+            .collect();
+
+        Ok(XkbKeymap {
+            keycodes,
+            keys,
+            types: self.types,
+            group_names,
+            modifier_map,
+        })
+    }
+}
+
+struct Parser {
+    tokens: Vec<Spanned>,
+    pos: usize,
+}
+
+const SECTION_FLAGS: &[&str] = &[
+    "default", "partial", "hidden", "alphanumeric_keys", "modifier_keys",
+    "keypad_keys", "function_keys", "alternate_group",
+];
+
+impl Parser {
+    fn new(text: &str) -> Result<Self, KeymapError> {
+        Ok(Self {
+            tokens: tokenize(text)?,
+            pos: 0,
+        })
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|s| &s.token)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).map(|s| s.token.clone());
+        if token.is_some() {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn error(&self, message: &str) -> KeymapError {
+        let (line, column) = self
+            .tokens
+            .get(self.pos)
+            .or_else(|| self.tokens.last())
+            .map(|s| (s.line, s.column))
+            .unwrap_or((1, 1));
+        KeymapError::Parse {
+            line,
+            column,
+            message: message.to_string(),
+        }
+    }
+
+    fn is_punct(&self, c: char) -> bool {
+        self.peek() == Some(&Token::Punct(c))
+    }
+
+    fn eat_punct(&mut self, c: char) -> bool {
+        if self.is_punct(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect_punct(&mut self, c: char) -> Result<(), KeymapError> {
+        if self.eat_punct(c) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("'{}' が必要です", c)))
+        }
+    }
+
+    fn expect_ident(&mut self) -> Result<String, KeymapError> {
+        match self.peek() {
+            Some(Token::Ident(_)) => match self.next() {
+                Some(Token::Ident(name)) => Ok(name),
+                _ => unreachable!(),
+            },
+            _ => Err(self.error("識別子が必要です")),
+        }
+    }
+
+    fn expect_string(&mut self) -> Result<String, KeymapError> {
+        match self.peek() {
+            Some(Token::Str(_)) => match self.next() {
+                Some(Token::Str(value)) => Ok(value),
+                _ => unreachable!(),
+            },
+            _ => Err(self.error("文字列が必要です")),
+        }
+    }
+
+    fn expect_key_name(&mut self) -> Result<String, KeymapError> {
+        match self.peek() {
+            Some(Token::KeyName(_)) => match self.next() {
+                Some(Token::KeyName(name)) => Ok(name),
+                _ => unreachable!(),
+            },
+            _ => Err(self.error("キー名 <...> が必要です")),
+        }
+    }
+
+    fn expect_number(&mut self) -> Result<i64, KeymapError> {
+        match self.peek() {
+            Some(Token::Number(value)) => {
+                let value = *value;
+                self.pos += 1;
+                Ok(value)
+            }
+            _ => Err(self.error("数値が必要です")),
+        }
+    }
+
+    /// ファイル全体（xkb_keymap ブロックまたは個別セクションの並び）を解析
+    fn parse_file(&mut self, builder: &mut KeymapBuilder) -> Result<(), KeymapError> {
+        while let Some(token) = self.peek().cloned() {
+            match token {
+                Token::Punct(';') => {
+                    self.pos += 1;
+                }
+                Token::Ident(word) if SECTION_FLAGS.contains(&word.as_str()) => {
+                    self.pos += 1;
+                }
+                Token::Ident(word) if word == "xkb_keymap" => {
+                    self.pos += 1;
+                    if let Some(Token::Str(_)) = self.peek() {
+                        self.pos += 1;
+                    }
+                    self.expect_punct('{')?;
+                    while !self.is_punct('}') {
+                        if self.peek().is_none() {
+                            return Err(self.error("xkb_keymap が閉じられていません"));
+                        }
+                        if self.eat_punct(';') {
+                            continue;
+                        }
+                        self.parse_section(builder)?;
+                    }
+                    self.expect_punct('}')?;
+                    self.eat_punct(';');
+                }
+                Token::Ident(_) => self.parse_section(builder)?,
+                _ => return Err(self.error("セクションが必要です")),
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_section(&mut self, builder: &mut KeymapBuilder) -> Result<(), KeymapError> {
+        let mut keyword = self.expect_ident()?;
+        while SECTION_FLAGS.contains(&keyword.as_str()) {
+            keyword = self.expect_ident()?;
+        }
+
+        if let Some(Token::Str(_)) = self.peek() {
+            self.pos += 1;
+        }
+
+        match keyword.as_str() {
+            "xkb_keycodes" => self.parse_block(builder, Self::parse_keycodes_statement),
+            "xkb_types" => self.parse_block(builder, Self::parse_types_statement),
+            "xkb_symbols" => self.parse_block(builder, Self::parse_symbols_statement),
+            "xkb_compatibility" | "xkb_compatibility_map" | "xkb_compat" | "xkb_geometry" => {
+                self.expect_punct('{')?;
+                self.skip_block_body()?;
+                self.eat_punct(';');
+                Ok(())
+            }
+            other => Err(self.error(&format!("不明なセクション: {}", other))),
+        }
+    }
+
+    fn parse_block(
+        &mut self,
+        builder: &mut KeymapBuilder,
+        statement: fn(&mut Self, &mut KeymapBuilder) -> Result<(), KeymapError>,
+    ) -> Result<(), KeymapError> {
+        self.expect_punct('{')?;
+        loop {
+            if self.eat_punct('}') {
+                break;
+            }
+            if self.peek().is_none() {
+                return Err(self.error("セクションが閉じられていません"));
+            }
+            if self.eat_punct(';') {
+                continue;
+            }
+            statement(self, builder)?;
+        }
+        self.eat_punct(';');
+        Ok(())
+    }
+
+    /// 開き括弧の直後から対応する閉じ括弧までを読み飛ばす
+    fn skip_block_body(&mut self) -> Result<(), KeymapError> {
+        let mut depth = 1;
+        while depth > 0 {
+            match self.next() {
+                Some(Token::Punct('{')) => depth += 1,
+                Some(Token::Punct('}')) => depth -= 1,
+                Some(_) => {}
+                None => return Err(self.error("ブロックが閉じられていません")),
+            }
+        }
+        Ok(())
+    }
+
+    /// 未対応の文を ';' まで読み飛ばす
+    fn skip_statement(&mut self) -> Result<(), KeymapError> {
+        let mut depth = 0;
+        loop {
+            match self.peek() {
+                None => return Err(self.error("';' が必要です")),
+                Some(Token::Punct('}')) if depth == 0 => return Ok(()),
+                _ => {}
+            }
+            match self.next() {
+                Some(Token::Punct('{')) | Some(Token::Punct('[')) | Some(Token::Punct('(')) => depth += 1,
+                Some(Token::Punct('}')) | Some(Token::Punct(']')) | Some(Token::Punct(')')) => depth -= 1,
+                Some(Token::Punct(';')) if depth == 0 => return Ok(()),
+                _ => {}
+            }
+        }
+    }
+
+    /// ',' または '}' まで値を読み飛ばす
+    fn skip_value(&mut self) -> Result<(), KeymapError> {
+        let mut depth = 0;
+        loop {
+            match self.peek() {
+                None => return Err(self.error("値が閉じられていません")),
+                Some(Token::Punct(',')) | Some(Token::Punct('}')) if depth == 0 => return Ok(()),
+                _ => {}
+            }
+            match self.next() {
+                Some(Token::Punct('[')) | Some(Token::Punct('(')) => depth += 1,
+                Some(Token::Punct(']')) | Some(Token::Punct(')')) => depth -= 1,
+                _ => {}
+            }
+        }
+    }
+
+    fn parse_keycodes_statement(&mut self, builder: &mut KeymapBuilder) -> Result<(), KeymapError> {
+        match self.peek().cloned() {
+            Some(Token::KeyName(name)) => {
+                self.pos += 1;
+                self.expect_punct('=')?;
+                let code = self.expect_number()?;
+                if !(0..=0xffff).contains(&code) {
+                    return Err(self.error("キーコードが範囲外です"));
+                }
+                builder.keycodes.insert(name, code as u32);
+                self.expect_punct(';')
+            }
+            Some(Token::Ident(word)) if word == "alias" => {
+                self.pos += 1;
+                let alias = self.expect_key_name()?;
+                self.expect_punct('=')?;
+                let target = self.expect_key_name()?;
+                builder.aliases.push((alias, target));
+                self.expect_punct(';')
+            }
+            _ => self.skip_statement(),
+        }
+    }
+
+    fn parse_types_statement(&mut self, builder: &mut KeymapBuilder) -> Result<(), KeymapError> {
+        match self.peek().cloned() {
+            Some(Token::Ident(word)) if word == "type" => {
+                self.pos += 1;
+                let name = self.expect_string()?;
+                self.expect_punct('{')?;
+
+                let mut key_type = KeyType {
+                    name: name.clone(),
+                    modifiers: 0,
+                    map: Vec::new(),
+                };
+
+                loop {
+                    if self.eat_punct('}') {
+                        break;
+                    }
+                    if self.peek().is_none() {
+                        return Err(self.error("type ブロックが閉じられていません"));
+                    }
+
+                    let field = self.expect_ident()?.to_lowercase();
+                    match field.as_str() {
+                        "modifiers" => {
+                            self.expect_punct('=')?;
+                            key_type.modifiers = self.parse_mod_mask()?;
+                            self.expect_punct(';')?;
+                        }
+                        "map" => {
+                            self.expect_punct('[')?;
+                            let mask = self.parse_mod_mask()?;
+                            self.expect_punct(']')?;
+                            self.expect_punct('=')?;
+                            let level = self.parse_level()?;
+                            key_type.map.retain(|(m, _)| *m != mask);
+                            key_type.map.push((mask, level));
+                            self.expect_punct(';')?;
+                        }
+                        _ => self.skip_statement()?,
+                    }
+                }
+                self.eat_punct(';');
+
+                builder.types.insert(name, key_type);
+                Ok(())
+            }
+            _ => self.skip_statement(),
+        }
+    }
+
+    fn parse_symbols_statement(&mut self, builder: &mut KeymapBuilder) -> Result<(), KeymapError> {
+        let mut word = match self.peek().cloned() {
+            Some(Token::Ident(word)) => word,
+            _ => return self.skip_statement(),
+        };
+
+        // マージモード指定は読み飛ばす
+        if matches!(word.as_str(), "replace" | "override" | "augment") {
+            self.pos += 1;
+            word = match self.peek().cloned() {
+                Some(Token::Ident(word)) => word,
+                _ => return self.skip_statement(),
+            };
+        }
+
+        match word.as_str() {
+            "key" => {
+                self.pos += 1;
+                let name = self.expect_key_name()?;
+                let groups = self.parse_key_body()?;
+                builder.keys.push((name, groups));
+                self.expect_punct(';')
+            }
+            "name" => {
+                self.pos += 1;
+                self.expect_punct('[')?;
+                let group = self.parse_group()?;
+                self.expect_punct(']')?;
+                self.expect_punct('=')?;
+                let value = self.expect_string()?;
+                builder.group_names.insert(group, value);
+                self.expect_punct(';')
+            }
+            "modifier_map" => {
+                self.pos += 1;
+                let mask = self.parse_mod_mask()?;
+                self.expect_punct('{')?;
+                loop {
+                    match self.next() {
+                        Some(Token::KeyName(name)) => {
+                            builder.modifier_map.push((mask, ModMapEntry::Key(name)));
+                        }
+                        Some(Token::Ident(sym)) => {
+                            builder.modifier_map.push((mask, ModMapEntry::Sym(sym)));
+                        }
+                        Some(Token::Punct(',')) => {}
+                        Some(Token::Punct('}')) => break,
+                        _ => return Err(self.error("modifier_map の要素が不正です")),
+                    }
+                }
+                self.expect_punct(';')
+            }
+            _ => self.skip_statement(),
+        }
+    }
+
+    fn parse_key_body(&mut self) -> Result<Vec<KeyGroup>, KeymapError> {
+        self.expect_punct('{')?;
+
+        let mut groups: Vec<KeyGroup> = Vec::new();
+        let mut default_type: Option<String> = None;
+        let mut next_group = 0;
+
+        fn group_mut(groups: &mut Vec<KeyGroup>, index: usize) -> &mut KeyGroup {
+            if groups.len() <= index {
+                groups.resize(index + 1, KeyGroup::default());
+            }
+            &mut groups[index]
+        }
+
+        loop {
+            if self.eat_punct('}') {
+                break;
+            }
+
+            match self.peek().cloned() {
+                Some(Token::Punct('[')) => {
+                    let levels = self.parse_symbol_list()?;
+                    group_mut(&mut groups, next_group).levels = levels;
+                    next_group += 1;
+                }
+                Some(Token::Ident(field)) => {
+                    self.pos += 1;
+                    let index = if self.eat_punct('[') {
+                        let group = self.parse_group()?;
+                        self.expect_punct(']')?;
+                        Some(group)
+                    } else {
+                        None
+                    };
+                    self.expect_punct('=')?;
+
+                    match field.to_lowercase().as_str() {
+                        "type" => {
+                            let type_name = self.expect_string()?;
+                            match index {
+                                Some(group) => group_mut(&mut groups, group).type_name = Some(type_name),
+                                None => default_type = Some(type_name),
+                            }
+                        }
+                        "symbols" => {
+                            let group = index.unwrap_or(next_group);
+                            let levels = self.parse_symbol_list()?;
+                            group_mut(&mut groups, group).levels = levels;
+                            next_group = group + 1;
+                        }
+                        _ => self.skip_value()?,
+                    }
+                }
+                None => return Err(self.error("key ブロックが閉じられていません")),
+                _ => return Err(self.error("key ブロックの要素が不正です")),
+            }
+
+            if !self.eat_punct(',') && !self.is_punct('}') {
+                return Err(self.error("',' または '}' が必要です"));
+            }
+        }
+
+        if let Some(type_name) = default_type {
+            for group in groups.iter_mut() {
+                if group.type_name.is_none() {
+                    group.type_name = Some(type_name.clone());
+                }
+            }
+        }
+
+        Ok(groups)
+    }
+
+    fn parse_symbol_list(&mut self) -> Result<Vec<Option<String>>, KeymapError> {
+        self.expect_punct('[')?;
+        let mut levels = Vec::new();
+
+        loop {
+            match self.next() {
+                Some(Token::Ident(sym)) => {
+                    if sym == "NoSymbol" {
+                        levels.push(None);
+                    } else {
+                        levels.push(Some(sym));
+                    }
+                }
+                // 0〜9 は数字のキーシンボルとして扱う
+                Some(Token::Number(value)) if (0..=9).contains(&value) => {
+                    levels.push(Some(value.to_string()));
+                }
+                Some(Token::Number(value)) => {
+                    levels.push(Some(format!("0x{:x}", value)));
+                }
+                _ => return Err(self.error("キーシンボルが必要です")),
+            }
+
+            if self.eat_punct(']') {
+                break;
+            }
+            self.expect_punct(',')?;
+        }
+
+        Ok(levels)
+    }
+
+    fn parse_group(&mut self) -> Result<usize, KeymapError> {
+        match self.next() {
+            Some(Token::Ident(name)) => {
+                let lower = name.to_lowercase();
+                lower
+                    .strip_prefix("group")
+                    .and_then(|n| n.parse::<usize>().ok())
+                    .filter(|n| *n >= 1)
+                    .map(|n| n - 1)
+                    .ok_or_else(|| self.error(&format!("不正なグループ: {}", name)))
+            }
+            Some(Token::Number(n)) if n >= 1 => Ok(n as usize - 1),
+            _ => Err(self.error("グループが必要です")),
+        }
+    }
+
+    fn parse_level(&mut self) -> Result<usize, KeymapError> {
+        match self.next() {
+            Some(Token::Ident(name)) => {
+                let lower = name.to_lowercase();
+                lower
+                    .strip_prefix("level")
+                    .and_then(|n| n.parse::<usize>().ok())
+                    .filter(|n| *n >= 1)
+                    .map(|n| n - 1)
+                    .ok_or_else(|| self.error(&format!("不正なレベル: {}", name)))
+            }
+            Some(Token::Number(n)) if n >= 1 => Ok(n as usize - 1),
+            _ => Err(self.error("レベルが必要です")),
+        }
+    }
+
+    fn parse_mod_mask(&mut self) -> Result<ModMask, KeymapError> {
+        let mut mask = 0;
+        loop {
+            let name = self.expect_ident()?;
+            mask |= modifier_mask_from_name(&name)
+                .ok_or_else(|| self.error(&format!("不明なモディファイア: {}", name)))?;
+
+            if !self.eat_punct('+') && !self.eat_punct('|') {
+                break;
+            }
+        }
+        Ok(mask)
+    }
+}
+
+/// モディファイア名をビットマスクに変換
+fn modifier_mask_from_name(name: &str) -> Option<ModMask> {
+    match name.to_lowercase().as_str() {
+        "none" => Some(0),
+        "all" | "any" => Some(MOD_ALL),
+        "shift" => Some(MOD_SHIFT),
+        "lock" => Some(MOD_LOCK),
+        "control" | "ctrl" => Some(MOD_CONTROL),
+        "mod1" => Some(MOD_MOD1),
+        "mod2" => Some(MOD_MOD2),
+        "mod3" => Some(MOD_MOD3),
+        "mod4" => Some(MOD_MOD4),
+        "mod5" => Some(MOD_MOD5),
+        "alt" | "meta" => Some(MOD_ALT),
+        "numlock" => Some(MOD_NUM_LOCK),
+        "super" | "hyper" => Some(MOD_SUPER),
+        "levelthree" | "altgr" => Some(MOD_LEVEL_THREE),
+        "scrolllock" | "levelfive" => Some(0),
+        _ => None,
+    }
+}
+
+/// ビットマスクを実モディファイア名の組み合わせ（例: "Shift+Mod5"）に変換
+fn modifier_mask_text(mask: ModMask) -> String {
+    const NAMES: [&str; 8] = ["Shift", "Lock", "Control", "Mod1", "Mod2", "Mod3", "Mod4", "Mod5"];
+    let names: Vec<&str> = (0..NAMES.len())
+        .filter(|bit| mask & (1 << bit) != 0)
+        .map(|bit| NAMES[bit])
+        .collect();
+    if names.is_empty() {
+        "None".to_string()
+    } else {
+        names.join("+")
+    }
+}
+
+/// `KeyModifier` の集合をビットマスクに変換
+fn modifier_set_mask(modifiers: &HashSet<KeyModifier>) -> ModMask {
+    modifiers.iter().fold(0, |mask, modifier| {
+        mask | match modifier {
+            KeyModifier::Shift => MOD_SHIFT,
+            KeyModifier::Ctrl => MOD_CONTROL,
+            KeyModifier::Alt | KeyModifier::Meta => MOD_ALT,
+            KeyModifier::Super | KeyModifier::Hyper => MOD_SUPER,
+            KeyModifier::LevelThree => MOD_LEVEL_THREE,
+            KeyModifier::CapsLock => MOD_LOCK,
+            KeyModifier::NumLock => MOD_NUM_LOCK,
+        }
+    })
+}
+
+/// modifier_map がないキーに対する既定のモディファイア
+fn default_modifier_for_keysym(keysym: &str) -> Option<ModMask> {
+    match keysym {
+        "Shift_L" | "Shift_R" => Some(MOD_SHIFT),
+        "Caps_Lock" | "Shift_Lock" => Some(MOD_LOCK),
+        "Control_L" | "Control_R" => Some(MOD_CONTROL),
+        "Alt_L" | "Alt_R" | "Meta_L" | "Meta_R" => Some(MOD_ALT),
+        "Num_Lock" => Some(MOD_NUM_LOCK),
+        "Super_L" | "Super_R" | "Hyper_L" | "Hyper_R" => Some(MOD_SUPER),
+        "ISO_Level3_Shift" | "Mode_switch" => Some(MOD_LEVEL_THREE),
+        _ => None,
+    }
+}
+
+/// type 指定のないキーに適用するキータイプ名を決定
+fn automatic_type_name(levels: &[Option<String>]) -> &'static str {
+    let is_alpha_pair = |lower: Option<&Option<String>>, upper: Option<&Option<String>>| {
+        let lower = lower.and_then(|s| s.as_deref()).and_then(keysym_to_char);
+        let upper = upper.and_then(|s| s.as_deref()).and_then(keysym_to_char);
+        match (lower, upper) {
+            (Some(l), Some(u)) => l.is_lowercase() && l.to_uppercase().eq(std::iter::once(u)),
+            _ => false,
+        }
+    };
+    let is_keypad = levels
+        .iter()
+        .flatten()
+        .any(|sym| sym.starts_with("KP_"));
+
+    match levels.len() {
+        0 | 1 => "ONE_LEVEL",
+        2 => {
+            if is_alpha_pair(levels.first(), levels.get(1)) {
+                "ALPHABETIC"
+            } else if is_keypad {
+                "KEYPAD"
+            } else {
+                "TWO_LEVEL"
+            }
+        }
+        _ => {
+            if is_alpha_pair(levels.first(), levels.get(1)) {
+                if is_alpha_pair(levels.get(2), levels.get(3)) {
+                    "FOUR_LEVEL_ALPHABETIC"
+                } else {
+                    "FOUR_LEVEL_SEMIALPHABETIC"
+                }
+            } else {
+                "FOUR_LEVEL"
+            }
+        }
+    }
+}
+
+/// デッドキーに対応するスペーシングアクセント
+fn dead_key_accent(keysym: &str) -> Option<char> {
+    match keysym {
+        "dead_acute" => Some('´'),
+        "dead_grave" => Some('`'),
+        "dead_circumflex" => Some('^'),
+        "dead_diaeresis" => Some('¨'),
+        "dead_tilde" => Some('~'),
+        "dead_cedilla" => Some('¸'),
+        _ => None,
+    }
+}
+
+/// デッドキーと基底文字を合成
+fn compose_dead_key(dead: &str, base: char) -> String {
+    let composed = match (dead, base) {
+        (_, ' ') => dead_key_accent(dead),
+        ("dead_acute", 'a') => Some('á'),
+        ("dead_acute", 'e') => Some('é'),
+        ("dead_acute", 'i') => Some('í'),
+        ("dead_acute", 'o') => Some('ó'),
+        ("dead_acute", 'u') => Some('ú'),
+        ("dead_acute", 'y') => Some('ý'),
+        ("dead_acute", 'A') => Some('Á'),
+        ("dead_acute", 'E') => Some('É'),
+        ("dead_acute", 'I') => Some('Í'),
+        ("dead_acute", 'O') => Some('Ó'),
+        ("dead_acute", 'U') => Some('Ú'),
+        ("dead_acute", 'Y') => Some('Ý'),
+        ("dead_grave", 'a') => Some('à'),
+        ("dead_grave", 'e') => Some('è'),
+        ("dead_grave", 'i') => Some('ì'),
+        ("dead_grave", 'o') => Some('ò'),
+        ("dead_grave", 'u') => Some('ù'),
+        ("dead_grave", 'A') => Some('À'),
+        ("dead_grave", 'E') => Some('È'),
+        ("dead_grave", 'I') => Some('Ì'),
+        ("dead_grave", 'O') => Some('Ò'),
+        ("dead_grave", 'U') => Some('Ù'),
+        ("dead_circumflex", 'a') => Some('â'),
+        ("dead_circumflex", 'e') => Some('ê'),
+        ("dead_circumflex", 'i') => Some('î'),
+        ("dead_circumflex", 'o') => Some('ô'),
+        ("dead_circumflex", 'u') => Some('û'),
+        ("dead_circumflex", 'A') => Some('Â'),
+        ("dead_circumflex", 'E') => Some('Ê'),
+        ("dead_circumflex", 'I') => Some('Î'),
+        ("dead_circumflex", 'O') => Some('Ô'),
+        ("dead_circumflex", 'U') => Some('Û'),
+        ("dead_diaeresis", 'a') => Some('ä'),
+        ("dead_diaeresis", 'e') => Some('ë'),
+        ("dead_diaeresis", 'i') => Some('ï'),
+        ("dead_diaeresis", 'o') => Some('ö'),
+        ("dead_diaeresis", 'u') => Some('ü'),
+        ("dead_diaeresis", 'y') => Some('ÿ'),
+        ("dead_diaeresis", 'A') => Some('Ä'),
+        ("dead_diaeresis", 'E') => Some('Ë'),
+        ("dead_diaeresis", 'I') => Some('Ï'),
+        ("dead_diaeresis", 'O') => Some('Ö'),
+        ("dead_diaeresis", 'U') => Some('Ü'),
+        ("dead_tilde", 'a') => Some('ã'),
+        ("dead_tilde", 'n') => Some('ñ'),
+        ("dead_tilde", 'o') => Some('õ'),
+        ("dead_tilde", 'A') => Some('Ã'),
+        ("dead_tilde", 'N') => Some('Ñ'),
+        ("dead_tilde", 'O') => Some('Õ'),
+        ("dead_cedilla", 'c') => Some('ç'),
+        ("dead_cedilla", 'C') => Some('Ç'),
+        _ => None,
+    };
+
+    match composed {
+        Some(ch) => ch.to_string(),
+        // 合成できない組み合わせはアクセントと基底文字をそのまま出力
+        None => match dead_key_accent(dead) {
+            Some(accent) => format!("{}{}", accent, base),
+            None => base.to_string(),
+        },
+    }
+}
+
+/// キーシンボルを入力文字に変換
+pub fn keysym_to_char(keysym: &str) -> Option<char> {
+    let mut chars = keysym.chars();
+    if let (Some(ch), None) = (chars.next(), chars.next()) {
+        return if ch.is_control() { None } else { Some(ch) };
+    }
+
+    // U+XXXX 形式のキーシンボル
+    if let Some(hex) = keysym.strip_prefix('U') {
+        if hex.len() >= 4 && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return u32::from_str_radix(hex, 16).ok().and_then(char::from_u32);
+        }
+    }
+
+    let ch = match keysym {
+        "space" => ' ',
+        "exclam" => '!',
+        "quotedbl" => '"',
+        "numbersign" => '#',
+        "dollar" => '$',
+        "percent" => '%',
+        "ampersand" => '&',
+        "apostrophe" => '\'',
+        "parenleft" => '(',
+        "parenright" => ')',
+        "asterisk" => '*',
+        "plus" => '+',
+        "comma" => ',',
+        "minus" => '-',
+        "period" => '.',
+        "slash" => '/',
+        "colon" => ':',
+        "semicolon" => ';',
+        "less" => '<',
+        "equal" => '=',
+        "greater" => '>',
+        "question" => '?',
+        "at" => '@',
+        "bracketleft" => '[',
+        "backslash" => '\\',
+        "bracketright" => ']',
+        "asciicircum" => '^',
+        "underscore" => '_',
+        "grave" => '`',
+        "braceleft" => '{',
+        "bar" => '|',
+        "braceright" => '}',
+        "asciitilde" => '~',
+        "exclamdown" => '¡',
+        "sterling" => '£',
+        "currency" => '¤',
+        "yen" => '¥',
+        "section" => '§',
+        "diaeresis" => '¨',
+        "notsign" => '¬',
+        "degree" => '°',
+        "plusminus" => '±',
+        "twosuperior" => '²',
+        "threesuperior" => '³',
+        "acute" => '´',
+        "mu" => 'µ',
+        "paragraph" => '¶',
+        "periodcentered" => '·',
+        "cedilla" => '¸',
+        "onesuperior" => '¹',
+        "guillemotleft" => '«',
+        "guillemotright" => '»',
+        "onequarter" => '¼',
+        "onehalf" => '½',
+        "questiondown" => '¿',
+        "multiply" => '×',
+        "division" => '÷',
+        "ssharp" => 'ß',
+        "adiaeresis" => 'ä',
+        "Adiaeresis" => 'Ä',
+        "odiaeresis" => 'ö',
+        "Odiaeresis" => 'Ö',
+        "udiaeresis" => 'ü',
+        "Udiaeresis" => 'Ü',
+        "EuroSign" => '€',
+        "KP_0" => '0',
+        "KP_1" => '1',
+        "KP_2" => '2',
+        "KP_3" => '3',
+        "KP_4" => '4',
+        "KP_5" => '5',
+        "KP_6" => '6',
+        "KP_7" => '7',
+        "KP_8" => '8',
+        "KP_9" => '9',
+        "KP_Decimal" => '.',
+        "KP_Add" => '+',
+        "KP_Subtract" => '-',
+        "KP_Multiply" => '*',
+        "KP_Divide" => '/',
+        _ => return None,
+    };
+
+    Some(ch)
+}
+
+// ---------------------------------------------------------------------------
+// 組み込みキーマップ
+// ---------------------------------------------------------------------------
+
+/// 標準キータイプ（すべてのキーマップに暗黙的に含まれる）
+const BUILTIN_TYPES: &str = r#"
+xkb_types "builtin" {
+    virtual_modifiers NumLock,Alt,LevelThree,Super;
+
+    type "ONE_LEVEL" {
+        modifiers = None;
+        map[None] = Level1;
+    };
+    type "TWO_LEVEL" {
+        modifiers = Shift;
+        map[Shift] = Level2;
+    };
+    type "ALPHABETIC" {
+        modifiers = Shift+Lock;
+        map[Shift] = Level2;
+        map[Lock] = Level2;
+    };
+    type "KEYPAD" {
+        modifiers = Shift+NumLock;
+        map[None] = Level1;
+        map[Shift] = Level2;
+        map[NumLock] = Level2;
+        map[Shift+NumLock] = Level1;
+    };
+    type "FOUR_LEVEL" {
+        modifiers = Shift+LevelThree;
+        map[None] = Level1;
+        map[Shift] = Level2;
+        map[LevelThree] = Level3;
+        map[Shift+LevelThree] = Level4;
+    };
+    type "FOUR_LEVEL_ALPHABETIC" {
+        modifiers = Shift+Lock+LevelThree;
+        map[None] = Level1;
+        map[Shift] = Level2;
+        map[Lock] = Level2;
+        map[LevelThree] = Level3;
+        map[Shift+LevelThree] = Level4;
+        map[Lock+LevelThree] = Level4;
+        map[Lock+Shift+LevelThree] = Level3;
+    };
+    type "FOUR_LEVEL_SEMIALPHABETIC" {
+        modifiers = Shift+Lock+LevelThree;
+        map[None] = Level1;
+        map[Shift] = Level2;
+        map[Lock] = Level2;
+        map[LevelThree] = Level3;
+        map[Shift+LevelThree] = Level4;
+        map[Lock+LevelThree] = Level3;
+        map[Lock+Shift+LevelThree] = Level4;
+    };
+};
+"#;
+
+/// evdev キーコード定義（XKBキーコード = evdevスキャンコード + 8）
+const BUILTIN_KEYCODES: &str = r#"
+xkb_keycodes "evdev" {
+    minimum = 8;
+    maximum = 255;
+    <ESC>  = 9;
+    <AE01> = 10; <AE02> = 11; <AE03> = 12; <AE04> = 13; <AE05> = 14;
+    <AE06> = 15; <AE07> = 16; <AE08> = 17; <AE09> = 18; <AE10> = 19;
+    <AE11> = 20; <AE12> = 21; <BKSP> = 22; <TAB>  = 23;
+    <AD01> = 24; <AD02> = 25; <AD03> = 26; <AD04> = 27; <AD05> = 28;
+    <AD06> = 29; <AD07> = 30; <AD08> = 31; <AD09> = 32; <AD10> = 33;
+    <AD11> = 34; <AD12> = 35; <RTRN> = 36; <LCTL> = 37;
+    <AC01> = 38; <AC02> = 39; <AC03> = 40; <AC04> = 41; <AC05> = 42;
+    <AC06> = 43; <AC07> = 44; <AC08> = 45; <AC09> = 46; <AC10> = 47;
+    <AC11> = 48; <TLDE> = 49; <LFSH> = 50; <BKSL> = 51;
+    <AB01> = 52; <AB02> = 53; <AB03> = 54; <AB04> = 55; <AB05> = 56;
+    <AB06> = 57; <AB07> = 58; <AB08> = 59; <AB09> = 60; <AB10> = 61;
+    <RTSH> = 62; <KPMU> = 63; <LALT> = 64; <SPCE> = 65; <CAPS> = 66;
+    <FK01> = 67; <FK02> = 68; <FK03> = 69; <FK04> = 70; <FK05> = 71;
+    <FK06> = 72; <FK07> = 73; <FK08> = 74; <FK09> = 75; <FK10> = 76;
+    <NMLK> = 77; <SCLK> = 78;
+    <KP7>  = 79; <KP8>  = 80; <KP9>  = 81; <KPSU> = 82;
+    <KP4>  = 83; <KP5>  = 84; <KP6>  = 85; <KPAD> = 86;
+    <KP1>  = 87; <KP2>  = 88; <KP3>  = 89; <KP0>  = 90; <KPDL> = 91;
+    <LVL3> = 92; <LSGT> = 94; <FK11> = 95; <FK12> = 96; <AB11> = 97;
+    <KATA> = 98; <HIRA> = 99; <HENK> = 100; <HKTG> = 101; <MUHE> = 102;
+    <KPEN> = 104; <RCTL> = 105; <KPDV> = 106; <PRSC> = 107; <RALT> = 108;
+    <HOME> = 110; <UP>   = 111; <PGUP> = 112; <LEFT> = 113; <RGHT> = 114;
+    <END>  = 115; <DOWN> = 116; <PGDN> = 117; <INS>  = 118; <DELE> = 119;
+    <AE13> = 132; <LWIN> = 133; <RWIN> = 134; <COMP> = 135;
+    alias <HZTG> = <TLDE>;
+};
+"#;
+
+/// 標準のモディファイア動作（xkb_compatibility）
+///
+/// このモジュールのパーサーは読み飛ばし、同じ規則を `XkbKeymap::key_action` で
+/// 導出しますが、キーマップを受け取るクライアントはこのセクションから動作を決めます。
+const BUILTIN_COMPAT: &str = r#"
+xkb_compatibility "builtin" {
+    virtual_modifiers NumLock,Alt,LevelThree,Super;
+
+    interpret.useModMapMods = AnyLevel;
+    interpret.repeat = False;
+
+    interpret ISO_Level3_Shift+AnyOf(all) {
+        virtualModifier = LevelThree;
+        useModMapMods = level1;
+        action = SetMods(modifiers=LevelThree,clearLocks);
+    };
+    interpret Alt_L+AnyOf(all) {
+        virtualModifier = Alt;
+        action = SetMods(modifiers=modMapMods,clearLocks);
+    };
+    interpret Alt_R+AnyOf(all) {
+        virtualModifier = Alt;
+        action = SetMods(modifiers=modMapMods,clearLocks);
+    };
+    interpret Meta_L+AnyOf(all) {
+        virtualModifier = Alt;
+        action = SetMods(modifiers=modMapMods,clearLocks);
+    };
+    interpret Meta_R+AnyOf(all) {
+        virtualModifier = Alt;
+        action = SetMods(modifiers=modMapMods,clearLocks);
+    };
+    interpret Super_L+AnyOf(all) {
+        virtualModifier = Super;
+        action = SetMods(modifiers=modMapMods,clearLocks);
+    };
+    interpret Super_R+AnyOf(all) {
+        virtualModifier = Super;
+        action = SetMods(modifiers=modMapMods,clearLocks);
+    };
+    interpret Num_Lock+AnyOf(all) {
+        virtualModifier = NumLock;
+        action = LockMods(modifiers=NumLock);
+    };
+    interpret Caps_Lock+AnyOfOrNone(all) {
+        action = LockMods(modifiers=Lock);
+    };
+    interpret Shift_Lock+AnyOf(all) {
+        action = LockMods(modifiers=modMapMods);
+    };
+    interpret ISO_Next_Group {
+        useModMapMods = level1;
+        action = LockGroup(group=+1);
+    };
+    interpret ISO_Prev_Group {
+        useModMapMods = level1;
+        action = LockGroup(group=-1);
+    };
+    interpret Any+AnyOf(all) {
+        action = SetMods(modifiers=modMapMods,clearLocks);
+    };
+
+    indicator "Caps Lock" {
+        whichModState = locked;
+        modifiers = Lock;
+    };
+    indicator "Num Lock" {
+        whichModState = locked;
+        modifiers = NumLock;
+    };
+};
+"#;
+
+/// 全レイアウト共通のキー定義
+const COMMON_SYMBOLS: &str = r#"
+    key <ESC>  { [ Escape ] };
+    key <BKSP> { [ BackSpace ] };
+    key <TAB>  { [ Tab, ISO_Left_Tab ] };
+    key <RTRN> { [ Return ] };
+    key <SPCE> { [ space ] };
+    key <LCTL> { [ Control_L ] };
+    key <RCTL> { [ Control_R ] };
+    key <LFSH> { [ Shift_L ] };
+    key <RTSH> { [ Shift_R ] };
+    key <LALT> { [ Alt_L, Meta_L ] };
+    key <LWIN> { [ Super_L ] };
+    key <RWIN> { [ Super_R ] };
+    key <COMP> { [ Menu ] };
+    key <CAPS> { [ Caps_Lock ] };
+    key <NMLK> { [ Num_Lock ] };
+    key <SCLK> { [ Scroll_Lock ] };
+    key <PRSC> { [ Print ] };
+    key <FK01> { [ F1 ] };  key <FK02> { [ F2 ] };  key <FK03> { [ F3 ] };
+    key <FK04> { [ F4 ] };  key <FK05> { [ F5 ] };  key <FK06> { [ F6 ] };
+    key <FK07> { [ F7 ] };  key <FK08> { [ F8 ] };  key <FK09> { [ F9 ] };
+    key <FK10> { [ F10 ] }; key <FK11> { [ F11 ] }; key <FK12> { [ F12 ] };
+    key <HOME> { [ Home ] };
+    key <END>  { [ End ] };
+    key <PGUP> { [ Prior ] };
+    key <PGDN> { [ Next ] };
+    key <INS>  { [ Insert ] };
+    key <DELE> { [ Delete ] };
+    key <UP>   { [ Up ] };
+    key <DOWN> { [ Down ] };
+    key <LEFT> { [ Left ] };
+    key <RGHT> { [ Right ] };
+    key <KP7>  { [ KP_Home, KP_7 ] };
+    key <KP8>  { [ KP_Up, KP_8 ] };
+    key <KP9>  { [ KP_Prior, KP_9 ] };
+    key <KP4>  { [ KP_Left, KP_4 ] };
+    key <KP5>  { [ KP_Begin, KP_5 ] };
+    key <KP6>  { [ KP_Right, KP_6 ] };
+    key <KP1>  { [ KP_End, KP_1 ] };
+    key <KP2>  { [ KP_Down, KP_2 ] };
+    key <KP3>  { [ KP_Next, KP_3 ] };
+    key <KP0>  { [ KP_Insert, KP_0 ] };
+    key <KPDL> { [ KP_Delete, KP_Decimal ] };
+    key <KPDV> { [ KP_Divide ] };
+    key <KPMU> { [ KP_Multiply ] };
+    key <KPSU> { [ KP_Subtract ] };
+    key <KPAD> { [ KP_Add ] };
+    key <KPEN> { [ KP_Enter ] };
+
+    key <AD01> { [ q, Q ] }; key <AD02> { [ w, W ] }; key <AD03> { [ e, E ] };
+    key <AD04> { [ r, R ] }; key <AD05> { [ t, T ] }; key <AD06> { [ y, Y ] };
+    key <AD07> { [ u, U ] }; key <AD08> { [ i, I ] }; key <AD09> { [ o, O ] };
+    key <AD10> { [ p, P ] };
+    key <AC01> { [ a, A ] }; key <AC02> { [ s, S ] }; key <AC03> { [ d, D ] };
+    key <AC04> { [ f, F ] }; key <AC05> { [ g, G ] }; key <AC06> { [ h, H ] };
+    key <AC07> { [ j, J ] }; key <AC08> { [ k, K ] }; key <AC09> { [ l, L ] };
+    key <AB01> { [ z, Z ] }; key <AB02> { [ x, X ] }; key <AB03> { [ c, C ] };
+    key <AB04> { [ v, V ] }; key <AB05> { [ b, B ] }; key <AB06> { [ n, N ] };
+    key <AB07> { [ m, M ] };
+
+    modifier_map Shift   { <LFSH>, <RTSH> };
+    modifier_map Lock    { <CAPS> };
+    modifier_map Control { <LCTL>, <RCTL> };
+    modifier_map Mod1    { <LALT> };
+    modifier_map Mod2    { <NMLK> };
+    modifier_map Mod4    { <LWIN>, <RWIN> };
+"#;
+
+const US_SYMBOLS: &str = r#"
+    name[Group1] = "English (US)";
+    key <TLDE> { [ grave, asciitilde ] };
+    key <AE01> { [ 1, exclam ] };
+    key <AE02> { [ 2, at ] };
+    key <AE03> { [ 3, numbersign ] };
+    key <AE04> { [ 4, dollar ] };
+    key <AE05> { [ 5, percent ] };
+    key <AE06> { [ 6, asciicircum ] };
+    key <AE07> { [ 7, ampersand ] };
+    key <AE08> { [ 8, asterisk ] };
+    key <AE09> { [ 9, parenleft ] };
+    key <AE10> { [ 0, parenright ] };
+    key <AE11> { [ minus, underscore ] };
+    key <AE12> { [ equal, plus ] };
+    key <AD11> { [ bracketleft, braceleft ] };
+    key <AD12> { [ bracketright, braceright ] };
+    key <AC10> { [ semicolon, colon ] };
+    key <AC11> { [ apostrophe, quotedbl ] };
+    key <BKSL> { [ backslash, bar ] };
+    key <AB08> { [ comma, less ] };
+    key <AB09> { [ period, greater ] };
+    key <AB10> { [ slash, question ] };
+    key <RALT> { [ Alt_R, Meta_R ] };
+    modifier_map Mod1 { <RALT> };
+"#;
+
+const DE_SYMBOLS: &str = r#"
+    name[Group1] = "German";
+    key <TLDE> { [ dead_circumflex, degree ] };
+    key <AE01> { [ 1, exclam, onesuperior ] };
+    key <AE02> { [ 2, quotedbl, twosuperior ] };
+    key <AE03> { [ 3, section, threesuperior ] };
+    key <AE04> { [ 4, dollar, onequarter ] };
+    key <AE05> { [ 5, percent, onehalf ] };
+    key <AE06> { [ 6, ampersand, notsign ] };
+    key <AE07> { [ 7, slash, braceleft ] };
+    key <AE08> { [ 8, parenleft, bracketleft ] };
+    key <AE09> { [ 9, parenright, bracketright ] };
+    key <AE10> { [ 0, equal, braceright ] };
+    key <AE11> { [ ssharp, question, backslash, questiondown ] };
+    key <AE12> { [ dead_acute, dead_grave, dead_cedilla ] };
+    key <AD01> { [ q, Q, at ] };
+    key <AD03> { [ e, E, EuroSign ] };
+    key <AD06> { [ z, Z ] };
+    key <AD11> { [ udiaeresis, Udiaeresis ] };
+    key <AD12> { [ plus, asterisk, asciitilde ] };
+    key <AC10> { [ odiaeresis, Odiaeresis ] };
+    key <AC11> { [ adiaeresis, Adiaeresis ] };
+    key <BKSL> { [ numbersign, apostrophe ] };
+    key <LSGT> { [ less, greater, bar ] };
+    key <AB01> { [ y, Y ] };
+    key <AB07> { [ m, M, mu ] };
+    key <AB08> { [ comma, semicolon ] };
+    key <AB09> { [ period, colon ] };
+    key <AB10> { [ minus, underscore ] };
+    key <RALT> { [ ISO_Level3_Shift ] };
+    modifier_map Mod5 { <RALT> };
+"#;
+
+const JP_SYMBOLS: &str = r#"
+    name[Group1] = "Japanese";
+    key <TLDE> { [ Zenkaku_Hankaku ] };
+    key <AE01> { [ 1, exclam ] };
+    key <AE02> { [ 2, quotedbl ] };
+    key <AE03> { [ 3, numbersign ] };
+    key <AE04> { [ 4, dollar ] };
+    key <AE05> { [ 5, percent ] };
+    key <AE06> { [ 6, ampersand ] };
+    key <AE07> { [ 7, apostrophe ] };
+    key <AE08> { [ 8, parenleft ] };
+    key <AE09> { [ 9, parenright ] };
+    key <AE10> { [ 0, NoSymbol ] };
+    key <AE11> { [ minus, equal ] };
+    key <AE12> { [ asciicircum, asciitilde ] };
+    key <AE13> { [ backslash, bar ] };
+    key <AD11> { [ at, grave ] };
+    key <AD12> { [ bracketleft, braceleft ] };
+    key <AC10> { [ semicolon, plus ] };
+    key <AC11> { [ colon, asterisk ] };
+    key <BKSL> { [ bracketright, braceright ] };
+    key <AB08> { [ comma, less ] };
+    key <AB09> { [ period, greater ] };
+    key <AB10> { [ slash, question ] };
+    key <AB11> { [ backslash, underscore ] };
+    key <MUHE> { [ Muhenkan ] };
+    key <HENK> { [ Henkan_Mode ] };
+    key <HKTG> { [ Hiragana_Katakana ] };
+    key <RALT> { [ Alt_R, Meta_R ] };
+    modifier_map Mod1 { <RALT> };
+"#;
+
+/// 組み込みレイアウトの xkb_keymap テキストを生成
+///
+/// クライアントにそのまま渡せるよう、標準キータイプとモディファイアの動作を含む
+/// 完全なキーマップ（keycodes / types / compatibility / symbols）を生成します。
+pub fn builtin_keymap_text(layout: &str) -> Option<String> {
+    let symbols = match layout {
+        "us" => US_SYMBOLS,
+        "de" => DE_SYMBOLS,
+        "jp" => JP_SYMBOLS,
+        _ => return None,
+    };
+
+    Some(format!(
+        "xkb_keymap {{\n{}\n{}\n{}\nxkb_symbols \"{}\" {{\n{}\n{}\n}};\n}};\n",
+        BUILTIN_KEYCODES, BUILTIN_TYPES, BUILTIN_COMPAT, layout, COMMON_SYMBOLS, symbols
+    ))
+}
+
+/// 組み込みレイアウト名の一覧
+pub fn builtin_layout_names() -> &'static [&'static str] {
+    &["us", "de", "jp"]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(manager: &mut KeyboardLayoutManager, keycode: u32) -> Option<KeyTranslation> {
+        let result = manager.process_key(keycode, true);
+        manager.process_key(keycode, false);
+        result
+    }
+
+    #[test]
+    fn test_builtin_keymaps_compile() {
+        for name in builtin_layout_names() {
+            let keymap = XkbKeymap::builtin(name).expect("組み込みキーマップのコンパイルに失敗");
+            assert_eq!(keymap.group_count(), 1);
+            assert_eq!(keymap.keycode("AC01"), Some(38));
+        }
+    }
+
+    #[test]
+    fn test_builtin_keymap_text_is_self_contained() {
+        // クライアントは標準キータイプを補わないため、テキストだけで解釈できる必要がある
+        for name in builtin_layout_names() {
+            let text = builtin_keymap_text(name).unwrap();
+            for section in ["xkb_keycodes", "xkb_types", "xkb_compatibility", "xkb_symbols"] {
+                assert!(text.contains(section), "{}: {} がありません", name, section);
+            }
+            assert!(text.contains("interpret Caps_Lock"));
+
+            let mut builder = KeymapBuilder::default();
+            Parser::new(&text).unwrap().parse_file(&mut builder).unwrap();
+            let keymap = builder.build().unwrap();
+            assert!(keymap.key_type("FOUR_LEVEL_SEMIALPHABETIC").is_some());
+            assert_eq!(keymap.keysym(38, 0, MOD_SHIFT), Some(KeySym("A".to_string())));
+        }
+    }
+
+    #[test]
+    fn test_to_text_round_trips_keymap() {
+        let text = r#"
+            xkb_keymap {
+                xkb_keycodes { <AC01> = 38; <RALT> = 108; <LSGT> = 94; };
+                xkb_symbols {
+                    name[Group1] = "First";
+                    name[Group2] = "Second \\ Layout";
+                    key <AC01> { symbols[Group1] = [ a, A, ae, AE ], symbols[Group2] = [ U0444, U0424 ] };
+                    key <RALT> { [ ISO_Level3_Shift ] };
+                    key <LSGT> { [ ISO_Next_Group ] };
+                    modifier_map Mod5 { <RALT> };
+                };
+            };
+        "#;
+        let keymaps = builtin_layout_names()
+            .iter()
+            .map(|name| XkbKeymap::builtin(name).unwrap())
+            .chain(std::iter::once(XkbKeymap::parse(text).unwrap()));
+
+        for keymap in keymaps {
+            let output = keymap.to_text();
+            let mut builder = KeymapBuilder::default();
+            Parser::new(&output).unwrap().parse_file(&mut builder).unwrap();
+            let parsed = builder.build().unwrap();
+
+            assert_eq!(parsed.group_names, keymap.group_names);
+            assert_eq!(parsed.modifier_map, keymap.modifier_map);
+            for code in keymap.keys.keys() {
+                for group in 0..keymap.group_count() {
+                    for mods in [0, MOD_SHIFT, MOD_LOCK, MOD_LEVEL_THREE, MOD_SHIFT | MOD_LEVEL_THREE] {
+                        assert_eq!(parsed.keysym(*code, group, mods), keymap.keysym(*code, group, mods));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_shift_and_caps_lock_levels() {
+        let mut manager = KeyboardLayoutManager::new();
+        let a = 38;
+
+        assert_eq!(press(&mut manager, a).unwrap().text.as_deref(), Some("a"));
+
+        // Shift押下中は大文字
+        manager.process_key(50, true);
+        assert!(manager.modifiers().contains(&KeyModifier::Shift));
+        assert_eq!(press(&mut manager, a).unwrap().text.as_deref(), Some("A"));
+        manager.process_key(50, false);
+
+        // Caps Lock はロックされ、数字キーには影響しない
+        press(&mut manager, 66);
+        assert!(manager.caps_lock_active());
+        assert_eq!(press(&mut manager, a).unwrap().key_sym, KeySym("A".to_string()));
+        assert_eq!(press(&mut manager, 10).unwrap().text.as_deref(), Some("1"));
+
+        press(&mut manager, 66);
+        assert!(!manager.caps_lock_active());
+    }
+
+    #[test]
+    fn test_num_lock_keypad() {
+        let mut manager = KeyboardLayoutManager::new();
+        assert_eq!(press(&mut manager, 79).unwrap().key_sym, KeySym("KP_Home".to_string()));
+
+        manager.set_num_lock(true);
+        assert!(manager.modifiers().contains(&KeyModifier::NumLock));
+        assert_eq!(press(&mut manager, 79).unwrap().key_sym, KeySym("KP_7".to_string()));
+    }
+
+    #[test]
+    fn test_german_level_three_and_dead_keys() {
+        let mut manager = KeyboardLayoutManager::with_layouts(&["de"]).unwrap();
+
+        // QWERTZ配列
+        assert_eq!(press(&mut manager, 29).unwrap().text.as_deref(), Some("z"));
+
+        // AltGr + q = @
+        manager.process_key(108, true);
+        assert!(manager.modifiers().contains(&KeyModifier::LevelThree));
+        let at = press(&mut manager, 24).unwrap();
+        assert_eq!(at.text.as_deref(), Some("@"));
+        assert_eq!(at.level, 2);
+        manager.process_key(108, false);
+
+        // デッドキー ´ + e = é
+        let dead = press(&mut manager, 21).unwrap();
+        assert!(dead.dead);
+        assert_eq!(dead.text, None);
+        assert_eq!(press(&mut manager, 26).unwrap().text.as_deref(), Some("é"));
+    }
+
+    #[test]
+    fn test_japanese_layout() {
+        let mut manager = KeyboardLayoutManager::with_layouts(&["jp"]).unwrap();
+        assert_eq!(press(&mut manager, 34).unwrap().text.as_deref(), Some("@"));
+        assert_eq!(
+            press(&mut manager, 49).unwrap().key_sym,
+            KeySym("Zenkaku_Hankaku".to_string())
+        );
+
+        // Shift + 0 はシンボルなし
+        manager.process_key(50, true);
+        assert!(press(&mut manager, 19).is_none());
+        manager.process_key(50, false);
+    }
+
+    #[test]
+    fn test_per_window_layout_memory() {
+        let mut manager = KeyboardLayoutManager::with_layouts(&["us", "de", "jp"]).unwrap();
+        assert_eq!(manager.layouts().len(), 3);
+
+        let editor = NodeId(1);
+        let terminal = NodeId(2);
+
+        manager.focus_changed(Some(editor));
+        manager.set_active_layout(1).unwrap();
+        assert_eq!(manager.active_layout().unwrap().name, "German");
+
+        // 新しいウィンドウは既定レイアウト
+        manager.focus_changed(Some(terminal));
+        assert_eq!(manager.active_index(), 0);
+        manager.next_layout();
+        manager.next_layout();
+        assert_eq!(manager.active_layout().unwrap().name, "Japanese");
+
+        manager.focus_changed(Some(editor));
+        assert_eq!(manager.active_index(), 1);
+        manager.focus_changed(Some(terminal));
+        assert_eq!(manager.active_index(), 2);
+
+        // グローバルモードではフォーカスで切り替わらない
+        manager.set_scope(LayoutScope::Global);
+        manager.focus_changed(Some(editor));
+        assert_eq!(manager.active_index(), 2);
+    }
+
+    #[test]
+    fn test_multi_group_keymap_and_parse_error() {
+        let text = r#"
+            xkb_keymap {
+                xkb_keycodes { <AC01> = 38; <LSGT> = 94; };
+                xkb_types { };
+                xkb_symbols {
+                    name[Group1] = "First";
+                    name[Group2] = "Second";
+                    key <AC01> { symbols[Group1] = [ a, A ], symbols[Group2] = [ U0444, U0424 ] };
+                    key <LSGT> { [ ISO_Next_Group ] };
+                };
+            };
+        "#;
+        let keymap = XkbKeymap::parse(text).unwrap();
+        assert_eq!(keymap.group_count(), 2);
+
+        let mut manager = KeyboardLayoutManager::empty();
+        manager.add_keymap(keymap);
+        assert_eq!(press(&mut manager, 38).unwrap().text.as_deref(), Some("a"));
+        press(&mut manager, 94);
+        assert_eq!(manager.active_layout().unwrap().name, "Second");
+        assert_eq!(press(&mut manager, 38).unwrap().text.as_deref(), Some("ф"));
+
+        let error = XkbKeymap::parse("xkb_symbols {\n  key <AC01> { [ a, ] };\n};").unwrap_err();
+        match error {
+            KeymapError::Parse { line, .. } => assert_eq!(line, 2),
+            other => panic!("予期しないエラー: {:?}", other),
+        }
+    }
+}
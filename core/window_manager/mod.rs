@@ -6,8 +6,8 @@
 //! このモジュールはLumosDesktopの中核となるウィンドウ管理システムを提供します。
 //! 一貫性のあるユーザーエクスペリエンスを提供するために、以下の機能を統合しています：
 //! 
-//! - コンポジター: ウィンドウの合成と描画
-//! - シーングラフ: UI要素の階層構造管理
+//! - コンポジター: ウィンドウの合成と描画、出力の構成管理、画面キャプチャ（ヘッドレスでの実行とWaylandサーバーを含む）
+//! - シーングラフ: UI要素の階層構造管理（スレッド間で共有でき、描画スレッドにはスナップショットを渡す）
 //! - レイアウトエンジン: ウィンドウの配置とワークスペース管理
 //! - 入力処理: キーボード・マウス・タッチイベントの処理
 //! - ジェスチャー認識: マルチタッチジェスチャー検出
@@ -22,13 +22,13 @@ pub mod gesture_recognizer;
 pub mod effects_pipeline;
 
 // 主要コンポーネントの再エクスポート
-pub use compositor::wayland_compositor::{LumosCompositor, Window, Rectangle};
-pub use scene_graph::scene_graph::{SceneGraph, NodeId, NodeType, Transform, BoundingBox};
+pub use compositor::{LumosCompositor, Window, Rectangle, Region, RepaintStats};
+pub use scene_graph::scene_graph::{SceneGraph, NodeId, NodeType, Transform, BoundingBox, SceneSnapshot, SnapshotReader};
 pub use layout_engine::layout_manager::{LayoutManager, Workspace, LayoutType};
 pub use input_translator::input_manager::{InputManager, InputEvent, KeyModifier};
 pub use gesture_recognizer::{GestureRecognizer, GestureType, GestureInfo, GestureState};
 
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, RwLock};
 use std::collections::HashMap;
 
 /// ウィンドウマネージャのメインクラス
@@ -36,7 +36,7 @@ use std::collections::HashMap;
 pub struct WindowManager {
     // コアコンポーネント
     pub compositor: Arc<Mutex<LumosCompositor>>,
-    pub scene_graph: Arc<Mutex<SceneGraph>>,
+    pub scene_graph: Arc<RwLock<SceneGraph>>,
     pub layout_manager: Arc<Mutex<LayoutManager>>,
     pub input_manager: Arc<Mutex<InputManager>>,
     
@@ -116,7 +116,7 @@ impl WindowManager {
         let config = config.unwrap_or_default();
         
         let compositor = Arc::new(Mutex::new(LumosCompositor::new()));
-        let scene_graph = Arc::new(Mutex::new(SceneGraph::new()));
+        let scene_graph = Arc::new(RwLock::new(SceneGraph::new()));
         let layout_manager = Arc::new(Mutex::new(LayoutManager::new()));
         let input_manager = Arc::new(Mutex::new(InputManager::new()));
         
@@ -231,6 +231,37 @@ impl WindowManager {
             compositor.stop();
         }
     }
+    
+    /// ディスプレイのない環境（CI）で動かすためのヘッドレスバックエンドを作成
+    ///
+    /// コンポジターはCPUでの合成に切り替わり、注入した入力イベントは入力マネージャで処理されます。
+    pub fn create_headless_backend(&self) -> compositor::HeadlessBackend {
+        compositor::HeadlessBackend::with_compositor(self.compositor.clone())
+            .with_input_manager(self.input_manager.clone())
+    }
+    
+    /// `$XDG_RUNTIME_DIR` にソケットを作成し、クライアントのウィンドウをコンポジターに表示するWaylandサーバーを起動
+    ///
+    /// クライアントには入力マネージャのアクティブなキーボードレイアウトのキーマップを送ります。
+    pub fn create_wayland_server(&self) -> Result<compositor::WaylandServer, compositor::WaylandServerError> {
+        compositor::WaylandServer::bind_auto(self.compositor.clone())
+            .map(|server| server.with_input_manager(self.input_manager.clone()))
+    }
+    
+    /// 描画スレッドがシーングラフのスナップショットを読むためのハンドル
+    ///
+    /// スナップショットは `SceneGraph::publish_snapshot` を呼ぶたびに更新されます。
+    pub fn scene_snapshots(&self) -> SnapshotReader {
+        self.scene_graph.read().unwrap_or_else(|poisoned| poisoned.into_inner()).snapshot_reader()
+    }
+    
+    /// 出力構成マネージャを作成（既定の保存先から保存済みの構成を読み込む）
+    pub fn create_output_manager(&self) -> Result<compositor::OutputManager, compositor::OutputConfigError> {
+        match compositor::OutputManager::default_path() {
+            Some(path) => compositor::OutputManager::open(path),
+            None => Ok(compositor::OutputManager::new()),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,20 @@
+// LumosDesktop シーングラフモジュール
+// ウィンドウやUIコンポーネントの階層構造と空間検索を提供します
+
+//! シーングラフモジュール
+//!
+//! ノードの親子関係と変換を管理し、描画順の走査やヒットテストを行います。
+//! ノードはアリーナに置かれ、シーングラフはスレッド間で共有できます。描画スレッドには
+//! 二重バッファのスナップショットを渡すので、描画中もシーングラフの変更を止めません。
+//! ノードのグローバルな境界は動的なR-treeに登録され、点や領域での検索を対数時間で行います。
+
+#[allow(clippy::module_inception)]
+pub mod scene_graph;
+pub mod spatial_index;
+
+// 主要な型の再エクスポート
+pub use scene_graph::{
+    SceneGraph, SceneNode, NodeId, NodeType, NodeProperties, Transform, BoundingBox,
+    SceneSnapshot, SnapshotNode, SnapshotReader,
+};
+pub use spatial_index::SpatialIndex;
@@ -1,14 +1,17 @@
 // LumosDesktop シーングラフ
 // ウィンドウやUIコンポーネントの階層構造を管理する高速シーングラフ
 
-use std::collections::{HashMap, VecDeque};
-use std::rc::{Rc, Weak};
-use std::cell::RefCell;
-use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Instant;
+
+use serde::{Serialize, Deserialize};
+
+use super::spatial_index::SpatialIndex;
 
 /// シーンノードの識別子
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct NodeId(pub u64);
 
 /// シーンノードの種類
@@ -201,6 +204,9 @@ impl Default for NodeProperties {
 }
 
 /// シーンノード
+///
+/// 親子関係は `NodeId` で持ち、ノード自体はシーングラフのアリーナに置かれます。
+#[derive(Debug, Clone)]
 pub struct SceneNode {
     pub id: NodeId,
     pub node_type: NodeType,
@@ -208,8 +214,8 @@ pub struct SceneNode {
     pub transform: Transform,
     pub bounds: BoundingBox,
     pub properties: NodeProperties,
-    pub parent: Option<Weak<RefCell<SceneNode>>>,
-    pub children: Vec<Rc<RefCell<SceneNode>>>,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
     pub render_data: Option<Arc<dyn std::any::Any + Send + Sync>>,
     pub last_update: Instant,
 }
@@ -230,171 +236,175 @@ impl SceneNode {
         }
     }
     
-    /// 子ノードの追加
-    pub fn add_child(&mut self, child: Rc<RefCell<SceneNode>>) {
-        // 親の設定
-        child.borrow_mut().parent = Some(Rc::downgrade(&Rc::new(RefCell::new(self.clone()))));
-        self.children.push(child);
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
     }
     
-    /// 子ノードの削除
-    pub fn remove_child(&mut self, id: NodeId) -> Option<Rc<RefCell<SceneNode>>> {
-        let index = self.children.iter().position(|child| child.borrow().id == id)?;
-        let child = self.children.remove(index);
-        child.borrow_mut().parent = None;
-        Some(child)
+    /// 子ノード（追加した順）
+    pub fn children(&self) -> &[NodeId] {
+        &self.children
+    }
+}
+
+/// ノードのアリーナ
+///
+/// ノードは配列にまとめて置き、`NodeId` から位置を引きます。削除で空いた位置は再利用しますが、
+/// `NodeId` は再利用しないので、削除済みのIDが別のノードを指すことはありません。
+#[derive(Debug, Clone, Default)]
+struct NodeArena {
+    slots: Vec<Option<SceneNode>>,
+    index: HashMap<NodeId, usize>,
+    free: Vec<usize>,
+}
+
+impl NodeArena {
+    fn insert(&mut self, node: SceneNode) {
+        let id = node.id;
+        let slot = match self.free.pop() {
+            Some(slot) => {
+                self.slots[slot] = Some(node);
+                slot
+            }
+            None => {
+                self.slots.push(Some(node));
+                self.slots.len() - 1
+            }
+        };
+        self.index.insert(id, slot);
     }
     
-    /// 指定された型の最も近い親を探す
-    pub fn find_parent_of_type(&self, node_type: &NodeType) -> Option<Rc<RefCell<SceneNode>>> {
-        let parent_weak = self.parent.as_ref()?;
-        let parent = parent_weak.upgrade()?;
-        
-        if parent.borrow().node_type == *node_type {
-            Some(parent)
-        } else {
-            parent.borrow().find_parent_of_type(node_type)
-        }
+    fn get(&self, id: NodeId) -> Option<&SceneNode> {
+        self.slots[*self.index.get(&id)?].as_ref()
     }
     
-    /// グローバル変換の計算
-    pub fn global_transform(&self) -> Transform {
-        match &self.parent {
-            Some(parent_weak) => {
-                if let Some(parent) = parent_weak.upgrade() {
-                    parent.borrow().global_transform().combine(&self.transform)
-                } else {
-                    self.transform.clone()
-                }
-            },
-            None => self.transform.clone(),
-        }
+    fn get_mut(&mut self, id: NodeId) -> Option<&mut SceneNode> {
+        let slot = *self.index.get(&id)?;
+        self.slots[slot].as_mut()
     }
     
-    /// グローバル境界ボックスの計算
-    pub fn global_bounds(&self) -> BoundingBox {
-        self.bounds.transform(&self.global_transform())
+    fn remove(&mut self, id: NodeId) -> Option<SceneNode> {
+        let slot = self.index.remove(&id)?;
+        self.free.push(slot);
+        self.slots[slot].take()
     }
     
-    /// ノードが表示されているかどうか
-    pub fn is_effectively_visible(&self) -> bool {
-        if !self.properties.visible || self.properties.opacity <= 0.0 {
-            return false;
-        }
-        
-        // 親が非表示なら子も非表示
-        match &self.parent {
-            Some(parent_weak) => {
-                if let Some(parent) = parent_weak.upgrade() {
-                    parent.borrow().is_effectively_visible()
-                } else {
-                    true
-                }
-            },
-            None => true,
-        }
+    fn len(&self) -> usize {
+        self.index.len()
+    }
+}
+
+/// スナップショットに含まれるノードの描画用の状態
+#[derive(Debug, Clone)]
+pub struct SnapshotNode {
+    pub id: NodeId,
+    pub node_type: NodeType,
+    /// グローバルな変換
+    pub transform: Transform,
+    /// グローバルな境界
+    pub bounds: BoundingBox,
+    /// 親の不透明度を掛けた実効的な不透明度
+    pub opacity: f32,
+    pub clip_to_bounds: bool,
+    pub layer: u32,
+    pub render_data: Option<Arc<dyn std::any::Any + Send + Sync>>,
+}
+
+/// 公開した時点のシーングラフの表示内容（表示されているノードだけを描画順に持つ）
+#[derive(Debug, Clone, Default)]
+pub struct SceneSnapshot {
+    generation: u64,
+    nodes: Vec<SnapshotNode>,
+    index: HashMap<NodeId, usize>,
+}
+
+impl SceneSnapshot {
+    /// 公開するたびに増える番号（0はまだ何も公開していない状態）
+    pub fn generation(&self) -> u64 {
+        self.generation
     }
     
-    /// 実効的な不透明度の計算
-    pub fn effective_opacity(&self) -> f32 {
-        let self_opacity = self.properties.opacity;
-        
-        match &self.parent {
-            Some(parent_weak) => {
-                if let Some(parent) = parent_weak.upgrade() {
-                    self_opacity * parent.borrow().effective_opacity()
-                } else {
-                    self_opacity
-                }
-            },
-            None => self_opacity,
-        }
+    /// 描画順のノード
+    pub fn nodes(&self) -> &[SnapshotNode] {
+        &self.nodes
+    }
+    
+    pub fn get(&self, id: NodeId) -> Option<&SnapshotNode> {
+        self.index.get(&id).map(|&index| &self.nodes[index])
+    }
+    
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
     }
 }
 
-impl Clone for SceneNode {
-    fn clone(&self) -> Self {
-        Self {
-            id: self.id,
-            node_type: self.node_type.clone(),
-            name: self.name.clone(),
-            transform: self.transform.clone(),
-            bounds: self.bounds,
-            properties: self.properties.clone(),
-            parent: self.parent.clone(),
-            children: Vec::new(), // 子はクローンしない
-            render_data: self.render_data.clone(),
-            last_update: self.last_update,
-        }
+/// スナップショットの二重バッファ
+#[derive(Debug, Default)]
+struct SnapshotBuffers {
+    buffers: [RwLock<Arc<SceneSnapshot>>; 2],
+    /// 描画スレッドが読む側のバッファ
+    front: AtomicUsize,
+}
+
+/// 描画スレッドがスナップショットを読むためのハンドル
+///
+/// シーングラフは裏のバッファに書き込んでから表と入れ替えるので、描画スレッドはシーングラフの
+/// ロックを取らずに、書きかけでないスナップショットを読めます。
+#[derive(Debug, Clone)]
+pub struct SnapshotReader {
+    shared: Arc<SnapshotBuffers>,
+}
+
+impl SnapshotReader {
+    /// 最後に公開されたスナップショット
+    pub fn latest(&self) -> Arc<SceneSnapshot> {
+        let front = self.shared.front.load(Ordering::Acquire);
+        self.shared.buffers[front].read().unwrap_or_else(|poisoned| poisoned.into_inner()).clone()
     }
 }
 
 /// シーングラフ
+///
+/// ノードは `Rc` を使わずにアリーナに置くので、シーングラフは `Send + Sync` です。
+/// 入力やレイアウトのスレッドとは `RwLock` で共有し、描画スレッドには `SnapshotReader` を渡します。
 pub struct SceneGraph {
-    root: Rc<RefCell<SceneNode>>,
-    nodes: HashMap<NodeId, Weak<RefCell<SceneNode>>>,
+    nodes: NodeArena,
+    root: NodeId,
     next_id: u64,
-    dirty_nodes: Vec<NodeId>,
+    dirty_nodes: HashSet<NodeId>,
     cached_transforms: HashMap<NodeId, Transform>,
     cached_bounds: HashMap<NodeId, BoundingBox>,
     spatial_index: SpatialIndex,
+    snapshots: Arc<SnapshotBuffers>,
+    generation: u64,
     update_time: Instant,
 }
 
-/// 空間インデックス - 効率的な空間検索のために
-struct SpatialIndex {
-    // 簡易的な実装。実際の実装ではQuadtreeやR-treeなどを使用する
-    nodes: HashMap<NodeId, BoundingBox>,
-}
-
-impl SpatialIndex {
-    fn new() -> Self {
-        Self { nodes: HashMap::new() }
-    }
-    
-    fn update(&mut self, id: NodeId, bounds: BoundingBox) {
-        self.nodes.insert(id, bounds);
-    }
-    
-    fn remove(&mut self, id: &NodeId) {
-        self.nodes.remove(id);
-    }
-    
-    fn query_region(&self, region: &BoundingBox) -> Vec<NodeId> {
-        self.nodes.iter()
-            .filter(|(_, bounds)| bounds.intersects(region))
-            .map(|(&id, _)| id)
-            .collect()
-    }
-    
-    fn query_point(&self, point: (f32, f32, f32)) -> Vec<NodeId> {
-        self.nodes.iter()
-            .filter(|(_, bounds)| bounds.contains_point(point))
-            .map(|(&id, _)| id)
-            .collect()
+impl Default for SceneGraph {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 impl SceneGraph {
     pub fn new() -> Self {
         let root_id = NodeId(0);
-        let root = Rc::new(RefCell::new(SceneNode::new(
-            root_id,
-            NodeType::Root,
-            "root".to_string(),
-        )));
-        
-        let mut nodes = HashMap::new();
-        nodes.insert(root_id, Rc::downgrade(&root));
+        let mut nodes = NodeArena::default();
+        nodes.insert(SceneNode::new(root_id, NodeType::Root, "root".to_string()));
         
         Self {
-            root,
             nodes,
+            root: root_id,
             next_id: 1,
-            dirty_nodes: Vec::new(),
+            dirty_nodes: HashSet::new(),
             cached_transforms: HashMap::new(),
             cached_bounds: HashMap::new(),
             spatial_index: SpatialIndex::new(),
+            snapshots: Arc::new(SnapshotBuffers::default()),
+            generation: 0,
             update_time: Instant::now(),
         }
     }
@@ -408,89 +418,131 @@ impl SceneGraph {
     
     /// ノードの作成と追加
     pub fn create_node(&mut self, parent_id: NodeId, node_type: NodeType, name: String) -> Result<NodeId, String> {
+        if self.nodes.get(parent_id).is_none() {
+            return Err("指定された親ノードが存在しません".to_string());
+        }
+        
         let id = self.generate_id();
-        let node = Rc::new(RefCell::new(SceneNode::new(id, node_type, name)));
-        
-        // ノードをノードマップに追加
-        self.nodes.insert(id, Rc::downgrade(&node));
-        
-        // 親ノードに子を追加
-        if let Some(parent_weak) = self.nodes.get(&parent_id) {
-            if let Some(parent) = parent_weak.upgrade() {
-                // 親に子を追加
-                parent.borrow_mut().add_child(node);
-                // ノードが変更されたことをマーク
-                self.mark_dirty(id);
-                
-                Ok(id)
-            } else {
-                Err("親ノードが既に削除されています".to_string())
-            }
-        } else {
-            Err("指定された親ノードが存在しません".to_string())
+        let mut node = SceneNode::new(id, node_type, name);
+        node.parent = Some(parent_id);
+        self.nodes.insert(node);
+        if let Some(parent) = self.nodes.get_mut(parent_id) {
+            parent.children.push(id);
         }
+        
+        // ノードが変更されたことをマーク
+        self.mark_dirty(id);
+        Ok(id)
     }
     
-    /// ルートノードの取得
-    pub fn root(&self) -> Rc<RefCell<SceneNode>> {
-        self.root.clone()
+    /// ルートノードのID
+    pub fn root(&self) -> NodeId {
+        self.root
     }
     
     /// ノードの取得
-    pub fn get_node(&self, id: NodeId) -> Option<Rc<RefCell<SceneNode>>> {
-        self.nodes.get(&id).and_then(|weak| weak.upgrade())
+    pub fn get_node(&self, id: NodeId) -> Option<&SceneNode> {
+        self.nodes.get(id)
+    }
+    
+    /// ノードを変更のために取得（変換や境界を変えた場合は `mark_dirty` を呼ぶ）
+    pub fn get_node_mut(&mut self, id: NodeId) -> Option<&mut SceneNode> {
+        self.nodes.get_mut(id)
+    }
+    
+    /// ノードの数（ルートを含む）
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+    
+    /// ノードの変換を設定
+    pub fn set_transform(&mut self, id: NodeId, transform: Transform) -> bool {
+        let Some(node) = self.nodes.get_mut(id) else { return false };
+        node.transform = transform;
+        node.last_update = Instant::now();
+        self.mark_dirty(id);
+        true
     }
     
-    /// ノードの削除
+    /// ノードのローカルな境界を設定
+    pub fn set_bounds(&mut self, id: NodeId, bounds: BoundingBox) -> bool {
+        let Some(node) = self.nodes.get_mut(id) else { return false };
+        node.bounds = bounds;
+        node.last_update = Instant::now();
+        self.mark_dirty(id);
+        true
+    }
+    
+    /// ノードを別の親の下に移動
+    pub fn set_parent(&mut self, id: NodeId, parent_id: NodeId) -> Result<(), String> {
+        if id == self.root {
+            return Err("ルートノードは移動できません".to_string());
+        }
+        let Some(old_parent) = self.nodes.get(id).map(|node| node.parent) else {
+            return Err("指定されたノードが存在しません".to_string());
+        };
+        if self.nodes.get(parent_id).is_none() {
+            return Err("指定された親ノードが存在しません".to_string());
+        }
+        // 自分の子孫の下には移動できない
+        let mut ancestor = Some(parent_id);
+        while let Some(current) = ancestor {
+            if current == id {
+                return Err("ノードを自身の子孫の下に移動することはできません".to_string());
+            }
+            ancestor = self.nodes.get(current).and_then(|node| node.parent);
+        }
+        
+        if let Some(old_parent) = old_parent.and_then(|old_parent| self.nodes.get_mut(old_parent)) {
+            old_parent.children.retain(|&child| child != id);
+        }
+        if let Some(parent) = self.nodes.get_mut(parent_id) {
+            parent.children.push(id);
+        }
+        if let Some(node) = self.nodes.get_mut(id) {
+            node.parent = Some(parent_id);
+        }
+        self.mark_dirty(id);
+        Ok(())
+    }
+    
+    /// ノードの削除（子孫も削除される）
     pub fn remove_node(&mut self, id: NodeId) -> Result<(), String> {
-        if id == self.root.borrow().id {
+        if id == self.root {
             return Err("ルートノードは削除できません".to_string());
         }
         
-        if let Some(node_weak) = self.nodes.get(&id) {
-            if let Some(node) = node_weak.upgrade() {
-                // 親から自分を削除
-                if let Some(parent_weak) = &node.borrow().parent {
-                    if let Some(parent) = parent_weak.upgrade() {
-                        parent.borrow_mut().remove_child(id);
-                    }
-                }
-                
-                // 子ノードも再帰的に削除
-                let children_ids: Vec<NodeId> = node.borrow()
-                    .children
-                    .iter()
-                    .map(|child| child.borrow().id)
-                    .collect();
-                
-                for child_id in children_ids {
-                    self.remove_node(child_id)?;
-                }
-                
-                // ノードを削除
-                self.nodes.remove(&id);
-                self.cached_transforms.remove(&id);
-                self.cached_bounds.remove(&id);
-                self.spatial_index.remove(&id);
-                
-                Ok(())
-            } else {
-                Err("ノードは既に削除されています".to_string())
-            }
-        } else {
-            Err("指定されたノードが存在しません".to_string())
+        let Some(node) = self.nodes.remove(id) else {
+            return Err("指定されたノードが存在しません".to_string());
+        };
+        // 親から自分を削除
+        if let Some(parent) = node.parent.and_then(|parent| self.nodes.get_mut(parent)) {
+            parent.children.retain(|&child| child != id);
+        }
+        
+        // 子ノードも削除
+        let mut removed = vec![node];
+        while let Some(node) = removed.pop() {
+            removed.extend(node.children.iter().filter_map(|&child| self.nodes.remove(child)));
+            self.cached_transforms.remove(&node.id);
+            self.cached_bounds.remove(&node.id);
+            self.dirty_nodes.remove(&node.id);
+            self.spatial_index.remove(&node.id);
         }
+        
+        Ok(())
     }
     
     /// ノードを変更済みとしてマーク
+    ///
+    /// 次の `update` で、マークしたノードとその子孫の境界だけを空間インデックスに反映します。
     pub fn mark_dirty(&mut self, id: NodeId) {
-        if !self.dirty_nodes.contains(&id) {
-            self.dirty_nodes.push(id);
-            
-            // 子ノードも再帰的にマーク
-            if let Some(node) = self.get_node(id) {
-                for child in &node.borrow().children {
-                    self.mark_dirty(child.borrow().id);
+        let mut stack = vec![id];
+        while let Some(id) = stack.pop() {
+            if self.dirty_nodes.insert(id) {
+                // 子ノードもマーク
+                if let Some(node) = self.nodes.get(id) {
+                    stack.extend_from_slice(&node.children);
                 }
             }
         }
@@ -501,41 +553,144 @@ impl SceneGraph {
         let now = Instant::now();
         
         // 変更されたノードの変換と境界を再計算
-        for &id in &self.dirty_nodes {
-            if let Some(node) = self.get_node(id) {
-                let global_transform = node.borrow().global_transform();
-                let global_bounds = node.borrow().global_bounds();
-                
-                self.cached_transforms.insert(id, global_transform);
-                self.cached_bounds.insert(id, global_bounds);
-                self.spatial_index.update(id, global_bounds);
-            }
+        for id in std::mem::take(&mut self.dirty_nodes) {
+            let (Some(global_transform), Some(global_bounds)) = (self.global_transform(id), self.global_bounds(id)) else {
+                continue;
+            };
+            self.cached_transforms.insert(id, global_transform);
+            self.cached_bounds.insert(id, global_bounds);
+            self.spatial_index.update(id, global_bounds);
         }
         
-        self.dirty_nodes.clear();
         self.update_time = now;
     }
     
+    /// 描画スレッド用のスナップショットの読み出し口
+    pub fn snapshot_reader(&self) -> SnapshotReader {
+        SnapshotReader { shared: self.snapshots.clone() }
+    }
+    
+    /// 変更を反映し、現在の表示内容をスナップショットとして描画スレッドに公開する
+    ///
+    /// フレームごとに1回呼び出します。公開したスナップショットの番号を返します。
+    pub fn publish_snapshot(&mut self) -> u64 {
+        self.update();
+        self.generation += 1;
+        
+        let back = 1 - self.snapshots.front.load(Ordering::Acquire);
+        {
+            let mut buffer = self.snapshots.buffers[back].write().unwrap_or_else(|poisoned| poisoned.into_inner());
+            // 描画スレッドが前のスナップショットを手放していれば、その領域を使い回す
+            match Arc::get_mut(&mut buffer) {
+                Some(snapshot) => self.fill_snapshot(snapshot),
+                None => {
+                    let mut snapshot = SceneSnapshot::default();
+                    self.fill_snapshot(&mut snapshot);
+                    *buffer = Arc::new(snapshot);
+                }
+            }
+        }
+        self.snapshots.front.store(back, Ordering::Release);
+        self.generation
+    }
+    
+    fn fill_snapshot(&self, snapshot: &mut SceneSnapshot) {
+        snapshot.generation = self.generation;
+        snapshot.nodes.clear();
+        snapshot.index.clear();
+        
+        let mut queue = VecDeque::from([(self.root, 1.0)]);
+        while let Some((id, parent_opacity)) = queue.pop_front() {
+            let Some(node) = self.nodes.get(id) else { continue };
+            if !node.properties.visible || node.properties.opacity <= 0.0 {
+                continue;
+            }
+            let opacity = parent_opacity * node.properties.opacity;
+            snapshot.index.insert(id, snapshot.nodes.len());
+            snapshot.nodes.push(SnapshotNode {
+                id,
+                node_type: node.node_type.clone(),
+                transform: self.cached_transforms.get(&id).copied().unwrap_or(node.transform),
+                bounds: self.cached_bounds.get(&id).copied().unwrap_or(node.bounds),
+                opacity,
+                clip_to_bounds: node.properties.clip_to_bounds,
+                layer: node.properties.layer,
+                render_data: node.render_data.clone(),
+            });
+            queue.extend(self.children_in_layer_order(node).into_iter().map(|child| (child, opacity)));
+        }
+    }
+    
+    /// 子ノードをレイヤー順に並べる
+    fn children_in_layer_order(&self, node: &SceneNode) -> Vec<NodeId> {
+        let mut children = node.children.clone();
+        children.sort_by_key(|&child| self.nodes.get(child).map_or(0, |child| child.properties.layer));
+        children
+    }
+    
+    /// 指定された型の最も近い親を探す
+    pub fn find_parent_of_type(&self, id: NodeId, node_type: &NodeType) -> Option<NodeId> {
+        let mut current = self.nodes.get(id)?.parent;
+        while let Some(parent_id) = current {
+            let parent = self.nodes.get(parent_id)?;
+            if parent.node_type == *node_type {
+                return Some(parent_id);
+            }
+            current = parent.parent;
+        }
+        None
+    }
+    
+    /// グローバル変換の計算
+    pub fn global_transform(&self, id: NodeId) -> Option<Transform> {
+        let node = self.nodes.get(id)?;
+        Some(match node.parent.and_then(|parent| self.global_transform(parent)) {
+            Some(parent_transform) => parent_transform.combine(&node.transform),
+            None => node.transform,
+        })
+    }
+    
+    /// グローバル境界ボックスの計算
+    pub fn global_bounds(&self, id: NodeId) -> Option<BoundingBox> {
+        let node = self.nodes.get(id)?;
+        Some(node.bounds.transform(&self.global_transform(id)?))
+    }
+    
+    /// ノードが表示されているかどうか（親が非表示なら子も非表示）
+    pub fn is_effectively_visible(&self, id: NodeId) -> bool {
+        let mut current = Some(id);
+        while let Some(id) = current {
+            let Some(node) = self.nodes.get(id) else { return false };
+            if !node.properties.visible || node.properties.opacity <= 0.0 {
+                return false;
+            }
+            current = node.parent;
+        }
+        true
+    }
+    
+    /// 実効的な不透明度の計算
+    pub fn effective_opacity(&self, id: NodeId) -> f32 {
+        let mut opacity = 1.0;
+        let mut current = Some(id);
+        while let Some(node) = current.and_then(|id| self.nodes.get(id)) {
+            opacity *= node.properties.opacity;
+            current = node.parent;
+        }
+        opacity
+    }
+    
     /// 描画順にノードを取得
-    pub fn get_nodes_in_draw_order(&self) -> Vec<Rc<RefCell<SceneNode>>> {
+    pub fn get_nodes_in_draw_order(&self) -> Vec<NodeId> {
         let mut result = Vec::new();
-        let mut queue = VecDeque::new();
-        
-        queue.push_back(self.root.clone());
+        let mut queue = VecDeque::from([self.root]);
         
-        while let Some(node) = queue.pop_front() {
-            let node_ref = node.borrow();
-            
-            if node_ref.is_effectively_visible() {
-                result.push(node.clone());
-                
+        while let Some(id) = queue.pop_front() {
+            let Some(node) = self.nodes.get(id) else { continue };
+            if node.properties.visible && node.properties.opacity > 0.0 {
+                result.push(id);
                 // 子ノードをレイヤー順にソートして追加
-                let mut children: Vec<_> = node_ref.children.clone();
-                children.sort_by_key(|child| child.borrow().properties.layer);
-                
-                for child in children {
-                    queue.push_back(child);
-                }
+                queue.extend(self.children_in_layer_order(node));
             }
         }
         
@@ -550,12 +705,8 @@ impl SceneGraph {
         // インタラクティブなノードだけをフィルタリング
         candidates.into_iter()
             .filter(|&id| {
-                if let Some(node) = self.get_node(id) {
-                    let node_ref = node.borrow();
-                    node_ref.properties.interactive && node_ref.is_effectively_visible()
-                } else {
-                    false
-                }
+                self.nodes.get(id).is_some_and(|node| node.properties.interactive)
+                    && self.is_effectively_visible(id)
             })
             .collect()
     }
@@ -568,22 +719,22 @@ impl SceneGraph {
     /// ツリー構造を文字列として取得（デバッグ用）
     pub fn print_tree(&self) -> String {
         let mut result = String::new();
-        self.print_node(&self.root, 0, &mut result);
+        self.print_node(self.root, 0, &mut result);
         result
     }
     
-    fn print_node(&self, node: &Rc<RefCell<SceneNode>>, depth: usize, result: &mut String) {
-        let node_ref = node.borrow();
+    fn print_node(&self, id: NodeId, depth: usize, result: &mut String) {
+        let Some(node) = self.nodes.get(id) else { return };
         let indent = "  ".repeat(depth);
         
         result.push_str(&format!("{}{}: {} ({:?})\n",
             indent,
-            node_ref.id.0,
-            node_ref.name,
-            node_ref.node_type
+            node.id.0,
+            node.name,
+            node.node_type
         ));
         
-        for child in &node_ref.children {
+        for &child in &node.children {
             self.print_node(child, depth + 1, result);
         }
     }
@@ -592,11 +743,12 @@ impl SceneGraph {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::thread;
     
     #[test]
     fn test_scene_graph_creation() {
         let mut graph = SceneGraph::new();
-        let root_id = graph.root().borrow().id;
+        let root_id = graph.root();
         
         // パネルの追加
         let panel_id = graph.create_node(
@@ -627,10 +779,11 @@ mod tests {
         
         // 親子関係の検証
         let panel = graph.get_node(panel_id).unwrap();
-        assert_eq!(panel.borrow().children.len(), 1);
+        assert_eq!(panel.children().len(), 1);
+        assert_eq!(graph.get_node(widget_id).unwrap().parent(), Some(panel_id));
         
         let root = graph.get_node(root_id).unwrap();
-        assert_eq!(root.borrow().children.len(), 2);
+        assert_eq!(root.children().len(), 2);
         
         // ノードの削除
         graph.remove_node(panel_id).unwrap();
@@ -638,7 +791,8 @@ mod tests {
         assert!(graph.get_node(widget_id).is_none()); // 子も削除される
         
         let root_after = graph.get_node(root_id).unwrap();
-        assert_eq!(root_after.borrow().children.len(), 1); // パネルが削除されたので1つのみ
+        assert_eq!(root_after.children().len(), 1); // パネルが削除されたので1つのみ
+        assert_eq!(graph.node_count(), 2);
     }
     
     #[test]
@@ -661,4 +815,135 @@ mod tests {
         assert_eq!(transformed.min, (10.0, 10.0, 0.0));
         assert_eq!(transformed.max, (30.0, 30.0, 0.0));
     }
-} 
\ No newline at end of file
+    
+    fn translation(x: f32, y: f32) -> Transform {
+        Transform { position: (x, y, 0.0), ..Transform::identity() }
+    }
+    
+    #[test]
+    fn test_hit_test_follows_dirty_nodes() {
+        let mut graph = SceneGraph::new();
+        let root_id = graph.root();
+        
+        // 格子状に並べたウィンドウ
+        let mut windows = Vec::new();
+        for i in 0..100 {
+            let id = graph.create_node(root_id, NodeType::Window, format!("window_{}", i)).unwrap();
+            graph.set_bounds(id, BoundingBox::from_size(90.0, 90.0, 0.0));
+            graph.set_transform(id, translation((i % 10) as f32 * 100.0, (i / 10) as f32 * 100.0));
+            windows.push(id);
+        }
+        graph.update();
+        
+        assert_eq!(graph.hit_test((150.0, 250.0, 0.0)), vec![windows[21]]);
+        assert!(graph.hit_test((195.0, 250.0, 0.0)).is_empty());
+        let mut region = graph.query_region(BoundingBox::new((0.0, 0.0, 0.0), (150.0, 50.0, 0.0)));
+        region.sort_by_key(|id| id.0);
+        assert_eq!(region, vec![windows[0], windows[1]]);
+        
+        // 移動したノードは次の更新でインデックスに反映される
+        graph.get_node_mut(windows[21]).unwrap().transform.position = (2000.0, 2000.0, 0.0);
+        graph.mark_dirty(windows[21]);
+        assert_eq!(graph.hit_test((150.0, 250.0, 0.0)), vec![windows[21]]);
+        graph.update();
+        assert!(graph.hit_test((150.0, 250.0, 0.0)).is_empty());
+        assert_eq!(graph.hit_test((2050.0, 2050.0, 0.0)), vec![windows[21]]);
+        
+        // 非表示のノードと削除したノードはヒットしない
+        graph.get_node_mut(windows[0]).unwrap().properties.visible = false;
+        assert!(graph.hit_test((50.0, 50.0, 0.0)).is_empty());
+        graph.remove_node(windows[21]).unwrap();
+        assert!(graph.hit_test((2050.0, 2050.0, 0.0)).is_empty());
+    }
+    
+    #[test]
+    fn test_children_follow_parent() {
+        let mut graph = SceneGraph::new();
+        let window = graph.create_node(graph.root(), NodeType::Window, "window".to_string()).unwrap();
+        let button = graph.create_node(window, NodeType::Widget, "button".to_string()).unwrap();
+        graph.set_transform(window, translation(100.0, 100.0));
+        graph.set_transform(button, translation(10.0, 20.0));
+        graph.set_bounds(button, BoundingBox::from_size(30.0, 10.0, 0.0));
+        graph.get_node_mut(window).unwrap().properties.opacity = 0.5;
+        graph.get_node_mut(button).unwrap().properties.opacity = 0.5;
+        graph.update();
+        
+        assert_eq!(graph.global_bounds(button).unwrap().min, (110.0, 120.0, 0.0));
+        assert_eq!(graph.hit_test((115.0, 125.0, 0.0)), vec![button]);
+        assert_eq!(graph.effective_opacity(button), 0.25);
+        assert_eq!(graph.find_parent_of_type(button, &NodeType::Window), Some(window));
+        
+        // 親を動かすと子も動く
+        graph.set_transform(window, translation(500.0, 100.0));
+        graph.update();
+        assert!(graph.hit_test((115.0, 125.0, 0.0)).is_empty());
+        assert_eq!(graph.hit_test((515.0, 125.0, 0.0)), vec![button]);
+        
+        // 親を非表示にすると子もヒットしない
+        graph.get_node_mut(window).unwrap().properties.visible = false;
+        assert!(graph.hit_test((515.0, 125.0, 0.0)).is_empty());
+        
+        // 別の親に付け替える（自分の子孫の下には付けられない）
+        let panel = graph.create_node(graph.root(), NodeType::Panel, "panel".to_string()).unwrap();
+        assert!(graph.set_parent(window, button).is_err());
+        graph.set_parent(button, panel).unwrap();
+        graph.update();
+        assert_eq!(graph.get_node(window).unwrap().children(), &[] as &[NodeId]);
+        assert_eq!(graph.hit_test((15.0, 25.0, 0.0)), vec![button]);
+    }
+    
+    #[test]
+    fn test_snapshots_are_shared_with_render_thread() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<SceneGraph>();
+        assert_send_sync::<SnapshotReader>();
+        
+        let graph = Arc::new(RwLock::new(SceneGraph::new()));
+        let reader = graph.read().unwrap().snapshot_reader();
+        assert_eq!(reader.latest().generation(), 0);
+        
+        let window = {
+            let mut graph = graph.write().unwrap();
+            let root = graph.root();
+            let window = graph.create_node(root, NodeType::Window, "window".to_string()).unwrap();
+            graph.set_bounds(window, BoundingBox::from_size(100.0, 100.0, 0.0));
+            let hidden = graph.create_node(root, NodeType::Overlay, "hidden".to_string()).unwrap();
+            graph.get_node_mut(hidden).unwrap().properties.visible = false;
+            assert_eq!(graph.publish_snapshot(), 1);
+            window
+        };
+        
+        // 描画スレッドはシーングラフのロックを取らずにスナップショットを読む
+        let render = {
+            let reader = reader.clone();
+            thread::spawn(move || {
+                let snapshot = reader.latest();
+                (snapshot.generation(), snapshot.nodes().iter().map(|node| node.id).collect::<Vec<_>>())
+            })
+        };
+        // 入力スレッドは読み取りロックでヒットテストする
+        let input = {
+            let graph = graph.clone();
+            thread::spawn(move || graph.read().unwrap().hit_test((50.0, 50.0, 0.0)))
+        };
+        assert_eq!(render.join().unwrap(), (1, vec![NodeId(0), window]));
+        assert_eq!(input.join().unwrap(), vec![window]);
+        
+        // 公開するまで描画スレッドからは前の状態が見える
+        let first = reader.latest();
+        graph.write().unwrap().set_transform(window, translation(300.0, 0.0));
+        assert_eq!(reader.latest().get(window).unwrap().bounds.min, (0.0, 0.0, 0.0));
+        assert_eq!(graph.write().unwrap().publish_snapshot(), 2);
+        let second = reader.latest();
+        assert_eq!(second.generation(), 2);
+        assert_eq!(second.get(window).unwrap().bounds.min, (300.0, 0.0, 0.0));
+        
+        // 古いスナップショットを持っている間も内容は変わらない
+        graph.write().unwrap().remove_node(window).unwrap();
+        graph.write().unwrap().publish_snapshot();
+        assert_eq!(first.generation(), 1);
+        assert!(first.get(window).is_some());
+        assert!(reader.latest().get(window).is_none());
+        assert_eq!(reader.latest().len(), 1);
+    }
+}
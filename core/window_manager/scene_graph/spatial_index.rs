@@ -0,0 +1,545 @@
+// LumosDesktop 空間インデックス
+// シーンノードの境界ボックスを動的なR-treeで管理し、点や領域での検索を高速に行う
+
+use std::collections::HashMap;
+
+use super::scene_graph::{BoundingBox, NodeId};
+
+/// 木のノードが持つ要素の最大数（超えると分割する）
+const MAX_ENTRIES: usize = 8;
+/// 木のノードが持つ要素の最小数（下回ると要素を入れ直す）
+const MIN_ENTRIES: usize = 3;
+
+/// XY平面での面積（Z方向の厚みはほとんどのノードで0なので使わない）
+fn area(bounds: &BoundingBox) -> f32 {
+    (bounds.max.0 - bounds.min.0).max(0.0) * (bounds.max.1 - bounds.min.1).max(0.0)
+}
+
+/// `bounds` を `other` が入るように広げたときの面積の増分
+fn enlargement(bounds: &BoundingBox, other: &BoundingBox) -> f32 {
+    area(&bounds.union(other)) - area(bounds)
+}
+
+/// `outer` が `inner` を完全に含むかどうか
+fn encloses(outer: &BoundingBox, inner: &BoundingBox) -> bool {
+    outer.contains_point(inner.min) && outer.contains_point(inner.max)
+}
+
+fn same_bounds(a: &BoundingBox, b: &BoundingBox) -> bool {
+    a.min == b.min && a.max == b.max
+}
+
+/// 要素を2つのグループに分ける（Guttmanの二次分割）
+fn quadratic_split<T>(mut items: Vec<T>, bounds_of: impl Fn(&T) -> BoundingBox) -> (Vec<T>, Vec<T>) {
+    // 同じグループにすると無駄な面積が最も大きくなる2つを種にする
+    let (mut seed_a, mut seed_b, mut worst) = (0, 1, f32::NEG_INFINITY);
+    for i in 0..items.len() {
+        for j in i + 1..items.len() {
+            let (a, b) = (bounds_of(&items[i]), bounds_of(&items[j]));
+            let waste = area(&a.union(&b)) - area(&a) - area(&b);
+            if waste > worst {
+                (seed_a, seed_b, worst) = (i, j, waste);
+            }
+        }
+    }
+    // seed_a < seed_b なので、先に seed_b を取り除いても seed_a の位置は変わらない
+    let item_b = items.swap_remove(seed_b);
+    let item_a = items.swap_remove(seed_a);
+    let (mut bounds_a, mut bounds_b) = (bounds_of(&item_a), bounds_of(&item_b));
+    let (mut group_a, mut group_b) = (vec![item_a], vec![item_b]);
+
+    while !items.is_empty() {
+        // 片方が最小数に届かなくなる場合は残りをすべてそちらに入れる
+        if group_a.len() + items.len() <= MIN_ENTRIES {
+            group_a.append(&mut items);
+            break;
+        }
+        if group_b.len() + items.len() <= MIN_ENTRIES {
+            group_b.append(&mut items);
+            break;
+        }
+
+        // どちらに入れるかで面積の増分が最も違うものから決める
+        let index = items
+            .iter()
+            .map(|item| {
+                let bounds = bounds_of(item);
+                (enlargement(&bounds_a, &bounds) - enlargement(&bounds_b, &bounds)).abs()
+            })
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map_or(0, |(index, _)| index);
+        let item = items.swap_remove(index);
+        let bounds = bounds_of(&item);
+        let (grow_a, grow_b) = (enlargement(&bounds_a, &bounds), enlargement(&bounds_b, &bounds));
+        let to_a = if grow_a != grow_b {
+            grow_a < grow_b
+        } else if area(&bounds_a) != area(&bounds_b) {
+            area(&bounds_a) < area(&bounds_b)
+        } else {
+            group_a.len() <= group_b.len()
+        };
+        if to_a {
+            bounds_a = bounds_a.union(&bounds);
+            group_a.push(item);
+        } else {
+            bounds_b = bounds_b.union(&bounds);
+            group_b.push(item);
+        }
+    }
+    (group_a, group_b)
+}
+
+/// 木のノードの要素
+#[derive(Debug, Clone)]
+enum Entries {
+    /// シーンノードとそのグローバルな境界
+    Leaf(Vec<(NodeId, BoundingBox)>),
+    /// 子の木ノードのインデックス
+    Internal(Vec<usize>),
+}
+
+impl Entries {
+    fn len(&self) -> usize {
+        match self {
+            Entries::Leaf(entries) => entries.len(),
+            Entries::Internal(children) => children.len(),
+        }
+    }
+}
+
+/// R-treeのノード
+#[derive(Debug, Clone)]
+struct TreeNode {
+    bounds: BoundingBox,
+    parent: Option<usize>,
+    entries: Entries,
+}
+
+impl TreeNode {
+    fn empty_leaf(parent: Option<usize>) -> Self {
+        Self {
+            bounds: BoundingBox::from_size(0.0, 0.0, 0.0),
+            parent,
+            entries: Entries::Leaf(Vec::new()),
+        }
+    }
+}
+
+/// 空間インデックス - シーンノードの境界ボックスを管理する動的なR-tree
+///
+/// 木のノードは配列に置き、シーンノードがどの葉にあるかを覚えておきます。
+/// 更新と削除は葉から根までをたどるだけで済み、境界が葉の中に収まる小さな移動では
+/// 木の形を変えません。点と領域の検索はノード数の対数程度の時間で終わります。
+#[derive(Debug, Clone)]
+pub struct SpatialIndex {
+    nodes: Vec<TreeNode>,
+    /// 再利用できる木ノードのインデックス
+    free: Vec<usize>,
+    root: usize,
+    /// シーンノードが入っている葉
+    leaves: HashMap<NodeId, usize>,
+}
+
+impl Default for SpatialIndex {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SpatialIndex {
+    pub fn new() -> Self {
+        Self {
+            nodes: vec![TreeNode::empty_leaf(None)],
+            free: Vec::new(),
+            root: 0,
+            leaves: HashMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+
+    pub fn contains(&self, id: &NodeId) -> bool {
+        self.leaves.contains_key(id)
+    }
+
+    /// 登録されている境界
+    pub fn bounds(&self, id: &NodeId) -> Option<BoundingBox> {
+        let leaf = *self.leaves.get(id)?;
+        match &self.nodes[leaf].entries {
+            Entries::Leaf(entries) => entries.iter().find(|(entry, _)| entry == id).map(|(_, bounds)| *bounds),
+            Entries::Internal(_) => None,
+        }
+    }
+
+    /// ノードの境界を登録または更新
+    pub fn update(&mut self, id: NodeId, bounds: BoundingBox) {
+        if let Some(&leaf) = self.leaves.get(&id) {
+            // 葉の範囲に収まる移動は、その場で書き換えて境界を詰めるだけにする
+            if encloses(&self.nodes[leaf].bounds, &bounds) {
+                if let Entries::Leaf(entries) = &mut self.nodes[leaf].entries {
+                    if let Some(entry) = entries.iter_mut().find(|(entry, _)| *entry == id) {
+                        entry.1 = bounds;
+                    }
+                }
+                self.refit(leaf);
+                return;
+            }
+            self.remove(&id);
+        }
+        self.insert(id, bounds);
+    }
+
+    /// ノードを削除
+    pub fn remove(&mut self, id: &NodeId) -> bool {
+        let Some(leaf) = self.leaves.remove(id) else { return false };
+        if let Entries::Leaf(entries) = &mut self.nodes[leaf].entries {
+            entries.retain(|(entry, _)| entry != id);
+        }
+        self.condense(leaf);
+        true
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    /// 領域と交差するノード
+    pub fn query_region(&self, region: &BoundingBox) -> Vec<NodeId> {
+        self.search(|bounds| bounds.intersects(region))
+    }
+
+    /// 点を含むノード
+    pub fn query_point(&self, point: (f32, f32, f32)) -> Vec<NodeId> {
+        self.search(|bounds| bounds.contains_point(point))
+    }
+
+    fn search(&self, hits: impl Fn(&BoundingBox) -> bool) -> Vec<NodeId> {
+        let mut result = Vec::new();
+        let mut stack = vec![self.root];
+        while let Some(index) = stack.pop() {
+            match &self.nodes[index].entries {
+                Entries::Leaf(entries) => {
+                    result.extend(entries.iter().filter(|(_, bounds)| hits(bounds)).map(|(id, _)| *id));
+                }
+                Entries::Internal(children) => {
+                    stack.extend(children.iter().copied().filter(|&child| hits(&self.nodes[child].bounds)));
+                }
+            }
+        }
+        result
+    }
+
+    fn alloc(&mut self, node: TreeNode) -> usize {
+        match self.free.pop() {
+            Some(index) => {
+                self.nodes[index] = node;
+                index
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        }
+    }
+
+    fn release(&mut self, index: usize) {
+        self.nodes[index] = TreeNode::empty_leaf(None);
+        self.free.push(index);
+    }
+
+    fn insert(&mut self, id: NodeId, bounds: BoundingBox) {
+        let leaf = self.choose_leaf(&bounds);
+        if let Entries::Leaf(entries) = &mut self.nodes[leaf].entries {
+            entries.push((id, bounds));
+        }
+        self.leaves.insert(id, leaf);
+        if self.nodes[leaf].entries.len() > MAX_ENTRIES {
+            self.split(leaf);
+        } else {
+            self.refit(leaf);
+        }
+    }
+
+    /// 境界を入れたときに面積の増分が最も小さい葉
+    fn choose_leaf(&self, bounds: &BoundingBox) -> usize {
+        let mut index = self.root;
+        while let Entries::Internal(children) = &self.nodes[index].entries {
+            index = children
+                .iter()
+                .copied()
+                .min_by(|&a, &b| {
+                    let (a, b) = (&self.nodes[a].bounds, &self.nodes[b].bounds);
+                    enlargement(a, bounds).total_cmp(&enlargement(b, bounds)).then(area(a).total_cmp(&area(b)))
+                })
+                .expect("内部ノードには子がある");
+        }
+        index
+    }
+
+    /// あふれたノードを2つに分け、必要なら親も分割する
+    fn split(&mut self, index: usize) {
+        let entries = std::mem::replace(&mut self.nodes[index].entries, Entries::Leaf(Vec::new()));
+        let (keep, moved) = match entries {
+            Entries::Leaf(entries) => {
+                let (a, b) = quadratic_split(entries, |(_, bounds)| *bounds);
+                (Entries::Leaf(a), Entries::Leaf(b))
+            }
+            Entries::Internal(children) => {
+                let (a, b) = quadratic_split(children, |&child| self.nodes[child].bounds);
+                (Entries::Internal(a), Entries::Internal(b))
+            }
+        };
+        self.nodes[index].entries = keep;
+        let parent = self.nodes[index].parent;
+        let sibling = self.alloc(TreeNode { entries: moved, ..TreeNode::empty_leaf(parent) });
+        match &self.nodes[sibling].entries {
+            Entries::Leaf(entries) => {
+                for (id, _) in entries {
+                    self.leaves.insert(*id, sibling);
+                }
+            }
+            Entries::Internal(children) => {
+                for child in children.clone() {
+                    self.nodes[child].parent = Some(sibling);
+                }
+            }
+        }
+        self.update_bounds(index);
+        self.update_bounds(sibling);
+
+        match parent {
+            None => {
+                // 根を分割した場合は木が1段高くなる
+                let root = self.alloc(TreeNode {
+                    entries: Entries::Internal(vec![index, sibling]),
+                    ..TreeNode::empty_leaf(None)
+                });
+                self.nodes[index].parent = Some(root);
+                self.nodes[sibling].parent = Some(root);
+                self.update_bounds(root);
+                self.root = root;
+            }
+            Some(parent) => {
+                if let Entries::Internal(children) = &mut self.nodes[parent].entries {
+                    children.push(sibling);
+                }
+                if self.nodes[parent].entries.len() > MAX_ENTRIES {
+                    self.split(parent);
+                } else {
+                    self.refit(parent);
+                }
+            }
+        }
+    }
+
+    /// 要素が減ったノードから根までの境界を詰め、少なくなりすぎたノードは解体して入れ直す
+    fn condense(&mut self, mut index: usize) {
+        let mut orphans = Vec::new();
+        while let Some(parent) = self.nodes[index].parent {
+            if self.nodes[index].entries.len() < MIN_ENTRIES {
+                if let Entries::Internal(children) = &mut self.nodes[parent].entries {
+                    children.retain(|&child| child != index);
+                }
+                self.collect(index, &mut orphans);
+            } else {
+                self.update_bounds(index);
+            }
+            index = parent;
+        }
+        self.update_bounds(index);
+
+        // 子が1つだけの根は取り除いて木を低くする
+        loop {
+            let child = match &self.nodes[self.root].entries {
+                Entries::Internal(children) if children.len() == 1 => children[0],
+                Entries::Internal(children) if children.is_empty() => {
+                    self.nodes[self.root].entries = Entries::Leaf(Vec::new());
+                    break;
+                }
+                _ => break,
+            };
+            self.release(self.root);
+            self.nodes[child].parent = None;
+            self.root = child;
+        }
+
+        for (id, bounds) in orphans {
+            self.insert(id, bounds);
+        }
+    }
+
+    /// 部分木のシーンノードを集め、木ノードを解放する
+    fn collect(&mut self, index: usize, out: &mut Vec<(NodeId, BoundingBox)>) {
+        match std::mem::replace(&mut self.nodes[index].entries, Entries::Leaf(Vec::new())) {
+            Entries::Leaf(entries) => out.extend(entries),
+            Entries::Internal(children) => {
+                for child in children {
+                    self.collect(child, out);
+                }
+            }
+        }
+        self.release(index);
+    }
+
+    /// ノードの境界を要素から計算し直し、変わった場合は真を返す
+    fn update_bounds(&mut self, index: usize) -> bool {
+        let bounds = match &self.nodes[index].entries {
+            Entries::Leaf(entries) => entries.iter().map(|(_, bounds)| *bounds).reduce(|a, b| a.union(&b)),
+            Entries::Internal(children) => {
+                children.iter().map(|&child| self.nodes[child].bounds).reduce(|a, b| a.union(&b))
+            }
+        };
+        let Some(bounds) = bounds else { return false };
+        if same_bounds(&self.nodes[index].bounds, &bounds) {
+            return false;
+        }
+        self.nodes[index].bounds = bounds;
+        true
+    }
+
+    /// ノードから根に向かって、境界が変わらなくなるまで詰める
+    fn refit(&mut self, mut index: usize) {
+        while self.update_bounds(index) {
+            match self.nodes[index].parent {
+                Some(parent) => index = parent,
+                None => break,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 再現できる疑似乱数
+    struct Lcg(u64);
+
+    impl Lcg {
+        fn next(&mut self, max: f32) -> f32 {
+            self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (self.0 >> 40) as f32 / (1u64 << 24) as f32 * max
+        }
+
+        fn rect(&mut self) -> BoundingBox {
+            let (x, y) = (self.next(2000.0), self.next(2000.0));
+            let (width, height) = (self.next(300.0), self.next(300.0));
+            BoundingBox::new((x, y, 0.0), (x + width, y + height, 0.0))
+        }
+    }
+
+    /// 木の構造が正しいか（境界が子を含む、親と葉の対応、要素数）を確認
+    fn check_tree(index: &SpatialIndex) {
+        let mut stack = vec![(index.root, None)];
+        let mut count = 0;
+        while let Some((node, parent)) = stack.pop() {
+            let tree_node = &index.nodes[node];
+            assert_eq!(tree_node.parent, parent);
+            if node != index.root {
+                assert!((MIN_ENTRIES..=MAX_ENTRIES).contains(&tree_node.entries.len()));
+            }
+            match &tree_node.entries {
+                Entries::Leaf(entries) => {
+                    for (id, bounds) in entries {
+                        assert!(encloses(&tree_node.bounds, bounds));
+                        assert_eq!(index.leaves[id], node);
+                        count += 1;
+                    }
+                }
+                Entries::Internal(children) => {
+                    for &child in children {
+                        assert!(encloses(&tree_node.bounds, &index.nodes[child].bounds));
+                        stack.push((child, Some(node)));
+                    }
+                }
+            }
+        }
+        assert_eq!(count, index.len());
+    }
+
+    fn sorted(mut ids: Vec<NodeId>) -> Vec<NodeId> {
+        ids.sort_by_key(|id| id.0);
+        ids
+    }
+
+    fn assert_same_results(index: &SpatialIndex, expected: &HashMap<NodeId, BoundingBox>, rng: &mut Lcg) {
+        check_tree(index);
+        for _ in 0..50 {
+            let point = (rng.next(2300.0), rng.next(2300.0), 0.0);
+            let linear = expected.iter().filter(|(_, b)| b.contains_point(point)).map(|(&id, _)| id).collect();
+            assert_eq!(sorted(index.query_point(point)), sorted(linear));
+
+            let region = rng.rect();
+            let linear = expected.iter().filter(|(_, b)| b.intersects(&region)).map(|(&id, _)| id).collect();
+            assert_eq!(sorted(index.query_region(&region)), sorted(linear));
+        }
+    }
+
+    #[test]
+    fn test_matches_linear_scan() {
+        let mut rng = Lcg(7);
+        let mut index = SpatialIndex::new();
+        let mut expected = HashMap::new();
+
+        for i in 0..500 {
+            let bounds = rng.rect();
+            index.update(NodeId(i), bounds);
+            expected.insert(NodeId(i), bounds);
+        }
+        assert_eq!(index.len(), 500);
+        assert_same_results(&index, &expected, &mut rng);
+
+        // ポインターに追従するような小さな移動と、別の場所への移動
+        for i in 0..500 {
+            let previous = expected[&NodeId(i)];
+            let bounds = if i % 2 == 0 {
+                let (dx, dy) = (rng.next(4.0) - 2.0, rng.next(4.0) - 2.0);
+                BoundingBox::new(
+                    (previous.min.0 + dx, previous.min.1 + dy, 0.0),
+                    (previous.max.0 + dx, previous.max.1 + dy, 0.0),
+                )
+            } else {
+                rng.rect()
+            };
+            index.update(NodeId(i), bounds);
+            expected.insert(NodeId(i), bounds);
+        }
+        assert_same_results(&index, &expected, &mut rng);
+        assert_eq!(index.bounds(&NodeId(3)).map(|b| b.min), Some(expected[&NodeId(3)].min));
+
+        for i in (0..500).filter(|i| i % 3 != 0) {
+            assert!(index.remove(&NodeId(i)));
+            expected.remove(&NodeId(i));
+        }
+        assert!(!index.remove(&NodeId(1)));
+        assert_eq!(index.len(), expected.len());
+        assert_same_results(&index, &expected, &mut rng);
+    }
+
+    #[test]
+    fn test_removing_everything_collapses_tree() {
+        let mut rng = Lcg(42);
+        let mut index = SpatialIndex::new();
+        for i in 0..200 {
+            index.update(NodeId(i), rng.rect());
+        }
+        assert!(matches!(index.nodes[index.root].entries, Entries::Internal(_)));
+
+        for i in 0..200 {
+            index.remove(&NodeId(i));
+            check_tree(&index);
+        }
+        assert!(index.is_empty());
+        assert!(matches!(&index.nodes[index.root].entries, Entries::Leaf(entries) if entries.is_empty()));
+        // 解放した木ノードは再利用される
+        assert_eq!(index.nodes.len() - index.free.len(), 1);
+        assert!(index.query_point((10.0, 10.0, 0.0)).is_empty());
+    }
+}
@@ -209,7 +209,7 @@ impl WindowManager {
                 CompositorEvent::WindowCreated(window_id) => {
                     // シーングラフにノードを作成
                     if let Ok(mut sg) = scene_graph.write() {
-                        let root_id = sg.root().borrow().id;
+                        let root_id = sg.root();
                         if let Ok(node_id) = sg.create_node(
                             root_id,
                             NodeType::Window,
@@ -424,6 +424,8 @@ impl WindowManager {
         
         // 更新間隔の計算
         let update_interval = Duration::from_secs_f64(1.0 / self.config.update_rate as f64);
+        // フレームスケジューラに渡すモノトニック時計
+        let clock = Instant::now();
         
         while self.running {
             let loop_start = Instant::now();
@@ -438,15 +440,19 @@ impl WindowManager {
                 layout_mgr.update_layout(layout_mgr.current_workspace_id());
             }
             
-            // コンポジターの更新
-            if let Ok(mut compositor) = self.compositor.lock() {
-                compositor.render_frame();
-            }
+            // コンポジターの更新（フレームスケジューラに従って合成し、表示のフィードバックを発火）
+            let next_frame = match self.compositor.lock() {
+                Ok(mut compositor) => compositor.dispatch_frames(clock.elapsed()),
+                Err(_) => None,
+            };
             
-            // 次の更新まで待機
-            let elapsed = loop_start.elapsed();
-            if elapsed < update_interval {
-                thread::sleep(update_interval - elapsed);
+            // 次の更新か、次にフレームを処理する時刻まで待機
+            let mut wait = update_interval.saturating_sub(loop_start.elapsed());
+            if let Some(next_frame) = next_frame {
+                wait = wait.min(next_frame.saturating_sub(clock.elapsed()));
+            }
+            if !wait.is_zero() {
+                thread::sleep(wait);
             }
             
             self.last_update = Instant::now();
@@ -0,0 +1,75 @@
+// LumosDesktop 入力トレース回帰テスト
+// tests/traces の入力トレースを再生してジェスチャー認識結果を検証する
+
+#[cfg(test)]
+mod input_replay_tests {
+    use std::path::PathBuf;
+
+    use lumos_desktop::core::window_manager::gesture_recognizer::{
+        GestureManager, GestureType, GestureState, GestureInfo
+    };
+    use lumos_desktop::core::window_manager::input_translator::{
+        InputManager, InputReplayer, InputTrace, ReplaySpeed
+    };
+
+    fn trace_path(name: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("traces")
+            .join(name)
+    }
+
+    // 合成したピンチアウトのトレースがピンチとして認識されることをテスト
+    #[test]
+    fn test_pinch_out_trace() -> Result<(), Box<dyn std::error::Error>> {
+        let trace = InputTrace::load(trace_path("pinch_out.jsonl"))?;
+        assert_eq!(trace.len(), 16);
+
+        let mut gesture_manager = GestureManager::new();
+        gesture_manager.register_default_recognizers();
+
+        let mut recognized: Vec<GestureInfo> = Vec::new();
+        let mut replayer = InputReplayer::new(trace).with_speed(ReplaySpeed::Immediate);
+        replayer.run_with(|_, event| {
+            recognized.extend(
+                gesture_manager
+                    .process_event(event)
+                    .into_iter()
+                    .filter(|g| g.gesture_type == GestureType::Pinch),
+            );
+            Ok(())
+        })?;
+
+        assert_eq!(recognized.first().map(|g| g.state), Some(GestureState::Began));
+        let ended = recognized
+            .iter()
+            .find(|g| g.state == GestureState::Ended)
+            .expect("ピンチが終了していません");
+        assert!(ended.scale > 1.0);
+
+        Ok(())
+    }
+
+    // トレースを InputManager に再生してもタッチ状態が残らないことをテスト
+    #[test]
+    fn test_trace_replay_into_input_manager() -> Result<(), Box<dyn std::error::Error>> {
+        let trace = InputTrace::load(trace_path("pinch_out.jsonl"))?;
+        let mut manager = InputManager::new();
+        let mut replayer = InputReplayer::new(trace);
+
+        // 2本目の指が触れた直後は2点がアクティブ
+        replayer.add_checkpoint(1, |ctx| {
+            let touches = ctx.manager.get_active_touches().len();
+            if touches == 2 {
+                Ok(())
+            } else {
+                Err(format!("アクティブなタッチ数が不正: {}", touches))
+            }
+        });
+
+        replayer.run(&mut manager)?;
+        assert!(manager.get_active_touches().is_empty());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,147 @@
+// LumosDesktop ソフトウェアレンダラー参照画像テスト
+// GPUのないCI環境でソフトウェアバックエンドの描画結果を参照画像と比較する
+
+#[cfg(test)]
+mod software_renderer_tests {
+    use std::path::PathBuf;
+
+    use futures::executor::block_on;
+
+    use lumos_desktop::core::graphics::{GraphicsConfig, PixelData, Renderer, SoftwareRenderer};
+    use lumos_desktop::core::graphics::renderer::{
+        BlendFactor, BlendState, BufferTarget, BufferUsage, PipelineState, TextureFormat,
+        VertexAttribute, VertexFormat, VertexLayout,
+    };
+    use lumos_desktop::core::graphics::software_backend::SHADER_VERTEX_COLOR;
+
+    const WIDTH: u32 = 64;
+    const HEIGHT: u32 = 48;
+
+    /// 参照画像を更新する場合に設定する環境変数
+    const UPDATE_ENV: &str = "LUMOS_UPDATE_REFERENCE_IMAGES";
+
+    /// 1チャンネルあたりの許容誤差
+    const TOLERANCE: u8 = 2;
+
+    fn reference_path(name: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("reference_images")
+            .join(name)
+    }
+
+    fn vertex_layout() -> VertexLayout {
+        VertexLayout {
+            attributes: vec![
+                VertexAttribute { name: "position".to_string(), format: VertexFormat::Float3, offset: 0, location: 0, divisor: 0 },
+                VertexAttribute { name: "color".to_string(), format: VertexFormat::Float4, offset: 12, location: 1, divisor: 0 },
+            ],
+            stride: 28,
+        }
+    }
+
+    fn vertex_bytes(vertices: &[[f32; 7]]) -> Vec<u8> {
+        vertices.iter().flatten().flat_map(|f| f.to_le_bytes()).collect()
+    }
+
+    /// グラデーションの三角形の上に半透明の四角形を重ねたシーンを描画
+    fn render_scene() -> Result<PixelData, Box<dyn std::error::Error>> {
+        let config = GraphicsConfig::default();
+        let mut renderer = SoftwareRenderer::new(&config)?;
+        renderer.initialize(&config)?;
+
+        let triangle = vertex_bytes(&[
+            [0.0, 0.9, 0.5, 1.0, 0.0, 0.0, 1.0],
+            [-0.9, -0.9, 0.5, 0.0, 1.0, 0.0, 1.0],
+            [0.9, -0.9, 0.5, 0.0, 0.0, 1.0, 1.0],
+        ]);
+        let overlay = vertex_bytes(&[
+            [-0.5, -0.5, 0.2, 1.0, 1.0, 1.0, 0.5],
+            [0.5, -0.5, 0.2, 1.0, 1.0, 1.0, 0.5],
+            [0.5, 0.1, 0.2, 1.0, 1.0, 1.0, 0.5],
+            [-0.5, -0.5, 0.2, 1.0, 1.0, 1.0, 0.5],
+            [0.5, 0.1, 0.2, 1.0, 1.0, 1.0, 0.5],
+            [-0.5, 0.1, 0.2, 1.0, 1.0, 1.0, 0.5],
+        ]);
+        renderer.create_buffer("triangle", BufferTarget::Vertex, BufferUsage::Static, Some(&triangle), triangle.len())?;
+        renderer.create_buffer("overlay", BufferTarget::Vertex, BufferUsage::Static, Some(&overlay), overlay.len())?;
+
+        let target = renderer.create_render_target("scene", WIDTH, HEIGHT, TextureFormat::R8G8B8A8)?;
+        let mut context = renderer.create_context()?;
+        let mut pass = context.create_render_pass(target)?;
+        let mut commands = context.create_command_buffer()?;
+
+        pass.begin()?;
+        commands.begin()?;
+        commands.clear([0.1, 0.1, 0.15, 1.0], 1.0, 0)?;
+
+        let opaque = PipelineState { vertex_layout: vertex_layout(), ..PipelineState::default() };
+        context.set_shader(SHADER_VERTEX_COLOR)?;
+        context.set_pipeline_state(&opaque)?;
+        context.set_vertex_buffer("triangle", 0)?;
+        commands.draw(3, 0)?;
+
+        let translucent = PipelineState {
+            blend_state: BlendState {
+                enabled: true,
+                src_rgb: BlendFactor::SrcAlpha,
+                dst_rgb: BlendFactor::OneMinusSrcAlpha,
+                src_alpha: BlendFactor::One,
+                dst_alpha: BlendFactor::OneMinusSrcAlpha,
+                ..BlendState::default()
+            },
+            ..opaque.clone()
+        };
+        context.set_pipeline_state(&translucent)?;
+        context.set_vertex_buffer("overlay", 0)?;
+        commands.draw(6, 0)?;
+
+        commands.end()?;
+        pass.end()?;
+        commands.submit()?;
+
+        Ok(block_on(renderer.read_render_target("scene"))?)
+    }
+
+    // 描画結果が参照画像と一致することをテスト
+    #[test]
+    fn test_scene_matches_reference() -> Result<(), Box<dyn std::error::Error>> {
+        let pixels = render_scene()?;
+        let path = reference_path("software_scene.png");
+
+        if std::env::var_os(UPDATE_ENV).is_some() {
+            pixels.save_png(&path)?;
+            return Ok(());
+        }
+
+        let reference = PixelData::load_png(&path)?;
+        assert_eq!((reference.width, reference.height), (WIDTH, HEIGHT));
+
+        let mismatches = reference.count_mismatches(&pixels, TOLERANCE);
+        assert_eq!(
+            mismatches, 0,
+            "参照画像と異なる画素があります（{} を設定すると参照画像を更新します）", UPDATE_ENV
+        );
+
+        Ok(())
+    }
+
+    // ソフトウェアAPIを指定するとGPUなしでも初期化できることをテスト
+    #[test]
+    fn test_graphics_manager_software_fallback() -> Result<(), Box<dyn std::error::Error>> {
+        use lumos_desktop::core::graphics::{GraphicsApi, GraphicsManager};
+
+        let config = GraphicsConfig {
+            preferred_api: GraphicsApi::Software,
+            ..GraphicsConfig::default()
+        };
+        let mut manager = GraphicsManager::with_config(config);
+        manager.initialize()?;
+
+        assert_eq!(manager.get_current_api(), GraphicsApi::Software);
+        let renderer = manager.get_renderer().expect("レンダラーが作成されていません");
+        assert_eq!(renderer.lock().unwrap().name(), "SoftwareRenderer");
+
+        Ok(())
+    }
+}
@@ -419,6 +419,16 @@ fn apply_color_shift(color_str: &str, shift: &DynamicColorShift) -> String {
     color_str.to_string()
 }
 
+/// 時間帯の設定の色温度
+fn time_color_temperature(settings: &DynamicThemeSettings, env: &EnvironmentState) -> ColorTemperature {
+    let neutral = DynamicColorShift::default().color_temperature;
+    if !settings.enabled {
+        return neutral;
+    }
+    settings.time_based.get(&env.time_of_day)
+        .map_or(neutral, |time_settings| time_settings.color_shift.color_temperature)
+}
+
 /// 動的テーママネージャー
 pub struct DynamicThemeManager {
     /// 動的テーマ設定
@@ -429,6 +439,8 @@ pub struct DynamicThemeManager {
     weather_provider: Option<Box<dyn Fn() -> Option<WeatherCondition> + Send + Sync>>,
     /// 動的テーマの変更リスナー
     listeners: RwLock<Vec<Box<dyn Fn(&Theme) + Send + Sync>>>,
+    /// 色温度の変更リスナー（コンポジターの夜間モードなど）
+    temperature_listeners: RwLock<Vec<Box<dyn Fn(ColorTemperature) + Send + Sync>>>,
     /// 更新スレッドハンドル
     update_thread: Option<thread::JoinHandle<()>>,
     /// テーマエンジンの参照
@@ -445,6 +457,7 @@ impl DynamicThemeManager {
             environment: RwLock::new(EnvironmentState::default()),
             weather_provider: None,
             listeners: RwLock::new(Vec::new()),
+            temperature_listeners: RwLock::new(Vec::new()),
             update_thread: None,
             theme_engine,
             running: Arc::new(RwLock::new(false)),
@@ -511,6 +524,13 @@ impl DynamicThemeManager {
         self.environment.read().unwrap().clone()
     }
     
+    /// 現在の時間帯の色温度（動的テーマが無効な場合は標準の昼光色）
+    pub fn color_temperature(&self) -> ColorTemperature {
+        let settings = self.settings.read().unwrap();
+        let env = self.environment.read().unwrap();
+        time_color_temperature(&settings, &env)
+    }
+    
     /// 動的テーマを適用
     pub fn apply_dynamic_theme(&self) {
         let settings = self.settings.read().unwrap();
@@ -561,6 +581,8 @@ impl DynamicThemeManager {
             }
         }
         
+        let temperature = time_color_temperature(&settings, &env);
+        
         // テーマを設定
         let mut current = self.theme_engine.current_theme.write().unwrap();
         *current = dynamic_theme.clone();
@@ -570,6 +592,10 @@ impl DynamicThemeManager {
         for listener in listeners.iter() {
             listener(&dynamic_theme);
         }
+        let temperature_listeners = self.temperature_listeners.read().unwrap();
+        for listener in temperature_listeners.iter() {
+            listener(temperature);
+        }
     }
     
     /// テーマにカラーシフトを適用
@@ -613,6 +639,15 @@ impl DynamicThemeManager {
         listeners.push(Box::new(listener));
     }
     
+    /// 色温度のリスナーを追加（テーマを適用するたびに現在の時間帯の色温度で呼ばれる）
+    pub fn add_color_temperature_listener<F>(&self, listener: F)
+    where
+        F: Fn(ColorTemperature) + Send + Sync + 'static,
+    {
+        let mut listeners = self.temperature_listeners.write().unwrap();
+        listeners.push(Box::new(listener));
+    }
+    
     /// 更新スレッドを開始
     pub fn start(&mut self) {
         {
@@ -675,6 +710,7 @@ impl Clone for DynamicThemeManager {
             environment: RwLock::new(self.environment.read().unwrap().clone()),
             weather_provider: None, // クローン不可能なため
             listeners: RwLock::new(Vec::new()), // リスナーはクローンしない
+            temperature_listeners: RwLock::new(Vec::new()),
             update_thread: None, // スレッドはクローンしない
             theme_engine: self.theme_engine.clone(),
             running: self.running.clone(),
@@ -721,6 +757,20 @@ mod tests {
         }
     }
     
+    #[test]
+    fn test_time_color_temperature() {
+        let mut settings = DynamicThemeSettings::default();
+        let env = EnvironmentState {
+            time_of_day: TimeOfDay::LateNight,
+            ..EnvironmentState::default()
+        };
+        assert_eq!(time_color_temperature(&settings, &env), 2700);
+        
+        // 無効な場合は夜間モードを使わない
+        settings.enabled = false;
+        assert_eq!(time_color_temperature(&settings, &env), 6500);
+    }
+    
     #[test]
     fn test_color_shift() {
         let original_color = "#ff0000"; // 純粋な赤